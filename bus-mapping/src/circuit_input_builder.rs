@@ -3,6 +3,7 @@
 
 mod access;
 mod block;
+mod bundle;
 mod call;
 mod execution;
 mod input_state_ref;
@@ -13,21 +14,30 @@ mod transaction;
 use self::access::gen_state_access_trace;
 use crate::error::Error;
 use crate::evm::opcodes::{gen_associated_ops, gen_begin_tx_ops, gen_end_tx_ops};
-use crate::operation::{CallContextField, RW};
+use crate::operation::{CallContextField, CallContextOp, Operation, RW};
 use crate::rpc::GethClient;
 use crate::state_db::{self, CodeDB, StateDB};
 pub use access::{Access, AccessSet, AccessValue, CodeSource};
 pub use block::{Block, BlockContext};
+pub use bundle::BlockBundle;
 pub use call::{Call, CallContext, CallKind};
 use core::fmt::Debug;
 use eth_types::sign_types::{pk_bytes_le, pk_bytes_swap_endianness, SignData};
-use eth_types::{self, geth_types, Address, GethExecStep, GethExecTrace, Word};
+use eth_types::trace_stream::stream_geth_exec_traces;
+use eth_types::{
+    self, evm_types::MemoryAddress, geth_types, Address, GethExecStep, GethExecTrace, ToWord, Word,
+    H256,
+};
 use ethers_providers::JsonRpcClient;
 pub use execution::{CopyDataType, CopyEvent, CopyStep, ExecState, ExecStep, NumberOrHash};
 pub use input_state_ref::CircuitInputStateRef;
 use itertools::Itertools;
+use std::cmp::min;
 use std::collections::HashMap;
-pub use transaction::{Transaction, TransactionContext};
+use std::io::{BufReader, Read};
+use std::iter;
+use std::ops::RangeInclusive;
+pub use transaction::{Transaction, TransactionContext, TxTraceSummary};
 
 /// Builder to generate a complete circuit input from data gathered from a geth
 /// instance. This structure is the centre of the crate and is intended to be
@@ -59,6 +69,30 @@ pub struct CircuitInputBuilder {
     pub block_ctx: BlockContext,
 }
 
+/// How the RW counter and the transaction ids evolve across the consecutive
+/// blocks handled by a [`CircuitInputBuilder`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CounterMode {
+    /// Restart the RW counter and the transaction ids in each block.
+    PerBlock,
+    /// Continue the RW counter and the transaction ids of the previous block.
+    Continuous,
+}
+
+/// A transaction whose geth trace is passed to the [`CircuitInputBuilder`]
+/// one step at a time, started by [`CircuitInputBuilder::begin_streamed_tx`].
+#[derive(Debug)]
+pub struct StreamedTx {
+    tx: Transaction,
+    tx_ctx: TransactionContext,
+    /// Index of the transaction in the block
+    tx_index: usize,
+    /// Index of the first step of `geth_steps` in the geth trace
+    step_index: usize,
+    /// Steps of the geth trace which haven't been handled yet
+    geth_steps: Vec<GethExecStep>,
+}
+
 impl<'a> CircuitInputBuilder {
     /// Create a new CircuitInputBuilder from the given `eth_block` and
     /// `constants`.
@@ -71,6 +105,49 @@ impl<'a> CircuitInputBuilder {
         }
     }
 
+    /// Start a new block following the current one, on top of the state
+    /// after the current block, and return the circuit input of the current
+    /// block.  With [`CounterMode::Continuous`] the blocks can be proven
+    /// together as a batch: the last transaction of the current block is
+    /// followed by the first transaction of the next one.  The cumulative gas
+    /// used of the receipts restarts from zero in every block.
+    pub fn begin_next_block(&mut self, mut block: Block, mode: CounterMode) -> Block {
+        let mut block_ctx = BlockContext::new();
+        if mode == CounterMode::Continuous {
+            self.read_next_tx_id();
+            block_ctx.rwc = self.block_ctx.rwc;
+            block.tx_id_offset = self.block.tx_id_offset + self.block.txs.len();
+        }
+        self.block_ctx = block_ctx;
+        std::mem::replace(&mut self.block, block)
+    }
+
+    /// Add to the EndTx step of the last transaction of the block the read of
+    /// the TxId of the next transaction, which is only done by the EndTx of
+    /// the transactions which are not the last one of their block.
+    fn read_next_tx_id(&mut self) {
+        let next_tx_id = self.block.tx_id_offset + self.block.txs.len() + 1;
+        let end_tx = match self
+            .block
+            .txs
+            .last_mut()
+            .and_then(|tx| tx.steps_mut().last_mut())
+        {
+            Some(step) if step.exec_state == ExecState::EndTx => step,
+            _ => return,
+        };
+        let op_ref = self.block.container.insert(Operation::new(
+            self.block_ctx.rwc.inc_pre(),
+            RW::READ,
+            CallContextOp {
+                call_id: self.block_ctx.rwc.0,
+                field: CallContextField::TxId,
+                value: next_tx_id.into(),
+            },
+        ));
+        end_tx.bus_mapping_instance.push(op_ref);
+    }
+
     /// Obtain a mutable reference to the state that the `CircuitInputBuilder`
     /// maintains, contextualized to a particular transaction and a
     /// particular execution step in that transaction.
@@ -108,7 +185,15 @@ impl<'a> CircuitInputBuilder {
             ),
         );
 
-        Transaction::new(call_id, &self.sdb, &mut self.code_db, eth_tx, is_success)
+        Transaction::new(
+            call_id,
+            &self.sdb,
+            &mut self.code_db,
+            eth_tx,
+            self.block.number.as_u64(),
+            self.block.base_fee,
+            is_success,
+        )
     }
 
     /// Iterate over all generated CallContext RwCounterEndOfReversion
@@ -148,43 +233,248 @@ impl<'a> CircuitInputBuilder {
         Ok(())
     }
 
+    /// Handle a block whose execution traces, as returned by
+    /// `debug_traceBlockByNumber`, are read from the readers returned by
+    /// `open_traces`.  The traces are read twice: first to collect the
+    /// [`TxTraceSummary`] of each transaction, and then to handle their steps
+    /// as they are parsed, so that the steps are never all in memory at the
+    /// same time.
+    pub fn handle_block_from_reader<R: Read>(
+        &mut self,
+        eth_block: &EthBlock,
+        mut open_traces: impl FnMut() -> std::io::Result<R>,
+    ) -> Result<(), Error> {
+        let mut summaries = Vec::new();
+        let headers = stream_geth_exec_traces(BufReader::new(open_traces()?), |tx_index, step| {
+            if summaries.len() <= tx_index {
+                summaries.resize_with(tx_index + 1, TxTraceSummary::default);
+            }
+            summaries[tx_index]
+                .push_step(&step)
+                .map_err(|error| Error::TxError {
+                    tx_index,
+                    error: Box::new(error),
+                })
+        })?;
+        if headers.len() != eth_block.transactions.len() {
+            return Err(Error::InvalidGethExecTrace(
+                "Number of traces doesn't match the number of transactions",
+            ));
+        }
+        summaries.resize_with(headers.len(), TxTraceSummary::default);
+
+        let mut summaries = summaries.into_iter();
+        let mut streamed_tx = None;
+        let mut next_tx_index = 0;
+        let mut begin_next_tx = |builder: &mut Self, streamed_tx: &mut Option<StreamedTx>| {
+            if let Some(tx) = streamed_tx.take() {
+                builder.end_streamed_tx(tx)?;
+            }
+            let tx_index = next_tx_index;
+            next_tx_index += 1;
+            let eth_tx =
+                eth_block
+                    .transactions
+                    .get(tx_index)
+                    .ok_or(Error::InvalidGethExecTrace(
+                        "More traces than transactions in the block",
+                    ))?;
+            *streamed_tx = Some(builder.begin_streamed_tx(
+                eth_tx,
+                headers[tx_index].failed,
+                summaries.next().unwrap_or_default(),
+                tx_index + 1 == eth_block.transactions.len(),
+            )?);
+            Ok::<_, Error>(tx_index)
+        };
+        let mut current_tx_index = None;
+        stream_geth_exec_traces(BufReader::new(open_traces()?), |tx_index, step| {
+            // Transactions without steps are handled on the way.
+            while current_tx_index.map_or(true, |current| current < tx_index) {
+                current_tx_index = Some(begin_next_tx(self, &mut streamed_tx)?);
+            }
+            let tx = streamed_tx
+                .as_mut()
+                .ok_or(Error::InternalError("Step streamed without a transaction"))?;
+            self.handle_streamed_step(tx, step)
+        })?;
+        while current_tx_index.map_or(0, |current| current + 1) < headers.len() {
+            current_tx_index = Some(begin_next_tx(self, &mut streamed_tx)?);
+        }
+        if let Some(tx) = streamed_tx.take() {
+            self.end_streamed_tx(tx)?;
+        }
+        self.set_value_ops_call_context_rwc_eor();
+        Ok(())
+    }
+
     /// Handle a transaction with its corresponding execution trace to generate
     /// all the associated operations.  Each operation is registered in
     /// `self.block.container`, and each step stores the
     /// [`OperationRef`](crate::exec_trace::OperationRef) to each of the
-    /// generated operations.
+    /// generated operations.  Errors are wrapped in [`Error::TxError`] or
+    /// [`Error::ExecStepError`] to report where they occurred.
     fn handle_tx(
         &mut self,
         eth_tx: &eth_types::Transaction,
         geth_trace: &GethExecTrace,
         is_last_tx: bool,
     ) -> Result<(), Error> {
-        let mut tx = self.new_tx(eth_tx, !geth_trace.failed)?;
-        let mut tx_ctx = TransactionContext::new(eth_tx, geth_trace, is_last_tx)?;
+        let tx_index = self.block.txs.len();
+        let tx_ctx = TransactionContext::new(eth_tx, geth_trace, is_last_tx).map_err(|error| {
+            Error::TxError {
+                tx_index,
+                error: Box::new(error),
+            }
+        })?;
+        let (mut tx, mut tx_ctx) = self.begin_tx(eth_tx, geth_trace.failed, tx_ctx)?;
+
+        let struct_logs = &geth_trace.struct_logs;
+        for index in 0..struct_logs.len() {
+            // The opcode handlers look ahead at most one step.
+            let geth_steps = &struct_logs[index..min(index + 2, struct_logs.len())];
+            self.handle_step(&mut tx, &mut tx_ctx, tx_index, index, geth_steps)?;
+        }
+
+        self.end_tx(tx, tx_ctx, tx_index)
+    }
+
+    /// Begin a transaction whose steps are then passed one at a time to
+    /// [`Self::handle_streamed_step`], and which is finished by
+    /// [`Self::end_streamed_tx`].  `failed` is the `failed` field of the
+    /// geth trace, and `summary` is the [`TxTraceSummary`] of its steps.
+    pub fn begin_streamed_tx(
+        &mut self,
+        eth_tx: &eth_types::Transaction,
+        failed: bool,
+        summary: TxTraceSummary,
+        is_last_tx: bool,
+    ) -> Result<StreamedTx, Error> {
+        let tx_index = self.block.txs.len();
+        let tx_ctx = TransactionContext::from_summary(eth_tx, failed, summary, is_last_tx)
+            .map_err(|error| Error::TxError {
+                tx_index,
+                error: Box::new(error),
+            })?;
+        let (tx, tx_ctx) = self.begin_tx(eth_tx, failed, tx_ctx)?;
+        Ok(StreamedTx {
+            tx,
+            tx_ctx,
+            tx_index,
+            step_index: 0,
+            geth_steps: Vec::with_capacity(2),
+        })
+    }
+
+    /// Pass the next step of the geth trace of a streamed transaction.  Each
+    /// step is handled once the step after it is known.
+    pub fn handle_streamed_step(
+        &mut self,
+        streamed_tx: &mut StreamedTx,
+        geth_step: GethExecStep,
+    ) -> Result<(), Error> {
+        streamed_tx.geth_steps.push(geth_step);
+        if streamed_tx.geth_steps.len() == 2 {
+            self.handle_step(
+                &mut streamed_tx.tx,
+                &mut streamed_tx.tx_ctx,
+                streamed_tx.tx_index,
+                streamed_tx.step_index,
+                &streamed_tx.geth_steps,
+            )?;
+            streamed_tx.step_index += 1;
+            streamed_tx.geth_steps.remove(0);
+        }
+        Ok(())
+    }
+
+    /// Handle the last step of a streamed transaction and finish it.
+    pub fn end_streamed_tx(&mut self, mut streamed_tx: StreamedTx) -> Result<(), Error> {
+        if !streamed_tx.geth_steps.is_empty() {
+            self.handle_step(
+                &mut streamed_tx.tx,
+                &mut streamed_tx.tx_ctx,
+                streamed_tx.tx_index,
+                streamed_tx.step_index,
+                &streamed_tx.geth_steps,
+            )?;
+        }
+        self.end_tx(streamed_tx.tx, streamed_tx.tx_ctx, streamed_tx.tx_index)
+    }
+
+    /// Create the [`Transaction`] of a transaction and generate its BeginTx
+    /// step.
+    fn begin_tx(
+        &mut self,
+        eth_tx: &eth_types::Transaction,
+        failed: bool,
+        mut tx_ctx: TransactionContext,
+    ) -> Result<(Transaction, TransactionContext), Error> {
+        let tx_index = self.block.txs.len();
+        let tx_error = |error| Error::TxError {
+            tx_index,
+            error: Box::new(error),
+        };
+        let mut tx = self.new_tx(eth_tx, !failed).map_err(tx_error)?;
+        tx_ctx.id += self.block.tx_id_offset;
 
         // TODO: Move into gen_associated_steps with
         // - execution_state: BeginTx
         // - op: None
         // Generate BeginTx step
-        let begin_tx_step = gen_begin_tx_ops(&mut self.state_ref(&mut tx, &mut tx_ctx))?;
+        let begin_tx_step =
+            gen_begin_tx_ops(&mut self.state_ref(&mut tx, &mut tx_ctx)).map_err(tx_error)?;
         tx.steps_mut().push(begin_tx_step);
 
-        for (index, geth_step) in geth_trace.struct_logs.iter().enumerate() {
-            let mut state_ref = self.state_ref(&mut tx, &mut tx_ctx);
-            log::trace!("handle {}th opcode {:?} ", index, geth_step.op);
-            let exec_steps = gen_associated_ops(
-                &geth_step.op,
-                &mut state_ref,
-                &geth_trace.struct_logs[index..],
-            )?;
-            tx.steps_mut().extend(exec_steps);
-        }
+        Ok((tx, tx_ctx))
+    }
 
+    /// Generate the steps of `geth_steps[0]`, where `geth_steps` holds the
+    /// following steps of the geth trace that the opcode handlers may look
+    /// at.
+    fn handle_step(
+        &mut self,
+        tx: &mut Transaction,
+        tx_ctx: &mut TransactionContext,
+        tx_index: usize,
+        step_index: usize,
+        geth_steps: &[GethExecStep],
+    ) -> Result<(), Error> {
+        let geth_step = &geth_steps[0];
+        let mut state_ref = self.state_ref(tx, tx_ctx);
+        log::trace!("handle {}th opcode {:?} ", step_index, geth_step.op);
+        let exec_steps =
+            gen_associated_ops(&geth_step.op, &mut state_ref, geth_steps).map_err(|error| {
+                Error::ExecStepError {
+                    tx_index,
+                    step_index,
+                    pc: geth_step.pc,
+                    op: geth_step.op,
+                    error: Box::new(error),
+                }
+            })?;
+        tx.steps_mut().extend(exec_steps);
+        Ok(())
+    }
+
+    /// Generate the EndTx step of a transaction and add it to the block.
+    fn end_tx(
+        &mut self,
+        mut tx: Transaction,
+        mut tx_ctx: TransactionContext,
+        tx_index: usize,
+    ) -> Result<(), Error> {
         // TODO: Move into gen_associated_steps with
         // - execution_state: EndTx
         // - op: None
         // Generate EndTx step
-        let end_tx_step = gen_end_tx_ops(&mut self.state_ref(&mut tx, &mut tx_ctx))?;
+        let end_tx_step =
+            gen_end_tx_ops(&mut self.state_ref(&mut tx, &mut tx_ctx)).map_err(|error| {
+                Error::TxError {
+                    tx_index,
+                    error: Box::new(error),
+                }
+            })?;
         tx.steps_mut().push(end_tx_step);
 
         self.sdb.commit_tx();
@@ -241,8 +531,8 @@ pub fn keccak_inputs_tx_circuit(
     // Keccak inputs from SignVerify Chip
     let sign_verify_inputs = keccak_inputs_sign_verify(&sign_datas);
     inputs.extend_from_slice(&sign_verify_inputs);
-    // NOTE: We don't verify the Tx Hash in the circuit yet, so we don't have more
-    // hash inputs.
+    // Keccak inputs from the RLP encoding of the signing payloads
+    inputs.extend(txs.iter().map(|tx| tx.sign_payload(chain_id)));
     Ok(inputs)
 }
 
@@ -251,10 +541,45 @@ pub fn get_create_init_code<'a, 'b>(
     call_ctx: &'a CallContext,
     step: &'b GethExecStep,
 ) -> Result<&'a [u8], Error> {
-    let offset = step.stack.nth_last(1)?;
-    let length = step.stack.nth_last(2)?;
-    Ok(&call_ctx.memory.0
-        [offset.low_u64() as usize..(offset.low_u64() + length.low_u64()) as usize])
+    let (offset, length) = get_memory_offset_length(step, 1)?;
+    call_ctx
+        .memory
+        .0
+        .get(offset..offset + length)
+        .ok_or(Error::InvalidGethExecStep(
+            "init code out of memory bounds",
+            step.clone(),
+        ))
+}
+
+/// Retrieve the memory offset and length of an operation from the stack words
+/// at `nth` and `nth + 1`.
+pub fn get_memory_offset_length(step: &GethExecStep, nth: usize) -> Result<(usize, usize), Error> {
+    let length = MemoryAddress::try_from(step.stack.nth_last(nth + 1)?)?.0;
+    let offset = range_offset(step.stack.nth_last(nth)?, length)?;
+    Ok((offset, length))
+}
+
+/// Retrieve the memory offset, data offset and length of CALLDATACOPY,
+/// CODECOPY, EXTCODECOPY or RETURNDATACOPY from the stack words at `nth`,
+/// `nth + 1` and `nth + 2`.  The EVM copies zeros from a data offset past the
+/// end of the data, but only data ranges that fit in a `usize` are supported.
+pub fn get_copy_offsets_length(step: &GethExecStep, nth: usize) -> Result<(u64, u64, u64), Error> {
+    let length = MemoryAddress::try_from(step.stack.nth_last(nth + 2)?)?.0;
+    let memory_offset = range_offset(step.stack.nth_last(nth)?, length)?;
+    let data_offset = range_offset(step.stack.nth_last(nth + 1)?, length)?;
+    Ok((memory_offset as u64, data_offset as u64, length as u64))
+}
+
+/// Convert the offset of a range of `length` bytes to a `usize`.  The offset
+/// is ignored by the EVM when the length is zero, so then it is read as zero
+/// if it doesn't fit.  Otherwise the end of the range must fit in a `usize`.
+fn range_offset(offset: Word, length: usize) -> Result<usize, Error> {
+    match MemoryAddress::try_from(offset) {
+        Ok(offset) if offset.0.checked_add(length).is_some() => Ok(offset.0),
+        _ if length == 0 => Ok(0),
+        _ => Err(eth_types::Error::WordToMemAddr.into()),
+    }
 }
 
 /// Retrieve the memory offset and length of call.
@@ -270,6 +595,119 @@ pub fn get_call_memory_offset_length(step: &GethExecStep, nth: usize) -> Result<
 
 type EthBlock = eth_types::Block<eth_types::Transaction>;
 
+/// Circuit inputs of a range of consecutive blocks.
+#[derive(Debug)]
+pub struct BlockRangeInputs {
+    /// Circuit input of each block of the range
+    pub blocks: Vec<Block>,
+    /// Blocks of the range, with their transactions
+    pub eth_blocks: Vec<EthBlock>,
+    /// State root of the parent of the first block of the range
+    pub prev_state_root: H256,
+    /// `eth_getProof` responses of the accounts and storage slots accessed
+    /// by any block of the range, at the state before the first block
+    pub proofs: Vec<eth_types::EIP1186ProofResponse>,
+    /// StateDB after the last block of the range
+    pub sdb: StateDB,
+    /// CodeDB with the codes used in all the blocks of the range
+    pub code_db: CodeDB,
+}
+
+impl BlockRangeInputs {
+    /// Partial StateDB before the first block of the range, with the accounts
+    /// and storage slots of the proofs.
+    pub fn prev_state(&self) -> StateDB {
+        build_state_code_db(&self.proofs, iter::empty()).0
+    }
+}
+
+/// Build a partial StateDB and a CodeDB from the `eth_getProof` responses and
+/// the codes of the accessed accounts.
+fn build_state_code_db(
+    proofs: &[eth_types::EIP1186ProofResponse],
+    codes: impl IntoIterator<Item = Vec<u8>>,
+) -> (StateDB, CodeDB) {
+    let mut sdb = StateDB::new();
+    for proof in proofs {
+        let mut storage = HashMap::new();
+        for storage_proof in &proof.storage_proof {
+            storage.insert(storage_proof.key, storage_proof.value);
+        }
+        sdb.set_account(
+            &proof.address,
+            state_db::Account {
+                nonce: proof.nonce,
+                balance: proof.balance,
+                storage,
+                code_hash: proof.code_hash,
+            },
+        )
+    }
+
+    let mut code_db = CodeDB::new();
+    for code in codes {
+        code_db.insert(code);
+    }
+    (sdb, code_db)
+}
+
+/// For each step in TxExecTraces, gen the associated ops and state circuit
+/// inputs
+fn gen_inputs_from_state(
+    chain_id: Word,
+    history_hashes: Vec<Word>,
+    sdb: StateDB,
+    code_db: CodeDB,
+    eth_block: &EthBlock,
+    geth_traces: &[eth_types::GethExecTrace],
+) -> Result<CircuitInputBuilder, Error> {
+    let block = Block::new(chain_id, history_hashes, eth_block)?;
+    let mut builder = CircuitInputBuilder::new(sdb, code_db, block);
+    builder.handle_block(eth_block, geth_traces)?;
+    Ok(builder)
+}
+
+/// Generate the circuit inputs of each block of a range of consecutive blocks,
+/// starting at the state before the first block.  `history_hashes` are the
+/// hashes before the first block; the parent of each following block is
+/// appended to the history of the previous one, keeping the last 256.
+/// Returns the circuit inputs together with the StateDB and CodeDB after the
+/// last block.
+fn gen_inputs_range_from_state(
+    chain_id: Word,
+    history_hashes: Vec<Word>,
+    sdb: StateDB,
+    code_db: CodeDB,
+    eth_blocks: &[EthBlock],
+    geth_traces: &[Vec<eth_types::GethExecTrace>],
+    mode: CounterMode,
+) -> Result<(Vec<Block>, StateDB, CodeDB), Error> {
+    let (first_block, next_blocks) = eth_blocks
+        .split_first()
+        .ok_or(Error::InternalError("empty block range"))?;
+    let mut history_hashes = history_hashes;
+    let mut builder = gen_inputs_from_state(
+        chain_id,
+        history_hashes.clone(),
+        sdb,
+        code_db,
+        first_block,
+        &geth_traces[0],
+    )?;
+    let mut blocks = Vec::new();
+    for (eth_block, block_geth_traces) in next_blocks.iter().zip_eq(&geth_traces[1..]) {
+        history_hashes.push(eth_block.parent_hash.to_word());
+        if history_hashes.len() > 256 {
+            history_hashes.remove(0);
+        }
+        let block = Block::new(chain_id, history_hashes.clone(), eth_block)?;
+        blocks.push(builder.begin_next_block(block, mode));
+        builder.handle_block(eth_block, block_geth_traces)?;
+    }
+    blocks.push(builder.block);
+    Ok((blocks, builder.sdb, builder.code_db))
+}
+
 /// Struct that wraps a GethClient and contains methods to perform all the steps
 /// necessary to generate the circuit inputs for a block by querying geth for
 /// the necessary information and using the CircuitInputBuilder.
@@ -277,6 +715,7 @@ pub struct BuilderClient<P: JsonRpcClient> {
     cli: GethClient<P>,
     chain_id: Word,
     history_hashes: Vec<Word>,
+    prestate_tracer: bool,
 }
 
 impl<P: JsonRpcClient> BuilderClient<P> {
@@ -289,9 +728,19 @@ impl<P: JsonRpcClient> BuilderClient<P> {
             chain_id: chain_id.into(),
             // TODO: Get history hashes
             history_hashes: Vec::new(),
+            prestate_tracer: false,
         })
     }
 
+    /// Get the state accesses of each block from the geth `prestateTracer`
+    /// instead of the struct logs of its transactions, and check the calls of
+    /// the circuit inputs generated by [`Self::gen_inputs`] and
+    /// [`Self::gen_inputs_range`] against the geth `callTracer`.
+    pub fn with_prestate_tracer(mut self) -> Self {
+        self.prestate_tracer = true;
+        self
+    }
+
     /// Step 1. Query geth for Block, Txs and TxExecTraces
     pub async fn get_block(
         &self,
@@ -303,7 +752,9 @@ impl<P: JsonRpcClient> BuilderClient<P> {
         Ok((eth_block, geth_traces))
     }
 
-    /// Step 2. Get State Accesses from TxExecTraces
+    /// Step 2. Get State Accesses from TxExecTraces.  The accesses that are
+    /// reverted are included, since their state is needed to generate the
+    /// circuit inputs of the revert sections.
     pub fn get_state_accesses(
         &self,
         eth_block: &EthBlock,
@@ -311,6 +762,8 @@ impl<P: JsonRpcClient> BuilderClient<P> {
     ) -> Result<AccessSet, Error> {
         let mut block_access_trace = vec![Access::new(
             None,
+            0,
+            false,
             RW::WRITE,
             AccessValue::Account {
                 address: eth_block
@@ -327,6 +780,67 @@ impl<P: JsonRpcClient> BuilderClient<P> {
         Ok(AccessSet::from(block_access_trace))
     }
 
+    /// Step 2, using the geth `prestateTracer` to get the accounts, storage
+    /// keys and codes accessed by the transactions of the block.
+    pub async fn get_state_accesses_from_prestate(
+        &self,
+        block_num: u64,
+        eth_block: &EthBlock,
+    ) -> Result<AccessSet, Error> {
+        let traces = self
+            .cli
+            .trace_block_prestate_by_number(block_num.into())
+            .await?;
+        let mut access_set = AccessSet::from_prestate(&traces);
+        access_set
+            .state
+            .entry(
+                eth_block
+                    .author
+                    .ok_or(Error::EthTypeError(eth_types::Error::IncompleteBlock))?,
+            )
+            .or_default();
+        Ok(access_set)
+    }
+
+    /// Step 2, from the struct logs or the geth `prestateTracer` depending on
+    /// [`Self::with_prestate_tracer`].
+    async fn get_block_state_accesses(
+        &self,
+        block_num: u64,
+        eth_block: &EthBlock,
+        geth_traces: &[eth_types::GethExecTrace],
+    ) -> Result<AccessSet, Error> {
+        if self.prestate_tracer {
+            self.get_state_accesses_from_prestate(block_num, eth_block)
+                .await
+        } else {
+            self.get_state_accesses(eth_block, geth_traces)
+        }
+    }
+
+    /// Check that the calls of the transactions of the circuit inputs of a
+    /// block match the call frames returned by the geth `callTracer`.
+    pub async fn check_call_traces(&self, block_num: u64, block: &Block) -> Result<(), Error> {
+        let call_traces = self
+            .cli
+            .trace_block_calls_by_number(block_num.into())
+            .await?;
+        if call_traces.len() != block.txs.len() {
+            return Err(Error::InvalidGethExecTrace(
+                "Number of call traces doesn't match the number of transactions",
+            ));
+        }
+        for (tx_index, (tx, call_trace)) in block.txs.iter().zip(call_traces.iter()).enumerate() {
+            tx.check_call_trace(call_trace)
+                .map_err(|error| Error::TxError {
+                    tx_index,
+                    error: Box::new(error),
+                })?;
+        }
+        Ok(())
+    }
+
     /// Step 3. Query geth for all accounts, storage keys, and codes from
     /// Accesses
     pub async fn get_state(
@@ -363,34 +877,47 @@ impl<P: JsonRpcClient> BuilderClient<P> {
         Ok((proofs, codes))
     }
 
+    /// Get the state root of the header of the parent of a block, which is
+    /// the root of the state the proofs of [`Self::get_state`] start at.
+    pub async fn get_prev_state_root(&self, block_num: u64) -> Result<H256, Error> {
+        let parent_num = block_num
+            .checked_sub(1)
+            .ok_or(Error::InternalError("the genesis block has no parent"))?;
+        let parent_block = self.cli.get_block_by_number(parent_num.into()).await?;
+        Ok(parent_block.state_root)
+    }
+
+    /// Perform steps 1 to 3 and collect all the data queried from geth into
+    /// a [`BlockBundle`], from which the circuit inputs can be generated
+    /// offline.
+    pub async fn get_block_bundle(&self, block_num: u64) -> Result<BlockBundle, Error> {
+        let (eth_block, geth_traces) = self.get_block(block_num).await?;
+        let access_set = self
+            .get_block_state_accesses(block_num, &eth_block, &geth_traces)
+            .await?;
+        let (proofs, codes) = self.get_state(block_num, access_set).await?;
+        let prev_state_root = self.get_prev_state_root(block_num).await?;
+        Ok(BlockBundle {
+            chain_id: self.chain_id,
+            history_hashes: self.history_hashes.clone(),
+            eth_block,
+            prev_state_root,
+            geth_traces,
+            proofs,
+            codes: codes
+                .into_iter()
+                .map(|(address, code)| (address, code.into()))
+                .collect(),
+        })
+    }
+
     /// Step 4. Build a partial StateDB from step 3
     pub fn build_state_code_db(
         &self,
         proofs: Vec<eth_types::EIP1186ProofResponse>,
         codes: HashMap<Address, Vec<u8>>,
     ) -> (StateDB, CodeDB) {
-        let mut sdb = StateDB::new();
-        for proof in proofs {
-            let mut storage = HashMap::new();
-            for storage_proof in proof.storage_proof {
-                storage.insert(storage_proof.key, storage_proof.value);
-            }
-            sdb.set_account(
-                &proof.address,
-                state_db::Account {
-                    nonce: proof.nonce,
-                    balance: proof.balance,
-                    storage,
-                    code_hash: proof.code_hash,
-                },
-            )
-        }
-
-        let mut code_db = CodeDB::new();
-        for (_address, code) in codes {
-            code_db.insert(code.clone());
-        }
-        (sdb, code_db)
+        build_state_code_db(&proofs, codes.into_values())
     }
 
     /// Step 5. For each step in TxExecTraces, gen the associated ops and state
@@ -402,10 +929,69 @@ impl<P: JsonRpcClient> BuilderClient<P> {
         eth_block: &EthBlock,
         geth_traces: &[eth_types::GethExecTrace],
     ) -> Result<CircuitInputBuilder, Error> {
-        let block = Block::new(self.chain_id, self.history_hashes.clone(), eth_block)?;
-        let mut builder = CircuitInputBuilder::new(sdb, code_db, block);
-        builder.handle_block(eth_block, geth_traces)?;
-        Ok(builder)
+        gen_inputs_from_state(
+            self.chain_id,
+            self.history_hashes.clone(),
+            sdb,
+            code_db,
+            eth_block,
+            geth_traces,
+        )
+    }
+
+    /// Perform all the steps to generate the circuit inputs of each block of a
+    /// range of consecutive blocks.  The state is queried once, before the
+    /// first block, and threaded through the blocks.  The circuit inputs can
+    /// be converted to witness blocks with chained state roots by
+    /// `zkevm_circuits::witness::block_range_convert`.
+    pub async fn gen_inputs_range(
+        &self,
+        block_nums: RangeInclusive<u64>,
+        mode: CounterMode,
+    ) -> Result<BlockRangeInputs, Error> {
+        if block_nums.is_empty() {
+            return Err(Error::InternalError("empty block range"));
+        }
+        let first_block_num = *block_nums.start();
+        let prev_state_root = self.get_prev_state_root(first_block_num).await?;
+        let mut eth_blocks = Vec::new();
+        let mut geth_traces = Vec::new();
+        let mut access_set = AccessSet::from(Vec::new());
+        for block_num in block_nums.clone() {
+            let (eth_block, block_geth_traces) = self.get_block(block_num).await?;
+            access_set.extend(
+                self.get_block_state_accesses(block_num, &eth_block, &block_geth_traces)
+                    .await?,
+            );
+            eth_blocks.push(eth_block);
+            geth_traces.push(block_geth_traces);
+        }
+        let (proofs, codes) = self.get_state(first_block_num, access_set).await?;
+        let (sdb, code_db) = build_state_code_db(&proofs, codes.into_values());
+
+        let (blocks, sdb, code_db) = gen_inputs_range_from_state(
+            self.chain_id,
+            self.history_hashes.clone(),
+            sdb,
+            code_db,
+            &eth_blocks,
+            &geth_traces,
+            mode,
+        )?;
+        if self.prestate_tracer {
+            for (block_num, block) in block_nums.zip(blocks.iter()) {
+                self.check_call_traces(block_num, block).await?;
+            }
+        }
+
+        Ok(BlockRangeInputs {
+            blocks,
+            eth_blocks,
+            prev_state_root,
+            proofs,
+            sdb,
+            code_db,
+        })
     }
 
     /// Perform all the steps to generate the circuit inputs
@@ -419,11 +1005,11 @@ impl<P: JsonRpcClient> BuilderClient<P> {
         ),
         Error,
     > {
-        let (eth_block, geth_traces) = self.get_block(block_num).await?;
-        let access_set = self.get_state_accesses(&eth_block, &geth_traces)?;
-        let (proofs, codes) = self.get_state(block_num, access_set).await?;
-        let (state_db, code_db) = self.build_state_code_db(proofs, codes);
-        let builder = self.gen_inputs_from_state(state_db, code_db, &eth_block, &geth_traces)?;
-        Ok((builder, eth_block))
+        let bundle = self.get_block_bundle(block_num).await?;
+        let builder = bundle.gen_inputs()?;
+        if self.prestate_tracer {
+            self.check_call_traces(block_num, &builder.block).await?;
+        }
+        Ok((builder, bundle.eth_block))
     }
 }
@@ -1,5 +1,7 @@
 use crate::{operation::RW, Error};
-use eth_types::{evm_types::OpcodeId, Address, GethExecStep, GethExecTrace, ToAddress, Word};
+use eth_types::{
+    evm_types::OpcodeId, Address, GethExecStep, GethExecTrace, GethPrestateTrace, ToAddress, Word,
+};
 use ethers_core::utils::get_contract_address;
 use std::collections::{hash_map::Entry, HashMap, HashSet};
 
@@ -29,18 +31,49 @@ pub enum AccessValue {
 #[derive(Debug, PartialEq, Eq)]
 pub struct Access {
     step_index: Option<usize>,
+    /// Call depth of the frame in which the access takes effect, which is the
+    /// callee frame for the accesses of the *CALL*/CREATE* opcodes, and 0 for
+    /// the accesses of the transaction itself that are never reverted.
+    depth: usize,
+    /// Whether the frame in which the access takes effect, or one of its
+    /// parents, reverted.
+    reverted: bool,
     rw: RW,
     value: AccessValue,
 }
 
 impl Access {
-    pub(crate) fn new(step_index: Option<usize>, rw: RW, value: AccessValue) -> Self {
+    pub(crate) fn new(
+        step_index: Option<usize>,
+        depth: usize,
+        reverted: bool,
+        rw: RW,
+        value: AccessValue,
+    ) -> Self {
         Self {
             step_index,
+            depth,
+            reverted,
             rw,
             value,
         }
     }
+
+    /// Call depth of the frame in which the access takes effect.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Whether the access was reverted.
+    pub fn reverted(&self) -> bool {
+        self.reverted
+    }
+
+    /// Return true if the access is a write that persisted after the
+    /// transaction.
+    pub fn is_persisted_write(&self) -> bool {
+        self.rw == RW::WRITE && !self.reverted
+    }
 }
 
 /// Given a trace and assuming that the first step is a *CALL*/CREATE* kind
@@ -91,6 +124,46 @@ impl From<Vec<Access>> for AccessSet {
     }
 }
 
+impl AccessSet {
+    /// Return the set of the writes of `list` which persisted, that is, the
+    /// writes that were not reverted.  For the accesses of a transaction, this
+    /// is the set of accounts, storage slots and codes the transaction changed.
+    pub fn persisted_writes(list: Vec<Access>) -> Self {
+        Self::from(
+            list.into_iter()
+                .filter(Access::is_persisted_write)
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Return the set of the accounts, storage slots and codes in the
+    /// results of the geth `prestateTracer` for the transactions of a block.
+    /// Codes are only included for the accounts which have code.
+    pub fn from_prestate(traces: &[GethPrestateTrace]) -> Self {
+        let mut access_set = Self::from(Vec::new());
+        for (address, account) in traces.iter().flatten() {
+            access_set.state.entry(*address).or_default().extend(
+                account
+                    .storage
+                    .keys()
+                    .map(|key| Word::from_big_endian(key.as_bytes())),
+            );
+            if account.code.as_ref().map_or(false, |code| !code.is_empty()) {
+                access_set.code.insert(*address);
+            }
+        }
+        access_set
+    }
+
+    /// Add the accesses of `other` to this set.
+    pub fn extend(&mut self, other: AccessSet) {
+        for (address, keys) in other.state {
+            self.state.entry(address).or_default().extend(keys);
+        }
+        self.code.extend(other.code);
+    }
+}
+
 /// Source of the code in the EVM execution.
 #[derive(Debug, Clone, Copy)]
 pub enum CodeSource {
@@ -109,8 +182,8 @@ impl Default for CodeSource {
 }
 
 /// Generate the State Access trace from the given trace.  All state read/write
-/// accesses are reported, without distinguishing those that happen in revert
-/// sections.
+/// accesses are reported, including those that happen in revert sections,
+/// which are tagged as reverted.
 pub fn gen_state_access_trace<TX>(
     _block: &eth_types::Block<TX>,
     tx: &eth_types::Transaction,
@@ -119,25 +192,59 @@ pub fn gen_state_access_trace<TX>(
     use AccessValue::{Account, Code, Storage};
     use RW::{READ, WRITE};
 
-    let mut call_stack: Vec<(Address, CodeSource)> = Vec::new();
-    let mut accs = vec![Access::new(None, WRITE, Account { address: tx.from })];
+    // The sender pays for the gas even if the transaction fails.
+    let tx_reverted = geth_trace.failed;
+    let mut call_stack: Vec<(Address, CodeSource, bool)> = Vec::new();
+    let mut accs = vec![Access::new(
+        None,
+        0,
+        false,
+        WRITE,
+        Account { address: tx.from },
+    )];
     if let Some(to) = tx.to {
-        call_stack.push((to, CodeSource::Address(to)));
-        accs.push(Access::new(None, WRITE, Account { address: to }));
+        call_stack.push((to, CodeSource::Address(to), tx_reverted));
+        accs.push(Access::new(
+            None,
+            1,
+            tx_reverted,
+            WRITE,
+            Account { address: to },
+        ));
         // Code may be null if the account is not a contract
-        accs.push(Access::new(None, READ, Code { address: to }));
+        accs.push(Access::new(
+            None,
+            1,
+            tx_reverted,
+            READ,
+            Code { address: to },
+        ));
     } else {
         let address = get_contract_address(tx.from, tx.nonce);
-        call_stack.push((address, CodeSource::Tx));
-        accs.push(Access::new(None, WRITE, Account { address }));
-        accs.push(Access::new(None, WRITE, Code { address }));
+        call_stack.push((address, CodeSource::Tx, tx_reverted));
+        accs.push(Access::new(
+            None,
+            1,
+            tx_reverted,
+            WRITE,
+            Account { address },
+        ));
+        accs.push(Access::new(None, 1, tx_reverted, WRITE, Code { address }));
     }
 
     for (index, step) in geth_trace.struct_logs.iter().enumerate() {
         let next_step = geth_trace.struct_logs.get(index + 1);
         let i = Some(index);
-        let (contract_address, code_source) = &call_stack[call_stack.len() - 1];
-        let (contract_address, code_source) = (*contract_address, *code_source);
+        let (contract_address, code_source, reverted) = call_stack[call_stack.len() - 1];
+        let depth = step.depth as usize;
+        // The accesses of the *CALL*/CREATE* opcodes take effect in the callee
+        // frame, which reverts when the call fails, even if it has no steps.
+        let callee_reverted = || -> bool {
+            reverted
+                || get_call_result(&geth_trace.struct_logs[index..])
+                    .map(|result| result.is_zero())
+                    .unwrap_or(true)
+        };
 
         let (mut push_call_stack, mut pop_call_stack) = (false, false);
         if let Some(next_step) = next_step {
@@ -149,109 +256,120 @@ pub fn gen_state_access_trace<TX>(
             OpcodeId::SSTORE => {
                 let address = contract_address;
                 let key = step.stack.nth_last(0)?;
-                accs.push(Access::new(i, WRITE, Storage { address, key }));
+                accs.push(Access::new(
+                    i,
+                    depth,
+                    reverted,
+                    WRITE,
+                    Storage { address, key },
+                ));
             }
             OpcodeId::SLOAD => {
                 let address = contract_address;
                 let key = step.stack.nth_last(0)?;
-                accs.push(Access::new(i, READ, Storage { address, key }));
+                accs.push(Access::new(
+                    i,
+                    depth,
+                    reverted,
+                    READ,
+                    Storage { address, key },
+                ));
             }
             OpcodeId::SELFBALANCE => {
                 let address = contract_address;
-                accs.push(Access::new(i, READ, Account { address }));
+                accs.push(Access::new(i, depth, reverted, READ, Account { address }));
             }
             OpcodeId::CODESIZE => {
                 if let CodeSource::Address(address) = code_source {
-                    accs.push(Access::new(i, READ, Code { address }));
+                    accs.push(Access::new(i, depth, reverted, READ, Code { address }));
                 }
             }
             OpcodeId::CODECOPY => {
                 if let CodeSource::Address(address) = code_source {
-                    accs.push(Access::new(i, READ, Code { address }));
+                    accs.push(Access::new(i, depth, reverted, READ, Code { address }));
                 }
             }
             OpcodeId::BALANCE => {
                 let address = step.stack.nth_last(0)?.to_address();
-                accs.push(Access::new(i, READ, Account { address }));
+                accs.push(Access::new(i, depth, reverted, READ, Account { address }));
             }
             OpcodeId::EXTCODEHASH => {
                 let address = step.stack.nth_last(0)?.to_address();
-                accs.push(Access::new(i, READ, Account { address }));
+                accs.push(Access::new(i, depth, reverted, READ, Account { address }));
             }
             OpcodeId::EXTCODESIZE => {
                 let address = step.stack.nth_last(0)?.to_address();
-                accs.push(Access::new(i, READ, Code { address }));
+                accs.push(Access::new(i, depth, reverted, READ, Code { address }));
             }
             OpcodeId::EXTCODECOPY => {
                 let address = step.stack.nth_last(0)?.to_address();
-                accs.push(Access::new(i, READ, Code { address }));
+                accs.push(Access::new(i, depth, reverted, READ, Code { address }));
             }
             OpcodeId::SELFDESTRUCT => {
                 let address = contract_address;
-                accs.push(Access::new(i, WRITE, Account { address }));
+                accs.push(Access::new(i, depth, reverted, WRITE, Account { address }));
                 let address = step.stack.nth_last(0)?.to_address();
-                accs.push(Access::new(i, WRITE, Account { address }));
-            }
-            OpcodeId::CREATE => {
-                if push_call_stack {
-                    // Find CREATE result
-                    let address = get_call_result(&geth_trace.struct_logs[index..])
-                        .unwrap_or_else(Word::zero)
-                        .to_address();
-                    if !address.is_zero() {
-                        accs.push(Access::new(i, WRITE, Account { address }));
-                        accs.push(Access::new(i, WRITE, Code { address }));
-                    }
-                    call_stack.push((address, CodeSource::Address(address)));
-                }
+                accs.push(Access::new(i, depth, reverted, WRITE, Account { address }));
             }
-            OpcodeId::CREATE2 => {
+            OpcodeId::CREATE | OpcodeId::CREATE2 => {
                 if push_call_stack {
-                    // Find CREATE2 result
+                    // Find CREATE/CREATE2 result, which is zero when the
+                    // creation failed.
                     let address = get_call_result(&geth_trace.struct_logs[index..])
                         .unwrap_or_else(Word::zero)
                         .to_address();
+                    let reverted = reverted || address.is_zero();
                     if !address.is_zero() {
-                        accs.push(Access::new(i, WRITE, Account { address }));
-                        accs.push(Access::new(i, WRITE, Code { address }));
+                        accs.push(Access::new(
+                            i,
+                            depth + 1,
+                            reverted,
+                            WRITE,
+                            Account { address },
+                        ));
+                        accs.push(Access::new(i, depth + 1, reverted, WRITE, Code { address }));
                     }
-                    call_stack.push((address, CodeSource::Address(address)));
+                    call_stack.push((address, CodeSource::Address(address), reverted));
                 }
             }
-            OpcodeId::CALL => {
+            OpcodeId::CALL | OpcodeId::CALLCODE => {
+                let reverted = callee_reverted();
                 let address = contract_address;
-                accs.push(Access::new(i, WRITE, Account { address }));
-
-                let address = step.stack.nth_last(1)?.to_address();
-                accs.push(Access::new(i, WRITE, Account { address }));
-                accs.push(Access::new(i, READ, Code { address }));
-                if push_call_stack {
-                    call_stack.push((address, CodeSource::Address(address)));
-                }
-            }
-            OpcodeId::CALLCODE => {
-                let address = contract_address;
-                accs.push(Access::new(i, WRITE, Account { address }));
+                accs.push(Access::new(
+                    i,
+                    depth + 1,
+                    reverted,
+                    WRITE,
+                    Account { address },
+                ));
 
                 let address = step.stack.nth_last(1)?.to_address();
-                accs.push(Access::new(i, WRITE, Account { address }));
-                accs.push(Access::new(i, READ, Code { address }));
+                accs.push(Access::new(
+                    i,
+                    depth + 1,
+                    reverted,
+                    WRITE,
+                    Account { address },
+                ));
+                accs.push(Access::new(i, depth + 1, reverted, READ, Code { address }));
                 if push_call_stack {
-                    call_stack.push((address, CodeSource::Address(address)));
+                    call_stack.push((address, CodeSource::Address(address), reverted));
                 }
             }
             OpcodeId::DELEGATECALL => {
+                let reverted = callee_reverted();
                 let address = step.stack.nth_last(1)?.to_address();
-                accs.push(Access::new(i, READ, Code { address }));
+                accs.push(Access::new(i, depth + 1, reverted, READ, Code { address }));
                 if push_call_stack {
-                    call_stack.push((contract_address, CodeSource::Address(address)));
+                    call_stack.push((contract_address, CodeSource::Address(address), reverted));
                 }
             }
             OpcodeId::STATICCALL => {
+                let reverted = callee_reverted();
                 let address = step.stack.nth_last(1)?.to_address();
-                accs.push(Access::new(i, READ, Code { address }));
+                accs.push(Access::new(i, depth + 1, reverted, READ, Code { address }));
                 if push_call_stack {
-                    call_stack.push((address, CodeSource::Address(address)));
+                    call_stack.push((address, CodeSource::Address(address), reverted));
                 }
             }
             _ => {}
@@ -59,6 +59,10 @@ pub struct Block {
     pub difficulty: Word,
     /// base fee
     pub base_fee: Word,
+    /// Hash of the block
+    pub hash: Hash,
+    /// State root after the block
+    pub state_root: Hash,
     /// Container of operations done in this block.
     pub container: OperationContainer,
     /// Transactions contained in the block
@@ -67,6 +71,13 @@ pub struct Block {
     pub copy_events: Vec<CopyEvent>,
     /// Inputs to the SHA3 opcode
     pub sha3_inputs: Vec<Vec<u8>>,
+    /// Number of transactions of the previous blocks whose ids are continued
+    /// by the transactions of this block.
+    pub tx_id_offset: usize,
+    /// Whether the memory reconstructed before each step is checked against
+    /// the memory captured by geth, when the traces contain it.  Enabled by
+    /// default in debug builds.
+    pub check_memory: bool,
     code: HashMap<Hash, Vec<u8>>,
 }
 
@@ -99,11 +110,15 @@ impl Block {
             timestamp: eth_block.timestamp,
             difficulty: eth_block.difficulty,
             base_fee: eth_block.base_fee_per_gas.unwrap_or_default(),
+            hash: eth_block.hash.unwrap_or_default(),
+            state_root: eth_block.state_root,
             container: OperationContainer::new(),
             txs: Vec::new(),
             copy_events: Vec::new(),
             code: HashMap::new(),
             sha3_inputs: Vec::new(),
+            tx_id_offset: 0,
+            check_memory: cfg!(debug_assertions),
         })
     }
 
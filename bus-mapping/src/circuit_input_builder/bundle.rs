@@ -0,0 +1,99 @@
+//! Serializable bundle of all the data queried from geth to generate the
+//! circuit inputs of a block, so that the generation can be done offline.
+
+use super::{build_state_code_db, gen_inputs_from_state, CircuitInputBuilder};
+use crate::error::Error;
+use crate::state_db::StateDB;
+use eth_types::geth_types::GethData;
+use eth_types::{
+    Address, Block, Bytes, EIP1186ProofResponse, GethExecTrace, Transaction, Word, H256,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{Read, Write};
+
+/// All the data required to generate the circuit inputs of a block, as
+/// returned by [`BuilderClient::get_block_bundle`](super::BuilderClient).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BlockBundle {
+    /// Chain ID
+    pub chain_id: Word,
+    /// Hashes of the previous blocks
+    pub history_hashes: Vec<Word>,
+    /// Block, with its transactions
+    pub eth_block: Block<Transaction>,
+    /// State root of the parent block, at which the proofs start
+    pub prev_state_root: H256,
+    /// Execution trace of each transaction of the block
+    pub geth_traces: Vec<GethExecTrace>,
+    /// `eth_getProof` responses of the accessed accounts and storage slots,
+    /// at the state before the block
+    pub proofs: Vec<EIP1186ProofResponse>,
+    /// Code of the accessed accounts, at the state before the block
+    pub codes: HashMap<Address, Bytes>,
+}
+
+impl BlockBundle {
+    /// Write the bundle as JSON.
+    pub fn save<W: Write>(&self, writer: W) -> Result<(), Error> {
+        serde_json::to_writer(writer, self).map_err(Error::SerdeError)
+    }
+
+    /// Read a bundle written by [`BlockBundle::save`].
+    pub fn load<R: Read>(reader: R) -> Result<Self, Error> {
+        serde_json::from_reader(reader).map_err(Error::SerdeError)
+    }
+
+    /// Generate the circuit inputs of the block from the bundle only.
+    pub fn gen_inputs(&self) -> Result<CircuitInputBuilder, Error> {
+        let (sdb, code_db) =
+            build_state_code_db(&self.proofs, self.codes.values().map(|code| code.to_vec()));
+        gen_inputs_from_state(
+            self.chain_id,
+            self.history_hashes.clone(),
+            sdb,
+            code_db,
+            &self.eth_block,
+            &self.geth_traces,
+        )
+    }
+
+    /// Return the block data of the bundle as a [`GethData`].  The accounts
+    /// are left empty, since the state before the block is only available as
+    /// the proofs of the bundle.
+    pub fn geth_data(&self) -> GethData {
+        GethData {
+            chain_id: self.chain_id,
+            history_hashes: self.history_hashes.clone(),
+            eth_block: self.eth_block.clone(),
+            geth_traces: self.geth_traces.clone(),
+            accounts: Vec::new(),
+        }
+    }
+
+    /// Return the partial state before the block, made of the accounts and
+    /// storage slots of the proofs of the bundle.
+    pub fn prev_state(&self) -> StateDB {
+        let (sdb, _) = build_state_code_db(&self.proofs, []);
+        sdb
+    }
+
+    /// Check that the state after generating the circuit inputs with
+    /// [`BlockBundle::gen_inputs`] matches the state root of the block header,
+    /// by applying the changes of the [`StateDB`](crate::state_db::StateDB)
+    /// to the trie nodes of the proofs.  Block rewards are not applied by the
+    /// builder, so the check only passes on chains without them.
+    pub fn check_state_root(&self, builder: &CircuitInputBuilder) -> Result<(), Error> {
+        let state_root = builder
+            .sdb
+            .diff(&self.prev_state())
+            .post_state_root(&self.proofs)?;
+        if state_root != self.eth_block.state_root {
+            return Err(Error::StateRootMismatch(
+                self.eth_block.state_root,
+                state_root,
+            ));
+        }
+        Ok(())
+    }
+}
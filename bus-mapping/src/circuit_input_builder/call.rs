@@ -95,6 +95,15 @@ impl Call {
     pub fn is_create(&self) -> bool {
         self.kind.is_create()
     }
+
+    /// Address of the code executed by this call, which is `None` when the
+    /// code is the init code of a create.
+    pub fn code_address(&self) -> Option<Address> {
+        match self.code_source {
+            CodeSource::Address(address) => Some(address),
+            _ => None,
+        }
+    }
 }
 
 /// Context of a [`Call`].
@@ -1,26 +1,27 @@
 //! CircuitInput builder tooling module.
 
 use super::{
-    get_call_memory_offset_length, get_create_init_code, Block, BlockContext, Call, CallContext,
-    CallKind, CodeSource, CopyEvent, ExecState, ExecStep, Transaction, TransactionContext,
+    get_call_memory_offset_length, get_create_init_code, get_memory_offset_length, Block,
+    BlockContext, Call, CallContext, CallKind, CodeSource, CopyEvent, ExecState, ExecStep,
+    Transaction, TransactionContext,
 };
 use crate::{
     error::{get_step_reported_error, ExecError},
     exec_trace::OperationRef,
     operation::{
         AccountField, AccountOp, CallContextField, CallContextOp, MemoryOp, Op, OpEnum, Operation,
-        StackOp, Target, TxAccessListAccountOp, TxLogField, TxLogOp, TxReceiptField, TxReceiptOp,
-        RW,
+        StackOp, Target, TxAccessListAccountOp, TxAccessListAccountStorageOp, TxLogField, TxLogOp,
+        TxReceiptField, TxReceiptOp, RW,
     },
     state_db::{CodeDB, StateDB},
     Error,
 };
 use eth_types::{
-    evm_types::{Gas, MemoryAddress, OpcodeId, StackAddress},
+    evm_types::{precompiles::precompile, Gas, MemoryAddress, OpcodeId, StackAddress},
     Address, GethExecStep, ToAddress, ToBigEndian, Word, H256,
 };
 use ethers_core::utils::{get_contract_address, get_create2_address};
-use std::cmp::max;
+use std::cmp::{max, min};
 
 /// Reference to the internal state of the CircuitInputBuilder in a particular
 /// [`ExecStep`].
@@ -403,6 +404,35 @@ impl<'a> CircuitInputStateRef<'a> {
         Ok(())
     }
 
+    /// Push a write type [`TxAccessListAccountStorageOp`] into the
+    /// [`OperationContainer`](crate::operation::OperationContainer) with the
+    /// next [`RWCounter`](crate::operation::RWCounter), and then
+    /// adds a reference to the stored operation ([`OperationRef`]) inside
+    /// the bus-mapping instance of the current [`ExecStep`].  Then increase
+    /// the `block_ctx` [`RWCounter`](crate::operation::RWCounter)  by one.
+    pub fn tx_accesslist_account_storage_write(
+        &mut self,
+        step: &mut ExecStep,
+        tx_id: usize,
+        address: Address,
+        key: Word,
+        is_warm: bool,
+        is_warm_prev: bool,
+    ) -> Result<(), Error> {
+        self.push_op(
+            step,
+            RW::WRITE,
+            TxAccessListAccountStorageOp {
+                tx_id,
+                address,
+                key,
+                is_warm,
+                is_warm_prev,
+            },
+        );
+        Ok(())
+    }
+
     /// Push 2 reversible [`AccountOp`] to update `sender` and `receiver`'s
     /// balance by `value`, with `sender` being extraly charged with `fee`.
     pub fn transfer_with_fee(
@@ -569,14 +599,13 @@ impl<'a> CircuitInputStateRef<'a> {
         address.0[0..19] == [0u8; 19] && (1..=9).contains(&address.0[19])
     }
 
-    // TODO: Remove unwrap() and add err handling.
     /// Parse [`Call`] from a *CALL*/CREATE* step.
     pub fn parse_call(&mut self, step: &GethExecStep) -> Result<Call, Error> {
         let is_success = *self
             .tx_ctx
             .call_is_success
             .get(self.tx.calls().len())
-            .unwrap();
+            .ok_or(Error::InternalError("call success not found"))?;
         let kind = CallKind::try_from(step.op)?;
         let caller = self.call()?;
         let caller_ctx = self.call_ctx()?;
@@ -814,6 +843,25 @@ impl<'a> CircuitInputStateRef<'a> {
             callee_account.code_hash = code_hash;
         }
 
+        // Set the return data of the caller, which is only kept from a call
+        // ending with RETURN and from a call or create ending with REVERT.
+        if !call.is_root {
+            let return_data = match step.op {
+                OpcodeId::RETURN if call.is_success && !call.is_create() => true,
+                OpcodeId::REVERT => step.error.is_none(),
+                _ => false,
+            };
+            let return_data = if return_data {
+                let (offset, length) = get_memory_offset_length(step, 0)?;
+                self.call_ctx()?
+                    .memory
+                    .read_chunk(offset.into(), length.into())
+            } else {
+                Vec::new()
+            };
+            self.caller_ctx_mut()?.return_data = return_data;
+        }
+
         // Handle reversion if this call doens't end successfully
         if !self.call()?.is_success {
             self.handle_reversion();
@@ -824,6 +872,45 @@ impl<'a> CircuitInputStateRef<'a> {
         Ok(())
     }
 
+    /// Handle the return from a call to a precompiled contract, which is not
+    /// traced by geth.  The contract is run to write its output to the memory
+    /// of the caller and to keep it as the return data of the caller.
+    pub fn handle_precompile_return(&mut self, step: &GethExecStep) -> Result<(), Error> {
+        let call = self.call()?.clone();
+        let precompile = call
+            .code_address()
+            .and_then(|address| precompile(&address))
+            .ok_or(Error::InternalError(
+                "call is not to a precompiled contract",
+            ))?;
+        // A failed call to a precompiled contract returns no data.
+        let output = if call.is_success {
+            precompile
+                .run(&self.call_ctx()?.call_data)
+                .map_err(Error::PrecompileError)?
+        } else {
+            Vec::new()
+        };
+
+        self.handle_return(step)?;
+
+        let caller_ctx = self.call_ctx_mut()?;
+        let offset = call.return_data_offset as usize;
+        let length = min(output.len(), call.return_data_length as usize);
+        caller_ctx
+            .memory
+            .0
+            .get_mut(offset..offset + length)
+            .ok_or(Error::InvalidGethExecStep(
+                "return data out of caller memory bounds",
+                step.clone(),
+            ))?
+            .copy_from_slice(&output[..length]);
+        caller_ctx.return_data = output;
+
+        Ok(())
+    }
+
     /// Push a copy event to the state.
     pub fn push_copy(&mut self, copy: CopyEvent) {
         self.block.add_copy_event(copy);
@@ -835,7 +922,11 @@ impl<'a> CircuitInputStateRef<'a> {
         next_step: Option<&GethExecStep>,
     ) -> Result<Option<ExecError>, Error> {
         if let Some(error) = &step.error {
-            return Ok(Some(get_step_reported_error(&step.op, error)));
+            return get_step_reported_error(&step.op, error)
+                .map(Some)
+                .ok_or_else(|| {
+                    Error::UnexpectedExecStepError("unknown error reported by geth", step.clone())
+                });
         }
 
         if matches!(step.op, OpcodeId::INVALID(_)) {
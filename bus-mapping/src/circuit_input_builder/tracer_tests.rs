@@ -9,11 +9,12 @@ use crate::operation::RWCounter;
 use crate::state_db::Account;
 use eth_types::evm_types::{stack::Stack, Gas, OpcodeId};
 use eth_types::{
-    address, bytecode, geth_types::GethData, word, Bytecode, Hash, ToAddress, ToWord, Word,
+    address, bytecode, geth_types::GethData, word, Bytecode, GethCallTrace, Hash, ToAddress,
+    ToWord, Word,
 };
 use lazy_static::lazy_static;
 use mock::test_ctx::{helpers::*, LoggerConfig, TestContext};
-use mock::MOCK_COINBASE;
+use mock::{eth, MOCK_ACCOUNTS, MOCK_COINBASE};
 use pretty_assertions::assert_eq;
 use std::collections::HashSet;
 
@@ -1487,10 +1488,225 @@ fn tracer_err_stack_underflow() {
     );
 }
 
+#[test]
+fn tracer_err_unknown() {
+    let code = bytecode! {
+        SWAP5
+    };
+    let block: GethData = TestContext::<2, 1>::new_with_logger_config(
+        None,
+        account_0_code_account_1_no_code(code),
+        tx_from_1_to_0,
+        |block, _tx| block.number(0xcafeu64),
+        LoggerConfig::enable_memory(),
+    )
+    .unwrap()
+    .into();
+
+    let index = 0; // SWAP5
+    let mut step = block.geth_traces[0].struct_logs[index].clone();
+    step.error = Some("unknown error".to_string());
+    let next_step = block.geth_traces[0].struct_logs.get(index + 1);
+
+    let mut builder = CircuitInputBuilderTx::new(&block, &step);
+    assert!(matches!(
+        builder.state_ref().get_step_err(&step, next_step),
+        Err(Error::UnexpectedExecStepError(_, _))
+    ));
+}
+
 //
 // Circuit Input Builder tests
 //
 
+#[test]
+fn exec_step_error_location() {
+    let code = bytecode! {
+        PUSH1(0x1)
+        PUSH1(0x0)
+        MSTORE
+        STOP
+    };
+    let mut block: GethData = TestContext::<2, 1>::new_with_logger_config(
+        None,
+        account_0_code_account_1_no_code(code),
+        tx_from_1_to_0,
+        |block, _tx| block.number(0xcafeu64),
+        LoggerConfig::enable_memory(),
+    )
+    .unwrap()
+    .into();
+
+    // Corrupt the memory of the STOP step, which no longer matches the memory
+    // reconstructed by the builder.
+    block.geth_traces[0].struct_logs[3].memory.0[31] = 0x2;
+
+    let mut builder =
+        crate::mock::BlockData::new_from_geth_data(block.clone()).new_circuit_input_builder();
+    let err = builder
+        .handle_block(&block.eth_block, &block.geth_traces)
+        .unwrap_err();
+    match err {
+        Error::ExecStepError {
+            tx_index,
+            step_index,
+            pc,
+            op,
+            error,
+        } => {
+            assert_eq!((tx_index, step_index, pc.0, op), (0, 3, 5, OpcodeId::STOP));
+            assert!(matches!(*error, Error::InvalidGethExecStep(_, _)));
+        }
+        err => panic!("unexpected error {:?}", err),
+    }
+}
+
+#[test]
+fn handle_block_from_reader() {
+    let code_a = bytecode! {
+        PUSH1(0x0) // retLength
+        PUSH1(0x0) // retOffset
+        PUSH1(0x0) // argsLength
+        PUSH1(0x0) // argsOffset
+        PUSH1(0x0) // value
+        PUSH32(*WORD_ADDR_B) // addr
+        PUSH32(0x1_0000) // gas
+        CALL
+
+        PUSH2(0xaa)
+    };
+    let code_b = bytecode! {
+        PUSH1(0x01) // value
+        PUSH1(0x02) // key
+        SSTORE
+
+        PUSH3(0xbb)
+    };
+    let block: GethData = TestContext::<3, 2>::new(
+        None,
+        |accs| {
+            accs[0]
+                .address(address!("0x0000000000000000000000000000000000000000"))
+                .code(code_a);
+            accs[1].address(*ADDR_B).code(code_b);
+            accs[2]
+                .address(address!("0x000000000000000000000000000000000cafe002"))
+                .balance(Word::from(1u64 << 30));
+        },
+        |mut txs, accs| {
+            txs[0].to(accs[0].address).from(accs[2].address);
+            txs[1]
+                .to(accs[1].address)
+                .from(accs[2].address)
+                .nonce(Word::one());
+        },
+        |block, _tx| block.number(0xcafeu64),
+    )
+    .unwrap()
+    .into();
+
+    let mut builder =
+        crate::mock::BlockData::new_from_geth_data(block.clone()).new_circuit_input_builder();
+    builder
+        .handle_block(&block.eth_block, &block.geth_traces)
+        .unwrap();
+
+    let traces = serde_json::to_vec(
+        &block
+            .geth_traces
+            .iter()
+            .map(|trace| serde_json::json!({ "result": trace }))
+            .collect::<Vec<_>>(),
+    )
+    .unwrap();
+    let mut streamed_builder =
+        crate::mock::BlockData::new_from_geth_data(block.clone()).new_circuit_input_builder();
+    streamed_builder
+        .handle_block_from_reader(&block.eth_block, || Ok(traces.as_slice()))
+        .unwrap();
+
+    assert_eq!(
+        format!("{:?}", streamed_builder.block.txs),
+        format!("{:?}", builder.block.txs)
+    );
+    assert_eq!(streamed_builder.block.container, builder.block.container);
+}
+
+#[test]
+fn check_call_trace() {
+    let code_a = bytecode! {
+        PUSH1(0x0) // retLength
+        PUSH1(0x0) // retOffset
+        PUSH1(0x0) // argsLength
+        PUSH1(0x0) // argsOffset
+        PUSH1(0x0) // value
+        PUSH32(*WORD_ADDR_B) // addr
+        PUSH32(0x1_0000) // gas
+        CALL
+
+        PUSH2(0xaa)
+    };
+    let code_b = bytecode! {
+        PUSH1(0x0) // length
+        PUSH1(0x0) // offset
+        REVERT
+    };
+    let block: GethData = TestContext::<3, 1>::new(
+        None,
+        |accs| {
+            accs[0]
+                .address(address!("0x0000000000000000000000000000000000000000"))
+                .code(code_a);
+            accs[1].address(*ADDR_B).code(code_b);
+            accs[2]
+                .address(address!("0x000000000000000000000000000000000cafe002"))
+                .balance(Word::from(1u64 << 30));
+        },
+        |mut txs, accs| {
+            txs[0].to(accs[0].address).from(accs[2].address);
+        },
+        |block, _tx| block.number(0xcafeu64),
+    )
+    .unwrap()
+    .into();
+
+    let mut builder =
+        crate::mock::BlockData::new_from_geth_data(block.clone()).new_circuit_input_builder();
+    builder
+        .handle_block(&block.eth_block, &block.geth_traces)
+        .unwrap();
+
+    let frame = |call_type, from, to, error: Option<&str>| GethCallTrace {
+        call_type,
+        from,
+        to: Some(to),
+        value: None,
+        gas: Default::default(),
+        gas_used: Default::default(),
+        input: Default::default(),
+        output: None,
+        error: error.map(str::to_string),
+        calls: Vec::new(),
+    };
+    let addr_a = address!("0x0000000000000000000000000000000000000000");
+    let addr_sender = address!("0x000000000000000000000000000000000cafe002");
+    let mut call_trace = frame(OpcodeId::CALL, addr_sender, addr_a, None);
+    call_trace.calls.push(frame(
+        OpcodeId::CALL,
+        addr_a,
+        *ADDR_B,
+        Some("execution reverted"),
+    ));
+    builder.block.txs[0].check_call_trace(&call_trace).unwrap();
+
+    // The subcall reverted
+    call_trace.calls[0].error = None;
+    assert!(matches!(
+        builder.block.txs[0].check_call_trace(&call_trace),
+        Err(Error::CallTraceMismatch(1))
+    ));
+}
+
 #[test]
 fn create2_address() {
     // code_creator outputs 0x6050.
@@ -1774,14 +1990,16 @@ fn test_gen_access_trace() {
     assert_eq!(
         access_trace,
         vec![
-            Access::new(None, WRITE, Account { address: ADDR_0 }),
-            Access::new(None, WRITE, Account { address: *ADDR_A }),
-            Access::new(None, READ, Code { address: *ADDR_A }),
-            Access::new(Some(7), WRITE, Account { address: *ADDR_A }),
-            Access::new(Some(7), WRITE, Account { address: *ADDR_B }),
-            Access::new(Some(7), READ, Code { address: *ADDR_B }),
+            Access::new(None, 0, false, WRITE, Account { address: ADDR_0 }),
+            Access::new(None, 1, false, WRITE, Account { address: *ADDR_A }),
+            Access::new(None, 1, false, READ, Code { address: *ADDR_A }),
+            Access::new(Some(7), 2, false, WRITE, Account { address: *ADDR_A }),
+            Access::new(Some(7), 2, false, WRITE, Account { address: *ADDR_B }),
+            Access::new(Some(7), 2, false, READ, Code { address: *ADDR_B }),
             Access::new(
                 Some(13),
+                2,
+                false,
                 WRITE,
                 Storage {
                     address: *ADDR_B,
@@ -1790,6 +2008,8 @@ fn test_gen_access_trace() {
             ),
             Access::new(
                 Some(15),
+                2,
+                false,
                 READ,
                 Storage {
                     address: *ADDR_B,
@@ -1861,9 +2081,11 @@ fn test_gen_access_trace_call_EOA_no_new_stack_frame() {
     assert_eq!(
         access_trace,
         vec![
-            Access::new(None, WRITE, Account { address: *ADDR_B }),
+            Access::new(None, 0, false, WRITE, Account { address: *ADDR_B }),
             Access::new(
                 None,
+                1,
+                false,
                 WRITE,
                 Account {
                     address: *MOCK_COINBASE
@@ -1871,6 +2093,8 @@ fn test_gen_access_trace_call_EOA_no_new_stack_frame() {
             ),
             Access::new(
                 None,
+                1,
+                false,
                 READ,
                 Code {
                     address: *MOCK_COINBASE
@@ -1878,15 +2102,19 @@ fn test_gen_access_trace_call_EOA_no_new_stack_frame() {
             ),
             Access::new(
                 Some(7),
+                2,
+                false,
                 WRITE,
                 Account {
                     address: *MOCK_COINBASE
                 }
             ),
-            Access::new(Some(7), WRITE, Account { address: *ADDR_B }),
-            Access::new(Some(7), READ, Code { address: *ADDR_B }),
+            Access::new(Some(7), 2, false, WRITE, Account { address: *ADDR_B }),
+            Access::new(Some(7), 2, false, READ, Code { address: *ADDR_B }),
             Access::new(
                 Some(10),
+                1,
+                false,
                 WRITE,
                 Storage {
                     address: *MOCK_COINBASE,
@@ -1895,6 +2123,8 @@ fn test_gen_access_trace_call_EOA_no_new_stack_frame() {
             ),
             Access::new(
                 Some(12),
+                1,
+                false,
                 READ,
                 Storage {
                     address: *MOCK_COINBASE,
@@ -2003,6 +2233,8 @@ fn test_gen_access_trace_create_push_call_stack() {
         vec![
             Access::new(
                 None,
+                0,
+                false,
                 WRITE,
                 Account {
                     address: Address::zero()
@@ -2010,6 +2242,8 @@ fn test_gen_access_trace_create_push_call_stack() {
             ),
             Access::new(
                 None,
+                1,
+                false,
                 WRITE,
                 Account {
                     address: *MOCK_COINBASE
@@ -2017,6 +2251,8 @@ fn test_gen_access_trace_create_push_call_stack() {
             ),
             Access::new(
                 None,
+                1,
+                false,
                 READ,
                 Code {
                     address: *MOCK_COINBASE
@@ -2024,13 +2260,15 @@ fn test_gen_access_trace_create_push_call_stack() {
             ),
             Access::new(
                 Some(7),
+                2,
+                false,
                 WRITE,
                 Account {
                     address: *MOCK_COINBASE
                 }
             ),
-            Access::new(Some(7), WRITE, Account { address: *ADDR_B }),
-            Access::new(Some(7), READ, Code { address: *ADDR_B }),
+            Access::new(Some(7), 2, false, WRITE, Account { address: *ADDR_B }),
+            Access::new(Some(7), 2, false, READ, Code { address: *ADDR_B }),
         ]
     );
 
@@ -2047,3 +2285,193 @@ fn test_gen_access_trace_create_push_call_stack() {
         }
     )
 }
+
+#[test]
+fn test_gen_access_trace_reverted_call() {
+    use AccessValue::{Account, Code, Storage};
+    use RW::{READ, WRITE};
+
+    // code_a calls code_b, which writes storage and reverts, and then writes
+    // its own storage.
+    let code_a = bytecode! {
+        PUSH1(0x0) // retLength
+        PUSH1(0x0) // retOffset
+        PUSH1(0x0) // argsLength
+        PUSH1(0x0) // argsOffset
+        PUSH1(0x0) // value
+        PUSH32(*WORD_ADDR_B) // addr
+        PUSH32(0x1_0000) // gas
+        CALL
+        PUSH1(0x01) // value
+        PUSH1(0x04) // key
+        SSTORE
+    };
+    let code_b = bytecode! {
+        PUSH1(0x01) // value
+        PUSH1(0x02) // key
+        SSTORE
+        PUSH1(0x00) // length
+        PUSH1(0x00) // offset
+        REVERT
+    };
+
+    // Get the execution steps from the external tracer
+    let block: GethData = TestContext::<3, 1>::new_with_logger_config(
+        None,
+        |accs| {
+            accs[0].address(*MOCK_COINBASE).code(code_a);
+            accs[1].address(*ADDR_B).code(code_b);
+            accs[2].balance(Word::from(1u64 << 30));
+        },
+        |mut txs, accs| {
+            txs[0].to(accs[0].address).from(accs[2].address);
+        },
+        |block, _tx| block.number(0xcafeu64),
+        LoggerConfig::enable_memory(),
+    )
+    .unwrap()
+    .into();
+
+    let access_trace = gen_state_access_trace(
+        &block.eth_block,
+        &block.eth_block.transactions[0],
+        &block.geth_traces[0],
+    )
+    .unwrap();
+
+    let coinbase = *MOCK_COINBASE;
+    assert_eq!(
+        access_trace,
+        vec![
+            Access::new(None, 0, false, WRITE, Account { address: *ADDR_A }),
+            Access::new(None, 1, false, WRITE, Account { address: coinbase }),
+            Access::new(None, 1, false, READ, Code { address: coinbase }),
+            Access::new(Some(7), 2, true, WRITE, Account { address: coinbase }),
+            Access::new(Some(7), 2, true, WRITE, Account { address: *ADDR_B }),
+            Access::new(Some(7), 2, true, READ, Code { address: *ADDR_B }),
+            Access::new(
+                Some(10),
+                2,
+                true,
+                WRITE,
+                Storage {
+                    address: *ADDR_B,
+                    key: Word::from(2),
+                }
+            ),
+            Access::new(
+                Some(16),
+                1,
+                false,
+                WRITE,
+                Storage {
+                    address: coinbase,
+                    key: Word::from(4),
+                }
+            ),
+        ]
+    );
+
+    assert_eq!(
+        AccessSet::persisted_writes(access_trace),
+        AccessSet {
+            state: HashMap::from_iter([
+                (*ADDR_A, HashSet::new()),
+                (coinbase, HashSet::from_iter([Word::from(4)])),
+            ]),
+            code: HashSet::new(),
+        }
+    )
+}
+
+/// Two consecutive blocks, each one with a transfer from a different account.
+fn consecutive_blocks() -> Vec<GethData> {
+    let parent_hash = Hash::from_low_u64_be(0xcafe);
+    (0..2u64)
+        .map(|index| {
+            TestContext::<3, 1>::new(
+                None,
+                |accs| {
+                    for (acc, address) in accs.iter_mut().zip(MOCK_ACCOUNTS.iter()) {
+                        acc.address(*address).balance(eth(10));
+                    }
+                },
+                |mut txs, accs| {
+                    txs[0]
+                        .from(accs[1 + index as usize].address)
+                        .to(accs[0].address)
+                        .value(eth(1));
+                },
+                |block, _tx| {
+                    if index == 0 {
+                        block.number(0xcafe).hash(parent_hash)
+                    } else {
+                        block.number(0xcaff).parent_hash(parent_hash)
+                    }
+                },
+            )
+            .unwrap()
+            .into()
+        })
+        .collect()
+}
+
+#[test]
+fn gen_inputs_range_counter_modes() {
+    for mode in [CounterMode::PerBlock, CounterMode::Continuous] {
+        let geth_blocks = consecutive_blocks();
+        let eth_blocks = geth_blocks
+            .iter()
+            .map(|block| block.eth_block.clone())
+            .collect_vec();
+        let geth_traces = geth_blocks
+            .iter()
+            .map(|block| block.geth_traces.clone())
+            .collect_vec();
+        let first = crate::mock::BlockData::new_from_geth_data(geth_blocks[0].clone());
+        let (blocks, _, _) = gen_inputs_range_from_state(
+            first.chain_id,
+            first.history_hashes.clone(),
+            first.sdb,
+            first.code_db,
+            &eth_blocks,
+            &geth_traces,
+            mode,
+        )
+        .unwrap();
+        assert_eq!(blocks.len(), 2);
+
+        // The history of the second block ends with the first block.
+        assert_eq!(blocks[0].history_hashes, first.history_hashes);
+        let mut history_hashes = first.history_hashes.clone();
+        history_hashes.push(eth_blocks[1].parent_hash.to_word());
+        assert_eq!(blocks[1].history_hashes, history_hashes);
+
+        let end_tx = blocks[0].txs[0].steps().last().unwrap();
+        let begin_tx = &blocks[1].txs[0].steps()[0];
+        assert_eq!(end_tx.exec_state, ExecState::EndTx);
+        assert_eq!(blocks[0].tx_id_offset, 0);
+        // The cumulative gas used restarts in every block, so the first tx of
+        // the second block doesn't read the receipt of the previous tx.
+        assert!(blocks[1]
+            .container
+            .tx_receipt
+            .iter()
+            .all(|op| op.rw().is_write()));
+        match mode {
+            CounterMode::PerBlock => {
+                assert_eq!(blocks[1].tx_id_offset, 0);
+                assert_eq!(begin_tx.rwc.0, 1);
+            }
+            CounterMode::Continuous => {
+                assert_eq!(blocks[1].tx_id_offset, 1);
+                // The EndTx of the first block reads the id of the next tx,
+                // and the RW counter of the second block continues after it.
+                assert_eq!(
+                    begin_tx.rwc.0,
+                    end_tx.rwc.0 + end_tx.bus_mapping_instance.len()
+                );
+            }
+        }
+    }
+}
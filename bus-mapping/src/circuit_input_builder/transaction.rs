@@ -3,8 +3,11 @@
 use std::collections::BTreeMap;
 
 use eth_types::evm_types::Memory;
+use eth_types::evm_types::OpcodeId;
 use eth_types::Signature;
-use eth_types::{geth_types, Address, GethExecTrace, Word};
+use eth_types::{
+    geth_types, AccessList, Address, GethCallTrace, GethExecStep, GethExecTrace, Word,
+};
 use ethers_core::utils::get_contract_address;
 
 use crate::{
@@ -14,11 +17,57 @@ use crate::{
 
 use super::{call::ReversionGroup, Call, CallContext, CallKind, CodeSource, ExecStep};
 
+/// Information about the steps of a geth trace needed before handling them,
+/// collected one step at a time.
+#[derive(Debug, Default)]
+pub struct TxTraceSummary {
+    /// Index, depth and opcode of the previous step.
+    prev_step: Option<(usize, u16, OpcodeId)>,
+    /// Indices of the steps of the calls which haven't returned yet.
+    call_indices: Vec<usize>,
+    /// Call `is_success` indexed by the index of the step of the call.
+    call_is_success: BTreeMap<usize, bool>,
+    /// Whether the memory was captured in any step.
+    memory_captured: bool,
+}
+
+impl TxTraceSummary {
+    /// Add the next step of the geth trace.  The `is_success` of each call is
+    /// at the top of stack at the step after the call.
+    pub fn push_step(&mut self, geth_step: &GethExecStep) -> Result<(), Error> {
+        let index = match self.prev_step {
+            Some((prev_index, prev_depth, prev_op)) => {
+                // Dive into call
+                if prev_depth + 1 == geth_step.depth {
+                    self.call_indices.push(prev_index);
+                // Emerge from call
+                } else if prev_depth - 1 == geth_step.depth {
+                    let is_success = !geth_step.stack.last()?.is_zero();
+                    let call_index = self.call_indices.pop().ok_or(Error::InvalidGethExecTrace(
+                        "Call returned without being called",
+                    ))?;
+                    self.call_is_success.insert(call_index, is_success);
+                // Callee with empty code
+                } else if CallKind::try_from(prev_op).is_ok() {
+                    let is_success = !geth_step.stack.last()?.is_zero();
+                    self.call_is_success.insert(prev_index, is_success);
+                }
+                prev_index + 1
+            }
+            None => 0,
+        };
+        self.prev_step = Some((index, geth_step.depth, geth_step.op));
+        self.memory_captured |= !geth_step.memory.is_empty();
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 /// Context of a [`Transaction`] which can mutate in an [`ExecStep`].
 pub struct TransactionContext {
-    /// Unique identifier of transaction of the block. The value is `index + 1`.
-    id: usize,
+    /// Unique identifier of transaction of the block. The value is `index + 1`,
+    /// plus the `tx_id_offset` of the block.
+    pub(crate) id: usize,
     /// The index of logs made in the transaction.
     pub(crate) log_id: usize,
     /// Identifier if this transaction is last one of the block or not.
@@ -33,6 +82,8 @@ pub struct TransactionContext {
     /// in the inner most revert (which we track with the last element in
     /// the reversion groups stack), and skip it in the outer revert.
     pub(crate) reversion_groups: Vec<ReversionGroup>,
+    /// Whether the memory was captured in the steps of the geth trace.
+    pub(crate) memory_captured: bool,
 }
 
 impl TransactionContext {
@@ -42,32 +93,25 @@ impl TransactionContext {
         geth_trace: &GethExecTrace,
         is_last_tx: bool,
     ) -> Result<Self, Error> {
-        // Iterate over geth_trace to inspect and collect each call's is_success, which
-        // is at the top of stack at the step after a call.
-        let call_is_success = {
-            let mut call_is_success_map = BTreeMap::new();
-            let mut call_indices = Vec::new();
-            for (index, geth_step) in geth_trace.struct_logs.iter().enumerate() {
-                if let Some(geth_next_step) = geth_trace.struct_logs.get(index + 1) {
-                    // Dive into call
-                    if geth_step.depth + 1 == geth_next_step.depth {
-                        call_indices.push(index);
-                    // Emerge from call
-                    } else if geth_step.depth - 1 == geth_next_step.depth {
-                        let is_success = !geth_next_step.stack.last()?.is_zero();
-                        call_is_success_map.insert(call_indices.pop().unwrap(), is_success);
-                    // Callee with empty code
-                    } else if CallKind::try_from(geth_step.op).is_ok() {
-                        let is_success = !geth_next_step.stack.last()?.is_zero();
-                        call_is_success_map.insert(index, is_success);
-                    }
-                }
-            }
+        let mut summary = TxTraceSummary::default();
+        for geth_step in geth_trace.struct_logs.iter() {
+            summary.push_step(geth_step)?;
+        }
+        Self::from_summary(eth_tx, geth_trace.failed, summary, is_last_tx)
+    }
 
-            std::iter::once(!geth_trace.failed)
-                .chain(call_is_success_map.into_values())
-                .collect()
-        };
+    /// Create a new Self from the [`TxTraceSummary`] of the steps of the geth
+    /// trace, so that the steps don't need to be kept in memory.  `failed`
+    /// is the `failed` field of the geth trace.
+    pub fn from_summary(
+        eth_tx: &eth_types::Transaction,
+        failed: bool,
+        summary: TxTraceSummary,
+        is_last_tx: bool,
+    ) -> Result<Self, Error> {
+        let call_is_success = std::iter::once(!failed)
+            .chain(summary.call_is_success.into_values())
+            .collect();
 
         let mut tx_ctx = Self {
             id: eth_tx
@@ -80,6 +124,7 @@ impl TransactionContext {
             call_is_success,
             calls: Vec::new(),
             reversion_groups: Vec::new(),
+            memory_captured: summary.memory_captured,
         };
         tx_ctx.push_call_ctx(0, eth_tx.input.to_vec());
 
@@ -181,12 +226,20 @@ impl TransactionContext {
 #[derive(Debug, Clone)]
 /// Result of the parsing of an Ethereum Transaction.
 pub struct Transaction {
+    /// EIP-2718 transaction type
+    pub transaction_type: u64,
+    /// Number of the block including the transaction
+    pub block_number: u64,
     /// Nonce
     pub nonce: u64,
     /// Gas
     pub gas: u64,
-    /// Gas price
+    /// Effective gas price
     pub gas_price: Word,
+    /// Gas fee cap.  Equal to the gas price for legacy transactions.
+    pub gas_fee_cap: Word,
+    /// Gas tip cap.  Equal to the gas price for legacy transactions.
+    pub gas_tip_cap: Word,
     /// From / Caller Address
     pub from: Address,
     /// To / Callee Address
@@ -195,6 +248,8 @@ pub struct Transaction {
     pub value: Word,
     /// Input / Call Data
     pub input: Vec<u8>,
+    /// EIP-2930 access list.  Empty for legacy transactions.
+    pub access_list: AccessList,
     /// Signature
     pub signature: Signature,
     /// Calls made in the transaction
@@ -206,13 +261,18 @@ pub struct Transaction {
 impl From<&Transaction> for geth_types::Transaction {
     fn from(tx: &Transaction) -> geth_types::Transaction {
         geth_types::Transaction {
+            transaction_type: tx.transaction_type,
+            block_number: tx.block_number,
             from: tx.from,
-            to: Some(tx.to),
+            to: (!tx.is_create()).then_some(tx.to),
             nonce: Word::from(tx.nonce),
             gas_limit: Word::from(tx.gas),
             value: tx.value,
             gas_price: tx.gas_price,
+            gas_fee_cap: tx.gas_fee_cap,
+            gas_tip_cap: tx.gas_tip_cap,
             call_data: tx.input.clone().into(),
+            access_list: Some(tx.access_list.clone()),
             v: tx.signature.v,
             r: tx.signature.r,
             s: tx.signature.s,
@@ -222,12 +282,16 @@ impl From<&Transaction> for geth_types::Transaction {
 }
 
 impl Transaction {
-    /// Create a new Self.
+    /// Create a new Self, included in the block `block_number`.  The
+    /// `base_fee` of the block is used to compute the effective gas price of
+    /// dynamic fee transactions.
     pub fn new(
         call_id: usize,
         sdb: &StateDB,
         code_db: &mut CodeDB,
         eth_tx: &eth_types::Transaction,
+        block_number: u64,
+        base_fee: Word,
         is_success: bool,
     ) -> Result<Self, Error> {
         let (found, _) = sdb.get_account(&eth_tx.from);
@@ -276,14 +340,22 @@ impl Transaction {
             }
         };
 
+        let gas_price = geth_types::effective_gas_price(eth_tx, base_fee);
+        let geth_tx = geth_types::Transaction::from(eth_tx);
+
         Ok(Self {
+            transaction_type: geth_tx.transaction_type,
+            block_number,
             nonce: eth_tx.nonce.as_u64(),
             gas: eth_tx.gas.as_u64(),
-            gas_price: eth_tx.gas_price.unwrap_or_default(),
+            gas_price,
+            gas_fee_cap: geth_tx.gas_fee_cap,
+            gas_tip_cap: geth_tx.gas_tip_cap,
             from: eth_tx.from,
             to: eth_tx.to.unwrap_or_default(),
             value: eth_tx.value,
             input: eth_tx.input.to_vec(),
+            access_list: geth_tx.access_list.unwrap_or_default(),
             calls: vec![call],
             steps: Vec::new(),
             signature: Signature {
@@ -325,6 +397,45 @@ impl Transaction {
         self.calls.push(call);
     }
 
+    /// Check that the calls of this transaction match the call frames returned
+    /// by the geth `callTracer` for it.  The calls which fail before entering
+    /// the callee, such as when the balance of the caller is insufficient,
+    /// have no call frame, and the `SELFDESTRUCT` frames have no call, so both
+    /// are skipped.
+    pub fn check_call_trace(&self, call_trace: &GethCallTrace) -> Result<(), Error> {
+        let mut calls = self.calls.iter();
+        let frames = call_trace.flatten();
+        for (index, frame) in frames.iter().enumerate() {
+            if frame.call_type == OpcodeId::SELFDESTRUCT {
+                continue;
+            }
+            let kind = CallKind::try_from(frame.call_type)?;
+            loop {
+                let call = calls.next().ok_or(Error::CallTraceMismatch(index))?;
+                let (from, to) = match call.kind {
+                    CallKind::DelegateCall => (call.address, call.code_address()),
+                    CallKind::Create | CallKind::Create2 => {
+                        (call.caller_address, Some(call.address))
+                    }
+                    _ => (call.caller_address, call.code_address()),
+                };
+                if call.kind == kind
+                    && (from, to) == (frame.from, frame.to)
+                    && call.is_success == frame.error.is_none()
+                {
+                    break;
+                }
+                if call.is_success {
+                    return Err(Error::CallTraceMismatch(index));
+                }
+            }
+        }
+        if calls.any(|call| call.is_success) {
+            return Err(Error::CallTraceMismatch(frames.len()));
+        }
+        Ok(())
+    }
+
     /// Return last step in this transaction.
     pub fn last_step(&self) -> &ExecStep {
         if self.steps().is_empty() {
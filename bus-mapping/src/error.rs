@@ -1,7 +1,10 @@
 //! Error module for the bus-mapping crate
 
 use core::fmt::{Display, Formatter, Result as FmtResult};
-use eth_types::{evm_types::OpcodeId, Address, GethExecStep, Word, H256};
+use eth_types::{
+    evm_types::{precompiles::PrecompileError, OpcodeId, ProgramCounter},
+    Address, GethExecStep, Word, H256,
+};
 use ethers_providers::ProviderError;
 use std::error::Error as StdError;
 
@@ -17,6 +20,8 @@ pub enum Error {
     SerdeError(serde_json::error::Error),
     /// JSON-RPC related error.
     JSONRpcError(ProviderError),
+    /// Error while reading execution traces.
+    IoError(std::io::Error),
     /// OpcodeId is not a call type.
     OpcodeIdNotCallType,
     /// Account not found in the StateDB
@@ -36,10 +41,41 @@ pub enum Error {
     InvalidGethExecStep(&'static str, GethExecStep),
     /// Eth type related error.
     EthTypeError(eth_types::Error),
+    /// The state root computed after a block doesn't match the one of its
+    /// header: (header state root, computed state root)
+    StateRootMismatch(H256, H256),
     /// EVM Execution error
     ExecutionError(ExecError),
+    /// A precompiled contract called successfully couldn't be run to
+    /// reconstruct its output.
+    PrecompileError(PrecompileError),
+    /// The calls of a transaction don't match the call frames returned by the
+    /// geth `callTracer`: index of the first call frame which doesn't match
+    CallTraceMismatch(usize),
     /// Internal Code error
     InternalError(&'static str),
+    /// Error while generating the circuit inputs of a transaction, outside of
+    /// the steps of its execution trace.
+    TxError {
+        /// Index of the transaction in the block
+        tx_index: usize,
+        /// Underlying error
+        error: Box<Error>,
+    },
+    /// Error while generating the circuit inputs of a step of the execution
+    /// trace of a transaction.
+    ExecStepError {
+        /// Index of the transaction in the block
+        tx_index: usize,
+        /// Index of the step in the execution trace of the transaction
+        step_index: usize,
+        /// Program counter of the step
+        pc: ProgramCounter,
+        /// Opcode of the step
+        op: OpcodeId,
+        /// Underlying error
+        error: Box<Error>,
+    },
 }
 
 impl From<eth_types::Error> for Error {
@@ -54,6 +90,12 @@ impl From<ProviderError> for Error {
     }
 }
 
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::IoError(err)
+    }
+}
+
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         write!(f, "{:?}", self)
@@ -142,7 +184,7 @@ pub enum ExecError {
 }
 
 // TODO: Move to impl block.
-pub(crate) fn get_step_reported_error(op: &OpcodeId, error: &str) -> ExecError {
+pub(crate) fn get_step_reported_error(op: &OpcodeId, error: &str) -> Option<ExecError> {
     if error == GETH_ERR_OUT_OF_GAS || error == GETH_ERR_GAS_UINT_OVERFLOW {
         // NOTE: We report a GasUintOverflow error as an OutOfGas error
         let oog_err = match op {
@@ -174,12 +216,12 @@ pub(crate) fn get_step_reported_error(op: &OpcodeId, error: &str) -> ExecError {
             OpcodeId::SELFDESTRUCT => OogError::SelfDestruct,
             _ => OogError::Constant,
         };
-        ExecError::OutOfGas(oog_err)
+        Some(ExecError::OutOfGas(oog_err))
     } else if error.starts_with(GETH_ERR_STACK_OVERFLOW) {
-        ExecError::StackOverflow
+        Some(ExecError::StackOverflow)
     } else if error.starts_with(GETH_ERR_STACK_UNDERFLOW) {
-        ExecError::StackUnderflow
+        Some(ExecError::StackUnderflow)
     } else {
-        panic!("Unknown GethExecStep.error: {}", error);
+        None
     }
 }
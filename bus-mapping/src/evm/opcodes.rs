@@ -1,6 +1,6 @@
 //! Definition of each opcode of the EVM.
 use crate::{
-    circuit_input_builder::{CircuitInputStateRef, ExecStep},
+    circuit_input_builder::{get_memory_offset_length, CircuitInputStateRef, ExecStep},
     evm::OpcodeId,
     operation::{
         AccountField, CallContextField, TxAccessListAccountOp, TxReceiptField, TxRefundOp, RW,
@@ -20,6 +20,7 @@ pub use self::sha3::sha3_tests::{gen_sha3_code, MemoryKind};
 
 mod address;
 mod balance;
+mod blockctx;
 mod call;
 mod calldatacopy;
 mod calldataload;
@@ -56,6 +57,7 @@ mod memory_expansion_test;
 use self::sha3::Sha3;
 use address::Address;
 use balance::Balance;
+use blockctx::BlockCtx;
 use call::Call;
 use calldatacopy::Calldatacopy;
 use calldataload::Calldataload;
@@ -164,15 +166,15 @@ fn fn_gen_associated_ops(opcode_id: &OpcodeId) -> FnGenAssociatedOps {
         OpcodeId::RETURNDATASIZE => StackOnlyOpcode::<0, 1>::gen_associated_ops,
         OpcodeId::RETURNDATACOPY => Returndatacopy::gen_associated_ops,
         OpcodeId::EXTCODEHASH => Extcodehash::gen_associated_ops,
-        OpcodeId::BLOCKHASH => StackOnlyOpcode::<1, 1>::gen_associated_ops,
-        OpcodeId::COINBASE => StackOnlyOpcode::<0, 1>::gen_associated_ops,
-        OpcodeId::TIMESTAMP => StackOnlyOpcode::<0, 1>::gen_associated_ops,
-        OpcodeId::NUMBER => StackOnlyOpcode::<0, 1>::gen_associated_ops,
-        OpcodeId::DIFFICULTY => StackOnlyOpcode::<0, 1>::gen_associated_ops,
-        OpcodeId::GASLIMIT => StackOnlyOpcode::<0, 1>::gen_associated_ops,
+        OpcodeId::BLOCKHASH => BlockCtx::<1>::gen_associated_ops,
+        OpcodeId::COINBASE => BlockCtx::<0>::gen_associated_ops,
+        OpcodeId::TIMESTAMP => BlockCtx::<0>::gen_associated_ops,
+        OpcodeId::NUMBER => BlockCtx::<0>::gen_associated_ops,
+        OpcodeId::DIFFICULTY => BlockCtx::<0>::gen_associated_ops,
+        OpcodeId::GASLIMIT => BlockCtx::<0>::gen_associated_ops,
         OpcodeId::CHAINID => StackOnlyOpcode::<0, 1>::gen_associated_ops,
         OpcodeId::SELFBALANCE => Selfbalance::gen_associated_ops,
-        OpcodeId::BASEFEE => StackOnlyOpcode::<0, 1>::gen_associated_ops,
+        OpcodeId::BASEFEE => BlockCtx::<0>::gen_associated_ops,
         OpcodeId::POP => StackOnlyOpcode::<1, 0>::gen_associated_ops,
         OpcodeId::MLOAD => Mload::gen_associated_ops,
         OpcodeId::MSTORE => Mstore::<false>::gen_associated_ops,
@@ -259,14 +261,16 @@ pub fn gen_associated_ops(
 ) -> Result<Vec<ExecStep>, Error> {
     let fn_gen_associated_ops = fn_gen_associated_ops(opcode_id);
 
-    let memory_enabled = !geth_steps.iter().all(|s| s.memory.is_empty());
-    if memory_enabled {
-        assert_eq!(
-            &state.call_ctx()?.memory,
-            &geth_steps[0].memory,
-            "last step of {:?} goes wrong",
-            opcode_id
-        );
+    // The memory is reconstructed from the steps, so the memory captured by
+    // geth is only used to check the reconstruction.
+    if state.block.check_memory
+        && state.tx_ctx.memory_captured
+        && state.call_ctx()?.memory != geth_steps[0].memory
+    {
+        return Err(Error::InvalidGethExecStep(
+            "reconstructed memory differs from the memory of the step",
+            geth_steps[0].clone(),
+        ));
     }
 
     // check if have error
@@ -277,7 +281,7 @@ pub fn gen_associated_ops(
     } else {
         None
     };
-    if let Some(exec_error) = state.get_step_err(geth_step, next_step).unwrap() {
+    if let Some(exec_error) = state.get_step_err(geth_step, next_step)? {
         log::warn!(
             "geth error {:?} occurred in  {:?}",
             exec_error,
@@ -288,6 +292,8 @@ pub fn gen_associated_ops(
         // for `oog_or_stack_error` error message will be returned by geth_step error
         // field, when this kind of error happens, no more proceeding
         if geth_step.op.is_call_or_create() && !exec_step.oog_or_stack_error() {
+            // The memory is expanded before the call or create fails.
+            call_or_create_expand_memory(state, geth_step)?;
             let call = state.parse_call(geth_step)?;
             // Switch to callee's call context
             state.push_call(call);
@@ -348,11 +354,16 @@ pub fn gen_begin_tx_ops(state: &mut CircuitInputStateRef) -> Result<ExecStep, Er
         .input
         .iter()
         .fold(0, |acc, byte| acc + if *byte == 0 { 4 } else { 16 });
+    let access_list_gas_cost = state.tx.access_list.0.iter().fold(0, |acc, item| {
+        acc + GasCost::ACCESS_LIST_PER_ADDRESS.as_u64()
+            + GasCost::ACCESS_LIST_PER_STORAGE_KEY.as_u64() * item.storage_keys.len() as u64
+    });
     let intrinsic_gas_cost = if state.tx.is_create() {
         GasCost::CREATION_TX.as_u64()
     } else {
         GasCost::TX.as_u64()
-    } + call_data_gas_cost;
+    } + call_data_gas_cost
+        + access_list_gas_cost;
     exec_step.gas_cost = GasCost(intrinsic_gas_cost);
 
     // Transfer with fee
@@ -377,12 +388,10 @@ pub fn gen_begin_tx_ops(state: &mut CircuitInputStateRef) -> Result<ExecStep, Er
         // 1. Creation transaction.
         (true, _, _) => {
             warn!("Creation transaction is left unimplemented");
-            Ok(exec_step)
         }
         // 2. Call to precompiled.
         (_, true, _) => {
             warn!("Call to precompiled is left unimplemented");
-            Ok(exec_step)
         }
         (_, _, is_empty_code_hash) => {
             state.account_read(
@@ -396,40 +405,67 @@ pub fn gen_begin_tx_ops(state: &mut CircuitInputStateRef) -> Result<ExecStep, Er
             // 3. Call to account with empty code.
             if is_empty_code_hash {
                 warn!("Call to account with empty code is left unimplemented");
-                return Ok(exec_step);
-            }
-
-            // 4. Call to account with non-empty code.
-            for (field, value) in [
-                (CallContextField::Depth, call.depth.into()),
-                (
-                    CallContextField::CallerAddress,
-                    call.caller_address.to_word(),
-                ),
-                (CallContextField::CalleeAddress, call.address.to_word()),
-                (
-                    CallContextField::CallDataOffset,
-                    call.call_data_offset.into(),
-                ),
-                (
-                    CallContextField::CallDataLength,
-                    call.call_data_length.into(),
-                ),
-                (CallContextField::Value, call.value),
-                (CallContextField::IsStatic, (call.is_static as usize).into()),
-                (CallContextField::LastCalleeId, 0.into()),
-                (CallContextField::LastCalleeReturnDataOffset, 0.into()),
-                (CallContextField::LastCalleeReturnDataLength, 0.into()),
-                (CallContextField::IsRoot, 1.into()),
-                (CallContextField::IsCreate, 0.into()),
-                (CallContextField::CodeHash, code_hash.to_word()),
-            ] {
-                state.call_context_write(&mut exec_step, call.call_id, field, value);
+            } else {
+                // 4. Call to account with non-empty code.
+                for (field, value) in [
+                    (CallContextField::Depth, call.depth.into()),
+                    (
+                        CallContextField::CallerAddress,
+                        call.caller_address.to_word(),
+                    ),
+                    (CallContextField::CalleeAddress, call.address.to_word()),
+                    (
+                        CallContextField::CallDataOffset,
+                        call.call_data_offset.into(),
+                    ),
+                    (
+                        CallContextField::CallDataLength,
+                        call.call_data_length.into(),
+                    ),
+                    (CallContextField::Value, call.value),
+                    (CallContextField::IsStatic, (call.is_static as usize).into()),
+                    (CallContextField::LastCalleeId, 0.into()),
+                    (CallContextField::LastCalleeReturnDataOffset, 0.into()),
+                    (CallContextField::LastCalleeReturnDataLength, 0.into()),
+                    (CallContextField::IsRoot, 1.into()),
+                    (CallContextField::IsCreate, 0.into()),
+                    (CallContextField::CodeHash, code_hash.to_word()),
+                ] {
+                    state.call_context_write(&mut exec_step, call.call_id, field, value);
+                }
             }
+        }
+    }
 
-            Ok(exec_step)
+    // Add the addresses and storage keys of the EIP-2930 access list into the
+    // access list.  These writes come last so that the number of rw operations
+    // before them doesn't depend on the access list.
+    for item in state.tx.access_list.0.clone() {
+        let is_warm_prev = !state.sdb.add_account_to_access_list(item.address);
+        state.tx_accesslist_account_write(
+            &mut exec_step,
+            state.tx_ctx.id(),
+            item.address,
+            true,
+            is_warm_prev,
+        )?;
+        for key in item.storage_keys {
+            let key = key.to_word();
+            let is_warm_prev = !state
+                .sdb
+                .add_account_storage_to_access_list((item.address, key));
+            state.tx_accesslist_account_storage_write(
+                &mut exec_step,
+                state.tx_ctx.id(),
+                item.address,
+                key,
+                true,
+                is_warm_prev,
+            )?;
         }
     }
+
+    Ok(exec_step)
 }
 
 pub fn gen_end_tx_ops(state: &mut CircuitInputStateRef) -> Result<ExecStep, Error> {
@@ -512,7 +548,8 @@ pub fn gen_end_tx_ops(state: &mut CircuitInputStateRef) -> Result<ExecStep, Erro
         log_id as u64,
     )?;
 
-    if state.tx_ctx.id() > 1 {
+    // The cumulative gas used restarts from zero at the first tx of each block
+    if state.tx_ctx.id() > state.block.tx_id_offset + 1 {
         // query pre tx cumulative gas
         state.tx_receipt_read(
             &mut exec_step,
@@ -542,6 +579,33 @@ pub fn gen_end_tx_ops(state: &mut CircuitInputStateRef) -> Result<ExecStep, Erro
     Ok(exec_step)
 }
 
+/// Expand the memory of the current call to the arguments and the return
+/// data of a *CALL*, or to the init code of a CREATE*, as geth does before
+/// the step is executed.
+fn call_or_create_expand_memory(
+    state: &mut CircuitInputStateRef,
+    geth_step: &GethExecStep,
+) -> Result<(), Error> {
+    // CALL        (gas, addr, value, argsOffset, argsLength, retOffset, retLength)
+    // CALLCODE    (gas, addr, value, argsOffset, argsLength, retOffset, retLength)
+    // DELEGATECALL(gas, addr,        argsOffset, argsLength, retOffset, retLength)
+    // STATICCALL  (gas, addr,        argsOffset, argsLength, retOffset, retLength)
+    // CREATE      (value, offset, length)
+    // CREATE2     (value, offset, length, salt)
+    let (args_pos, ret_pos) = match geth_step.op {
+        OpcodeId::CALL | OpcodeId::CALLCODE => (3, Some(5)),
+        OpcodeId::DELEGATECALL | OpcodeId::STATICCALL => (2, Some(4)),
+        OpcodeId::CREATE | OpcodeId::CREATE2 => (1, None),
+        _ => return Err(Error::OpcodeIdNotCallType),
+    };
+    let (args_offset, args_length) = get_memory_offset_length(geth_step, args_pos)?;
+    let (ret_offset, ret_length) = match ret_pos {
+        Some(ret_pos) => get_memory_offset_length(geth_step, ret_pos)?,
+        None => (0, 0),
+    };
+    state.call_expand_memory(args_offset, args_length, ret_offset, ret_length)
+}
+
 #[derive(Debug, Copy, Clone)]
 struct DummyCall;
 
@@ -561,29 +625,11 @@ fn dummy_gen_call_ops(
     let geth_step = &geth_steps[0];
     let mut exec_step = state.new_step(geth_step)?;
 
-    let (args_offset, args_length, ret_offset, ret_length) = {
-        // CALLCODE    (gas, addr, value, argsOffset, argsLength, retOffset, retLength)
-        // DELEGATECALL(gas, addr,        argsOffset, argsLength, retOffset, retLength)
-        // STATICCALL  (gas, addr,        argsOffset, argsLength, retOffset, retLength)
-        let pos = match geth_step.op {
-            OpcodeId::CALLCODE => (3, 4, 5, 6),
-            OpcodeId::DELEGATECALL | OpcodeId::STATICCALL => (2, 3, 4, 5),
-            _ => unreachable!("opcode is not of call type"),
-        };
-        (
-            geth_step.stack.nth_last(pos.0)?.as_usize(),
-            geth_step.stack.nth_last(pos.1)?.as_usize(),
-            geth_step.stack.nth_last(pos.2)?.as_usize(),
-            geth_step.stack.nth_last(pos.3)?.as_usize(),
-        )
-    };
-    state.call_expand_memory(args_offset, args_length, ret_offset, ret_length)?;
+    call_or_create_expand_memory(state, geth_step)?;
 
     let tx_id = state.tx_ctx.id();
     let call = state.parse_call(geth_step)?;
-
-    let (_, account) = state.sdb.get_account(&call.address);
-    let callee_code_hash = account.code_hash;
+    let code_address = call.code_address().ok_or(Error::OpcodeIdNotCallType)?;
 
     let is_warm = state.sdb.check_account_in_access_list(&call.address);
     state.push_op_reversible(
@@ -600,11 +646,14 @@ fn dummy_gen_call_ops(
     state.push_call(call.clone());
 
     match (
-        state.is_precompiled(&call.address),
-        callee_code_hash.to_fixed_bytes() == *EMPTY_HASH,
+        state.is_precompiled(&code_address),
+        call.code_hash.to_fixed_bytes() == *EMPTY_HASH,
     ) {
         // 1. Call to precompiled.
-        (true, _) => Ok(vec![exec_step]),
+        (true, _) => {
+            state.handle_precompile_return(geth_step)?;
+            Ok(vec![exec_step])
+        }
         // 2. Call to account with empty code.
         (_, true) => {
             state.handle_return(geth_step)?;
@@ -0,0 +1,51 @@
+use super::Opcode;
+use crate::circuit_input_builder::{CircuitInputStateRef, ExecStep};
+use crate::operation::CallContextField;
+use crate::Error;
+use eth_types::GethExecStep;
+
+/// Placeholder structure used to implement [`Opcode`] trait over it
+/// corresponding to the opcodes reading a field of the block of the
+/// transaction: take N words and return one.  The block is found from the
+/// number of the block of the transaction, so the TxId is read first.
+/// - N = 0: COINBASE, TIMESTAMP, NUMBER, DIFFICULTY, GASLIMIT, BASEFEE
+/// - N = 1: BLOCKHASH
+#[derive(Debug, Copy, Clone)]
+pub(crate) struct BlockCtx<const N_POP: usize>;
+
+impl<const N_POP: usize> Opcode for BlockCtx<N_POP> {
+    fn gen_associated_ops(
+        state: &mut CircuitInputStateRef,
+        geth_steps: &[GethExecStep],
+    ) -> Result<Vec<ExecStep>, Error> {
+        let geth_step = &geth_steps[0];
+        let mut exec_step = state.new_step(geth_step)?;
+        let tx_id = state.tx_ctx.id();
+
+        // CallContext read of the TxId
+        state.call_context_read(
+            &mut exec_step,
+            state.call()?.call_id,
+            CallContextField::TxId,
+            tx_id.into(),
+        );
+
+        // N_POP stack reads
+        for i in 0..N_POP {
+            state.stack_read(
+                &mut exec_step,
+                geth_step.stack.nth_last_filled(i),
+                geth_step.stack.nth_last(i)?,
+            )?;
+        }
+
+        // Stack write of the block field
+        state.stack_write(
+            &mut exec_step,
+            geth_steps[1].stack.last_filled(),
+            geth_steps[1].stack.last()?,
+        )?;
+
+        Ok(vec![exec_step])
+    }
+}
@@ -1,6 +1,6 @@
 use super::Opcode;
 use crate::{
-    circuit_input_builder::{CircuitInputStateRef, ExecStep},
+    circuit_input_builder::{get_memory_offset_length, CircuitInputStateRef, ExecStep},
     operation::{AccountField, CallContextField, TxAccessListAccountOp, RW},
     Error,
 };
@@ -27,10 +27,8 @@ impl Opcode for Call {
         let geth_step = &geth_steps[0];
         let mut exec_step = state.new_step(geth_step)?;
 
-        let args_offset = geth_step.stack.nth_last(3)?.as_usize();
-        let args_length = geth_step.stack.nth_last(4)?.as_usize();
-        let ret_offset = geth_step.stack.nth_last(5)?.as_usize();
-        let ret_length = geth_step.stack.nth_last(6)?.as_usize();
+        let (args_offset, args_length) = get_memory_offset_length(geth_step, 3)?;
+        let (ret_offset, ret_length) = get_memory_offset_length(geth_step, 5)?;
 
         // we need to keep the memory until parse_call complete
         state.call_expand_memory(args_offset, args_length, ret_offset, ret_length)?;
@@ -160,6 +158,7 @@ impl Opcode for Call {
             // 1. Call to precompiled.
             (true, _) => {
                 warn!("Call to precompiled is left unimplemented");
+                state.handle_precompile_return(geth_step)?;
                 Ok(vec![exec_step])
             }
             // 2. Call to account with empty code.
@@ -241,3 +240,69 @@ impl Opcode for Call {
         }
     }
 }
+
+#[cfg(test)]
+mod call_tests {
+    use crate::mock::BlockData;
+    use eth_types::geth_types::GethData;
+    use eth_types::{bytecode, word, Address, Word};
+    use mock::test_ctx::LoggerConfig;
+    use mock::TestContext;
+
+    #[test]
+    fn test_precompile_return_data() {
+        // The output of the identity precompile is written to the memory of
+        // the caller, which is checked against the memory captured by geth.
+        let code = bytecode! {
+            PUSH32(word!("0x0123456789abcdef0123456789abcdef0123456789abcdef0123456789abcdef"))
+            PUSH1(0)
+            MSTORE
+            // CALL(gas, addr, value, argsOffset, argsLength, retOffset, retLength)
+            PUSH1(0x20)
+            PUSH1(0x20)
+            PUSH1(0x20)
+            PUSH1(0)
+            PUSH1(0)
+            PUSH1(4)
+            PUSH2(0xFFFF)
+            CALL
+            // STATICCALL(gas, addr, argsOffset, argsLength, retOffset, retLength)
+            PUSH1(0x10)
+            PUSH1(0x40)
+            PUSH1(0x20)
+            PUSH1(0)
+            PUSH1(4)
+            PUSH2(0xFFFF)
+            STATICCALL
+            // The return data is the whole output.
+            PUSH1(0x20)
+            PUSH1(0)
+            PUSH1(0x60)
+            RETURNDATACOPY
+            STOP
+        };
+        let block: GethData = TestContext::<3, 1>::new_with_logger_config(
+            None,
+            |accs| {
+                accs[0].address(mock::MOCK_ACCOUNTS[0]).code(code);
+                accs[1]
+                    .address(mock::MOCK_ACCOUNTS[1])
+                    .balance(Word::from(1u64 << 30));
+                accs[2].address(Address::from_low_u64_be(4));
+            },
+            |mut txs, accs| {
+                txs[0].to(accs[0].address).from(accs[1].address);
+            },
+            |block, _tx| block,
+            LoggerConfig::enable_memory(),
+        )
+        .unwrap()
+        .into();
+
+        let mut builder = BlockData::new_from_geth_data(block.clone()).new_circuit_input_builder();
+        builder.block.check_memory = true;
+        builder
+            .handle_block(&block.eth_block, &block.geth_traces)
+            .unwrap();
+    }
+}
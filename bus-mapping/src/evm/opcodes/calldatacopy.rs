@@ -1,5 +1,5 @@
 use super::Opcode;
-use crate::circuit_input_builder::{CircuitInputStateRef, ExecStep};
+use crate::circuit_input_builder::{get_copy_offsets_length, CircuitInputStateRef, ExecStep};
 use crate::circuit_input_builder::{CopyDataType, CopyEvent, NumberOrHash};
 use crate::operation::{CallContextField, MemoryOp, RW};
 use crate::Error;
@@ -17,13 +17,11 @@ impl Opcode for Calldatacopy {
         let exec_steps = vec![gen_calldatacopy_step(state, geth_step)?];
 
         // reconstruction
-        let memory_offset = geth_step.stack.nth_last(0)?.as_u64();
-        let data_offset = geth_step.stack.nth_last(1)?.as_u64();
-        let length = geth_step.stack.nth_last(2)?.as_usize();
+        let (memory_offset, data_offset, length) = get_copy_offsets_length(geth_step, 0)?;
         let call_ctx = state.call_ctx_mut()?;
         let memory = &mut call_ctx.memory;
         if length != 0 {
-            let minimal_length = memory_offset as usize + length;
+            let minimal_length = (memory_offset + length) as usize;
             memory.extend_at_least(minimal_length);
 
             let mem_starts = memory_offset as usize;
@@ -143,16 +141,15 @@ fn gen_copy_event(
     geth_step: &GethExecStep,
 ) -> Result<CopyEvent, Error> {
     let rw_counter_start = state.block_ctx.rwc;
-    let memory_offset = geth_step.stack.nth_last(0)?.as_u64();
-    let data_offset = geth_step.stack.nth_last(1)?.as_u64();
-    let length = geth_step.stack.nth_last(2)?.as_u64();
+    let (memory_offset, data_offset, length) = get_copy_offsets_length(geth_step, 0)?;
 
     let call_data_offset = state.call()?.call_data_offset;
     let call_data_length = state.call()?.call_data_length;
-    let (src_addr, src_addr_end) = (
-        call_data_offset + data_offset,
-        call_data_offset + call_data_length,
-    );
+    let src_addr = match call_data_offset.checked_add(data_offset) {
+        Some(src_addr) if src_addr.checked_add(length).is_some() => src_addr,
+        _ => return Err(eth_types::Error::WordToMemAddr.into()),
+    };
+    let src_addr_end = call_data_offset + call_data_length;
 
     let mut exec_step = state.new_step(geth_step)?;
     let copy_steps = gen_copy_steps(
@@ -3,7 +3,7 @@ use crate::{
     operation::{CallContextField, MemoryOp, RW},
     Error,
 };
-use eth_types::{GethExecStep, U256};
+use eth_types::{evm_types::MemoryAddress, GethExecStep, U256};
 
 use super::Opcode;
 
@@ -59,8 +59,12 @@ impl Opcode for Calldataload {
         }
 
         let call = state.call()?.clone();
-        let (src_addr, src_addr_end, caller_id, call_data) = (
-            call.call_data_offset as usize + offset.as_usize(),
+        let src_addr = MemoryAddress::try_from(offset)
+            .ok()
+            .and_then(|offset| (call.call_data_offset as usize).checked_add(offset.0))
+            .filter(|src_addr| src_addr.checked_add(32).is_some())
+            .ok_or(eth_types::Error::WordToMemAddr)?;
+        let (src_addr_end, caller_id, call_data) = (
             call.call_data_offset as usize + call.call_data_length as usize,
             call.caller_id,
             state.call_ctx()?.call_data.to_vec(),
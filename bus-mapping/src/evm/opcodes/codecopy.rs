@@ -1,6 +1,7 @@
 use crate::{
     circuit_input_builder::{
-        CircuitInputStateRef, CopyDataType, CopyEvent, ExecStep, NumberOrHash,
+        get_copy_offsets_length, CircuitInputStateRef, CopyDataType, CopyEvent, ExecStep,
+        NumberOrHash,
     },
     Error,
 };
@@ -21,9 +22,7 @@ impl Opcode for Codecopy {
 
         // reconstruction
 
-        let dest_offset = geth_step.stack.nth_last(0)?.as_u64();
-        let code_offset = geth_step.stack.nth_last(1)?.as_u64();
-        let length = geth_step.stack.nth_last(2)?.as_u64();
+        let (dest_offset, code_offset, length) = get_copy_offsets_length(geth_step, 0)?;
 
         let code_hash = state.call()?.code_hash;
         let code = state.code(code_hash)?;
@@ -104,9 +103,7 @@ fn gen_copy_event(
 ) -> Result<CopyEvent, Error> {
     let rw_counter_start = state.block_ctx.rwc;
 
-    let dst_offset = geth_step.stack.nth_last(0)?.as_u64();
-    let code_offset = geth_step.stack.nth_last(1)?.as_u64();
-    let length = geth_step.stack.nth_last(2)?.as_u64();
+    let (dst_offset, code_offset, length) = get_copy_offsets_length(geth_step, 0)?;
 
     let code_hash = state.call()?.code_hash;
     let bytecode: Bytecode = state.code(code_hash)?.into();
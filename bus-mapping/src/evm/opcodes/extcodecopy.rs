@@ -1,5 +1,5 @@
 use super::Opcode;
-use crate::circuit_input_builder::{CircuitInputStateRef, ExecStep};
+use crate::circuit_input_builder::{get_copy_offsets_length, CircuitInputStateRef, ExecStep};
 use crate::operation::{TxAccessListAccountOp, RW};
 use crate::Error;
 use eth_types::{GethExecStep, ToAddress};
@@ -17,12 +17,12 @@ impl Opcode for Extcodecopy {
 
         // reconstruction
         let address = geth_steps[0].stack.nth_last(0)?.to_address();
-        let dest_offset = geth_steps[0].stack.nth_last(1)?.as_u64();
-        let code_offset = geth_steps[0].stack.nth_last(2)?.as_u64();
-        let length = geth_steps[0].stack.nth_last(3)?.as_u64();
+        let (dest_offset, code_offset, length) = get_copy_offsets_length(geth_step, 1)?;
 
         let (exist, account) = state.sdb.get_account(&address);
-        assert!(exist, "target account does not exist");
+        if !exist {
+            return Err(Error::AccountNotFound(address));
+        }
         let code = state.code(account.code_hash)?;
 
         let call_ctx = state.call_ctx_mut()?;
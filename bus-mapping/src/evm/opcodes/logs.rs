@@ -1,5 +1,7 @@
 use super::Opcode;
-use crate::circuit_input_builder::{CircuitInputStateRef, ExecState, ExecStep};
+use crate::circuit_input_builder::{
+    get_memory_offset_length, CircuitInputStateRef, ExecState, ExecStep,
+};
 use crate::circuit_input_builder::{CopyDataType, CopyEvent, NumberOrHash};
 use crate::operation::{CallContextField, TxLogField};
 use crate::Error;
@@ -24,8 +26,7 @@ impl Opcode for Log {
         }
 
         // reconstruction
-        let offset = geth_step.stack.nth_last(0)?.as_usize();
-        let length = geth_step.stack.nth_last(1)?.as_usize();
+        let (offset, length) = get_memory_offset_length(geth_step, 0)?;
 
         if length != 0 {
             state
@@ -101,7 +102,11 @@ fn gen_log_step(
     // generates topic operation dynamically
     let topic_count = match exec_step.exec_state {
         ExecState::Op(op_id) => (op_id.as_u8() - OpcodeId::LOG0.as_u8()) as usize,
-        _ => panic!("currently only handle successful log state"),
+        _ => {
+            return Err(Error::InternalError(
+                "currently only handle successful log state",
+            ))
+        }
     };
 
     for i in 0..topic_count {
@@ -171,10 +176,9 @@ fn gen_copy_event(
     let rw_counter_start = state.block_ctx.rwc;
 
     assert!(state.call()?.is_persistent, "Error: Call is not persistent");
-    let memory_start = geth_step.stack.nth_last(0)?.as_u64();
-    let msize = geth_step.stack.nth_last(1)?.as_usize();
+    let (memory_start, msize) = get_memory_offset_length(geth_step, 0)?;
 
-    let (src_addr, src_addr_end) = (memory_start, memory_start + msize as u64);
+    let (src_addr, src_addr_end) = (memory_start as u64, (memory_start + msize) as u64);
 
     let steps = gen_copy_steps(state, exec_step, src_addr, msize)?;
 
@@ -1,4 +1,4 @@
-use crate::circuit_input_builder::{CircuitInputStateRef, ExecStep};
+use crate::circuit_input_builder::{get_memory_offset_length, CircuitInputStateRef, ExecStep};
 use crate::evm::Opcode;
 use crate::Error;
 use eth_types::GethExecStep;
@@ -15,11 +15,12 @@ impl Opcode for Return {
         let exec_step = state.new_step(geth_step)?;
 
         let current_call = state.call()?.clone();
-        let offset = geth_step.stack.nth_last(0)?.as_usize();
-        let length = geth_step.stack.nth_last(1)?.as_usize();
+        let (offset, length) = get_memory_offset_length(geth_step, 0)?;
 
-        // can we use ref here?
-        let memory = state.call_ctx()?.memory.clone();
+        let data = state
+            .call_ctx()?
+            .memory
+            .read_chunk(offset.into(), length.into());
         // skip reconstruction for root-level return/revert
         if !current_call.is_root {
             if !current_call.is_create() {
@@ -31,16 +32,18 @@ impl Opcode for Return {
                 // already resized in Call::reconstruct_memory
                 // caller_ctx.memory.extend_at_least(return_offset + length);
                 let copy_len = std::cmp::min(current_call.return_data_length as usize, length);
-                caller_ctx.memory.0[return_offset..return_offset + copy_len]
-                    .copy_from_slice(&memory.0[offset..offset + copy_len]);
-                caller_ctx.return_data.resize(length as usize, 0);
-                caller_ctx.return_data[0..copy_len]
-                    .copy_from_slice(&memory.0[offset..offset + copy_len]);
+                caller_ctx
+                    .memory
+                    .0
+                    .get_mut(return_offset..return_offset + copy_len)
+                    .ok_or(Error::InvalidGethExecStep(
+                        "return data out of caller memory bounds",
+                        geth_step.clone(),
+                    ))?
+                    .copy_from_slice(&data[..copy_len]);
             } else {
                 // dealing with contract creation
-                assert!(offset + length <= memory.0.len());
-                let code = memory.0[offset..offset + length].to_vec();
-                state.code_db.insert(code);
+                state.code_db.insert(data);
             }
         }
 
@@ -53,8 +56,9 @@ impl Opcode for Return {
 mod return_tests {
     use crate::mock::BlockData;
     use eth_types::geth_types::GethData;
-    use eth_types::{bytecode, word};
+    use eth_types::{bytecode, word, Word};
     use mock::test_ctx::helpers::{account_0_code_account_1_no_code, tx_from_1_to_0};
+    use mock::test_ctx::LoggerConfig;
     use mock::TestContext;
 
     #[test]
@@ -99,12 +103,14 @@ mod return_tests {
             CALL
             STOP
         };
-        // Get the execution steps from the external tracer
-        let block: GethData = TestContext::<2, 1>::new(
+        // Get the execution steps from the external tracer, with the memory to
+        // check the memory written to the caller
+        let block: GethData = TestContext::<2, 1>::new_with_logger_config(
             None,
             account_0_code_account_1_no_code(code),
             tx_from_1_to_0,
             |block, _tx| block.number(0xcafeu64),
+            LoggerConfig::enable_memory(),
         )
         .unwrap()
         .into();
@@ -157,7 +163,33 @@ mod return_tests {
             CALL
             STOP
         };
-        // Get the execution steps from the external tracer
+        // Get the execution steps from the external tracer, with the memory to
+        // check the memory written to the caller
+        let block: GethData = TestContext::<2, 1>::new_with_logger_config(
+            None,
+            account_0_code_account_1_no_code(code),
+            tx_from_1_to_0,
+            |block, _tx| block.number(0xcafeu64),
+            LoggerConfig::enable_memory(),
+        )
+        .unwrap()
+        .into();
+
+        let mut builder = BlockData::new_from_geth_data(block.clone()).new_circuit_input_builder();
+        builder
+            .handle_block(&block.eth_block, &block.geth_traces)
+            .unwrap();
+    }
+
+    #[test]
+    fn test_zero_length_large_offset() {
+        // The offset is ignored when the length is zero, even if it doesn't
+        // fit in a memory address.
+        let code = bytecode! {
+            PUSH1(0)
+            PUSH32(Word::MAX)
+            RETURN
+        };
         let block: GethData = TestContext::<2, 1>::new(
             None,
             account_0_code_account_1_no_code(code),
@@ -1,4 +1,4 @@
-use crate::circuit_input_builder::{CircuitInputStateRef, ExecStep};
+use crate::circuit_input_builder::{get_copy_offsets_length, CircuitInputStateRef, ExecStep};
 use crate::evm::Opcode;
 use crate::Error;
 use eth_types::GethExecStep;
@@ -16,29 +16,28 @@ impl Opcode for Returndatacopy {
 
         // reconstruction
         let geth_step = &geth_steps[0];
-        let dest_offset = geth_step.stack.nth_last(0)?;
-        let offset = geth_step.stack.nth_last(1)?;
-        let size = geth_step.stack.nth_last(2)?;
+        let (dest_offset, offset, length) = get_copy_offsets_length(geth_step, 0)?;
+        let (mem_starts, data_starts, length) =
+            (dest_offset as usize, offset as usize, length as usize);
 
         // can we reduce this clone?
         let return_data = state.call_ctx()?.return_data.clone();
 
         let call_ctx = state.call_ctx_mut()?;
         let memory = &mut call_ctx.memory;
-        let length = size.as_usize();
         if length != 0 {
-            let mem_starts = dest_offset.as_usize();
             let mem_ends = mem_starts + length;
-            let data_starts = offset.as_usize();
             let data_ends = data_starts + length;
-            let minimal_length = dest_offset.as_usize() + length;
             if data_ends <= return_data.len() {
-                memory.extend_at_least(minimal_length);
+                memory.extend_at_least(mem_ends);
                 memory[mem_starts..mem_ends].copy_from_slice(&return_data[data_starts..data_ends]);
-            } else {
-                assert_eq!(geth_steps.len(), 1);
+            } else if geth_steps.len() != 1 {
                 // if overflows this opcode would fails current context, so
                 // there is no more steps.
+                return Err(Error::InvalidGethExecStep(
+                    "return data out of bounds without error",
+                    geth_step.clone(),
+                ));
             }
         }
         Ok(vec![exec_step])
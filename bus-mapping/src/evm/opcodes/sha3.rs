@@ -1,10 +1,11 @@
 use crate::{
     circuit_input_builder::{
-        CircuitInputStateRef, CopyDataType, CopyEvent, ExecStep, NumberOrHash,
+        get_memory_offset_length, CircuitInputStateRef, CopyDataType, CopyEvent, ExecStep,
+        NumberOrHash,
     },
     Error,
 };
-use eth_types::{GethExecStep, Word, U256};
+use eth_types::{GethExecStep, Word};
 use ethers_core::utils::keccak256;
 
 use super::Opcode;
@@ -30,17 +31,18 @@ impl Opcode for Sha3 {
         let size = geth_step.stack.nth_last(1)?;
         state.stack_read(&mut exec_step, geth_step.stack.nth_last_filled(1), size)?;
 
-        if size.gt(&U256::zero()) {
+        let (memory_offset, length) = get_memory_offset_length(geth_step, 0)?;
+        if length != 0 {
             state
                 .call_ctx_mut()?
                 .memory
-                .extend_at_least(offset.as_usize() + size.as_usize());
+                .extend_at_least(memory_offset + length);
         }
 
         let memory = state
             .call_ctx()?
             .memory
-            .read_chunk(offset.as_usize().into(), size.as_usize().into());
+            .read_chunk(memory_offset.into(), length.into());
 
         // keccak-256 hash of the given data in memory.
         let sha3 = keccak256(&memory);
@@ -53,18 +55,18 @@ impl Opcode for Sha3 {
 
         // Memory read operations
         let rw_counter_start = state.block_ctx.rwc;
-        let mut steps = Vec::with_capacity(size.as_usize());
+        let mut steps = Vec::with_capacity(length);
         for (i, byte) in memory.iter().enumerate() {
             // Read step
-            state.memory_read(&mut exec_step, (offset.as_usize() + i).into(), *byte)?;
+            state.memory_read(&mut exec_step, (memory_offset + i).into(), *byte)?;
             steps.push((*byte, false));
         }
         state.block.sha3_inputs.push(memory);
 
         let call_id = state.call()?.call_id;
         state.push_copy(CopyEvent {
-            src_addr: offset.as_u64(),
-            src_addr_end: offset.as_u64() + size.as_u64(),
+            src_addr: memory_offset as u64,
+            src_addr_end: (memory_offset + length) as u64,
             src_type: CopyDataType::Memory,
             src_id: NumberOrHash::Number(call_id),
             dst_addr: 0,
@@ -1,14 +1,18 @@
 //! Module which contains all the RPC calls that are needed at any point to
 //! query a Geth node in order to get a Block, Tx or Trace info.
 
+pub mod replay;
+
 use crate::Error;
 use eth_types::{
-    Address, Block, Bytes, EIP1186ProofResponse, GethExecTrace, Hash, ResultGethExecTraces,
-    Transaction, Word, U64,
+    Address, Block, Bytes, EIP1186ProofResponse, GethCallTrace, GethExecTrace, GethPrestateTrace,
+    Hash, ResultGethExecTraces, ResultGethTrace, Transaction, Word, U64,
 };
 pub use ethers_core::types::BlockNumber;
 use ethers_providers::JsonRpcClient;
-use serde::Serialize;
+use serde::{de::DeserializeOwned, Serialize};
+
+pub use replay::ReplayProvider;
 
 /// Serialize a type.
 ///
@@ -47,6 +51,13 @@ impl Default for GethLoggerConfig {
     }
 }
 
+#[derive(Serialize)]
+#[doc(hidden)]
+pub(crate) struct GethTracerConfig {
+    /// name of the built-in tracer
+    tracer: &'static str,
+}
+
 /// Placeholder structure designed to contain the methods that the BusMapping
 /// needs in order to enable Geth queries.
 pub struct GethClient<P: JsonRpcClient>(pub P);
@@ -132,6 +143,42 @@ impl<P: JsonRpcClient> GethClient<P> {
         Ok(resp.0.into_iter().map(|step| step.result).collect())
     }
 
+    /// Calls `debug_traceBlockByNumber` via JSON-RPC with the `prestateTracer`
+    /// returning a [`Vec<GethPrestateTrace>`] with the state accessed by
+    /// each transaction of the block, before its execution.
+    pub async fn trace_block_prestate_by_number(
+        &self,
+        block_num: BlockNumber,
+    ) -> Result<Vec<GethPrestateTrace>, Error> {
+        self.trace_block_with_tracer(block_num, "prestateTracer")
+            .await
+    }
+
+    /// Calls `debug_traceBlockByNumber` via JSON-RPC with the `callTracer`
+    /// returning a [`Vec<GethCallTrace>`] with the tree of calls of each
+    /// transaction of the block.
+    pub async fn trace_block_calls_by_number(
+        &self,
+        block_num: BlockNumber,
+    ) -> Result<Vec<GethCallTrace>, Error> {
+        self.trace_block_with_tracer(block_num, "callTracer").await
+    }
+
+    async fn trace_block_with_tracer<T: DeserializeOwned + Send>(
+        &self,
+        block_num: BlockNumber,
+        tracer: &'static str,
+    ) -> Result<Vec<T>, Error> {
+        let num = serialize(&block_num);
+        let cfg = serialize(&GethTracerConfig { tracer });
+        let resp: Vec<ResultGethTrace<T>> = self
+            .0
+            .request("debug_traceBlockByNumber", [num, cfg])
+            .await
+            .map_err(|e| Error::JSONRpcError(e.into()))?;
+        Ok(resp.into_iter().map(|trace| trace.result).collect())
+    }
+
     /// Calls `eth_getCode` via JSON-RPC returning a contract code
     pub async fn get_code(
         &self,
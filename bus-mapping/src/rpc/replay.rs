@@ -0,0 +1,164 @@
+//! JSON-RPC client that replays geth responses recorded in a fixture
+//! directory, so that the [`GethClient`](super::GethClient) queries can be
+//! served without a running geth node.
+
+use async_trait::async_trait;
+use core::fmt::{Display, Formatter, Result as FmtResult};
+use ethers_core::utils::keccak256;
+use ethers_providers::{JsonRpcClient, ProviderError};
+use serde::{de::DeserializeOwned, Serialize};
+use std::error::Error as StdError;
+use std::fmt::Debug;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+/// Error type of the [`ReplayProvider`].
+#[derive(Debug)]
+pub enum ReplayError {
+    /// No response has been recorded for the request.
+    NotRecorded(PathBuf),
+    /// Error reading or writing a fixture file.
+    IoError(std::io::Error),
+    /// Serde de/serialization error.
+    SerdeError(serde_json::Error),
+    /// Error of the wrapped provider while recording.
+    ProviderError(ProviderError),
+}
+
+impl Display for ReplayError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl StdError for ReplayError {}
+
+impl From<ReplayError> for ProviderError {
+    fn from(err: ReplayError) -> Self {
+        ProviderError::JsonRpcClientError(Box::new(err))
+    }
+}
+
+#[derive(Debug)]
+enum Mode<P> {
+    Replay,
+    Record(P),
+}
+
+/// JSON-RPC client which serves each request with the response recorded for
+/// it in a fixture directory.  In recording mode, the requests are forwarded
+/// to the wrapped provider and their responses are written into the fixture
+/// directory.
+///
+/// Each response is stored as JSON in its own file, named after the method
+/// and the hash of the parameters of the request.
+#[derive(Debug)]
+pub struct ReplayProvider<P> {
+    dir: PathBuf,
+    mode: Mode<P>,
+}
+
+impl<P> ReplayProvider<P> {
+    /// Create a provider which replays the responses recorded in `dir`.
+    pub fn new(dir: impl AsRef<Path>) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            mode: Mode::Replay,
+        }
+    }
+
+    /// Create a provider which forwards the requests to `provider` and records
+    /// their responses into `dir`.
+    pub fn record(dir: impl AsRef<Path>, provider: P) -> Self {
+        Self {
+            dir: dir.as_ref().to_path_buf(),
+            mode: Mode::Record(provider),
+        }
+    }
+
+    /// Path of the fixture file of a request.
+    fn fixture_path<T: Serialize>(&self, method: &str, params: &T) -> Result<PathBuf, ReplayError> {
+        let params = serde_json::to_string(params).map_err(ReplayError::SerdeError)?;
+        let hash = keccak256(params.as_bytes());
+        let mut id = [0u8; 8];
+        id.copy_from_slice(&hash[..8]);
+        Ok(self
+            .dir
+            .join(format!("{}_{:016x}.json", method, u64::from_be_bytes(id))))
+    }
+}
+
+#[async_trait]
+impl<P: JsonRpcClient> JsonRpcClient for ReplayProvider<P> {
+    type Error = ReplayError;
+
+    async fn request<T, R>(&self, method: &str, params: T) -> Result<R, Self::Error>
+    where
+        T: Debug + Serialize + Send + Sync,
+        R: DeserializeOwned,
+    {
+        let path = self.fixture_path(method, &params)?;
+        match &self.mode {
+            Mode::Replay => {
+                let file = File::open(&path).map_err(|err| match err.kind() {
+                    std::io::ErrorKind::NotFound => ReplayError::NotRecorded(path.clone()),
+                    _ => ReplayError::IoError(err),
+                })?;
+                serde_json::from_reader(file).map_err(ReplayError::SerdeError)
+            }
+            Mode::Record(provider) => {
+                let resp: serde_json::Value = provider
+                    .request(method, params)
+                    .await
+                    .map_err(|err| ReplayError::ProviderError(err.into()))?;
+                fs::create_dir_all(&self.dir).map_err(ReplayError::IoError)?;
+                let file = File::create(&path).map_err(ReplayError::IoError)?;
+                serde_json::to_writer_pretty(file, &resp).map_err(ReplayError::SerdeError)?;
+                serde_json::from_value(resp).map_err(ReplayError::SerdeError)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod replay_tests {
+    use super::*;
+    use crate::rpc::GethClient;
+    use eth_types::{address, Address};
+    use ethers_providers::Http;
+
+    #[tokio::test]
+    async fn replay_recorded_responses() {
+        let dir = tempfile::tempdir().unwrap();
+        let recorded = ReplayProvider::<Http>::new(dir.path());
+        for (method, resp) in [
+            ("eth_chainId", "\"0x539\""),
+            (
+                "eth_coinbase",
+                "\"0x00000000000000000000000000000000000000c0\"",
+            ),
+        ] {
+            fs::write(recorded.fixture_path(method, &()).unwrap(), resp).unwrap();
+        }
+
+        let cli = GethClient::new(ReplayProvider::<Http>::new(dir.path()));
+        assert_eq!(cli.get_chain_id().await.unwrap(), 1337);
+        let coinbase: Address = cli.get_coinbase().await.unwrap();
+        assert_eq!(
+            coinbase,
+            address!("0x00000000000000000000000000000000000000c0")
+        );
+        assert!(cli.get_block_by_number(1.into()).await.is_err());
+
+        // Responses recorded from another provider are replayed the same way.
+        let dir_copy = tempfile::tempdir().unwrap();
+        let cli_copy = GethClient::new(ReplayProvider::record(
+            dir_copy.path(),
+            ReplayProvider::<Http>::new(dir.path()),
+        ));
+        assert_eq!(cli_copy.get_chain_id().await.unwrap(), 1337);
+        let cli = GethClient::new(ReplayProvider::<Http>::new(dir_copy.path()));
+        assert_eq!(cli.get_chain_id().await.unwrap(), 1337);
+        assert!(cli.get_coinbase().await.is_err());
+    }
+}
@@ -1,10 +1,14 @@
 //! Implementation of an in-memory key-value database to represent the
 //! Ethereum State Trie.
 
-use eth_types::{Address, Hash, Word, H256, U256};
+use crate::Error;
+use eth_types::{
+    mpt::{self, Trie},
+    Address, EIP1186ProofResponse, Hash, Word, H256, U256,
+};
 use ethers_core::utils::keccak256;
 use lazy_static::lazy_static;
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 
 lazy_static! {
     static ref ACCOUNT_ZERO: Account = Account::zero();
@@ -257,12 +261,164 @@ impl StateDB {
         }
         self.refund = 0;
     }
+
+    /// Return the changes of the accounts and storage slots from `prev`, the
+    /// [`StateDB`] before the block, to `self`.  Accounts without any change
+    /// are not included.
+    pub fn diff(&self, prev: &StateDB) -> StateDiff {
+        let addresses: HashSet<&Address> = self.state.keys().chain(prev.state.keys()).collect();
+        let mut diff = BTreeMap::new();
+        for address in addresses {
+            let (_, before) = prev.get_account(address);
+            let (_, after) = self.get_account(address);
+            let destructed = self.destructed_account.contains(address);
+
+            let mut keys: HashSet<&Word> =
+                before.storage.keys().chain(after.storage.keys()).collect();
+            keys.extend(
+                self.dirty_storage
+                    .keys()
+                    .filter(|(addr, _)| addr == address)
+                    .map(|(_, key)| key),
+            );
+            let storage: BTreeMap<Word, (Word, Word)> = keys
+                .into_iter()
+                .map(|key| {
+                    let (_, before) = prev.get_storage(address, key);
+                    let (_, after) = self.get_storage(address, key);
+                    (*key, (*before, *after))
+                })
+                .filter(|(_, (before, after))| destructed || before != after)
+                .collect();
+
+            let account_diff = AccountDiff {
+                nonce: (before.nonce, after.nonce),
+                balance: (before.balance, after.balance),
+                code_hash: (code_hash(before), code_hash(after)),
+                storage,
+                destructed,
+            };
+            if account_diff.is_changed() {
+                diff.insert(*address, account_diff);
+            }
+        }
+        StateDiff(diff)
+    }
+}
+
+/// Code hash of an account, where the zero hash returned by `eth_getProof`
+/// for the accounts that don't exist is read as the hash of the empty code.
+fn code_hash(account: &Account) -> Hash {
+    if account.code_hash.is_zero() {
+        *CODE_HASH_ZERO
+    } else {
+        account.code_hash
+    }
+}
+
+/// Changes of an account between two [`StateDB`]s, with each value before and
+/// after.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountDiff {
+    /// Nonce
+    pub nonce: (Word, Word),
+    /// Balance
+    pub balance: (Word, Word),
+    /// Code hash
+    pub code_hash: (Hash, Hash),
+    /// Storage slots whose value changed.  When the account is destructed,
+    /// all the known slots are included, since its whole storage is cleared.
+    pub storage: BTreeMap<Word, (Word, Word)>,
+    /// Whether the account executed `SELFDESTRUCT`
+    pub destructed: bool,
+}
+
+impl AccountDiff {
+    fn is_changed(&self) -> bool {
+        self.nonce.0 != self.nonce.1
+            || self.balance.0 != self.balance.1
+            || self.code_hash.0 != self.code_hash.1
+            || !self.storage.is_empty()
+            || self.destructed
+    }
+
+    /// Return if the account is empty after the changes, in which case it is
+    /// removed from the state trie (EIP-161).
+    fn is_empty_after(&self) -> bool {
+        self.nonce.1.is_zero() && self.balance.1.is_zero() && self.code_hash.1 == *CODE_HASH_ZERO
+    }
+}
+
+/// Changes of the accounts between two [`StateDB`]s, as returned by
+/// [`StateDB::diff`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct StateDiff(pub BTreeMap<Address, AccountDiff>);
+
+impl StateDiff {
+    /// Compute the state root after the changes by applying them to an
+    /// in-memory Merkle Patricia Trie built from `proofs`, the `eth_getProof`
+    /// responses of the accessed accounts and storage slots at the state
+    /// before the changes.  The state root before the changes is the hash of
+    /// the first node of the account proofs.
+    ///
+    /// Every changed account must have a proof, otherwise
+    /// [`Error::AccountNotFound`] is returned.  Removing a key can require a
+    /// sibling node that is not in the proofs, in which case
+    /// [`eth_types::Error::MissingTrieNode`] is returned.
+    pub fn post_state_root(&self, proofs: &[EIP1186ProofResponse]) -> Result<H256, Error> {
+        let state_root = proofs
+            .iter()
+            .find_map(|proof| proof.account_proof.first())
+            .map(|root| H256(keccak256(root)))
+            .ok_or(Error::InternalError("missing account proofs"))?;
+        let mut accounts = Trie::from_root_hash(state_root);
+        for proof in proofs {
+            accounts.load_proof(&mpt::account_key(&proof.address), &proof.account_proof)?;
+        }
+
+        for (address, diff) in &self.0 {
+            let proof = proofs
+                .iter()
+                .find(|proof| proof.address == *address)
+                .ok_or(Error::AccountNotFound(*address))?;
+            // The storage of a destructed account is cleared, and the storage
+            // hash of an account that doesn't exist is zero for some clients.
+            let mut storage = if diff.destructed || proof.storage_hash.is_zero() {
+                Trie::new()
+            } else {
+                Trie::from_root_hash(proof.storage_hash)
+            };
+            for storage_proof in &proof.storage_proof {
+                storage.load_proof(&mpt::storage_key(&storage_proof.key), &storage_proof.proof)?;
+            }
+            for (key, (_, value)) in &diff.storage {
+                storage.insert(&mpt::storage_key(key), mpt::encode_storage_value(*value))?;
+            }
+
+            let account_key = mpt::account_key(address);
+            let storage_root = storage.root_hash();
+            if diff.is_empty_after() && storage_root == *mpt::EMPTY_TRIE_HASH {
+                accounts.remove(&account_key)?;
+            } else {
+                accounts.insert(
+                    &account_key,
+                    mpt::encode_account(
+                        diff.nonce.1,
+                        diff.balance.1,
+                        storage_root,
+                        diff.code_hash.1,
+                    ),
+                )?;
+            }
+        }
+        Ok(accounts.root_hash())
+    }
 }
 
 #[cfg(test)]
 mod statedb_tests {
     use super::*;
-    use eth_types::address;
+    use eth_types::{address, StorageProof};
 
     #[test]
     fn statedb() {
@@ -318,4 +474,150 @@ mod statedb_tests {
         assert!(found);
         assert_eq!(value, &Word::from(102));
     }
+
+    /// Build the state trie of `accounts` and return it, with the storage trie
+    /// of each account.
+    fn state_trie(accounts: &HashMap<Address, Account>) -> (Trie, HashMap<Address, Trie>) {
+        let mut state = Trie::new();
+        let mut storage_tries = HashMap::new();
+        for (address, account) in accounts {
+            let mut storage = Trie::new();
+            for (key, value) in &account.storage {
+                storage
+                    .insert(&mpt::storage_key(key), mpt::encode_storage_value(*value))
+                    .unwrap();
+            }
+            state
+                .insert(
+                    &mpt::account_key(address),
+                    mpt::encode_account(
+                        account.nonce,
+                        account.balance,
+                        storage.root_hash(),
+                        account.code_hash,
+                    ),
+                )
+                .unwrap();
+            storage_tries.insert(*address, storage);
+        }
+        (state, storage_tries)
+    }
+
+    #[test]
+    fn state_diff_post_state_root() {
+        let addr_a = address!("0x0000000000000000000000000000000000000001");
+        let addr_b = address!("0x0000000000000000000000000000000000000002");
+        let addr_c = address!("0x0000000000000000000000000000000000000003");
+        let untouched: Vec<Address> = (4..20).map(Address::from_low_u64_be).collect();
+
+        let account = |nonce: u64, balance: u64, storage: &[(u64, u64)]| Account {
+            nonce: Word::from(nonce),
+            balance: Word::from(balance),
+            storage: storage
+                .iter()
+                .map(|(key, value)| (Word::from(*key), Word::from(*value)))
+                .collect(),
+            code_hash: *CODE_HASH_ZERO,
+        };
+        let mut accounts = HashMap::new();
+        accounts.insert(addr_a, account(1, 100, &[(1, 10), (2, 20), (3, 30)]));
+        accounts.insert(addr_b, account(0, 50, &[]));
+        for (i, address) in untouched.iter().enumerate() {
+            accounts.insert(*address, account(i as u64, 1, &[(1, 1)]));
+        }
+        let (state, storage_tries) = state_trie(&accounts);
+
+        // Proofs of the accessed accounts and storage slots, as returned by
+        // `eth_getProof`, from which the StateDB before the block is built.
+        let mut prev_sdb = StateDB::new();
+        let proofs: Vec<_> = [(addr_a, vec![1, 2, 4]), (addr_b, vec![]), (addr_c, vec![])]
+            .into_iter()
+            .map(|(address, keys)| {
+                let account = accounts
+                    .get(&address)
+                    .cloned()
+                    .unwrap_or_else(Account::zero);
+                let storage = storage_tries.get(&address).cloned().unwrap_or_default();
+                let storage_proof = keys
+                    .into_iter()
+                    .map(|key| {
+                        let key = Word::from(key);
+                        let proof = storage.proof(&mpt::storage_key(&key)).unwrap();
+                        StorageProof {
+                            key,
+                            value: account.storage.get(&key).copied().unwrap_or_default(),
+                            proof: proof.into_iter().map(Into::into).collect(),
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                prev_sdb.set_account(
+                    &address,
+                    Account {
+                        storage: storage_proof
+                            .iter()
+                            .map(|proof| (proof.key, proof.value))
+                            .collect(),
+                        ..account.clone()
+                    },
+                );
+                EIP1186ProofResponse {
+                    address,
+                    balance: account.balance,
+                    code_hash: account.code_hash,
+                    nonce: account.nonce,
+                    storage_hash: storage.root_hash(),
+                    account_proof: state
+                        .proof(&mpt::account_key(&address))
+                        .unwrap()
+                        .into_iter()
+                        .map(Into::into)
+                        .collect(),
+                    storage_proof,
+                }
+            })
+            .collect();
+
+        // a sends all the balance of b to the new account c and updates its
+        // storage, which leaves b empty.
+        let mut sdb = prev_sdb.clone();
+        sdb.increase_nonce(&addr_a);
+        sdb.get_account_mut(&addr_b).1.balance = Word::zero();
+        sdb.get_account_mut(&addr_c).1.balance = Word::from(50);
+        sdb.set_storage(&addr_a, &Word::from(1), &Word::from(11));
+        sdb.set_storage(&addr_a, &Word::from(2), &Word::zero());
+        sdb.set_storage(&addr_a, &Word::from(4), &Word::from(40));
+        sdb.commit_tx();
+
+        let diff = sdb.diff(&prev_sdb);
+        assert_eq!(
+            diff.0.keys().copied().collect::<Vec<_>>(),
+            vec![addr_a, addr_b, addr_c]
+        );
+        let diff_a = &diff.0[&addr_a];
+        assert_eq!(diff_a.nonce, (Word::from(1), Word::from(2)));
+        assert_eq!(
+            diff_a
+                .storage
+                .iter()
+                .map(|(k, v)| (*k, *v))
+                .collect::<Vec<_>>(),
+            vec![
+                (Word::from(1), (Word::from(10), Word::from(11))),
+                (Word::from(2), (Word::from(20), Word::zero())),
+                (Word::from(4), (Word::zero(), Word::from(40))),
+            ]
+        );
+        assert_eq!(diff.0[&addr_b].balance, (Word::from(50), Word::zero()));
+        assert_eq!(diff.0[&addr_c].balance, (Word::zero(), Word::from(50)));
+
+        let mut post_accounts = accounts;
+        post_accounts.remove(&addr_b);
+        post_accounts.insert(addr_a, account(2, 100, &[(1, 11), (3, 30), (4, 40)]));
+        post_accounts.insert(addr_c, account(0, 50, &[]));
+        let (post_state, _) = state_trie(&post_accounts);
+        assert_eq!(
+            diff.post_state_root(&proofs).unwrap(),
+            post_state.root_hash()
+        );
+    }
 }
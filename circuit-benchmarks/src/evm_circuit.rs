@@ -1,62 +1,7 @@
 //! Evm circuit benchmarks
 
-use eth_types::Field;
-use halo2_proofs::{
-    circuit::{Layouter, SimpleFloorPlanner},
-    plonk::{Circuit, ConstraintSystem, Error, Expression},
-};
-use zkevm_circuits::evm_circuit::{witness::Block, EvmCircuit};
-use zkevm_circuits::table::{BlockTable, BytecodeTable, RwTable, TxTable};
-
-#[derive(Debug, Default)]
-pub struct TestCircuit<F> {
-    block: Block<F>,
-}
-
-impl<F: Field> Circuit<F> for TestCircuit<F> {
-    type Config = EvmCircuit<F>;
-    type FloorPlanner = SimpleFloorPlanner;
-
-    fn without_witnesses(&self) -> Self {
-        Self::default()
-    }
-
-    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
-        let tx_table = TxTable::construct(meta);
-        let rw_table = RwTable::construct(meta);
-        let bytecode_table = BytecodeTable::construct(meta);
-        let block_table = BlockTable::construct(meta);
-        let copy_table = [(); 11].map(|_| meta.advice_column());
-        let keccak_table = [(); 4].map(|_| meta.advice_column());
-        // Use constant expression to mock constant instance column for a more
-        // reasonable benchmark.
-        let power_of_randomness = [(); 31].map(|_| Expression::Constant(F::one()));
-
-        EvmCircuit::configure(
-            meta,
-            power_of_randomness,
-            &tx_table,
-            &rw_table,
-            &bytecode_table,
-            &block_table,
-            &copy_table,
-            &keccak_table,
-        )
-    }
-
-    fn synthesize(
-        &self,
-        config: Self::Config,
-        mut layouter: impl Layouter<F>,
-    ) -> Result<(), Error> {
-        config.assign_block(&mut layouter, &self.block)?;
-        Ok(())
-    }
-}
-
 #[cfg(test)]
 mod evm_circ_benches {
-    use super::*;
     use ark_std::{end_timer, start_timer};
     use halo2_proofs::plonk::{create_proof, keygen_pk, keygen_vk, verify_proof};
     use halo2_proofs::poly::kzg::commitment::{KZGCommitmentScheme, ParamsKZG, ParamsVerifierKZG};
@@ -72,6 +17,8 @@ mod evm_circ_benches {
     use rand::SeedableRng;
     use rand_xorshift::XorShiftRng;
     use std::env::var;
+    use zkevm_circuits::evm_circuit::EvmCircuit;
+    use zkevm_circuits::util::SubCircuit;
 
     #[cfg_attr(not(feature = "benches"), ignore)]
     #[test]
@@ -81,7 +28,9 @@ mod evm_circ_benches {
             .parse()
             .expect("Cannot parse DEGREE env var as u32");
 
-        let circuit = TestCircuit::<Fr>::default();
+        let circuit = EvmCircuit::<Fr>::default();
+        let instance = circuit.instance();
+        let instance_refs: Vec<&[Fr]> = instance.iter().map(|v| &v[..]).collect();
         let mut rng = XorShiftRng::from_seed([
             0x59, 0x62, 0xbe, 0x5d, 0x76, 0x3d, 0x31, 0x8d, 0x17, 0xdb, 0x37, 0x32, 0x54, 0x06,
             0xbc, 0xe5,
@@ -109,12 +58,12 @@ mod evm_circ_benches {
             Challenge255<G1Affine>,
             XorShiftRng,
             Blake2bWrite<Vec<u8>, G1Affine, Challenge255<G1Affine>>,
-            TestCircuit<Fr>,
+            EvmCircuit<Fr>,
         >(
             &general_params,
             &pk,
             &[circuit],
-            &[&[]],
+            &[&instance_refs],
             rng,
             &mut transcript,
         )
@@ -137,7 +86,7 @@ mod evm_circ_benches {
             &verifier_params,
             pk.get_vk(),
             strategy,
-            &[&[]],
+            &[&instance_refs],
             &mut verifier_transcript,
         )
         .expect("failed to verify bench circuit");
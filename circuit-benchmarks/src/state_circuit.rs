@@ -19,6 +19,7 @@ mod tests {
     use std::env::var;
     use zkevm_circuits::evm_circuit::witness::RwMap;
     use zkevm_circuits::state_circuit::StateCircuit;
+    use zkevm_circuits::util::SubCircuit;
 
     #[cfg_attr(not(feature = "benches"), ignore)]
     #[test]
@@ -28,7 +29,9 @@ mod tests {
             .parse()
             .expect("Cannot parse DEGREE env var as u32");
 
-        let empty_circuit = StateCircuit::<Fr>::new(Fr::default(), RwMap::default(), 1 << 16);
+        let empty_circuit = StateCircuit::<Fr>::new(RwMap::default(), 1 << 16);
+        let instance = empty_circuit.instance();
+        let instance_refs: Vec<&[Fr]> = instance.iter().map(|v| &v[..]).collect();
 
         // Initialize the polynomial commitment parameters
         let mut rng = XorShiftRng::from_seed([
@@ -49,9 +52,6 @@ mod tests {
         // Create a proof
         let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
 
-        let instance = empty_circuit.instance();
-        let instances: Vec<&[Fr]> = instance.iter().map(|v| v.as_slice()).collect();
-
         // Bench proof generation time
         let proof_message = format!("State Circuit Proof generation with {} rows", degree);
         let start2 = start_timer!(|| proof_message);
@@ -66,7 +66,7 @@ mod tests {
             &general_params,
             &pk,
             &[empty_circuit],
-            &[&instances],
+            &[&instance_refs],
             rng,
             &mut transcript,
         )
@@ -89,7 +89,7 @@ mod tests {
             &verifier_params,
             pk.get_vk(),
             strategy,
-            &[&instances],
+            &[&instance_refs],
             &mut verifier_transcript,
         )
         .expect("failed to verify bench circuit");
@@ -10,7 +10,6 @@ mod tests {
     use halo2_proofs::poly::kzg::multiopen::{ProverSHPLONK, VerifierSHPLONK};
     use halo2_proofs::poly::kzg::strategy::SingleStrategy;
     use halo2_proofs::{
-        arithmetic::FieldExt,
         halo2curves::bn256::{Bn256, Fr, G1Affine},
         poly::commitment::ParamsProver,
         transcript::{
@@ -19,11 +18,7 @@ mod tests {
     };
     use rand::SeedableRng;
     use rand_chacha::ChaCha20Rng;
-    use std::marker::PhantomData;
-    use zkevm_circuits::tx_circuit::{
-        sign_verify::{SignVerifyChip, POW_RAND_SIZE, VERIF_HEIGHT},
-        Curve, TxCircuit,
-    };
+    use zkevm_circuits::tx_circuit::{Curve, TxCircuit};
     use zkevm_circuits::tx_circuit::{Group, Secp256k1Affine};
 
     use crate::bench_params::DEGREE;
@@ -46,22 +41,9 @@ mod tests {
 
         let txs = vec![mock::CORRECT_MOCK_TXS[0].clone().into()];
 
-        let randomness = Fr::from(0xcafeu64);
-        let mut instance: Vec<Vec<Fr>> = (1..POW_RAND_SIZE + 1)
-            .map(|exp| vec![randomness.pow(&[exp as u64, 0, 0, 0]); MAX_TXS * VERIF_HEIGHT])
-            .collect();
         // SignVerifyChip -> ECDSAChip -> MainGate instance column
-        instance.push(vec![]);
-        let circuit = TxCircuit::<Fr, MAX_TXS, MAX_CALLDATA> {
-            sign_verify: SignVerifyChip {
-                aux_generator,
-                window_size: 2,
-                _marker: PhantomData,
-            },
-            randomness,
-            txs,
-            chain_id,
-        };
+        let instance: Vec<Vec<Fr>> = vec![vec![]];
+        let circuit = TxCircuit::<Fr, MAX_TXS, MAX_CALLDATA>::new(aux_generator, chain_id, txs);
 
         // Bench setup generation
         let setup_message = format!("Setup generation with degree = {}", DEGREE);
@@ -1,5 +1,6 @@
 //! Error module for the eth-types crate
 
+use crate::{Address, H256};
 use core::fmt::{Display, Formatter, Result as FmtResult};
 use std::error::Error as StdError;
 
@@ -33,6 +34,31 @@ pub enum Error {
     WordToMemAddr,
     /// Signature parsing error.
     Signature(libsecp256k1::Error),
+    /// A trie node that is only known by its hash was needed to complete a
+    /// trie operation.
+    MissingTrieNode(H256),
+    /// Trie keys must all have the same length, so that no key is a prefix of
+    /// another one.
+    InvalidTrieKey,
+    /// A trie node couldn't be decoded from its RLP encoding.
+    InvalidTrieNode,
+    /// An `eth_getProof` response doesn't match the state it should prove.
+    InvalidStateProof(Address),
+    /// The `eth_getProof` responses of the state before a block don't start
+    /// at the state root of the header of its parent: (parent state root,
+    /// root of the proofs)
+    PrevStateRootMismatch(H256, H256),
+    /// The tries updated by the MPT updates of a block don't end at the state
+    /// root of its header: (header state root, root of the updated tries)
+    StateRootMismatch(H256, H256),
+    /// The blocks of a batch can't be proven together, with the reason: they
+    /// are not consecutive or weren't handled with continuous counters.
+    InvalidBatch(&'static str),
+    /// An MPT update can't be proved by the MPT circuit, with the reason.
+    /// The circuit doesn't support the updates that change the shape of the
+    /// tries above the updated leaf, such as inserting a key next to an
+    /// existing leaf or removing a key from a branch with two children.
+    UnsupportedMptUpdate(&'static str),
 }
 
 impl From<libsecp256k1::Error> for Error {
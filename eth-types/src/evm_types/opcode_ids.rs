@@ -8,7 +8,7 @@ use std::fmt;
 use std::str::FromStr;
 
 /// Opcode enum. One-to-one corresponding to an `u8` value.
-#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Hash)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
 pub enum OpcodeId {
     /// `STOP`
     STOP,
@@ -998,6 +998,21 @@ impl FromStr for OpcodeId {
     }
 }
 
+impl Serialize for OpcodeId {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            // Serialize an invalid opcode value as reported by geth
+            OpcodeId::INVALID(b) => {
+                serializer.serialize_str(&format!("opcode 0x{:x} not defined", b))
+            }
+            op => serializer.serialize_str(&op.to_string()),
+        }
+    }
+}
+
 impl<'de> Deserialize<'de> for OpcodeId {
     fn deserialize<D>(deserializer: D) -> Result<OpcodeId, D::Error>
     where
@@ -0,0 +1,455 @@
+//! Precompiled contracts of the EVM.
+
+use crate::Address;
+use ethers_core::utils::keccak256;
+use halo2_proofs::{
+    arithmetic::{CurveAffine, FieldExt},
+    halo2curves::{
+        bn256::{Bn256, Fq, Fq2, Fr, G1Affine, G2Affine, G2Prepared, Gt, G1},
+        group::{prime::PrimeCurveAffine, Curve},
+        pairing::{MillerLoopResult, MultiMillerLoop},
+        Coordinates,
+    },
+};
+use num_bigint::BigUint;
+use ripemd::Ripemd160;
+use sha2::{Digest, Sha256};
+
+/// Number of precompiled contracts, which are deployed at the addresses `1`
+/// to `PRECOMPILE_COUNT`.
+pub const PRECOMPILE_COUNT: u64 = 9;
+
+/// Return the precompiled contract deployed at `address`, if any.
+pub fn precompile(address: &Address) -> Option<Precompile> {
+    if address[..19].iter().any(|b| *b != 0) {
+        return None;
+    }
+    Some(match address[19] {
+        1 => Precompile::EcRecover,
+        2 => Precompile::Sha256,
+        3 => Precompile::Ripemd160,
+        4 => Precompile::Identity,
+        5 => Precompile::ModExp,
+        6 => Precompile::Bn256Add,
+        7 => Precompile::Bn256ScalarMul,
+        8 => Precompile::Bn256Pairing,
+        9 => Precompile::Blake2F,
+        _ => return None,
+    })
+}
+
+/// Precompiled contracts active since Berlin.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precompile {
+    /// Public key recovery from an ECDSA signature.
+    EcRecover,
+    /// SHA-256 hash.
+    Sha256,
+    /// RIPEMD-160 hash.
+    Ripemd160,
+    /// Copy of the input.
+    Identity,
+    /// Modular exponentiation (EIP-198).
+    ModExp,
+    /// Addition on the alt_bn128 curve (EIP-196).
+    Bn256Add,
+    /// Scalar multiplication on the alt_bn128 curve (EIP-196).
+    Bn256ScalarMul,
+    /// Pairing check on the alt_bn128 curve (EIP-197).
+    Bn256Pairing,
+    /// BLAKE2b compression function (EIP-152).
+    Blake2F,
+}
+
+/// Return `size` bytes of `data` from `start`, right padded with zeros.
+pub fn get_data(data: &[u8], start: u64, size: u64) -> Vec<u8> {
+    let len = data.len() as u64;
+    let start = start.min(len);
+    let end = start.saturating_add(size).min(len);
+    let mut result = data[start as usize..end as usize].to_vec();
+    result.resize(size as usize, 0);
+    result
+}
+
+/// Error of a precompiled contract that couldn't be run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrecompileError {
+    /// The input of the contract is invalid.
+    Failed(&'static str),
+}
+
+fn words(len: usize) -> u64 {
+    (len as u64 + 31) / 32
+}
+
+impl Precompile {
+    /// Gas required to run the contract with `input`.
+    pub fn required_gas(&self, input: &[u8]) -> u64 {
+        match self {
+            Self::EcRecover => 3000,
+            Self::Sha256 => 60 + 12 * words(input.len()),
+            Self::Ripemd160 => 600 + 120 * words(input.len()),
+            Self::Identity => 15 + 3 * words(input.len()),
+            Self::ModExp => modexp_gas(input),
+            Self::Bn256Add => 150,
+            Self::Bn256ScalarMul => 6000,
+            Self::Bn256Pairing => 45000 + 34000 * (input.len() as u64 / 192),
+            Self::Blake2F => {
+                if input.len() != BLAKE2F_INPUT_LENGTH {
+                    return 0;
+                }
+                u32::from_be_bytes(input[..4].try_into().unwrap()) as u64
+            }
+        }
+    }
+
+    /// Run the contract with `input`.
+    pub fn run(&self, input: &[u8]) -> Result<Vec<u8>, PrecompileError> {
+        Ok(match self {
+            Self::EcRecover => ecrecover(input),
+            Self::Sha256 => Sha256::digest(input).to_vec(),
+            Self::Ripemd160 => {
+                let mut output = vec![0u8; 12];
+                output.extend_from_slice(&Ripemd160::digest(input));
+                output
+            }
+            Self::Identity => input.to_vec(),
+            Self::ModExp => modexp(input),
+            Self::Bn256Add => bn256_add(input)?,
+            Self::Bn256ScalarMul => bn256_scalar_mul(input)?,
+            Self::Bn256Pairing => bn256_pairing(input)?,
+            Self::Blake2F => blake2f(input)?,
+        })
+    }
+}
+
+fn ecrecover(input: &[u8]) -> Vec<u8> {
+    let input = get_data(input, 0, 128);
+    let v = input[63].wrapping_sub(27);
+    // The signature must be valid and not malleable in v.
+    if input[32..63].iter().any(|b| *b != 0) || v > 1 {
+        return Vec::new();
+    }
+    let message = libsecp256k1::Message::parse_slice(&input[..32]).expect("32 bytes message");
+    let signature = match libsecp256k1::Signature::parse_standard_slice(&input[64..128]) {
+        Ok(signature) if !signature.r.is_zero() && !signature.s.is_zero() => signature,
+        _ => return Vec::new(),
+    };
+    let recovery_id = libsecp256k1::RecoveryId::parse(v).expect("valid recovery id");
+    match libsecp256k1::recover(&message, &signature, &recovery_id) {
+        Ok(pub_key) => {
+            let hash = keccak256(&pub_key.serialize()[1..]);
+            let mut output = vec![0u8; 12];
+            output.extend_from_slice(&hash[12..]);
+            output
+        }
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Lengths of the base, exponent and modulus of the MODEXP input.
+fn modexp_lengths(input: &[u8]) -> [BigUint; 3] {
+    [0, 32, 64].map(|offset| BigUint::from_bytes_be(&get_data(input, offset, 32)))
+}
+
+fn to_u64(value: &BigUint) -> u64 {
+    value.iter_u64_digits().next().unwrap_or(0)
+}
+
+/// Gas of MODEXP as specified by EIP-2565.
+fn modexp_gas(input: &[u8]) -> u64 {
+    let [base_len, exp_len, mod_len] = modexp_lengths(input);
+    let input = input.get(96..).unwrap_or_default();
+
+    // Head 32 bytes of the exponent for the adjusted exponent length.
+    let big32 = BigUint::from(32u64);
+    let exp_head = if BigUint::from(input.len()) <= base_len {
+        BigUint::default()
+    } else if exp_len > big32 {
+        BigUint::from_bytes_be(&get_data(input, to_u64(&base_len), 32))
+    } else {
+        BigUint::from_bytes_be(&get_data(input, to_u64(&base_len), to_u64(&exp_len)))
+    };
+    let msb = exp_head.bits().saturating_sub(1);
+    let mut adj_exp_len = BigUint::default();
+    if exp_len > big32 {
+        adj_exp_len = (exp_len - big32) * 8u64;
+    }
+    adj_exp_len += msb;
+
+    let max_len = base_len.max(mod_len);
+    let words = (max_len + 7u64) / 8u64;
+    let gas = &words * &words * adj_exp_len.max(BigUint::from(1u64)) / 3u64;
+    if gas.bits() > 64 {
+        return u64::MAX;
+    }
+    to_u64(&gas).max(200)
+}
+
+fn modexp(input: &[u8]) -> Vec<u8> {
+    let [base_len, exp_len, mod_len] = modexp_lengths(input).map(|len| to_u64(&len));
+    let input = input.get(96..).unwrap_or_default();
+    if base_len == 0 && mod_len == 0 {
+        return Vec::new();
+    }
+    let base = BigUint::from_bytes_be(&get_data(input, 0, base_len));
+    let exp = BigUint::from_bytes_be(&get_data(input, base_len, exp_len));
+    let modulus =
+        BigUint::from_bytes_be(&get_data(input, base_len.saturating_add(exp_len), mod_len));
+    let mut output = vec![0u8; mod_len as usize];
+    if modulus.bits() == 0 {
+        return output;
+    }
+    let result = base.modpow(&exp, &modulus).to_bytes_be();
+    output[mod_len as usize - result.len()..].copy_from_slice(&result);
+    output
+}
+
+/// Decode a big-endian coordinate of an alt_bn128 point.
+fn bn256_fq(bytes: &[u8]) -> Result<Fq, PrecompileError> {
+    let mut repr: [u8; 32] = bytes.try_into().expect("32 bytes coordinate");
+    repr.reverse();
+    Option::from(Fq::from_bytes(&repr))
+        .ok_or(PrecompileError::Failed("bn256: coordinate exceeds modulus"))
+}
+
+/// Decode a G1 point from its 64 bytes encoding, where the point at infinity
+/// is encoded as zeros.
+fn bn256_g1(bytes: &[u8]) -> Result<G1Affine, PrecompileError> {
+    if bytes.iter().all(|b| *b == 0) {
+        return Ok(G1Affine::identity());
+    }
+    let (x, y) = (bn256_fq(&bytes[..32])?, bn256_fq(&bytes[32..64])?);
+    Option::from(G1Affine::from_xy(x, y)).ok_or(PrecompileError::Failed("bn256: malformed point"))
+}
+
+/// Decode a G2 point from its 128 bytes encoding, where the imaginary part of
+/// each coordinate comes before its real part.
+fn bn256_g2(bytes: &[u8]) -> Result<G2Affine, PrecompileError> {
+    if bytes.iter().all(|b| *b == 0) {
+        return Ok(G2Affine::identity());
+    }
+    let x = Fq2 {
+        c0: bn256_fq(&bytes[32..64])?,
+        c1: bn256_fq(&bytes[..32])?,
+    };
+    let y = Fq2 {
+        c0: bn256_fq(&bytes[96..128])?,
+        c1: bn256_fq(&bytes[64..96])?,
+    };
+    let point: G2Affine = Option::from(G2Affine::from_xy(x, y))
+        .ok_or(PrecompileError::Failed("bn256: malformed point"))?;
+    // Unlike G1, the twist has points outside of the subgroup of order r,
+    // which are the ones that aren't negated by a multiplication by r - 1.
+    let point = point.to_curve();
+    if point * -Fr::from(1) != -point {
+        return Err(PrecompileError::Failed("bn256: malformed point"));
+    }
+    Ok(point.to_affine())
+}
+
+/// Encode a G1 point in 64 bytes.
+fn bn256_encode(point: G1) -> Vec<u8> {
+    match Option::<Coordinates<_>>::from(point.to_affine().coordinates()) {
+        Some(coordinates) => [coordinates.x(), coordinates.y()]
+            .iter()
+            .flat_map(|coordinate| coordinate.to_bytes().into_iter().rev())
+            .collect(),
+        None => vec![0u8; 64],
+    }
+}
+
+fn bn256_add(input: &[u8]) -> Result<Vec<u8>, PrecompileError> {
+    let input = get_data(input, 0, 128);
+    let (a, b) = (bn256_g1(&input[..64])?, bn256_g1(&input[64..])?);
+    Ok(bn256_encode(a.to_curve() + b.to_curve()))
+}
+
+fn bn256_scalar_mul(input: &[u8]) -> Result<Vec<u8>, PrecompileError> {
+    let input = get_data(input, 0, 96);
+    let point = bn256_g1(&input[..64])?;
+    // G1 has order r, so the scalar can be reduced modulo r.
+    let mut scalar = [0u8; 64];
+    scalar[..32].copy_from_slice(&input[64..]);
+    scalar[..32].reverse();
+    Ok(bn256_encode(
+        point.to_curve() * Fr::from_bytes_wide(&scalar),
+    ))
+}
+
+/// Check that the product of the pairings of the (G1, G2) pairs of the input
+/// is 1.
+fn bn256_pairing(input: &[u8]) -> Result<Vec<u8>, PrecompileError> {
+    if input.len() % 192 != 0 {
+        return Err(PrecompileError::Failed("bad elliptic curve pairing size"));
+    }
+    let pairs = input
+        .chunks(192)
+        .map(|pair| {
+            Ok((
+                bn256_g1(&pair[..64])?,
+                G2Prepared::from(bn256_g2(&pair[64..])?),
+            ))
+        })
+        .collect::<Result<Vec<_>, PrecompileError>>()?;
+    let terms: Vec<_> = pairs.iter().map(|(a, b)| (a, b)).collect();
+    let result = Bn256::multi_miller_loop(&terms).final_exponentiation();
+    let mut output = vec![0u8; 32];
+    output[31] = (result == Gt::identity()) as u8;
+    Ok(output)
+}
+
+const BLAKE2F_INPUT_LENGTH: usize = 213;
+
+const BLAKE2B_IV: [u64; 8] = [
+    0x6a09e667f3bcc908,
+    0xbb67ae8584caa73b,
+    0x3c6ef372fe94f82b,
+    0xa54ff53a5f1d36f1,
+    0x510e527fade682d1,
+    0x9b05688c2b3e6c1f,
+    0x1f83d9abfb41bd6b,
+    0x5be0cd19137e2179,
+];
+
+const BLAKE2B_SIGMA: [[usize; 16]; 10] = [
+    [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15],
+    [14, 10, 4, 8, 9, 15, 13, 6, 1, 12, 0, 2, 11, 7, 5, 3],
+    [11, 8, 12, 0, 5, 2, 15, 13, 10, 14, 3, 6, 7, 1, 9, 4],
+    [7, 9, 3, 1, 13, 12, 11, 14, 2, 6, 5, 10, 4, 0, 15, 8],
+    [9, 0, 5, 7, 2, 4, 10, 15, 14, 1, 11, 12, 6, 8, 3, 13],
+    [2, 12, 6, 10, 0, 11, 8, 3, 4, 13, 7, 5, 15, 14, 1, 9],
+    [12, 5, 1, 15, 14, 13, 4, 10, 0, 7, 6, 3, 9, 2, 8, 11],
+    [13, 11, 7, 14, 12, 1, 3, 9, 5, 0, 15, 4, 8, 6, 2, 10],
+    [6, 15, 14, 9, 11, 3, 0, 8, 12, 2, 13, 7, 1, 4, 10, 5],
+    [10, 2, 8, 4, 7, 6, 1, 5, 15, 11, 9, 14, 3, 12, 13, 0],
+];
+
+/// BLAKE2b compression function F as specified by EIP-152.
+fn blake2f(input: &[u8]) -> Result<Vec<u8>, PrecompileError> {
+    if input.len() != BLAKE2F_INPUT_LENGTH {
+        return Err(PrecompileError::Failed("invalid input length"));
+    }
+    let final_block = match input[212] {
+        0 => false,
+        1 => true,
+        _ => return Err(PrecompileError::Failed("invalid final flag")),
+    };
+    let rounds = u32::from_be_bytes(input[..4].try_into().unwrap());
+    let read_u64 =
+        |offset: usize| u64::from_le_bytes(input[offset..offset + 8].try_into().unwrap());
+    let mut h: [u64; 8] = std::array::from_fn(|i| read_u64(4 + i * 8));
+    let m: [u64; 16] = std::array::from_fn(|i| read_u64(68 + i * 8));
+    let t = [read_u64(196), read_u64(204)];
+
+    let mut v = [0u64; 16];
+    v[..8].copy_from_slice(&h);
+    v[8..].copy_from_slice(&BLAKE2B_IV);
+    v[12] ^= t[0];
+    v[13] ^= t[1];
+    if final_block {
+        v[14] = !v[14];
+    }
+    let mix = |v: &mut [u64; 16], a: usize, b: usize, c: usize, d: usize, x: u64, y: u64| {
+        v[a] = v[a].wrapping_add(v[b]).wrapping_add(x);
+        v[d] = (v[d] ^ v[a]).rotate_right(32);
+        v[c] = v[c].wrapping_add(v[d]);
+        v[b] = (v[b] ^ v[c]).rotate_right(24);
+        v[a] = v[a].wrapping_add(v[b]).wrapping_add(y);
+        v[d] = (v[d] ^ v[a]).rotate_right(16);
+        v[c] = v[c].wrapping_add(v[d]);
+        v[b] = (v[b] ^ v[c]).rotate_right(63);
+    };
+    for round in 0..rounds as usize {
+        let s = &BLAKE2B_SIGMA[round % 10];
+        mix(&mut v, 0, 4, 8, 12, m[s[0]], m[s[1]]);
+        mix(&mut v, 1, 5, 9, 13, m[s[2]], m[s[3]]);
+        mix(&mut v, 2, 6, 10, 14, m[s[4]], m[s[5]]);
+        mix(&mut v, 3, 7, 11, 15, m[s[6]], m[s[7]]);
+        mix(&mut v, 0, 5, 10, 15, m[s[8]], m[s[9]]);
+        mix(&mut v, 1, 6, 11, 12, m[s[10]], m[s[11]]);
+        mix(&mut v, 2, 7, 8, 13, m[s[12]], m[s[13]]);
+        mix(&mut v, 3, 4, 9, 14, m[s[14]], m[s[15]]);
+    }
+    for i in 0..8 {
+        h[i] ^= v[i] ^ v[i + 8];
+    }
+    Ok(h.iter().flat_map(|word| word.to_le_bytes()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Word;
+
+    fn be_bytes(words: &[Word]) -> Vec<u8> {
+        words
+            .iter()
+            .flat_map(|word| {
+                let mut bytes = [0u8; 32];
+                word.to_big_endian(&mut bytes);
+                bytes
+            })
+            .collect()
+    }
+
+    #[test]
+    fn bn256_add_and_scalar_mul() {
+        let generator = be_bytes(&[Word::from(1), Word::from(2)]);
+        let double = be_bytes(&[
+            Word::from_str_radix(
+                "030644e72e131a029b85045b68181585d97816a916871ca8d3c208c16d87cfd3",
+                16,
+            )
+            .unwrap(),
+            Word::from_str_radix(
+                "15ed738c0e0a7c92e7845f96b2ae9c0a68a6a449e3538fc7ff3ebf7a5a18a2c4",
+                16,
+            )
+            .unwrap(),
+        ]);
+
+        let input = [generator.clone(), generator.clone()].concat();
+        assert_eq!(Precompile::Bn256Add.run(&input), Ok(double.clone()));
+        // The point at infinity is the identity.
+        assert_eq!(Precompile::Bn256Add.run(&generator), Ok(generator.clone()));
+
+        let input = [generator.clone(), be_bytes(&[Word::from(2)])].concat();
+        assert_eq!(Precompile::Bn256ScalarMul.run(&input), Ok(double));
+        // The scalar is reduced modulo the order of G1.
+        let order = Word::from_little_endian(&(-Fr::from(1)).to_bytes()) + 1;
+        let input = [generator, be_bytes(&[order])].concat();
+        assert_eq!(Precompile::Bn256ScalarMul.run(&input), Ok(vec![0u8; 64]));
+
+        // (1, 3) is not on the curve.
+        let input = be_bytes(&[Word::from(1), Word::from(3)]);
+        assert!(Precompile::Bn256Add.run(&input).is_err());
+    }
+
+    #[test]
+    fn bn256_pairing_check() {
+        let g2 = G2Affine::generator();
+        let g2 = [g2.x.c1, g2.x.c0, g2.y.c1, g2.y.c0]
+            .iter()
+            .flat_map(|coordinate| coordinate.to_bytes().into_iter().rev())
+            .collect::<Vec<_>>();
+        let g1 = bn256_encode(G1Affine::generator().to_curve());
+        let neg_g1 = bn256_encode(-G1Affine::generator().to_curve());
+
+        let valid = [g1.clone(), g2.clone(), neg_g1, g2.clone()].concat();
+        let invalid = [g1.clone(), g2.clone(), g1, g2].concat();
+        let one = |value: u8| {
+            let mut output = vec![0u8; 32];
+            output[31] = value;
+            Ok(output)
+        };
+        assert_eq!(Precompile::Bn256Pairing.run(&valid), one(1));
+        assert_eq!(Precompile::Bn256Pairing.run(&invalid), one(0));
+        assert_eq!(Precompile::Bn256Pairing.run(&[]), one(1));
+        assert!(Precompile::Bn256Pairing.run(&valid[1..]).is_err());
+        assert_eq!(
+            Precompile::Bn256Pairing.required_gas(&valid),
+            45000 + 2 * 34000
+        );
+    }
+}
@@ -5,7 +5,10 @@ use crate::{
     AccessList, Address, Block, Bytes, Error, GethExecTrace, Hash, ToBigEndian, ToLittleEndian,
     Word, U64,
 };
-use ethers_core::types::TransactionRequest;
+use ethers_core::types::{
+    transaction::eip2718::TypedTransaction, Eip1559TransactionRequest, Eip2930TransactionRequest,
+    TransactionRequest,
+};
 use ethers_signers::{LocalWallet, Signer};
 use halo2_proofs::halo2curves::{group::ff::PrimeField, secp256k1};
 use num::Integer;
@@ -97,9 +100,32 @@ impl BlockConstants {
     }
 }
 
+/// EIP-2718 type of an access list (EIP-2930) transaction.
+pub const ACCESS_LIST_TX_TYPE: u64 = 1;
+
+/// EIP-2718 type of a dynamic fee (EIP-1559) transaction.
+pub const DYNAMIC_FEE_TX_TYPE: u64 = 2;
+
+/// Returns the gas price paid by `tx` in a block with the given `base_fee`.
+/// That is `min(max_fee_per_gas, base_fee + max_priority_fee_per_gas)` for a
+/// dynamic fee (EIP-1559) transaction, and its gas price otherwise.
+pub fn effective_gas_price(tx: &crate::Transaction, base_fee: Word) -> Word {
+    if tx.transaction_type == Some(U64::from(DYNAMIC_FEE_TX_TYPE)) {
+        let gas_fee_cap = tx.max_fee_per_gas.unwrap_or_default();
+        let gas_tip_cap = tx.max_priority_fee_per_gas.unwrap_or_default();
+        gas_fee_cap.min(base_fee + gas_tip_cap)
+    } else {
+        tx.gas_price.unwrap_or_default()
+    }
+}
+
 /// Definition of all of the constants related to an Ethereum transaction.
 #[derive(Debug, Default, Clone, Serialize)]
 pub struct Transaction {
+    /// EIP-2718 transaction type
+    pub transaction_type: u64,
+    /// Number of the block including the transaction
+    pub block_number: u64,
     /// Sender address
     pub from: Address,
     /// Recipient address (None for contract creation)
@@ -110,11 +136,14 @@ pub struct Transaction {
     pub gas_limit: Word,
     /// Transfered value
     pub value: Word,
-    /// Gas Price
+    /// Gas Price.  For a dynamic fee transaction this is the effective gas
+    /// price paid in its block.
     pub gas_price: Word,
-    /// Gas fee cap
+    /// Gas fee cap (`max_fee_per_gas`).  Equal to the gas price for legacy
+    /// transactions.
     pub gas_fee_cap: Word,
-    /// Gas tip cap
+    /// Gas tip cap (`max_priority_fee_per_gas`).  Equal to the gas price for
+    /// legacy transactions.
     pub gas_tip_cap: Word,
     /// The compiled code of a contract OR the first 4 bytes of the hash of the
     /// invoked method signature and encoded parameters. For details see
@@ -134,14 +163,16 @@ pub struct Transaction {
 impl From<&Transaction> for crate::Transaction {
     fn from(tx: &Transaction) -> crate::Transaction {
         crate::Transaction {
+            transaction_type: Some(tx.transaction_type.into()),
+            block_number: Some(tx.block_number.into()),
             from: tx.from,
             to: tx.to,
             nonce: tx.nonce,
             gas: tx.gas_limit,
             value: tx.value,
             gas_price: Some(tx.gas_price),
-            max_priority_fee_per_gas: Some(tx.gas_fee_cap),
-            max_fee_per_gas: Some(tx.gas_tip_cap),
+            max_priority_fee_per_gas: Some(tx.gas_tip_cap),
+            max_fee_per_gas: Some(tx.gas_fee_cap),
             input: tx.call_data.clone(),
             access_list: tx.access_list.clone(),
             v: tx.v.into(),
@@ -154,15 +185,27 @@ impl From<&Transaction> for crate::Transaction {
 
 impl From<&crate::Transaction> for Transaction {
     fn from(tx: &crate::Transaction) -> Transaction {
+        let transaction_type = tx.transaction_type.unwrap_or_default().as_u64();
+        let gas_price = tx.gas_price.unwrap_or_default();
+        let (gas_fee_cap, gas_tip_cap) = if transaction_type == DYNAMIC_FEE_TX_TYPE {
+            (
+                tx.max_fee_per_gas.unwrap_or_default(),
+                tx.max_priority_fee_per_gas.unwrap_or_default(),
+            )
+        } else {
+            (gas_price, gas_price)
+        };
         Transaction {
+            transaction_type,
+            block_number: tx.block_number.unwrap_or_default().as_u64(),
             from: tx.from,
             to: tx.to,
             nonce: tx.nonce,
             gas_limit: tx.gas,
             value: tx.value,
-            gas_price: tx.gas_price.unwrap_or_default(),
-            gas_fee_cap: tx.max_priority_fee_per_gas.unwrap_or_default(),
-            gas_tip_cap: tx.max_fee_per_gas.unwrap_or_default(),
+            gas_price,
+            gas_fee_cap,
+            gas_tip_cap,
             call_data: tx.input.clone(),
             access_list: tx.access_list.clone(),
             v: tx.v.as_u64(),
@@ -174,18 +217,80 @@ impl From<&crate::Transaction> for Transaction {
 
 impl From<&Transaction> for TransactionRequest {
     fn from(tx: &Transaction) -> TransactionRequest {
-        TransactionRequest::new()
+        let req = TransactionRequest::new()
             .from(tx.from)
-            .to(tx.to.unwrap())
             .nonce(tx.nonce)
             .value(tx.value)
             .data(tx.call_data.clone())
             .gas(tx.gas_limit)
-            .gas_price(tx.gas_price)
+            .gas_price(tx.gas_price);
+        match tx.to {
+            Some(to) => req.to(to),
+            None => req,
+        }
+    }
+}
+
+impl From<&Transaction> for Eip1559TransactionRequest {
+    fn from(tx: &Transaction) -> Eip1559TransactionRequest {
+        let req = Eip1559TransactionRequest::new()
+            .from(tx.from)
+            .nonce(tx.nonce)
+            .value(tx.value)
+            .data(tx.call_data.clone())
+            .gas(tx.gas_limit)
+            .max_fee_per_gas(tx.gas_fee_cap)
+            .max_priority_fee_per_gas(tx.gas_tip_cap)
+            .access_list(tx.access_list.clone().unwrap_or_default());
+        match tx.to {
+            Some(to) => req.to(to),
+            None => req,
+        }
     }
 }
 
 impl Transaction {
+    /// Whether this is a legacy transaction signed with replay protection
+    /// (EIP-155), which includes the chain id in its signing payload.
+    pub fn is_eip155(&self) -> bool {
+        self.transaction_type == 0 && self.v != 27 && self.v != 28
+    }
+
+    /// Return the payload whose keccak hash is signed by the sender of this
+    /// Transaction.
+    pub fn sign_payload(&self, chain_id: u64) -> Vec<u8> {
+        match self.transaction_type {
+            ACCESS_LIST_TX_TYPE => {
+                // 0x01 || rlp([chain_id, nonce, gasPrice, gas, to, value, data,
+                // access_list])
+                let req: TransactionRequest = self.into();
+                let req = Eip2930TransactionRequest::new(
+                    req.chain_id(chain_id),
+                    self.access_list.clone().unwrap_or_default(),
+                );
+                TypedTransaction::Eip2930(req).rlp().to_vec()
+            }
+            DYNAMIC_FEE_TX_TYPE => {
+                // 0x02 || rlp([chain_id, nonce, gasTipCap, gasFeeCap, gas, to, value,
+                // data, access_list])
+                let req: Eip1559TransactionRequest = self.into();
+                TypedTransaction::Eip1559(req.chain_id(chain_id))
+                    .rlp()
+                    .to_vec()
+            }
+            _ if self.is_eip155() => {
+                // rlp([nonce, gasPrice, gas, to, value, data, chain_id, 0, 0])
+                let req: TransactionRequest = self.into();
+                req.chain_id(chain_id).rlp().to_vec()
+            }
+            _ => {
+                // rlp([nonce, gasPrice, gas, to, value, data])
+                let req: TransactionRequest = self.into();
+                req.rlp().to_vec()
+            }
+        }
+    }
+
     /// Return the SignData associated with this Transaction.
     pub fn sign_data(&self, chain_id: u64) -> Result<SignData, Error> {
         let sig_r_le = self.r.to_le_bytes();
@@ -198,15 +303,20 @@ impl Transaction {
             secp256k1::Fq::from_repr(sig_s_le),
             Error::Signature(libsecp256k1::Error::InvalidSignature),
         )?;
-        // msg = rlp([nonce, gasPrice, gas, to, value, data, sig_v, r, s])
-        let req: TransactionRequest = self.into();
-        let msg = req.chain_id(chain_id).rlp();
+        let msg = self.sign_payload(chain_id);
+        let v = if self.transaction_type != 0 {
+            // The signature of a typed transaction holds the y parity as v.
+            self.v as u8
+        } else if self.is_eip155() {
+            (self.v - 35 - chain_id * 2) as u8
+        } else {
+            (self.v - 27) as u8
+        };
         let msg_hash: [u8; 32] = Keccak256::digest(&msg)
             .as_slice()
             .to_vec()
             .try_into()
             .expect("hash length isn't 32 bytes");
-        let v = (self.v - 35 - chain_id * 2) as u8;
         let pk = recover_pk(v, &self.r, &self.s, &msg_hash)?;
         // msg_hash = msg_hash % q
         let msg_hash = BigUint::from_bytes_be(msg_hash.as_slice());
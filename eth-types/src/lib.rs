@@ -20,7 +20,9 @@ pub mod error;
 pub mod bytecode;
 pub mod evm_types;
 pub mod geth_types;
+pub mod mpt;
 pub mod sign_types;
+pub mod trace_stream;
 
 pub use bytecode::Bytecode;
 pub use error::Error;
@@ -37,7 +39,10 @@ use crate::evm_types::{Gas, GasCost, OpcodeId, ProgramCounter};
 pub use ethers_core::abi::ethereum_types::U512;
 use ethers_core::types;
 pub use ethers_core::types::{
-    transaction::{eip2930::AccessList, response::Transaction},
+    transaction::{
+        eip2930::{AccessList, AccessListItem},
+        response::Transaction,
+    },
     Address, Block, Bytes, Signature, H160, H256, H64, U256, U64,
 };
 
@@ -205,7 +210,7 @@ impl<F: Field> ToScalar<F> for Address {
 }
 
 /// Struct used to define the storage proof
-#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct StorageProof {
     /// Storage key
     pub key: U256,
@@ -216,7 +221,7 @@ pub struct StorageProof {
 }
 
 /// Struct used to define the result of `eth_getProof` call
-#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct EIP1186ProofResponse {
     /// Account address
@@ -259,7 +264,7 @@ struct GethExecStepInternal {
 
 /// The execution step type returned by geth RPC debug_trace* methods.
 /// Corresponds to `StructLogRes` in `go-ethereum/internal/ethapi/api.go`.
-#[derive(Clone, Eq, PartialEq, Serialize)]
+#[derive(Clone, Eq, PartialEq)]
 #[doc(hidden)]
 pub struct GethExecStep {
     pub pc: ProgramCounter,
@@ -343,6 +348,38 @@ impl<'de> Deserialize<'de> for GethExecStep {
     }
 }
 
+// Serialize in the same format as geth, so that a serialized step can be
+// deserialized back.
+impl Serialize for GethExecStep {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        let mut ser = serializer.serialize_struct("GethExecStep", 10)?;
+        ser.serialize_field("pc", &self.pc)?;
+        ser.serialize_field("op", &self.op)?;
+        ser.serialize_field("gas", &self.gas)?;
+        ser.serialize_field("refund", &self.refund)?;
+        ser.serialize_field("gasCost", &self.gas_cost)?;
+        ser.serialize_field("depth", &self.depth)?;
+        ser.serialize_field("error", &self.error)?;
+        ser.serialize_field("stack", &self.stack)?;
+        ser.serialize_field(
+            "memory",
+            &self
+                .memory
+                .0
+                .chunks(32)
+                .map(hex::encode)
+                .collect::<Vec<_>>(),
+        )?;
+        ser.serialize_field("storage", &self.storage)?;
+        ser.end()
+    }
+}
+
 /// Helper type built to deal with the weird `result` field added between
 /// `GethExecutionTrace`s in `debug_traceBlockByHash` and
 /// `debug_traceBlockByNumber` Geth JSON-RPC calls.
@@ -378,6 +415,77 @@ pub struct GethExecTrace {
     pub struct_logs: Vec<GethExecStep>,
 }
 
+/// Helper type built to deal with the `result` field added around the result
+/// of a built-in geth tracer for each transaction in `debug_traceBlockByHash`
+/// and `debug_traceBlockByNumber` Geth JSON-RPC calls.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize)]
+#[doc(hidden)]
+pub struct ResultGethTrace<T> {
+    pub result: T,
+}
+
+/// The state of the accounts accessed by a transaction before its execution,
+/// as returned by the geth `prestateTracer`.
+pub type GethPrestateTrace = HashMap<Address, GethPrestateAccount>;
+
+/// The state of an account before the execution of a transaction, as returned
+/// by the geth `prestateTracer`.  Corresponds to `account` in
+/// `go-ethereum/eth/tracers/native/prestate.go`.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, Eq, PartialEq)]
+pub struct GethPrestateAccount {
+    /// Balance of the account
+    pub balance: Option<Word>,
+    /// Nonce of the account
+    pub nonce: Option<u64>,
+    /// Code of the account, when it's not empty
+    pub code: Option<Bytes>,
+    /// Storage slots accessed by the transaction, with their values
+    #[serde(default)]
+    pub storage: HashMap<H256, H256>,
+}
+
+/// A call frame of a transaction, as returned by the geth `callTracer`.
+/// Corresponds to `callFrame` in `go-ethereum/eth/tracers/native/call.go`.
+#[derive(Deserialize, Serialize, Clone, Debug, Eq, PartialEq)]
+pub struct GethCallTrace {
+    /// Opcode of the call, or `CALL`/`CREATE` for the transaction.
+    #[serde(rename = "type")]
+    pub call_type: OpcodeId,
+    /// Address of the caller
+    pub from: Address,
+    /// Address of the callee, or of the code for `DELEGATECALL` and
+    /// `CALLCODE`
+    pub to: Option<Address>,
+    /// Value transferred
+    pub value: Option<Word>,
+    /// Gas given to the call
+    pub gas: U64,
+    /// Gas used by the call
+    #[serde(rename = "gasUsed")]
+    pub gas_used: U64,
+    /// Input of the call
+    pub input: Bytes,
+    /// Output of the call
+    pub output: Option<Bytes>,
+    /// Error of the call when it failed
+    pub error: Option<String>,
+    /// Subcalls made by the call, in order
+    #[serde(default)]
+    pub calls: Vec<GethCallTrace>,
+}
+
+impl GethCallTrace {
+    /// Return the call frames of the tree of calls rooted in this frame, in
+    /// the order they were made.
+    pub fn flatten(&self) -> Vec<&GethCallTrace> {
+        let mut calls = vec![self];
+        for call in self.calls.iter() {
+            calls.extend(call.flatten());
+        }
+        calls
+    }
+}
+
 #[macro_export]
 /// Create an [`Address`] from a hex string.  Panics on invalid input.
 macro_rules! address {
@@ -551,6 +659,121 @@ mod tests {
             }
         );
     }
+
+    #[test]
+    fn serialize_geth_exec_trace() {
+        let trace = GethExecTrace {
+            gas: Gas(21100),
+            failed: true,
+            return_value: "".to_owned(),
+            struct_logs: vec![
+                GethExecStep {
+                    pc: ProgramCounter(0),
+                    op: OpcodeId::MSTORE,
+                    gas: Gas(100),
+                    refund: Gas(0),
+                    gas_cost: GasCost(6),
+                    depth: 1,
+                    error: None,
+                    stack: Stack(vec![word!("0x1234"), word!("0x0")]),
+                    storage: Storage(word_map!("0x1" => "0x6f")),
+                    memory: Memory::from(vec![word!("0x0"), word!("0x1234")]),
+                },
+                GethExecStep {
+                    pc: ProgramCounter(1),
+                    op: OpcodeId::INVALID(0xfe),
+                    gas: Gas(94),
+                    refund: Gas(0),
+                    gas_cost: GasCost(0),
+                    depth: 1,
+                    error: Some("invalid opcode: INVALID".to_owned()),
+                    stack: Stack::new(),
+                    storage: Storage(word_map!()),
+                    memory: Memory::new(),
+                },
+            ],
+        };
+        let trace_json = serde_json::to_string(&trace).expect("json-serialize GethExecTrace");
+        assert_eq!(
+            serde_json::from_str::<GethExecTrace>(&trace_json)
+                .expect("json-deserialize GethExecTrace"),
+            trace
+        );
+    }
+
+    #[test]
+    fn deserialize_geth_prestate_trace() {
+        let trace_json = r#"
+  {
+    "0x0000000000000000000000000000000000000000": {
+      "balance": "0x1f40"
+    },
+    "0x000000000000000000000000000000000cafe001": {
+      "balance": "0x0",
+      "nonce": 1,
+      "code": "0x6001600255",
+      "storage": {
+        "0x0000000000000000000000000000000000000000000000000000000000000002": "0x000000000000000000000000000000000000000000000000000000000000006f"
+      }
+    }
+  }
+        "#;
+        let trace: GethPrestateTrace =
+            serde_json::from_str(trace_json).expect("json-deserialize GethPrestateTrace");
+        assert_eq!(
+            trace[&address!("0x0000000000000000000000000000000000000000")],
+            GethPrestateAccount {
+                balance: Some(Word::from(0x1f40)),
+                ..Default::default()
+            }
+        );
+        let account = &trace[&address!("0x000000000000000000000000000000000cafe001")];
+        assert_eq!(account.nonce, Some(1));
+        assert_eq!(
+            account.code,
+            Some(Bytes::from(vec![0x60, 0x01, 0x60, 0x02, 0x55]))
+        );
+        assert_eq!(
+            account.storage[&H256::from_low_u64_be(2)],
+            H256::from_low_u64_be(0x6f)
+        );
+    }
+
+    #[test]
+    fn deserialize_geth_call_trace() {
+        let trace_json = r#"
+  {
+    "type": "CALL",
+    "from": "0x000000000000000000000000000000000cafe002",
+    "to": "0x0000000000000000000000000000000000000000",
+    "value": "0x0",
+    "gas": "0x5f5e100",
+    "gasUsed": "0xa410",
+    "input": "0x",
+    "output": "0x",
+    "calls": [
+      {
+        "type": "STATICCALL",
+        "from": "0x0000000000000000000000000000000000000000",
+        "to": "0x000000000000000000000000000000000cafe001",
+        "gas": "0x10000",
+        "gasUsed": "0x5e",
+        "input": "0x",
+        "error": "execution reverted"
+      }
+    ]
+  }
+        "#;
+        let trace: GethCallTrace =
+            serde_json::from_str(trace_json).expect("json-deserialize GethCallTrace");
+        let calls = trace.flatten();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(calls[0].call_type, OpcodeId::CALL);
+        assert_eq!(calls[1].call_type, OpcodeId::STATICCALL);
+        assert_eq!(calls[1].value, None);
+        assert_eq!(calls[1].gas_used, U64::from(0x5e));
+        assert_eq!(calls[1].error.as_deref(), Some("execution reverted"));
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,715 @@
+//! In-memory Merkle Patricia Trie, as used by Ethereum for the state trie and
+//! the account storage tries.
+//!
+//! Nodes that are only known by their hash (for example the siblings of the
+//! nodes returned by `eth_getProof`) are kept as [`TrieNode::Hash`], so that a
+//! partial trie can be read and updated as long as the touched paths are fully
+//! known.  Operations that need the content of such a node return
+//! [`Error::MissingTrieNode`].
+
+use crate::{Address, Error, ToBigEndian, Word, H256};
+use ethers_core::utils::{
+    keccak256,
+    rlp::{self, Rlp, RlpStream},
+};
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+
+lazy_static! {
+    /// Root hash of the empty trie: `keccak256(rlp(""))`.
+    pub static ref EMPTY_TRIE_HASH: H256 = H256(keccak256([0x80]));
+}
+
+/// Number of nibbles of the (hashed) keys of the state and storage tries.
+pub const KEY_NIBBLES: usize = 64;
+
+/// A node of the Merkle Patricia Trie.  Paths are stored as nibbles.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TrieNode {
+    /// Empty node, only found as the root of an empty trie or as an unused
+    /// branch child.
+    Empty,
+    /// Leaf node, holding the remaining path of the key and its value.
+    Leaf {
+        /// Remaining nibbles of the key
+        path: Vec<u8>,
+        /// RLP encoded value
+        value: Vec<u8>,
+    },
+    /// Extension node, holding the nibbles shared by all the keys below it.
+    Extension {
+        /// Shared nibbles
+        path: Vec<u8>,
+        /// Child node, always a branch (or a hash of a branch)
+        child: Box<TrieNode>,
+    },
+    /// Branch node.  Branch values are never used in the tries of Ethereum
+    /// because all keys have the same length, so they are not represented.
+    Branch {
+        /// Child nodes indexed by nibble
+        children: Box<[TrieNode; 16]>,
+    },
+    /// Node that is only known by its hash.
+    Hash(H256),
+}
+
+impl Default for TrieNode {
+    fn default() -> Self {
+        Self::Empty
+    }
+}
+
+impl TrieNode {
+    /// Returns the RLP encoding of the node.
+    ///
+    /// # Panics
+    ///
+    /// Panics when called on a [`TrieNode::Hash`], whose content is unknown.
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Self::Empty => vec![0x80],
+            Self::Leaf { path, value } => {
+                let mut stream = RlpStream::new_list(2);
+                stream.append(&hex_prefix(path, true));
+                stream.append(value);
+                stream.out().to_vec()
+            }
+            Self::Extension { path, child } => {
+                let mut stream = RlpStream::new_list(2);
+                stream.append(&hex_prefix(path, false));
+                child.append_reference(&mut stream);
+                stream.out().to_vec()
+            }
+            Self::Branch { children } => {
+                let mut stream = RlpStream::new_list(17);
+                for child in children.iter() {
+                    child.append_reference(&mut stream);
+                }
+                stream.append_empty_data();
+                stream.out().to_vec()
+            }
+            Self::Hash(_) => unreachable!("the content of a hash node is unknown"),
+        }
+    }
+
+    /// Decodes a node from its RLP encoding.  The children that are referenced
+    /// by their hash are decoded as [`TrieNode::Hash`].
+    pub fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        Self::decode_rlp(&Rlp::new(bytes))
+    }
+
+    fn decode_rlp(rlp: &Rlp) -> Result<Self, Error> {
+        let invalid = |_| Error::InvalidTrieNode;
+        if rlp.is_empty() {
+            return Ok(Self::Empty);
+        }
+        match rlp.item_count().map_err(invalid)? {
+            2 => {
+                let encoded_path = rlp.at(0).and_then(|path| path.data()).map_err(invalid)?;
+                let (path, is_leaf) = decode_hex_prefix(encoded_path)?;
+                let second = rlp.at(1).map_err(invalid)?;
+                if is_leaf {
+                    Ok(Self::Leaf {
+                        path,
+                        value: second.data().map_err(invalid)?.to_vec(),
+                    })
+                } else {
+                    Ok(Self::Extension {
+                        path,
+                        child: Box::new(Self::decode_reference(&second)?),
+                    })
+                }
+            }
+            17 => {
+                let mut children: [TrieNode; 16] = Default::default();
+                for (index, child) in children.iter_mut().enumerate() {
+                    *child = Self::decode_reference(&rlp.at(index).map_err(invalid)?)?;
+                }
+                if !rlp.at(16).map_err(invalid)?.is_empty() {
+                    return Err(Error::InvalidTrieNode);
+                }
+                Ok(Self::Branch {
+                    children: Box::new(children),
+                })
+            }
+            _ => Err(Error::InvalidTrieNode),
+        }
+    }
+
+    /// Decodes the reference to a child node, which is either the node itself
+    /// or its hash.
+    fn decode_reference(rlp: &Rlp) -> Result<Self, Error> {
+        if rlp.is_list() {
+            return Self::decode_rlp(rlp);
+        }
+        let data = rlp.data().map_err(|_| Error::InvalidTrieNode)?;
+        match data.len() {
+            0 => Ok(Self::Empty),
+            32 => Ok(Self::Hash(H256::from_slice(data))),
+            _ => Err(Error::InvalidTrieNode),
+        }
+    }
+
+    /// Returns the keccak256 hash of the RLP encoding of the node.
+    pub fn hash(&self) -> H256 {
+        match self {
+            Self::Hash(hash) => *hash,
+            _ => H256(keccak256(self.encode())),
+        }
+    }
+
+    /// Appends the reference to this node from its parent, which is the node
+    /// itself when its encoding is shorter than 32 bytes and its hash
+    /// otherwise.
+    fn append_reference(&self, stream: &mut RlpStream) {
+        match self {
+            Self::Empty => {
+                stream.append_empty_data();
+            }
+            Self::Hash(hash) => {
+                stream.append(hash);
+            }
+            _ => {
+                let encoded = self.encode();
+                if encoded.len() < 32 {
+                    stream.append_raw(&encoded, 1);
+                } else {
+                    stream.append(&H256(keccak256(encoded)));
+                }
+            }
+        }
+    }
+}
+
+/// Merkle Patricia Trie keyed by byte strings of equal length.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Trie {
+    root: TrieNode,
+}
+
+impl Trie {
+    /// Create a new empty trie.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a trie from its root node.
+    pub fn from_root(root: TrieNode) -> Self {
+        Self { root }
+    }
+
+    /// Create a trie that is only known by its root hash, whose nodes can be
+    /// added with [`Trie::load_proof`].
+    pub fn from_root_hash(root: H256) -> Self {
+        if root == *EMPTY_TRIE_HASH {
+            Self::new()
+        } else {
+            Self::from_root(TrieNode::Hash(root))
+        }
+    }
+
+    /// Returns the root node.
+    pub fn root(&self) -> &TrieNode {
+        &self.root
+    }
+
+    /// Returns the root hash.
+    pub fn root_hash(&self) -> H256 {
+        self.root.hash()
+    }
+
+    /// Returns the value stored under `key`, or `None` if there is no such
+    /// key in the trie.
+    pub fn get(&self, key: &[u8]) -> Result<Option<&[u8]>, Error> {
+        let path = nibbles(key);
+        let mut path = path.as_slice();
+        let mut node = &self.root;
+        loop {
+            node = match node {
+                TrieNode::Empty => return Ok(None),
+                TrieNode::Leaf {
+                    path: leaf_path,
+                    value,
+                } => return Ok((leaf_path.as_slice() == path).then_some(value.as_slice())),
+                TrieNode::Extension {
+                    path: ext_path,
+                    child,
+                } => {
+                    if !path.starts_with(ext_path) {
+                        return Ok(None);
+                    }
+                    path = &path[ext_path.len()..];
+                    child.as_ref()
+                }
+                TrieNode::Branch { children } => {
+                    let (nibble, rest) = path.split_first().ok_or(Error::InvalidTrieKey)?;
+                    path = rest;
+                    &children[*nibble as usize]
+                }
+                TrieNode::Hash(hash) => return Err(Error::MissingTrieNode(*hash)),
+            }
+        }
+    }
+
+    /// Set the value stored under `key`.  An empty value removes the key.
+    pub fn insert(&mut self, key: &[u8], value: Vec<u8>) -> Result<(), Error> {
+        if value.is_empty() {
+            return self.remove(key);
+        }
+        insert_at(&mut self.root, &nibbles(key), value)
+    }
+
+    /// Remove `key` from the trie.  Removing a key that doesn't exist is a
+    /// no-op.
+    pub fn remove(&mut self, key: &[u8]) -> Result<(), Error> {
+        remove_at(&mut self.root, &nibbles(key))
+    }
+
+    /// Add to the trie the nodes on the path to `key`, given by a proof in the
+    /// format of `eth_getProof`.  The proof must start at the root of the
+    /// trie.  Nodes of the proof that are not on the path are ignored.
+    pub fn load_proof<T: AsRef<[u8]>>(&mut self, key: &[u8], proof: &[T]) -> Result<(), Error> {
+        let nodes: HashMap<H256, &[u8]> = proof
+            .iter()
+            .map(|node| (H256(keccak256(node.as_ref())), node.as_ref()))
+            .collect();
+        let path = nibbles(key);
+        let mut path = path.as_slice();
+        let mut node = &mut self.root;
+        loop {
+            if let TrieNode::Hash(hash) = *node {
+                let bytes = nodes.get(&hash).ok_or(Error::MissingTrieNode(hash))?;
+                *node = TrieNode::decode(bytes)?;
+            }
+            node = match node {
+                TrieNode::Extension {
+                    path: ext_path,
+                    child,
+                } if path.starts_with(ext_path) => {
+                    path = &path[ext_path.len()..];
+                    child.as_mut()
+                }
+                TrieNode::Branch { children } if !path.is_empty() => {
+                    let child = &mut children[path[0] as usize];
+                    path = &path[1..];
+                    child
+                }
+                _ => return Ok(()),
+            };
+        }
+    }
+
+    /// Returns the RLP encoded nodes on the path from the root to `key`, in
+    /// the same format as `eth_getProof`: nodes that are embedded in their
+    /// parent because their encoding is shorter than 32 bytes are not
+    /// returned separately.
+    pub fn proof(&self, key: &[u8]) -> Result<Vec<Vec<u8>>, Error> {
+        let path = nibbles(key);
+        let mut path = path.as_slice();
+        let mut node = &self.root;
+        let mut proof = Vec::new();
+        loop {
+            match node {
+                TrieNode::Empty => break,
+                TrieNode::Hash(hash) => return Err(Error::MissingTrieNode(*hash)),
+                _ => {
+                    let encoded = node.encode();
+                    if proof.is_empty() || encoded.len() >= 32 {
+                        proof.push(encoded);
+                    }
+                }
+            }
+            node = match node {
+                TrieNode::Extension {
+                    path: ext_path,
+                    child,
+                } if path.starts_with(ext_path) => {
+                    path = &path[ext_path.len()..];
+                    child.as_ref()
+                }
+                TrieNode::Branch { children } if !path.is_empty() => {
+                    let child = &children[path[0] as usize];
+                    path = &path[1..];
+                    child
+                }
+                _ => break,
+            };
+        }
+        Ok(proof)
+    }
+}
+
+fn insert_at(node: &mut TrieNode, path: &[u8], value: Vec<u8>) -> Result<(), Error> {
+    match node {
+        TrieNode::Empty => {
+            *node = TrieNode::Leaf {
+                path: path.to_vec(),
+                value,
+            };
+        }
+        TrieNode::Leaf {
+            path: leaf_path,
+            value: leaf_value,
+        } => {
+            if leaf_path.as_slice() == path {
+                *leaf_value = value;
+                return Ok(());
+            }
+            let common = common_prefix_len(leaf_path, path);
+            if common == leaf_path.len() || common == path.len() {
+                return Err(Error::InvalidTrieKey);
+            }
+            let mut children: [TrieNode; 16] = Default::default();
+            children[leaf_path[common] as usize] = TrieNode::Leaf {
+                path: leaf_path[common + 1..].to_vec(),
+                value: std::mem::take(leaf_value),
+            };
+            children[path[common] as usize] = TrieNode::Leaf {
+                path: path[common + 1..].to_vec(),
+                value,
+            };
+            *node = with_prefix(
+                path[..common].to_vec(),
+                TrieNode::Branch {
+                    children: Box::new(children),
+                },
+            );
+        }
+        TrieNode::Extension {
+            path: ext_path,
+            child,
+        } => {
+            let common = common_prefix_len(ext_path, path);
+            if common == ext_path.len() {
+                return insert_at(child, &path[common..], value);
+            }
+            if common == path.len() {
+                return Err(Error::InvalidTrieKey);
+            }
+            let mut children: [TrieNode; 16] = Default::default();
+            children[ext_path[common] as usize] = with_prefix(
+                ext_path[common + 1..].to_vec(),
+                std::mem::take(child.as_mut()),
+            );
+            children[path[common] as usize] = TrieNode::Leaf {
+                path: path[common + 1..].to_vec(),
+                value,
+            };
+            *node = with_prefix(
+                path[..common].to_vec(),
+                TrieNode::Branch {
+                    children: Box::new(children),
+                },
+            );
+        }
+        TrieNode::Branch { children } => {
+            let (nibble, rest) = path.split_first().ok_or(Error::InvalidTrieKey)?;
+            insert_at(&mut children[*nibble as usize], rest, value)?;
+        }
+        TrieNode::Hash(hash) => return Err(Error::MissingTrieNode(*hash)),
+    }
+    Ok(())
+}
+
+fn remove_at(node: &mut TrieNode, path: &[u8]) -> Result<(), Error> {
+    match node {
+        TrieNode::Empty => {}
+        TrieNode::Leaf {
+            path: leaf_path, ..
+        } => {
+            if leaf_path.as_slice() == path {
+                *node = TrieNode::Empty;
+            }
+        }
+        TrieNode::Extension {
+            path: ext_path,
+            child,
+        } => {
+            if path.starts_with(ext_path) {
+                remove_at(child, &path[ext_path.len()..])?;
+                *node = with_prefix(std::mem::take(ext_path), std::mem::take(child.as_mut()));
+            }
+        }
+        TrieNode::Branch { children } => {
+            let (nibble, rest) = path.split_first().ok_or(Error::InvalidTrieKey)?;
+            remove_at(&mut children[*nibble as usize], rest)?;
+
+            let mut remaining = (0..16).filter(|i| children[*i] != TrieNode::Empty);
+            match (remaining.next(), remaining.next()) {
+                (None, _) => *node = TrieNode::Empty,
+                // A branch with a single child is merged into it, for which we
+                // need to know the kind of the child.
+                (Some(index), None) => {
+                    if let TrieNode::Hash(hash) = children[index] {
+                        return Err(Error::MissingTrieNode(hash));
+                    }
+                    let child = std::mem::take(&mut children[index]);
+                    *node = with_prefix(vec![index as u8], child);
+                }
+                _ => {}
+            }
+        }
+        TrieNode::Hash(hash) => return Err(Error::MissingTrieNode(*hash)),
+    }
+    Ok(())
+}
+
+/// Prepends `prefix` to the path of `node`, merging it into the path of leaf
+/// and extension nodes and creating an extension node otherwise.
+fn with_prefix(mut prefix: Vec<u8>, node: TrieNode) -> TrieNode {
+    match node {
+        TrieNode::Empty => TrieNode::Empty,
+        TrieNode::Leaf { path, value } => {
+            prefix.extend(path);
+            TrieNode::Leaf {
+                path: prefix,
+                value,
+            }
+        }
+        TrieNode::Extension { path, child } => {
+            prefix.extend(path);
+            TrieNode::Extension {
+                path: prefix,
+                child,
+            }
+        }
+        node if prefix.is_empty() => node,
+        node => TrieNode::Extension {
+            path: prefix,
+            child: Box::new(node),
+        },
+    }
+}
+
+fn common_prefix_len(a: &[u8], b: &[u8]) -> usize {
+    a.iter().zip(b.iter()).take_while(|(a, b)| a == b).count()
+}
+
+/// Split a byte string into nibbles, most significant nibble first.
+pub fn nibbles(key: &[u8]) -> Vec<u8> {
+    key.iter()
+        .flat_map(|byte| [byte >> 4, byte & 0xf])
+        .collect()
+}
+
+/// Hex-prefix encoding of a path of nibbles, as used in leaf and extension
+/// nodes.
+pub fn hex_prefix(path: &[u8], is_leaf: bool) -> Vec<u8> {
+    let flag = if is_leaf { 0x20 } else { 0x00 };
+    let mut encoded = Vec::with_capacity(path.len() / 2 + 1);
+    let rest = if path.len() % 2 == 1 {
+        encoded.push(flag | 0x10 | path[0]);
+        &path[1..]
+    } else {
+        encoded.push(flag);
+        path
+    };
+    encoded.extend(rest.chunks(2).map(|pair| (pair[0] << 4) | pair[1]));
+    encoded
+}
+
+/// Decodes a hex prefix encoded path into its nibbles and whether it is the
+/// path of a leaf.
+fn decode_hex_prefix(encoded: &[u8]) -> Result<(Vec<u8>, bool), Error> {
+    let mut path = nibbles(encoded);
+    let flag = *path.first().ok_or(Error::InvalidTrieNode)?;
+    if flag > 3 || (flag & 1 == 0 && path[1] != 0) {
+        return Err(Error::InvalidTrieNode);
+    }
+    path.drain(0..if flag & 1 == 1 { 1 } else { 2 });
+    Ok((path, flag & 2 == 2))
+}
+
+/// Returns the key of an account in the state trie.
+pub fn account_key(address: &Address) -> [u8; 32] {
+    keccak256(address.as_bytes())
+}
+
+/// Returns the key of a storage slot in an account storage trie.
+pub fn storage_key(key: &Word) -> [u8; 32] {
+    keccak256(key.to_be_bytes())
+}
+
+/// RLP encoding of an account, as stored in the leaves of the state trie.
+pub fn encode_account(nonce: Word, balance: Word, storage_root: H256, code_hash: H256) -> Vec<u8> {
+    let mut stream = RlpStream::new_list(4);
+    stream
+        .append(&nonce)
+        .append(&balance)
+        .append(&storage_root)
+        .append(&code_hash);
+    stream.out().to_vec()
+}
+
+/// RLP encoding of a storage value, as stored in the leaves of a storage
+/// trie.  Zero values are not stored, so they are encoded as an empty value.
+pub fn encode_storage_value(value: Word) -> Vec<u8> {
+    if value.is_zero() {
+        vec![]
+    } else {
+        rlp::encode(&value).to_vec()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::address;
+
+    fn hex_to_bytes(s: &str) -> Vec<u8> {
+        hex::decode(s).unwrap()
+    }
+
+    #[test]
+    fn empty_trie_root() {
+        assert_eq!(
+            Trie::new().root_hash(),
+            H256::from_slice(&hex_to_bytes(
+                "56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421"
+            ))
+        );
+    }
+
+    #[test]
+    fn hex_prefix_encoding() {
+        assert_eq!(hex_prefix(&[1, 2, 3, 4, 5], false), vec![0x11, 0x23, 0x45]);
+        assert_eq!(
+            hex_prefix(&[0, 1, 2, 3, 4, 5], false),
+            vec![0x00, 0x01, 0x23, 0x45]
+        );
+        assert_eq!(
+            hex_prefix(&[0xf, 1, 0xc, 0xb, 8], true),
+            vec![0x3f, 0x1c, 0xb8]
+        );
+        assert_eq!(
+            hex_prefix(&[0, 0xf, 1, 0xc, 0xb, 8], true),
+            vec![0x20, 0x0f, 0x1c, 0xb8]
+        );
+    }
+
+    // Test vector "emptyValues" from ethereum/tests
+    // `TrieTests/trietest_secureTrie.json`.
+    #[test]
+    fn secure_trie_root() {
+        let mut trie = Trie::new();
+        for (key, value) in [
+            ("do", "verb"),
+            ("ether", "wookiedoo"),
+            ("horse", "stallion"),
+            ("shaman", "horse"),
+            ("doge", "coin"),
+            ("ether", ""),
+            ("dog", "puppy"),
+            ("shaman", ""),
+        ] {
+            trie.insert(&keccak256(key.as_bytes()), value.as_bytes().to_vec())
+                .unwrap();
+        }
+        assert_eq!(
+            trie.root_hash(),
+            H256::from_slice(&hex_to_bytes(
+                "29b235a58c3c25ab83010c327d5932bcf05324b7d6b1185e650798034783ca9d"
+            ))
+        );
+    }
+
+    #[test]
+    fn insert_remove_roundtrip() {
+        let mut trie = Trie::new();
+        let addresses = [
+            address!("0x00000000000000000000000000000000000000aa"),
+            address!("0x00000000000000000000000000000000000000bb"),
+            address!("0x00000000000000000000000000000000000000cc"),
+        ];
+        let roots: Vec<_> = addresses
+            .iter()
+            .enumerate()
+            .map(|(i, address)| {
+                let account = encode_account(
+                    Word::from(i),
+                    Word::from(100),
+                    *EMPTY_TRIE_HASH,
+                    H256::zero(),
+                );
+                trie.insert(&account_key(address), account.clone()).unwrap();
+                assert_eq!(
+                    trie.get(&account_key(address)).unwrap(),
+                    Some(account.as_slice())
+                );
+                trie.root_hash()
+            })
+            .collect();
+
+        for (i, address) in addresses.iter().enumerate().rev() {
+            assert_eq!(trie.root_hash(), roots[i]);
+            trie.remove(&account_key(address)).unwrap();
+            assert_eq!(trie.get(&account_key(address)).unwrap(), None);
+        }
+        assert_eq!(trie.root_hash(), *EMPTY_TRIE_HASH);
+    }
+
+    #[test]
+    fn decode_roundtrip() {
+        let mut trie = Trie::new();
+        for i in 0..32u64 {
+            trie.insert(
+                &storage_key(&Word::from(i)),
+                encode_storage_value(Word::from(i + 1)),
+            )
+            .unwrap();
+        }
+        for node in trie.proof(&storage_key(&Word::from(3))).unwrap() {
+            assert_eq!(TrieNode::decode(&node).unwrap().encode(), node);
+        }
+    }
+
+    #[test]
+    fn load_proof_of_partial_trie() {
+        let mut trie = Trie::new();
+        for i in 0..32u64 {
+            trie.insert(
+                &storage_key(&Word::from(i)),
+                encode_storage_value(Word::from(i + 1)),
+            )
+            .unwrap();
+        }
+        let key = storage_key(&Word::from(5));
+        let mut partial = Trie::from_root_hash(trie.root_hash());
+        assert!(matches!(
+            partial.get(&key),
+            Err(Error::MissingTrieNode(hash)) if hash == trie.root_hash()
+        ));
+
+        partial
+            .load_proof(&key, &trie.proof(&key).unwrap())
+            .unwrap();
+        assert_eq!(partial.root_hash(), trie.root_hash());
+        assert_eq!(partial.get(&key).unwrap(), trie.get(&key).unwrap());
+
+        for target in [&mut trie, &mut partial] {
+            target
+                .insert(&key, encode_storage_value(Word::from(100)))
+                .unwrap();
+        }
+        assert_eq!(partial.root_hash(), trie.root_hash());
+        assert_eq!(partial.proof(&key).unwrap(), trie.proof(&key).unwrap());
+    }
+
+    #[test]
+    fn proof_starts_at_root() {
+        let mut trie = Trie::new();
+        for i in 0..32u64 {
+            trie.insert(
+                &storage_key(&Word::from(i)),
+                encode_storage_value(Word::from(i + 1)),
+            )
+            .unwrap();
+        }
+        let proof = trie.proof(&storage_key(&Word::from(7))).unwrap();
+        assert_eq!(H256(keccak256(&proof[0])), trie.root_hash());
+        for pair in proof.windows(2) {
+            let hash = keccak256(&pair[1]);
+            assert!(pair[0].windows(32).any(|window| window == hash));
+        }
+    }
+}
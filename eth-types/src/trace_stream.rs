@@ -0,0 +1,300 @@
+//! Streaming deserialization of the execution traces returned by geth.  Each
+//! step of the struct logs is passed to a callback as soon as it's parsed, so
+//! that the steps of a trace are never all in memory at the same time.
+
+use crate::{evm_types::Gas, Error, GethExecStep};
+use serde::de::{self, DeserializeSeed, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use std::{fmt, io::Read};
+
+/// Fields of a [`GethExecTrace`](crate::GethExecTrace) other than its struct
+/// logs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GethExecTraceHeader {
+    /// Used gas
+    pub gas: Gas,
+    /// True when the transaction has failed.
+    pub failed: bool,
+    /// Return value of execution which is a hex encoded byte array
+    pub return_value: String,
+}
+
+/// Deserialize a [`GethExecTrace`](crate::GethExecTrace) from `reader`,
+/// passing each of its steps to `on_step`, and return the other fields of the
+/// trace.  The `reader` should be buffered.
+pub fn stream_geth_exec_trace<R, E, F>(reader: R, mut on_step: F) -> Result<GethExecTraceHeader, E>
+where
+    R: Read,
+    E: From<Error>,
+    F: FnMut(GethExecStep) -> Result<(), E>,
+{
+    let mut error = None;
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let result = TraceSeed {
+        on_step: &mut |step| on_step(step).map_err(|e| error = Some(e)),
+    }
+    .deserialize(&mut deserializer)
+    .and_then(|header| deserializer.end().map(|_| header));
+    result.map_err(|serde_error| {
+        error
+            .take()
+            .unwrap_or_else(|| Error::SerdeError(serde_error).into())
+    })
+}
+
+/// Deserialize the execution traces of the transactions of a block, as
+/// returned by `debug_traceBlockByHash` and `debug_traceBlockByNumber`, from
+/// `reader`.  Each step is passed to `on_step` with the index of its
+/// transaction, and the other fields of each trace are returned.  The
+/// `reader` should be buffered.
+pub fn stream_geth_exec_traces<R, E, F>(
+    reader: R,
+    mut on_step: F,
+) -> Result<Vec<GethExecTraceHeader>, E>
+where
+    R: Read,
+    E: From<Error>,
+    F: FnMut(usize, GethExecStep) -> Result<(), E>,
+{
+    let mut error = None;
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+    let result = BlockSeed {
+        on_step: &mut |index, step| on_step(index, step).map_err(|e| error = Some(e)),
+    }
+    .deserialize(&mut deserializer)
+    .and_then(|headers| deserializer.end().map(|_| headers));
+    result.map_err(|serde_error| {
+        error
+            .take()
+            .unwrap_or_else(|| Error::SerdeError(serde_error).into())
+    })
+}
+
+/// Error returned to serde when a callback fails, which is replaced by the
+/// error of the callback.
+fn callback_error<E: de::Error>(_: ()) -> E {
+    E::custom("step callback failed")
+}
+
+/// Seed of the struct logs of a trace.
+struct StepsSeed<'a, F> {
+    on_step: &'a mut F,
+}
+
+impl<'de, 'a, F: FnMut(GethExecStep) -> Result<(), ()>> DeserializeSeed<'de> for StepsSeed<'a, F> {
+    type Value = ();
+
+    fn deserialize<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'a, F: FnMut(GethExecStep) -> Result<(), ()>> Visitor<'de> for StepsSeed<'a, F> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a sequence of struct logs")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(step) = seq.next_element::<GethExecStep>()? {
+            (self.on_step)(step).map_err(callback_error)?;
+        }
+        Ok(())
+    }
+}
+
+/// Seed of a trace, whose struct logs are passed to `on_step`.
+struct TraceSeed<'a, F> {
+    on_step: &'a mut F,
+}
+
+impl<'de, 'a, F: FnMut(GethExecStep) -> Result<(), ()>> DeserializeSeed<'de> for TraceSeed<'a, F> {
+    type Value = GethExecTraceHeader;
+
+    fn deserialize<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<GethExecTraceHeader, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a, F: FnMut(GethExecStep) -> Result<(), ()>> Visitor<'de> for TraceSeed<'a, F> {
+    type Value = GethExecTraceHeader;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an execution trace")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<GethExecTraceHeader, A::Error> {
+        let (mut gas, mut failed, mut return_value, mut struct_logs) = (None, None, None, false);
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "gas" => gas = Some(map.next_value()?),
+                "failed" => failed = Some(map.next_value()?),
+                "returnValue" => return_value = Some(map.next_value()?),
+                "structLogs" => {
+                    map.next_value_seed(StepsSeed {
+                        on_step: &mut *self.on_step,
+                    })?;
+                    struct_logs = true;
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        if !struct_logs {
+            return Err(de::Error::missing_field("structLogs"));
+        }
+        Ok(GethExecTraceHeader {
+            gas: gas.ok_or_else(|| de::Error::missing_field("gas"))?,
+            failed: failed.ok_or_else(|| de::Error::missing_field("failed"))?,
+            return_value: return_value.ok_or_else(|| de::Error::missing_field("returnValue"))?,
+        })
+    }
+}
+
+/// Seed of the `{"result": trace}` item of a block trace.
+struct ResultSeed<'a, F> {
+    trace: TraceSeed<'a, F>,
+}
+
+impl<'de, 'a, F: FnMut(GethExecStep) -> Result<(), ()>> DeserializeSeed<'de> for ResultSeed<'a, F> {
+    type Value = GethExecTraceHeader;
+
+    fn deserialize<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<GethExecTraceHeader, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, 'a, F: FnMut(GethExecStep) -> Result<(), ()>> Visitor<'de> for ResultSeed<'a, F> {
+    type Value = GethExecTraceHeader;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("an execution trace in a `result` field")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<GethExecTraceHeader, A::Error> {
+        let mut header = None;
+        let mut trace = Some(self.trace);
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "result" {
+                let trace = trace
+                    .take()
+                    .ok_or_else(|| de::Error::duplicate_field("result"))?;
+                header = Some(map.next_value_seed(trace)?);
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+        header.ok_or_else(|| de::Error::missing_field("result"))
+    }
+}
+
+/// Seed of the traces of a block, whose struct logs are passed to `on_step`
+/// with the index of their transaction.
+struct BlockSeed<'a, F> {
+    on_step: &'a mut F,
+}
+
+impl<'de, 'a, F: FnMut(usize, GethExecStep) -> Result<(), ()>> DeserializeSeed<'de>
+    for BlockSeed<'a, F>
+{
+    type Value = Vec<GethExecTraceHeader>;
+
+    fn deserialize<D: de::Deserializer<'de>>(
+        self,
+        deserializer: D,
+    ) -> Result<Vec<GethExecTraceHeader>, D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, 'a, F: FnMut(usize, GethExecStep) -> Result<(), ()>> Visitor<'de> for BlockSeed<'a, F> {
+    type Value = Vec<GethExecTraceHeader>;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a sequence of execution traces")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(
+        self,
+        mut seq: A,
+    ) -> Result<Vec<GethExecTraceHeader>, A::Error> {
+        let mut headers = Vec::new();
+        loop {
+            let index = headers.len();
+            let trace = TraceSeed {
+                on_step: &mut |step| (self.on_step)(index, step),
+            };
+            match seq.next_element_seed(ResultSeed { trace })? {
+                Some(header) => headers.push(header),
+                None => return Ok(headers),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod trace_stream_tests {
+    use super::*;
+    use crate::{GethExecTrace, ResultGethExecTraces};
+
+    const TRACE: &str = r#"{
+        "gas": 26809,
+        "failed": false,
+        "returnValue": "",
+        "structLogs": [
+            {"pc": 0, "op": "PUSH1", "gas": 22705, "gasCost": 3, "depth": 1, "stack": []},
+            {"pc": 2, "op": "PUSH1", "gas": 22702, "gasCost": 3, "depth": 1, "stack": ["0x80"]},
+            {"pc": 4, "op": "MSTORE", "gas": 22699, "gasCost": 12, "depth": 1,
+             "stack": ["0x80", "0x40"]}
+        ]
+    }"#;
+
+    #[test]
+    fn stream_trace() {
+        let expected: GethExecTrace = serde_json::from_str(TRACE).unwrap();
+        let mut steps = Vec::new();
+        let header = stream_geth_exec_trace(TRACE.as_bytes(), |step| {
+            steps.push(step);
+            Ok::<_, Error>(())
+        })
+        .unwrap();
+        assert_eq!(header.gas, expected.gas);
+        assert_eq!(header.failed, expected.failed);
+        assert_eq!(header.return_value, expected.return_value);
+        assert_eq!(steps, expected.struct_logs);
+    }
+
+    #[test]
+    fn stream_block_traces() {
+        let traces = format!(r#"[{{"result": {}}}, {{"result": {}}}]"#, TRACE, TRACE);
+        let expected: ResultGethExecTraces = serde_json::from_str(&traces).unwrap();
+        let mut steps = vec![Vec::new(); 2];
+        let headers = stream_geth_exec_traces(traces.as_bytes(), |index, step| {
+            steps[index].push(step);
+            Ok::<_, Error>(())
+        })
+        .unwrap();
+        assert_eq!(headers.len(), 2);
+        for (steps, expected) in steps.iter().zip(expected.0) {
+            assert_eq!(steps, &expected.result.struct_logs);
+        }
+    }
+
+    #[test]
+    fn stream_stops_at_callback_error() {
+        let mut count = 0;
+        let result = stream_geth_exec_trace(TRACE.as_bytes(), |_| {
+            count += 1;
+            Err(Error::TracingError("stop".to_string()))
+        });
+        assert!(matches!(result, Err(Error::TracingError(_))));
+        assert_eq!(count, 1);
+    }
+}
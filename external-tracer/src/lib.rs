@@ -1,4 +1,13 @@
-//! This module generates traces by connecting to an external tracer
+//! This module generates traces by connecting to an external tracer, which is
+//! geth through `geth-utils` with the `geth` feature, or the native Rust
+//! interpreter with the `native` feature.  When both features are enabled,
+//! the traces of the native interpreter are checked against the ones of geth.
+
+#[cfg(not(any(feature = "geth", feature = "native")))]
+compile_error!("either the `geth` or the `native` feature must be enabled");
+
+#[cfg(feature = "native")]
+pub mod native;
 
 use eth_types::{
     geth_types::{Account, BlockConstants, Transaction},
@@ -45,7 +54,7 @@ pub struct LoggerConfig {
 impl Default for LoggerConfig {
     fn default() -> Self {
         Self {
-            enable_memory: true,
+            enable_memory: false,
             disable_stack: false,
             disable_storage: false,
             enable_return_data: true,
@@ -54,6 +63,8 @@ impl Default for LoggerConfig {
 }
 
 impl LoggerConfig {
+    /// Configuration capturing the memory, which bus-mapping otherwise
+    /// reconstructs by itself.  Only useful to check the reconstruction.
     pub fn enable_memory() -> Self {
         Self {
             enable_memory: true,
@@ -63,13 +74,85 @@ impl LoggerConfig {
 }
 
 /// Creates a trace for the specified config
+#[cfg(not(feature = "native"))]
+pub fn trace(config: &TraceConfig) -> Result<Vec<GethExecTrace>, Error> {
+    geth_trace(config)
+}
+
+/// Creates a trace for the specified config
+#[cfg(all(feature = "native", not(feature = "geth")))]
+pub fn trace(config: &TraceConfig) -> Result<Vec<GethExecTrace>, Error> {
+    native::trace(config)
+}
+
+/// Creates a trace for the specified config with the native interpreter, and
+/// checks that it's the same as the trace of geth.
+#[cfg(all(feature = "native", feature = "geth"))]
 pub fn trace(config: &TraceConfig) -> Result<Vec<GethExecTrace>, Error> {
+    let traces = native::trace(config);
+    match (&traces, geth_trace(config)) {
+        (Ok(traces), Ok(geth_traces)) => check_traces(traces, &geth_traces)?,
+        (Err(_), Err(_)) => (),
+        (_, geth_traces) => {
+            return Err(Error::TracingError(format!(
+                "native tracer returned {:?} but geth returned {:?}",
+                traces.as_ref().map(|_| ()),
+                geth_traces.map(|_| ()),
+            )))
+        }
+    }
+    traces
+}
+
+/// Check that the native traces are the same as the traces of geth, reporting
+/// the first difference.
+#[cfg(all(feature = "native", feature = "geth"))]
+fn check_traces(traces: &[GethExecTrace], geth_traces: &[GethExecTrace]) -> Result<(), Error> {
+    let mismatch = |message: String| {
+        Err(Error::TracingError(format!(
+            "native trace mismatch: {}",
+            message
+        )))
+    };
+    for (index, (trace, geth_trace)) in traces.iter().zip(geth_traces).enumerate() {
+        let steps = trace.struct_logs.iter().zip(&geth_trace.struct_logs);
+        for (step_index, (step, geth_step)) in steps.enumerate() {
+            if step != geth_step {
+                return mismatch(format!(
+                    "transactions[{}] step {}: {:?} != {:?}",
+                    index, step_index, step, geth_step
+                ));
+            }
+        }
+        if trace != geth_trace {
+            return mismatch(format!(
+                "transactions[{}]: gas {:?}, failed {}, return value {:?}, {} steps != gas {:?}, failed {}, return value {:?}, {} steps",
+                index,
+                trace.gas,
+                trace.failed,
+                trace.return_value,
+                trace.struct_logs.len(),
+                geth_trace.gas,
+                geth_trace.failed,
+                geth_trace.return_value,
+                geth_trace.struct_logs.len(),
+            ));
+        }
+    }
+    if traces.len() != geth_traces.len() {
+        return mismatch(format!("{} traces != {}", traces.len(), geth_traces.len()));
+    }
+    Ok(())
+}
+
+/// Creates a trace for the specified config with geth
+#[cfg(feature = "geth")]
+pub fn geth_trace(config: &TraceConfig) -> Result<Vec<GethExecTrace>, Error> {
+    let config = serde_json::to_string(&config).map_err(Error::SerdeError)?;
     // Get the trace
-    let trace_string = geth_utils::trace(&serde_json::to_string(&config).unwrap()).map_err(
-        |error| match error {
-            geth_utils::Error::TracingError(error) => Error::TracingError(error),
-        },
-    )?;
+    let trace_string = geth_utils::trace(&config).map_err(|error| match error {
+        geth_utils::Error::TracingError(error) => Error::TracingError(error),
+    })?;
 
     let trace = serde_json::from_str(&trace_string).map_err(Error::SerdeError)?;
     Ok(trace)
@@ -0,0 +1,510 @@
+//! Native tracer, which executes the transactions of a [`TraceConfig`] with an
+//! EVM interpreter written in Rust instead of calling geth through
+//! `geth-utils`.
+//!
+//! The interpreter follows the London rules of geth v1.10.18, the version used
+//! by `geth-utils`, including the quirks of its `StructLogger`: a step is
+//! captured after its gas has been charged but before the memory is expanded,
+//! and only the errors found before the step is captured are reported in it.
+
+mod evm;
+mod state;
+
+use crate::TraceConfig;
+use eth_types::{
+    evm_types::{
+        precompiles::{PrecompileError, PRECOMPILE_COUNT},
+        Gas, GasCost, OpcodeId, MAX_REFUND_QUOTIENT_OF_GAS_USED,
+    },
+    Address, Error, GethExecTrace, Word,
+};
+use ethers_core::utils::{get_contract_address, keccak256};
+use evm::{CallKind, Evm};
+use state::StateDB;
+use std::fmt;
+
+/// Stack size of the thread running the interpreter, which recurses for each
+/// call frame up to a depth of 1025.
+const STACK_SIZE: usize = 256 * 1024 * 1024;
+
+/// Error of a call frame, displayed with the message of geth.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ExecError {
+    StackUnderflow { len: usize, required: usize },
+    StackOverflow { len: usize, limit: usize },
+    OutOfGas,
+    GasUintOverflow,
+    InvalidJump,
+    InvalidOpcode(OpcodeId),
+    WriteProtection,
+    ReturnDataOutOfBounds,
+    ExecutionReverted,
+    Depth,
+    InsufficientBalance,
+    ContractAddressCollision,
+    CodeStoreOutOfGas,
+    MaxCodeSizeExceeded,
+    InvalidCode,
+    NonceUintOverflow,
+    PrecompileFailed(&'static str),
+}
+
+impl From<PrecompileError> for ExecError {
+    fn from(error: PrecompileError) -> Self {
+        match error {
+            PrecompileError::Failed(error) => Self::PrecompileFailed(error),
+        }
+    }
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StackUnderflow { len, required } => {
+                write!(f, "stack underflow ({} <=> {})", len, required)
+            }
+            Self::StackOverflow { len, limit } => {
+                write!(f, "stack limit reached {} ({})", len, limit)
+            }
+            Self::OutOfGas => write!(f, "out of gas"),
+            Self::GasUintOverflow => write!(f, "gas uint64 overflow"),
+            Self::InvalidJump => write!(f, "invalid jump destination"),
+            Self::InvalidOpcode(op) => write!(f, "invalid opcode: {:?}", op),
+            Self::WriteProtection => write!(f, "write protection"),
+            Self::ReturnDataOutOfBounds => write!(f, "return data out of bounds"),
+            Self::ExecutionReverted => write!(f, "execution reverted"),
+            Self::Depth => write!(f, "max call depth exceeded"),
+            Self::InsufficientBalance => write!(f, "insufficient balance for transfer"),
+            Self::ContractAddressCollision => write!(f, "contract address collision"),
+            Self::CodeStoreOutOfGas => write!(f, "contract creation code storage out of gas"),
+            Self::MaxCodeSizeExceeded => write!(f, "max code size exceeded"),
+            Self::InvalidCode => write!(f, "invalid code: must not begin with 0xef"),
+            Self::NonceUintOverflow => write!(f, "nonce uint64 overflow"),
+            Self::PrecompileFailed(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+/// Creates a trace for the specified config with the native interpreter.
+pub fn trace(config: &TraceConfig) -> Result<Vec<GethExecTrace>, Error> {
+    std::thread::scope(|scope| {
+        std::thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || trace_transactions(config))
+            .expect("failed to spawn the native tracer thread")
+            .join()
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+    .map_err(|error| Error::TracingError(format!("Failed to run Trace, err: {}", error)))
+}
+
+fn trace_transactions(config: &TraceConfig) -> Result<Vec<GethExecTrace>, String> {
+    let block_gas_limit = config.block_constants.gas_limit.low_u64();
+    let txs_gas_limit: u64 = config
+        .transactions
+        .iter()
+        .map(|tx| tx.gas_limit.low_u64())
+        .sum();
+    if txs_gas_limit > block_gas_limit {
+        return Err(format!(
+            "txs total gas: {} Exceeds block gas limit: {}",
+            txs_gas_limit, block_gas_limit
+        ));
+    }
+
+    let mut state = StateDB::new(&config.accounts);
+    config
+        .transactions
+        .iter()
+        .enumerate()
+        .map(|(index, tx)| {
+            let trace = apply_transaction(config, &mut state, tx).map_err(|error| {
+                format!("Failed to apply config.Transactions[{}]: {}", index, error)
+            })?;
+            state.finalise();
+            Ok(trace)
+        })
+        .collect()
+}
+
+/// Execute a transaction as `core.ApplyMessage` of geth, without base fee.
+fn apply_transaction(
+    config: &TraceConfig,
+    state: &mut StateDB,
+    tx: &eth_types::geth_types::Transaction,
+) -> Result<GethExecTrace, String> {
+    let block = &config.block_constants;
+    let gas_limit = tx.gas_limit.low_u64();
+    // The gas price is always set, so it is also the fee and the tip caps.
+    let gas_price = tx.gas_price;
+
+    let nonce = state.nonce(&tx.from);
+    let tx_nonce = tx.nonce.low_u64();
+    if nonce < tx_nonce {
+        return Err(format!(
+            "nonce too high: address {:?}, tx: {} state: {}",
+            tx.from, tx_nonce, nonce
+        ));
+    }
+    if nonce > tx_nonce {
+        return Err(format!(
+            "nonce too low: address {:?}, tx: {} state: {}",
+            tx.from, tx_nonce, nonce
+        ));
+    }
+    let code_hash = state.code_hash(&tx.from);
+    if !code_hash.is_zero() && code_hash.0 != keccak256([]) {
+        return Err(format!(
+            "sender not an eoa: address {:?}, codehash: {:?}",
+            tx.from, code_hash
+        ));
+    }
+    if !gas_price.is_zero() && gas_price < block.base_fee {
+        return Err(format!(
+            "max fee per gas less than block base fee: address {:?}, maxFeePerGas: {} baseFee: {}",
+            tx.from, gas_price, block.base_fee
+        ));
+    }
+
+    let gas_value = Word::from(gas_limit).saturating_mul(gas_price);
+    let balance = state.balance(&tx.from);
+    let required_balance = gas_value.saturating_add(tx.value);
+    if balance < required_balance {
+        return Err(format!(
+            "insufficient funds for gas * price + value: address {:?} have {} want {}",
+            tx.from, balance, required_balance
+        ));
+    }
+    state.sub_balance(tx.from, gas_value);
+
+    let intrinsic_gas = intrinsic_gas(tx);
+    if gas_limit < intrinsic_gas {
+        return Err(format!(
+            "intrinsic gas too low: have {}, want {}",
+            gas_limit, intrinsic_gas
+        ));
+    }
+    if state.balance(&tx.from) < tx.value {
+        return Err(format!(
+            "insufficient funds for transfer: address {:?}",
+            tx.from
+        ));
+    }
+
+    state.add_address_to_access_list(tx.from);
+    if let Some(to) = tx.to {
+        state.add_address_to_access_list(to);
+    }
+    for address in 1..=PRECOMPILE_COUNT {
+        state.add_address_to_access_list(Address::from_low_u64_be(address));
+    }
+    for item in tx.access_list.iter().flat_map(|access_list| &access_list.0) {
+        state.add_address_to_access_list(item.address);
+        for key in &item.storage_keys {
+            state.add_slot_to_access_list(item.address, Word::from_big_endian(key.as_bytes()));
+        }
+    }
+
+    let gas = gas_limit - intrinsic_gas;
+    let mut evm = Evm::new(config, state, tx.from, gas_price);
+    let result = match tx.to {
+        None => {
+            let address = get_contract_address(tx.from, evm.state.nonce(&tx.from));
+            evm.create(tx.from, tx.call_data.to_vec(), gas, tx.value, address)
+        }
+        Some(to) => {
+            evm.state.set_nonce(tx.from, nonce + 1);
+            evm.call(
+                CallKind::Call,
+                tx.from,
+                to,
+                to,
+                tx.value,
+                tx.call_data.to_vec(),
+                gas,
+                false,
+            )
+        }
+    };
+    let struct_logs = evm.into_struct_logs();
+
+    // EIP-3529: the refund is capped to a fifth of the gas used.
+    let refund = state
+        .refund()
+        .min((gas_limit - result.gas_left) / MAX_REFUND_QUOTIENT_OF_GAS_USED as u64);
+    let gas_left = result.gas_left + refund;
+    state.add_balance(tx.from, Word::from(gas_left) * gas_price);
+    let gas_used = gas_limit - gas_left;
+    // The effective tip is negative when the gas price is below the base fee,
+    // which is allowed without base fee.
+    if gas_price >= block.base_fee {
+        state.add_balance(
+            block.coinbase,
+            Word::from(gas_used) * (gas_price - block.base_fee),
+        );
+    } else {
+        state.sub_balance(
+            block.coinbase,
+            Word::from(gas_used) * (block.base_fee - gas_price),
+        );
+    }
+
+    Ok(GethExecTrace {
+        gas: Gas(gas_used),
+        failed: result.error.is_some(),
+        return_value: hex::encode(&result.output),
+        struct_logs,
+    })
+}
+
+fn intrinsic_gas(tx: &eth_types::geth_types::Transaction) -> u64 {
+    let mut gas = if tx.to.is_none() {
+        GasCost::CREATION_TX
+    } else {
+        GasCost::TX
+    }
+    .as_u64();
+    gas += tx
+        .call_data
+        .iter()
+        .map(|byte| if *byte == 0 { 4 } else { 16 })
+        .sum::<u64>();
+    if let Some(access_list) = &tx.access_list {
+        gas += access_list.0.len() as u64 * GasCost::ACCESS_LIST_PER_ADDRESS.as_u64();
+        gas += access_list
+            .0
+            .iter()
+            .map(|item| {
+                item.storage_keys.len() as u64 * GasCost::ACCESS_LIST_PER_STORAGE_KEY.as_u64()
+            })
+            .sum::<u64>();
+    }
+    gas
+}
+
+#[cfg(test)]
+mod test {
+    use super::trace;
+    use crate::{LoggerConfig, TraceConfig};
+    use eth_types::{
+        address, bytecode,
+        evm_types::{Gas, GasCost, OpcodeId},
+        geth_types::{Account, BlockConstants, Transaction},
+        word, Bytes, Word,
+    };
+    use std::collections::HashMap;
+
+    fn config(accounts: HashMap<eth_types::Address, Account>, tx: Transaction) -> TraceConfig {
+        TraceConfig {
+            block_constants: BlockConstants {
+                gas_limit: word!("0xcf080"),
+                ..Default::default()
+            },
+            accounts,
+            transactions: vec![tx],
+            ..Default::default()
+        }
+    }
+
+    fn call_tx(gas_limit: u64) -> Transaction {
+        Transaction {
+            from: address!("0x00000000000000000000000000000000000000fe"),
+            to: Some(address!("0x00000000000000000000000000000000000000ff")),
+            gas_limit: Word::from(gas_limit),
+            ..Default::default()
+        }
+    }
+
+    fn contract(code: Bytes) -> HashMap<eth_types::Address, Account> {
+        HashMap::from([(
+            address!("0x00000000000000000000000000000000000000ff"),
+            Account {
+                code,
+                ..Default::default()
+            },
+        )])
+    }
+
+    #[test]
+    fn valid_tx() {
+        let mut create_tx = call_tx(53000);
+        create_tx.to = None;
+        let mut priced_tx = call_tx(21000);
+        priced_tx.gas_price = word!("0x77359400");
+        let funded = HashMap::from([(
+            address!("0x00000000000000000000000000000000000000fe"),
+            Account {
+                balance: word!("0x2632e314a000"),
+                ..Default::default()
+            },
+        )]);
+
+        for config in [
+            // Minimal call tx with gas_limit = 21000
+            config(HashMap::new(), call_tx(21000)),
+            // Minimal creation tx with gas_limit = 53000
+            config(HashMap::new(), create_tx),
+            // Normal call tx with gas_limit = 21000 and gas_price = 2 Gwei
+            config(funded, priced_tx),
+        ] {
+            let traces = trace(&config).unwrap();
+            assert_eq!(traces.len(), 1);
+            assert!(!traces[0].failed);
+            assert!(traces[0].struct_logs.is_empty());
+        }
+    }
+
+    #[test]
+    fn invalid_tx() {
+        let mut unfunded_tx = call_tx(21000);
+        unfunded_tx.gas_price = word!("0x1111");
+        let mut transfer_tx = call_tx(21000);
+        transfer_tx.value = word!("0x100");
+
+        for config in [
+            // Insufficient gas for intrinsic usage
+            config(HashMap::new(), call_tx(0)),
+            // Insufficient balance to buy gas
+            config(HashMap::new(), unfunded_tx),
+            // Insufficient balance to do the first transfer
+            config(HashMap::new(), transfer_tx),
+            // Total gas above the block gas limit
+            config(HashMap::new(), call_tx(0xcf081)),
+        ] {
+            assert!(trace(&config).is_err());
+        }
+    }
+
+    #[test]
+    fn storage_gas_and_refund() {
+        let code = bytecode! {
+            PUSH1(0x2a)
+            PUSH1(0)
+            SSTORE
+            PUSH1(0)
+            SLOAD
+            PUSH1(0)
+            PUSH1(0)
+            SSTORE
+            STOP
+        };
+        let traces = trace(&config(contract(code.into()), call_tx(100000))).unwrap();
+        let steps = &traces[0].struct_logs;
+
+        let ops: Vec<_> = steps.iter().map(|step| step.op).collect();
+        assert_eq!(
+            ops,
+            [
+                OpcodeId::PUSH1,
+                OpcodeId::PUSH1,
+                OpcodeId::SSTORE,
+                OpcodeId::PUSH1,
+                OpcodeId::SLOAD,
+                OpcodeId::PUSH1,
+                OpcodeId::PUSH1,
+                OpcodeId::SSTORE,
+                OpcodeId::STOP
+            ]
+        );
+        // Cold slot set from zero, warm read, then reset to its original value.
+        assert_eq!(steps[2].gas_cost, GasCost(22100));
+        assert_eq!(steps[4].gas_cost, GasCost(100));
+        assert_eq!(steps[7].gas_cost, GasCost(100));
+        assert_eq!(steps[8].refund, Gas(19900));
+        assert_eq!(
+            steps[4].storage.get_or_err(&Word::zero()).unwrap(),
+            word!("0x2a")
+        );
+        assert_eq!(steps[0].gas, Gas(100000 - 21000));
+        assert!(steps
+            .iter()
+            .all(|step| step.depth == 1 && step.error.is_none()));
+
+        // The refund is capped to a fifth of the gas used.
+        let gas_used = 21000 + 3 * 5 + 22100 + 100 + 100;
+        assert_eq!(traces[0].gas, Gas(gas_used - gas_used / 5));
+    }
+
+    #[test]
+    fn memory_captured_before_expansion() {
+        let code = bytecode! {
+            PUSH1(0x01)
+            PUSH1(0x20)
+            MSTORE
+            MSIZE
+            STOP
+        };
+        let config = TraceConfig {
+            logger_config: LoggerConfig::enable_memory(),
+            ..config(contract(code.into()), call_tx(100000))
+        };
+        let traces = trace(&config).unwrap();
+        let steps = &traces[0].struct_logs;
+
+        assert_eq!(steps[2].op, OpcodeId::MSTORE);
+        assert_eq!(steps[2].gas_cost, GasCost(3 + 6));
+        assert!(steps[2].memory.0.is_empty());
+        assert_eq!(steps[3].memory.0.len(), 64);
+        assert_eq!(steps[4].stack.last().unwrap(), Word::from(64));
+    }
+
+    #[test]
+    fn step_errors() {
+        // Errors found before a step is captured are reported in the step.
+        let traces = trace(&config(contract(bytecode! { ADD }.into()), call_tx(100000))).unwrap();
+        assert!(traces[0].failed);
+        assert_eq!(traces[0].gas, Gas(100000));
+        assert_eq!(
+            traces[0].struct_logs[0].error.as_deref(),
+            Some("stack underflow (0 <=> 2)")
+        );
+
+        // Errors found by the execution of a step are not.
+        let code = bytecode! {
+            PUSH1(0x03)
+            JUMP
+        };
+        let traces = trace(&config(contract(code.into()), call_tx(100000))).unwrap();
+        assert!(traces[0].failed);
+        assert_eq!(traces[0].struct_logs.len(), 2);
+        assert!(traces[0]
+            .struct_logs
+            .iter()
+            .all(|step| step.error.is_none()));
+    }
+
+    #[test]
+    fn nested_call() {
+        let callee = address!("0x0000000000000000000000000000000000000100");
+        let code = bytecode! {
+            PUSH1(0)
+            PUSH1(0)
+            PUSH1(0)
+            PUSH1(0)
+            PUSH1(0)
+            PUSH2(0x100)
+            PUSH2(0xffff)
+            CALL
+            STOP
+        };
+        let mut accounts = contract(code.into());
+        accounts.insert(
+            callee,
+            Account {
+                code: bytecode! { PUSH1(1) STOP }.into(),
+                ..Default::default()
+            },
+        );
+        let traces = trace(&config(accounts, call_tx(100000))).unwrap();
+        let steps = &traces[0].struct_logs;
+
+        let call = &steps[7];
+        assert_eq!(call.op, OpcodeId::CALL);
+        // Cold account access plus the gas passed to the callee.
+        assert_eq!(call.gas_cost, GasCost(2600 + 0xffff));
+        assert_eq!((steps[8].depth, steps[9].depth), (2, 2));
+        assert_eq!(steps[8].gas, Gas(0xffff));
+        assert_eq!(steps[10].op, OpcodeId::STOP);
+        assert_eq!(steps[10].stack.last().unwrap(), Word::one());
+    }
+}
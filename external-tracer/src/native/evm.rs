@@ -0,0 +1,1297 @@
+//! EVM interpreter of the native tracer, which follows the London rules and
+//! the tracing behaviour of geth.
+
+use super::{state::StateDB, ExecError};
+use crate::TraceConfig;
+use eth_types::{
+    evm_types::{
+        precompiles::{get_data, precompile, Precompile},
+        Gas, GasCost, Memory, OpcodeId, ProgramCounter, Stack, Storage,
+        GAS_STIPEND_CALL_WITH_VALUE,
+    },
+    Address, GethExecStep, ToAddress, ToBigEndian, ToWord, Word, H256, U512,
+};
+use ethers_core::utils::{get_contract_address, get_create2_address, keccak256};
+use std::collections::HashMap;
+
+const STACK_LIMIT: usize = 1024;
+const CALL_CREATE_DEPTH: usize = 1024;
+const MAX_CODE_SIZE: usize = 24576;
+const CREATE_DATA_GAS: u64 = 200;
+const SSTORE_SENTRY_GAS: u64 = 2300;
+const LOG_DATA_GAS: u64 = 8;
+const EXP_BYTE_GAS: u64 = 50;
+
+const WARM_ACCESS: u64 = GasCost::WARM_ACCESS.as_u64();
+const COLD_SLOAD: u64 = GasCost::COLD_SLOAD.as_u64();
+
+/// Kind of message call.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum CallKind {
+    Call,
+    CallCode,
+    DelegateCall,
+    StaticCall,
+}
+
+/// Result of a message call or a contract creation.
+#[derive(Debug)]
+pub(super) struct CallResult {
+    /// Return data, or the deployed code of a creation
+    pub(super) output: Vec<u8>,
+    /// Gas left to return to the caller
+    pub(super) gas_left: u64,
+    /// Execution error, if the call failed
+    pub(super) error: Option<ExecError>,
+}
+
+impl CallResult {
+    fn failed(gas_left: u64, error: ExecError) -> Self {
+        Self {
+            output: Vec::new(),
+            gas_left,
+            error: Some(error),
+        }
+    }
+}
+
+/// Code being executed in a call frame, with its context.
+struct Contract {
+    caller: Address,
+    address: Address,
+    value: Word,
+    input: Vec<u8>,
+    code: Vec<u8>,
+    jump_dests: Vec<bool>,
+    gas: u64,
+}
+
+impl Contract {
+    fn new(
+        caller: Address,
+        address: Address,
+        value: Word,
+        input: Vec<u8>,
+        code: Vec<u8>,
+        gas: u64,
+    ) -> Self {
+        let mut jump_dests = vec![false; code.len()];
+        let mut pc = 0;
+        while pc < code.len() {
+            let byte = code[pc];
+            if byte == OpcodeId::JUMPDEST.as_u8() {
+                jump_dests[pc] = true;
+            } else if (OpcodeId::PUSH1.as_u8()..=OpcodeId::PUSH32.as_u8()).contains(&byte) {
+                pc += (byte - OpcodeId::PUSH1.as_u8()) as usize + 1;
+            }
+            pc += 1;
+        }
+        Self {
+            caller,
+            address,
+            value,
+            input,
+            code,
+            jump_dests,
+            gas,
+        }
+    }
+
+    fn use_gas(&mut self, gas: u64) -> bool {
+        if self.gas < gas {
+            return false;
+        }
+        self.gas -= gas;
+        true
+    }
+
+    fn valid_jump_dest(&self, dest: Word) -> bool {
+        dest < Word::from(self.code.len()) && self.jump_dests[dest.as_usize()]
+    }
+}
+
+/// Stack, memory and return data buffer of a call frame.
+#[derive(Default)]
+struct Frame {
+    stack: Vec<Word>,
+    memory: Vec<u8>,
+    /// Gas paid for the current memory size.
+    memory_gas_cost: u64,
+    return_data: Vec<u8>,
+}
+
+impl Frame {
+    fn pop(&mut self) -> Word {
+        self.stack
+            .pop()
+            .expect("stack size checked before execution")
+    }
+
+    fn push(&mut self, value: Word) {
+        self.stack.push(value);
+    }
+
+    /// Return the `n`th value from the top of the stack.
+    fn back(&self, n: usize) -> Word {
+        self.stack[self.stack.len() - 1 - n]
+    }
+
+    /// Return a copy of `size` bytes of memory from `offset`.  The memory has
+    /// been expanded before execution.
+    fn memory_get(&self, offset: Word, size: Word) -> Vec<u8> {
+        if size.is_zero() {
+            return Vec::new();
+        }
+        let offset = offset.as_usize();
+        self.memory[offset..offset + size.as_usize()].to_vec()
+    }
+
+    /// Write at most `size` bytes of `value` in memory from `offset`.
+    fn memory_set(&mut self, offset: Word, size: Word, value: &[u8]) {
+        if size.is_zero() {
+            return;
+        }
+        let offset = offset.low_u64() as usize;
+        let size = (size.low_u64() as usize).min(value.len());
+        self.memory[offset..offset + size].copy_from_slice(&value[..size]);
+    }
+}
+
+/// How the execution continues after an opcode.
+enum Next {
+    Continue,
+    Jump(usize),
+    Stop(Vec<u8>),
+    Revert(Vec<u8>),
+}
+
+/// Number of stack items (popped, pushed) by an opcode.
+fn stack_io(op: OpcodeId) -> (usize, usize) {
+    use OpcodeId::*;
+    match op {
+        STOP | JUMPDEST | INVALID(_) => (0, 0),
+        ADDMOD | MULMOD => (3, 1),
+        ADD | MUL | SUB | DIV | SDIV | MOD | SMOD | EXP | SIGNEXTEND | LT | GT | SLT | SGT | EQ
+        | AND | OR | XOR | BYTE | SHL | SHR | SAR | SHA3 => (2, 1),
+        ISZERO | NOT | BALANCE | CALLDATALOAD | EXTCODESIZE | EXTCODEHASH | BLOCKHASH | MLOAD
+        | SLOAD => (1, 1),
+        ADDRESS | ORIGIN | CALLER | CALLVALUE | CALLDATASIZE | CODESIZE | GASPRICE
+        | RETURNDATASIZE | COINBASE | TIMESTAMP | NUMBER | DIFFICULTY | GASLIMIT | CHAINID
+        | SELFBALANCE | BASEFEE | PC | MSIZE | GAS => (0, 1),
+        CALLDATACOPY | CODECOPY | RETURNDATACOPY => (3, 0),
+        EXTCODECOPY => (4, 0),
+        POP | JUMP | SELFDESTRUCT => (1, 0),
+        MSTORE | MSTORE8 | SSTORE | JUMPI | RETURN | REVERT => (2, 0),
+        CREATE => (3, 1),
+        CREATE2 => (4, 1),
+        CALL | CALLCODE => (7, 1),
+        DELEGATECALL | STATICCALL => (6, 1),
+        op if op.is_push() => (0, 1),
+        op if op.is_dup() => {
+            let n = (op.as_u8() - DUP1.as_u8()) as usize + 1;
+            (n, n + 1)
+        }
+        op if op.is_swap() => {
+            let n = (op.as_u8() - SWAP1.as_u8()) as usize + 2;
+            (n, n)
+        }
+        op if op.is_log() => ((op.as_u8() - LOG0.as_u8()) as usize + 2, 0),
+        _ => unreachable!("all opcodes are covered"),
+    }
+}
+
+fn has_dynamic_gas(op: OpcodeId) -> bool {
+    use OpcodeId::*;
+    op.is_log()
+        || matches!(
+            op,
+            EXP | SHA3
+                | BALANCE
+                | EXTCODESIZE
+                | EXTCODEHASH
+                | CALLDATACOPY
+                | CODECOPY
+                | EXTCODECOPY
+                | RETURNDATACOPY
+                | MLOAD
+                | MSTORE
+                | MSTORE8
+                | SLOAD
+                | SSTORE
+                | CREATE
+                | CREATE2
+                | CALL
+                | CALLCODE
+                | DELEGATECALL
+                | STATICCALL
+                | RETURN
+                | REVERT
+                | SELFDESTRUCT
+        )
+}
+
+fn to_word_size(size: u64) -> u64 {
+    if size > u64::MAX - 31 {
+        return u64::MAX / 32 + 1;
+    }
+    (size + 31) / 32
+}
+
+fn to_u64(value: Word) -> Option<u64> {
+    (value.bits() <= 64).then(|| value.low_u64())
+}
+
+/// Memory size required to access `length` bytes from `offset`, or `None` on
+/// overflow.
+fn memory_size_with_len(offset: Word, length: u64) -> Option<u64> {
+    if length == 0 {
+        return Some(0);
+    }
+    to_u64(offset)?.checked_add(length)
+}
+
+fn memory_size_of(offset: Word, length: Word) -> Option<u64> {
+    memory_size_with_len(offset, to_u64(length)?)
+}
+
+/// Memory size required by an opcode, or `None` on overflow.
+fn memory_size(op: OpcodeId, frame: &Frame) -> Option<u64> {
+    use OpcodeId::*;
+    let back = |n| frame.back(n);
+    match op {
+        SHA3 | RETURN | REVERT => memory_size_of(back(0), back(1)),
+        op if op.is_log() => memory_size_of(back(0), back(1)),
+        CALLDATACOPY | CODECOPY | RETURNDATACOPY => memory_size_of(back(0), back(2)),
+        EXTCODECOPY => memory_size_of(back(1), back(3)),
+        MLOAD | MSTORE => memory_size_with_len(back(0), 32),
+        MSTORE8 => memory_size_with_len(back(0), 1),
+        CREATE | CREATE2 => memory_size_of(back(1), back(2)),
+        CALL | CALLCODE => {
+            Some(memory_size_of(back(5), back(6))?.max(memory_size_of(back(3), back(4))?))
+        }
+        DELEGATECALL | STATICCALL => {
+            Some(memory_size_of(back(4), back(5))?.max(memory_size_of(back(2), back(3))?))
+        }
+        _ => Some(0),
+    }
+}
+
+/// Cost of expanding the memory of `frame` to `new_size` bytes.
+fn memory_gas_cost(frame: &mut Frame, new_size: u64) -> Option<u64> {
+    if new_size == 0 {
+        return Some(0);
+    }
+    if new_size > 0x1FFFFFFFE0 {
+        return None;
+    }
+    let words = to_word_size(new_size);
+    if words * 32 > frame.memory.len() as u64 {
+        let total = words * GasCost::MEMORY_EXPANSION_LINEAR_COEFF.as_u64()
+            + words * words / GasCost::MEMORY_EXPANSION_QUAD_DENOMINATOR.as_u64();
+        let fee = total - frame.memory_gas_cost;
+        frame.memory_gas_cost = total;
+        return Some(fee);
+    }
+    Some(0)
+}
+
+/// Cost of the memory expansion plus `word_gas` per word of `size` bytes.
+fn memory_words_gas(frame: &mut Frame, memory_size: u64, size: Word, word_gas: u64) -> Option<u64> {
+    let gas = memory_gas_cost(frame, memory_size)?;
+    let words_gas = to_word_size(to_u64(size)?).checked_mul(word_gas)?;
+    gas.checked_add(words_gas)
+}
+
+fn is_negative(value: Word) -> bool {
+    value.bit(255)
+}
+
+fn negate(value: Word) -> Word {
+    (!value).overflowing_add(Word::one()).0
+}
+
+fn abs(value: Word) -> Word {
+    if is_negative(value) {
+        negate(value)
+    } else {
+        value
+    }
+}
+
+fn signed_lt(a: Word, b: Word) -> bool {
+    match (is_negative(a), is_negative(b)) {
+        (true, false) => true,
+        (false, true) => false,
+        _ => a < b,
+    }
+}
+
+fn bool_word(value: bool) -> Word {
+    if value {
+        Word::one()
+    } else {
+        Word::zero()
+    }
+}
+
+fn hash_word(hash: H256) -> Word {
+    Word::from_big_endian(hash.as_bytes())
+}
+
+fn reduce(value: U512) -> Word {
+    value
+        .try_into()
+        .expect("value reduced by a 256 bits modulus")
+}
+
+/// Struct logs of the execution, in the format of the geth `StructLogger`.
+#[derive(Default)]
+struct StructLogger {
+    /// Storage slots read or written by each contract in the transaction.
+    storage: HashMap<Address, HashMap<Word, Word>>,
+    logs: Vec<GethExecStep>,
+}
+
+/// EVM executing a transaction, which records its struct logs.
+pub(super) struct Evm<'a> {
+    config: &'a TraceConfig,
+    pub(super) state: &'a mut StateDB,
+    origin: Address,
+    gas_price: Word,
+    depth: usize,
+    logger: StructLogger,
+    /// Gas to pass to the callee of the CALL-like opcode being executed,
+    /// computed with its dynamic gas.
+    call_gas_temp: u64,
+}
+
+impl<'a> Evm<'a> {
+    pub(super) fn new(
+        config: &'a TraceConfig,
+        state: &'a mut StateDB,
+        origin: Address,
+        gas_price: Word,
+    ) -> Self {
+        Self {
+            config,
+            state,
+            origin,
+            gas_price,
+            depth: 0,
+            logger: StructLogger::default(),
+            call_gas_temp: 0,
+        }
+    }
+
+    /// Return the struct logs of the execution.
+    pub(super) fn into_struct_logs(self) -> Vec<GethExecStep> {
+        self.logger.logs
+    }
+
+    /// Execute a message call.  `caller`, `address` and `value` are the context
+    /// of the executed code, which is the code of `code_address`.
+    #[allow(clippy::too_many_arguments)]
+    pub(super) fn call(
+        &mut self,
+        kind: CallKind,
+        caller: Address,
+        address: Address,
+        code_address: Address,
+        value: Word,
+        input: Vec<u8>,
+        gas: u64,
+        read_only: bool,
+    ) -> CallResult {
+        if self.depth > CALL_CREATE_DEPTH {
+            return CallResult::failed(gas, ExecError::Depth);
+        }
+        if matches!(kind, CallKind::Call | CallKind::CallCode)
+            && self.state.balance(&caller) < value
+        {
+            return CallResult::failed(gas, ExecError::InsufficientBalance);
+        }
+        let snapshot = self.state.clone();
+        let precompile = precompile(&code_address);
+        match kind {
+            CallKind::Call => {
+                if !self.state.exist(&address) {
+                    if precompile.is_none() && value.is_zero() {
+                        return CallResult {
+                            output: Vec::new(),
+                            gas_left: gas,
+                            error: None,
+                        };
+                    }
+                    self.state.create_account(address);
+                }
+                self.state.transfer(caller, address, value);
+            }
+            // Touch the account
+            CallKind::StaticCall => self.state.add_balance(address, Word::zero()),
+            CallKind::CallCode | CallKind::DelegateCall => (),
+        }
+
+        let mut result = if let Some(precompile) = precompile {
+            self.run_precompile(precompile, &input, gas)
+        } else {
+            let code = self.state.code(&code_address).to_vec();
+            let mut contract = Contract::new(caller, address, value, input, code, gas);
+            let (output, error) =
+                self.run(&mut contract, read_only || kind == CallKind::StaticCall);
+            CallResult {
+                output,
+                gas_left: contract.gas,
+                error,
+            }
+        };
+        if let Some(error) = &result.error {
+            *self.state = snapshot;
+            if *error != ExecError::ExecutionReverted {
+                result.gas_left = 0;
+            }
+        }
+        result
+    }
+
+    fn run_precompile(&mut self, precompile: Precompile, input: &[u8], gas: u64) -> CallResult {
+        let required_gas = precompile.required_gas(input);
+        if gas < required_gas {
+            return CallResult::failed(0, ExecError::OutOfGas);
+        }
+        match precompile.run(input) {
+            Ok(output) => CallResult {
+                output,
+                gas_left: gas - required_gas,
+                error: None,
+            },
+            Err(error) => CallResult::failed(gas - required_gas, error.into()),
+        }
+    }
+
+    /// Create a contract at `address` by running `init_code`.
+    pub(super) fn create(
+        &mut self,
+        caller: Address,
+        init_code: Vec<u8>,
+        gas: u64,
+        value: Word,
+        address: Address,
+    ) -> CallResult {
+        if self.depth > CALL_CREATE_DEPTH {
+            return CallResult::failed(gas, ExecError::Depth);
+        }
+        if self.state.balance(&caller) < value {
+            return CallResult::failed(gas, ExecError::InsufficientBalance);
+        }
+        let nonce = self.state.nonce(&caller);
+        if nonce == u64::MAX {
+            return CallResult::failed(gas, ExecError::NonceUintOverflow);
+        }
+        self.state.set_nonce(caller, nonce + 1);
+        self.state.add_address_to_access_list(address);
+        let code_hash = self.state.code_hash(&address);
+        if self.state.nonce(&address) != 0
+            || (!code_hash.is_zero() && code_hash != H256(keccak256([])))
+        {
+            return CallResult::failed(0, ExecError::ContractAddressCollision);
+        }
+
+        let snapshot = self.state.clone();
+        self.state.create_account(address);
+        self.state.set_nonce(address, 1);
+        self.state.transfer(caller, address, value);
+        let mut contract = Contract::new(caller, address, value, Vec::new(), init_code, gas);
+        let (output, mut error) = self.run(&mut contract, false);
+
+        if error.is_none() && output.len() > MAX_CODE_SIZE {
+            error = Some(ExecError::MaxCodeSizeExceeded);
+        }
+        if error.is_none() && output.first() == Some(&0xef) {
+            error = Some(ExecError::InvalidCode);
+        }
+        if error.is_none() {
+            if contract.use_gas(output.len() as u64 * CREATE_DATA_GAS) {
+                self.state.set_code(address, output.clone());
+            } else {
+                error = Some(ExecError::CodeStoreOutOfGas);
+            }
+        }
+        if let Some(error) = &error {
+            *self.state = snapshot;
+            if *error != ExecError::ExecutionReverted {
+                contract.gas = 0;
+            }
+        }
+        CallResult {
+            output,
+            gas_left: contract.gas,
+            error,
+        }
+    }
+
+    /// Run the code of `contract`, returning its output.
+    fn run(&mut self, contract: &mut Contract, read_only: bool) -> (Vec<u8>, Option<ExecError>) {
+        self.depth += 1;
+        let result = if contract.code.is_empty() {
+            (Vec::new(), None)
+        } else {
+            self.interpret(contract, read_only)
+        };
+        self.depth -= 1;
+        result
+    }
+
+    fn interpret(
+        &mut self,
+        contract: &mut Contract,
+        read_only: bool,
+    ) -> (Vec<u8>, Option<ExecError>) {
+        let mut frame = Frame::default();
+        let mut pc = 0;
+        loop {
+            let gas = contract.gas;
+            let byte = contract.code.get(pc).copied().unwrap_or_default();
+            let op = OpcodeId::try_from(byte).unwrap_or(OpcodeId::INVALID(byte));
+
+            // Like geth, the step is captured after charging its gas but
+            // before expanding the memory.
+            let mut cost = op.constant_gas_cost().as_u64();
+            match self.consume_gas(op, contract, &mut frame, &mut cost) {
+                Ok(memory_size) => {
+                    self.capture_state(pc, op, gas, cost, contract, &frame, None);
+                    if memory_size > frame.memory.len() as u64 {
+                        frame.memory.resize(memory_size as usize, 0);
+                    }
+                }
+                Err(error) => {
+                    self.capture_state(pc, op, gas, cost, contract, &frame, Some(&error));
+                    return (Vec::new(), Some(error));
+                }
+            }
+
+            match self.execute(op, pc, contract, &mut frame, read_only) {
+                Ok(Next::Continue) => pc += 1,
+                Ok(Next::Jump(dest)) => pc = dest,
+                Ok(Next::Stop(output)) => return (output, None),
+                Ok(Next::Revert(output)) => return (output, Some(ExecError::ExecutionReverted)),
+                Err(error) => return (Vec::new(), Some(error)),
+            }
+        }
+    }
+
+    /// Check the stack and charge the gas of an opcode, returning the memory
+    /// size it requires.  `cost` is set to the gas charged.
+    fn consume_gas(
+        &mut self,
+        op: OpcodeId,
+        contract: &mut Contract,
+        frame: &mut Frame,
+        cost: &mut u64,
+    ) -> Result<u64, ExecError> {
+        let (pops, pushes) = stack_io(op);
+        let len = frame.stack.len();
+        if len < pops {
+            return Err(ExecError::StackUnderflow {
+                len,
+                required: pops,
+            });
+        }
+        let limit = STACK_LIMIT + pops - pushes;
+        if len > limit {
+            return Err(ExecError::StackOverflow { len, limit });
+        }
+        if !contract.use_gas(*cost) {
+            return Err(ExecError::OutOfGas);
+        }
+        if !has_dynamic_gas(op) {
+            return Ok(0);
+        }
+
+        let memory_size = memory_size(op, frame)
+            .and_then(|size| to_word_size(size).checked_mul(32))
+            .ok_or(ExecError::GasUintOverflow)?;
+        let dynamic_gas = self.dynamic_gas(op, contract, frame, memory_size);
+        *cost = cost.wrapping_add(dynamic_gas.unwrap_or_default());
+        match dynamic_gas {
+            Some(gas) if contract.use_gas(gas) => Ok(memory_size),
+            _ => Err(ExecError::OutOfGas),
+        }
+    }
+
+    /// Dynamic gas of an opcode, or `None` when it can't be paid.
+    fn dynamic_gas(
+        &mut self,
+        op: OpcodeId,
+        contract: &mut Contract,
+        frame: &mut Frame,
+        memory_size: u64,
+    ) -> Option<u64> {
+        use OpcodeId::*;
+        match op {
+            EXP => Some((frame.back(1).bits() as u64 + 7) / 8 * EXP_BYTE_GAS),
+            SHA3 => memory_words_gas(
+                frame,
+                memory_size,
+                frame.back(1),
+                GasCost::COPY_SHA3.as_u64(),
+            ),
+            CALLDATACOPY | CODECOPY | RETURNDATACOPY => {
+                memory_words_gas(frame, memory_size, frame.back(2), GasCost::COPY.as_u64())
+            }
+            EXTCODECOPY => {
+                let gas =
+                    memory_words_gas(frame, memory_size, frame.back(3), GasCost::COPY.as_u64())?;
+                gas.checked_add(self.account_access_gas(frame.back(0).to_address()))
+            }
+            BALANCE | EXTCODESIZE | EXTCODEHASH => {
+                Some(self.account_access_gas(frame.back(0).to_address()))
+            }
+            MLOAD | MSTORE | MSTORE8 | RETURN | REVERT | CREATE => {
+                memory_gas_cost(frame, memory_size)
+            }
+            CREATE2 => memory_words_gas(
+                frame,
+                memory_size,
+                frame.back(2),
+                GasCost::COPY_SHA3.as_u64(),
+            ),
+            op if op.is_log() => {
+                let topics = (op.as_u8() - LOG0.as_u8()) as u64;
+                let data_gas = to_u64(frame.back(1))?.checked_mul(LOG_DATA_GAS)?;
+                memory_gas_cost(frame, memory_size)?
+                    .checked_add((topics + 1) * GasCost::LOG.as_u64())?
+                    .checked_add(data_gas)
+            }
+            SLOAD => {
+                let key = frame.back(0);
+                if self.state.slot_in_access_list(contract.address, key) {
+                    Some(WARM_ACCESS)
+                } else {
+                    self.state.add_slot_to_access_list(contract.address, key);
+                    Some(COLD_SLOAD)
+                }
+            }
+            SSTORE => self.sstore_gas(contract, frame),
+            CALL | CALLCODE | DELEGATECALL | STATICCALL => {
+                self.call_gas(op, contract, frame, memory_size)
+            }
+            SELFDESTRUCT => {
+                let beneficiary = frame.back(0).to_address();
+                let mut gas = self.account_access_gas(beneficiary);
+                if gas > 0 {
+                    gas += WARM_ACCESS;
+                }
+                if self.state.empty(&beneficiary)
+                    && !self.state.balance(&contract.address).is_zero()
+                {
+                    gas += GasCost::NEW_ACCOUNT.as_u64();
+                }
+                Some(gas)
+            }
+            _ => unreachable!("opcode without dynamic gas"),
+        }
+    }
+
+    /// Surcharge of the access to a cold account (EIP-2929), which is warm
+    /// afterwards.
+    fn account_access_gas(&mut self, address: Address) -> u64 {
+        if self.state.address_in_access_list(&address) {
+            return 0;
+        }
+        self.state.add_address_to_access_list(address);
+        GasCost::COLD_ACCOUNT_ACCESS.as_u64() - WARM_ACCESS
+    }
+
+    /// Gas of SSTORE as specified by EIP-2200, EIP-2929 and EIP-3529, which
+    /// also updates the refund counter.
+    fn sstore_gas(&mut self, contract: &Contract, frame: &Frame) -> Option<u64> {
+        if contract.gas <= SSTORE_SENTRY_GAS {
+            return None;
+        }
+        let (key, value) = (frame.back(0), frame.back(1));
+        let address = contract.address;
+        let current = self.state.storage(&address, &key);
+        let mut cost = 0;
+        if !self.state.slot_in_access_list(address, key) {
+            cost = COLD_SLOAD;
+            self.state.add_slot_to_access_list(address, key);
+        }
+        if current == value {
+            return Some(cost + WARM_ACCESS);
+        }
+        let original = self.state.committed_storage(&address, &key);
+        if original == current {
+            if original.is_zero() {
+                return Some(cost + GasCost::SSTORE_SET.as_u64());
+            }
+            if value.is_zero() {
+                self.state
+                    .add_refund(GasCost::SSTORE_CLEARS_SCHEDULE.as_u64());
+            }
+            return Some(cost + GasCost::SSTORE_RESET.as_u64());
+        }
+        if !original.is_zero() {
+            if current.is_zero() {
+                self.state
+                    .sub_refund(GasCost::SSTORE_CLEARS_SCHEDULE.as_u64());
+            } else if value.is_zero() {
+                self.state
+                    .add_refund(GasCost::SSTORE_CLEARS_SCHEDULE.as_u64());
+            }
+        }
+        if original == value {
+            if original.is_zero() {
+                self.state
+                    .add_refund(GasCost::SSTORE_SET.as_u64() - WARM_ACCESS);
+            } else {
+                self.state
+                    .add_refund(GasCost::SSTORE_RESET.as_u64() - WARM_ACCESS);
+            }
+        }
+        Some(cost + WARM_ACCESS)
+    }
+
+    /// Dynamic gas of the CALL-like opcodes, which includes the gas passed to
+    /// the callee.
+    fn call_gas(
+        &mut self,
+        op: OpcodeId,
+        contract: &mut Contract,
+        frame: &mut Frame,
+        memory_size: u64,
+    ) -> Option<u64> {
+        let address = frame.back(1).to_address();
+        let cold_gas = self.account_access_gas(address);
+        // The cold access is charged before computing the gas available for
+        // the call.
+        if !contract.use_gas(cold_gas) {
+            return None;
+        }
+
+        let transfers_value =
+            matches!(op, OpcodeId::CALL | OpcodeId::CALLCODE) && !frame.back(2).is_zero();
+        let mut gas = 0;
+        if op == OpcodeId::CALL && transfers_value && self.state.empty(&address) {
+            gas += GasCost::NEW_ACCOUNT.as_u64();
+        }
+        if transfers_value {
+            gas += GasCost::CALL_WITH_VALUE.as_u64();
+        }
+        gas = gas.checked_add(memory_gas_cost(frame, memory_size)?)?;
+
+        // EIP-150: all but one 64th of the available gas
+        let available_gas = contract.gas.wrapping_sub(gas);
+        let capped_gas = available_gas - available_gas / 64;
+        self.call_gas_temp = match to_u64(frame.back(0)) {
+            Some(requested_gas) if requested_gas <= capped_gas => requested_gas,
+            _ => capped_gas,
+        };
+
+        contract.gas += cold_gas;
+        gas.checked_add(self.call_gas_temp)?.checked_add(cold_gas)
+    }
+
+    /// Execute an opcode whose gas has been charged.
+    fn execute(
+        &mut self,
+        op: OpcodeId,
+        pc: usize,
+        contract: &mut Contract,
+        frame: &mut Frame,
+        read_only: bool,
+    ) -> Result<Next, ExecError> {
+        use OpcodeId::*;
+        let block = &self.config.block_constants;
+        match op {
+            STOP => return Ok(Next::Stop(Vec::new())),
+            ADD => {
+                let (a, b) = (frame.pop(), frame.pop());
+                frame.push(a.overflowing_add(b).0);
+            }
+            MUL => {
+                let (a, b) = (frame.pop(), frame.pop());
+                frame.push(a.overflowing_mul(b).0);
+            }
+            SUB => {
+                let (a, b) = (frame.pop(), frame.pop());
+                frame.push(a.overflowing_sub(b).0);
+            }
+            DIV => {
+                let (a, b) = (frame.pop(), frame.pop());
+                frame.push(if b.is_zero() { b } else { a / b });
+            }
+            SDIV => {
+                let (a, b) = (frame.pop(), frame.pop());
+                let quotient = if b.is_zero() { b } else { abs(a) / abs(b) };
+                frame.push(if is_negative(a) != is_negative(b) {
+                    negate(quotient)
+                } else {
+                    quotient
+                });
+            }
+            MOD => {
+                let (a, b) = (frame.pop(), frame.pop());
+                frame.push(if b.is_zero() { b } else { a % b });
+            }
+            SMOD => {
+                let (a, b) = (frame.pop(), frame.pop());
+                let remainder = if b.is_zero() { b } else { abs(a) % abs(b) };
+                frame.push(if is_negative(a) {
+                    negate(remainder)
+                } else {
+                    remainder
+                });
+            }
+            ADDMOD => {
+                let (a, b, n) = (frame.pop(), frame.pop(), frame.pop());
+                frame.push(if n.is_zero() {
+                    n
+                } else {
+                    reduce((U512::from(a) + U512::from(b)) % U512::from(n))
+                });
+            }
+            MULMOD => {
+                let (a, b, n) = (frame.pop(), frame.pop(), frame.pop());
+                frame.push(if n.is_zero() {
+                    n
+                } else {
+                    reduce(a.full_mul(b) % U512::from(n))
+                });
+            }
+            EXP => {
+                let (base, exponent) = (frame.pop(), frame.pop());
+                frame.push(base.overflowing_pow(exponent).0);
+            }
+            SIGNEXTEND => {
+                let (size, value) = (frame.pop(), frame.pop());
+                frame.push(if size < Word::from(31) {
+                    let bit = size.as_usize() * 8 + 7;
+                    let mask = (Word::one() << (bit + 1)) - 1;
+                    if value.bit(bit) {
+                        value | !mask
+                    } else {
+                        value & mask
+                    }
+                } else {
+                    value
+                });
+            }
+            LT => {
+                let (a, b) = (frame.pop(), frame.pop());
+                frame.push(bool_word(a < b));
+            }
+            GT => {
+                let (a, b) = (frame.pop(), frame.pop());
+                frame.push(bool_word(a > b));
+            }
+            SLT => {
+                let (a, b) = (frame.pop(), frame.pop());
+                frame.push(bool_word(signed_lt(a, b)));
+            }
+            SGT => {
+                let (a, b) = (frame.pop(), frame.pop());
+                frame.push(bool_word(signed_lt(b, a)));
+            }
+            EQ => {
+                let (a, b) = (frame.pop(), frame.pop());
+                frame.push(bool_word(a == b));
+            }
+            ISZERO => {
+                let a = frame.pop();
+                frame.push(bool_word(a.is_zero()));
+            }
+            AND => {
+                let (a, b) = (frame.pop(), frame.pop());
+                frame.push(a & b);
+            }
+            OR => {
+                let (a, b) = (frame.pop(), frame.pop());
+                frame.push(a | b);
+            }
+            XOR => {
+                let (a, b) = (frame.pop(), frame.pop());
+                frame.push(a ^ b);
+            }
+            NOT => {
+                let a = frame.pop();
+                frame.push(!a);
+            }
+            BYTE => {
+                let (index, value) = (frame.pop(), frame.pop());
+                frame.push(if index < Word::from(32) {
+                    Word::from(value.byte(31 - index.as_usize()))
+                } else {
+                    Word::zero()
+                });
+            }
+            SHL => {
+                let (shift, value) = (frame.pop(), frame.pop());
+                frame.push(if shift < Word::from(256) {
+                    value << shift.as_usize()
+                } else {
+                    Word::zero()
+                });
+            }
+            SHR => {
+                let (shift, value) = (frame.pop(), frame.pop());
+                frame.push(if shift < Word::from(256) {
+                    value >> shift.as_usize()
+                } else {
+                    Word::zero()
+                });
+            }
+            SAR => {
+                let (shift, value) = (frame.pop(), frame.pop());
+                let shift = if shift < Word::from(256) {
+                    shift.as_usize()
+                } else {
+                    255
+                };
+                frame.push(if is_negative(value) {
+                    !(!value >> shift)
+                } else {
+                    value >> shift
+                });
+            }
+            SHA3 => {
+                let (offset, size) = (frame.pop(), frame.pop());
+                let data = frame.memory_get(offset, size);
+                frame.push(Word::from_big_endian(&keccak256(data)));
+            }
+            ADDRESS => frame.push(contract.address.to_word()),
+            BALANCE => {
+                let address = frame.pop().to_address();
+                frame.push(self.state.balance(&address));
+            }
+            ORIGIN => frame.push(self.origin.to_word()),
+            CALLER => frame.push(contract.caller.to_word()),
+            CALLVALUE => frame.push(contract.value),
+            CALLDATALOAD => {
+                let offset = frame.pop();
+                frame.push(match to_u64(offset) {
+                    Some(offset) => Word::from_big_endian(&get_data(&contract.input, offset, 32)),
+                    None => Word::zero(),
+                });
+            }
+            CALLDATASIZE => frame.push(Word::from(contract.input.len())),
+            CALLDATACOPY | CODECOPY => {
+                let (memory_offset, data_offset, size) = (frame.pop(), frame.pop(), frame.pop());
+                let data = if op == CALLDATACOPY {
+                    &contract.input
+                } else {
+                    &contract.code
+                };
+                let data = get_data(
+                    data,
+                    to_u64(data_offset).unwrap_or(u64::MAX),
+                    size.low_u64(),
+                );
+                frame.memory_set(memory_offset, size, &data);
+            }
+            CODESIZE => frame.push(Word::from(contract.code.len())),
+            GASPRICE => frame.push(self.gas_price),
+            EXTCODESIZE => {
+                let address = frame.pop().to_address();
+                frame.push(Word::from(self.state.code(&address).len()));
+            }
+            EXTCODECOPY => {
+                let address = frame.pop().to_address();
+                let (memory_offset, code_offset, size) = (frame.pop(), frame.pop(), frame.pop());
+                let code = get_data(
+                    self.state.code(&address),
+                    to_u64(code_offset).unwrap_or(u64::MAX),
+                    size.low_u64(),
+                );
+                frame.memory_set(memory_offset, size, &code);
+            }
+            RETURNDATASIZE => frame.push(Word::from(frame.return_data.len())),
+            RETURNDATACOPY => {
+                let (memory_offset, data_offset, size) = (frame.pop(), frame.pop(), frame.pop());
+                let start = to_u64(data_offset).ok_or(ExecError::ReturnDataOutOfBounds)?;
+                let end = to_u64(data_offset.overflowing_add(size).0)
+                    .filter(|end| *end <= frame.return_data.len() as u64)
+                    .ok_or(ExecError::ReturnDataOutOfBounds)?;
+                let data = frame.return_data[start as usize..end as usize].to_vec();
+                frame.memory_set(memory_offset, size, &data);
+            }
+            EXTCODEHASH => {
+                let address = frame.pop().to_address();
+                frame.push(if self.state.empty(&address) {
+                    Word::zero()
+                } else {
+                    hash_word(self.state.code_hash(&address))
+                });
+            }
+            BLOCKHASH => {
+                let number = frame.pop();
+                let current = block.number.as_u64();
+                let lower = current.saturating_sub(256);
+                frame.push(match to_u64(number) {
+                    Some(number) if number >= lower && number < current => {
+                        // The history hashes end with the parent block hash.
+                        let history = &self.config.history_hashes;
+                        (history.len() as u64)
+                            .checked_sub(current - number)
+                            .map(|index| history[index as usize])
+                            .unwrap_or_default()
+                    }
+                    _ => Word::zero(),
+                });
+            }
+            COINBASE => frame.push(block.coinbase.to_word()),
+            TIMESTAMP => frame.push(block.timestamp),
+            NUMBER => frame.push(Word::from(block.number.as_u64())),
+            DIFFICULTY => frame.push(block.difficulty),
+            GASLIMIT => frame.push(Word::from(block.gas_limit.low_u64())),
+            CHAINID => frame.push(self.config.chain_id),
+            SELFBALANCE => frame.push(self.state.balance(&contract.address)),
+            BASEFEE => frame.push(block.base_fee),
+            POP => {
+                frame.pop();
+            }
+            MLOAD => {
+                let offset = frame.pop();
+                let value = Word::from_big_endian(&frame.memory_get(offset, Word::from(32)));
+                frame.push(value);
+            }
+            MSTORE => {
+                let (offset, value) = (frame.pop(), frame.pop());
+                frame.memory_set(offset, Word::from(32), &value.to_be_bytes());
+            }
+            MSTORE8 => {
+                let (offset, value) = (frame.pop(), frame.pop());
+                frame.memory_set(offset, Word::one(), &[value.byte(0)]);
+            }
+            SLOAD => {
+                let key = frame.pop();
+                frame.push(self.state.storage(&contract.address, &key));
+            }
+            SSTORE => {
+                if read_only {
+                    return Err(ExecError::WriteProtection);
+                }
+                let (key, value) = (frame.pop(), frame.pop());
+                self.state.set_storage(contract.address, key, value);
+            }
+            JUMP => {
+                let dest = frame.pop();
+                if !contract.valid_jump_dest(dest) {
+                    return Err(ExecError::InvalidJump);
+                }
+                return Ok(Next::Jump(dest.as_usize()));
+            }
+            JUMPI => {
+                let (dest, condition) = (frame.pop(), frame.pop());
+                if !condition.is_zero() {
+                    if !contract.valid_jump_dest(dest) {
+                        return Err(ExecError::InvalidJump);
+                    }
+                    return Ok(Next::Jump(dest.as_usize()));
+                }
+            }
+            PC => frame.push(Word::from(pc)),
+            MSIZE => frame.push(Word::from(frame.memory.len())),
+            GAS => frame.push(Word::from(contract.gas)),
+            JUMPDEST => (),
+            op if op.is_push() => {
+                let size = (op.as_u8() - PUSH1.as_u8()) as usize + 1;
+                let data = get_data(&contract.code, pc as u64 + 1, size as u64);
+                frame.push(Word::from_big_endian(&data));
+                return Ok(Next::Jump(pc + 1 + size));
+            }
+            op if op.is_dup() => {
+                let n = (op.as_u8() - DUP1.as_u8()) as usize;
+                frame.push(frame.back(n));
+            }
+            op if op.is_swap() => {
+                let n = (op.as_u8() - SWAP1.as_u8()) as usize + 1;
+                let len = frame.stack.len();
+                frame.stack.swap(len - 1, len - 1 - n);
+            }
+            op if op.is_log() => {
+                if read_only {
+                    return Err(ExecError::WriteProtection);
+                }
+                let (pops, _) = stack_io(op);
+                for _ in 0..pops {
+                    frame.pop();
+                }
+            }
+            CREATE | CREATE2 => {
+                if read_only {
+                    return Err(ExecError::WriteProtection);
+                }
+                let (value, offset, size) = (frame.pop(), frame.pop(), frame.pop());
+                let init_code = frame.memory_get(offset, size);
+                let address = if op == CREATE {
+                    get_contract_address(contract.address, self.state.nonce(&contract.address))
+                } else {
+                    let salt = frame.pop();
+                    get_create2_address(
+                        contract.address,
+                        salt.to_be_bytes().to_vec(),
+                        init_code.clone(),
+                    )
+                };
+                // EIP-150: all but one 64th of the available gas
+                let gas = contract.gas - contract.gas / 64;
+                contract.gas -= gas;
+
+                let result = self.create(contract.address, init_code, gas, value, address);
+                frame.push(if result.error.is_none() {
+                    address.to_word()
+                } else {
+                    Word::zero()
+                });
+                contract.gas += result.gas_left;
+                frame.return_data = if result.error == Some(ExecError::ExecutionReverted) {
+                    result.output
+                } else {
+                    Vec::new()
+                };
+            }
+            CALL | CALLCODE | DELEGATECALL | STATICCALL => {
+                frame.pop();
+                let mut gas = self.call_gas_temp;
+                let code_address = frame.pop().to_address();
+                let value = if matches!(op, CALL | CALLCODE) {
+                    frame.pop()
+                } else {
+                    Word::zero()
+                };
+                let (input_offset, input_size) = (frame.pop(), frame.pop());
+                let (output_offset, output_size) = (frame.pop(), frame.pop());
+                let input = frame.memory_get(input_offset, input_size);
+                if op == CALL && read_only && !value.is_zero() {
+                    return Err(ExecError::WriteProtection);
+                }
+                if !value.is_zero() {
+                    gas += GAS_STIPEND_CALL_WITH_VALUE;
+                }
+
+                let result = match op {
+                    CALL => self.call(
+                        CallKind::Call,
+                        contract.address,
+                        code_address,
+                        code_address,
+                        value,
+                        input,
+                        gas,
+                        read_only,
+                    ),
+                    CALLCODE => self.call(
+                        CallKind::CallCode,
+                        contract.address,
+                        contract.address,
+                        code_address,
+                        value,
+                        input,
+                        gas,
+                        read_only,
+                    ),
+                    DELEGATECALL => self.call(
+                        CallKind::DelegateCall,
+                        contract.caller,
+                        contract.address,
+                        code_address,
+                        contract.value,
+                        input,
+                        gas,
+                        read_only,
+                    ),
+                    _ => self.call(
+                        CallKind::StaticCall,
+                        contract.address,
+                        code_address,
+                        code_address,
+                        Word::zero(),
+                        input,
+                        gas,
+                        read_only,
+                    ),
+                };
+                frame.push(bool_word(result.error.is_none()));
+                if matches!(result.error, None | Some(ExecError::ExecutionReverted)) {
+                    frame.memory_set(output_offset, output_size, &result.output);
+                }
+                contract.gas += result.gas_left;
+                frame.return_data = result.output;
+            }
+            RETURN | REVERT => {
+                let (offset, size) = (frame.pop(), frame.pop());
+                let output = frame.memory_get(offset, size);
+                return Ok(if op == RETURN {
+                    Next::Stop(output)
+                } else {
+                    Next::Revert(output)
+                });
+            }
+            SELFDESTRUCT => {
+                if read_only {
+                    return Err(ExecError::WriteProtection);
+                }
+                let beneficiary = frame.pop().to_address();
+                let balance = self.state.balance(&contract.address);
+                self.state.add_balance(beneficiary, balance);
+                self.state.suicide(contract.address);
+                return Ok(Next::Stop(Vec::new()));
+            }
+            INVALID(_) => return Err(ExecError::InvalidOpcode(op)),
+            _ => unreachable!("all opcodes are covered"),
+        }
+        Ok(Next::Continue)
+    }
+
+    /// Record a step, as `StructLogger.CaptureState` of geth.
+    #[allow(clippy::too_many_arguments)]
+    fn capture_state(
+        &mut self,
+        pc: usize,
+        op: OpcodeId,
+        gas: u64,
+        cost: u64,
+        contract: &Contract,
+        frame: &Frame,
+        error: Option<&ExecError>,
+    ) {
+        let logger_config = &self.config.logger_config;
+        let mut storage = Storage::empty();
+        if !logger_config.disable_storage && matches!(op, OpcodeId::SLOAD | OpcodeId::SSTORE) {
+            let contract_storage = self.logger.storage.entry(contract.address).or_default();
+            let entry = if op == OpcodeId::SLOAD && !frame.stack.is_empty() {
+                let key = frame.back(0);
+                Some((key, self.state.storage(&contract.address, &key)))
+            } else if op == OpcodeId::SSTORE && frame.stack.len() >= 2 {
+                Some((frame.back(0), frame.back(1)))
+            } else {
+                None
+            };
+            if let Some((key, value)) = entry {
+                contract_storage.insert(key, value);
+                storage = Storage::new(contract_storage.clone());
+            }
+        }
+
+        self.logger.logs.push(GethExecStep {
+            pc: ProgramCounter(pc),
+            op,
+            gas: Gas(gas),
+            gas_cost: GasCost(cost),
+            refund: Gas(self.state.refund()),
+            depth: self.depth as u16,
+            error: error.map(ToString::to_string),
+            stack: if logger_config.disable_stack {
+                Stack::new()
+            } else {
+                Stack(frame.stack.clone())
+            },
+            memory: if logger_config.enable_memory {
+                Memory(frame.memory.clone())
+            } else {
+                Memory::new()
+            },
+            storage,
+        });
+    }
+}
@@ -0,0 +1,213 @@
+//! World state of the native tracer.
+
+use eth_types::{geth_types, Address, Word, H256};
+use ethers_core::utils::keccak256;
+use std::collections::{HashMap, HashSet};
+
+/// Account of the [`StateDB`].
+#[derive(Debug, Clone, Default)]
+pub(super) struct Account {
+    pub(super) nonce: u64,
+    pub(super) balance: Word,
+    pub(super) code: Vec<u8>,
+    pub(super) storage: HashMap<Word, Word>,
+    /// Storage at the beginning of the current transaction.
+    pub(super) committed_storage: HashMap<Word, Word>,
+    /// Whether the account has executed SELFDESTRUCT in the current
+    /// transaction.
+    pub(super) suicided: bool,
+}
+
+impl Account {
+    /// An account is empty when it has no nonce, balance or code (EIP-161).
+    pub(super) fn is_empty(&self) -> bool {
+        self.nonce == 0 && self.balance.is_zero() && self.code.is_empty()
+    }
+}
+
+/// State of the accounts, with the EIP-2929 access list and the refund
+/// counter of the transaction being executed.
+///
+/// Reverting a call frame restores a clone of the [`StateDB`] taken before the
+/// call, which is simple and fast enough for the small states used in tests.
+#[derive(Debug, Clone, Default)]
+pub(super) struct StateDB {
+    accounts: HashMap<Address, Account>,
+    access_list_addresses: HashSet<Address>,
+    access_list_slots: HashSet<(Address, Word)>,
+    refund: u64,
+}
+
+impl StateDB {
+    /// Create the state from the accounts of the trace config.  Empty accounts
+    /// don't exist, as in geth after the state is finalised.
+    pub(super) fn new(accounts: &HashMap<Address, geth_types::Account>) -> Self {
+        let mut state = Self::default();
+        for (address, account) in accounts {
+            state.accounts.insert(
+                *address,
+                Account {
+                    nonce: account.nonce.low_u64(),
+                    balance: account.balance,
+                    code: account.code.to_vec(),
+                    storage: account.storage.clone(),
+                    ..Default::default()
+                },
+            );
+        }
+        state.finalise();
+        state
+    }
+
+    /// Finalise the state after a transaction: remove the self destructed and
+    /// empty accounts, commit the storage and clear the access list and the
+    /// refund counter.
+    pub(super) fn finalise(&mut self) {
+        self.accounts
+            .retain(|_, account| !account.suicided && !account.is_empty());
+        for account in self.accounts.values_mut() {
+            account.committed_storage = account.storage.clone();
+        }
+        self.access_list_addresses.clear();
+        self.access_list_slots.clear();
+        self.refund = 0;
+    }
+
+    pub(super) fn exist(&self, address: &Address) -> bool {
+        self.accounts.contains_key(address)
+    }
+
+    pub(super) fn empty(&self, address: &Address) -> bool {
+        self.accounts
+            .get(address)
+            .map(Account::is_empty)
+            .unwrap_or(true)
+    }
+
+    /// Create an account, keeping the balance of a previous one at the same
+    /// address.
+    pub(super) fn create_account(&mut self, address: Address) {
+        let balance = self.balance(&address);
+        self.accounts.insert(
+            address,
+            Account {
+                balance,
+                ..Default::default()
+            },
+        );
+    }
+
+    fn account_mut(&mut self, address: Address) -> &mut Account {
+        self.accounts.entry(address).or_default()
+    }
+
+    pub(super) fn nonce(&self, address: &Address) -> u64 {
+        self.accounts.get(address).map(|a| a.nonce).unwrap_or(0)
+    }
+
+    pub(super) fn set_nonce(&mut self, address: Address, nonce: u64) {
+        self.account_mut(address).nonce = nonce;
+    }
+
+    pub(super) fn balance(&self, address: &Address) -> Word {
+        self.accounts
+            .get(address)
+            .map(|a| a.balance)
+            .unwrap_or_default()
+    }
+
+    pub(super) fn add_balance(&mut self, address: Address, value: Word) {
+        let account = self.account_mut(address);
+        account.balance = account.balance.overflowing_add(value).0;
+    }
+
+    pub(super) fn sub_balance(&mut self, address: Address, value: Word) {
+        let account = self.account_mut(address);
+        account.balance = account.balance.overflowing_sub(value).0;
+    }
+
+    /// Transfer `value` from `from` to `to`.  The caller must have checked
+    /// that the balance of `from` is enough.
+    pub(super) fn transfer(&mut self, from: Address, to: Address, value: Word) {
+        self.sub_balance(from, value);
+        self.add_balance(to, value);
+    }
+
+    pub(super) fn code(&self, address: &Address) -> &[u8] {
+        self.accounts
+            .get(address)
+            .map(|a| a.code.as_slice())
+            .unwrap_or_default()
+    }
+
+    /// Hash of the code of an account, which is zero for an account that
+    /// doesn't exist.
+    pub(super) fn code_hash(&self, address: &Address) -> H256 {
+        self.accounts
+            .get(address)
+            .map(|a| H256(keccak256(&a.code)))
+            .unwrap_or_default()
+    }
+
+    pub(super) fn set_code(&mut self, address: Address, code: Vec<u8>) {
+        self.account_mut(address).code = code;
+    }
+
+    pub(super) fn storage(&self, address: &Address, key: &Word) -> Word {
+        self.accounts
+            .get(address)
+            .and_then(|a| a.storage.get(key).copied())
+            .unwrap_or_default()
+    }
+
+    pub(super) fn committed_storage(&self, address: &Address, key: &Word) -> Word {
+        self.accounts
+            .get(address)
+            .and_then(|a| a.committed_storage.get(key).copied())
+            .unwrap_or_default()
+    }
+
+    pub(super) fn set_storage(&mut self, address: Address, key: Word, value: Word) {
+        self.account_mut(address).storage.insert(key, value);
+    }
+
+    /// Mark the account as self destructed and clear its balance.
+    pub(super) fn suicide(&mut self, address: Address) {
+        if let Some(account) = self.accounts.get_mut(&address) {
+            account.suicided = true;
+            account.balance = Word::zero();
+        }
+    }
+
+    pub(super) fn refund(&self) -> u64 {
+        self.refund
+    }
+
+    pub(super) fn add_refund(&mut self, gas: u64) {
+        self.refund += gas;
+    }
+
+    pub(super) fn sub_refund(&mut self, gas: u64) {
+        self.refund = self
+            .refund
+            .checked_sub(gas)
+            .expect("refund counter below zero");
+    }
+
+    pub(super) fn address_in_access_list(&self, address: &Address) -> bool {
+        self.access_list_addresses.contains(address)
+    }
+
+    pub(super) fn slot_in_access_list(&self, address: Address, key: Word) -> bool {
+        self.access_list_slots.contains(&(address, key))
+    }
+
+    pub(super) fn add_address_to_access_list(&mut self, address: Address) {
+        self.access_list_addresses.insert(address);
+    }
+
+    pub(super) fn add_slot_to_access_list(&mut self, address: Address, key: Word) {
+        self.access_list_addresses.insert(address);
+        self.access_list_slots.insert((address, key));
+    }
+}
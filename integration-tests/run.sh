@@ -92,6 +92,7 @@ if [ -n "$STEP_GENDATA" ]; then
     echo "+ Gen blockchain data..."
     git submodule update --init --recursive contracts/vendor
     rm gendata_output.json > /dev/null 2>&1 || true
+    rm -r fixtures > /dev/null 2>&1 || true
     cargo run --bin gen_blockchain_data
 fi
 
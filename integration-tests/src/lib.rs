@@ -3,7 +3,7 @@
 #![deny(rustdoc::broken_intra_doc_links)]
 #![deny(missing_docs)]
 
-use bus_mapping::rpc::GethClient;
+use bus_mapping::rpc::{GethClient, ReplayProvider};
 use env_logger::Env;
 use eth_types::Address;
 use ethers::{
@@ -17,7 +17,8 @@ use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::env::{self, VarError};
-use std::fs::File;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
 use std::sync::Once;
 use std::time::Duration;
 use url::Url;
@@ -36,6 +37,10 @@ pub const CONTRACTS: &[(&str, &str)] = &[
 ];
 /// Path to gen_blockchain_data output file
 pub const GENDATA_OUTPUT_PATH: &str = "gendata_output.json";
+/// Path to the recorded JSON-RPC responses of geth0, with a directory per
+/// test, and to the copy of the gen_blockchain_data output they were recorded
+/// with.
+pub const FIXTURES_PATH: &str = "fixtures";
 
 const GETH0_URL_DEFAULT: &str = "http://localhost:8545";
 
@@ -47,6 +52,10 @@ lazy_static! {
         Err(VarError::NotPresent) => GETH0_URL_DEFAULT.to_string(),
         Err(e) => panic!("Error in GETH0_URL env var: {:?}", e),
     };
+    /// When the `GETH0_REPLAY` env var is set, the JSON-RPC responses of geth0 and the
+    /// gen_blockchain_data output are replayed from [`FIXTURES_PATH`] instead of being queried
+    /// (and recorded there) from the geth0 instance.
+    pub static ref GETH0_REPLAY: bool = env::var("GETH0_REPLAY").is_ok();
 }
 
 static LOG_INIT: Once = Once::new();
@@ -58,10 +67,17 @@ pub fn log_init() {
     });
 }
 
-/// Get the integration test [`GethClient`]
-pub fn get_client() -> GethClient<Http> {
+/// Get the integration test [`GethClient`], which records the responses of
+/// geth0, or replays them if [`GETH0_REPLAY`] is set.  The responses are
+/// recorded in a fixture directory per test, named `test_name`, so that the
+/// tests running in parallel don't write the same fixture files.
+pub fn get_client(test_name: &str) -> GethClient<ReplayProvider<Http>> {
+    let dir = Path::new(FIXTURES_PATH).join(test_name);
+    if *GETH0_REPLAY {
+        return GethClient::new(ReplayProvider::new(dir));
+    }
     let transport = Http::new(Url::parse(&GETH0_URL).expect("invalid url"));
-    GethClient::new(transport)
+    GethClient::new(ReplayProvider::record(dir, transport))
 }
 
 /// Get the integration test [`Provider`]
@@ -72,7 +88,7 @@ pub fn get_provider() -> Provider<Http> {
 
 /// Get the chain id by querying the geth client.
 pub async fn get_chain_id() -> u64 {
-    let client = get_client();
+    let client = get_client("get_chain_id");
     client.get_chain_id().await.unwrap()
 }
 
@@ -107,19 +123,33 @@ pub struct GenDataOutput {
 }
 
 impl GenDataOutput {
-    /// Load [`GenDataOutput`] from the json file.
+    /// Path of the copy of the json file in the fixtures.
+    fn fixture_path() -> PathBuf {
+        Path::new(FIXTURES_PATH).join(GENDATA_OUTPUT_PATH)
+    }
+
+    /// Load [`GenDataOutput`] from the json file, or from its copy in the
+    /// fixtures if [`GETH0_REPLAY`] is set.
     pub fn load() -> Self {
-        serde_json::from_reader(File::open(GENDATA_OUTPUT_PATH).expect("cannot read file"))
+        let path = if *GETH0_REPLAY {
+            Self::fixture_path()
+        } else {
+            PathBuf::from(GENDATA_OUTPUT_PATH)
+        };
+        serde_json::from_reader(File::open(path).expect("cannot read file"))
             .expect("cannot deserialize json from file")
     }
 
-    /// Store [`GenDataOutput`] into the json file.
+    /// Store [`GenDataOutput`] into the json file, and its copy into the
+    /// fixtures.
     pub fn store(&self) {
         serde_json::to_writer(
             &File::create(GENDATA_OUTPUT_PATH).expect("cannot create file"),
             self,
         )
         .expect("cannot serialize json into file");
+        fs::create_dir_all(FIXTURES_PATH).expect("cannot create fixtures dir");
+        fs::copy(GENDATA_OUTPUT_PATH, Self::fixture_path()).expect("cannot copy file");
     }
 }
 
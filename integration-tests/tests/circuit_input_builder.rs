@@ -1,16 +1,17 @@
 #![cfg(feature = "circuit_input_builder")]
 
-use bus_mapping::circuit_input_builder::BuilderClient;
+use bus_mapping::circuit_input_builder::{BlockBundle, BuilderClient};
 use integration_tests::{get_client, log_init, GenDataOutput};
 use lazy_static::lazy_static;
 use log::trace;
+use zkevm_circuits::witness::block_convert_with_proofs;
 
 lazy_static! {
     pub static ref GEN_DATA: GenDataOutput = GenDataOutput::load();
 }
 
 async fn test_circuit_input_builder_block(block_num: u64) {
-    let cli = get_client();
+    let cli = get_client(&format!("circuit_input_builder_block_{}", block_num));
     let cli = BuilderClient::new(cli).await.unwrap();
 
     // 1. Query geth for Block, Txs and TxExecTraces
@@ -20,6 +21,17 @@ async fn test_circuit_input_builder_block(block_num: u64) {
     let access_set = cli.get_state_accesses(&eth_block, &geth_trace).unwrap();
     trace!("AccessSet: {:#?}", access_set);
 
+    // The prestate tracer reports the accounts and storage slots accessed in
+    // the struct logs
+    let prestate_access_set = cli
+        .get_state_accesses_from_prestate(block_num, &eth_block)
+        .await
+        .unwrap();
+    for (address, keys) in access_set.state.iter() {
+        let prestate_keys = prestate_access_set.state.get(address).unwrap();
+        assert!(keys.is_subset(prestate_keys));
+    }
+
     // 3. Query geth for all accounts, storage keys, and codes from Accesses
     let (proofs, codes) = cli.get_state(block_num, access_set).await.unwrap();
 
@@ -34,6 +46,40 @@ async fn test_circuit_input_builder_block(block_num: u64) {
         .unwrap();
 
     trace!("CircuitInputBuilder: {:#?}", builder);
+
+    // The calls match the call frames of the call tracer
+    cli.check_call_traces(block_num, &builder.block)
+        .await
+        .unwrap();
+
+    // The same circuit inputs are generated offline from a saved block bundle
+    let mut bundle_json = Vec::new();
+    cli.get_block_bundle(block_num)
+        .await
+        .unwrap()
+        .save(&mut bundle_json)
+        .unwrap();
+    let bundle = BlockBundle::load(&bundle_json[..]).unwrap();
+    let builder_offline = bundle.gen_inputs().unwrap();
+    assert_eq!(
+        format!("{:?}", builder_offline.block.container),
+        format!("{:?}", builder.block.container)
+    );
+
+    // The state after the block matches the state root of its header
+    bundle.check_state_root(&builder_offline).unwrap();
+
+    // The proofs start at the state root of the parent block, and the MPT
+    // updates of the block can be replayed on them
+    let witness_block = block_convert_with_proofs(
+        &builder_offline.block,
+        &builder_offline.code_db,
+        &bundle.prev_state(),
+        &bundle.proofs,
+        bundle.prev_state_root,
+    )
+    .unwrap();
+    assert_eq!(witness_block.prev_state_root, bundle.prev_state_root);
 }
 
 macro_rules! declare_tests {
@@ -4,7 +4,7 @@ use bus_mapping::circuit_input_builder::BuilderClient;
 use bus_mapping::operation::OperationContainer;
 use eth_types::geth_types;
 use halo2_proofs::{
-    arithmetic::{CurveAffine, Field, FieldExt},
+    arithmetic::CurveAffine,
     dev::MockProver,
     halo2curves::{
         bn256::Fr,
@@ -17,15 +17,13 @@ use log::trace;
 use paste::paste;
 use rand_chacha::rand_core::SeedableRng;
 use rand_chacha::ChaCha20Rng;
-use std::marker::PhantomData;
 use zkevm_circuits::bytecode_circuit::dev::test_bytecode_circuit;
 use zkevm_circuits::copy_circuit::dev::test_copy_circuit;
 use zkevm_circuits::evm_circuit::witness::RwMap;
 use zkevm_circuits::evm_circuit::{test::run_test_circuit, witness::block_convert};
 use zkevm_circuits::state_circuit::StateCircuit;
-use zkevm_circuits::tx_circuit::{
-    sign_verify::SignVerifyChip, Secp256k1Affine, TxCircuit, POW_RAND_SIZE, VERIF_HEIGHT,
-};
+use zkevm_circuits::tx_circuit::{Secp256k1Affine, TxCircuit};
+use zkevm_circuits::util::SubCircuit;
 
 lazy_static! {
     pub static ref GEN_DATA: GenDataOutput = GenDataOutput::load();
@@ -33,7 +31,7 @@ lazy_static! {
 
 async fn test_evm_circuit_block(block_num: u64) {
     log::info!("test evm circuit, block number: {}", block_num);
-    let cli = get_client();
+    let cli = get_client(&format!("evm_circuit_block_{}", block_num));
     let cli = BuilderClient::new(cli).await.unwrap();
     let (builder, _) = cli.gen_inputs(block_num).await.unwrap();
 
@@ -43,7 +41,7 @@ async fn test_evm_circuit_block(block_num: u64) {
 
 async fn test_state_circuit_block(block_num: u64) {
     log::info!("test state circuit, block number: {}", block_num);
-    let cli = get_client();
+    let cli = get_client(&format!("state_circuit_block_{}", block_num));
     let cli = BuilderClient::new(cli).await.unwrap();
     let (builder, _) = cli.gen_inputs(block_num).await.unwrap();
 
@@ -64,11 +62,9 @@ async fn test_state_circuit_block(block_num: u64) {
         ..Default::default()
     });
 
-    let randomness = Fr::from(0xcafeu64);
-    let circuit = StateCircuit::<Fr>::new(randomness, rw_map, 1 << 16);
-    let power_of_randomness = circuit.instance();
+    let circuit = StateCircuit::<Fr>::new(rw_map, 1 << 16);
 
-    let prover = MockProver::<Fr>::run(DEGREE as u32, &circuit, power_of_randomness).unwrap();
+    let prover = MockProver::<Fr>::run(DEGREE as u32, &circuit, circuit.instance()).unwrap();
     prover.verify().expect("state_circuit verification failed");
 }
 
@@ -76,7 +72,7 @@ async fn test_tx_circuit_block(block_num: u64) {
     const DEGREE: u32 = 20;
 
     log::info!("test tx circuit, block number: {}", block_num);
-    let cli = get_client();
+    let cli = get_client(&format!("tx_circuit_block_{}", block_num));
     let cli = BuilderClient::new(cli).await.unwrap();
 
     let (_, eth_block) = cli.gen_inputs(block_num).await.unwrap();
@@ -89,22 +85,9 @@ async fn test_tx_circuit_block(block_num: u64) {
     let mut rng = ChaCha20Rng::seed_from_u64(2);
     let aux_generator = <Secp256k1Affine as CurveAffine>::CurveExt::random(&mut rng).to_affine();
 
-    let randomness = Fr::random(&mut rng);
-    let mut instance: Vec<Vec<Fr>> = (1..POW_RAND_SIZE + 1)
-        .map(|exp| vec![randomness.pow(&[exp as u64, 0, 0, 0]); txs.len() * VERIF_HEIGHT])
-        .collect();
-
-    instance.push(vec![]);
-    let circuit = TxCircuit::<Fr, 4, { 4 * (4 + 32 + 32) }> {
-        sign_verify: SignVerifyChip {
-            aux_generator,
-            window_size: 2,
-            _marker: PhantomData,
-        },
-        randomness,
-        txs,
-        chain_id: CHAIN_ID,
-    };
+    // SignVerifyChip -> ECDSAChip -> MainGate instance column
+    let instance = vec![vec![]];
+    let circuit = TxCircuit::<Fr, 4, { 4 * (4 + 32 + 32) }>::new(aux_generator, CHAIN_ID, txs);
 
     let prover = MockProver::run(DEGREE, &circuit, instance).unwrap();
 
@@ -115,7 +98,7 @@ pub async fn test_bytecode_circuit_block(block_num: u64) {
     const DEGREE: u32 = 16;
 
     log::info!("test bytecode circuit, block number: {}", block_num);
-    let cli = get_client();
+    let cli = get_client(&format!("bytecode_circuit_block_{}", block_num));
     let cli = BuilderClient::new(cli).await.unwrap();
     let (builder, _) = cli.gen_inputs(block_num).await.unwrap();
     let bytecodes: Vec<Vec<u8>> = builder.code_db.0.values().cloned().collect();
@@ -127,7 +110,7 @@ pub async fn test_copy_circuit_block(block_num: u64) {
     const DEGREE: u32 = 16;
 
     log::info!("test copy circuit, block number: {}", block_num);
-    let cli = get_client();
+    let cli = get_client(&format!("copy_circuit_block_{}", block_num));
     let cli = BuilderClient::new(cli).await.unwrap();
     let (builder, _) = cli.gen_inputs(block_num).await.unwrap();
     let block = block_convert(&builder.block, &builder.code_db);
@@ -1,7 +1,9 @@
 //! Mock Block definition and builder related methods.
 
 use crate::{MockTransaction, MOCK_CHAIN_ID};
-use eth_types::{Address, Block, Bytes, Hash, Transaction, Word, H64, U64};
+use eth_types::{
+    geth_types::effective_gas_price, Address, Block, Bytes, Hash, Transaction, Word, H64, U64,
+};
 use ethers_core::types::Bloom;
 use ethers_core::types::OtherFields;
 
@@ -91,7 +93,13 @@ impl From<MockBlock> for Block<Transaction> {
             transactions: mock
                 .transactions
                 .iter_mut()
-                .map(|mock_tx| (mock_tx.chain_id(mock.chain_id).to_owned()).into())
+                .map(|mock_tx| {
+                    let mut tx: Transaction = (mock_tx.chain_id(mock.chain_id).to_owned()).into();
+                    // Like a node does for mined transactions, report the gas price paid in
+                    // this block.
+                    tx.gas_price = Some(effective_gas_price(&tx, mock.base_fee_per_gas));
+                    tx
+                })
                 .collect::<Vec<Transaction>>(),
             size: Some(mock.size),
             mix_hash: Some(mock.mix_hash),
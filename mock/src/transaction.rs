@@ -3,13 +3,16 @@
 use super::{MOCK_ACCOUNTS, MOCK_CHAIN_ID, MOCK_GASPRICE};
 use eth_types::word;
 use eth_types::{
-    geth_types::Transaction as GethTransaction, AccessList, Address, Bytes, Hash, Transaction,
-    Word, U64,
+    geth_types::{Transaction as GethTransaction, ACCESS_LIST_TX_TYPE, DYNAMIC_FEE_TX_TYPE},
+    AccessList, Address, Bytes, Hash, Transaction, Word, U64,
 };
 use ethers_core::types::OtherFields;
 use ethers_core::{
     rand::{CryptoRng, RngCore},
-    types::TransactionRequest,
+    types::{
+        transaction::eip2718::TypedTransaction, Eip1559TransactionRequest,
+        Eip2930TransactionRequest, TransactionRequest,
+    },
 };
 use ethers_signers::{LocalWallet, Signer};
 use lazy_static::lazy_static;
@@ -301,15 +304,42 @@ impl MockTransaction {
     /// Consumes the mutable ref to the MockTransaction returning the structure
     /// by value.
     pub fn build(&mut self) -> Self {
-        let tx = TransactionRequest::new()
-            .from(self.from.address())
-            .to(self.to.clone().unwrap_or_default().address())
-            .nonce(self.nonce)
-            .value(self.value)
-            .data(self.input.clone())
-            .gas(self.gas)
-            .gas_price(self.gas_price)
-            .chain_id(self.chain_id.low_u64());
+        let tx: TypedTransaction = if self.transaction_type == U64::from(DYNAMIC_FEE_TX_TYPE) {
+            Eip1559TransactionRequest::new()
+                .from(self.from.address())
+                .to(self.to.clone().unwrap_or_default().address())
+                .nonce(self.nonce)
+                .value(self.value)
+                .data(self.input.clone())
+                .gas(self.gas)
+                .max_fee_per_gas(self.max_fee_per_gas)
+                .max_priority_fee_per_gas(self.max_priority_fee_per_gas)
+                .access_list(self.access_list.clone())
+                .chain_id(self.chain_id.low_u64())
+                .into()
+        } else if self.transaction_type == U64::from(ACCESS_LIST_TX_TYPE) {
+            let req = TransactionRequest::new()
+                .from(self.from.address())
+                .to(self.to.clone().unwrap_or_default().address())
+                .nonce(self.nonce)
+                .value(self.value)
+                .data(self.input.clone())
+                .gas(self.gas)
+                .gas_price(self.gas_price)
+                .chain_id(self.chain_id.low_u64());
+            Eip2930TransactionRequest::new(req, self.access_list.clone()).into()
+        } else {
+            TransactionRequest::new()
+                .from(self.from.address())
+                .to(self.to.clone().unwrap_or_default().address())
+                .nonce(self.nonce)
+                .value(self.value)
+                .data(self.input.clone())
+                .gas(self.gas)
+                .gas_price(self.gas_price)
+                .chain_id(self.chain_id.low_u64())
+                .into()
+        };
 
         match (self.v, self.r, self.s) {
             (None, None, None) => {
@@ -319,9 +349,16 @@ impl MockTransaction {
                         .from
                         .as_wallet()
                         .with_chain_id(self.chain_id.low_u64())
-                        .sign_transaction_sync(&tx.into());
+                        .sign_transaction_sync(&tx);
+                    // Typed transactions hold the y parity of the signature as v, while
+                    // the wallet returns it in its EIP-155 form.
+                    let v = if !self.transaction_type.is_zero() {
+                        sig.v - 35 - 2 * self.chain_id.low_u64()
+                    } else {
+                        sig.v
+                    };
                     // Set sig parameters
-                    self.sig_data((sig.v, sig.r, sig.s));
+                    self.sig_data((v, sig.r, sig.s));
                 }
             }
             (Some(_), Some(_), Some(_)) => (),
@@ -0,0 +1,199 @@
+//! Versioned files written and read by the prover.
+//!
+//! The binary files (KZG parameters, verifying keys and proofs) start with a
+//! [`Header`] that identifies their kind and the version of the format, so
+//! that a file written by an incompatible version of the prover is rejected
+//! instead of being misinterpreted.  The verifying keys and the proofs are
+//! also tagged with the [`CircuitParams`] they were generated for.  The public
+//! inputs are written as JSON so that they can be inspected and supplied by
+//! the verifier.
+
+use anyhow::{bail, Context, Result};
+use halo2_proofs::halo2curves::{bn256::Fr, group::ff::PrimeField};
+use serde::{Deserialize, Serialize};
+use std::io::{Read, Write};
+use zkevm_circuits::witness::CircuitParams;
+
+/// Magic bytes at the start of every binary file written by the prover.
+const MAGIC: [u8; 8] = *b"ZKEVMPRV";
+
+/// Version of the format of the files written by the prover.  It must be
+/// increased whenever the layout of a file or the circuit changes in a way
+/// that makes the previously written files unusable.
+pub const VERSION: u32 = 3;
+
+/// Kind of a binary file written by the prover.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileKind {
+    /// KZG parameters
+    Params,
+    /// Verifying key of the Super Circuit
+    VerifyingKey,
+    /// Proof of a block
+    Proof,
+}
+
+impl FileKind {
+    fn tag(self) -> u8 {
+        match self {
+            Self::Params => 0,
+            Self::VerifyingKey => 1,
+            Self::Proof => 2,
+        }
+    }
+}
+
+/// Header of a binary file written by the prover.
+pub struct Header;
+
+impl Header {
+    /// Write the header of a file of the given kind.
+    pub fn write<W: Write>(writer: &mut W, kind: FileKind) -> Result<()> {
+        writer.write_all(&MAGIC)?;
+        writer.write_all(&VERSION.to_le_bytes())?;
+        writer.write_all(&[kind.tag()])?;
+        Ok(())
+    }
+
+    /// Read the header of a file, checking that it was written by this
+    /// version of the prover and that it has the expected kind.
+    pub fn read<R: Read>(reader: &mut R, kind: FileKind) -> Result<()> {
+        let mut magic = [0u8; 8];
+        reader
+            .read_exact(&mut magic)
+            .context("could not read file header")?;
+        if magic != MAGIC {
+            bail!("not a file written by the zkEVM prover");
+        }
+        let version = read_u32(reader)?;
+        if version != VERSION {
+            bail!(
+                "file has format version {}, but this prover uses version {}",
+                version,
+                VERSION
+            );
+        }
+        let mut tag = [0u8; 1];
+        reader.read_exact(&mut tag)?;
+        if tag[0] != kind.tag() {
+            bail!("expected a {:?} file, found file kind {}", kind, tag[0]);
+        }
+        Ok(())
+    }
+}
+
+/// Write the [`CircuitParams`] a verifying key or a proof was generated for.
+pub fn write_circuit_params<W: Write>(writer: &mut W, params: &CircuitParams) -> Result<()> {
+    writer.write_all(&params.degree.to_le_bytes())?;
+    for size in [
+        params.max_txs,
+        params.max_calldata,
+        params.max_rws,
+        params.max_bytecode,
+        params.max_copy_rows,
+        params.max_keccak_rows,
+        params.max_evm_rows,
+        params.max_mpt_rows,
+    ] {
+        writer.write_all(&(size as u64).to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Read the [`CircuitParams`] written by [`write_circuit_params`].
+pub fn read_circuit_params<R: Read>(reader: &mut R) -> Result<CircuitParams> {
+    Ok(CircuitParams {
+        degree: read_u32(reader)?,
+        max_txs: read_u64(reader)? as usize,
+        max_calldata: read_u64(reader)? as usize,
+        max_rws: read_u64(reader)? as usize,
+        max_bytecode: read_u64(reader)? as usize,
+        max_copy_rows: read_u64(reader)? as usize,
+        max_keccak_rows: read_u64(reader)? as usize,
+        max_evm_rows: read_u64(reader)? as usize,
+        max_mpt_rows: read_u64(reader)? as usize,
+    })
+}
+
+/// Write the proof bytes, prefixed by their length.
+pub fn write_proof<W: Write>(writer: &mut W, proof: &[u8]) -> Result<()> {
+    writer.write_all(&(proof.len() as u64).to_le_bytes())?;
+    writer.write_all(proof)?;
+    Ok(())
+}
+
+/// Read the proof bytes written by [`write_proof`].
+pub fn read_proof<R: Read>(reader: &mut R) -> Result<Vec<u8>> {
+    let len = read_u64(reader)?;
+    let mut proof = vec![0u8; len as usize];
+    reader
+        .read_exact(&mut proof)
+        .context("proof file is truncated")?;
+    Ok(proof)
+}
+
+/// Public inputs of a proof, as the hex encoding of the little endian
+/// representation of the values of each instance column.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PublicInputs {
+    /// Version of the prover that generated the public inputs
+    pub version: u32,
+    /// Values of each instance column
+    pub instances: Vec<Vec<String>>,
+}
+
+impl PublicInputs {
+    /// Encode the values of the instance columns.
+    pub fn new(instances: &[Vec<Fr>]) -> Self {
+        Self {
+            version: VERSION,
+            instances: instances
+                .iter()
+                .map(|column| {
+                    column
+                        .iter()
+                        .map(|value| hex::encode(value.to_repr()))
+                        .collect()
+                })
+                .collect(),
+        }
+    }
+
+    /// Decode the values of the instance columns.
+    pub fn instances(&self) -> Result<Vec<Vec<Fr>>> {
+        if self.version != VERSION {
+            bail!(
+                "public inputs have format version {}, but this prover uses version {}",
+                self.version,
+                VERSION
+            );
+        }
+        self.instances
+            .iter()
+            .map(|column| {
+                column
+                    .iter()
+                    .map(|value| {
+                        let mut repr = [0u8; 32];
+                        hex::decode_to_slice(value, &mut repr)
+                            .with_context(|| format!("invalid public input {}", value))?;
+                        Option::from(Fr::from_repr(repr))
+                            .with_context(|| format!("public input {} is not in the field", value))
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+fn read_u32<R: Read>(reader: &mut R) -> Result<u32> {
+    let mut bytes = [0u8; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64> {
+    let mut bytes = [0u8; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
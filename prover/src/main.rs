@@ -0,0 +1,440 @@
+//! Command line tool to generate the KZG parameters and the verifying key of
+//! the Super Circuit, and to prove and verify blocks with them.
+//!
+//! The blocks are read from the [`BlockBundle`] files saved with
+//! [`BuilderClient::get_block_bundle`](bus_mapping::circuit_input_builder::BuilderClient),
+//! so that proving doesn't require access to a node.
+//!
+//! The sub-circuits are padded to the sizes of the [`CircuitParams`] given
+//! to `keygen`, which are stored with the verifying key, so that the same
+//! keys can prove any block that fits in them.  The keys are derived from a
+//! circuit without block, so `keygen` doesn't need one.  The maximum number
+//! of transactions and size of the calldata are generics of the Super
+//! Circuit, so they must be one of the [`SUPPORTED_SIZES`] compiled in the
+//! prover.
+//!
+//! The proving key isn't written by `keygen`: the `ProvingKey` of the
+//! halo2_proofs version used by the circuits has no serialization, so it's
+//! derived again from the verifying key and the circuit without block when
+//! proving, which is cheap compared to the proof generation.
+
+mod files;
+
+use anyhow::{bail, Context, Result};
+use bus_mapping::circuit_input_builder::BlockBundle;
+use clap::{Parser, Subcommand};
+use files::{
+    read_circuit_params, read_proof, write_circuit_params, write_proof, FileKind, Header,
+    PublicInputs,
+};
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::{create_proof, keygen_pk, keygen_vk, verify_proof, VerifyingKey},
+    poly::{
+        commitment::{Params, ParamsProver},
+        kzg::{
+            commitment::{KZGCommitmentScheme, ParamsKZG, ParamsVerifierKZG},
+            multiopen::{ProverSHPLONK, VerifierSHPLONK},
+            strategy::SingleStrategy,
+        },
+    },
+    transcript::{
+        Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
+    },
+};
+use rand::rngs::OsRng;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use zkevm_circuits::{
+    super_circuit::SuperCircuit,
+    witness::{CircuitParams, MptState},
+};
+
+/// Maximum number of transactions and size of the calldata of all the
+/// transactions, as `(max_txs, max_calldata)`, of the Super Circuits compiled
+/// in the prover.  Adding a size requires adding it to [`with_sizes`].
+const SUPPORTED_SIZES: [(usize, usize); 3] = [(1, 32), (1, 4096), (4, 16384)];
+
+/// Call the generic function `$run` with the maximum number of transactions
+/// and size of the calldata of `$circuit_params` as its `MAX_TXS` and
+/// `MAX_CALLDATA` generics, failing if they aren't [`SUPPORTED_SIZES`].
+macro_rules! with_sizes {
+    ($circuit_params:expr, $run:ident($($arg:expr),* $(,)?)) => {
+        match ($circuit_params.max_txs, $circuit_params.max_calldata) {
+            (1, 32) => $run::<1, 32>($($arg),*),
+            (1, 4096) => $run::<1, 4096>($($arg),*),
+            (4, 16384) => $run::<4, 16384>($($arg),*),
+            (max_txs, max_calldata) => bail!(
+                "the prover doesn't support circuits of {} txs and {} bytes of calldata, the \
+                 supported (txs, calldata) sizes are {:?}",
+                max_txs,
+                max_calldata,
+                SUPPORTED_SIZES
+            ),
+        }
+    };
+}
+
+/// zkEVM Super Circuit prover and verifier
+#[derive(Parser, Debug)]
+#[clap(author, version, about, long_about = None)]
+struct Args {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Generate KZG parameters for circuits of up to 2^degree rows.  The
+    /// parameters come from a local setup, so they must only be used for
+    /// testing.
+    Params {
+        /// Degree of the parameters
+        #[clap(long)]
+        degree: u32,
+        /// Output parameters file
+        #[clap(long)]
+        out: PathBuf,
+    },
+    /// Derive the verifying key of the Super Circuit padded to the given
+    /// sizes, with the degree of the KZG parameters.
+    Keygen {
+        /// KZG parameters file
+        #[clap(long)]
+        params: PathBuf,
+        /// Output verifying key file
+        #[clap(long)]
+        vk: PathBuf,
+        /// Maximum number of transactions.  The supported (max-txs,
+        /// max-calldata) sizes are (1, 32), (1, 4096) and (4, 16384).
+        #[clap(long)]
+        max_txs: usize,
+        /// Maximum size of the calldata of all the transactions
+        #[clap(long)]
+        max_calldata: usize,
+        /// Number of rows of the rw table and of the State circuit
+        #[clap(long)]
+        max_rws: usize,
+        /// Number of rows of the Bytecode circuit
+        #[clap(long)]
+        max_bytecode: usize,
+        /// Number of rows of the Copy circuit
+        #[clap(long)]
+        max_copy_rows: usize,
+        /// Number of rows of the Keccak circuit
+        #[clap(long)]
+        max_keccak_rows: usize,
+        /// Number of rows of the EVM circuit
+        #[clap(long)]
+        max_evm_rows: usize,
+        /// Number of rows of the MPT circuit
+        #[clap(long)]
+        max_mpt_rows: usize,
+    },
+    /// Prove a block.
+    Prove {
+        /// KZG parameters file
+        #[clap(long)]
+        params: PathBuf,
+        /// Verifying key file
+        #[clap(long)]
+        vk: PathBuf,
+        /// Block bundle file of the block to prove
+        #[clap(long)]
+        block: PathBuf,
+        /// Output proof file
+        #[clap(long)]
+        proof: PathBuf,
+        /// Output public inputs file
+        #[clap(long)]
+        public_inputs: PathBuf,
+    },
+    /// Verify the proof of a block.
+    Verify {
+        /// KZG parameters file
+        #[clap(long)]
+        params: PathBuf,
+        /// Verifying key file
+        #[clap(long)]
+        vk: PathBuf,
+        /// Proof file
+        #[clap(long)]
+        proof: PathBuf,
+        /// Public inputs file
+        #[clap(long)]
+        public_inputs: PathBuf,
+    },
+}
+
+fn main() -> Result<()> {
+    env_logger::init();
+
+    match Args::parse().command {
+        Command::Params { degree, out } => {
+            let params = ParamsKZG::<Bn256>::setup(degree, OsRng);
+            write_params(&out, &params)?;
+        }
+        Command::Keygen {
+            params,
+            vk,
+            max_txs,
+            max_calldata,
+            max_rws,
+            max_bytecode,
+            max_copy_rows,
+            max_keccak_rows,
+            max_evm_rows,
+            max_mpt_rows,
+        } => {
+            let params = read_params(&params)?;
+            let circuit_params = CircuitParams {
+                degree: params.k(),
+                max_txs,
+                max_calldata,
+                max_rws,
+                max_bytecode,
+                max_copy_rows,
+                max_keccak_rows,
+                max_evm_rows,
+                max_mpt_rows,
+            };
+            let verifying_key = with_sizes!(circuit_params, keygen(&params, circuit_params))?;
+            write_vk(&vk, &circuit_params, &verifying_key)?;
+        }
+        Command::Prove {
+            params,
+            vk,
+            block,
+            proof,
+            public_inputs,
+        } => {
+            let params = read_params(&params)?;
+            let (circuit_params, mut vk_reader) = read_vk_header(&vk, &params)?;
+            let (instance, proof_bytes) = with_sizes!(
+                circuit_params,
+                prove(&params, circuit_params, &mut vk_reader, &block)
+            )?;
+
+            let mut writer = create(&proof)?;
+            Header::write(&mut writer, FileKind::Proof)?;
+            write_circuit_params(&mut writer, &circuit_params)?;
+            write_proof(&mut writer, &proof_bytes)?;
+            writer.flush()?;
+            serde_json::to_writer(create(&public_inputs)?, &PublicInputs::new(&instance))?;
+        }
+        Command::Verify {
+            params,
+            vk,
+            proof,
+            public_inputs,
+        } => {
+            let params = read_params(&params)?;
+            let (circuit_params, mut vk_reader) = read_vk_header(&vk, &params)?;
+
+            let mut reader = open(&proof)?;
+            Header::read(&mut reader, FileKind::Proof)?;
+            let proof_params = read_circuit_params(&mut reader)?;
+            if proof_params != circuit_params {
+                bail!(
+                    "proof was generated for the circuit parameters {:?}, but the verifying key \
+                     has {:?}",
+                    proof_params,
+                    circuit_params
+                );
+            }
+            let proof = read_proof(&mut reader)?;
+            let public_inputs: PublicInputs = serde_json::from_reader(open(&public_inputs)?)
+                .context("could not read the public inputs")?;
+            let instance = public_inputs.instances()?;
+
+            with_sizes!(
+                circuit_params,
+                verify(&params, &mut vk_reader, &proof, &instance)
+            )?;
+            println!("proof is valid");
+        }
+    }
+    Ok(())
+}
+
+/// Derive the verifying key of the Super Circuit without block padded to the
+/// circuit parameters.
+fn keygen<const MAX_TXS: usize, const MAX_CALLDATA: usize>(
+    params: &ParamsKZG<Bn256>,
+    circuit_params: CircuitParams,
+) -> Result<VerifyingKey<G1Affine>> {
+    let circuit = keygen_circuit::<MAX_TXS, MAX_CALLDATA>(circuit_params)?;
+    keygen_vk(params, &circuit).context("could not derive vk")
+}
+
+/// Prove the block of a bundle file with the verifying key read from
+/// `vk_reader`, and return the instance and the proof.
+fn prove<const MAX_TXS: usize, const MAX_CALLDATA: usize>(
+    params: &ParamsKZG<Bn256>,
+    circuit_params: CircuitParams,
+    vk_reader: &mut impl Read,
+    block: &Path,
+) -> Result<(Vec<Vec<Fr>>, Vec<u8>)> {
+    let verifying_key =
+        VerifyingKey::read::<_, SuperCircuit<Fr, MAX_TXS, MAX_CALLDATA>>(vk_reader, params)
+            .context("could not read verifying key")?;
+    let proving_key = keygen_pk(
+        params,
+        verifying_key,
+        &keygen_circuit::<MAX_TXS, MAX_CALLDATA>(circuit_params)?,
+    )
+    .context("could not derive pk")?;
+    let (instance, circuit) = build_circuit::<MAX_TXS, MAX_CALLDATA>(block, circuit_params)?;
+
+    let instance_refs: Vec<&[Fr]> = instance.iter().map(|v| &v[..]).collect();
+    let mut transcript = Blake2bWrite::<_, G1Affine, Challenge255<_>>::init(vec![]);
+    create_proof::<
+        KZGCommitmentScheme<Bn256>,
+        ProverSHPLONK<'_, Bn256>,
+        Challenge255<G1Affine>,
+        OsRng,
+        Blake2bWrite<Vec<u8>, G1Affine, Challenge255<G1Affine>>,
+        SuperCircuit<Fr, MAX_TXS, MAX_CALLDATA>,
+    >(
+        params,
+        &proving_key,
+        &[circuit],
+        &[&instance_refs],
+        OsRng,
+        &mut transcript,
+    )
+    .context("could not generate the proof")?;
+    Ok((instance, transcript.finalize()))
+}
+
+/// Verify a proof with the verifying key read from `vk_reader`.
+fn verify<const MAX_TXS: usize, const MAX_CALLDATA: usize>(
+    params: &ParamsKZG<Bn256>,
+    vk_reader: &mut impl Read,
+    proof: &[u8],
+    instance: &[Vec<Fr>],
+) -> Result<()> {
+    let verifying_key =
+        VerifyingKey::read::<_, SuperCircuit<Fr, MAX_TXS, MAX_CALLDATA>>(vk_reader, params)
+            .context("could not read verifying key")?;
+    let instance_refs: Vec<&[Fr]> = instance.iter().map(|v| &v[..]).collect();
+
+    let verifier_params: ParamsVerifierKZG<Bn256> = params.verifier_params().clone();
+    let mut transcript = Blake2bRead::<_, G1Affine, Challenge255<_>>::init(proof);
+    let strategy = SingleStrategy::new(params);
+    if verify_proof::<
+        KZGCommitmentScheme<Bn256>,
+        VerifierSHPLONK<'_, Bn256>,
+        Challenge255<G1Affine>,
+        Blake2bRead<&[u8], G1Affine, Challenge255<G1Affine>>,
+        SingleStrategy<'_, Bn256>,
+    >(
+        &verifier_params,
+        &verifying_key,
+        strategy,
+        &[&instance_refs],
+        &mut transcript,
+    )
+    .is_err()
+    {
+        bail!("proof is invalid");
+    }
+    Ok(())
+}
+
+/// Build the Super Circuit without block padded to the circuit parameters,
+/// from which the keys are derived, checking that all the parameters are set
+/// and that the padded circuit fits in the degree.
+fn keygen_circuit<const MAX_TXS: usize, const MAX_CALLDATA: usize>(
+    circuit_params: CircuitParams,
+) -> Result<SuperCircuit<Fr, MAX_TXS, MAX_CALLDATA>> {
+    let circuit = SuperCircuit::new_for_keygen(circuit_params)
+        .context("could not build the circuit of the keys")?;
+    circuit
+        .check_params()
+        .context("circuit parameters don't fit in the degree")?;
+    Ok(circuit)
+}
+
+/// Build the Super Circuit of the block of a bundle file padded to the
+/// circuit parameters, checking that the block fits in them, and return it
+/// with its instance.
+fn build_circuit<const MAX_TXS: usize, const MAX_CALLDATA: usize>(
+    path: &Path,
+    circuit_params: CircuitParams,
+) -> Result<(Vec<Vec<Fr>>, SuperCircuit<Fr, MAX_TXS, MAX_CALLDATA>)> {
+    let bundle = BlockBundle::load(open(path)?)
+        .with_context(|| format!("could not read block bundle {}", path.display()))?;
+    let builder = bundle
+        .gen_inputs()
+        .context("could not generate the circuit inputs")?;
+    let state = MptState::from_proofs(&bundle.prev_state(), &bundle.proofs, bundle.prev_state_root)
+        .context("could not build the state tries from the proofs")?;
+    let (_, circuit, instance) =
+        SuperCircuit::build_from_circuit_input_builder(&builder, state, circuit_params)
+            .context("could not build the circuit")?;
+    circuit
+        .check_params()
+        .with_context(|| format!("block {} can't be proved", path.display()))?;
+    Ok((instance, circuit))
+}
+
+fn write_params(path: &Path, params: &ParamsKZG<Bn256>) -> Result<()> {
+    let mut writer = create(path)?;
+    Header::write(&mut writer, FileKind::Params)?;
+    params.write(&mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+fn read_params(path: &Path) -> Result<ParamsKZG<Bn256>> {
+    let mut reader = open(path)?;
+    Header::read(&mut reader, FileKind::Params)?;
+    ParamsKZG::read(&mut reader)
+        .with_context(|| format!("could not read parameters {}", path.display()))
+}
+
+fn write_vk(
+    path: &Path,
+    circuit_params: &CircuitParams,
+    verifying_key: &VerifyingKey<G1Affine>,
+) -> Result<()> {
+    let mut writer = create(path)?;
+    Header::write(&mut writer, FileKind::VerifyingKey)?;
+    write_circuit_params(&mut writer, circuit_params)?;
+    verifying_key.write(&mut writer)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read the header of a verifying key with the circuit parameters it was
+/// derived for, checking that they match the KZG parameters, and return the
+/// reader of the key.
+fn read_vk_header(
+    path: &Path,
+    params: &ParamsKZG<Bn256>,
+) -> Result<(CircuitParams, BufReader<File>)> {
+    let mut reader = open(path)?;
+    Header::read(&mut reader, FileKind::VerifyingKey)?;
+    let circuit_params = read_circuit_params(&mut reader)?;
+    if circuit_params.degree != params.k() {
+        bail!(
+            "verifying key has degree {}, but the parameters have degree {}",
+            circuit_params.degree,
+            params.k()
+        );
+    }
+    Ok((circuit_params, reader))
+}
+
+fn create(path: &Path) -> Result<BufWriter<File>> {
+    let file =
+        File::create(path).with_context(|| format!("could not create {}", path.display()))?;
+    Ok(BufWriter::new(file))
+}
+
+fn open(path: &Path) -> Result<BufReader<File>> {
+    let file = File::open(path).with_context(|| format!("could not open {}", path.display()))?;
+    Ok(BufReader::new(file))
+}
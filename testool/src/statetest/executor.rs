@@ -262,14 +262,16 @@ impl StateTest {
                 },
 
                 transactions: vec![geth_types::Transaction {
+                    transaction_type: 0,
+                    block_number: self.env.current_number,
                     from: self.from,
                     to: self.to,
                     nonce: self.nonce,
                     value: self.value,
                     gas_limit: U256::from(self.gas_limit),
                     gas_price: self.gas_price,
-                    gas_fee_cap: U256::zero(),
-                    gas_tip_cap: U256::zero(),
+                    gas_fee_cap: self.gas_price,
+                    gas_tip_cap: self.gas_price,
                     call_data: self.data,
                     access_list: None,
                     v: sig.v,
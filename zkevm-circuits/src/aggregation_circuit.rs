@@ -0,0 +1,471 @@
+//! The Aggregation circuit verifies the proofs of circuits of the zkEVM, like
+//! the ones of the [`SuperCircuit`](crate::super_circuit::SuperCircuit) for
+//! the blocks of a range, in a single SNARK.
+//!
+//! The proofs, created with the KZG (GWC19) multi-open scheme over BN254 and
+//! a [`PoseidonTranscript`], are verified with the succinct verifier, and
+//! their accumulators are folded into a single one with the KZG accumulation
+//! scheme.  The final pairing check is left to the verifier of the
+//! aggregation proof, which gets the limbs of the accumulator as the first
+//! instances, followed by the instances of each aggregated proof, in order.
+//! Every instance of the aggregated proofs is exposed, so none of them can be
+//! chosen freely by the prover of the aggregation proof.
+//!
+//! # Scope
+//!
+//! The circuit doesn't aggregate separate proofs of the EVM, State, Tx,
+//! Bytecode, Copy, Keccak and PI circuits, and doesn't check that the
+//! commitments of their shared tables match across the proofs.  Those
+//! commitments can't be compared: the unusable rows of the advice columns are
+//! filled with random values, the tables of different circuits have different
+//! sizes and layouts, and the second phase columns contain RLCs with the
+//! challenges of each proof.  Binding the tables across proofs would need
+//! table commitments that don't depend on the challenges, which is not
+//! implemented.  Instead, the sub-circuits are proven together in the
+//! [`SuperCircuit`](crate::super_circuit::SuperCircuit), where the tables are
+//! assigned once, and this circuit aggregates its proofs, for instance the
+//! proofs of the blocks of a range.
+
+use ecc::{BaseFieldEccChip, EccConfig};
+use halo2_proofs::{
+    circuit::{Layouter, SimpleFloorPlanner, Value},
+    halo2curves::bn256::{Bn256, Fr, G1Affine},
+    plonk::{create_proof, Circuit, ConstraintSystem, Error, ProvingKey},
+    poly::{
+        commitment::ParamsProver,
+        kzg::{
+            commitment::{KZGCommitmentScheme, ParamsKZG},
+            multiopen::ProverGWC,
+        },
+    },
+    transcript::TranscriptWriterBuffer,
+};
+use itertools::Itertools;
+use maingate::{
+    AssignedValue, MainGate, MainGateConfig, MainGateInstructions, RangeChip, RangeConfig,
+    RangeInstructions, RegionCtx,
+};
+use plonk_verifier::{
+    loader::{self, native::NativeLoader},
+    pcs::{
+        kzg::{
+            Gwc19, Kzg, KzgAccumulator, KzgAs, KzgDecidingKey, KzgSuccinctVerifyingKey,
+            LimbsEncoding,
+        },
+        AccumulationScheme, AccumulationSchemeProver, Decider,
+    },
+    system::halo2::{compile, transcript, Config},
+    util::arithmetic::fe_to_limbs,
+    verifier::{self, PlonkVerifier},
+    Protocol,
+};
+use rand::RngCore;
+use std::{iter, rc::Rc};
+
+/// Number of limbs of a base field element of the accumulator
+pub const LIMBS: usize = 4;
+/// Number of bits of a limb of a base field element of the accumulator
+pub const BITS: usize = 68;
+
+const T: usize = 5;
+const RATE: usize = 4;
+const R_F: usize = 8;
+const R_P: usize = 60;
+
+type Pcs = Kzg<Bn256, Gwc19>;
+type As = KzgAs<Pcs>;
+type Plonk = verifier::Plonk<Pcs, LimbsEncoding<LIMBS, BITS>>;
+type Svk = KzgSuccinctVerifyingKey<G1Affine>;
+type EccChip = BaseFieldEccChip<G1Affine, LIMBS, BITS>;
+type Halo2Loader<'a> = loader::halo2::Halo2Loader<'a, G1Affine, EccChip>;
+
+/// Poseidon transcript of the proofs verified by the Aggregation circuit
+pub type PoseidonTranscript<L, S> =
+    transcript::halo2::PoseidonTranscript<G1Affine, L, S, T, RATE, R_F, R_P>;
+
+/// Proof of a circuit, with the protocol to verify it and its instances
+#[derive(Clone, Debug)]
+pub struct Snark {
+    protocol: Protocol<G1Affine>,
+    instances: Vec<Vec<Fr>>,
+    proof: Vec<u8>,
+}
+
+impl Snark {
+    /// Return a new Snark
+    pub fn new(protocol: Protocol<G1Affine>, instances: Vec<Vec<Fr>>, proof: Vec<u8>) -> Self {
+        Self {
+            protocol,
+            instances,
+            proof,
+        }
+    }
+}
+
+impl From<Snark> for SnarkWitness {
+    fn from(snark: Snark) -> Self {
+        Self {
+            protocol: snark.protocol,
+            instances: snark
+                .instances
+                .into_iter()
+                .map(|instances| instances.into_iter().map(Value::known).collect())
+                .collect(),
+            proof: Value::known(snark.proof),
+        }
+    }
+}
+
+/// Witness of a [`Snark`] in the Aggregation circuit
+#[derive(Clone, Debug)]
+struct SnarkWitness {
+    protocol: Protocol<G1Affine>,
+    instances: Vec<Vec<Value<Fr>>>,
+    proof: Value<Vec<u8>>,
+}
+
+impl SnarkWitness {
+    fn without_witnesses(&self) -> Self {
+        Self {
+            protocol: self.protocol.clone(),
+            instances: self
+                .instances
+                .iter()
+                .map(|instances| vec![Value::unknown(); instances.len()])
+                .collect(),
+            proof: Value::unknown(),
+        }
+    }
+
+    fn proof(&self) -> Value<&[u8]> {
+        self.proof.as_ref().map(Vec::as_slice)
+    }
+}
+
+/// Create a proof of `circuit` with the transcript expected by the
+/// Aggregation circuit, and return it with the protocol to verify it.
+pub fn gen_snark<C: Circuit<Fr>>(
+    params: &ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    circuit: C,
+    instances: Vec<Vec<Fr>>,
+    rng: impl RngCore,
+) -> Result<Snark, Error> {
+    let protocol = compile(
+        params,
+        pk.get_vk(),
+        Config::kzg().with_num_instance(instances.iter().map(Vec::len).collect()),
+    );
+    let instance_refs: Vec<&[Fr]> = instances.iter().map(Vec::as_slice).collect();
+    let mut transcript = PoseidonTranscript::<NativeLoader, _>::init(Vec::new());
+    create_proof::<KZGCommitmentScheme<Bn256>, ProverGWC<_>, _, _, _, _>(
+        params,
+        pk,
+        &[circuit],
+        &[&instance_refs],
+        rng,
+        &mut transcript,
+    )?;
+    Ok(Snark::new(protocol, instances, transcript.finalize()))
+}
+
+/// Verify the snarks in the circuit and return the accumulator of their
+/// verification with the instances of all the snarks.
+fn aggregate<'a>(
+    svk: &Svk,
+    loader: &Rc<Halo2Loader<'a>>,
+    snarks: &[SnarkWitness],
+    as_proof: Value<&'_ [u8]>,
+) -> Result<
+    (
+        KzgAccumulator<G1Affine, Rc<Halo2Loader<'a>>>,
+        Vec<loader::halo2::Scalar<'a, G1Affine, EccChip>>,
+    ),
+    plonk_verifier::Error,
+> {
+    let mut all_instances = Vec::new();
+    let mut accumulators = Vec::new();
+    for snark in snarks {
+        let protocol = snark.protocol.loaded(loader);
+        let instances = snark
+            .instances
+            .iter()
+            .map(|instances| {
+                instances
+                    .iter()
+                    .map(|instance| loader.assign_scalar(*instance))
+                    .collect_vec()
+            })
+            .collect_vec();
+        let mut transcript = PoseidonTranscript::<Rc<Halo2Loader>, _>::new(loader, snark.proof());
+        let proof = Plonk::read_proof(svk, &protocol, &instances, &mut transcript)?;
+        accumulators.extend(Plonk::succinct_verify(svk, &protocol, &instances, &proof)?);
+        all_instances.extend(instances.into_iter().flatten());
+    }
+
+    let mut transcript = PoseidonTranscript::<Rc<Halo2Loader>, _>::new(loader, as_proof);
+    let proof = As::read_proof(&Default::default(), &accumulators, &mut transcript)?;
+    let accumulator = As::verify(&Default::default(), &accumulators, &proof)?;
+
+    Ok((accumulator, all_instances))
+}
+
+/// Config for the Aggregation circuit
+#[derive(Clone, Debug)]
+pub struct AggregationConfig {
+    main_gate_config: MainGateConfig,
+    range_config: RangeConfig,
+}
+
+impl AggregationConfig {
+    /// Return a new AggregationConfig
+    pub fn new(meta: &mut ConstraintSystem<Fr>) -> Self {
+        let main_gate_config = MainGate::<Fr>::configure(meta);
+        let range_config = RangeChip::<Fr>::configure(
+            meta,
+            &main_gate_config,
+            vec![BITS / LIMBS],
+            EccChip::rns().overflow_lengths(),
+        );
+        Self {
+            main_gate_config,
+            range_config,
+        }
+    }
+
+    fn main_gate(&self) -> MainGate<Fr> {
+        MainGate::new(self.main_gate_config.clone())
+    }
+
+    fn range_chip(&self) -> RangeChip<Fr> {
+        RangeChip::new(self.range_config.clone())
+    }
+
+    fn ecc_chip(&self) -> EccChip {
+        EccChip::new(EccConfig::new(
+            self.range_config.clone(),
+            self.main_gate_config.clone(),
+        ))
+    }
+}
+
+/// Aggregation circuit verifying the proofs of circuits of the zkEVM.
+#[derive(Clone, Debug)]
+pub struct AggregationCircuit {
+    svk: Svk,
+    snarks: Vec<SnarkWitness>,
+    instances: Vec<Fr>,
+    as_proof: Value<Vec<u8>>,
+}
+
+impl AggregationCircuit {
+    /// Verify the proofs natively, check the pairing of their accumulator,
+    /// and return the circuit aggregating them.
+    pub fn new(
+        params: &ParamsKZG<Bn256>,
+        snarks: impl IntoIterator<Item = Snark>,
+        rng: impl RngCore,
+    ) -> Result<Self, plonk_verifier::Error> {
+        let svk: Svk = params.get_g()[0].into();
+        let dk: KzgDecidingKey<Bn256> = (params.g2(), params.s_g2()).into();
+        let snarks = snarks.into_iter().collect_vec();
+
+        let mut accumulators = Vec::new();
+        for snark in &snarks {
+            let mut transcript = PoseidonTranscript::<NativeLoader, _>::new(snark.proof.as_slice());
+            let proof =
+                Plonk::read_proof(&svk, &snark.protocol, &snark.instances, &mut transcript)?;
+            accumulators.extend(Plonk::succinct_verify(
+                &svk,
+                &snark.protocol,
+                &snark.instances,
+                &proof,
+            )?);
+        }
+        let mut transcript = PoseidonTranscript::<NativeLoader, _>::new(Vec::new());
+        let accumulator =
+            As::create_proof(&Default::default(), &accumulators, &mut transcript, rng)?;
+        let as_proof = transcript.finalize();
+        if !Pcs::decide(&dk, accumulator.clone()) {
+            return Err(plonk_verifier::Error::AssertionFailure(
+                "the pairing check of the aggregated proofs fails".to_string(),
+            ));
+        }
+
+        let KzgAccumulator { lhs, rhs } = accumulator;
+        let instances = [lhs.x, lhs.y, rhs.x, rhs.y]
+            .map(fe_to_limbs::<_, _, LIMBS, BITS>)
+            .into_iter()
+            .flatten()
+            .chain(
+                snarks
+                    .iter()
+                    .flat_map(|snark| snark.instances.iter().flatten().copied()),
+            )
+            .collect();
+
+        Ok(Self {
+            svk,
+            snarks: snarks.into_iter().map_into().collect(),
+            instances,
+            as_proof: Value::known(as_proof),
+        })
+    }
+
+    /// Positions of the limbs of the accumulator in the instances
+    pub fn accumulator_indices() -> Vec<(usize, usize)> {
+        (0..4 * LIMBS).map(|idx| (0, idx)).collect()
+    }
+
+    /// Number of instances of each instance column
+    pub fn num_instance(&self) -> Vec<usize> {
+        vec![self.instances.len()]
+    }
+
+    /// Instances of the circuit: the limbs of the accumulator followed by the
+    /// instances of each aggregated proof.
+    pub fn instances(&self) -> Vec<Vec<Fr>> {
+        vec![self.instances.clone()]
+    }
+
+    fn as_proof(&self) -> Value<&[u8]> {
+        self.as_proof.as_ref().map(Vec::as_slice)
+    }
+}
+
+impl Circuit<Fr> for AggregationCircuit {
+    type Config = AggregationConfig;
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self {
+            svk: self.svk,
+            snarks: self
+                .snarks
+                .iter()
+                .map(SnarkWitness::without_witnesses)
+                .collect(),
+            instances: Vec::new(),
+            as_proof: Value::unknown(),
+        }
+    }
+
+    fn configure(meta: &mut ConstraintSystem<Fr>) -> Self::Config {
+        AggregationConfig::new(meta)
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<Fr>,
+    ) -> Result<(), Error> {
+        let main_gate = config.main_gate();
+        let range_chip = config.range_chip();
+        range_chip.load_table(&mut layouter)?;
+
+        let (lhs, rhs, instances) = layouter.assign_region(
+            || "aggregation",
+            |region| {
+                let ctx = RegionCtx::new(region, 0);
+                let loader = Halo2Loader::new(config.ecc_chip(), ctx);
+                let (KzgAccumulator { lhs, rhs }, instances) =
+                    aggregate(&self.svk, &loader, &self.snarks, self.as_proof())
+                        .map_err(|_| Error::Synthesis)?;
+                Ok((
+                    lhs.assigned(),
+                    rhs.assigned(),
+                    instances
+                        .iter()
+                        .map(|instance| instance.assigned())
+                        .collect_vec(),
+                ))
+            },
+        )?;
+
+        for (cell, row) in iter::empty()
+            .chain(lhs.x().limbs())
+            .chain(lhs.y().limbs())
+            .chain(rhs.x().limbs())
+            .chain(rhs.y().limbs())
+            .map(AssignedValue::from)
+            .chain(instances)
+            .zip(0..)
+        {
+            main_gate.expose_public(layouter.namespace(|| "instance"), cell, row)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod aggregation_circuit_tests {
+    use super::*;
+    use crate::pi_circuit::{PiCircuit, PublicData};
+    use crate::util::SubCircuit;
+    use eth_types::H256;
+    use halo2_proofs::{
+        dev::MockProver,
+        plonk::{keygen_pk, keygen_vk},
+        poly::commitment::Params,
+    };
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    /// Create a proof of the PublicInputs circuit of `public_data`.
+    fn gen_pi_snark(
+        params: &ParamsKZG<Bn256>,
+        public_data: PublicData,
+        rng: impl RngCore,
+    ) -> Snark {
+        let circuit = PiCircuit::<Fr, 2, 8>::new(public_data);
+        let instances = circuit.instance();
+        let vk = keygen_vk(params, &circuit).unwrap();
+        let pk = keygen_pk(params, vk, &circuit).unwrap();
+        gen_snark(params, &pk, circuit, instances, rng).unwrap()
+    }
+
+    // High memory usage test.  Run in serial with:
+    // `cargo test [...] serial_ -- --ignored --test-threads 1`
+    #[ignore]
+    #[test]
+    fn serial_test_aggregation_of_pi_circuit_proofs() {
+        let mut rng = ChaCha20Rng::seed_from_u64(2);
+        let k = 21;
+        let params = ParamsKZG::<Bn256>::setup(k, &mut rng);
+        let mut snark_params = params.clone();
+        snark_params.downsize(13);
+
+        let snarks = [1, 2].map(|root| {
+            let public_data = PublicData {
+                prev_state_root: H256::from_low_u64_be(root),
+                ..Default::default()
+            };
+            gen_pi_snark(&snark_params, public_data, &mut rng)
+        });
+        let circuit = AggregationCircuit::new(&params, snarks.clone(), &mut rng).unwrap();
+        let instances = circuit.instances();
+        assert_eq!(
+            instances[0][4 * LIMBS..],
+            snarks
+                .iter()
+                .flat_map(|snark| snark.instances.iter().flatten().copied())
+                .collect_vec()
+        );
+        let prover = MockProver::run(k, &circuit, instances.clone()).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+
+        // The instances of the aggregated proofs are constrained.
+        let mut wrong_instances = instances;
+        *wrong_instances[0].last_mut().unwrap() += Fr::from(1);
+        let prover = MockProver::run(k, &circuit, wrong_instances).unwrap();
+        assert!(prover.verify().is_err());
+
+        // A proof checked against wrong instances fails the pairing check.
+        let mut wrong_snark = snarks[1].clone();
+        wrong_snark.instances[0][0] += Fr::from(1);
+        assert!(
+            AggregationCircuit::new(&params, [snarks[0].clone(), wrong_snark], &mut rng).is_err()
+        );
+    }
+}
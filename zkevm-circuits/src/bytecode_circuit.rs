@@ -4,3 +4,7 @@ pub(crate) mod bytecode_unroller;
 /// Bytecode circuit tester
 pub mod dev;
 pub(crate) mod param;
+
+pub use bytecode_unroller::{
+    BytecodeCircuit, BytecodeCircuitConfig, BytecodeCircuitConfigArgs, UnrolledBytecode,
+};
@@ -3,14 +3,18 @@ use crate::{
         and, constraint_builder::BaseConstraintBuilder, not, or, select, RandomLinearCombination,
     },
     table::{BytecodeFieldTag, BytecodeTable, DynamicTableColumns, KeccakTable},
-    util::{Challenges, Expr},
+    util::{Challenges, Expr, SubCircuit, SubCircuitConfig},
+    witness,
 };
 use bus_mapping::evm::OpcodeId;
 use eth_types::{Field, ToLittleEndian, Word};
 use gadgets::is_zero::{IsZeroChip, IsZeroConfig, IsZeroInstruction};
 use halo2_proofs::{
     circuit::{Layouter, Region, Value},
-    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Fixed, Selector, VirtualCells},
+    plonk::{
+        Advice, Column, ConstraintSystem, Error, Expression, Fixed, SecondPhase, Selector,
+        VirtualCells,
+    },
     poly::Rotation,
 };
 use keccak256::plain::Keccak;
@@ -36,7 +40,7 @@ pub struct UnrolledBytecode<F: Field> {
 
 #[derive(Clone, Debug)]
 /// Bytecode circuit configuration
-pub struct Config<F> {
+pub struct BytecodeCircuitConfig<F> {
     minimum_rows: usize,
     q_enable: Column<Fixed>,
     q_first: Column<Fixed>,
@@ -56,19 +60,34 @@ pub struct Config<F> {
     pub(crate) keccak_table: KeccakTable,
 }
 
-impl<F: Field> Config<F> {
-    pub(crate) fn configure(
+/// Circuit configuration arguments
+pub struct BytecodeCircuitConfigArgs<F: Field> {
+    /// BytecodeTable
+    pub bytecode_table: BytecodeTable,
+    /// KeccakTable
+    pub keccak_table: KeccakTable,
+    /// Challenges
+    pub challenges: Challenges<Expression<F>>,
+}
+
+impl<F: Field> SubCircuitConfig<F> for BytecodeCircuitConfig<F> {
+    type ConfigArgs = BytecodeCircuitConfigArgs<F>;
+
+    /// Return a new BytecodeCircuitConfig
+    fn new(
         meta: &mut ConstraintSystem<F>,
-        bytecode_table: BytecodeTable,
-        keccak_table: KeccakTable,
-        challenges: Challenges<Expression<F>>,
+        Self::ConfigArgs {
+            bytecode_table,
+            keccak_table,
+            challenges,
+        }: Self::ConfigArgs,
     ) -> Self {
         let q_enable = meta.fixed_column();
         let q_first = meta.fixed_column();
         let q_last = meta.selector();
         let value = bytecode_table.value;
         let push_rindex = meta.advice_column();
-        let hash_input_rlc = meta.advice_column();
+        let hash_input_rlc = meta.advice_column_in(SecondPhase);
         let code_length = meta.advice_column();
         let byte_push_size = meta.advice_column();
         let is_final = meta.advice_column();
@@ -360,7 +379,7 @@ impl<F: Field> Config<F> {
             constraints
         });
 
-        Config {
+        BytecodeCircuitConfig {
             minimum_rows: meta.minimum_rows(),
             q_enable,
             q_first,
@@ -380,7 +399,9 @@ impl<F: Field> Config<F> {
             keccak_table,
         }
     }
+}
 
+impl<F: Field> BytecodeCircuitConfig<F> {
     pub(crate) fn assign(
         &self,
         layouter: &mut impl Layouter<F>,
@@ -621,6 +642,72 @@ impl<F: Field> Config<F> {
     }
 }
 
+/// BytecodeCircuit proves that the bytecodes of the bytecode table are the
+/// ones of their code hashes.
+#[derive(Clone, Default, Debug)]
+pub struct BytecodeCircuit<F: Field> {
+    /// Unrolled bytecodes
+    pub bytecodes: Vec<UnrolledBytecode<F>>,
+    /// Circuit size, including its unusable rows
+    pub size: usize,
+}
+
+impl<F: Field> BytecodeCircuit<F> {
+    /// Return a new BytecodeCircuit
+    pub fn new(bytecodes: Vec<UnrolledBytecode<F>>, size: usize) -> Self {
+        Self { bytecodes, size }
+    }
+
+    /// Each bytecode takes a header row and a row per byte.
+    fn num_rows_required(block: &witness::Block<F>) -> usize {
+        block
+            .bytecodes
+            .values()
+            .map(|bytecode| bytecode.bytes.len() + 1)
+            .sum()
+    }
+}
+
+impl<F: Field> SubCircuit<F> for BytecodeCircuit<F> {
+    type Config = BytecodeCircuitConfig<F>;
+
+    /// The size is the `max_bytecode` of the circuit params when set.
+    fn new_from_block(block: &witness::Block<F>) -> Self {
+        let bytecodes = block
+            .bytecodes
+            .values()
+            .map(|bytecode| unroll(bytecode.bytes.clone()))
+            .collect();
+        // Instead of using 1 << k - NUM_BLINDING_ROWS, we use a much smaller
+        // number of enabled rows because otherwise it penalizes significantly
+        // the MockProver verification time.
+        let size = match block.circuit_params.max_bytecode {
+            0 => Self::num_rows_required(block) + 64,
+            max_bytecode => max_bytecode,
+        };
+        Self::new(bytecodes, size)
+    }
+
+    /// Make the assignments to the BytecodeCircuit, including the bytecode
+    /// table
+    fn synthesize_sub(
+        &self,
+        config: &Self::Config,
+        challenges: &Challenges<Value<F>>,
+        layouter: &mut impl Layouter<F>,
+    ) -> Result<(), Error> {
+        config.load(layouter)?;
+        config.assign(layouter, self.size, &self.bytecodes, challenges)
+    }
+
+    /// The push table takes 256 rows.
+    fn min_num_rows_block(block: &witness::Block<F>) -> usize {
+        Self::num_rows_required(block)
+            .max(block.circuit_params.max_bytecode)
+            .max(256)
+    }
+}
+
 /// Get unrolled bytecode from raw bytes
 pub fn unroll<F: Field>(bytes: Vec<u8>) -> UnrolledBytecode<F> {
     let code_hash = keccak(&bytes[..]);
@@ -691,10 +778,6 @@ mod tests {
     use eth_types::Bytecode;
     use halo2_proofs::halo2curves::bn256::Fr;
 
-    fn get_randomness<F: Field>() -> F {
-        F::from(123456)
-    }
-
     /// Verify unrolling code
     #[test]
     fn bytecode_unrolling() {
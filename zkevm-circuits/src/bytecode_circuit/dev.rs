@@ -1,6 +1,8 @@
-use super::bytecode_unroller::{unroll, Config, UnrolledBytecode};
+use super::bytecode_unroller::{
+    unroll, BytecodeCircuitConfig, BytecodeCircuitConfigArgs, UnrolledBytecode,
+};
 use crate::table::{BytecodeTable, KeccakTable};
-use crate::util::Challenges;
+use crate::util::{Challenges, SubCircuitConfig};
 use eth_types::Field;
 use halo2_proofs::{
     circuit::Layouter,
@@ -15,7 +17,7 @@ pub(crate) struct BytecodeCircuitTester<F: Field> {
 }
 
 impl<F: Field> Circuit<F> for BytecodeCircuitTester<F> {
-    type Config = (Config<F>, Challenges);
+    type Config = (BytecodeCircuitConfig<F>, Challenges);
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
@@ -29,7 +31,14 @@ impl<F: Field> Circuit<F> for BytecodeCircuitTester<F> {
 
         let config = {
             let challenges = challenges.exprs(meta);
-            Config::configure(meta, bytecode_table, keccak_table, challenges)
+            BytecodeCircuitConfig::new(
+                meta,
+                BytecodeCircuitConfigArgs {
+                    bytecode_table,
+                    keccak_table,
+                    challenges,
+                },
+            )
         };
 
         (config, challenges)
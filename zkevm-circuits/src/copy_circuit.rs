@@ -12,10 +12,10 @@ use gadgets::{
 };
 use halo2_proofs::{
     circuit::{Layouter, Region, Value},
-    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Fixed, Selector},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Fixed, SecondPhase, Selector},
     poly::Rotation,
 };
-use std::iter::once;
+use std::{iter::once, marker::PhantomData};
 
 use crate::{
     evm_circuit::{
@@ -23,8 +23,10 @@ use crate::{
         witness::Block,
     },
     table::{
-        BytecodeFieldTag, CopyTable, LookupTable, RwTableTag, TxContextFieldTag, TxLogFieldTag,
+        BytecodeFieldTag, BytecodeTable, CopyTable, LookupTable, RwTable, RwTableTag,
+        TxContextFieldTag, TxLogFieldTag, TxTable,
     },
+    util::{Challenges, SubCircuit, SubCircuitConfig},
 };
 
 /// Encode the type `NumberOrHash` into a field element
@@ -47,7 +49,7 @@ pub fn number_or_hash_to_field<F: Field>(v: &NumberOrHash, randomness: F) -> F {
 
 /// The rw table shared between evm circuit and state circuit
 #[derive(Clone, Copy, Debug)]
-pub struct CopyCircuit<F> {
+pub struct CopyCircuitConfig<F> {
     /// Whether the row is enabled or not.
     pub q_enable: Column<Fixed>,
     /// Whether this row denotes a step. A read row is a step and a write row is
@@ -71,21 +73,41 @@ pub struct CopyCircuit<F> {
     pub addr_lt_addr_end: LtConfig<F, 8>,
 }
 
-impl<F: Field> CopyCircuit<F> {
+/// Circuit configuration arguments
+pub struct CopyCircuitConfigArgs<F: Field> {
+    /// TxTable
+    pub tx_table: TxTable,
+    /// RwTable
+    pub rw_table: RwTable,
+    /// BytecodeTable
+    pub bytecode_table: BytecodeTable,
+    /// CopyTable
+    pub copy_table: CopyTable,
+    /// q_enable
+    pub q_enable: Column<Fixed>,
+    /// Challenges
+    pub challenges: Challenges<Expression<F>>,
+}
+
+impl<F: Field> SubCircuitConfig<F> for CopyCircuitConfig<F> {
+    type ConfigArgs = CopyCircuitConfigArgs<F>;
+
     /// Configure the Copy Circuit constraining read-write steps and doing
     /// appropriate lookups to the Tx Table, RW Table and Bytecode Table.
-    pub fn configure(
+    fn new(
         meta: &mut ConstraintSystem<F>,
-        tx_table: &dyn LookupTable<F>,
-        rw_table: &dyn LookupTable<F>,
-        bytecode_table: &dyn LookupTable<F>,
-        copy_table: CopyTable,
-        q_enable: Column<Fixed>,
-        randomness: Expression<F>,
+        Self::ConfigArgs {
+            tx_table,
+            rw_table,
+            bytecode_table,
+            copy_table,
+            q_enable,
+            challenges,
+        }: Self::ConfigArgs,
     ) -> Self {
         let q_step = meta.complex_selector();
         let is_last = meta.advice_column();
-        let value = meta.advice_column();
+        let value = meta.advice_column_in(SecondPhase);
         let is_code = meta.advice_column();
         let is_pad = meta.advice_column();
         let is_first = copy_table.is_first;
@@ -205,7 +227,11 @@ impl<F: Field> CopyCircuit<F> {
                 },
             );
 
-            cb.gate(meta.query_fixed(q_enable, Rotation::cur()))
+            let is_padding = tag.value_equals(CopyDataType::Padding, Rotation::cur())(meta);
+            cb.gate(and::expr([
+                meta.query_fixed(q_enable, Rotation::cur()),
+                not::expr(is_padding),
+            ]))
         });
 
         meta.create_gate("verify step (q_step == 1)", |meta| {
@@ -264,7 +290,11 @@ impl<F: Field> CopyCircuit<F> {
                 meta.query_advice(is_pad, Rotation::next()),
             );
 
-            cb.gate(meta.query_selector(q_step))
+            let is_padding = tag.value_equals(CopyDataType::Padding, Rotation::cur())(meta);
+            cb.gate(and::expr([
+                meta.query_selector(q_step),
+                not::expr(is_padding),
+            ]))
         });
 
         meta.create_gate("verify_step (q_step == 0)", |meta| {
@@ -273,7 +303,7 @@ impl<F: Field> CopyCircuit<F> {
             cb.require_equal(
                 "rows[2].value == rows[0].value * r + rows[1].value",
                 meta.query_advice(value, Rotation(2)),
-                meta.query_advice(value, Rotation::cur()) * randomness
+                meta.query_advice(value, Rotation::cur()) * challenges.keccak_input()
                     + meta.query_advice(value, Rotation::next()),
             );
 
@@ -375,13 +405,17 @@ impl<F: Field> CopyCircuit<F> {
             copy_table,
         }
     }
+}
 
-    /// Assign a witness block to the Copy Circuit.
-    pub fn assign_block(
+impl<F: Field> CopyCircuitConfig<F> {
+    /// Assign the copy events to the Copy Circuit, with its enabled rows padded
+    /// up to `max_copy_rows`.
+    pub fn assign_copy_events(
         &self,
         layouter: &mut impl Layouter<F>,
-        block: &Block<F>,
-        randomness: F,
+        copy_events: &[CopyEvent],
+        max_copy_rows: usize,
+        challenges: &Challenges<Value<F>>,
     ) -> Result<(), Error> {
         let tag_chip = BinaryNumberChip::construct(self.copy_table.tag);
         let lt_chip = LtChip::construct(self.addr_lt_addr_end);
@@ -390,18 +424,20 @@ impl<F: Field> CopyCircuit<F> {
             || "assign copy table",
             |mut region| {
                 let mut offset = 0;
-                for copy_event in block.copy_events.iter() {
+                for copy_event in copy_events.iter() {
                     let rlc_acc = if copy_event.dst_type == CopyDataType::RlcAcc {
                         let values = copy_event
                             .bytes
                             .iter()
                             .map(|(value, _is_code)| *value)
                             .collect::<Vec<u8>>();
-                        rlc::value(values.iter().rev(), randomness)
+                        challenges
+                            .keccak_input()
+                            .map(|keccak_input| rlc::value(values.iter().rev(), keccak_input))
                     } else {
-                        F::zero()
+                        Value::known(F::zero())
                     };
-                    let mut value_acc = F::zero();
+                    let mut value_acc = Value::known(F::zero());
                     for (step_idx, (is_read_step, copy_step)) in copy_event
                         .bytes
                         .iter()
@@ -428,19 +464,19 @@ impl<F: Field> CopyCircuit<F> {
                     {
                         let value = if copy_event.dst_type == CopyDataType::RlcAcc {
                             if is_read_step {
-                                F::from(copy_step.value as u64)
+                                Value::known(F::from(copy_step.value as u64))
                             } else {
-                                value_acc =
-                                    value_acc * randomness + F::from(copy_step.value as u64);
+                                value_acc = value_acc * challenges.keccak_input()
+                                    + Value::known(F::from(copy_step.value as u64));
                                 value_acc
                             }
                         } else {
-                            F::from(copy_step.value as u64)
+                            Value::known(F::from(copy_step.value as u64))
                         };
                         self.assign_step(
                             &mut region,
                             offset,
-                            randomness,
+                            challenges,
                             copy_event,
                             step_idx,
                             &copy_step,
@@ -452,9 +488,15 @@ impl<F: Field> CopyCircuit<F> {
                         offset += 1;
                     }
                 }
+                // pad the enabled rows up to the maximum number of rows, so
+                // that the fixed columns don't depend on the copy events
+                while offset + 2 < max_copy_rows {
+                    self.assign_padding_row(&mut region, offset, true, &tag_chip, &lt_chip)?;
+                    offset += 1;
+                }
                 // pad two rows in the end to satisfy Halo2 cell assignment check
                 for _ in 0..2 {
-                    self.assign_padding_row(&mut region, offset, &tag_chip)?;
+                    self.assign_padding_row(&mut region, offset, false, &tag_chip, &lt_chip)?;
                     offset += 1;
                 }
                 Ok(())
@@ -467,12 +509,12 @@ impl<F: Field> CopyCircuit<F> {
         &self,
         region: &mut Region<F>,
         offset: usize,
-        randomness: F,
+        challenges: &Challenges<Value<F>>,
         copy_event: &CopyEvent,
         step_idx: usize,
         copy_step: &CopyStep,
-        value: F,
-        rlc_acc: F,
+        value: Value<F>,
+        rlc_acc: Value<F>,
         tag_chip: &BinaryNumberChip<F, CopyDataType, 3>,
         lt_chip: &LtChip<F, 8>,
     ) -> Result<(), Error> {
@@ -521,7 +563,11 @@ impl<F: Field> CopyCircuit<F> {
             || format!("assign id {}", offset),
             self.copy_table.id,
             offset,
-            || Value::known(number_or_hash_to_field(id, randomness)),
+            || {
+                challenges
+                    .evm_word()
+                    .map(|evm_word| number_or_hash_to_field(id, evm_word))
+            },
         )?;
         // addr
         let copy_step_addr: u64 =
@@ -552,14 +598,14 @@ impl<F: Field> CopyCircuit<F> {
             || format!("assign value {}", offset),
             self.value,
             offset,
-            || Value::known(value),
+            || value,
         )?;
         // rlc_acc
         region.assign_advice(
             || format!("assign rlc_acc {}", offset),
             self.copy_table.rlc_acc,
             offset,
-            || Value::known(rlc_acc),
+            || rlc_acc,
         )?;
         // is_code
         region.assign_advice(
@@ -624,19 +670,29 @@ impl<F: Field> CopyCircuit<F> {
         Ok(())
     }
 
+    /// Assign a padding row, which is enabled when it's within the maximum
+    /// number of rows of the circuit.  The constraints of the copy steps are
+    /// disabled on the padding rows by their tag.
     fn assign_padding_row(
         &self,
         region: &mut Region<F>,
         offset: usize,
+        enabled: bool,
         tag_chip: &BinaryNumberChip<F, CopyDataType, 3>,
+        lt_chip: &LtChip<F, 8>,
     ) -> Result<(), Error> {
         // q_enable
         region.assign_fixed(
             || "q_enable",
             self.q_enable,
             offset,
-            || Value::known(F::zero()),
+            || Value::known(F::from(enabled as u64)),
         )?;
+        // enable q_step on the rows where it would be enabled for a Read step
+        if enabled && offset % 2 == 0 {
+            self.q_step.enable(region, offset)?;
+            lt_chip.assign(region, offset, F::zero(), F::zero())?;
+        }
         // is_first
         region.assign_advice(
             || format!("assign is_first {}", offset),
@@ -722,11 +778,64 @@ impl<F: Field> CopyCircuit<F> {
             || Value::known(F::zero()),
         )?;
         // tag
-        tag_chip.assign(region, offset, &CopyDataType::default())?;
+        tag_chip.assign(region, offset, &CopyDataType::Padding)?;
         Ok(())
     }
 }
 
+/// Copy Circuit proving the copy events of a block
+#[derive(Clone, Default, Debug)]
+pub struct CopyCircuit<F: Field> {
+    /// Copy events
+    pub copy_events: Vec<CopyEvent>,
+    /// Max number of rows in copy circuit
+    pub max_copy_rows: usize,
+    _marker: PhantomData<F>,
+}
+
+impl<F: Field> CopyCircuit<F> {
+    /// Return a new CopyCircuit
+    pub fn new(copy_events: Vec<CopyEvent>, max_copy_rows: usize) -> Self {
+        Self {
+            copy_events,
+            max_copy_rows,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<F: Field> SubCircuit<F> for CopyCircuit<F> {
+    type Config = CopyCircuitConfig<F>;
+
+    fn new_from_block(block: &Block<F>) -> Self {
+        Self::new(
+            block.copy_events.clone(),
+            block.circuit_params.max_copy_rows,
+        )
+    }
+
+    /// Make the assignments to the CopyCircuit, including the copy table
+    fn synthesize_sub(
+        &self,
+        config: &Self::Config,
+        challenges: &Challenges<Value<F>>,
+        layouter: &mut impl Layouter<F>,
+    ) -> Result<(), Error> {
+        config.assign_copy_events(layouter, &self.copy_events, self.max_copy_rows, challenges)
+    }
+
+    /// Each copied byte takes a read and a write row, and the copy events are
+    /// followed by two disabled rows.
+    fn min_num_rows_block(block: &Block<F>) -> usize {
+        (2 + block
+            .copy_events
+            .iter()
+            .map(|copy_event| copy_event.bytes.len() * 2)
+            .sum::<usize>())
+        .max(block.circuit_params.max_copy_rows)
+    }
+}
+
 /// Dev helpers
 #[cfg(any(feature = "test", test))]
 pub mod dev {
@@ -741,7 +850,7 @@ pub mod dev {
     use crate::{
         evm_circuit::witness::Block,
         table::{BytecodeTable, RwTable, TxTable},
-        util::{power_of_randomness_from_instance, Challenges},
+        util::{Challenges, SubCircuit, SubCircuitConfig},
     };
 
     #[derive(Clone)]
@@ -749,30 +858,22 @@ pub mod dev {
         tx_table: TxTable,
         rw_table: RwTable,
         bytecode_table: BytecodeTable,
-        copy_circuit: CopyCircuit<F>,
+        copy_circuit: CopyCircuitConfig<F>,
     }
 
     #[derive(Default)]
     struct CopyCircuitTester<F> {
         block: Block<F>,
-        randomness: F,
     }
 
     impl<F: Field> CopyCircuitTester<F> {
-        fn get_randomness() -> F {
-            F::random(rand::thread_rng())
-        }
-
-        pub fn new(block: Block<F>, randomness: F) -> Self {
-            Self { block, randomness }
-        }
-        pub fn r() -> Expression<F> {
-            123456u64.expr()
+        pub fn new(block: Block<F>) -> Self {
+            Self { block }
         }
     }
 
     impl<F: Field> Circuit<F> for CopyCircuitTester<F> {
-        type Config = CopyCircuitTesterConfig<F>;
+        type Config = (CopyCircuitTesterConfig<F>, Challenges);
         type FloorPlanner = SimpleFloorPlanner;
 
         fn without_witnesses(&self) -> Self {
@@ -784,62 +885,64 @@ pub mod dev {
             let rw_table = RwTable::construct(meta);
             let bytecode_table = BytecodeTable::construct(meta);
             let q_enable = meta.fixed_column();
+            let challenges = Challenges::construct(meta);
 
-            let randomness = power_of_randomness_from_instance::<_, 1>(meta);
             let copy_table = CopyTable::construct(meta, q_enable);
-            let copy_circuit = CopyCircuit::configure(
+            let copy_circuit = CopyCircuitConfig::new(
                 meta,
-                &tx_table,
-                &rw_table,
-                &bytecode_table,
-                copy_table,
-                q_enable,
-                randomness[0].clone(),
+                CopyCircuitConfigArgs {
+                    tx_table: tx_table.clone(),
+                    rw_table,
+                    bytecode_table: bytecode_table.clone(),
+                    copy_table,
+                    q_enable,
+                    challenges: challenges.exprs(meta),
+                },
             );
 
-            CopyCircuitTesterConfig {
+            let config = CopyCircuitTesterConfig {
                 tx_table,
                 rw_table,
                 bytecode_table,
                 copy_circuit,
-            }
+            };
+
+            (config, challenges)
         }
 
         fn synthesize(
             &self,
-            config: Self::Config,
+            (config, challenges): Self::Config,
             mut layouter: impl Layouter<F>,
         ) -> Result<(), halo2_proofs::plonk::Error> {
-            let challenges = Challenges::mock(Value::known(self.randomness));
+            let challenges = challenges.values(&mut layouter);
 
             config
                 .tx_table
-                .load(&mut layouter, &self.block.txs, self.randomness)?;
+                .load(&mut layouter, &self.block.txs, &challenges)?;
             config.rw_table.load(
                 &mut layouter,
                 &self.block.rws.table_assignments(),
-                self.block.state_circuit_pad_to,
-                self.randomness,
+                self.block.circuit_params.max_rws,
+                &challenges,
             )?;
             config.bytecode_table.load(
                 &mut layouter,
                 self.block.bytecodes.values(),
                 &challenges,
             )?;
-            config
-                .copy_circuit
-                .assign_block(&mut layouter, &self.block, self.randomness)
+            CopyCircuit::new_from_block(&self.block).synthesize_sub(
+                &config.copy_circuit,
+                &challenges,
+                &mut layouter,
+            )
         }
     }
 
     /// Test copy circuit with the provided block witness
     pub fn test_copy_circuit<F: Field>(k: u32, block: Block<F>) -> Result<(), Vec<VerifyFailure>> {
-        let randomness = CopyCircuitTester::<F>::get_randomness();
-        let circuit = CopyCircuitTester::<F>::new(block, randomness);
-        let num_rows = 1 << k;
-        const NUM_BLINDING_ROWS: usize = 7 - 1;
-        let instance = vec![vec![randomness; num_rows - NUM_BLINDING_ROWS]];
-        let prover = MockProver::<F>::run(k, &circuit, instance).unwrap();
+        let circuit = CopyCircuitTester::<F>::new(block);
+        let prover = MockProver::<F>::run(k, &circuit, vec![]).unwrap();
         prover.verify()
     }
 }
@@ -928,6 +1031,14 @@ mod tests {
         assert_eq!(test_copy_circuit(10, block), Ok(()));
     }
 
+    #[test]
+    fn copy_circuit_valid_codecopy_padded() {
+        let builder = gen_codecopy_data();
+        let mut block = block_convert(&builder.block, &builder.code_db);
+        block.circuit_params.max_copy_rows = 500;
+        assert_eq!(test_copy_circuit(10, block), Ok(()));
+    }
+
     #[test]
     fn copy_circuit_valid_sha3() {
         let builder = gen_sha3_data();
@@ -2,7 +2,7 @@
 
 #![allow(missing_docs)]
 use halo2_proofs::{
-    circuit::{Layouter, Value},
+    circuit::{AssignedCell, Layouter, SimpleFloorPlanner, Value},
     plonk::*,
 };
 
@@ -13,57 +13,102 @@ pub(crate) mod util;
 
 pub mod table;
 
-use crate::table::LookupTable;
 pub use crate::witness;
+use crate::{
+    table::{BlockTable, BytecodeTable, CopyTable, KeccakTable, RwTable, TxTable},
+    util::{Challenges, SubCircuit, SubCircuitConfig},
+};
 use eth_types::Field;
 use execution::ExecutionConfig;
 use itertools::Itertools;
+use strum::IntoEnumIterator;
 use table::FixedTableTag;
 use witness::Block;
 
-/// EvmCircuit implements verification of execution trace of a block.
+/// EvmCircuitConfig implements verification of execution trace of a block.
 #[derive(Clone, Debug)]
-pub struct EvmCircuit<F> {
+pub struct EvmCircuitConfig<F> {
     fixed_table: [Column<Fixed>; 4],
     byte_table: [Column<Fixed>; 1],
     pub(crate) execution: Box<ExecutionConfig<F>>,
+    // The state of the first step and of the step following the last step
+    instance: Column<Instance>,
+    // External tables
+    tx_table: TxTable,
+    rw_table: RwTable,
+    bytecode_table: BytecodeTable,
+    block_table: BlockTable,
+    copy_table: CopyTable,
+    keccak_table: KeccakTable,
 }
 
-impl<F: Field> EvmCircuit<F> {
-    /// Configure EvmCircuit
-    #[allow(clippy::too_many_arguments)]
-    pub fn configure(
+/// Circuit configuration arguments
+pub struct EvmCircuitConfigArgs<F: Field> {
+    /// Challenge
+    pub challenges: Challenges<Expression<F>>,
+    /// TxTable
+    pub tx_table: TxTable,
+    /// RwTable
+    pub rw_table: RwTable,
+    /// BytecodeTable
+    pub bytecode_table: BytecodeTable,
+    /// BlockTable
+    pub block_table: BlockTable,
+    /// CopyTable
+    pub copy_table: CopyTable,
+    /// KeccakTable
+    pub keccak_table: KeccakTable,
+}
+
+impl<F: Field> SubCircuitConfig<F> for EvmCircuitConfig<F> {
+    type ConfigArgs = EvmCircuitConfigArgs<F>;
+
+    /// Configure EvmCircuitConfig
+    fn new(
         meta: &mut ConstraintSystem<F>,
-        power_of_randomness: [Expression<F>; 31],
-        tx_table: &dyn LookupTable<F>,
-        rw_table: &dyn LookupTable<F>,
-        bytecode_table: &dyn LookupTable<F>,
-        block_table: &dyn LookupTable<F>,
-        copy_table: &dyn LookupTable<F>,
-        keccak_table: &dyn LookupTable<F>,
+        Self::ConfigArgs {
+            challenges,
+            tx_table,
+            rw_table,
+            bytecode_table,
+            block_table,
+            copy_table,
+            keccak_table,
+        }: Self::ConfigArgs,
     ) -> Self {
         let fixed_table = [(); 4].map(|_| meta.fixed_column());
         let byte_table = [(); 1].map(|_| meta.fixed_column());
         let execution = Box::new(ExecutionConfig::configure(
             meta,
-            power_of_randomness,
+            challenges,
             &fixed_table,
             &byte_table,
-            tx_table,
-            rw_table,
-            bytecode_table,
-            block_table,
-            copy_table,
-            keccak_table,
+            &tx_table,
+            &rw_table,
+            &bytecode_table,
+            &block_table,
+            &copy_table,
+            &keccak_table,
         ));
+        let instance = meta.instance_column();
+        meta.enable_equality(instance);
 
         Self {
             fixed_table,
             byte_table,
             execution,
+            instance,
+            tx_table,
+            rw_table,
+            bytecode_table,
+            block_table,
+            copy_table,
+            keccak_table,
         }
     }
+}
 
+impl<F: Field> EvmCircuitConfig<F> {
     /// Load fixed table
     pub fn load_fixed_table(
         &self,
@@ -111,9 +156,12 @@ impl<F: Field> EvmCircuit<F> {
         &self,
         layouter: &mut impl Layouter<F>,
         block: &Block<F>,
+        challenges: &Challenges<Value<F>>,
     ) -> Result<(), Error> {
-        self.execution.assign_block(layouter, block, false)?;
-        Ok(())
+        let cells = self
+            .execution
+            .assign_block(layouter, block, challenges, false)?;
+        self.constrain_instance(layouter, cells)
     }
 
     #[cfg(any(feature = "test", test))]
@@ -122,8 +170,24 @@ impl<F: Field> EvmCircuit<F> {
         &self,
         layouter: &mut impl Layouter<F>,
         block: &Block<F>,
+        challenges: &Challenges<Value<F>>,
     ) -> Result<(), Error> {
-        self.execution.assign_block(layouter, block, true)?;
+        let cells = self
+            .execution
+            .assign_block(layouter, block, challenges, true)?;
+        self.constrain_instance(layouter, cells)
+    }
+
+    /// Constrain the cells of the state of the first step and of the step
+    /// following the last step to be the public inputs.
+    fn constrain_instance(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        cells: Vec<AssignedCell<F, F>>,
+    ) -> Result<(), Error> {
+        for (offset, cell) in cells.iter().enumerate() {
+            layouter.constrain_instance(cell.cell(), self.instance, offset)?;
+        }
         Ok(())
     }
 
@@ -140,26 +204,160 @@ impl<F: Field> EvmCircuit<F> {
     pub fn get_num_rows_required(&self, block: &Block<F>) -> usize {
         // Start at 1 so we can be sure there is an unused `next` row available
         let mut num_rows = 1;
-        for transaction in &block.txs {
-            for step in &transaction.steps {
-                num_rows += self.execution.get_step_height(step.execution_state);
-            }
+        // The first step of the next chunk is assigned after the last step of a chunk
+        for (_, step) in block.chunk_steps().chain(block.chunk_next_step()) {
+            num_rows += self.execution.get_step_height(step.execution_state);
         }
         num_rows
     }
 }
 
+/// EvmCircuit proves the execution trace of a block.
+#[derive(Clone, Default, Debug)]
+pub struct EvmCircuit<F> {
+    /// Block witness
+    pub block: Block<F>,
+    fixed_table_tags: Vec<FixedTableTag>,
+}
+
+impl<F: Field> EvmCircuit<F> {
+    /// Return a new EvmCircuit
+    pub fn new(block: Block<F>) -> Self {
+        Self {
+            block,
+            fixed_table_tags: FixedTableTag::iter().collect(),
+        }
+    }
+
+    /// Return the number of rows of all the fixed tables, which start with an
+    /// all-zero row, and of the byte table.
+    pub(crate) fn fixed_table_rows() -> usize {
+        256.max(
+            1 + FixedTableTag::iter()
+                .map(|tag| tag.build::<F>().count())
+                .sum::<usize>(),
+        )
+    }
+}
+
+impl<F: Field> SubCircuit<F> for EvmCircuit<F> {
+    type Config = EvmCircuitConfig<F>;
+
+    fn new_from_block(block: &Block<F>) -> Self {
+        Self::new(block.clone())
+    }
+
+    /// The fields of the state of the first step, followed by the ones of
+    /// the first step of the next chunk or of the EndBlock step.
+    fn instance(&self) -> Vec<Vec<F>> {
+        vec![execution::step_state_instance(&self.block)]
+    }
+
+    /// Make the assignments to the EvmCircuit
+    fn synthesize_sub(
+        &self,
+        config: &Self::Config,
+        challenges: &Challenges<Value<F>>,
+        layouter: &mut impl Layouter<F>,
+    ) -> Result<(), Error> {
+        config.load_fixed_table(layouter, self.fixed_table_tags.clone())?;
+        config.load_byte_table(layouter)?;
+        config.assign_block(layouter, &self.block, challenges)
+    }
+
+    /// The steps are padded to `max_evm_rows` when it's set.
+    fn min_num_rows_block(block: &Block<F>) -> usize {
+        let mut cs = ConstraintSystem::default();
+        let (config, _) = Self::configure(&mut cs);
+        config
+            .get_num_rows_required(block)
+            .max(block.circuit_params.max_evm_rows)
+            .max(Self::fixed_table_rows())
+    }
+}
+
+impl<F: Field> Circuit<F> for EvmCircuit<F> {
+    type Config = (EvmCircuitConfig<F>, Challenges);
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let tx_table = TxTable::construct(meta);
+        let rw_table = RwTable::construct(meta);
+        let bytecode_table = BytecodeTable::construct(meta);
+        let block_table = BlockTable::construct(meta);
+        let q_copy_table = meta.fixed_column();
+        let copy_table = CopyTable::construct(meta, q_copy_table);
+        let keccak_table = KeccakTable::construct(meta);
+        let challenges = Challenges::construct(meta);
+        let challenges_expr = challenges.exprs(meta);
+
+        (
+            EvmCircuitConfig::new(
+                meta,
+                EvmCircuitConfigArgs {
+                    challenges: challenges_expr,
+                    tx_table,
+                    rw_table,
+                    bytecode_table,
+                    block_table,
+                    copy_table,
+                    keccak_table,
+                },
+            ),
+            challenges,
+        )
+    }
+
+    fn synthesize(
+        &self,
+        (config, challenges): Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let challenges = challenges.values(&mut layouter);
+        let block = &self.block;
+
+        config
+            .tx_table
+            .load(&mut layouter, block.tx_table_txs(), &challenges)?;
+        config.rw_table.load(
+            &mut layouter,
+            &block.state_rws().table_assignments(),
+            block.circuit_params.max_rws,
+            &challenges,
+        )?;
+        config
+            .bytecode_table
+            .load(&mut layouter, block.bytecodes.values(), &challenges)?;
+        config
+            .block_table
+            .load(&mut layouter, &block.context, &challenges)?;
+        config.copy_table.load(&mut layouter, block, &challenges)?;
+        config
+            .keccak_table
+            .dev_load(&mut layouter, &block.sha3_inputs, &challenges)?;
+
+        self.synthesize_sub(&config, &challenges, &mut layouter)
+    }
+}
+
 #[cfg(any(feature = "test", test))]
 pub mod test {
     use crate::{
-        evm_circuit::{table::FixedTableTag, witness::Block, EvmCircuit},
+        evm_circuit::{
+            execution::step_state_instance, table::FixedTableTag, witness::Block, EvmCircuitConfig,
+            EvmCircuitConfigArgs,
+        },
         table::{BlockTable, BytecodeTable, CopyTable, KeccakTable, RwTable, TxTable},
-        util::{power_of_randomness_from_instance, Challenges},
+        util::{Challenges, SubCircuitConfig},
     };
     use bus_mapping::evm::OpcodeId;
     use eth_types::{Field, Word};
     use halo2_proofs::{
-        circuit::{Layouter, SimpleFloorPlanner, Value},
+        circuit::{Layouter, SimpleFloorPlanner},
         dev::{MockProver, VerifyFailure},
         plonk::{Circuit, ConstraintSystem, Error},
     };
@@ -222,7 +420,8 @@ pub mod test {
         block_table: BlockTable,
         copy_table: CopyTable,
         keccak_table: KeccakTable,
-        pub evm_circuit: EvmCircuit<F>,
+        challenges: Challenges,
+        pub evm_circuit: EvmCircuitConfig<F>,
     }
 
     #[derive(Default)]
@@ -256,17 +455,20 @@ pub mod test {
             let q_copy_table = meta.fixed_column();
             let copy_table = CopyTable::construct(meta, q_copy_table);
             let keccak_table = KeccakTable::construct(meta);
+            let challenges = Challenges::construct(meta);
 
-            let power_of_randomness = power_of_randomness_from_instance(meta);
-            let evm_circuit = EvmCircuit::configure(
+            let challenges_expr = challenges.exprs(meta);
+            let evm_circuit = EvmCircuitConfig::new(
                 meta,
-                power_of_randomness,
-                &tx_table,
-                &rw_table,
-                &bytecode_table,
-                &block_table,
-                &copy_table,
-                &keccak_table,
+                EvmCircuitConfigArgs {
+                    challenges: challenges_expr,
+                    tx_table: tx_table.clone(),
+                    rw_table,
+                    bytecode_table: bytecode_table.clone(),
+                    block_table: block_table.clone(),
+                    copy_table,
+                    keccak_table: keccak_table.clone(),
+                },
             );
 
             Self::Config {
@@ -276,6 +478,7 @@ pub mod test {
                 block_table,
                 copy_table,
                 keccak_table,
+                challenges,
                 evm_circuit,
             }
         }
@@ -285,7 +488,7 @@ pub mod test {
             config: Self::Config,
             mut layouter: impl Layouter<F>,
         ) -> Result<(), Error> {
-            let challenges = Challenges::mock(Value::known(self.block.randomness));
+            let challenges = config.challenges.values(&mut layouter);
 
             config
                 .evm_circuit
@@ -293,13 +496,13 @@ pub mod test {
             config.evm_circuit.load_byte_table(&mut layouter)?;
             config
                 .tx_table
-                .load(&mut layouter, &self.block.txs, self.block.randomness)?;
+                .load(&mut layouter, self.block.tx_table_txs(), &challenges)?;
             self.block.rws.check_rw_counter_sanity();
             config.rw_table.load(
                 &mut layouter,
-                &self.block.rws.table_assignments(),
-                self.block.state_circuit_pad_to,
-                self.block.randomness,
+                &self.block.state_rws().table_assignments(),
+                self.block.circuit_params.max_rws,
+                &challenges,
             )?;
             config.bytecode_table.load(
                 &mut layouter,
@@ -308,10 +511,10 @@ pub mod test {
             )?;
             config
                 .block_table
-                .load(&mut layouter, &self.block.context, self.block.randomness)?;
+                .load(&mut layouter, &self.block.context, &challenges)?;
             config
                 .copy_table
-                .load(&mut layouter, &self.block, self.block.randomness)?;
+                .load(&mut layouter, &self.block, &challenges)?;
 
             config
                 .keccak_table
@@ -319,7 +522,7 @@ pub mod test {
 
             config
                 .evm_circuit
-                .assign_block_exact(&mut layouter, &self.block)
+                .assign_block_exact(&mut layouter, &self.block, &challenges)
         }
     }
 
@@ -359,12 +562,10 @@ pub mod test {
         let k = k.max(log2_ceil(64 + num_rows_required_for_steps));
         log::debug!("evm circuit uses k = {}", k);
 
-        let power_of_randomness = (1..32)
-            .map(|exp| vec![block.randomness.pow(&[exp, 0, 0, 0]); (1 << k) - 64])
-            .collect();
         let (active_gate_rows, active_lookup_rows) = TestCircuit::get_active_rows(&block);
+        let instance = vec![step_state_instance(&block)];
         let circuit = TestCircuit::<F>::new(block, fixed_table_tags);
-        let prover = MockProver::<F>::run(k, &circuit, power_of_randomness).unwrap();
+        let prover = MockProver::<F>::run(k, &circuit, instance).unwrap();
         prover.verify_at_rows(active_gate_rows.into_iter(), active_lookup_rows.into_iter())
     }
 }
@@ -374,7 +575,12 @@ mod evm_circuit_stats {
 
     use super::test::*;
     use super::*;
-    use crate::evm_circuit::step::ExecutionState;
+    use crate::evm_circuit::{
+        execution::step_state_instance,
+        step::ExecutionState,
+        witness::{block_convert, chunk_convert},
+    };
+    use bus_mapping::mock::BlockData;
     use eth_types::{bytecode, evm_types::OpcodeId, geth_types::GethData};
     use halo2_proofs::halo2curves::bn256::Fr;
     use halo2_proofs::plonk::ConstraintSystem;
@@ -386,6 +592,56 @@ mod evm_circuit_stats {
         run_test_circuit(Block::<Fr>::default()).unwrap();
     }
 
+    #[test]
+    fn chunked_evm_circuit() {
+        let code = bytecode! {
+            PUSH1(0x01)
+            PUSH1(0x02)
+            ADD
+            PUSH1(0x03)
+            MUL
+            STOP
+        };
+        let block: GethData = TestContext::<2, 3>::new(
+            None,
+            account_0_code_account_1_no_code(code),
+            |txs, accs| {
+                for tx in txs {
+                    tx.from(accs[1].address).to(accs[0].address);
+                }
+            },
+            |block, _tx| block.number(0xcafeu64),
+        )
+        .unwrap()
+        .into();
+        let mut builder = BlockData::new_from_geth_data(block.clone()).new_circuit_input_builder();
+        builder
+            .handle_block(&block.eth_block, &block.geth_traces)
+            .unwrap();
+        let block = block_convert(&builder.block, &builder.code_db);
+
+        // Each transaction has 8 steps: BeginTx, the 6 steps of the code and
+        // EndTx, so two of them don't fit in a chunk.
+        let chunks = chunk_convert(&block, 10);
+        assert_eq!(chunks.len(), 3);
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.chunk.as_ref().unwrap().txs, i..i + 1);
+        }
+        // The instance is the state of the first step followed by the one after
+        // the last step.
+        for (chunk, next_chunk) in chunks.iter().tuple_windows() {
+            let (instance, next_instance) =
+                (step_state_instance(chunk), step_state_instance(next_chunk));
+            assert_eq!(
+                instance[instance.len() / 2..],
+                next_instance[..next_instance.len() / 2]
+            );
+        }
+        for chunk in chunks {
+            run_test_circuit(chunk).unwrap();
+        }
+    }
+
     /// This function prints to stdout a table with all the implemented states
     /// and their responsible opcodes with the following stats:
     /// - height: number of rows in the EVM circuit used by the execution state
@@ -2,25 +2,28 @@ use super::util::{CachedRegion, CellManager, StoredExpression};
 use crate::{
     evm_circuit::{
         param::{MAX_STEP_HEIGHT, STEP_WIDTH},
-        step::{ExecutionState, Step},
+        step::{ExecutionState, Step, StepState},
         table::Table,
         util::{
             constraint_builder::{BaseConstraintBuilder, ConstraintBuilder},
             rlc, CellType,
         },
-        witness::{Block, Call, ExecStep, Transaction},
+        witness::{Block, Call, Chunk, ExecStep, Transaction},
     },
     table::LookupTable,
-    util::{query_expression, Expr},
+    util::{query_expression, Challenges, Expr},
 };
 use eth_types::Field;
 use halo2_proofs::{
     arithmetic::FieldExt,
-    circuit::{Layouter, Region, Value},
-    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector, VirtualCells},
+    circuit::{AssignedCell, Layouter, Region, Value},
+    plonk::{
+        Advice, Column, ConstraintSystem, Error, Expression, FirstPhase, SecondPhase, Selector,
+        ThirdPhase, VirtualCells,
+    },
     poly::Rotation,
 };
-use std::{collections::HashMap, iter};
+use std::{array, collections::HashMap, iter};
 use strum::IntoEnumIterator;
 
 mod add_sub;
@@ -159,6 +162,9 @@ pub(crate) struct ExecutionConfig<F> {
     num_rows_inv: Column<Advice>,
     // Selector enabled in the row where the first execution step starts.
     q_step_first: Selector,
+    // Selector enabled in the row where the first execution step starts, only when it's the
+    // first step of the block and not the first step of a later chunk of the block.
+    q_block_first: Selector,
     // Selector enabled in the row where the last execution step starts.
     q_step_last: Selector,
     advices: [Column<Advice>; STEP_WIDTH],
@@ -270,7 +276,7 @@ impl<F: Field> ExecutionConfig<F> {
     #[allow(clippy::too_many_arguments)]
     pub(crate) fn configure(
         meta: &mut ConstraintSystem<F>,
-        power_of_randomness: [Expression<F>; 31],
+        challenges: Challenges<Expression<F>>,
         fixed_table: &dyn LookupTable<F>,
         byte_table: &dyn LookupTable<F>,
         tx_table: &dyn LookupTable<F>,
@@ -285,16 +291,27 @@ impl<F: Field> ExecutionConfig<F> {
         let num_rows_until_next_step = meta.advice_column();
         let num_rows_inv = meta.advice_column();
         let q_step_first = meta.complex_selector();
+        let q_block_first = meta.complex_selector();
         let q_step_last = meta.complex_selector();
-        let advices = [(); STEP_WIDTH].map(|_| meta.advice_column());
+        let advices = array::from_fn(|index| match CellType::for_column(index).phase() {
+            0 => meta.advice_column_in(FirstPhase),
+            1 => meta.advice_column_in(SecondPhase),
+            _ => meta.advice_column_in(ThirdPhase),
+        });
 
         let step_curr = Step::new(meta, advices, 0);
         let mut height_map = HashMap::new();
 
+        // The state of the first step and of the step following the last step are
+        // exposed as public inputs, to link the chunks of a block.
+        for cell in step_curr.state.boundary_cells() {
+            meta.enable_equality(cell.column());
+        }
+
         meta.create_gate("Constrain execution state", |meta| {
             let q_usable = meta.query_selector(q_usable);
             let q_step = meta.query_advice(q_step, Rotation::cur());
-            let q_step_first = meta.query_selector(q_step_first);
+            let q_block_first = meta.query_selector(q_block_first);
             let q_step_last = meta.query_selector(q_step_last);
 
             let execution_state_selector_constraints = step_curr.state.execution_state.configure();
@@ -304,7 +321,7 @@ impl<F: Field> ExecutionConfig<F> {
                     step_curr.execution_state_selector([ExecutionState::BeginTx]);
                 iter::once((
                     "First step should be BeginTx",
-                    q_step_first * (1.expr() - begin_tx_selector),
+                    q_block_first * (1.expr() - begin_tx_selector),
                 ))
             };
 
@@ -371,9 +388,9 @@ impl<F: Field> ExecutionConfig<F> {
                     q_usable,
                     q_step,
                     num_rows_until_next_step,
-                    q_step_first,
+                    q_block_first,
                     q_step_last,
-                    &power_of_randomness,
+                    &challenges,
                     &step_curr,
                     &step_next,
                     &mut height_map,
@@ -389,6 +406,7 @@ impl<F: Field> ExecutionConfig<F> {
             num_rows_until_next_step,
             num_rows_inv,
             q_step_first,
+            q_block_first,
             q_step_last,
             advices,
             // internal states
@@ -502,7 +520,7 @@ impl<F: Field> ExecutionConfig<F> {
             block_table,
             copy_table,
             keccak_table,
-            &power_of_randomness,
+            &challenges,
             &cell_manager,
         );
 
@@ -525,9 +543,9 @@ impl<F: Field> ExecutionConfig<F> {
         q_usable: Selector,
         q_step: Column<Advice>,
         num_rows_until_next_step: Column<Advice>,
-        q_step_first: Selector,
+        q_block_first: Selector,
         q_step_last: Selector,
-        power_of_randomness: &[Expression<F>; 31],
+        challenges: &Challenges<Expression<F>>,
         step_curr: &Step<F>,
         step_next: &Step<F>,
         height_map: &mut HashMap<ExecutionState, usize>,
@@ -539,7 +557,7 @@ impl<F: Field> ExecutionConfig<F> {
             let mut cb = ConstraintBuilder::new(
                 step_curr.clone(),
                 step_next.clone(),
-                power_of_randomness,
+                challenges,
                 G::EXECUTION_STATE,
             );
             G::configure(&mut cb);
@@ -552,7 +570,7 @@ impl<F: Field> ExecutionConfig<F> {
         let mut cb = ConstraintBuilder::new(
             step_curr.clone(),
             step_next.clone(),
-            power_of_randomness,
+            challenges,
             G::EXECUTION_STATE,
         );
 
@@ -584,7 +602,7 @@ impl<F: Field> ExecutionConfig<F> {
         let q_steps: &dyn Fn(&mut VirtualCells<F>) -> Expression<F> =
             &|meta| meta.query_advice(q_step, Rotation::cur());
         let q_steps_first: &dyn Fn(&mut VirtualCells<F>) -> Expression<F> =
-            &|meta| meta.query_selector(q_step_first);
+            &|meta| meta.query_selector(q_block_first);
         for (selector, constraints) in [
             (q_steps, constraints),
             (q_steps_first, constraints_first_step),
@@ -674,9 +692,10 @@ impl<F: Field> ExecutionConfig<F> {
         block_table: &dyn LookupTable<F>,
         copy_table: &dyn LookupTable<F>,
         keccak_table: &dyn LookupTable<F>,
-        power_of_randomness: &[Expression<F>; 31],
+        challenges: &Challenges<Expression<F>>,
         cell_manager: &CellManager<F>,
     ) {
+        let power_of_randomness = challenges.lookup_input_powers_of_randomness();
         for column in cell_manager.columns().iter() {
             if let CellType::Lookup(table) = column.cell_type {
                 let name = format!("{:?}", table);
@@ -694,7 +713,7 @@ impl<F: Field> ExecutionConfig<F> {
                     .table_exprs(meta);
                     vec![(
                         column.expr(),
-                        rlc::expr(&table_expressions, power_of_randomness),
+                        rlc::expr(&table_expressions, &power_of_randomness),
                     )]
                 });
             }
@@ -704,24 +723,27 @@ impl<F: Field> ExecutionConfig<F> {
     /// Assign block
     /// When exact is enabled, assign exact steps in block without padding for
     /// unit test purpose
+    ///
+    /// Return the cells of the state of the first step of the chunk of the
+    /// block, followed by the ones of the first step of the next chunk or of
+    /// the EndBlock step, which are exposed as public inputs, see
+    /// [`step_state_instance`].
     pub fn assign_block(
         &self,
         layouter: &mut impl Layouter<F>,
         block: &Block<F>,
+        challenges: &Challenges<Value<F>>,
         exact: bool,
-    ) -> Result<(), Error> {
-        let power_of_randomness = (1..32)
-            .map(|exp| block.randomness.pow(&[exp, 0, 0, 0]))
-            .collect::<Vec<F>>()
-            .try_into()
-            .unwrap();
-
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
         layouter.assign_region(
             || "Execution step",
             |mut region| {
                 let mut offset = 0;
 
                 self.q_step_first.enable(&mut region, offset)?;
+                if block.chunk.as_ref().map_or(true, Chunk::is_first) {
+                    self.q_block_first.enable(&mut region, offset)?;
+                }
 
                 // handle EndBlock
                 let dummy_tx = Transaction {
@@ -729,31 +751,39 @@ impl<F: Field> ExecutionConfig<F> {
                     ..Default::default()
                 };
                 let last_tx = block.txs.last().unwrap_or(&dummy_tx);
-                let end_block_state = &ExecStep {
-                    rw_counter: if block.txs.is_empty() {
-                        0
-                    } else {
-                        // if it is the first tx,  less 1 rw lookup, refer to end_tx gadget
-                        last_tx.steps.last().unwrap().rw_counter + 9 - (last_tx.id == 1) as usize
-                    },
-                    execution_state: ExecutionState::EndBlock,
-                    ..Default::default()
+                let end_block_state = &end_block_step(block);
+                // Collect all steps of the chunk.  The first step of the next chunk is
+                // only assigned as the next step of the last one, to constrain the
+                // transition to it.
+                let next_chunk_step = block.chunk_next_step();
+                let num_end_block_steps = match next_chunk_step {
+                    Some(_) => 0,
+                    None => usize::MAX,
                 };
-                // Collect all steps
+                let end_block_steps =
+                    iter::repeat((last_tx, end_block_state)).take(num_end_block_steps);
                 let mut steps = block
-                    .txs
-                    .iter()
-                    .flat_map(|tx| tx.steps.iter().map(move |step| (tx, step)))
-                    .chain(iter::repeat((last_tx, end_block_state)))
+                    .chunk_steps()
+                    .chain(next_chunk_step)
+                    .chain(end_block_steps)
                     .peekable();
 
                 let mut last_height = 0;
+                let mut first_cells = None;
+                let mut end_cells = None;
+                let mut next_cells = None;
                 while let Some((transaction, step)) = steps.next() {
+                    if steps.peek().is_none() {
+                        // The first step of the next chunk
+                        end_cells = next_cells.take();
+                        break;
+                    }
+
                     let call = &transaction.calls[step.call_index];
                     let height = self.get_step_height(step.execution_state);
 
                     // Assign the step witness
-                    self.assign_exec_step(
+                    let (cells, cells_next) = self.assign_exec_step(
                         &mut region,
                         offset,
                         block,
@@ -764,8 +794,15 @@ impl<F: Field> ExecutionConfig<F> {
                         steps.peek().map(|&(transaction, step)| {
                             (transaction, &transaction.calls[step.call_index], step)
                         }),
-                        power_of_randomness,
+                        challenges,
                     )?;
+                    next_cells = cells_next;
+                    if first_cells.is_none() {
+                        first_cells = Some(cells.clone());
+                    }
+                    if step.execution_state == ExecutionState::EndBlock && end_cells.is_none() {
+                        end_cells = Some(cells);
+                    }
 
                     // q_step logic
                     for idx in 0..height {
@@ -800,18 +837,19 @@ impl<F: Field> ExecutionConfig<F> {
                     last_height = height;
 
                     if step.execution_state == ExecutionState::EndBlock {
-                        // evm_circuit_pad_to == 0 means no extra padding
-                        if exact || block.evm_circuit_pad_to == 0 {
+                        let pad_to = block.circuit_params.max_evm_rows;
+                        // max_evm_rows == 0 means no extra padding
+                        if exact || pad_to == 0 {
                             // no padding
                             break;
                         } else {
                             // padding
-                            if offset >= block.evm_circuit_pad_to {
-                                if offset > block.evm_circuit_pad_to {
+                            if offset >= pad_to {
+                                if offset > pad_to {
                                     log::warn!(
                                         "evm circuit offset larger than padding: {} > {}",
                                         offset,
-                                        block.evm_circuit_pad_to
+                                        pad_to
                                     );
                                 }
                                 break;
@@ -834,13 +872,19 @@ impl<F: Field> ExecutionConfig<F> {
                     || Value::known(F::zero()),
                 )?;
 
-                self.q_step_last.enable(&mut region, offset - last_height)?;
+                // The transition from the last step of a chunk to the first step of the next
+                // chunk is constrained.
+                if next_chunk_step.is_none() {
+                    self.q_step_last.enable(&mut region, offset - last_height)?;
+                }
 
-                Ok(())
+                Ok(first_cells.into_iter().chain(end_cells).flatten().collect())
             },
         )
     }
 
+    /// Assign the step and the next step, and return the cells of their
+    /// states exposed at the boundaries of a chunk.
     #[allow(clippy::too_many_arguments)]
     fn assign_exec_step(
         &self,
@@ -852,14 +896,14 @@ impl<F: Field> ExecutionConfig<F> {
         step: &ExecStep,
         height: usize,
         next: Option<(&Transaction, &Call, &ExecStep)>,
-        power_of_randomness: [F; 31],
-    ) -> Result<(), Error> {
+        challenges: &Challenges<Value<F>>,
+    ) -> Result<(Vec<AssignedCell<F, F>>, Option<Vec<AssignedCell<F, F>>>), Error> {
         // Make the region large enough for the current step and the next step.
         // The next step's next step may also be accessed, so make the region large
         // enough for 3 steps.
         let region = &mut CachedRegion::<'_, '_, F>::new(
             region,
-            power_of_randomness,
+            challenges,
             STEP_WIDTH,
             MAX_STEP_HEIGHT * 3,
             self.advices[0].index(),
@@ -870,18 +914,21 @@ impl<F: Field> ExecutionConfig<F> {
         // These may be used in stored expressions and
         // so their witness values need to be known to be able
         // to correctly calculate the intermediate value.
-        if let Some((transaction_next, call_next, step_next)) = next {
-            self.assign_exec_step_int(
-                region,
-                offset + height,
-                block,
-                transaction_next,
-                call_next,
-                step_next,
-            )?;
-        }
+        let next_cells = next
+            .map(|(transaction_next, call_next, step_next)| {
+                self.assign_exec_step_int(
+                    region,
+                    offset + height,
+                    block,
+                    transaction_next,
+                    call_next,
+                    step_next,
+                )
+            })
+            .transpose()?;
 
-        self.assign_exec_step_int(region, offset, block, transaction, call, step)
+        let cells = self.assign_exec_step_int(region, offset, block, transaction, call, step)?;
+        Ok((cells, next_cells))
     }
 
     fn assign_exec_step_int(
@@ -892,9 +939,10 @@ impl<F: Field> ExecutionConfig<F> {
         transaction: &Transaction,
         call: &Call,
         step: &ExecStep,
-    ) -> Result<(), Error> {
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
         log::trace!("assign_exec_step offset:{} step:{:?}", offset, step);
-        self.step
+        let cells = self
+            .step
             .assign_exec_step(region, offset, block, transaction, call, step)?;
 
         macro_rules! assign_exec_step {
@@ -1066,8 +1114,13 @@ impl<F: Field> ExecutionConfig<F> {
         // Fill in the witness values for stored expressions
         let assigned_stored_expressions = self.assign_stored_expressions(region, offset, step)?;
 
-        Self::check_rw_lookup(&assigned_stored_expressions, step, block);
-        Ok(())
+        Self::check_rw_lookup(
+            &assigned_stored_expressions,
+            step,
+            block,
+            region.challenges(),
+        );
+        Ok(cells)
     }
 
     fn assign_stored_expressions(
@@ -1095,6 +1148,7 @@ impl<F: Field> ExecutionConfig<F> {
         assigned_stored_expressions: &[(String, F)],
         step: &ExecStep,
         block: &Block<F>,
+        challenges: &Challenges<Value<F>>,
     ) {
         let mut assigned_rw_values = Vec::new();
         // Reversion lookup expressions have different ordering compared to rw table,
@@ -1113,9 +1167,12 @@ impl<F: Field> ExecutionConfig<F> {
         for (idx, assigned_rw_value) in assigned_rw_values.iter().enumerate() {
             let rw_idx = step.rw_indices[idx];
             let rw = block.rws[rw_idx];
-            let table_assignments = rw.table_assignment(block.randomness);
-            let rlc = table_assignments.rlc(block.randomness);
-            if rlc != assigned_rw_value.1 {
+            let rlc = challenges.evm_word().zip(challenges.lookup_input()).map(
+                |(evm_word, lookup_input)| rw.table_assignment_aux(evm_word).rlc(lookup_input),
+            );
+            let mut mismatch = false;
+            rlc.map(|rlc| mismatch = rlc != assigned_rw_value.1);
+            if mismatch {
                 log::error!(
                     "incorrect rw witness. lookup input name: \"{}\". rw: {:?}, rw index: {:?}, {}th rw of step {:?}",
                     assigned_rw_value.0,
@@ -1127,3 +1184,41 @@ impl<F: Field> ExecutionConfig<F> {
         }
     }
 }
+
+/// Return the EndBlock step following the last step of the block.
+fn end_block_step<F>(block: &Block<F>) -> ExecStep {
+    ExecStep {
+        rw_counter: match block.txs.last() {
+            None => 0,
+            // if it is the first tx,  less 1 rw lookup, refer to end_tx gadget
+            Some(last_tx) => {
+                last_tx.steps.last().unwrap().rw_counter + 9 - (last_tx.id == 1) as usize
+            }
+        },
+        execution_state: ExecutionState::EndBlock,
+        ..Default::default()
+    }
+}
+
+/// Return the public inputs of the EVM circuit for the chunk of the block, or
+/// for the whole block: the fields of the state of the first step, followed by
+/// the ones of the first step of the next chunk, or of the EndBlock step for
+/// the last chunk.  The end of a chunk is the beginning of the next one.
+pub(crate) fn step_state_instance<F: Field>(block: &Block<F>) -> Vec<F> {
+    let dummy_tx = Transaction {
+        calls: vec![Default::default()],
+        ..Default::default()
+    };
+    let last_tx = block.txs.last().unwrap_or(&dummy_tx);
+    let end_block_state = end_block_step(block);
+    let end = block
+        .chunk_next_step()
+        .unwrap_or((last_tx, &end_block_state));
+    let begin = block.chunk_steps().next().unwrap_or(end);
+    [begin, end]
+        .into_iter()
+        .flat_map(|(transaction, step)| {
+            StepState::boundary_values(&transaction.calls[step.call_index], step)
+        })
+        .collect()
+}
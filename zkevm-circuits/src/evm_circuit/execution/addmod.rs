@@ -213,11 +213,8 @@ impl<F: Field> ExecutionGadget<F> for AddModGadget<F> {
         self.cmp_r_n.assign(region, offset, r, n)?;
         self.cmp_areduced_n.assign(region, offset, a_reduced, n)?;
 
-        self.n_is_zero.assign(
-            region,
-            offset,
-            Word::random_linear_combine(n.to_le_bytes(), block.randomness),
-        )?;
+        self.n_is_zero
+            .assign_value(region, offset, region.word_rlc(n))?;
 
         Ok(())
     }
@@ -9,12 +9,14 @@ use crate::{
                 ConstraintBuilder, ReversionInfo, StepStateTransition,
                 Transition::{Delta, To},
             },
-            math_gadget::{MulWordByU64Gadget, RangeCheckGadget},
-            select, CachedRegion, Cell, RandomLinearCombination, Word,
+            math_gadget::{AddWordsGadget, CmpWordsGadget, MulWordByU64Gadget, RangeCheckGadget},
+            select, CachedRegion, Cell, Word,
         },
         witness::{Block, Call, ExecStep, Transaction},
     },
-    table::{AccountFieldTag, CallContextFieldTag, TxFieldTag as TxContextFieldTag},
+    table::{
+        AccountFieldTag, BlockContextFieldTag, CallContextFieldTag, TxFieldTag as TxContextFieldTag,
+    },
     util::Expr,
 };
 use eth_types::{evm_types::GasCost, Field, ToLittleEndian, ToScalar};
@@ -24,9 +26,17 @@ use halo2_proofs::plonk::Error;
 #[derive(Clone, Debug)]
 pub(crate) struct BeginTxGadget<F> {
     tx_id: Cell<F>,
+    tx_block_number: Cell<F>,
     tx_nonce: Cell<F>,
     tx_gas: Cell<F>,
     tx_gas_price: Word<F>,
+    tx_gas_fee_cap: Word<F>,
+    tx_gas_tip_cap: Word<F>,
+    base_fee: Word<F>,
+    add_base_fee_and_tip_cap: AddWordsGadget<F, 2, true>,
+    cmp_gas_fee_cap: CmpWordsGadget<F>,
+    cmp_base_fee: CmpWordsGadget<F>,
+    cmp_gas_tip_cap: CmpWordsGadget<F>,
     mul_gas_fee_by_gas: MulWordByU64Gadget<F>,
     tx_caller_address: Cell<F>,
     tx_callee_address: Cell<F>,
@@ -34,6 +44,8 @@ pub(crate) struct BeginTxGadget<F> {
     tx_value: Word<F>,
     tx_call_data_length: Cell<F>,
     tx_call_data_gas_cost: Cell<F>,
+    tx_access_list_addresses_len: Cell<F>,
+    tx_access_list_storage_keys_len: Cell<F>,
     reversion_info: ReversionInfo<F>,
     sufficient_gas_left: RangeCheckGadget<F, N_BYTES_GAS>,
     transfer_with_gas_fee: TransferWithGasFeeGadget<F>,
@@ -64,8 +76,9 @@ impl<F: Field> ExecutionGadget<F> for BeginTxGadget<F> {
             reversion_info.is_persistent(),
         );
 
-        let [tx_nonce, tx_gas, tx_caller_address, tx_callee_address, tx_is_create, tx_call_data_length, tx_call_data_gas_cost] =
+        let [tx_block_number, tx_nonce, tx_gas, tx_caller_address, tx_callee_address, tx_is_create, tx_call_data_length, tx_call_data_gas_cost, tx_access_list_addresses_len, tx_access_list_storage_keys_len] =
             [
+                TxContextFieldTag::BlockNumber,
                 TxContextFieldTag::Nonce,
                 TxContextFieldTag::Gas,
                 TxContextFieldTag::CallerAddress,
@@ -73,10 +86,17 @@ impl<F: Field> ExecutionGadget<F> for BeginTxGadget<F> {
                 TxContextFieldTag::IsCreate,
                 TxContextFieldTag::CallDataLength,
                 TxContextFieldTag::CallDataGasCost,
+                TxContextFieldTag::AccessListAddressesLen,
+                TxContextFieldTag::AccessListStorageKeysLen,
             ]
             .map(|field_tag| cb.tx_context(tx_id.expr(), field_tag, None));
-        let [tx_gas_price, tx_value] = [TxContextFieldTag::GasPrice, TxContextFieldTag::Value]
-            .map(|field_tag| cb.tx_context_as_word(tx_id.expr(), field_tag, None));
+        let [tx_gas_price, tx_gas_fee_cap, tx_gas_tip_cap, tx_value] = [
+            TxContextFieldTag::GasPrice,
+            TxContextFieldTag::GasFeeCap,
+            TxContextFieldTag::GasTipCap,
+            TxContextFieldTag::Value,
+        ]
+        .map(|field_tag| cb.tx_context_as_word(tx_id.expr(), field_tag, None));
 
         // Add first step constraint to have both rw_counter and tx_id to be 1
         cb.add_constraint_first_step(
@@ -95,19 +115,55 @@ impl<F: Field> ExecutionGadget<F> for BeginTxGadget<F> {
             None,
         );
 
-        // TODO: Implement EIP 1559 (currently it only supports legacy
-        // transaction format)
+        // The effective gas price is min(gas_fee_cap, base_fee + gas_tip_cap)
+        // (EIP-1559).  Legacy transactions have both caps equal to their gas
+        // price, which is then always the minimum.  The base fee is the one of
+        // the block of the tx.
+        let base_fee = cb.query_word();
+        cb.block_lookup(
+            BlockContextFieldTag::BaseFee.expr(),
+            Some(tx_block_number.expr()),
+            base_fee.expr(),
+        );
+        let base_fee_plus_tip_cap = cb.query_word();
+        let add_base_fee_and_tip_cap = AddWordsGadget::construct(
+            cb,
+            [base_fee.clone(), tx_gas_tip_cap.clone()],
+            base_fee_plus_tip_cap.clone(),
+        );
+        let cmp_gas_fee_cap =
+            CmpWordsGadget::construct(cb, &tx_gas_fee_cap, &base_fee_plus_tip_cap);
+        cb.require_equal(
+            "tx_gas_price == min(tx_gas_fee_cap, base_fee + tx_gas_tip_cap)",
+            tx_gas_price.expr(),
+            select::expr(
+                cmp_gas_fee_cap.lt.clone(),
+                tx_gas_fee_cap.expr(),
+                base_fee_plus_tip_cap.expr(),
+            ),
+        );
+        // The max fee per gas must cover the base fee.
+        let cmp_base_fee = CmpWordsGadget::construct(cb, &tx_gas_fee_cap, &base_fee);
+        cb.require_zero("tx_gas_fee_cap >= base_fee", cmp_base_fee.lt.clone());
+        // The max priority fee per gas can't exceed the max fee per gas.
+        let cmp_gas_tip_cap = CmpWordsGadget::construct(cb, &tx_gas_fee_cap, &tx_gas_tip_cap);
+        cb.require_zero(
+            "tx_gas_tip_cap <= tx_gas_fee_cap",
+            cmp_gas_tip_cap.lt.clone(),
+        );
+
         // Calculate transaction gas fee
         let mul_gas_fee_by_gas =
             MulWordByU64Gadget::construct(cb, tx_gas_price.clone(), tx_gas.expr());
 
-        // TODO: Take gas cost of access list (EIP 2930) into consideration.
-        // Use intrinsic gas
+        // Use intrinsic gas, including the cost of the access list (EIP-2930)
         let intrinsic_gas_cost = select::expr(
             tx_is_create.expr(),
             GasCost::CREATION_TX.expr(),
             GasCost::TX.expr(),
-        ) + tx_call_data_gas_cost.expr();
+        ) + tx_call_data_gas_cost.expr()
+            + tx_access_list_addresses_len.expr() * GasCost::ACCESS_LIST_PER_ADDRESS.expr()
+            + tx_access_list_storage_keys_len.expr() * GasCost::ACCESS_LIST_PER_STORAGE_KEY.expr();
 
         // Check gas_left is sufficient
         let gas_left = tx_gas.expr() - intrinsic_gas_cost;
@@ -143,7 +199,7 @@ impl<F: Field> ExecutionGadget<F> for BeginTxGadget<F> {
         // TODO: Handle precompiled
 
         // Read code_hash of callee
-        let code_hash = cb.query_cell();
+        let code_hash = cb.query_cell_phase2();
         cb.account_read(
             tx_callee_address.expr(),
             AccountFieldTag::CodeHash,
@@ -172,8 +228,22 @@ impl<F: Field> ExecutionGadget<F> for BeginTxGadget<F> {
             cb.call_context_lookup(true.expr(), Some(call_id.expr()), field_tag, value);
         }
 
+        // The addresses and storage keys of the access list are written into
+        // the access list after all the lookups above, one write each.  The Tx
+        // Circuit looks up each of these writes from the entries of the access
+        // list of the transaction, starting at `AccessListRwCounter`.
+        let access_list_rw_counter = cb.curr.state.rw_counter.expr() + cb.rw_counter_offset();
+        cb.tx_context_lookup(
+            tx_id.expr(),
+            TxContextFieldTag::AccessListRwCounter,
+            None,
+            access_list_rw_counter,
+        );
+        let access_list_writes =
+            tx_access_list_addresses_len.expr() + tx_access_list_storage_keys_len.expr();
+
         cb.require_step_state_transition(StepStateTransition {
-            // 23 reads and writes:
+            // 23 + access list length reads and writes:
             //   - Write CallContext TxId
             //   - Write CallContext RwCounterEndOfReversion
             //   - Write CallContext IsPersistent
@@ -197,7 +267,9 @@ impl<F: Field> ExecutionGadget<F> for BeginTxGadget<F> {
             //   - Write CallContext IsRoot
             //   - Write CallContext IsCreate
             //   - Write CallContext CodeHash
-            rw_counter: Delta(23.expr()),
+            //   - Write TxAccessListAccount for each address of the access list
+            //   - Write TxAccessListAccountStorage for each storage key of the access list
+            rw_counter: Delta(23.expr() + access_list_writes),
             call_id: To(call_id.expr()),
             is_root: To(true.expr()),
             is_create: To(false.expr()),
@@ -210,9 +282,17 @@ impl<F: Field> ExecutionGadget<F> for BeginTxGadget<F> {
 
         Self {
             tx_id,
+            tx_block_number,
             tx_nonce,
             tx_gas,
             tx_gas_price,
+            tx_gas_fee_cap,
+            tx_gas_tip_cap,
+            base_fee,
+            add_base_fee_and_tip_cap,
+            cmp_gas_fee_cap,
+            cmp_base_fee,
+            cmp_gas_tip_cap,
             mul_gas_fee_by_gas,
             tx_caller_address,
             tx_callee_address,
@@ -220,6 +300,8 @@ impl<F: Field> ExecutionGadget<F> for BeginTxGadget<F> {
             tx_value,
             tx_call_data_length,
             tx_call_data_gas_cost,
+            tx_access_list_addresses_len,
+            tx_access_list_storage_keys_len,
             reversion_info,
             sufficient_gas_left,
             transfer_with_gas_fee,
@@ -243,12 +325,39 @@ impl<F: Field> ExecutionGadget<F> for BeginTxGadget<F> {
 
         self.tx_id
             .assign(region, offset, Value::known(F::from(tx.id as u64)))?;
+        self.tx_block_number
+            .assign(region, offset, Value::known(F::from(tx.block_number)))?;
         self.tx_nonce
             .assign(region, offset, Value::known(F::from(tx.nonce)))?;
         self.tx_gas
             .assign(region, offset, Value::known(F::from(tx.gas)))?;
         self.tx_gas_price
             .assign(region, offset, Some(tx.gas_price.to_le_bytes()))?;
+        self.tx_gas_fee_cap
+            .assign(region, offset, Some(tx.gas_fee_cap.to_le_bytes()))?;
+        self.tx_gas_tip_cap
+            .assign(region, offset, Some(tx.gas_tip_cap.to_le_bytes()))?;
+        let base_fee = block
+            .context
+            .ctxs
+            .get(&tx.block_number)
+            .ok_or(Error::Synthesis)?
+            .base_fee;
+        self.base_fee
+            .assign(region, offset, Some(base_fee.to_le_bytes()))?;
+        let (base_fee_plus_tip_cap, _) = base_fee.overflowing_add(tx.gas_tip_cap);
+        self.add_base_fee_and_tip_cap.assign(
+            region,
+            offset,
+            [base_fee, tx.gas_tip_cap],
+            base_fee_plus_tip_cap,
+        )?;
+        self.cmp_gas_fee_cap
+            .assign(region, offset, tx.gas_fee_cap, base_fee_plus_tip_cap)?;
+        self.cmp_base_fee
+            .assign(region, offset, tx.gas_fee_cap, base_fee)?;
+        self.cmp_gas_tip_cap
+            .assign(region, offset, tx.gas_fee_cap, tx.gas_tip_cap)?;
         self.mul_gas_fee_by_gas
             .assign(region, offset, tx.gas_price, tx.gas, gas_fee)?;
         self.tx_caller_address.assign(
@@ -281,6 +390,16 @@ impl<F: Field> ExecutionGadget<F> for BeginTxGadget<F> {
             offset,
             Value::known(F::from(tx.call_data_gas_cost)),
         )?;
+        self.tx_access_list_addresses_len.assign(
+            region,
+            offset,
+            Value::known(F::from(tx.access_list_addresses_len)),
+        )?;
+        self.tx_access_list_storage_keys_len.assign(
+            region,
+            offset,
+            Value::known(F::from(tx.access_list_storage_keys_len)),
+        )?;
         self.reversion_info.assign(
             region,
             offset,
@@ -297,14 +416,8 @@ impl<F: Field> ExecutionGadget<F> for BeginTxGadget<F> {
             tx.value,
             gas_fee,
         )?;
-        self.code_hash.assign(
-            region,
-            offset,
-            Value::known(RandomLinearCombination::random_linear_combine(
-                callee_code_hash.to_le_bytes(),
-                block.randomness,
-            )),
-        )?;
+        self.code_hash
+            .assign(region, offset, region.word_rlc(callee_code_hash))?;
         Ok(())
     }
 }
@@ -316,7 +429,10 @@ mod test {
         witness::block_convert,
     };
     use bus_mapping::{evm::OpcodeId, mock::BlockData};
-    use eth_types::{self, bytecode, evm_types::GasCost, geth_types::GethData, Word};
+    use eth_types::{
+        self, bytecode, evm_types::GasCost, geth_types::GethData, AccessList, AccessListItem, Word,
+        H256,
+    };
     use mock::{
         eth, gwei, test_ctx::helpers::account_0_code_account_1_no_code, TestContext, MOCK_ACCOUNTS,
     };
@@ -403,6 +519,159 @@ mod test {
         );
     }
 
+    #[test]
+    fn begin_tx_gadget_dynamic_fee() {
+        // (max_fee_per_gas, max_priority_fee_per_gas): the effective gas price is
+        // capped by the fee cap in the first case and by the tip in the second.
+        for (gas_fee_cap, gas_tip_cap) in [(gwei(3), gwei(2)), (gwei(5), gwei(1))] {
+            let block: GethData = TestContext::<2, 1>::new(
+                None,
+                account_0_code_account_1_no_code(bytecode! { STOP }),
+                |mut txs, accs| {
+                    txs[0]
+                        .to(accs[0].address)
+                        .from(accs[1].address)
+                        .transaction_type(2)
+                        .max_fee_per_gas(gas_fee_cap)
+                        .max_priority_fee_per_gas(gas_tip_cap)
+                        .value(eth(1));
+                },
+                |block, _tx| block.base_fee_per_gas(gwei(2)),
+            )
+            .unwrap()
+            .into();
+
+            let mut builder =
+                BlockData::new_from_geth_data(block.clone()).new_circuit_input_builder();
+            builder
+                .handle_block(&block.eth_block, &block.geth_traces)
+                .unwrap();
+            let block = block_convert(&builder.block, &builder.code_db);
+            assert_eq!(block.txs[0].gas_price, gwei(3));
+
+            assert_eq!(run_test_circuit(block), Ok(()));
+        }
+    }
+
+    #[test]
+    fn begin_tx_gadget_fee_cap_below_base_fee() {
+        let block: GethData = TestContext::<2, 1>::new(
+            None,
+            account_0_code_account_1_no_code(bytecode! { STOP }),
+            |mut txs, accs| {
+                txs[0]
+                    .to(accs[0].address)
+                    .from(accs[1].address)
+                    .transaction_type(2)
+                    .max_fee_per_gas(gwei(3))
+                    .max_priority_fee_per_gas(gwei(2))
+                    .value(eth(1));
+            },
+            |block, _tx| block.base_fee_per_gas(gwei(2)),
+        )
+        .unwrap()
+        .into();
+
+        let mut builder = BlockData::new_from_geth_data(block.clone()).new_circuit_input_builder();
+        builder
+            .handle_block(&block.eth_block, &block.geth_traces)
+            .unwrap();
+        let mut block = block_convert(&builder.block, &builder.code_db);
+        // The effective gas price is still the fee cap, but it doesn't cover
+        // the base fee anymore.
+        for ctx in block.context.ctxs.values_mut() {
+            ctx.base_fee = gwei(4);
+        }
+
+        assert!(run_test_circuit(block).is_err());
+    }
+
+    #[test]
+    fn begin_tx_gadget_tip_cap_above_fee_cap() {
+        let block: GethData = TestContext::<2, 1>::new(
+            None,
+            account_0_code_account_1_no_code(bytecode! { STOP }),
+            |mut txs, accs| {
+                txs[0]
+                    .to(accs[0].address)
+                    .from(accs[1].address)
+                    .transaction_type(2)
+                    .max_fee_per_gas(gwei(3))
+                    .max_priority_fee_per_gas(gwei(1))
+                    .value(eth(1));
+            },
+            |block, _tx| block.base_fee_per_gas(gwei(2)),
+        )
+        .unwrap()
+        .into();
+
+        let mut builder = BlockData::new_from_geth_data(block.clone()).new_circuit_input_builder();
+        builder
+            .handle_block(&block.eth_block, &block.geth_traces)
+            .unwrap();
+        let mut block = block_convert(&builder.block, &builder.code_db);
+        // The effective gas price is still the fee cap, but the tip cap exceeds
+        // it.
+        block.txs[0].gas_tip_cap = gwei(4);
+
+        assert!(run_test_circuit(block).is_err());
+    }
+
+    #[test]
+    fn begin_tx_gadget_access_list() {
+        let block: GethData = TestContext::<2, 1>::new(
+            None,
+            account_0_code_account_1_no_code(bytecode! { STOP }),
+            |mut txs, accs| {
+                txs[0]
+                    .to(accs[0].address)
+                    .from(accs[1].address)
+                    .transaction_type(1)
+                    .access_list(AccessList(vec![
+                        AccessListItem {
+                            address: accs[0].address,
+                            storage_keys: vec![H256::from_low_u64_be(1)],
+                        },
+                        AccessListItem {
+                            address: MOCK_ACCOUNTS[2],
+                            storage_keys: vec![H256::from_low_u64_be(2), H256::from_low_u64_be(3)],
+                        },
+                    ]))
+                    .value(eth(1));
+            },
+            |block, _tx| block,
+        )
+        .unwrap()
+        .into();
+
+        let mut builder = BlockData::new_from_geth_data(block.clone()).new_circuit_input_builder();
+        builder
+            .handle_block(&block.eth_block, &block.geth_traces)
+            .unwrap();
+        let begin_tx = &builder.block.txs()[0].steps()[0];
+        assert_eq!(
+            begin_tx.gas_cost.as_u64(),
+            GasCost::TX.as_u64()
+                + 2 * GasCost::ACCESS_LIST_PER_ADDRESS.as_u64()
+                + 3 * GasCost::ACCESS_LIST_PER_STORAGE_KEY.as_u64()
+        );
+        let block = block_convert(&builder.block, &builder.code_db);
+        assert_eq!(block.txs[0].access_list_addresses_len, 2);
+        assert_eq!(block.txs[0].access_list_storage_keys_len, 3);
+        assert_eq!(
+            block.txs[0].access_list_rw_counter,
+            block.txs[0].steps[0].rw_counter + 23
+        );
+
+        assert_eq!(run_test_circuit(block.clone()), Ok(()));
+
+        // The writes of the access list start at the `AccessListRwCounter` of
+        // the tx table, from which the Tx Circuit looks them up.
+        let mut block = block;
+        block.txs[0].access_list_rw_counter += 1;
+        assert!(run_test_circuit(block).is_err());
+    }
+
     #[test]
     fn begin_tx_large_nonce() {
         // This test checks that the rw table assignment and evm circuit are consistent
@@ -6,26 +6,32 @@ use crate::{
         util::{
             common_gadget::SameContextGadget,
             constraint_builder::{ConstraintBuilder, StepStateTransition, Transition::Delta},
-            from_bytes, CachedRegion, RandomLinearCombination,
+            from_bytes, CachedRegion, Cell, RandomLinearCombination,
         },
         witness::{Block, Call, ExecStep, Transaction},
     },
-    table::BlockContextFieldTag,
+    table::{BlockContextFieldTag, CallContextFieldTag, TxContextFieldTag},
     util::Expr,
 };
 use bus_mapping::evm::OpcodeId;
 use eth_types::Field;
 use eth_types::ToLittleEndian;
-use halo2_proofs::plonk::Error;
+use halo2_proofs::{circuit::Value, plonk::Error};
 
 #[derive(Clone, Debug)]
 pub(crate) struct BlockCtxGadget<F, const N_BYTES: usize> {
     same_context: SameContextGadget<F>,
+    tx_id: Cell<F>,
+    block_number: Cell<F>,
     value: RandomLinearCombination<F, N_BYTES>,
 }
 
 impl<F: Field, const N_BYTES: usize> BlockCtxGadget<F, N_BYTES> {
     fn construct(cb: &mut ConstraintBuilder<F>) -> Self {
+        // Lookup the number of the block of the tx
+        let tx_id = cb.call_context(None, CallContextFieldTag::TxId);
+        let block_number = cb.tx_context(tx_id.expr(), TxContextFieldTag::BlockNumber, None);
+
         let value = cb.query_rlc();
 
         // Push the const generic parameter N_BYTES value to the stack
@@ -43,11 +49,11 @@ impl<F: Field, const N_BYTES: usize> BlockCtxGadget<F, N_BYTES> {
         } else {
             from_bytes::expr(&value.cells)
         };
-        cb.block_lookup(blockctx_tag, None, value_expr);
+        cb.block_lookup(blockctx_tag, Some(block_number.expr()), value_expr);
 
         // State transition
         let step_state_transition = StepStateTransition {
-            rw_counter: Delta(1.expr()),
+            rw_counter: Delta(2.expr()),
             program_counter: Delta(1.expr()),
             stack_pointer: Delta((-1).expr()),
             gas_left: Delta(-OpcodeId::TIMESTAMP.constant_gas_cost().expr()),
@@ -57,9 +63,27 @@ impl<F: Field, const N_BYTES: usize> BlockCtxGadget<F, N_BYTES> {
 
         Self {
             same_context,
+            tx_id,
+            block_number,
             value,
         }
     }
+
+    fn assign_exec_step(
+        &self,
+        region: &mut CachedRegion<'_, '_, F>,
+        offset: usize,
+        tx: &Transaction,
+        step: &ExecStep,
+    ) -> Result<(), Error> {
+        self.same_context.assign_exec_step(region, offset, step)?;
+        self.tx_id
+            .assign(region, offset, Value::known(F::from(tx.id as u64)))?;
+        self.block_number
+            .assign(region, offset, Value::known(F::from(tx.block_number)))?;
+
+        Ok(())
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -83,15 +107,13 @@ impl<F: Field> ExecutionGadget<F> for BlockCtxU64Gadget<F> {
         region: &mut CachedRegion<'_, '_, F>,
         offset: usize,
         block: &Block<F>,
-        _: &Transaction,
+        tx: &Transaction,
         _: &Call,
         step: &ExecStep,
     ) -> Result<(), Error> {
-        self.value_u64
-            .same_context
-            .assign_exec_step(region, offset, step)?;
+        self.value_u64.assign_exec_step(region, offset, tx, step)?;
 
-        let value = block.rws[step.rw_indices[0]].stack_value();
+        let value = block.rws[step.rw_indices[1]].stack_value();
 
         self.value_u64.value.assign(
             region,
@@ -124,15 +146,13 @@ impl<F: Field> ExecutionGadget<F> for BlockCtxU160Gadget<F> {
         region: &mut CachedRegion<'_, '_, F>,
         offset: usize,
         block: &Block<F>,
-        _: &Transaction,
+        tx: &Transaction,
         _: &Call,
         step: &ExecStep,
     ) -> Result<(), Error> {
-        self.value_u160
-            .same_context
-            .assign_exec_step(region, offset, step)?;
+        self.value_u160.assign_exec_step(region, offset, tx, step)?;
 
-        let value = block.rws[step.rw_indices[0]].stack_value();
+        let value = block.rws[step.rw_indices[1]].stack_value();
 
         self.value_u160.value.assign(
             region,
@@ -169,15 +189,13 @@ impl<F: Field> ExecutionGadget<F> for BlockCtxU256Gadget<F> {
         region: &mut CachedRegion<'_, '_, F>,
         offset: usize,
         block: &Block<F>,
-        _: &Transaction,
+        tx: &Transaction,
         _: &Call,
         step: &ExecStep,
     ) -> Result<(), Error> {
-        self.value_u256
-            .same_context
-            .assign_exec_step(region, offset, step)?;
+        self.value_u256.assign_exec_step(region, offset, tx, step)?;
 
-        let value = block.rws[step.rw_indices[0]].stack_value();
+        let value = block.rws[step.rw_indices[1]].stack_value();
 
         self.value_u256
             .value
@@ -12,7 +12,7 @@ use crate::{
         },
         witness::{Block, Call, ExecStep, Transaction},
     },
-    table::BlockContextFieldTag,
+    table::{BlockContextFieldTag, CallContextFieldTag, TxContextFieldTag},
     util::Expr,
 };
 use bus_mapping::evm::OpcodeId;
@@ -23,6 +23,7 @@ use halo2_proofs::{circuit::Value, plonk::Error};
 #[derive(Clone, Debug)]
 pub(crate) struct BlockHashGadget<F> {
     same_context: SameContextGadget<F>,
+    tx_id: Cell<F>,
     block_number: RandomLinearCombination<F, N_BYTES_U64>,
     current_block_number: Cell<F>,
     block_hash: Word<F>,
@@ -36,16 +37,14 @@ impl<F: Field> ExecutionGadget<F> for BlockHashGadget<F> {
     const EXECUTION_STATE: ExecutionState = ExecutionState::BLOCKHASH;
 
     fn configure(cb: &mut ConstraintBuilder<F>) -> Self {
+        // Lookup the number of the block of the tx
+        let tx_id = cb.call_context(None, CallContextFieldTag::TxId);
+        let current_block_number =
+            cb.tx_context(tx_id.expr(), TxContextFieldTag::BlockNumber, None);
+
         let block_number = cb.query_rlc();
         cb.stack_pop(block_number.expr());
 
-        let current_block_number = cb.query_cell();
-        cb.block_lookup(
-            BlockContextFieldTag::Number.expr(),
-            None,
-            current_block_number.expr(),
-        );
-
         let block_lt = LtGadget::construct(
             cb,
             from_bytes::expr(&block_number.cells),
@@ -73,7 +72,7 @@ impl<F: Field> ExecutionGadget<F> for BlockHashGadget<F> {
         cb.stack_push(block_hash.expr());
 
         let step_state_transition = StepStateTransition {
-            rw_counter: Delta(2.expr()),
+            rw_counter: Delta(3.expr()),
             program_counter: Delta(1.expr()),
             gas_left: Delta(-OpcodeId::BLOCKHASH.constant_gas_cost().expr()),
             ..Default::default()
@@ -83,6 +82,7 @@ impl<F: Field> ExecutionGadget<F> for BlockHashGadget<F> {
         let same_context = SameContextGadget::construct(cb, opcode, step_state_transition);
         Self {
             same_context,
+            tx_id,
             block_number,
             current_block_number,
             block_hash,
@@ -96,13 +96,16 @@ impl<F: Field> ExecutionGadget<F> for BlockHashGadget<F> {
         region: &mut CachedRegion<'_, '_, F>,
         offset: usize,
         block: &Block<F>,
-        _: &Transaction,
+        tx: &Transaction,
         _: &Call,
         step: &ExecStep,
     ) -> Result<(), Error> {
         self.same_context.assign_exec_step(region, offset, step)?;
 
-        let block_number = block.rws[step.rw_indices[0]].stack_value();
+        self.tx_id
+            .assign(region, offset, Value::known(F::from(tx.id as u64)))?;
+
+        let block_number = block.rws[step.rw_indices[1]].stack_value();
         self.block_number.assign(
             region,
             offset,
@@ -114,22 +117,14 @@ impl<F: Field> ExecutionGadget<F> for BlockHashGadget<F> {
         )?;
         let block_number: F = block_number.to_scalar().unwrap();
 
-        let current_block_number = block.context.number;
-        self.current_block_number.assign(
-            region,
-            offset,
-            Value::known(
-                current_block_number
-                    .to_scalar()
-                    .expect("unexpected U256 -> Scalar conversion failure"),
-            ),
-        )?;
-        let current_block_number: F = current_block_number.to_scalar().unwrap();
+        let current_block_number = F::from(tx.block_number);
+        self.current_block_number
+            .assign(region, offset, Value::known(current_block_number))?;
 
         self.block_hash.assign(
             region,
             offset,
-            Some(block.rws[step.rw_indices[1]].stack_value().to_le_bytes()),
+            Some(block.rws[step.rw_indices[2]].stack_value().to_le_bytes()),
         )?;
 
         self.block_lt
@@ -79,9 +79,9 @@ impl<F: Field> ExecutionGadget<F> for CallGadget<F> {
         let gas_word = cb.query_word();
         let callee_address_word = cb.query_word();
         let value = cb.query_word();
-        let cd_offset = cb.query_cell();
+        let cd_offset = cb.query_cell_phase2();
         let cd_length = cb.query_rlc();
-        let rd_offset = cb.query_cell();
+        let rd_offset = cb.query_cell_phase2();
         let rd_length = cb.query_rlc();
         let is_success = cb.query_bool();
 
@@ -166,12 +166,14 @@ impl<F: Field> ExecutionGadget<F> for CallGadget<F> {
         );
 
         // Verify gas cost
-        let [callee_nonce, callee_code_hash] = [AccountFieldTag::Nonce, AccountFieldTag::CodeHash]
-            .map(|field_tag| {
-                let value = cb.query_cell();
-                cb.account_read(callee_address.clone(), field_tag, value.expr());
-                value
-            });
+        let [callee_nonce, callee_code_hash] = [
+            (AccountFieldTag::Nonce, cb.query_cell()),
+            (AccountFieldTag::CodeHash, cb.query_cell_phase2()),
+        ]
+        .map(|(field_tag, value)| {
+            cb.account_read(callee_address.clone(), field_tag, value.expr());
+            value
+        });
         let is_empty_nonce_and_balance = BatchedIsZeroGadget::construct(
             cb,
             [
@@ -419,12 +421,12 @@ impl<F: Field> ExecutionGadget<F> for CallGadget<F> {
         )?;
         self.value_is_zero
             .assign(region, offset, sum::value(&value.to_le_bytes()))?;
-        let cd_address =
-            self.cd_address
-                .assign(region, offset, cd_offset, cd_length, block.randomness)?;
-        let rd_address =
-            self.rd_address
-                .assign(region, offset, rd_offset, rd_length, block.randomness)?;
+        let cd_address = self
+            .cd_address
+            .assign(region, offset, cd_offset, cd_length)?;
+        let rd_address = self
+            .rd_address
+            .assign(region, offset, rd_offset, rd_length)?;
         let (_, memory_expansion_gas_cost) = self.memory_expansion.assign(
             region,
             offset,
@@ -447,29 +449,25 @@ impl<F: Field> ExecutionGadget<F> for CallGadget<F> {
                     .expect("unexpected U256 -> Scalar conversion failure"),
             ),
         )?;
-        self.callee_code_hash.assign(
-            region,
-            offset,
-            Value::known(Word::random_linear_combine(
-                callee_code_hash.to_le_bytes(),
-                block.randomness,
-            )),
-        )?;
-        let is_empty_nonce_and_balance = self.is_empty_nonce_and_balance.assign(
+        self.callee_code_hash
+            .assign(region, offset, region.word_rlc(callee_code_hash))?;
+        self.is_empty_nonce_and_balance.assign(
             region,
             offset,
             [
-                F::from(callee_nonce.low_u64()),
-                Word::random_linear_combine(callee_balance_pair.1.to_le_bytes(), block.randomness),
+                Value::known(F::from(callee_nonce.low_u64())),
+                region.word_rlc(callee_balance_pair.1),
             ],
         )?;
-        let is_empty_code_hash = self.is_empty_code_hash.assign(
+        self.is_empty_code_hash.assign_value(
             region,
             offset,
-            Word::random_linear_combine(callee_code_hash.to_le_bytes(), block.randomness),
-            Word::random_linear_combine(*EMPTY_HASH_LE, block.randomness),
+            region.word_rlc(callee_code_hash),
+            region.empty_hash_rlc(),
         )?;
-        let is_empty_account = is_empty_nonce_and_balance * is_empty_code_hash;
+        let is_empty_account = callee_nonce.is_zero()
+            && callee_balance_pair.1.is_zero()
+            && callee_code_hash.to_le_bytes() == *EMPTY_HASH_LE;
         let has_value = !value.is_zero();
         let gas_cost = if is_warm_prev {
             GasCost::WARM_ACCESS.as_u64()
@@ -477,7 +475,7 @@ impl<F: Field> ExecutionGadget<F> for CallGadget<F> {
             GasCost::COLD_ACCOUNT_ACCESS.as_u64()
         } + if has_value {
             GasCost::CALL_WITH_VALUE.as_u64()
-                + if is_empty_account == F::one() {
+                + if is_empty_account {
                     GasCost::NEW_ACCOUNT.as_u64()
                 } else {
                     0
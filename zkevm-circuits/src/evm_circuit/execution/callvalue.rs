@@ -5,7 +5,7 @@ use crate::{
         util::{
             common_gadget::SameContextGadget,
             constraint_builder::{ConstraintBuilder, StepStateTransition, Transition::Delta},
-            CachedRegion, Cell, Word,
+            CachedRegion, Cell,
         },
         witness::{Block, Call, ExecStep, Transaction},
     },
@@ -13,8 +13,8 @@ use crate::{
     util::Expr,
 };
 use bus_mapping::evm::OpcodeId;
-use eth_types::{Field, ToLittleEndian};
-use halo2_proofs::{circuit::Value, plonk::Error};
+use eth_types::Field;
+use halo2_proofs::plonk::Error;
 
 #[derive(Clone, Debug)]
 pub(crate) struct CallValueGadget<F> {
@@ -30,7 +30,7 @@ impl<F: Field> ExecutionGadget<F> for CallValueGadget<F> {
     const EXECUTION_STATE: ExecutionState = ExecutionState::CALLVALUE;
 
     fn configure(cb: &mut ConstraintBuilder<F>) -> Self {
-        let call_value = cb.query_cell();
+        let call_value = cb.query_cell_phase2();
 
         // Lookup rw_table -> call_context with call value
         cb.call_context_lookup(
@@ -73,14 +73,8 @@ impl<F: Field> ExecutionGadget<F> for CallValueGadget<F> {
 
         let call_value = block.rws[step.rw_indices[1]].stack_value();
 
-        self.call_value.assign(
-            region,
-            offset,
-            Value::known(Word::random_linear_combine(
-                call_value.to_le_bytes(),
-                block.randomness,
-            )),
-        )?;
+        self.call_value
+            .assign(region, offset, region.word_rlc(call_value))?;
 
         Ok(())
     }
@@ -5,7 +5,7 @@ use crate::{
         util::{
             common_gadget::SameContextGadget,
             constraint_builder::{ConstraintBuilder, StepStateTransition, Transition::Delta},
-            CachedRegion, Cell, Word,
+            CachedRegion, Cell,
         },
         witness::{Block, Call, ExecStep, Transaction},
     },
@@ -13,8 +13,8 @@ use crate::{
     util::Expr,
 };
 use bus_mapping::evm::OpcodeId;
-use eth_types::{Field, ToLittleEndian};
-use halo2_proofs::{circuit::Value, plonk::Error};
+use eth_types::Field;
+use halo2_proofs::plonk::Error;
 
 #[derive(Clone, Debug)]
 pub(crate) struct ChainIdGadget<F> {
@@ -28,7 +28,7 @@ impl<F: Field> ExecutionGadget<F> for ChainIdGadget<F> {
     const EXECUTION_STATE: ExecutionState = ExecutionState::CHAINID;
 
     fn configure(cb: &mut ConstraintBuilder<F>) -> Self {
-        let chain_id = cb.query_cell();
+        let chain_id = cb.query_cell_phase2();
 
         // Push the value to the stack
         cb.stack_push(chain_id.expr());
@@ -65,14 +65,8 @@ impl<F: Field> ExecutionGadget<F> for ChainIdGadget<F> {
         self.same_context.assign_exec_step(region, offset, step)?;
         let chain_id = block.rws[step.rw_indices[0]].stack_value();
 
-        self.chain_id.assign(
-            region,
-            offset,
-            Value::known(Word::random_linear_combine(
-                chain_id.to_le_bytes(),
-                block.randomness,
-            )),
-        )?;
+        self.chain_id
+            .assign(region, offset, region.word_rlc(chain_id))?;
         Ok(())
     }
 }
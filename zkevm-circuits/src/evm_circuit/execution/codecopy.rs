@@ -50,7 +50,7 @@ impl<F: Field> ExecutionGadget<F> for CodeCopyGadget<F> {
         let opcode = cb.query_cell();
 
         // Query elements to be popped from the stack.
-        let dst_memory_offset = cb.query_cell();
+        let dst_memory_offset = cb.query_cell_phase2();
         let code_offset = cb.query_rlc();
         let size = cb.query_rlc();
 
@@ -171,9 +171,9 @@ impl<F: Field> ExecutionGadget<F> for CodeCopyGadget<F> {
         )?;
 
         // assign the destination memory offset.
-        let memory_address =
-            self.dst_memory_addr
-                .assign(region, offset, dest_offset, size, block.randomness)?;
+        let memory_address = self
+            .dst_memory_addr
+            .assign(region, offset, dest_offset, size)?;
 
         // assign to gadgets handling memory expansion cost and copying cost.
         let (_, memory_expansion_cost) = self.memory_expansion.assign(
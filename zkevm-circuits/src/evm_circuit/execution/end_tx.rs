@@ -10,22 +10,20 @@ use crate::{
                 AddWordsGadget, ConstantDivisionGadget, IsEqualGadget, MinMaxGadget,
                 MulWordByU64Gadget,
             },
-            CachedRegion, Cell,
+            not, CachedRegion, Cell,
         },
         witness::{Block, Call, ExecStep, Transaction},
     },
-    table::{
-        BlockContextFieldTag, CallContextFieldTag, RwTableTag, TxContextFieldTag, TxReceiptFieldTag,
-    },
+    table::{BlockContextFieldTag, CallContextFieldTag, TxContextFieldTag, TxReceiptFieldTag},
     util::Expr,
 };
 use eth_types::{evm_types::MAX_REFUND_QUOTIENT_OF_GAS_USED, Field, ToScalar};
 use halo2_proofs::{circuit::Value, plonk::Error};
-use strum::EnumCount;
 
 #[derive(Clone, Debug)]
 pub(crate) struct EndTxGadget<F> {
     tx_id: Cell<F>,
+    tx_block_number: Cell<F>,
     tx_gas: Cell<F>,
     max_refund: ConstantDivisionGadget<F, N_BYTES_GAS>,
     refund: Cell<F>,
@@ -39,6 +37,8 @@ pub(crate) struct EndTxGadget<F> {
     coinbase_reward: UpdateBalanceGadget<F, 2, true>,
     current_cumulative_gas_used: Cell<F>,
     is_first_tx: IsEqualGadget<F>,
+    prev_tx_block_number: Cell<F>,
+    is_same_block: IsEqualGadget<F>,
     is_persistent: Cell<F>,
 }
 
@@ -51,9 +51,12 @@ impl<F: Field> ExecutionGadget<F> for EndTxGadget<F> {
         let tx_id = cb.call_context(None, CallContextFieldTag::TxId);
         let is_persistent = cb.call_context(None, CallContextFieldTag::IsPersistent);
 
-        let [tx_gas, tx_caller_address] =
-            [TxContextFieldTag::Gas, TxContextFieldTag::CallerAddress]
-                .map(|field_tag| cb.tx_context(tx_id.expr(), field_tag, None));
+        let [tx_block_number, tx_gas, tx_caller_address] = [
+            TxContextFieldTag::BlockNumber,
+            TxContextFieldTag::Gas,
+            TxContextFieldTag::CallerAddress,
+        ]
+        .map(|field_tag| cb.tx_context(tx_id.expr(), field_tag, None));
         let tx_gas_price = cb.tx_context_as_word(tx_id.expr(), TxContextFieldTag::GasPrice, None);
 
         // Calculate effective gas to refund
@@ -80,14 +83,15 @@ impl<F: Field> ExecutionGadget<F> for EndTxGadget<F> {
             None,
         );
 
-        // Add gas_used * effective_tip to coinbase's balance
+        // Add gas_used * effective_tip to the balance of the coinbase of the
+        // block of the tx
         let coinbase = cb.query_cell();
         let base_fee = cb.query_word();
         for (tag, value) in [
             (BlockContextFieldTag::Coinbase, coinbase.expr()),
             (BlockContextFieldTag::BaseFee, base_fee.expr()),
         ] {
-            cb.block_lookup(tag.expr(), None, value);
+            cb.block_lookup(tag.expr(), Some(tx_block_number.expr()), value);
         }
         let effective_tip = cb.query_word();
         let sub_gas_price_by_base_fee =
@@ -115,17 +119,33 @@ impl<F: Field> ExecutionGadget<F> for EndTxGadget<F> {
             cb.curr.state.log_id.expr(),
         );
 
+        // The cumulative gas used restarts from zero at the first tx of each
+        // block, which is the first tx of the batch or a tx whose block number
+        // differs from the one of the previous tx.
         let is_first_tx = IsEqualGadget::construct(cb, tx_id.expr(), 1.expr());
+        let prev_tx_block_number = cb.query_cell();
+        cb.condition(not::expr(is_first_tx.expr()), |cb| {
+            cb.tx_context_lookup(
+                tx_id.expr() - 1.expr(),
+                TxContextFieldTag::BlockNumber,
+                None,
+                prev_tx_block_number.expr(),
+            );
+        });
+        let is_same_block =
+            IsEqualGadget::construct(cb, prev_tx_block_number.expr(), tx_block_number.expr());
+        let is_first_tx_in_block =
+            is_first_tx.expr() + not::expr(is_first_tx.expr()) * not::expr(is_same_block.expr());
 
         let current_cumulative_gas_used = cb.query_cell();
-        cb.condition(is_first_tx.expr(), |cb| {
+        cb.condition(is_first_tx_in_block.clone(), |cb| {
             cb.require_zero(
-                "current_cumulative_gas_used is zero when tx is first tx",
+                "current_cumulative_gas_used is zero when tx is first tx of its block",
                 current_cumulative_gas_used.expr(),
             );
         });
 
-        cb.condition(1.expr() - is_first_tx.expr(), |cb| {
+        cb.condition(not::expr(is_first_tx_in_block.clone()), |cb| {
             cb.tx_receipt_lookup(
                 0.expr(),
                 tx_id.expr() - 1.expr(),
@@ -152,7 +172,7 @@ impl<F: Field> ExecutionGadget<F> for EndTxGadget<F> {
                 );
 
                 cb.require_step_state_transition(StepStateTransition {
-                    rw_counter: Delta(10.expr() - is_first_tx.expr()),
+                    rw_counter: Delta(10.expr() - is_first_tx_in_block.clone()),
                     ..StepStateTransition::any()
                 });
             },
@@ -162,7 +182,7 @@ impl<F: Field> ExecutionGadget<F> for EndTxGadget<F> {
             cb.next.execution_state_selector([ExecutionState::EndBlock]),
             |cb| {
                 cb.require_step_state_transition(StepStateTransition {
-                    rw_counter: Delta(9.expr() - is_first_tx.expr()),
+                    rw_counter: Delta(9.expr() - is_first_tx_in_block),
                     ..StepStateTransition::any()
                 });
             },
@@ -170,6 +190,7 @@ impl<F: Field> ExecutionGadget<F> for EndTxGadget<F> {
 
         Self {
             tx_id,
+            tx_block_number,
             tx_gas,
             max_refund,
             refund,
@@ -183,6 +204,8 @@ impl<F: Field> ExecutionGadget<F> for EndTxGadget<F> {
             coinbase_reward,
             current_cumulative_gas_used,
             is_first_tx,
+            prev_tx_block_number,
+            is_same_block,
             is_persistent,
         }
     }
@@ -201,8 +224,16 @@ impl<F: Field> ExecutionGadget<F> for EndTxGadget<F> {
         let [(caller_balance, caller_balance_prev), (coinbase_balance, coinbase_balance_prev)] =
             [step.rw_indices[3], step.rw_indices[4]].map(|idx| block.rws[idx].account_value_pair());
 
+        let context = block
+            .context
+            .ctxs
+            .get(&tx.block_number)
+            .ok_or(Error::Synthesis)?;
+
         self.tx_id
             .assign(region, offset, Value::known(F::from(tx.id as u64)))?;
+        self.tx_block_number
+            .assign(region, offset, Value::known(F::from(tx.block_number)))?;
         self.tx_gas
             .assign(region, offset, Value::known(F::from(tx.gas)))?;
         let (max_refund, _) = self.max_refund.assign(region, offset, gas_used as u128)?;
@@ -239,11 +270,13 @@ impl<F: Field> ExecutionGadget<F> for EndTxGadget<F> {
             vec![gas_fee_refund],
             caller_balance,
         )?;
-        let effective_tip = tx.gas_price - block.context.base_fee;
+        // An invalid tx whose gas price doesn't cover the base fee fails the
+        // constraint of sub_gas_price_by_base_fee.
+        let effective_tip = tx.gas_price.saturating_sub(context.base_fee);
         self.sub_gas_price_by_base_fee.assign(
             region,
             offset,
-            [effective_tip, block.context.base_fee],
+            [effective_tip, context.base_fee],
             tx.gas_price,
         )?;
         self.mul_effective_tip_by_gas_used.assign(
@@ -257,8 +290,7 @@ impl<F: Field> ExecutionGadget<F> for EndTxGadget<F> {
             region,
             offset,
             Value::known(
-                block
-                    .context
+                context
                     .coinbase
                     .to_scalar()
                     .expect("unexpected Address -> Scalar conversion failure"),
@@ -272,17 +304,24 @@ impl<F: Field> ExecutionGadget<F> for EndTxGadget<F> {
             coinbase_balance,
         )?;
 
-        let current_cumulative_gas_used: u64 = if tx.id == 1 {
+        let prev_tx_block_number = if tx.id == 1 {
             0
         } else {
-            // first transaction needs TxReceiptFieldTag::COUNT(3) lookups to tx receipt,
-            // while later transactions need 4 (with one extra cumulative gas read) lookups
-            let rw = &block.rws[(
-                RwTableTag::TxReceipt,
-                (tx.id - 2) * (TxReceiptFieldTag::COUNT + 1) + 2,
-            )];
-            rw.receipt_value()
+            block
+                .txs
+                .iter()
+                .find(|prev_tx| prev_tx.id == tx.id - 1)
+                .ok_or(Error::Synthesis)?
+                .block_number
         };
+        let current_cumulative_gas_used: u64 =
+            if tx.id == 1 || prev_tx_block_number != tx.block_number {
+                0
+            } else {
+                // the cumulative gas of the previous tx is read right after the
+                // PostStateOrStatus and LogLength receipt writes
+                block.rws[step.rw_indices[7]].receipt_value()
+            };
 
         self.current_cumulative_gas_used.assign(
             region,
@@ -291,6 +330,17 @@ impl<F: Field> ExecutionGadget<F> for EndTxGadget<F> {
         )?;
         self.is_first_tx
             .assign(region, offset, F::from(tx.id as u64), F::one())?;
+        self.prev_tx_block_number.assign(
+            region,
+            offset,
+            Value::known(F::from(prev_tx_block_number)),
+        )?;
+        self.is_same_block.assign(
+            region,
+            offset,
+            F::from(prev_tx_block_number),
+            F::from(tx.block_number),
+        )?;
         self.is_persistent.assign(
             region,
             offset,
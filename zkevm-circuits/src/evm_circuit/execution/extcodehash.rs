@@ -61,13 +61,13 @@ impl<F: Field> ExecutionGadget<F> for ExtcodehashGadget<F> {
             AccountFieldTag::Nonce,
             nonce.expr(),
         );
-        let balance = cb.query_cell();
+        let balance = cb.query_cell_phase2();
         cb.account_read(
             from_bytes::expr(&external_address.cells),
             AccountFieldTag::Balance,
             balance.expr(),
         );
-        let code_hash = cb.query_cell();
+        let code_hash = cb.query_cell_phase2();
         cb.account_read(
             from_bytes::expr(&external_address.cells),
             AccountFieldTag::CodeHash,
@@ -156,21 +156,17 @@ impl<F: Field> ExecutionGadget<F> for ExtcodehashGadget<F> {
 
         let [nonce, balance, code_hash] = [5, 6, 7].map(|i| {
             block.rws[step.rw_indices[i]]
-                .table_assignment(block.randomness)
+                .table_assignment(region.challenges().evm_word())
                 .value
         });
 
-        self.nonce.assign(region, offset, Value::known(nonce))?;
-        self.balance.assign(region, offset, Value::known(balance))?;
-        self.code_hash
-            .assign(region, offset, Value::known(code_hash))?;
+        self.nonce.assign(region, offset, nonce)?;
+        self.balance.assign(region, offset, balance)?;
+        self.code_hash.assign(region, offset, code_hash)?;
 
-        let empty_code_hash_rlc = Word::random_linear_combine(*EMPTY_HASH_LE, block.randomness);
-        self.is_empty.assign(
-            region,
-            offset,
-            [nonce, balance, code_hash - empty_code_hash_rlc],
-        )?;
+        let code_hash_diff = code_hash - region.empty_hash_rlc();
+        self.is_empty
+            .assign(region, offset, [nonce, balance, code_hash_diff])?;
 
         Ok(())
     }
@@ -11,14 +11,14 @@ use crate::{
             },
             from_bytes,
             math_gadget::IsZeroGadget,
-            select, CachedRegion, Cell, RandomLinearCombination, Word,
+            select, CachedRegion, Cell, RandomLinearCombination,
         },
         witness::{Block, Call, ExecStep, Transaction},
     },
     util::Expr,
 };
 use eth_types::{evm_types::OpcodeId, Field, ToLittleEndian};
-use halo2_proofs::{circuit::Value, plonk::Error};
+use halo2_proofs::plonk::Error;
 
 #[derive(Clone, Debug)]
 pub(crate) struct JumpiGadget<F> {
@@ -35,7 +35,7 @@ impl<F: Field> ExecutionGadget<F> for JumpiGadget<F> {
 
     fn configure(cb: &mut ConstraintBuilder<F>) -> Self {
         let destination = cb.query_rlc();
-        let condition = cb.query_cell();
+        let condition = cb.query_cell_phase2();
 
         // Pop the value from the stack
         cb.stack_pop(destination.expr());
@@ -94,7 +94,7 @@ impl<F: Field> ExecutionGadget<F> for JumpiGadget<F> {
 
         let [destination, condition] =
             [step.rw_indices[0], step.rw_indices[1]].map(|idx| block.rws[idx].stack_value());
-        let condition = Word::random_linear_combine(condition.to_le_bytes(), block.randomness);
+        let condition = region.word_rlc(condition);
 
         self.destination.assign(
             region,
@@ -105,9 +105,9 @@ impl<F: Field> ExecutionGadget<F> for JumpiGadget<F> {
                     .unwrap(),
             ),
         )?;
-        self.condition
-            .assign(region, offset, Value::known(condition))?;
-        self.is_condition_zero.assign(region, offset, condition)?;
+        self.condition.assign(region, offset, condition)?;
+        self.is_condition_zero
+            .assign_value(region, offset, condition)?;
 
         Ok(())
     }
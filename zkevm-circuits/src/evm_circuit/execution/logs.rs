@@ -10,7 +10,7 @@ use crate::{
                 Transition::{Delta, To},
             },
             memory_gadget::{MemoryAddressGadget, MemoryExpansionGadget},
-            not, sum, CachedRegion, Cell, Word,
+            not, sum, CachedRegion, Cell,
         },
         witness::{Block, Call, ExecStep, Transaction},
     },
@@ -20,7 +20,7 @@ use crate::{
 use array_init::array_init;
 use bus_mapping::circuit_input_builder::CopyDataType;
 use eth_types::Field;
-use eth_types::{evm_types::GasCost, evm_types::OpcodeId, ToLittleEndian, ToScalar};
+use eth_types::{evm_types::GasCost, evm_types::OpcodeId, ToScalar, U256};
 use halo2_proofs::{circuit::Value, plonk::Error};
 
 #[derive(Clone, Debug)]
@@ -45,7 +45,7 @@ impl<F: Field> ExecutionGadget<F> for LogGadget<F> {
     const EXECUTION_STATE: ExecutionState = ExecutionState::LOG;
 
     fn configure(cb: &mut ConstraintBuilder<F>) -> Self {
-        let mstart = cb.query_cell();
+        let mstart = cb.query_cell_phase2();
         let msize = cb.query_rlc();
 
         // Pop mstart_address, msize from stack
@@ -74,7 +74,7 @@ impl<F: Field> ExecutionGadget<F> for LogGadget<F> {
         });
 
         // constrain topics in logs
-        let topics = array_init(|_| cb.query_cell());
+        let topics = array_init(|_| cb.query_cell_phase2());
         let topic_selectors: [Cell<F>; 4] = array_init(|_| cb.query_cell());
         for (idx, topic) in topics.iter().enumerate() {
             cb.condition(topic_selectors[idx].expr(), |cb| {
@@ -197,9 +197,9 @@ impl<F: Field> ExecutionGadget<F> for LogGadget<F> {
         let [memory_start, msize] =
             [step.rw_indices[0], step.rw_indices[1]].map(|idx| block.rws[idx].stack_value());
 
-        let memory_address =
-            self.memory_address
-                .assign(region, offset, memory_start, msize, block.randomness)?;
+        let memory_address = self
+            .memory_address
+            .assign(region, offset, memory_start, msize)?;
 
         // Memory expansion
         self.memory_expansion
@@ -218,18 +218,15 @@ impl<F: Field> ExecutionGadget<F> for LogGadget<F> {
         };
 
         for i in 0..4 {
-            let mut topic = Word::random_linear_combine([0; 32], block.randomness);
+            let mut topic = region.word_rlc(U256::zero());
             if i < topic_count {
-                topic = Word::random_linear_combine(
-                    block.rws[topic_stack_entry].stack_value().to_le_bytes(),
-                    block.randomness,
-                );
+                topic = region.word_rlc(block.rws[topic_stack_entry].stack_value());
                 self.topic_selectors[i].assign(region, offset, Value::known(F::one()))?;
                 topic_stack_entry.1 += 1;
             } else {
                 self.topic_selectors[i].assign(region, offset, Value::known(F::zero()))?;
             }
-            self.topics[i].assign(region, offset, Value::known(topic))?;
+            self.topics[i].assign(region, offset, topic)?;
         }
 
         self.contract_address.assign(
@@ -143,8 +143,7 @@ impl<F: Field> ExecutionGadget<F> for MulModGadget<F> {
         self.d.assign(region, offset, Some(d.to_le_bytes()))?;
         self.e.assign(region, offset, Some(e.to_le_bytes()))?;
 
-        self.modword
-            .assign(region, offset, a, n, a_reduced, block.randomness)?;
+        self.modword.assign(region, offset, a, n, a_reduced)?;
         self.mul512_left
             .assign(region, offset, [a_reduced, b, d, e], None)?;
         self.mul512_right
@@ -5,7 +5,7 @@ use crate::{
         util::{
             common_gadget::SameContextGadget,
             constraint_builder::{ConstraintBuilder, StepStateTransition, Transition::Delta},
-            CachedRegion, Cell, Word,
+            CachedRegion, Cell,
         },
         witness::{Block, Call, ExecStep, Transaction},
     },
@@ -13,7 +13,7 @@ use crate::{
     util::Expr,
 };
 use bus_mapping::evm::OpcodeId;
-use eth_types::{Field, ToLittleEndian, ToScalar};
+use eth_types::{Field, ToScalar};
 use halo2_proofs::{circuit::Value, plonk::Error};
 
 #[derive(Clone, Debug)]
@@ -31,7 +31,7 @@ impl<F: Field> ExecutionGadget<F> for SelfbalanceGadget<F> {
     fn configure(cb: &mut ConstraintBuilder<F>) -> Self {
         let callee_address = cb.call_context(None, CallContextFieldTag::CalleeAddress);
 
-        let self_balance = cb.query_cell();
+        let self_balance = cb.query_cell_phase2();
         cb.account_read(
             callee_address.expr(),
             AccountFieldTag::Balance,
@@ -79,14 +79,8 @@ impl<F: Field> ExecutionGadget<F> for SelfbalanceGadget<F> {
         )?;
 
         let self_balance = block.rws[step.rw_indices[2]].stack_value();
-        self.self_balance.assign(
-            region,
-            offset,
-            Value::known(Word::random_linear_combine(
-                self_balance.to_le_bytes(),
-                block.randomness,
-            )),
-        )?;
+        self.self_balance
+            .assign(region, offset, region.word_rlc(self_balance))?;
 
         Ok(())
     }
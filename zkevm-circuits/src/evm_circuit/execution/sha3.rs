@@ -36,7 +36,7 @@ impl<F: Field> ExecutionGadget<F> for Sha3Gadget<F> {
     fn configure(cb: &mut ConstraintBuilder<F>) -> Self {
         let opcode = cb.query_cell();
 
-        let offset = cb.query_cell();
+        let offset = cb.query_cell_phase2();
         let size = cb.query_rlc();
         let sha3_rlc = cb.query_rlc();
 
@@ -47,7 +47,7 @@ impl<F: Field> ExecutionGadget<F> for Sha3Gadget<F> {
         let memory_address = MemoryAddressGadget::construct(cb, offset, size);
 
         let copy_rwc_inc = cb.query_cell();
-        let rlc_acc = cb.query_cell();
+        let rlc_acc = cb.query_cell_phase2();
         cb.condition(memory_address.has_length(), |cb| {
             cb.copy_table_lookup(
                 cb.curr.state.call_id.expr(),
@@ -117,9 +117,9 @@ impl<F: Field> ExecutionGadget<F> for Sha3Gadget<F> {
         let [memory_offset, size, sha3_output] =
             [step.rw_indices[0], step.rw_indices[1], step.rw_indices[2]]
                 .map(|idx| block.rws[idx].stack_value());
-        let memory_address =
-            self.memory_address
-                .assign(region, offset, memory_offset, size, block.randomness)?;
+        let memory_address = self
+            .memory_address
+            .assign(region, offset, memory_offset, size)?;
         self.sha3_rlc
             .assign(region, offset, Some(sha3_output.to_le_bytes()))?;
 
@@ -135,8 +135,11 @@ impl<F: Field> ExecutionGadget<F> for Sha3Gadget<F> {
         let values: Vec<u8> = (3..3 + (size.low_u64() as usize))
             .map(|i| block.rws[step.rw_indices[i]].memory_value())
             .collect();
-        let rlc_acc = rlc::value(values.iter().rev(), block.randomness);
-        self.rlc_acc.assign(region, offset, Value::known(rlc_acc))?;
+        let rlc_acc = region
+            .challenges()
+            .keccak_input()
+            .map(|randomness| rlc::value(values.iter().rev(), randomness));
+        self.rlc_acc.assign(region, offset, rlc_acc)?;
 
         // Memory expansion and dynamic gas cost for reading it.
         let (_, memory_expansion_gas_cost) = self.memory_expansion.assign(
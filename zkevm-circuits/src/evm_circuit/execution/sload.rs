@@ -7,14 +7,14 @@ use crate::{
             constraint_builder::{
                 ConstraintBuilder, ReversionInfo, StepStateTransition, Transition::Delta,
             },
-            select, CachedRegion, Cell, Word,
+            select, CachedRegion, Cell,
         },
         witness::{Block, Call, ExecStep, Transaction},
     },
     table::CallContextFieldTag,
     util::Expr,
 };
-use eth_types::{evm_types::GasCost, Field, ToLittleEndian, ToScalar};
+use eth_types::{evm_types::GasCost, Field, ToScalar};
 use halo2_proofs::{
     circuit::Value,
     plonk::{Error, Expression},
@@ -44,12 +44,12 @@ impl<F: Field> ExecutionGadget<F> for SloadGadget<F> {
         let mut reversion_info = cb.reversion_info_read(None);
         let callee_address = cb.call_context(None, CallContextFieldTag::CalleeAddress);
 
-        let key = cb.query_cell();
+        let key = cb.query_cell_phase2();
         // Pop the key from the stack
         cb.stack_pop(key.expr());
 
-        let value = cb.query_cell();
-        let committed_value = cb.query_cell();
+        let value = cb.query_cell_phase2();
+        let committed_value = cb.query_cell_phase2();
         cb.account_storage_read(
             callee_address.expr(),
             key.expr(),
@@ -123,32 +123,12 @@ impl<F: Field> ExecutionGadget<F> for SloadGadget<F> {
 
         let [key, value] =
             [step.rw_indices[4], step.rw_indices[6]].map(|idx| block.rws[idx].stack_value());
-        self.key.assign(
-            region,
-            offset,
-            Value::known(Word::random_linear_combine(
-                key.to_le_bytes(),
-                block.randomness,
-            )),
-        )?;
-        self.value.assign(
-            region,
-            offset,
-            Value::known(Word::random_linear_combine(
-                value.to_le_bytes(),
-                block.randomness,
-            )),
-        )?;
+        self.key.assign(region, offset, region.word_rlc(key))?;
+        self.value.assign(region, offset, region.word_rlc(value))?;
 
         let (_, committed_value) = block.rws[step.rw_indices[5]].aux_pair();
-        self.committed_value.assign(
-            region,
-            offset,
-            Value::known(Word::random_linear_combine(
-                committed_value.to_le_bytes(),
-                block.randomness,
-            )),
-        )?;
+        self.committed_value
+            .assign(region, offset, region.word_rlc(committed_value))?;
 
         let (_, is_warm) = block.rws[step.rw_indices[7]].tx_access_list_value_pair();
         self.is_warm
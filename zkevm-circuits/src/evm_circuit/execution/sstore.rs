@@ -8,7 +8,7 @@ use crate::{
                 ConstraintBuilder, ReversionInfo, StepStateTransition, Transition::Delta,
             },
             math_gadget::{IsEqualGadget, IsZeroGadget},
-            not, select, CachedRegion, Cell, Word,
+            not, select, CachedRegion, Cell,
         },
         witness::{Block, Call, ExecStep, Transaction},
     },
@@ -16,7 +16,7 @@ use crate::{
     util::Expr,
 };
 
-use eth_types::{evm_types::GasCost, Field, ToLittleEndian, ToScalar};
+use eth_types::{evm_types::GasCost, Field, ToScalar};
 use halo2_proofs::{
     circuit::Value,
     plonk::{Error, Expression},
@@ -56,16 +56,16 @@ impl<F: Field> ExecutionGadget<F> for SstoreGadget<F> {
         let mut reversion_info = cb.reversion_info_read(None);
         let callee_address = cb.call_context(None, CallContextFieldTag::CalleeAddress);
 
-        let key = cb.query_cell();
+        let key = cb.query_cell_phase2();
         // Pop the key from the stack
         cb.stack_pop(key.expr());
 
-        let value = cb.query_cell();
+        let value = cb.query_cell_phase2();
         // Pop the value from the stack
         cb.stack_pop(value.expr());
 
-        let value_prev = cb.query_cell();
-        let original_value = cb.query_cell();
+        let value_prev = cb.query_cell_phase2();
+        let original_value = cb.query_cell_phase2();
         cb.account_storage_write(
             callee_address.expr(),
             key.expr(),
@@ -169,40 +169,14 @@ impl<F: Field> ExecutionGadget<F> for SstoreGadget<F> {
 
         let [key, value] =
             [step.rw_indices[5], step.rw_indices[6]].map(|idx| block.rws[idx].stack_value());
-        self.key.assign(
-            region,
-            offset,
-            Value::known(Word::random_linear_combine(
-                key.to_le_bytes(),
-                block.randomness,
-            )),
-        )?;
-        self.value.assign(
-            region,
-            offset,
-            Value::known(Word::random_linear_combine(
-                value.to_le_bytes(),
-                block.randomness,
-            )),
-        )?;
+        self.key.assign(region, offset, region.word_rlc(key))?;
+        self.value.assign(region, offset, region.word_rlc(value))?;
 
         let (_, value_prev, _, original_value) = block.rws[step.rw_indices[7]].storage_value_aux();
-        self.value_prev.assign(
-            region,
-            offset,
-            Value::known(Word::random_linear_combine(
-                value_prev.to_le_bytes(),
-                block.randomness,
-            )),
-        )?;
-        self.original_value.assign(
-            region,
-            offset,
-            Value::known(Word::random_linear_combine(
-                original_value.to_le_bytes(),
-                block.randomness,
-            )),
-        )?;
+        self.value_prev
+            .assign(region, offset, region.word_rlc(value_prev))?;
+        self.original_value
+            .assign(region, offset, region.word_rlc(original_value))?;
 
         let (_, is_warm) = block.rws[step.rw_indices[8]].tx_access_list_value_pair();
         self.is_warm
@@ -220,7 +194,6 @@ impl<F: Field> ExecutionGadget<F> for SstoreGadget<F> {
             value_prev,
             original_value,
             is_warm,
-            block.randomness,
         )?;
 
         self.tx_refund.assign(
@@ -231,7 +204,6 @@ impl<F: Field> ExecutionGadget<F> for SstoreGadget<F> {
             value,
             value_prev,
             original_value,
-            block.randomness,
         )?;
         Ok(())
     }
@@ -307,48 +279,28 @@ impl<F: Field> SstoreGasGadget<F> {
         value_prev: eth_types::Word,
         original_value: eth_types::Word,
         is_warm: bool,
-        randomness: F,
     ) -> Result<(), Error> {
-        self.value.assign(
-            region,
-            offset,
-            Value::known(Word::random_linear_combine(value.to_le_bytes(), randomness)),
-        )?;
-        self.value_prev.assign(
-            region,
-            offset,
-            Value::known(Word::random_linear_combine(
-                value_prev.to_le_bytes(),
-                randomness,
-            )),
-        )?;
-        self.original_value.assign(
-            region,
-            offset,
-            Value::known(Word::random_linear_combine(
-                original_value.to_le_bytes(),
-                randomness,
-            )),
-        )?;
+        self.value.assign(region, offset, region.word_rlc(value))?;
+        self.value_prev
+            .assign(region, offset, region.word_rlc(value_prev))?;
+        self.original_value
+            .assign(region, offset, region.word_rlc(original_value))?;
         self.is_warm
             .assign(region, offset, Value::known(F::from(is_warm as u64)))?;
-        self.value_eq_prev.assign(
+        self.value_eq_prev.assign_value(
             region,
             offset,
-            Word::random_linear_combine(value.to_le_bytes(), randomness),
-            Word::random_linear_combine(value_prev.to_le_bytes(), randomness),
+            region.word_rlc(value),
+            region.word_rlc(value_prev),
         )?;
-        self.original_eq_prev.assign(
+        self.original_eq_prev.assign_value(
             region,
             offset,
-            Word::random_linear_combine(original_value.to_le_bytes(), randomness),
-            Word::random_linear_combine(value_prev.to_le_bytes(), randomness),
-        )?;
-        self.original_is_zero.assign(
-            region,
-            offset,
-            Word::random_linear_combine(original_value.to_le_bytes(), randomness),
+            region.word_rlc(original_value),
+            region.word_rlc(value_prev),
         )?;
+        self.original_is_zero
+            .assign_value(region, offset, region.word_rlc(original_value))?;
         debug_assert_eq!(
             calc_expected_gas_cost(value, value_prev, original_value, is_warm),
             gas_cost
@@ -452,63 +404,40 @@ impl<F: Field> SstoreTxRefundGadget<F> {
         value: eth_types::Word,
         value_prev: eth_types::Word,
         original_value: eth_types::Word,
-        randomness: F,
     ) -> Result<(), Error> {
         self.tx_refund_old
             .assign(region, offset, Value::known(F::from(tx_refund_old)))?;
-        self.value.assign(
-            region,
-            offset,
-            Value::known(Word::random_linear_combine(value.to_le_bytes(), randomness)),
-        )?;
-        self.value_prev.assign(
-            region,
-            offset,
-            Value::known(Word::random_linear_combine(
-                value_prev.to_le_bytes(),
-                randomness,
-            )),
-        )?;
-        self.original_value.assign(
-            region,
-            offset,
-            Value::known(Word::random_linear_combine(
-                original_value.to_le_bytes(),
-                randomness,
-            )),
-        )?;
-        self.value_prev_is_zero_gadget.assign(
-            region,
-            offset,
-            Word::random_linear_combine(value_prev.to_le_bytes(), randomness),
-        )?;
-        self.value_is_zero_gadget.assign(
-            region,
-            offset,
-            Word::random_linear_combine(value.to_le_bytes(), randomness),
-        )?;
-        self.original_is_zero_gadget.assign(
+        self.value.assign(region, offset, region.word_rlc(value))?;
+        self.value_prev
+            .assign(region, offset, region.word_rlc(value_prev))?;
+        self.original_value
+            .assign(region, offset, region.word_rlc(original_value))?;
+        self.value_prev_is_zero_gadget
+            .assign_value(region, offset, region.word_rlc(value_prev))?;
+        self.value_is_zero_gadget
+            .assign_value(region, offset, region.word_rlc(value))?;
+        self.original_is_zero_gadget.assign_value(
             region,
             offset,
-            Word::random_linear_combine(original_value.to_le_bytes(), randomness),
+            region.word_rlc(original_value),
         )?;
-        self.original_eq_value_gadget.assign(
+        self.original_eq_value_gadget.assign_value(
             region,
             offset,
-            Word::random_linear_combine(original_value.to_le_bytes(), randomness),
-            Word::random_linear_combine(value.to_le_bytes(), randomness),
+            region.word_rlc(original_value),
+            region.word_rlc(value),
         )?;
-        self.prev_eq_value_gadget.assign(
+        self.prev_eq_value_gadget.assign_value(
             region,
             offset,
-            Word::random_linear_combine(value_prev.to_le_bytes(), randomness),
-            Word::random_linear_combine(value.to_le_bytes(), randomness),
+            region.word_rlc(value_prev),
+            region.word_rlc(value),
         )?;
-        self.original_eq_prev_gadget.assign(
+        self.original_eq_prev_gadget.assign_value(
             region,
             offset,
-            Word::random_linear_combine(original_value.to_le_bytes(), randomness),
-            Word::random_linear_combine(value_prev.to_le_bytes(), randomness),
+            region.word_rlc(original_value),
+            region.word_rlc(value_prev),
         )?;
         debug_assert_eq!(
             calc_expected_tx_refund(tx_refund_old, value, value_prev, original_value),
@@ -5,14 +5,14 @@ use crate::{
         util::{
             common_gadget::SameContextGadget,
             constraint_builder::{ConstraintBuilder, StepStateTransition, Transition::Delta},
-            CachedRegion, Cell, Word,
+            CachedRegion, Cell,
         },
         witness::{Block, Call, ExecStep, Transaction},
     },
     util::Expr,
 };
-use eth_types::{evm_types::OpcodeId, Field, ToLittleEndian};
-use halo2_proofs::{circuit::Value, plonk::Error};
+use eth_types::{evm_types::OpcodeId, Field};
+use halo2_proofs::plonk::Error;
 
 #[derive(Clone, Debug)]
 pub(crate) struct SwapGadget<F> {
@@ -28,7 +28,7 @@ impl<F: Field> ExecutionGadget<F> for SwapGadget<F> {
     fn configure(cb: &mut ConstraintBuilder<F>) -> Self {
         let opcode = cb.query_cell();
 
-        let values = [cb.query_cell(), cb.query_cell()];
+        let values = [cb.query_cell_phase2(), cb.query_cell_phase2()];
 
         // The stack index we have to peek, deduced from the 'x' value of
         // 'swapx' The offset starts at 1 for SWAP1
@@ -74,14 +74,7 @@ impl<F: Field> ExecutionGadget<F> for SwapGadget<F> {
                 .map(|idx| block.rws[idx].stack_value())
                 .iter(),
         ) {
-            cell.assign(
-                region,
-                offset,
-                Value::known(Word::random_linear_combine(
-                    value.to_le_bytes(),
-                    block.randomness,
-                )),
-            )?;
+            cell.assign(region, offset, region.word_rlc(value))?;
         }
 
         Ok(())
@@ -1,11 +1,15 @@
 use super::table::Table;
 
 // Step dimension
-pub(crate) const STEP_WIDTH: usize = 128;
+pub(crate) const STEP_WIDTH: usize = 132;
 /// Step height
 pub const MAX_STEP_HEIGHT: usize = 21;
 pub(crate) const N_CELLS_STEP_STATE: usize = 11;
 
+/// Number of columns of storage cells in the second phase, which hold the
+/// values computed from the challenges, like random linear combinations.
+pub(crate) const N_PHASE2_COLUMNS: usize = 4;
+
 /// Lookups done per row.
 pub(crate) const LOOKUP_CONFIG: &[(Table, usize)] = &[
     (Table::Fixed, 8),
@@ -2,16 +2,15 @@ use super::util::{CachedRegion, CellManager, CellType};
 use crate::{
     evm_circuit::{
         param::{MAX_STEP_HEIGHT, STEP_WIDTH},
-        util::{Cell, RandomLinearCombination},
+        util::Cell,
         witness::{Block, Call, ExecStep, Transaction},
     },
     util::Expr,
 };
 use bus_mapping::evm::OpcodeId;
-use eth_types::ToLittleEndian;
 use halo2_proofs::{
     arithmetic::FieldExt,
-    circuit::Value,
+    circuit::{AssignedCell, Value},
     plonk::{Advice, Column, ConstraintSystem, Error, Expression},
 };
 use std::iter;
@@ -337,8 +336,8 @@ pub(crate) struct DynamicSelectorHalf<F> {
 
 impl<F: FieldExt> DynamicSelectorHalf<F> {
     pub(crate) fn new(cell_manager: &mut CellManager<F>, count: usize) -> Self {
-        let target_pairs = cell_manager.query_cells(CellType::Storage, (count + 1) / 2);
-        let target_odd = cell_manager.query_cell(CellType::Storage);
+        let target_pairs = cell_manager.query_cells(CellType::StoragePhase1, (count + 1) / 2);
+        let target_odd = cell_manager.query_cell(CellType::StoragePhase1);
         Self {
             count,
             target_pairs,
@@ -393,31 +392,19 @@ impl<F: FieldExt> DynamicSelectorHalf<F> {
             .expect("Select some Targets")
     }
 
-    pub(crate) fn assign(
-        &self,
-        region: &mut CachedRegion<'_, '_, F>,
-        offset: usize,
-        target: usize,
-    ) -> Result<(), Error> {
+    /// Return the cells of the selector: `target_odd` followed by
+    /// `target_pairs`.
+    pub(crate) fn cells(&self) -> impl Iterator<Item = &Cell<F>> {
+        iter::once(&self.target_odd).chain(&self.target_pairs)
+    }
+
+    /// Return the values of [`DynamicSelectorHalf::cells`] for `target`.
+    pub(crate) fn values(count: usize, target: usize) -> impl Iterator<Item = F> {
         let odd = target % 2 == 1;
         let pair_index = target / 2;
-        self.target_odd.assign(
-            region,
-            offset,
-            Value::known(if odd { F::one() } else { F::zero() }),
-        )?;
-        for (index, cell) in self.target_pairs.iter().enumerate() {
-            cell.assign(
-                region,
-                offset,
-                Value::known(if index == pair_index {
-                    F::one()
-                } else {
-                    F::zero()
-                }),
-            )?;
-        }
-        Ok(())
+        iter::once(odd)
+            .chain((0..(count + 1) / 2).map(move |index| index == pair_index))
+            .map(|bit| if bit { F::one() } else { F::zero() })
     }
 }
 
@@ -455,6 +442,49 @@ pub(crate) struct StepState<F> {
     pub(crate) log_id: Cell<F>,
 }
 
+impl<F: FieldExt> StepState<F> {
+    /// Return the cells of the fields exposed as public inputs at the
+    /// boundaries of a chunk of a block, to link consecutive chunks: the cells
+    /// of the execution state selector followed by the other fields.  The code
+    /// hash, which is an RLC with the challenge of the proof, is not exposed.
+    pub(crate) fn boundary_cells(&self) -> Vec<&Cell<F>> {
+        let fields = [
+            &self.rw_counter,
+            &self.call_id,
+            &self.is_root,
+            &self.is_create,
+            &self.program_counter,
+            &self.stack_pointer,
+            &self.gas_left,
+            &self.memory_word_size,
+            &self.reversible_write_counter,
+            &self.log_id,
+        ];
+        self.execution_state.cells().chain(fields).collect()
+    }
+
+    /// Return the values of the cells of [`StepState::boundary_cells`] for
+    /// `step` of `call`.
+    pub(crate) fn boundary_values(call: &Call, step: &ExecStep) -> Vec<F> {
+        let fields = [
+            step.rw_counter as u64,
+            call.id as u64,
+            call.is_root as u64,
+            call.is_create as u64,
+            step.program_counter,
+            step.stack_pointer as u64,
+            step.gas_left,
+            step.memory_word_size(),
+            step.reversible_write_counter as u64,
+            step.log_id as u64,
+        ]
+        .map(F::from);
+        DynamicSelectorHalf::values(ExecutionState::amount(), step.execution_state as usize)
+            .chain(fields)
+            .collect()
+    }
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct Step<F> {
     pub(crate) state: StepState<F>,
@@ -474,17 +504,17 @@ impl<F: FieldExt> Step<F> {
                     &mut cell_manager,
                     ExecutionState::amount(),
                 ),
-                rw_counter: cell_manager.query_cell(CellType::Storage),
-                call_id: cell_manager.query_cell(CellType::Storage),
-                is_root: cell_manager.query_cell(CellType::Storage),
-                is_create: cell_manager.query_cell(CellType::Storage),
-                code_hash: cell_manager.query_cell(CellType::Storage),
-                program_counter: cell_manager.query_cell(CellType::Storage),
-                stack_pointer: cell_manager.query_cell(CellType::Storage),
-                gas_left: cell_manager.query_cell(CellType::Storage),
-                memory_word_size: cell_manager.query_cell(CellType::Storage),
-                reversible_write_counter: cell_manager.query_cell(CellType::Storage),
-                log_id: cell_manager.query_cell(CellType::Storage),
+                rw_counter: cell_manager.query_cell(CellType::StoragePhase1),
+                call_id: cell_manager.query_cell(CellType::StoragePhase1),
+                is_root: cell_manager.query_cell(CellType::StoragePhase1),
+                is_create: cell_manager.query_cell(CellType::StoragePhase1),
+                code_hash: cell_manager.query_cell(CellType::StoragePhase2),
+                program_counter: cell_manager.query_cell(CellType::StoragePhase1),
+                stack_pointer: cell_manager.query_cell(CellType::StoragePhase1),
+                gas_left: cell_manager.query_cell(CellType::StoragePhase1),
+                memory_word_size: cell_manager.query_cell(CellType::StoragePhase1),
+                reversible_write_counter: cell_manager.query_cell(CellType::StoragePhase1),
+                log_id: cell_manager.query_cell(CellType::StoragePhase1),
             }
         };
         Self {
@@ -502,68 +532,25 @@ impl<F: FieldExt> Step<F> {
             .selector(execution_states.into_iter().map(|s| s as usize))
     }
 
+    /// Assign the state of `step`, and return the cells of its fields which
+    /// are exposed at the boundaries of a chunk, see
+    /// [`StepState::boundary_cells`].
     pub(crate) fn assign_exec_step(
         &self,
         region: &mut CachedRegion<'_, '_, F>,
         offset: usize,
-        block: &Block<F>,
+        _: &Block<F>,
         _: &Transaction,
         call: &Call,
         step: &ExecStep,
-    ) -> Result<(), Error> {
-        self.state
-            .execution_state
-            .assign(region, offset, step.execution_state as usize)?;
-        self.state.rw_counter.assign(
-            region,
-            offset,
-            Value::known(F::from(step.rw_counter as u64)),
-        )?;
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        let code_hash = region.word_rlc(call.code_hash);
+        self.state.code_hash.assign(region, offset, code_hash)?;
         self.state
-            .call_id
-            .assign(region, offset, Value::known(F::from(call.id as u64)))?;
-        self.state
-            .is_root
-            .assign(region, offset, Value::known(F::from(call.is_root as u64)))?;
-        self.state.is_create.assign(
-            region,
-            offset,
-            Value::known(F::from(call.is_create as u64)),
-        )?;
-        self.state.code_hash.assign(
-            region,
-            offset,
-            Value::known(RandomLinearCombination::random_linear_combine(
-                call.code_hash.to_le_bytes(),
-                block.randomness,
-            )),
-        )?;
-        self.state.program_counter.assign(
-            region,
-            offset,
-            Value::known(F::from(step.program_counter as u64)),
-        )?;
-        self.state.stack_pointer.assign(
-            region,
-            offset,
-            Value::known(F::from(step.stack_pointer as u64)),
-        )?;
-        self.state
-            .gas_left
-            .assign(region, offset, Value::known(F::from(step.gas_left)))?;
-        self.state.memory_word_size.assign(
-            region,
-            offset,
-            Value::known(F::from(step.memory_word_size())),
-        )?;
-        self.state.reversible_write_counter.assign(
-            region,
-            offset,
-            Value::known(F::from(step.reversible_write_counter as u64)),
-        )?;
-        self.state
-            .log_id
-            .assign(region, offset, Value::known(F::from(step.log_id as u64)))?;
-        Ok(())
+            .boundary_cells()
+            .into_iter()
+            .zip(StepState::boundary_values(call, step))
+            .map(|(cell, value)| cell.assign(region, offset, Value::known(value)))
+            .collect()
     }
 }
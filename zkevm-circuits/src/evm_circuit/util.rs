@@ -1,17 +1,18 @@
 use crate::{
     evm_circuit::{
-        param::{LOOKUP_CONFIG, N_BYTES_MEMORY_ADDRESS},
+        param::{LOOKUP_CONFIG, N_BYTES_MEMORY_ADDRESS, N_PHASE2_COLUMNS},
         table::Table,
     },
-    util::{query_expression, Expr},
+    util::{query_expression, Challenges, Expr},
 };
-use eth_types::U256;
+use eth_types::{ToLittleEndian, U256};
 use halo2_proofs::{
     arithmetic::FieldExt,
     circuit::{AssignedCell, Region, Value},
     plonk::{Advice, Assigned, Column, ConstraintSystem, Error, Expression, VirtualCells},
     poly::Rotation,
 };
+use keccak256::EMPTY_HASH_LE;
 use std::collections::BTreeMap;
 
 pub(crate) mod common_gadget;
@@ -57,6 +58,10 @@ impl<F: FieldExt> Cell<F> {
             || value,
         )
     }
+
+    pub(crate) fn column(&self) -> Column<Advice> {
+        self.column
+    }
 }
 
 impl<F: FieldExt> Expr<F> for Cell<F> {
@@ -74,7 +79,7 @@ impl<F: FieldExt> Expr<F> for &Cell<F> {
 pub struct CachedRegion<'r, 'b, F: FieldExt> {
     region: &'r mut Region<'b, F>,
     advice: Vec<Vec<F>>,
-    power_of_randomness: [F; 31],
+    challenges: &'r Challenges<Value<F>>,
     width_start: usize,
     height_start: usize,
 }
@@ -83,7 +88,7 @@ impl<'r, 'b, F: FieldExt> CachedRegion<'r, 'b, F> {
     /// New cached region
     pub(crate) fn new(
         region: &'r mut Region<'b, F>,
-        power_of_randomness: [F; 31],
+        challenges: &'r Challenges<Value<F>>,
         width: usize,
         height: usize,
         width_start: usize,
@@ -92,7 +97,7 @@ impl<'r, 'b, F: FieldExt> CachedRegion<'r, 'b, F> {
         Self {
             region,
             advice: vec![vec![F::zero(); height]; width],
-            power_of_randomness,
+            challenges,
             width_start,
             height_start,
         }
@@ -107,23 +112,45 @@ impl<'r, 'b, F: FieldExt> CachedRegion<'r, 'b, F> {
         to: V,
     ) -> Result<AssignedCell<VR, F>, Error>
     where
-        V: FnMut() -> Value<VR> + 'v,
+        V: Fn() -> Value<VR> + 'v,
         for<'vr> Assigned<F>: From<&'vr VR>,
         A: Fn() -> AR,
         AR: Into<String>,
     {
         // Actually set the value
-        let res = self.region.assign_advice(annotation, column, offset, to);
+        let res = self.region.assign_advice(annotation, column, offset, &to);
         // Cache the value
-        if let Result::Ok(cell) = &res {
-            cell.value_field().map(|f| {
+        // Note that the value of the `AssignedCell` is unknown when the column
+        // is in a different phase than the current one, so we call `to` again
+        // here to cache the value.
+        if res.is_ok() {
+            to().map(|v| {
                 self.advice[column.index() - self.width_start][offset - self.height_start] =
-                    f.evaluate();
+                    Assigned::from(&v).evaluate();
             });
         }
         res
     }
 
+    pub fn challenges(&self) -> &Challenges<Value<F>> {
+        self.challenges
+    }
+
+    /// Returns the random linear combination of the word with the `evm_word`
+    /// challenge.
+    pub fn word_rlc(&self, n: U256) -> Value<F> {
+        self.challenges
+            .evm_word()
+            .map(|r| Word::random_linear_combine(n.to_le_bytes(), r))
+    }
+
+    /// Returns the random linear combination of the hash of the empty code.
+    pub fn empty_hash_rlc(&self) -> Value<F> {
+        self.challenges
+            .evm_word()
+            .map(|r| Word::random_linear_combine(*EMPTY_HASH_LE, r))
+    }
+
     pub fn get_fixed(&self, _row_index: usize, _column_index: usize, _rotation: Rotation) -> F {
         unimplemented!("fixed column");
     }
@@ -132,10 +159,6 @@ impl<'r, 'b, F: FieldExt> CachedRegion<'r, 'b, F> {
         self.advice[column_index - self.width_start]
             [(((row_index - self.height_start) as i32) + rotation.0) as usize]
     }
-
-    pub fn get_instance(&self, _row_index: usize, column_index: usize, _rotation: Rotation) -> F {
-        self.power_of_randomness[column_index]
-    }
 }
 
 #[derive(Debug, Clone)]
@@ -154,37 +177,82 @@ impl<F: FieldExt> StoredExpression<F> {
         offset: usize,
     ) -> Result<AssignedCell<F, F>, Error> {
         let value = self.expr.evaluate(
-            &|scalar| scalar,
+            &|scalar| Value::known(scalar),
             &|_| unimplemented!("selector column"),
             &|fixed_query| {
-                region.get_fixed(offset, fixed_query.column_index(), fixed_query.rotation())
+                Value::known(region.get_fixed(
+                    offset,
+                    fixed_query.column_index(),
+                    fixed_query.rotation(),
+                ))
             },
             &|advide_query| {
-                region.get_advice(offset, advide_query.column_index(), advide_query.rotation())
-            },
-            &|instance_query| {
-                region.get_instance(
+                Value::known(region.get_advice(
                     offset,
-                    instance_query.column_index(),
-                    instance_query.rotation(),
-                )
+                    advide_query.column_index(),
+                    advide_query.rotation(),
+                ))
             },
-            &|_| unimplemented!(),
+            &|_| unimplemented!("instance column"),
+            &|challenge| *region.challenges().indexed()[challenge.index()],
             &|a| -a,
             &|a, b| a + b,
             &|a, b| a * b,
-            &|a, scalar| a * scalar,
+            &|a, scalar| a * Value::known(scalar),
         );
-        self.cell.assign(region, offset, Value::known(value))
+        self.cell.assign(region, offset, value)
     }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub(crate) enum CellType {
-    Storage,
+    StoragePhase1,
+    StoragePhase2,
     Lookup(Table),
 }
 
+impl CellType {
+    /// Returns the type of the cells of the column at `index` in the step: the
+    /// lookup columns of [`LOOKUP_CONFIG`] come first, followed by
+    /// [`N_PHASE2_COLUMNS`] storage columns in the second phase, and the rest
+    /// are storage columns in the first phase.
+    pub(crate) fn for_column(index: usize) -> Self {
+        let mut start = 0;
+        for &(table, count) in LOOKUP_CONFIG {
+            if index < start + count {
+                return CellType::Lookup(table);
+            }
+            start += count;
+        }
+        if index < start + N_PHASE2_COLUMNS {
+            CellType::StoragePhase2
+        } else {
+            CellType::StoragePhase1
+        }
+    }
+
+    /// Returns the phase of the column of the cells, starting at 0.  Bytes
+    /// are committed in the first phase because they are the inputs of the
+    /// random linear combinations, and the lookups are compressed with the
+    /// `lookup_input` challenge which is only usable in the third phase.
+    pub(crate) fn phase(&self) -> u8 {
+        match self {
+            CellType::StoragePhase1 | CellType::Lookup(Table::Byte) => 0,
+            CellType::StoragePhase2 => 1,
+            CellType::Lookup(_) => 2,
+        }
+    }
+
+    /// Returns the storage cell type of the phase.
+    pub(crate) fn storage_for_phase(phase: u8) -> Self {
+        match phase {
+            0 => CellType::StoragePhase1,
+            1 => CellType::StoragePhase2,
+            _ => unreachable!("no storage cells in phase {}", phase),
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct CellColumn<F> {
     pub(crate) index: usize,
@@ -225,22 +293,13 @@ impl<F: FieldExt> CellManager<F> {
                 }
                 columns.push(CellColumn {
                     index: c,
-                    cell_type: CellType::Storage,
+                    cell_type: CellType::for_column(c),
                     height: 0,
                     expr: cells[c * height].expr(),
                 });
             }
         });
 
-        // Mark columns used for lookups
-        let mut column_idx = 0;
-        for &(table, count) in LOOKUP_CONFIG {
-            for _ in 0usize..count {
-                columns[column_idx].cell_type = CellType::Lookup(table);
-                column_idx += 1;
-            }
-        }
-
         Self {
             width,
             height,
@@ -264,6 +323,32 @@ impl<F: FieldExt> CellManager<F> {
         self.query_cells(cell_type, 1)[0].clone()
     }
 
+    /// Returns the storage cell type of the first phase in which the value of
+    /// the expression is known, that is, the last phase of the cells and
+    /// challenges it queries.
+    pub(crate) fn storage_for_expr(&self, expr: &Expression<F>) -> CellType {
+        let phase = expr.evaluate(
+            &|_| 0,
+            &|_| 0,
+            &|_| 0,
+            &|advice_query| self.column_phase(advice_query.column_index()),
+            &|_| 0,
+            &|_| 1,
+            &|a| a,
+            &|a, b| a.max(b),
+            &|a, b| a.max(b),
+            &|a, _| a,
+        );
+        CellType::storage_for_phase(phase)
+    }
+
+    fn column_phase(&self, column_index: usize) -> u8 {
+        self.columns
+            .iter()
+            .find(|column| self.cells[column.index * self.height].column.index() == column_index)
+            .map_or(0, |column| column.cell_type.phase())
+    }
+
     fn next_column(&self, cell_type: CellType) -> usize {
         let mut best_index: Option<usize> = None;
         let mut best_height = self.height;
@@ -16,7 +16,7 @@ use crate::{
     util::Expr,
     witness::{Block, Call, ExecStep},
 };
-use eth_types::{Field, ToLittleEndian, ToScalar, U256};
+use eth_types::{Field, ToScalar, U256};
 use halo2_proofs::{
     circuit::Value,
     plonk::{Error, Expression},
@@ -231,14 +231,9 @@ impl<F: Field> RestoreContextGadget<F> {
             )?;
         }
 
-        self.caller_code_hash.assign(
-            region,
-            offset,
-            Value::known(Word::random_linear_combine(
-                caller_code_hash.to_le_bytes(),
-                block.randomness,
-            )),
-        )?;
+        let caller_code_hash = region.word_rlc(caller_code_hash);
+        self.caller_code_hash
+            .assign(region, offset, caller_code_hash)?;
 
         Ok(())
     }
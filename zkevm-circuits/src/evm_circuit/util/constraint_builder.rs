@@ -9,7 +9,7 @@ use crate::{
         AccountFieldTag, BytecodeFieldTag, CallContextFieldTag, RwTableTag, TxContextFieldTag,
         TxLogFieldTag, TxReceiptFieldTag,
     },
-    util::Expr,
+    util::{Challenges, Expr},
 };
 use eth_types::Field;
 use halo2_proofs::{
@@ -242,7 +242,9 @@ pub(crate) struct ConstraintBuilder<'a, F> {
     pub max_degree: usize,
     pub(crate) curr: Step<F>,
     pub(crate) next: Step<F>,
-    power_of_randomness: &'a [Expression<F>; 31],
+    challenges: &'a Challenges<Expression<F>>,
+    power_of_randomness: [Expression<F>; 31],
+    lookup_powers_of_randomness: [Expression<F>; 31],
     execution_state: ExecutionState,
     constraints: Vec<(&'static str, Expression<F>)>,
     constraints_first_step: Vec<(&'static str, Expression<F>)>,
@@ -259,14 +261,16 @@ impl<'a, F: Field> ConstraintBuilder<'a, F> {
     pub(crate) fn new(
         curr: Step<F>,
         next: Step<F>,
-        power_of_randomness: &'a [Expression<F>; 31],
+        challenges: &'a Challenges<Expression<F>>,
         execution_state: ExecutionState,
     ) -> Self {
         Self {
             max_degree: MAX_DEGREE,
             curr,
             next,
-            power_of_randomness,
+            challenges,
+            power_of_randomness: challenges.evm_word_powers_of_randomness(),
+            lookup_powers_of_randomness: challenges.lookup_input_powers_of_randomness(),
             execution_state,
             constraints: Vec::new(),
             constraints_first_step: Vec::new(),
@@ -306,8 +310,12 @@ impl<'a, F: Field> ConstraintBuilder<'a, F> {
         )
     }
 
+    pub(crate) fn challenges(&self) -> &Challenges<Expression<F>> {
+        self.challenges
+    }
+
     pub(crate) fn power_of_randomness(&self) -> &[Expression<F>] {
-        self.power_of_randomness
+        &self.power_of_randomness
     }
 
     pub(crate) fn execution_state(&self) -> ExecutionState {
@@ -353,7 +361,7 @@ impl<'a, F: Field> ConstraintBuilder<'a, F> {
     }
 
     pub(crate) fn query_rlc<const N: usize>(&mut self) -> RandomLinearCombination<F, N> {
-        RandomLinearCombination::<F, N>::new(self.query_bytes(), self.power_of_randomness)
+        RandomLinearCombination::<F, N>::new(self.query_bytes(), &self.power_of_randomness)
     }
 
     pub(crate) fn query_bytes<const N: usize>(&mut self) -> [Cell<F>; N] {
@@ -365,7 +373,20 @@ impl<'a, F: Field> ConstraintBuilder<'a, F> {
     }
 
     pub(crate) fn query_cell(&mut self) -> Cell<F> {
-        self.query_cell_with_type(CellType::Storage)
+        self.query_cell_with_type(CellType::StoragePhase1)
+    }
+
+    /// Returns a cell in the second phase, for values which are computed from
+    /// the challenges, like the random linear combination of a word.
+    pub(crate) fn query_cell_phase2(&mut self) -> Cell<F> {
+        self.query_cell_with_type(CellType::StoragePhase2)
+    }
+
+    /// Returns a cell in the phase in which the value of the expression is
+    /// known.
+    pub(crate) fn query_cell_for_expr(&mut self, expr: &Expression<F>) -> Cell<F> {
+        let cell_type = self.storage_for_expr(expr);
+        self.query_cell_with_type(cell_type)
     }
 
     pub(crate) fn query_cell_with_type(&mut self, cell_type: CellType) -> Cell<F> {
@@ -382,6 +403,12 @@ impl<'a, F: Field> ConstraintBuilder<'a, F> {
         .query_cells(cell_type, count)
     }
 
+    /// Returns the storage cell type of the phase in which the value of the
+    /// expression is known.
+    pub(crate) fn storage_for_expr(&self, expr: &Expression<F>) -> CellType {
+        self.curr.cell_manager.storage_for_expr(expr)
+    }
+
     // Common
 
     pub(crate) fn require_zero(&mut self, name: &'static str, constraint: Expression<F>) {
@@ -911,7 +938,11 @@ impl<'a, F: Field> ConstraintBuilder<'a, F> {
         call_id: Option<Expression<F>>,
         field_tag: CallContextFieldTag,
     ) -> Cell<F> {
-        let cell = self.query_cell();
+        let cell = match field_tag {
+            // Only these two fields hold random linear combinations
+            CallContextFieldTag::CodeHash | CallContextFieldTag::Value => self.query_cell_phase2(),
+            _ => self.query_cell(),
+        };
         self.call_context_lookup(false.expr(), call_id, field_tag, cell.expr());
         cell
     }
@@ -1243,7 +1274,7 @@ impl<'a, F: Field> ConstraintBuilder<'a, F> {
 
         let compressed_expr = self.split_expression(
             "Lookup compression",
-            rlc::expr(&lookup.input_exprs(), self.power_of_randomness),
+            rlc::expr(&lookup.input_exprs(), &self.lookup_powers_of_randomness),
             MAX_DEGREE - IMPLICIT_DEGREE,
         );
         self.store_expression(name, compressed_expr, CellType::Lookup(lookup.table()));
@@ -1329,7 +1360,8 @@ impl<'a, F: Field> ConstraintBuilder<'a, F> {
                             if expr.degree() > max_degree {
                                 self.split_expression(name, expr, max_degree)
                             } else {
-                                self.store_expression(name, expr, CellType::Storage)
+                                let cell_type = self.storage_for_expr(&expr);
+                                self.store_expression(name, expr, cell_type)
                             }
                         };
                         if a.degree() >= b.degree() {
@@ -21,7 +21,7 @@ pub struct IsZeroGadget<F> {
 
 impl<F: Field> IsZeroGadget<F> {
     pub(crate) fn construct(cb: &mut ConstraintBuilder<F>, value: Expression<F>) -> Self {
-        let inverse = cb.query_cell();
+        let inverse = cb.query_cell_for_expr(&value);
 
         let is_zero = 1.expr() - (value.clone() * inverse.expr());
         // when `value != 0` check `inverse = a.invert()`: value * (1 - value *
@@ -55,6 +55,25 @@ impl<F: Field> IsZeroGadget<F> {
             F::zero()
         })
     }
+
+    /// Assigns a value which might be unknown in the current phase, like a
+    /// random linear combination.
+    pub(crate) fn assign_value(
+        &self,
+        region: &mut CachedRegion<'_, '_, F>,
+        offset: usize,
+        value: Value<F>,
+    ) -> Result<Value<F>, Error> {
+        let inverse = value.map(|value| value.invert().unwrap_or(F::zero()));
+        self.inverse.assign(region, offset, inverse)?;
+        Ok(value.map(|value| {
+            if value.is_zero().into() {
+                F::one()
+            } else {
+                F::zero()
+            }
+        }))
+    }
 }
 
 /// Returns `1` when `lhs == rhs`, and returns `0` otherwise.
@@ -87,6 +106,18 @@ impl<F: Field> IsEqualGadget<F> {
     ) -> Result<F, Error> {
         self.is_zero.assign(region, offset, lhs - rhs)
     }
+
+    /// Assigns values which might be unknown in the current phase, like random
+    /// linear combinations.
+    pub(crate) fn assign_value(
+        &self,
+        region: &mut CachedRegion<'_, '_, F>,
+        offset: usize,
+        lhs: Value<F>,
+        rhs: Value<F>,
+    ) -> Result<Value<F>, Error> {
+        self.is_zero.assign_value(region, offset, lhs - rhs)
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -97,8 +128,15 @@ pub struct BatchedIsZeroGadget<F, const N: usize> {
 
 impl<F: Field, const N: usize> BatchedIsZeroGadget<F, N> {
     pub(crate) fn construct(cb: &mut ConstraintBuilder<F>, values: [Expression<F>; N]) -> Self {
-        let is_zero = cb.query_bool();
-        let nonempty_witness = cb.query_cell();
+        // The witnesses are in the latest phase of the values
+        let cell_type = values
+            .iter()
+            .map(|value| cb.storage_for_expr(value))
+            .max()
+            .unwrap();
+        let is_zero = cb.query_cell_with_type(cell_type);
+        cb.require_boolean("Constrain is_zero to be a bool", is_zero.expr());
+        let nonempty_witness = cb.query_cell_with_type(cell_type);
 
         for value in values.iter() {
             cb.require_zero(
@@ -128,17 +166,24 @@ impl<F: Field, const N: usize> BatchedIsZeroGadget<F, N> {
         &self,
         region: &mut CachedRegion<'_, '_, F>,
         offset: usize,
-        values: [F; N],
-    ) -> Result<F, Error> {
-        let is_zero =
-            if let Some(inverse) = values.iter().find_map(|value| Option::from(value.invert())) {
-                self.nonempty_witness
-                    .assign(region, offset, Value::known(inverse))?;
-                F::zero()
-            } else {
+        values: [Value<F>; N],
+    ) -> Result<Value<F>, Error> {
+        let values: Value<Vec<F>> = values.into_iter().collect();
+        let inverse = values.clone().map(|values| {
+            values
+                .iter()
+                .find_map(|value| Option::from(value.invert()))
+                .unwrap_or(F::zero())
+        });
+        let is_zero = values.map(|values| {
+            if values.iter().all(|value| value.is_zero_vartime()) {
                 F::one()
-            };
-        self.is_zero.assign(region, offset, Value::known(is_zero))?;
+            } else {
+                F::zero()
+            }
+        });
+        self.nonempty_witness.assign(region, offset, inverse)?;
+        self.is_zero.assign(region, offset, is_zero)?;
 
         Ok(is_zero)
     }
@@ -1223,7 +1268,6 @@ impl<F: Field> ModGadget<F> {
         a: Word,
         n: Word,
         r: Word,
-        randomness: F,
     ) -> Result<(), Error> {
         let k = if n.is_zero() { Word::zero() } else { a / n };
         let a_or_zero = if n.is_zero() { Word::zero() } else { a };
@@ -1238,12 +1282,9 @@ impl<F: Field> ModGadget<F> {
             .assign(region, offset, F::from(a_or_zero_sum))?;
         self.mul.assign(region, offset, [k, n, r, a_or_zero])?;
         self.lt.assign(region, offset, r, n)?;
-        self.eq.assign(
-            region,
-            offset,
-            util::Word::random_linear_combine(a.to_le_bytes(), randomness),
-            util::Word::random_linear_combine(a_or_zero.to_le_bytes(), randomness),
-        )?;
+        let a_rlc = region.word_rlc(a);
+        let a_or_zero_rlc = region.word_rlc(a_or_zero);
+        self.eq.assign_value(region, offset, a_rlc, a_or_zero_rlc)?;
 
         Ok(())
     }
@@ -6,7 +6,7 @@ use crate::{
             constraint_builder::ConstraintBuilder,
             from_bytes,
             math_gadget::{ConstantDivisionGadget, IsZeroGadget, MinMaxGadget, RangeCheckGadget},
-            select, sum, Cell, MemoryAddress, Word,
+            select, sum, Cell, MemoryAddress,
         },
     },
     util::Expr,
@@ -99,16 +99,13 @@ impl<F: Field> MemoryAddressGadget<F> {
         offset: usize,
         memory_offset: U256,
         memory_length: U256,
-        randomness: F,
     ) -> Result<u64, Error> {
         let memory_offset_bytes = memory_offset.to_le_bytes();
         let memory_length_bytes = memory_length.to_le_bytes();
         let memory_length_is_zero = memory_length.is_zero();
-        self.memory_offset.assign(
-            region,
-            offset,
-            Value::known(Word::random_linear_combine(memory_offset_bytes, randomness)),
-        )?;
+        let memory_offset_rlc = region.word_rlc(memory_offset);
+        self.memory_offset
+            .assign(region, offset, memory_offset_rlc)?;
         self.memory_offset_bytes.assign(
             region,
             offset,
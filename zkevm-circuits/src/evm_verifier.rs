@@ -0,0 +1,275 @@
+//! Generation of an EVM verifier for the proofs of a circuit, like the
+//! [`SuperCircuit`](crate::super_circuit::SuperCircuit) or the
+//! [`AggregationCircuit`](crate::aggregation_circuit::AggregationCircuit), to
+//! settle them on an L1 contract.
+//!
+//! The verifier is generated as Yul source from the verifying key of the
+//! circuit and the parameters used to create it, or as Solidity source with
+//! the same code in the inline assembly of its fallback function, and
+//! compiled with `solc`.  It's called with the [`encode_calldata`] of the
+//! instances and the proof, and reverts when the proof is invalid.  The proofs
+//! must be created with [`gen_evm_proof`], since the verifier derives the
+//! challenges with a keccak256 transcript.
+
+use crate::aggregation_circuit::{BITS, LIMBS};
+use halo2_proofs::{
+    halo2curves::bn256::{Bn256, Fq, Fr, G1Affine},
+    plonk::{create_proof, Circuit, Error, ProvingKey, VerifyingKey},
+    poly::{
+        commitment::ParamsProver,
+        kzg::{
+            commitment::{KZGCommitmentScheme, ParamsKZG},
+            multiopen::ProverGWC,
+        },
+    },
+    transcript::TranscriptWriterBuffer,
+};
+use plonk_verifier::{
+    loader::{evm::EvmLoader, native::NativeLoader},
+    pcs::kzg::{Gwc19, Kzg, KzgDecidingKey, KzgSuccinctVerifyingKey, LimbsEncoding},
+    system::halo2::{compile, transcript::evm::EvmTranscript, Config},
+    verifier::{self, PlonkVerifier},
+};
+use rand::RngCore;
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    rc::Rc,
+};
+
+pub use plonk_verifier::loader::evm::{compile_yul, encode_calldata};
+
+type Pcs = Kzg<Bn256, Gwc19>;
+type Plonk = verifier::Plonk<Pcs, LimbsEncoding<LIMBS, BITS>>;
+
+/// Return the Yul source of the verifier of the proofs of the circuit of
+/// `vk`, with `num_instance` instances in each instance column.  The
+/// `accumulator_indices` are the positions of the limbs of the accumulator in
+/// the instances of an aggregation circuit, like the ones of
+/// [`AggregationCircuit::accumulator_indices`](crate::aggregation_circuit::AggregationCircuit::accumulator_indices),
+/// whose pairing is then also checked by the verifier.
+pub fn gen_verifier_yul(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    num_instance: Vec<usize>,
+    accumulator_indices: Option<Vec<(usize, usize)>>,
+) -> Result<String, plonk_verifier::Error> {
+    let svk: KzgSuccinctVerifyingKey<G1Affine> = params.get_g()[0].into();
+    let dk: KzgDecidingKey<Bn256> = (params.g2(), params.s_g2()).into();
+    let protocol = compile(
+        params,
+        vk,
+        Config::kzg()
+            .with_num_instance(num_instance.clone())
+            .with_accumulator_indices(accumulator_indices),
+    );
+
+    let loader = EvmLoader::new::<Fq, Fr>();
+    let protocol = protocol.loaded(&loader);
+    let mut transcript = EvmTranscript::<_, Rc<EvmLoader>, _, _>::new(&loader);
+    let instances = transcript.load_instances(num_instance);
+    let proof = Plonk::read_proof(&svk, &protocol, &instances, &mut transcript)?;
+    Plonk::verify(&svk, &dk, &protocol, &instances, &proof)?;
+
+    Ok(loader.yul_code())
+}
+
+/// Return the Solidity source of the verifier of the proofs of the circuit
+/// of `vk`, like [`gen_verifier_yul`] does for its Yul source.  The runtime
+/// code of the Yul verifier is the inline assembly of the fallback function
+/// of the `Verifier` contract, without the `bool` type annotations that
+/// inline assembly doesn't support.
+pub fn gen_verifier_sol(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    num_instance: Vec<usize>,
+    accumulator_indices: Option<Vec<(usize, usize)>>,
+) -> Result<String, plonk_verifier::Error> {
+    let yul = gen_verifier_yul(params, vk, num_instance, accumulator_indices)?;
+    let runtime_code = yul_runtime_code(&yul).ok_or_else(|| {
+        plonk_verifier::Error::AssertionFailure("missing Runtime object".to_string())
+    })?;
+    Ok(format!(
+        "// SPDX-License-Identifier: MIT
+pragma solidity ^0.8.0;
+
+contract Verifier {{
+    fallback() external {{
+        assembly {{
+{}
+        }}
+    }}
+}}
+",
+        runtime_code.replace(":bool", "")
+    ))
+}
+
+/// Return the body of the code block of the `Runtime` object of `yul`.
+fn yul_runtime_code(yul: &str) -> Option<&str> {
+    let object = yul.find("object \"Runtime\"")?;
+    let code = object + yul[object..].find("code")?;
+    let start = code + yul[code..].find('{')? + 1;
+    let mut depth = 1;
+    for (index, c) in yul[start..].char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&yul[start..start + index]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Return the deployment code of the verifier of the proofs of the circuit of
+/// `vk`, like [`gen_verifier_yul`] does for its source.  Requires `solc` in
+/// the `PATH`.
+pub fn gen_verifier(
+    params: &ParamsKZG<Bn256>,
+    vk: &VerifyingKey<G1Affine>,
+    num_instance: Vec<usize>,
+    accumulator_indices: Option<Vec<(usize, usize)>>,
+) -> Result<Vec<u8>, plonk_verifier::Error> {
+    gen_verifier_yul(params, vk, num_instance, accumulator_indices).map(|yul| compile_yul(&yul))
+}
+
+/// Compile the Solidity `source` of a single contract, like the one of
+/// [`gen_verifier_sol`], and return its deployment code.  Requires `solc` in
+/// the `PATH`.
+pub fn compile_solidity(source: &str) -> Vec<u8> {
+    let mut solc = Command::new("solc")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .args(["--bin", "-"])
+        .spawn()
+        .expect("solc should be in the PATH");
+    solc.stdin
+        .take()
+        .unwrap()
+        .write_all(source.as_bytes())
+        .unwrap();
+    let output = solc.wait_with_output().unwrap();
+    assert!(output.status.success(), "solc failed to compile the source");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    hex::decode(stdout.split_ascii_whitespace().last().unwrap()).unwrap()
+}
+
+/// Create a proof of `circuit` that can be verified by its EVM verifier.
+pub fn gen_evm_proof<C: Circuit<Fr>>(
+    params: &ParamsKZG<Bn256>,
+    pk: &ProvingKey<G1Affine>,
+    circuit: C,
+    instances: &[Vec<Fr>],
+    rng: impl RngCore,
+) -> Result<Vec<u8>, Error> {
+    let instance_refs: Vec<&[Fr]> = instances.iter().map(Vec::as_slice).collect();
+    let mut transcript = EvmTranscript::<G1Affine, NativeLoader, _, _>::init(Vec::new());
+    create_proof::<KZGCommitmentScheme<Bn256>, ProverGWC<_>, _, _, _, _>(
+        params,
+        pk,
+        &[circuit],
+        &[&instance_refs],
+        rng,
+        &mut transcript,
+    )?;
+    Ok(transcript.finalize())
+}
+
+#[cfg(test)]
+mod evm_verifier_tests {
+    use super::*;
+    use crate::pi_circuit::{PiCircuit, PublicData};
+    use crate::util::SubCircuit;
+    use eth_types::{GethExecTrace, Word};
+    use halo2_proofs::plonk::{keygen_pk, keygen_vk};
+    use mock::{eth, TestContext, MOCK_ACCOUNTS};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha20Rng;
+
+    /// Trace a call with `input` to an account with `code`.
+    fn trace_call(code: Vec<u8>, input: Vec<u8>) -> GethExecTrace {
+        let ctx = TestContext::<2, 1>::new(
+            None,
+            |accs| {
+                accs[0].address(MOCK_ACCOUNTS[0]).code(code);
+                accs[1].address(MOCK_ACCOUNTS[1]).balance(eth(10));
+            },
+            |mut txs, accs| {
+                txs[0]
+                    .from(accs[1].address)
+                    .to(accs[0].address)
+                    .input(input.into())
+                    .gas(Word::from(10_000_000u64));
+            },
+            |block, _tx| block,
+        )
+        .unwrap();
+        ctx.geth_traces[0].clone()
+    }
+
+    /// Return whether the verifier of `deployment_code` accepts `calldata`.
+    fn evm_verify(deployment_code: Vec<u8>, calldata: Vec<u8>) -> bool {
+        // Calling the deployment code returns the runtime code of the
+        // verifier, without the code size limit of a contract creation.
+        let deployment = trace_call(deployment_code, Vec::new());
+        assert!(!deployment.failed);
+        let runtime_code = hex::decode(deployment.return_value.trim_start_matches("0x")).unwrap();
+        !trace_call(runtime_code, calldata).failed
+    }
+
+    #[test]
+    fn runtime_code_of_yul_object() {
+        let yul = "object \"plonk_verifier\" {
+            code { return(0, datasize(\"Runtime\")) }
+            object \"Runtime\" {
+                code {
+                    let success:bool := true
+                    if not(success) { revert(0, 0) }
+                    return(0, 0)
+                }
+            }
+        }";
+        let runtime_code = yul_runtime_code(yul).unwrap();
+        assert!(runtime_code.trim().starts_with("let success:bool := true"));
+        assert!(runtime_code.trim().ends_with("return(0, 0)"));
+        assert_eq!(yul_runtime_code("object \"Runtime\" { code { "), None);
+    }
+
+    // Requires `solc` in the `PATH` to compile the verifiers.
+    #[cfg_attr(not(feature = "solc"), ignore)]
+    #[test]
+    fn evm_verifier_of_pi_circuit() {
+        let mut rng = ChaCha20Rng::seed_from_u64(2);
+        let k = 13;
+        let params = ParamsKZG::<Bn256>::setup(k, &mut rng);
+        let circuit = PiCircuit::<Fr, 2, 8>::new(PublicData::default());
+        let instances = circuit.instance();
+        let num_instance: Vec<usize> = instances.iter().map(Vec::len).collect();
+
+        let vk = keygen_vk(&params, &circuit).unwrap();
+        let pk = keygen_pk(&params, vk, &circuit).unwrap();
+        let yul_deployment_code =
+            gen_verifier(&params, pk.get_vk(), num_instance.clone(), None).unwrap();
+        let sol_deployment_code =
+            compile_solidity(&gen_verifier_sol(&params, pk.get_vk(), num_instance, None).unwrap());
+        let proof = gen_evm_proof(&params, &pk, circuit, &instances, &mut rng).unwrap();
+
+        let mut wrong_instances = instances.clone();
+        wrong_instances[0][0] += Fr::from(1);
+        for deployment_code in [yul_deployment_code, sol_deployment_code] {
+            assert!(evm_verify(
+                deployment_code.clone(),
+                encode_calldata(&instances, &proof)
+            ));
+            assert!(!evm_verify(
+                deployment_code,
+                encode_calldata(&wrong_instances, &proof)
+            ));
+        }
+    }
+}
@@ -8,14 +8,20 @@ use crate::{
         compose_rlc, get_absorb_positions, into_bits, pack_with_base, to_bytes, NUM_BITS_PER_WORD,
         NUM_WORDS_TO_ABSORB, RATE, RATE_IN_BITS, RHO_MATRIX,
     },
+    mpt_circuit,
     table::KeccakTable,
-    util::Expr,
+    util::{Challenges, Expr, SubCircuit, SubCircuitConfig},
+    witness,
 };
+use bus_mapping::circuit_input_builder::keccak_inputs_tx_circuit;
 use eth_types::Field;
 use gadgets::util::{and, select, sum, xor};
 use halo2_proofs::{
     circuit::{Layouter, Region, SimpleFloorPlanner, Value},
-    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Fixed, TableColumn, VirtualCells},
+    plonk::{
+        Advice, Circuit, Column, ConstraintSystem, Error, Expression, Fixed, SecondPhase,
+        TableColumn, VirtualCells,
+    },
     poly::Rotation,
 };
 use itertools::Itertools;
@@ -41,7 +47,7 @@ fn get_num_bits_per_theta_lookup() -> usize {
     num_bits as usize
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug)]
 struct KeccakRow<F> {
     q_padding: bool,
     q_padding_last: bool,
@@ -49,11 +55,11 @@ struct KeccakRow<F> {
     theta_c: [u8; THETA_C_WIDTH],
     input: [u8; ABSORB_WIDTH_PER_ROW],
     is_paddings: [bool; ABSORB_WIDTH_PER_ROW_BYTES],
-    data_rlcs: [F; ABSORB_WIDTH_PER_ROW_BYTES],
+    data_rlcs: [Value<F>; ABSORB_WIDTH_PER_ROW_BYTES],
     is_final: bool,
     length: usize,
-    data_rlc: F,
-    hash_rlc: F,
+    data_rlc: Value<F>,
+    hash_rlc: Value<F>,
 }
 
 /// KeccakBitConfig
@@ -80,19 +86,48 @@ pub struct KeccakBitConfig<F> {
 /// KeccakBitCircuit
 #[derive(Default)]
 pub struct KeccakBitCircuit<F: Field> {
-    witness: Vec<KeccakRow<F>>,
+    inputs: Vec<Vec<u8>>,
     size: usize,
+    num_rows: usize,
     _marker: PhantomData<F>,
 }
 
-impl<F: Field> KeccakBitCircuit<F> {
-    fn r() -> F {
-        F::from(123456)
+impl<F: Field> SubCircuit<F> for KeccakBitCircuit<F> {
+    type Config = KeccakBitConfig<F>;
+
+    /// The hashes are padded to the `max_keccak_rows` of the circuit params.
+    fn new_from_block(block: &witness::Block<F>) -> Self {
+        KeccakBitCircuit {
+            inputs: keccak_inputs(block).expect("invalid transaction signature or MPT update"),
+            size: 0,
+            num_rows: block.circuit_params.max_keccak_rows,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Make the assignments to the KeccakBitCircuit, including the keccak
+    /// table
+    fn synthesize_sub(
+        &self,
+        config: &Self::Config,
+        challenges: &Challenges<Value<F>>,
+        layouter: &mut impl Layouter<F>,
+    ) -> Result<(), Error> {
+        config.load(layouter)?;
+        config.assign_from_witness(layouter, &self.inputs, self.num_rows, challenges)
+    }
+
+    /// The theta c table takes `(MAX_INPUT_THETA_LOOKUP + 1)^n` rows for `n`
+    /// bits per theta lookup.
+    fn min_num_rows_block(block: &witness::Block<F>) -> usize {
+        get_num_rows_required_block(block)
+            .max(block.circuit_params.max_keccak_rows)
+            .max((MAX_INPUT_THETA_LOOKUP as usize + 1).pow(get_num_bits_per_theta_lookup() as u32))
     }
 }
 
 impl<F: Field> Circuit<F> for KeccakBitCircuit<F> {
-    type Config = KeccakBitConfig<F>;
+    type Config = (KeccakBitConfig<F>, Challenges);
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
@@ -100,17 +135,28 @@ impl<F: Field> Circuit<F> for KeccakBitCircuit<F> {
     }
 
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
-        KeccakBitConfig::configure(meta, KeccakBitCircuit::r())
+        let keccak_table = KeccakTable::construct(meta);
+        let challenges = Challenges::construct(meta);
+        let config = {
+            let challenges = challenges.exprs(meta);
+            KeccakBitConfig::new(
+                meta,
+                KeccakBitConfigArgs {
+                    keccak_table,
+                    challenges,
+                },
+            )
+        };
+        (config, challenges)
     }
 
     fn synthesize(
         &self,
-        config: Self::Config,
+        (config, challenges): Self::Config,
         mut layouter: impl Layouter<F>,
     ) -> Result<(), Error> {
-        config.load(&mut layouter)?;
-        config.assign(&mut layouter, &self.witness)?;
-        Ok(())
+        let challenges = challenges.values(&mut layouter);
+        self.synthesize_sub(&config, &challenges, &mut layouter)
     }
 }
 
@@ -118,8 +164,9 @@ impl<F: Field> KeccakBitCircuit<F> {
     /// Creates a new circuit instance
     pub fn new(size: usize) -> Self {
         KeccakBitCircuit {
-            witness: Vec::new(),
+            inputs: Vec::new(),
             size,
+            num_rows: 0,
             _marker: PhantomData,
         }
     }
@@ -130,14 +177,32 @@ impl<F: Field> KeccakBitCircuit<F> {
         self.size / (NUM_ROUNDS + 1) - 1
     }
 
-    /// Sets the witness using the data to be hashed
+    /// Sets the data to be hashed. The witness is generated at synthesis
+    /// time, once the challenges are known.
     pub fn generate_witness(&mut self, inputs: &[Vec<u8>]) {
-        self.witness = multi_keccak(inputs, KeccakBitCircuit::r());
+        self.inputs = inputs.to_vec();
     }
 }
 
-impl<F: Field> KeccakBitConfig<F> {
-    pub(crate) fn configure(meta: &mut ConstraintSystem<F>, r: F) -> Self {
+/// Circuit configuration arguments
+pub struct KeccakBitConfigArgs<F: Field> {
+    /// KeccakTable
+    pub keccak_table: KeccakTable,
+    /// Challenges
+    pub challenges: Challenges<Expression<F>>,
+}
+
+impl<F: Field> SubCircuitConfig<F> for KeccakBitConfig<F> {
+    type ConfigArgs = KeccakBitConfigArgs<F>;
+
+    /// Return a new KeccakBitConfig
+    fn new(
+        meta: &mut ConstraintSystem<F>,
+        Self::ConfigArgs {
+            keccak_table,
+            challenges,
+        }: Self::ConfigArgs,
+    ) -> Self {
         let num_bits_per_theta_lookup = get_num_bits_per_theta_lookup();
         info!("num_bits_per_theta_lookup: {}", num_bits_per_theta_lookup);
 
@@ -148,7 +213,6 @@ impl<F: Field> KeccakBitConfig<F> {
         let q_padding = meta.fixed_column();
         let q_padding_last = meta.fixed_column();
 
-        let keccak_table = KeccakTable::construct(meta);
         let is_final = keccak_table.is_enabled;
         let length = keccak_table.input_len;
         let data_rlc = keccak_table.input_rlc;
@@ -158,7 +222,7 @@ impl<F: Field> KeccakBitConfig<F> {
         let theta_c = array_init::array_init(|_| meta.advice_column());
         let input = array_init::array_init(|_| meta.advice_column());
         let is_paddings = array_init::array_init(|_| meta.advice_column());
-        let data_rlcs = array_init::array_init(|_| meta.advice_column());
+        let data_rlcs = array_init::array_init(|_| meta.advice_column_in(SecondPhase));
         let round_cst = array_init::array_init(|_| meta.fixed_column());
 
         let mut theta_c_table = Vec::new();
@@ -375,7 +439,7 @@ impl<F: Field> KeccakBitConfig<F> {
                 .map(|a| to_bytes::expr(&a[0]))
                 .take(4)
                 .concat();
-            let rlc = compose_rlc::expr(&hash_bytes, r);
+            let rlc = compose_rlc::expr(&hash_bytes, challenges.evm_word());
             cb.condition(start_new_hash(meta, Rotation::cur()), |cb| {
                 cb.require_equal(
                     "hash rlc check",
@@ -543,7 +607,7 @@ impl<F: Field> KeccakBitConfig<F> {
                     new_data_rlc = select::expr(
                         meta.query_advice(*is_padding, Rotation::cur()),
                         new_data_rlc.clone(),
-                        new_data_rlc.clone() * r + byte.clone(),
+                        new_data_rlc.clone() * challenges.keccak_input() + byte.clone(),
                     );
                     if idx < data_rlcs.len() - 1 {
                         let next_data_rlc = meta.query_advice(data_rlcs[idx + 1], Rotation::cur());
@@ -598,14 +662,25 @@ impl<F: Field> KeccakBitConfig<F> {
             _marker: PhantomData,
         }
     }
-    /// Sets the witness using the data to be hashed
+}
+
+impl<F: Field> KeccakBitConfig<F> {
+    /// Sets the witness using the data to be hashed, padded with the hashes
+    /// of empty inputs up to `num_rows` rows (no padding when 0).
     pub fn assign_from_witness(
         &self,
         layouter: &mut impl Layouter<F>,
         inputs: &[Vec<u8>],
-        r: F,
+        num_rows: usize,
+        challenges: &Challenges<Value<F>>,
     ) -> Result<(), Error> {
-        let witness = multi_keccak(inputs, r);
+        let mut witness = multi_keccak(inputs, challenges);
+        // The hash of an empty input takes a single keccak_f, and the fixed
+        // columns of a keccak_f don't depend on its input, so the fixed
+        // columns only depend on `num_rows`.
+        while witness.len() + NUM_ROUNDS + 1 <= num_rows {
+            keccak(&mut witness, &[], challenges);
+        }
         self.assign(layouter, &witness)
     }
 
@@ -658,9 +733,9 @@ impl<F: Field> KeccakBitConfig<F> {
             region,
             offset,
             [
-                F::from(row.is_final),
+                Value::known(F::from(row.is_final)),
                 row.data_rlc,
-                F::from(row.length as u64),
+                Value::known(F::from(row.length as u64)),
                 row.hash_rlc,
             ],
         )?;
@@ -717,7 +792,7 @@ impl<F: Field> KeccakBitConfig<F> {
                 || format!("assign padding selector {} {}", idx, offset),
                 *column,
                 offset,
-                || Value::known(*data_rlc),
+                || *data_rlc,
             )?;
         }
 
@@ -771,7 +846,7 @@ impl<F: Field> KeccakBitConfig<F> {
     }
 }
 
-fn keccak<F: Field>(rows: &mut Vec<KeccakRow<F>>, bytes: &[u8], r: F) {
+fn keccak<F: Field>(rows: &mut Vec<KeccakRow<F>>, bytes: &[u8], challenges: &Challenges<Value<F>>) {
     let mut bits = into_bits(bytes);
     let mut s = [[[0u8; NUM_BITS_PER_WORD]; 5]; 5];
     let absorb_positions = get_absorb_positions();
@@ -785,7 +860,7 @@ fn keccak<F: Field>(rows: &mut Vec<KeccakRow<F>>, bytes: &[u8], r: F) {
     bits.push(1);
 
     let mut length = 0usize;
-    let mut data_rlc = F::zero();
+    let mut data_rlc = Value::known(F::zero());
     let chunks = bits.chunks(RATE_IN_BITS);
     let num_chunks = chunks.len();
     for (idx, chunk) in chunks.enumerate() {
@@ -855,7 +930,7 @@ fn keccak<F: Field>(rows: &mut Vec<KeccakRow<F>>, bytes: &[u8], r: F) {
 
             // Padding/Length/Data rlc
             let mut is_paddings = [false; ABSORB_WIDTH_PER_ROW_BYTES];
-            let mut data_rlcs = [F::zero(); ABSORB_WIDTH_PER_ROW_BYTES];
+            let mut data_rlcs = [Value::known(F::zero()); ABSORB_WIDTH_PER_ROW_BYTES];
             if round < NUM_WORDS_TO_ABSORB {
                 for (padding_idx, is_padding) in is_paddings.iter_mut().enumerate() {
                     let byte_idx = round * 8 + padding_idx;
@@ -873,7 +948,7 @@ fn keccak<F: Field>(rows: &mut Vec<KeccakRow<F>>, bytes: &[u8], r: F) {
                 {
                     if !*padding {
                         let byte_value: F = pack_with_base(byte_bits, 2);
-                        data_rlc = data_rlc * r + byte_value;
+                        data_rlc = data_rlc * challenges.keccak_input() + Value::known(byte_value);
                     }
                     if idx < data_rlcs.len() - 1 {
                         data_rlcs[idx + 1] = data_rlc;
@@ -892,9 +967,11 @@ fn keccak<F: Field>(rows: &mut Vec<KeccakRow<F>>, bytes: &[u8], r: F) {
                     .map(|a| to_bytes::value(&a[0]))
                     .take(4)
                     .concat();
-                rlc::value(&hash_bytes, r)
+                challenges
+                    .evm_word()
+                    .map(|challenge| rlc::value(&hash_bytes, challenge))
             } else {
-                F::zero()
+                Value::known(F::zero())
             };
 
             rows.push(KeccakRow {
@@ -962,7 +1039,80 @@ fn keccak<F: Field>(rows: &mut Vec<KeccakRow<F>>, bytes: &[u8], r: F) {
     debug!("data rlc: {:x?}", data_rlc);
 }
 
-fn multi_keccak<F: Field>(bytes: &[Vec<u8>], r: F) -> Vec<KeccakRow<F>> {
+/// Number of rows used by the Keccak circuit to hash inputs of the given
+/// lengths.
+pub fn get_num_rows_required(input_lens: impl IntoIterator<Item = usize>) -> usize {
+    // A keccak_f is done for each full block of RATE bytes of an input, and
+    // one more for its last block with the padding.
+    1 + input_lens
+        .into_iter()
+        .map(|len| (len / RATE + 1) * (NUM_ROUNDS + 1))
+        .sum::<usize>()
+}
+
+/// Return the inputs hashed to prove `block`: the public keys and the signing
+/// payloads of the transactions for the Tx circuit, the bytecodes for the
+/// Bytecode circuit and the SHA3 inputs for the EVM circuit.
+pub fn keccak_inputs<F: Field>(
+    block: &witness::Block<F>,
+) -> Result<Vec<Vec<u8>>, bus_mapping::Error> {
+    let mut inputs = keccak_inputs_tx_circuit(&block.eth_txs, block.context.chain_id().as_u64())?;
+    inputs.extend(
+        block
+            .bytecodes
+            .values()
+            .map(|bytecode| bytecode.bytes.clone()),
+    );
+    inputs.extend_from_slice(&block.sha3_inputs);
+    inputs.extend(mpt_keccak_inputs(block)?);
+    Ok(inputs)
+}
+
+/// Inputs hashed by the MPT circuit.  The updates without trie nodes, like the
+/// mocked ones, can't be proved by the MPT circuit, so they have no inputs.
+fn mpt_keccak_inputs<F: Field>(
+    block: &witness::Block<F>,
+) -> Result<Vec<Vec<u8>>, bus_mapping::Error> {
+    if !block.mpt_updates.has_proofs() {
+        return Ok(vec![]);
+    }
+    mpt_circuit::keccak_inputs(&block.mpt_updates).map_err(bus_mapping::Error::EthTypeError)
+}
+
+/// Number of rows used by the Keccak circuit to hash the [`keccak_inputs`] of
+/// `block`, without recovering the public keys of the signatures.
+pub(crate) fn get_num_rows_required_block<F: Field>(block: &witness::Block<F>) -> usize {
+    let chain_id = block.context.chain_id().as_u64();
+    // The public key of each signature, plus the one of the padding signature
+    get_num_rows_required(
+        std::iter::repeat(64)
+            .take(block.eth_txs.len() + 1)
+            .chain(
+                block
+                    .eth_txs
+                    .iter()
+                    .map(|tx| tx.sign_payload(chain_id).len()),
+            )
+            .chain(
+                block
+                    .bytecodes
+                    .values()
+                    .map(|bytecode| bytecode.bytes.len()),
+            )
+            .chain(block.sha3_inputs.iter().map(Vec::len))
+            .chain(
+                mpt_keccak_inputs(block)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|input| input.len()),
+            ),
+    )
+}
+
+fn multi_keccak<F: Field>(
+    bytes: &[Vec<u8>],
+    challenges: &Challenges<Value<F>>,
+) -> Vec<KeccakRow<F>> {
     // Dummy first row so that the initial data can be absorbed
     // The initial data doesn't really matter, `is_final` just needs to be disabled.
     let mut rows: Vec<KeccakRow<F>> = vec![KeccakRow {
@@ -972,15 +1122,15 @@ fn multi_keccak<F: Field>(bytes: &[Vec<u8>], r: F) -> Vec<KeccakRow<F>> {
         theta_c: [0u8; THETA_C_WIDTH],
         input: [0u8; ABSORB_WIDTH_PER_ROW],
         is_paddings: [false; ABSORB_WIDTH_PER_ROW_BYTES],
-        data_rlcs: [F::zero(); ABSORB_WIDTH_PER_ROW_BYTES],
+        data_rlcs: [Value::known(F::zero()); ABSORB_WIDTH_PER_ROW_BYTES],
         is_final: false,
         length: 0usize,
-        data_rlc: F::zero(),
-        hash_rlc: F::zero(),
+        data_rlc: Value::known(F::zero()),
+        hash_rlc: Value::known(F::zero()),
     }];
     // Actual keccaks
     for bytes in bytes {
-        keccak(&mut rows, bytes, r);
+        keccak(&mut rows, bytes, challenges);
     }
     rows
 }
@@ -978,7 +978,7 @@ impl<F: Field> KeccakPackedConfig<F> {
                     ));
                 }
             }
-            let rlc = compose_rlc::expr(&hash_bytes, r);
+            let rlc = compose_rlc::expr(&hash_bytes, Expression::Constant(r));
             cb.condition(start_new_hash, |cb| {
                 cb.require_equal(
                     "hash rlc check",
@@ -16,10 +16,13 @@
 #![deny(unsafe_code)]
 #![deny(clippy::debug_assert_with_mut_call)]
 
+pub mod aggregation_circuit;
 pub mod bytecode_circuit;
 pub mod copy_circuit;
 pub mod evm_circuit;
+pub mod evm_verifier;
 pub mod keccak_circuit;
+pub mod mpt_circuit;
 pub mod pi_circuit;
 pub mod state_circuit;
 pub mod super_circuit;
@@ -0,0 +1,2262 @@
+//! The MPT circuit implementation.
+//!
+//! The MPT circuit proves the rows of the [`MptTable`]: for each update, that
+//! the state trie with root `old_root` maps the account field or the storage
+//! slot to `old_value`, and that setting it to `new_value` gives the state
+//! trie with root `new_root`.  The updates are chained, so that the `old_root`
+//! of each update is the `new_root` of the previous one.
+//!
+//! Each update is witnessed by the trie nodes on the paths to the account and
+//! to the storage slot before and after the update, in the format of
+//! `eth_getProof`.  The update is laid out one byte per row, in sections:
+//! - the address and the nibbles of its hash, which is its key in the state
+//!   trie,
+//! - for storage updates, the storage slot and the nibbles of its hash,
+//! - the nodes on the path to the account in the old state trie, and for
+//!   storage updates the nodes on the path to the slot in the old storage trie,
+//! - the same paths in the new tries.
+//!
+//! Every node is hashed with a lookup to the keccak table and linked to its
+//! parent, and its bytes are parsed as RLP to find the key nibbles, the child
+//! on the path and the values in the leaf.  Every node of a new path must be
+//! equal to the node at the same depth in the old path, except for the child on
+//! the path and the updated value, so that nothing else can change in the
+//! tries.  The nodes that are created by the update must be canonically
+//! encoded.
+//!
+//! # Scope
+//!
+//! The circuit only proves the updates that keep the nodes on the path above
+//! the updated leaf:
+//! - changing the value of an existing account field or storage slot,
+//! - proving that an account or a slot doesn't exist, when the path ends in an
+//!   empty branch child or in a leaf with another key,
+//! - inserting a leaf in an empty branch child or in an empty storage trie,
+//! - removing a leaf from a branch that keeps at least two children, or from a
+//!   storage trie with a single slot.
+//!
+//! The other updates are not proved, and building the witness of a block with
+//! any of them fails with [`eth_types::Error::UnsupportedMptUpdate`]:
+//! - inserting a key next to an existing leaf, which splits it into a branch
+//!   and possibly an extension node,
+//! - removing a key from a branch with two children, which merges the branch
+//!   with the remaining child,
+//! - paths that diverge from the key in an extension node,
+//! - nodes shorter than 32 bytes, which are embedded in their parent.
+//!
+//! Most blocks of a real chain insert or remove keys next to existing leaves,
+//! so they can't be proved until these updates are supported.
+mod node;
+#[cfg(test)]
+mod test;
+
+use crate::{
+    evm_circuit::util::{constraint_builder::BaseConstraintBuilder, not, rlc, select, sum},
+    table::{DynamicTableColumns, KeccakTable, MptTable},
+    util::{Challenges, Expr, SubCircuit, SubCircuitConfig},
+    witness::{self, MptUpdates},
+};
+use eth_types::{mpt::EMPTY_TRIE_HASH, Field};
+use ethers_core::utils::keccak256;
+use gadgets::is_zero::{IsZeroChip, IsZeroConfig, IsZeroInstruction};
+use halo2_proofs::{
+    circuit::{Layouter, Region, SimpleFloorPlanner, Value},
+    plonk::{
+        Advice, Circuit, Column, ConstraintSystem, Error, Expression, Fixed, SecondPhase, Selector,
+        VirtualCells,
+    },
+    poly::Rotation,
+};
+use keccak256::EMPTY_HASH;
+use node::{ByteRow, ItemTag, Node, NodeKind, Section, UpdateNodes};
+use std::{array, marker::PhantomData};
+
+const N_SECTIONS: usize = 6;
+const N_PROOF_TYPES: usize = 6;
+const N_ITEM_TAGS: usize = 7;
+const ITEM_TAGS: [ItemTag; N_ITEM_TAGS] = [
+    ItemTag::Path,
+    ItemTag::Child,
+    ItemTag::Value,
+    ItemTag::Nonce,
+    ItemTag::Balance,
+    ItemTag::StorageRoot,
+    ItemTag::CodeHash,
+];
+
+/// Config for MptCircuit
+#[derive(Clone, Debug)]
+pub struct MptCircuitConfig<F> {
+    minimum_rows: usize,
+    q_enable: Column<Fixed>,
+    q_first: Column<Fixed>,
+    q_last: Selector,
+    /// byte, hi, lo, is_zero, is_short, is_long, is_list of every byte, with an
+    /// enable column first
+    byte_table: [Column<Fixed>; 8],
+    pub(crate) mpt_table: MptTable,
+    pub(crate) keccak_table: KeccakTable,
+
+    // Columns that are constant in the rows of an update
+    update_index: Column<Advice>,
+    proof_types: [Column<Advice>; N_PROOF_TYPES],
+    old_exists: Column<Advice>,
+    new_exists: Column<Advice>,
+    old_len: Column<Advice>,
+    new_len: Column<Advice>,
+    address_hash: Column<Advice>,
+    slot_hash: Column<Advice>,
+
+    // Columns that are constant in the rows of a node
+    sections: [Column<Advice>; N_SECTIONS],
+    is_preimage: Column<Advice>,
+    is_key: Column<Advice>,
+    is_branch: Column<Advice>,
+    is_extension: Column<Advice>,
+    is_leaf: Column<Advice>,
+    padding: Column<Advice>,
+    depth: Column<Advice>,
+    node_len: Column<Advice>,
+    node_hash: Column<Advice>,
+    child_hash: Column<Advice>,
+    target_index: Column<Advice>,
+    child_is_empty: Column<Advice>,
+    is_root: Column<Advice>,
+    is_last: Column<Advice>,
+    is_inserted: Column<Advice>,
+
+    // Columns of each byte
+    byte: Column<Advice>,
+    hi: Column<Advice>,
+    lo: Column<Advice>,
+    is_zero: Column<Advice>,
+    is_short: Column<Advice>,
+    is_long: Column<Advice>,
+    is_list: Column<Advice>,
+    index: Column<Advice>,
+    is_final: Column<Advice>,
+    is_node_start: Column<Advice>,
+    hash_input_rlc: Column<Advice>,
+    is_header: Column<Advice>,
+    is_hdr_start: Column<Advice>,
+    hdr_rem: Column<Advice>,
+    hdr_len: Column<Advice>,
+    hdr_count: Column<Advice>,
+    is_item_start: Column<Advice>,
+    is_payload: Column<Advice>,
+    item_rem: Column<Advice>,
+    item_count: Column<Advice>,
+    item_tags: [Column<Advice>; N_ITEM_TAGS],
+    child_count: Column<Advice>,
+    is_child_item: Column<Advice>,
+    is_value_item: Column<Advice>,
+    item_rlc: Column<Advice>,
+    value_rlc: Column<Advice>,
+    masked_rlc: Column<Advice>,
+    key_rlc: Column<Advice>,
+    key_pending: Column<Advice>,
+    key_odd: Column<Advice>,
+    key_len: Column<Advice>,
+    is_hp_first: Column<Advice>,
+    key_step_nibble: Column<Advice>,
+    key_step_byte: Column<Advice>,
+    key_nibble: Column<Advice>,
+    item_end: IsZeroConfig<F>,
+    is_target: IsZeroConfig<F>,
+    key_match: IsZeroConfig<F>,
+}
+
+fn cur<F: Field>(meta: &mut VirtualCells<'_, F>, column: Column<Advice>) -> Expression<F> {
+    meta.query_advice(column, Rotation::cur())
+}
+
+fn prev<F: Field>(meta: &mut VirtualCells<'_, F>, column: Column<Advice>) -> Expression<F> {
+    meta.query_advice(column, Rotation::prev())
+}
+
+fn next<F: Field>(meta: &mut VirtualCells<'_, F>, column: Column<Advice>) -> Expression<F> {
+    meta.query_advice(column, Rotation::next())
+}
+
+/// RLC of a hash, as used for the words in the tables
+fn hash_rlc_expr<F: Field>(hash: &[u8; 32], evm_word: Expression<F>) -> Expression<F> {
+    hash.iter().fold(0.expr(), |acc, byte| {
+        acc * evm_word.clone() + (*byte).expr()
+    })
+}
+
+/// Log the reason why the updates can't be proved and fail the synthesis
+fn unprovable(err: eth_types::Error) -> Error {
+    log::error!("MPT Circuit: {}", err);
+    Error::Synthesis
+}
+
+/// Circuit configuration arguments
+pub struct MptCircuitConfigArgs<F: Field> {
+    /// MptTable
+    pub mpt_table: MptTable,
+    /// KeccakTable
+    pub keccak_table: KeccakTable,
+    /// Challenges
+    pub challenges: Challenges<Expression<F>>,
+}
+
+impl<F: Field> SubCircuitConfig<F> for MptCircuitConfig<F> {
+    type ConfigArgs = MptCircuitConfigArgs<F>;
+
+    /// Configure MptCircuit
+    fn new(
+        meta: &mut ConstraintSystem<F>,
+        Self::ConfigArgs {
+            mpt_table,
+            keccak_table,
+            challenges,
+        }: Self::ConfigArgs,
+    ) -> Self {
+        let q_enable = meta.fixed_column();
+        let q_first = meta.fixed_column();
+        let q_last = meta.selector();
+        let byte_table = array::from_fn(|_| meta.fixed_column());
+
+        let [update_index, old_exists, new_exists, old_len, new_len] =
+            array::from_fn(|_| meta.advice_column());
+        let proof_types = array::from_fn(|_| meta.advice_column());
+        let [address_hash, slot_hash] = array::from_fn(|_| meta.advice_column_in(SecondPhase));
+
+        let sections = array::from_fn(|_| meta.advice_column());
+        let [is_preimage, is_key, is_branch, is_extension, is_leaf, padding] =
+            array::from_fn(|_| meta.advice_column());
+        let [depth, node_len, target_index, child_is_empty, is_root, is_last, is_inserted] =
+            array::from_fn(|_| meta.advice_column());
+        let [node_hash, child_hash] = array::from_fn(|_| meta.advice_column_in(SecondPhase));
+
+        let [byte, hi, lo, is_zero, is_short, is_long, is_list] =
+            array::from_fn(|_| meta.advice_column());
+        let [index, is_final, is_node_start] = array::from_fn(|_| meta.advice_column());
+        let [is_header, is_hdr_start, hdr_rem, hdr_len, hdr_count] =
+            array::from_fn(|_| meta.advice_column());
+        let [is_item_start, is_payload, item_rem, item_count, child_count] =
+            array::from_fn(|_| meta.advice_column());
+        let item_tags = array::from_fn(|_| meta.advice_column());
+        let [is_child_item, is_value_item] = array::from_fn(|_| meta.advice_column());
+        let [key_pending, key_odd, key_len, is_hp_first, key_step_nibble, key_step_byte, key_nibble] =
+            array::from_fn(|_| meta.advice_column());
+        let [hash_input_rlc, item_rlc, value_rlc, masked_rlc, key_rlc] =
+            array::from_fn(|_| meta.advice_column_in(SecondPhase));
+        let item_rem_inv = meta.advice_column();
+        let target_inv = meta.advice_column();
+        let key_diff_inv = meta.advice_column_in(SecondPhase);
+
+        let mpt_columns = mpt_table.columns();
+        let [address, storage_key, proof_type, new_root, old_root, new_value, old_value]: [_; 7] =
+            mpt_columns.clone().try_into().unwrap();
+
+        let r_w = challenges.evm_word();
+        let r_k = challenges.keccak_input();
+        let empty_root = hash_rlc_expr(&EMPTY_TRIE_HASH.0, r_w.clone());
+        let empty_code_hash = hash_rlc_expr(&EMPTY_HASH, r_w.clone());
+
+        let item_end = IsZeroChip::configure(
+            meta,
+            |meta| meta.query_fixed(q_enable, Rotation::cur()),
+            |meta| cur(meta, item_rem),
+            item_rem_inv,
+        );
+        let is_target = IsZeroChip::configure(
+            meta,
+            |meta| meta.query_fixed(q_enable, Rotation::cur()),
+            |meta| cur(meta, item_count) - 1.expr() - cur(meta, target_index),
+            target_inv,
+        );
+        let key_match = IsZeroChip::configure(
+            meta,
+            |meta| meta.query_fixed(q_enable, Rotation::cur()),
+            |meta| {
+                cur(meta, key_rlc)
+                    - select::expr(
+                        cur(meta, sections[3]) + cur(meta, sections[5]),
+                        cur(meta, slot_hash),
+                        cur(meta, address_hash),
+                    )
+            },
+            key_diff_inv,
+        );
+
+        let update_columns = [
+            vec![
+                update_index,
+                old_exists,
+                new_exists,
+                old_len,
+                new_len,
+                address_hash,
+                slot_hash,
+            ],
+            proof_types.to_vec(),
+            mpt_columns.clone(),
+        ]
+        .concat();
+        let node_columns = [
+            sections.to_vec(),
+            vec![
+                is_preimage,
+                is_key,
+                is_branch,
+                is_extension,
+                is_leaf,
+                padding,
+                depth,
+                node_len,
+                node_hash,
+                child_hash,
+                target_index,
+                child_is_empty,
+                is_root,
+                is_last,
+                is_inserted,
+            ],
+        ]
+        .concat();
+
+        meta.create_gate("rows", |meta| {
+            let mut cb = BaseConstraintBuilder::default();
+
+            let q_first = meta.query_fixed(q_first, Rotation::cur());
+            let is_node_start_expr = cur(meta, is_node_start);
+            let padding_expr = cur(meta, padding);
+            let is_trie = cur(meta, is_branch) + cur(meta, is_extension) + cur(meta, is_leaf);
+
+            for column in [
+                is_preimage,
+                is_key,
+                is_branch,
+                is_extension,
+                is_leaf,
+                padding,
+                child_is_empty,
+                is_root,
+                is_last,
+                is_inserted,
+                is_final,
+                is_node_start,
+                old_exists,
+                new_exists,
+                is_header,
+                is_hdr_start,
+                is_item_start,
+                is_payload,
+                is_child_item,
+                is_value_item,
+                is_hp_first,
+                key_step_nibble,
+                key_step_byte,
+            ]
+            .into_iter()
+            .chain(sections)
+            .chain(proof_types)
+            .chain(item_tags)
+            {
+                cb.require_boolean("flag is boolean", cur(meta, column));
+            }
+            cb.require_equal(
+                "each row is in a section or is padding",
+                sum::expr(sections.map(|column| cur(meta, column))) + padding_expr.clone(),
+                1.expr(),
+            );
+            cb.require_equal(
+                "each row is in a node or is padding",
+                is_trie.clone() + cur(meta, is_preimage) + cur(meta, is_key) + padding_expr.clone(),
+                1.expr(),
+            );
+            cb.require_equal(
+                "keys are in the first two sections",
+                cur(meta, is_preimage) + cur(meta, is_key),
+                cur(meta, sections[0]) + cur(meta, sections[1]),
+            );
+            cb.require_equal(
+                "a node starts after the final row of the previous one",
+                is_node_start_expr.clone(),
+                q_first.clone() + not::expr(q_first.clone()) * prev(meta, is_final),
+            );
+            cb.require_equal(
+                "index := node_start ? 0 : index_prev + 1",
+                cur(meta, index),
+                not::expr(is_node_start_expr.clone()) * (prev(meta, index) + 1.expr()),
+            );
+            cb.condition(padding_expr.clone(), |cb| {
+                cb.require_equal("padding rows are final", cur(meta, is_final), 1.expr());
+            });
+            cb.condition(
+                cur(meta, is_final) * not::expr(padding_expr.clone()),
+                |cb| {
+                    cb.require_equal(
+                        "index + 1 == node_len on the final row",
+                        cur(meta, index) + 1.expr(),
+                        cur(meta, node_len),
+                    );
+                },
+            );
+            cb.require_equal(
+                "hash_input_rlc := node_start ? byte : hash_input_rlc_prev * r + byte",
+                cur(meta, hash_input_rlc),
+                not::expr(is_node_start_expr.clone()) * prev(meta, hash_input_rlc) * r_k.clone()
+                    + cur(meta, byte),
+            );
+
+            cb.condition(not::expr(is_node_start_expr.clone()), |cb| {
+                for column in node_columns.iter() {
+                    cb.require_equal(
+                        "node columns are constant in a node",
+                        cur(meta, *column),
+                        prev(meta, *column),
+                    );
+                }
+            });
+
+            let is_update_start =
+                is_node_start_expr.clone() * cur(meta, is_preimage) * cur(meta, sections[0]);
+            cb.condition(
+                not::expr(is_update_start.clone()) * not::expr(padding_expr.clone()),
+                |cb| {
+                    for column in update_columns.iter() {
+                        cb.require_equal(
+                            "update columns are constant in an update",
+                            cur(meta, *column),
+                            prev(meta, *column),
+                        );
+                    }
+                },
+            );
+            cb.condition(padding_expr.clone(), |cb| {
+                for column in update_columns.iter().chain(node_columns.iter()) {
+                    if *column != padding {
+                        cb.require_zero("padding rows are empty", cur(meta, *column));
+                    }
+                }
+            });
+            cb.condition(is_update_start, |cb| {
+                cb.require_equal(
+                    "update_index := update_index_prev + 1",
+                    cur(meta, update_index),
+                    not::expr(q_first.clone()) * (prev(meta, update_index) + 1.expr()),
+                );
+                cb.require_zero(
+                    "old_root == new_root_prev",
+                    not::expr(q_first.clone()) * (cur(meta, old_root) - prev(meta, new_root)),
+                );
+            });
+
+            cb.require_zero(
+                "only trie nodes have row kinds and item tags",
+                not::expr(is_trie)
+                    * sum::expr(
+                        [is_header, is_item_start, is_payload]
+                            .into_iter()
+                            .chain(item_tags)
+                            .map(|column| cur(meta, column)),
+                    ),
+            );
+
+            cb.gate(meta.query_fixed(q_enable, Rotation::cur()))
+        });
+
+        meta.create_gate("last row", |meta| {
+            let mut cb = BaseConstraintBuilder::default();
+            cb.require_equal("the last row is padding", cur(meta, padding), 1.expr());
+            cb.gate(meta.query_selector(q_last))
+        });
+
+        meta.create_gate("update", |meta| {
+            let mut cb = BaseConstraintBuilder::default();
+
+            let [f_nonce, f_balance, f_code_hash, f_destructed, f_not_exists, f_storage] =
+                proof_types.map(|column| cur(meta, column));
+            let old_exists = cur(meta, old_exists);
+            let new_exists = cur(meta, new_exists);
+
+            cb.require_equal(
+                "proof type is one of the supported ones",
+                sum::expr(proof_types.map(|column| cur(meta, column))),
+                1.expr(),
+            );
+            cb.require_equal(
+                "proof_type matches its flags",
+                cur(meta, proof_type),
+                sum::expr(
+                    proof_types
+                        .iter()
+                        .enumerate()
+                        .map(|(i, column)| (i + 1).expr() * cur(meta, *column)),
+                ),
+            );
+            cb.require_zero(
+                "storage_key is 0 for account updates",
+                not::expr(f_storage) * cur(meta, storage_key),
+            );
+            cb.require_equal(
+                "the paths only differ by the inserted or removed leaf",
+                cur(meta, new_len) - cur(meta, old_len),
+                new_exists.clone() - old_exists.clone(),
+            );
+            cb.require_zero(
+                "the root doesn't change if the key doesn't exist",
+                not::expr(old_exists.clone())
+                    * not::expr(new_exists.clone())
+                    * (cur(meta, new_root) - cur(meta, old_root)),
+            );
+            cb.condition(f_destructed, |cb| {
+                cb.require_equal("destructed account exists", old_exists.clone(), 1.expr());
+                cb.require_zero("destructed account is removed", new_exists.clone());
+                cb.require_zero("no value for destructed account", cur(meta, old_value));
+                cb.require_zero("no value for destructed account", cur(meta, new_value));
+            });
+            cb.condition(f_not_exists, |cb| {
+                cb.require_zero("account doesn't exist", old_exists.clone());
+                cb.require_zero("account doesn't exist", new_exists.clone());
+                cb.require_zero("no value for missing account", cur(meta, old_value));
+                cb.require_zero("no value for missing account", cur(meta, new_value));
+            });
+            cb.require_zero(
+                "accounts are not removed by field updates",
+                (f_nonce + f_balance + f_code_hash.clone())
+                    * old_exists.clone()
+                    * not::expr(new_exists.clone()),
+            );
+            let default_value = f_code_hash * empty_code_hash.clone();
+            cb.require_zero(
+                "old_value is the default value if the key doesn't exist",
+                not::expr(old_exists) * (cur(meta, old_value) - default_value.clone()),
+            );
+            cb.require_zero(
+                "new_value is the default value if the key doesn't exist",
+                not::expr(new_exists) * (cur(meta, new_value) - default_value),
+            );
+
+            cb.gate(meta.query_fixed(q_enable, Rotation::cur()) * not::expr(cur(meta, padding)))
+        });
+
+        meta.create_gate("section start", |meta| {
+            let mut cb = BaseConstraintBuilder::default();
+
+            let q_first = meta.query_fixed(q_first, Rotation::cur());
+            let section = sections.map(|column| cur(meta, column));
+            let section_prev = sections.map(|column| prev(meta, column));
+            let same_section = not::expr(q_first.clone())
+                * sum::expr(
+                    section
+                        .iter()
+                        .zip(section_prev.iter())
+                        .map(|(cur, prev)| cur.clone() * prev.clone()),
+                );
+            let is_trie = cur(meta, is_branch) + cur(meta, is_extension) + cur(meta, is_leaf);
+            let f_storage = cur(meta, proof_types[5]);
+
+            cb.require_equal(
+                "is_root := trie node that starts a section",
+                cur(meta, is_root),
+                is_trie.clone() * not::expr(same_section.clone()),
+            );
+            cb.condition(q_first, |cb| {
+                cb.require_equal(
+                    "the first row starts an update",
+                    section[0].clone(),
+                    1.expr(),
+                );
+            });
+            cb.condition(same_section.clone(), |cb| {
+                cb.require_equal(
+                    "the hash of a key follows its preimage",
+                    section[0].clone() + section[1].clone(),
+                    cur(meta, is_key) * prev(meta, is_preimage),
+                );
+                cb.require_equal(
+                    "depth := depth_prev + 1",
+                    cur(meta, depth),
+                    is_trie.clone() * (prev(meta, depth) + 1.expr()),
+                );
+                cb.require_zero(
+                    "node_hash == child_hash_prev",
+                    is_trie.clone() * (cur(meta, node_hash) - prev(meta, child_hash)),
+                );
+                cb.require_equal(
+                    "the parent of a node is a branch or an extension",
+                    is_trie.clone() * (prev(meta, is_branch) + prev(meta, is_extension)),
+                    is_trie.clone(),
+                );
+                cb.require_zero(
+                    "the parent of a node doesn't have an empty child",
+                    prev(meta, child_is_empty),
+                );
+                cb.require_zero(
+                    "the child of an extension is a branch",
+                    prev(meta, is_extension) * not::expr(cur(meta, is_branch)),
+                );
+            });
+            cb.condition(not::expr(same_section), |cb| {
+                cb.require_equal(
+                    "key sections start with the preimage",
+                    section[0].clone() + section[1].clone(),
+                    cur(meta, is_preimage),
+                );
+                cb.require_zero("trie sections start at depth 0", cur(meta, depth));
+                cb.require_zero(
+                    "the slot follows the address in storage updates",
+                    section[1].clone() * (2.expr() - section_prev[0].clone() - f_storage.clone()),
+                );
+                cb.require_zero(
+                    "the old account path follows the key preimages",
+                    section[2].clone()
+                        * (section_prev[1].clone() - f_storage.clone() + section_prev[0].clone()
+                            - not::expr(f_storage.clone())),
+                );
+                cb.require_zero(
+                    "the old storage path follows the old account path in storage updates",
+                    section[3].clone() * (2.expr() - section_prev[2].clone() - f_storage.clone()),
+                );
+                cb.require_zero(
+                    "the new account path follows the old paths",
+                    section[4].clone()
+                        * (1.expr() - section_prev[2].clone() - section_prev[3].clone()),
+                );
+                cb.require_zero(
+                    "the new storage path follows the new account path in storage updates",
+                    section[5].clone() * (2.expr() - section_prev[4].clone() - f_storage.clone()),
+                );
+                cb.require_zero(
+                    "the old account path starts at old_root",
+                    section[2].clone() * (cur(meta, node_hash) - cur(meta, old_root)),
+                );
+                cb.require_zero(
+                    "the new account path starts at new_root",
+                    section[4].clone() * (cur(meta, node_hash) - cur(meta, new_root)),
+                );
+                cb.require_zero(
+                    "storage paths start at the storage root of the account",
+                    (section[3].clone() + section[5].clone())
+                        * (cur(meta, node_hash) - prev(meta, child_hash)),
+                );
+                let skips_old_storage =
+                    section[4].clone() * section_prev[2].clone() * f_storage.clone();
+                cb.require_zero(
+                    "the old storage trie is empty if it has no path",
+                    skips_old_storage.clone() * (prev(meta, child_hash) - empty_root.clone()),
+                );
+                cb.require_zero(
+                    "the slot doesn't exist in an empty storage trie",
+                    skips_old_storage.clone() * cur(meta, old_exists),
+                );
+                cb.require_zero(
+                    "the path in an empty storage trie is empty",
+                    skips_old_storage * cur(meta, old_len),
+                );
+            });
+
+            cb.gate(
+                meta.query_fixed(q_enable, Rotation::cur())
+                    * cur(meta, is_node_start)
+                    * not::expr(cur(meta, padding)),
+            )
+        });
+
+        meta.create_gate("section end", |meta| {
+            let mut cb = BaseConstraintBuilder::default();
+
+            let section = sections.map(|column| cur(meta, column));
+            let section_end = not::expr(sum::expr(
+                sections.map(|column| cur(meta, column) * next(meta, column)),
+            ));
+            let f_storage = cur(meta, proof_types[5]);
+            let in_storage = section[3].clone() + section[5].clone();
+            let is_new = section[4].clone() + section[5].clone();
+            let in_target_trie = select::expr(
+                f_storage.clone(),
+                in_storage.clone(),
+                not::expr(in_storage.clone()),
+            );
+            let exists = cur(meta, is_leaf) * key_match.expr();
+
+            cb.require_equal(
+                "is_last := last node of the section",
+                cur(meta, is_last),
+                section_end,
+            );
+            cb.condition(cur(meta, is_last), |cb| {
+                cb.require_equal(
+                    "paths end in a leaf or in an empty branch child",
+                    cur(meta, is_leaf) + cur(meta, is_branch) * cur(meta, child_is_empty),
+                    1.expr(),
+                );
+            });
+            cb.condition(cur(meta, is_last) * in_target_trie.clone(), |cb| {
+                cb.require_equal(
+                    "the key exists iff the path ends in its leaf",
+                    select::expr(is_new.clone(), cur(meta, new_exists), cur(meta, old_exists)),
+                    exists.clone(),
+                );
+                cb.require_equal(
+                    "the path length is the number of nodes",
+                    select::expr(is_new.clone(), cur(meta, new_len), cur(meta, old_len)),
+                    cur(meta, depth) + 1.expr(),
+                );
+            });
+            cb.condition(
+                cur(meta, is_last) * not::expr(in_storage) * f_storage,
+                |cb| {
+                    cb.require_equal("storage updates need an existing account", exists, 1.expr());
+                },
+            );
+            cb.require_equal(
+                "is_inserted := leaf that only exists in the new trie",
+                cur(meta, is_inserted),
+                cur(meta, is_last)
+                    * is_new
+                    * in_target_trie
+                    * not::expr(cur(meta, old_exists))
+                    * cur(meta, new_exists),
+            );
+
+            cb.gate(
+                meta.query_fixed(q_enable, Rotation::cur())
+                    * cur(meta, is_final)
+                    * (cur(meta, is_branch) + cur(meta, is_extension) + cur(meta, is_leaf)),
+            )
+        });
+
+        meta.create_gate("update end", |meta| {
+            let mut cb = BaseConstraintBuilder::default();
+
+            let section = sections.map(|column| cur(meta, column));
+            let skips_new_storage = section[4].clone() * cur(meta, proof_types[5]);
+            cb.require_equal(
+                "updates end with the new paths",
+                section[4].clone() + section[5].clone(),
+                1.expr(),
+            );
+            cb.require_zero(
+                "the new storage trie is empty if it has no path",
+                skips_new_storage.clone() * (cur(meta, child_hash) - empty_root.clone()),
+            );
+            cb.require_zero(
+                "the slot doesn't exist in an empty storage trie",
+                skips_new_storage.clone() * cur(meta, new_exists),
+            );
+            cb.require_zero(
+                "the path in an empty storage trie is empty",
+                skips_new_storage * cur(meta, new_len),
+            );
+
+            cb.gate(
+                meta.query_fixed(q_enable, Rotation::cur())
+                    * cur(meta, is_final)
+                    * not::expr(cur(meta, padding))
+                    * not::expr(section[0].clone())
+                    * (next(meta, sections[0]) + next(meta, padding)),
+            )
+        });
+
+        meta.create_gate("key preimage", |meta| {
+            let mut cb = BaseConstraintBuilder::default();
+
+            let is_slot = cur(meta, sections[1]);
+            let value_rlc_prev = not::expr(cur(meta, is_node_start)) * prev(meta, value_rlc);
+            cb.require_equal(
+                "value_rlc := value_rlc_prev * base + byte",
+                cur(meta, value_rlc),
+                value_rlc_prev * select::expr(is_slot.clone(), r_w.clone(), 256.expr())
+                    + cur(meta, byte),
+            );
+            cb.condition(cur(meta, is_final), |cb| {
+                cb.require_equal(
+                    "addresses have 20 bytes and slots 32",
+                    cur(meta, node_len),
+                    select::expr(is_slot.clone(), 32.expr(), 20.expr()),
+                );
+                cb.require_equal(
+                    "the preimage is the address or the storage key",
+                    cur(meta, value_rlc),
+                    select::expr(is_slot.clone(), cur(meta, storage_key), cur(meta, address)),
+                );
+                cb.require_equal(
+                    "the hash of the preimage is the key",
+                    cur(meta, node_hash),
+                    select::expr(is_slot, cur(meta, slot_hash), cur(meta, address_hash)),
+                );
+            });
+
+            cb.gate(meta.query_fixed(q_enable, Rotation::cur()) * cur(meta, is_preimage))
+        });
+
+        meta.create_gate("key nibbles", |meta| {
+            let mut cb = BaseConstraintBuilder::default();
+
+            let is_node_start = cur(meta, is_node_start);
+            let is_odd = cur(meta, key_odd);
+            let value_rlc_prev = not::expr(is_node_start.clone()) * prev(meta, value_rlc);
+            cb.require_zero("key rows hold nibbles", cur(meta, hi));
+            cb.require_equal(
+                "nibbles alternate between high and low",
+                is_odd.clone(),
+                not::expr(is_node_start) * not::expr(prev(meta, key_odd)),
+            );
+            cb.require_equal(
+                "value_rlc := is_odd ? value_rlc_prev + nibble : value_rlc_prev * r + 16 * nibble",
+                cur(meta, value_rlc),
+                select::expr(
+                    is_odd.clone(),
+                    value_rlc_prev.clone() + cur(meta, byte),
+                    value_rlc_prev * r_w.clone() + 16.expr() * cur(meta, byte),
+                ),
+            );
+            cb.condition(cur(meta, is_final), |cb| {
+                cb.require_equal("keys have 64 nibbles", cur(meta, node_len), 64.expr());
+                cb.require_equal(
+                    "the nibbles are the hash of the preimage",
+                    cur(meta, value_rlc),
+                    select::expr(
+                        cur(meta, sections[1]),
+                        cur(meta, slot_hash),
+                        cur(meta, address_hash),
+                    ),
+                );
+            });
+
+            cb.gate(meta.query_fixed(q_enable, Rotation::cur()) * cur(meta, is_key))
+        });
+
+        meta.create_gate("trie node rlp", |meta| {
+            let mut cb = BaseConstraintBuilder::default();
+
+            let is_node_start = cur(meta, is_node_start);
+            let not_start = not::expr(is_node_start.clone());
+            let is_branch = cur(meta, is_branch);
+            let is_extension = cur(meta, is_extension);
+            let is_leaf = cur(meta, is_leaf);
+            let in_storage = cur(meta, sections[3]) + cur(meta, sections[5]);
+            let is_account_leaf = is_leaf.clone() * not::expr(in_storage.clone());
+            let byte = cur(meta, byte);
+            let is_header = cur(meta, is_header);
+            let is_hdr_start = cur(meta, is_hdr_start);
+            let is_item_start = cur(meta, is_item_start);
+            let is_payload = cur(meta, is_payload);
+            let hdr_rem = cur(meta, hdr_rem);
+            let hdr_rem_prev = prev(meta, hdr_rem);
+            let item_tags_cur = item_tags.map(|column| cur(meta, column));
+            let [is_path, is_child, is_value, is_nonce, is_balance, is_storage_root, is_code_hash] =
+                item_tags_cur.clone();
+            let item_end = item_end.expr();
+            let prev_item_end = 1.expr() - prev(meta, item_rem) * prev(meta, item_rem_inv);
+
+            // Headers have at most 2 length bytes, so hdr_rem is in {0, 1, 2}
+            let prev_hdr_open = not_start.clone()
+                * prev(meta, is_header)
+                * hdr_rem_prev.clone()
+                * (3.expr() - hdr_rem_prev.clone())
+                * Expression::Constant(F::from(2).invert().unwrap());
+            let prev_item_open = not_start.clone()
+                * (prev(meta, is_item_start) + prev(meta, is_payload))
+                * not::expr(prev_item_end);
+            let is_boundary = is_hdr_start.clone() + is_item_start.clone();
+
+            cb.require_equal(
+                "each row is a header, an item start or an item payload",
+                is_header.clone() + is_item_start.clone() + is_payload.clone(),
+                1.expr(),
+            );
+            cb.require_zero(
+                "header starts are header rows",
+                is_hdr_start.clone() * not::expr(is_header.clone()),
+            );
+            cb.require_zero(
+                "header rows are not tagged",
+                is_header.clone() * sum::expr(item_tags_cur.clone()),
+            );
+            cb.require_equal(
+                "payload rows follow an unfinished item",
+                is_payload.clone(),
+                prev_item_open,
+            );
+            cb.require_equal(
+                "length rows follow an unfinished header",
+                is_header.clone() - is_hdr_start.clone(),
+                prev_hdr_open,
+            );
+
+            // Grammar of the nodes
+            cb.condition(is_node_start.clone(), |cb| {
+                cb.require_equal("nodes start with a header", is_hdr_start.clone(), 1.expr());
+                cb.require_equal("nodes are lists", cur(meta, is_list), 1.expr());
+                cb.require_zero(
+                    "only branches have a child index",
+                    not::expr(is_branch.clone()) * cur(meta, target_index),
+                );
+                cb.require_zero(
+                    "only branches have an empty child",
+                    not::expr(is_branch.clone()) * cur(meta, child_is_empty),
+                );
+            });
+            cb.require_zero(
+                "only leaves have headers after the first one",
+                not_start.clone() * not::expr(is_leaf.clone()) * is_hdr_start.clone(),
+            );
+            let after_path = prev(meta, item_tags[0]) * is_boundary.clone();
+            cb.condition(is_leaf.clone() * after_path.clone(), |cb| {
+                cb.require_equal(
+                    "leaf values are wrapped in a string unless they are a single byte",
+                    is_hdr_start.clone(),
+                    1.expr() - in_storage.clone() * cur(meta, is_short),
+                );
+                cb.require_zero(
+                    "the wrapper of a leaf value is a string",
+                    is_hdr_start.clone() * cur(meta, is_list),
+                );
+            });
+            cb.condition(
+                not_start.clone()
+                    * is_leaf.clone()
+                    * is_boundary.clone()
+                    * not::expr(prev(meta, item_tags[0])),
+                |cb| {
+                    cb.require_equal(
+                        "accounts are lists wrapped in a string",
+                        is_hdr_start.clone(),
+                        cur(meta, is_list) * not::expr(in_storage.clone()),
+                    );
+                },
+            );
+
+            // Headers
+            cb.condition(is_hdr_start.clone(), |cb| {
+                let base = 0x80.expr() + 0x40.expr() * cur(meta, is_list);
+                cb.require_zero("headers are not single bytes", cur(meta, is_short));
+                cb.require_equal(
+                    "hdr_rem := is_long ? byte - base - 55 : 0",
+                    hdr_rem.clone(),
+                    cur(meta, is_long) * (byte.clone() - base.clone() - 55.expr()),
+                );
+                cb.require_equal(
+                    "hdr_len := is_long ? 0 : byte - base",
+                    cur(meta, hdr_len),
+                    not::expr(cur(meta, is_long)) * (byte.clone() - base),
+                );
+                cb.require_equal(
+                    "hdr_count := hdr_count_prev + 1",
+                    cur(meta, hdr_count),
+                    not_start.clone() * prev(meta, hdr_count) + 1.expr(),
+                );
+            });
+            cb.condition(is_header.clone() - is_hdr_start.clone(), |cb| {
+                cb.require_equal(
+                    "hdr_rem := hdr_rem_prev - 1",
+                    hdr_rem.clone(),
+                    hdr_rem_prev.clone() - 1.expr(),
+                );
+                cb.require_equal(
+                    "hdr_len := hdr_len_prev * 256 + byte",
+                    cur(meta, hdr_len),
+                    prev(meta, hdr_len) * 256.expr() + byte.clone(),
+                );
+                cb.require_zero(
+                    "lengths don't have leading zeros",
+                    prev(meta, is_hdr_start) * cur(meta, is_zero),
+                );
+            });
+            cb.condition(is_header.clone(), |cb| {
+                cb.require_in_set(
+                    "headers have at most 2 length bytes",
+                    hdr_rem.clone(),
+                    vec![0.expr(), 1.expr(), 2.expr()],
+                );
+            });
+            cb.condition(
+                is_header.clone()
+                    * (1.expr() - hdr_rem.clone())
+                    * (2.expr() - hdr_rem.clone())
+                    * Expression::Constant(F::from(2).invert().unwrap()),
+                |cb| {
+                    cb.require_equal(
+                        "headers cover the rest of the node",
+                        cur(meta, hdr_len),
+                        cur(meta, node_len) - cur(meta, index) - 1.expr(),
+                    );
+                },
+            );
+            cb.condition(not::expr(is_hdr_start.clone()), |cb| {
+                cb.require_equal(
+                    "hdr_count := hdr_count_prev",
+                    cur(meta, hdr_count),
+                    prev(meta, hdr_count),
+                );
+            });
+
+            // Items
+            cb.require_equal(
+                "item_count := item_count_prev + is_item_start",
+                cur(meta, item_count),
+                not_start.clone() * prev(meta, item_count) + is_item_start.clone(),
+            );
+            cb.require_equal(
+                "child_count := child_count_prev + non empty child",
+                cur(meta, child_count),
+                not_start.clone() * prev(meta, child_count)
+                    + is_branch.clone()
+                        * is_item_start.clone()
+                        * (byte.clone() - 0x80.expr())
+                        * Expression::Constant(F::from(32).invert().unwrap()),
+            );
+            cb.condition(is_item_start.clone(), |cb| {
+                cb.require_zero("items are short strings", cur(meta, is_long));
+                cb.require_zero("items are not lists", cur(meta, is_list));
+                cb.require_equal(
+                    "item_rem := is_short ? 0 : byte - 0x80",
+                    cur(meta, item_rem),
+                    not::expr(cur(meta, is_short)) * (byte.clone() - 0x80.expr()),
+                );
+                cb.require_equal(
+                    "item_rlc := is_short ? byte : 0",
+                    cur(meta, item_rlc),
+                    cur(meta, is_short) * byte.clone(),
+                );
+                cb.require_equal(
+                    "items of extensions and leaves are tagged",
+                    sum::expr(item_tags_cur.clone()),
+                    is_extension.clone() + is_leaf.clone(),
+                );
+                cb.require_equal(
+                    "item tags follow the item order",
+                    (is_extension.clone() + is_leaf.clone()) * cur(meta, item_count),
+                    sum::expr(
+                        ITEM_TAGS
+                            .iter()
+                            .zip(item_tags_cur.iter())
+                            .map(|(tag, flag)| tag.index().expr() * flag.clone()),
+                    ),
+                );
+                cb.require_zero(
+                    "account fields are after the headers",
+                    is_account_leaf.clone()
+                        * not::expr(is_path.clone())
+                        * (cur(meta, hdr_count) - 3.expr()),
+                );
+            });
+            cb.condition(is_payload.clone(), |cb| {
+                cb.require_equal(
+                    "item_rem := item_rem_prev - 1",
+                    cur(meta, item_rem),
+                    prev(meta, item_rem) - 1.expr(),
+                );
+                cb.require_equal(
+                    "item_rlc := item_rlc_prev * base + byte",
+                    cur(meta, item_rlc),
+                    prev(meta, item_rlc) * select::expr(is_nonce.clone(), 256.expr(), r_w.clone())
+                        + byte.clone(),
+                );
+                for column in item_tags {
+                    cb.require_equal(
+                        "item tags are constant in an item",
+                        cur(meta, column),
+                        prev(meta, column),
+                    );
+                }
+                cb.require_zero(
+                    "strings of a single byte are encoded as the byte",
+                    prev(meta, is_item_start) * item_end.clone() * cur(meta, is_short),
+                );
+            });
+            cb.require_zero(
+                "only extensions have a child item",
+                is_child.clone() * not::expr(is_extension.clone()),
+            );
+            cb.require_zero(
+                "only storage leaves have a value item",
+                is_value.clone() * not::expr(is_leaf.clone() * in_storage.clone()),
+            );
+            cb.require_zero(
+                "only account leaves have account fields",
+                (is_nonce.clone()
+                    + is_balance.clone()
+                    + is_storage_root.clone()
+                    + is_code_hash.clone())
+                    * not::expr(is_account_leaf.clone()),
+            );
+            cb.condition(cur(meta, is_final), |cb| {
+                cb.require_zero("nodes end with an item", is_header.clone());
+                cb.require_equal("nodes end with a complete item", item_end.clone(), 1.expr());
+                cb.require_equal(
+                    "nodes have 17, 2 or 5 items",
+                    cur(meta, item_count),
+                    17.expr() * is_branch.clone()
+                        + 2.expr() * is_extension.clone()
+                        + 2.expr() * is_leaf.clone() * in_storage.clone()
+                        + 5.expr() * is_account_leaf.clone(),
+                );
+            });
+
+            // Branches, extensions and hashes
+            cb.condition(is_branch.clone() * is_item_start.clone(), |cb| {
+                cb.require_in_set(
+                    "branch children are empty or hashes",
+                    byte.clone(),
+                    vec![0x80.expr(), 0xa0.expr()],
+                );
+            });
+            cb.condition(is_branch.clone() * cur(meta, is_final), |cb| {
+                cb.require_equal("branches have no value", byte.clone(), 0x80.expr());
+            });
+            cb.condition(
+                is_branch.clone() * is_item_start.clone() * is_target.expr(),
+                |cb| {
+                    cb.require_equal(
+                        "child_is_empty := child == 0x80",
+                        cur(meta, child_is_empty),
+                        (0xa0.expr() - byte.clone())
+                            * Expression::Constant(F::from(32).invert().unwrap()),
+                    );
+                },
+            );
+            cb.condition(
+                is_item_start.clone() * (is_child.clone() + is_storage_root.clone() + is_code_hash),
+                |cb| {
+                    cb.require_equal("hashes are 32 bytes", byte.clone(), 0xa0.expr());
+                },
+            );
+
+            cb.gate(
+                meta.query_fixed(q_enable, Rotation::cur())
+                    * (cur(meta, is_branch) + cur(meta, is_extension) + cur(meta, is_leaf)),
+            )
+        });
+
+        meta.create_gate("trie node path", |meta| {
+            let mut cb = BaseConstraintBuilder::default();
+
+            let is_branch = cur(meta, is_branch);
+            let is_leaf = cur(meta, is_leaf);
+            let in_storage = cur(meta, sections[3]) + cur(meta, sections[5]);
+            let [f_nonce, f_balance, f_code_hash, _, _, f_storage] =
+                proof_types.map(|column| cur(meta, column));
+            let [is_path, is_child, is_value, is_nonce, is_balance, is_storage_root, is_code_hash] =
+                item_tags.map(|column| cur(meta, column));
+            let is_node_start = cur(meta, is_node_start);
+            let is_item = cur(meta, is_item_start) + cur(meta, is_payload);
+            let item_rem_is_zero = item_end.expr();
+            let item_end = item_rem_is_zero.clone() * is_item.clone();
+            let is_new = cur(meta, sections[4]) + cur(meta, sections[5]);
+
+            // Items on the path
+            cb.require_equal(
+                "is_child_item := the item on the path to the key",
+                cur(meta, is_child_item),
+                is_branch.clone() * is_item.clone() * is_target.expr()
+                    + is_child
+                    + is_storage_root.clone() * f_storage,
+            );
+            cb.require_equal(
+                "is_value_item := the item that is updated",
+                cur(meta, is_value_item),
+                is_value.clone()
+                    + is_nonce.clone() * f_nonce.clone()
+                    + is_balance.clone() * f_balance.clone()
+                    + is_code_hash.clone() * f_code_hash.clone(),
+            );
+            cb.condition(
+                cur(meta, is_child_item) * item_end.clone() * not::expr(cur(meta, child_is_empty)),
+                |cb| {
+                    cb.require_equal(
+                        "child_hash is the item on the path",
+                        cur(meta, child_hash),
+                        cur(meta, item_rlc),
+                    );
+                },
+            );
+            cb.condition(
+                cur(meta, is_value_item)
+                    * item_end.clone()
+                    * select::expr(is_new, cur(meta, new_exists), cur(meta, old_exists)),
+                |cb| {
+                    let is_new = cur(meta, sections[4]) + cur(meta, sections[5]);
+                    cb.require_equal(
+                        "the value in the leaf is the value of the update",
+                        cur(meta, item_rlc),
+                        select::expr(is_new, cur(meta, new_value), cur(meta, old_value)),
+                    );
+                },
+            );
+            cb.require_equal(
+                "masked_rlc := the rlc of the items that are not on the path",
+                cur(meta, masked_rlc),
+                {
+                    let is_masked = is_item.clone()
+                        * not::expr(cur(meta, is_child_item) + cur(meta, is_value_item));
+                    not::expr(is_node_start.clone())
+                        * prev(meta, masked_rlc)
+                        * (1.expr() + is_masked.clone() * (r_w.clone() - 1.expr()))
+                        + is_masked * cur(meta, byte)
+                },
+            );
+            cb.condition(cur(meta, is_inserted) * item_end, |cb| {
+                cb.require_zero(
+                    "the nonce of a new account is 0",
+                    is_nonce.clone() * not::expr(f_nonce) * cur(meta, item_rlc),
+                );
+                cb.require_zero(
+                    "the balance of a new account is 0",
+                    is_balance.clone() * not::expr(f_balance) * cur(meta, item_rlc),
+                );
+                cb.require_zero(
+                    "the storage of a new account is empty",
+                    is_storage_root * (cur(meta, item_rlc) - empty_root.clone()),
+                );
+                cb.require_zero(
+                    "the code of a new account is empty",
+                    is_code_hash
+                        * not::expr(f_code_hash)
+                        * (cur(meta, item_rlc) - empty_code_hash.clone()),
+                );
+            });
+
+            // Canonical integers
+            let is_integer = is_value.clone() + is_nonce + is_balance;
+            cb.condition(is_integer.clone() * cur(meta, is_item_start), |cb| {
+                cb.require_zero(
+                    "integers are encoded without leading zeros",
+                    cur(meta, is_short) * cur(meta, is_zero),
+                );
+            });
+            cb.condition(
+                is_integer * cur(meta, is_payload) * prev(meta, is_item_start),
+                |cb| {
+                    cb.require_zero(
+                        "integers are encoded without leading zeros",
+                        cur(meta, is_zero),
+                    );
+                },
+            );
+            cb.require_zero(
+                "zero values are not stored",
+                is_value
+                    * cur(meta, is_item_start)
+                    * not::expr(cur(meta, is_short))
+                    * item_rem_is_zero,
+            );
+
+            // Key nibbles
+            let odd_flag = cur(meta, hi) - 2.expr() * is_leaf.clone();
+            cb.require_equal(
+                "is_hp_first := first byte of the hex prefix encoded path",
+                cur(meta, is_hp_first),
+                is_path.clone()
+                    * (cur(meta, is_item_start) * cur(meta, is_short)
+                        + cur(meta, is_payload) * prev(meta, is_item_start)),
+            );
+            cb.condition(cur(meta, is_hp_first), |cb| {
+                cb.require_boolean("hex prefix flag is valid", odd_flag.clone());
+                cb.require_zero(
+                    "even paths are padded with a zero nibble",
+                    not::expr(odd_flag.clone()) * cur(meta, lo),
+                );
+            });
+            cb.require_equal(
+                "key_step_nibble := branch child index or odd path nibble",
+                cur(meta, key_step_nibble),
+                is_branch.clone() * is_node_start.clone() + cur(meta, is_hp_first) * odd_flag,
+            );
+            cb.require_equal(
+                "key_step_byte := path byte after the hex prefix",
+                cur(meta, key_step_byte),
+                is_path * cur(meta, is_payload) * not::expr(prev(meta, is_item_start)),
+            );
+            cb.condition(cur(meta, key_step_nibble), |cb| {
+                cb.require_equal(
+                    "key_nibble := branch ? target_index : lo",
+                    cur(meta, key_nibble),
+                    select::expr(is_branch.clone(), cur(meta, target_index), cur(meta, lo)),
+                );
+            });
+
+            let step_nibble = cur(meta, key_step_nibble);
+            let step_byte = cur(meta, key_step_byte);
+            let nibble = cur(meta, key_nibble);
+            let reset = is_node_start * cur(meta, is_root);
+            cb.condition(reset.clone(), |cb| {
+                cb.require_zero("key_rlc starts at 0", cur(meta, key_rlc));
+                cb.require_equal(
+                    "key_pending starts with the first nibble",
+                    cur(meta, key_pending),
+                    step_nibble.clone() * nibble.clone(),
+                );
+                cb.require_equal(
+                    "key_odd starts with the first nibble",
+                    cur(meta, key_odd),
+                    step_nibble.clone(),
+                );
+                cb.require_equal(
+                    "key_len starts with the first nibble",
+                    cur(meta, key_len),
+                    step_nibble.clone(),
+                );
+            });
+            cb.condition(not::expr(reset), |cb| {
+                let key_rlc_prev = prev(meta, key_rlc);
+                let pending_prev = prev(meta, key_pending);
+                let odd_prev = prev(meta, key_odd);
+                let byte_value = odd_prev.clone()
+                    * (pending_prev.clone() * 16.expr() + cur(meta, hi))
+                    + not::expr(odd_prev.clone()) * cur(meta, byte);
+                cb.require_equal(
+                    "key_rlc := key_rlc_prev * r + byte for each complete byte",
+                    cur(meta, key_rlc),
+                    key_rlc_prev.clone()
+                        + step_nibble.clone()
+                            * odd_prev.clone()
+                            * (key_rlc_prev.clone() * (r_w.clone() - 1.expr())
+                                + pending_prev.clone() * 16.expr()
+                                + nibble.clone())
+                        + step_byte.clone()
+                            * (key_rlc_prev * (r_w.clone() - 1.expr()) + byte_value),
+                );
+                cb.require_equal(
+                    "key_pending := the nibble of an incomplete byte",
+                    cur(meta, key_pending),
+                    not::expr(step_nibble.clone() + step_byte.clone()) * pending_prev
+                        + step_nibble.clone() * not::expr(odd_prev.clone()) * nibble
+                        + step_byte.clone() * odd_prev.clone() * cur(meta, lo),
+                );
+                cb.require_equal(
+                    "key_odd := key_odd_prev xor step_nibble",
+                    cur(meta, key_odd),
+                    not::expr(step_nibble.clone() + step_byte.clone()) * odd_prev.clone()
+                        + step_nibble.clone() * not::expr(odd_prev.clone())
+                        + step_byte.clone() * odd_prev,
+                );
+                cb.require_equal(
+                    "key_len := key_len_prev + number of nibbles",
+                    cur(meta, key_len),
+                    prev(meta, key_len) + step_nibble + 2.expr() * step_byte,
+                );
+            });
+            cb.condition(is_leaf * cur(meta, is_final), |cb| {
+                cb.require_equal("leaf keys have 64 nibbles", cur(meta, key_len), 64.expr());
+                cb.require_zero("leaf keys have whole bytes", cur(meta, key_odd));
+            });
+
+            cb.gate(
+                meta.query_fixed(q_enable, Rotation::cur())
+                    * (cur(meta, is_branch) + cur(meta, is_extension) + cur(meta, is_leaf)),
+            )
+        });
+
+        meta.lookup_any("byte table", |meta| {
+            let enable = meta.query_fixed(q_enable, Rotation::cur());
+            [byte, hi, lo, is_zero, is_short, is_long, is_list]
+                .into_iter()
+                .map(|column| enable.clone() * cur(meta, column))
+                .zip(byte_table[1..].iter())
+                .map(|(input, column)| (input, meta.query_fixed(*column, Rotation::cur())))
+                .chain(std::iter::once((
+                    enable,
+                    meta.query_fixed(byte_table[0], Rotation::cur()),
+                )))
+                .collect()
+        });
+
+        // Values that must be in [0, 256), for the canonical encoding of the
+        // lengths and for the number of children of the branches.
+        type RangeCheck<'a, F> =
+            Box<dyn Fn(&mut VirtualCells<'_, F>) -> (Expression<F>, Expression<F>) + 'a>;
+        let range_checks: [(&str, RangeCheck<F>); 3] = [
+            (
+                "single length bytes are at least 56",
+                Box::new(|meta| {
+                    let hdr_rem = cur(meta, hdr_rem);
+                    (
+                        cur(meta, is_header)
+                            * not::expr(cur(meta, is_hdr_start))
+                            * prev(meta, is_hdr_start)
+                            * (1.expr() - hdr_rem.clone())
+                            * (2.expr() - hdr_rem)
+                            * Expression::Constant(F::from(2).invert().unwrap()),
+                        cur(meta, byte) - 56.expr(),
+                    )
+                }),
+            ),
+            (
+                "nonces have at most 8 bytes",
+                Box::new(|meta| {
+                    (
+                        cur(meta, item_tags[3]) * cur(meta, is_item_start),
+                        8.expr() - cur(meta, item_rem),
+                    )
+                }),
+            ),
+            (
+                "branches have at least 2 children",
+                Box::new(|meta| {
+                    (
+                        cur(meta, is_branch) * cur(meta, is_final),
+                        cur(meta, child_count) - 2.expr(),
+                    )
+                }),
+            ),
+        ];
+        for (name, range_check) in range_checks {
+            meta.lookup_any(name, |meta| {
+                let enable = meta.query_fixed(q_enable, Rotation::cur());
+                let (condition, value) = range_check(meta);
+                let condition = enable * condition;
+                vec![
+                    (
+                        condition.clone(),
+                        meta.query_fixed(byte_table[0], Rotation::cur()),
+                    ),
+                    (
+                        condition * value,
+                        meta.query_fixed(byte_table[1], Rotation::cur()),
+                    ),
+                ]
+            });
+        }
+
+        meta.lookup_any("keccak of hashed nodes", |meta| {
+            let enable = meta.query_fixed(q_enable, Rotation::cur())
+                * cur(meta, is_final)
+                * not::expr(cur(meta, padding) + cur(meta, is_key));
+            let input_exprs = [hash_input_rlc, node_len, node_hash]
+                .map(|column| enable.clone() * cur(meta, column));
+            let table_exprs = keccak_table
+                .columns()
+                .iter()
+                .map(|column| cur(meta, *column))
+                .collect::<Vec<_>>();
+            std::iter::once(enable)
+                .chain(input_exprs)
+                .zip(table_exprs)
+                .collect()
+        });
+
+        // The nibbles of the branch indices and the extension paths are the
+        // nibbles of the key at the same position.  The leaf paths are only
+        // compared with the key as a whole, which they don't match in the
+        // proofs that a key doesn't exist.
+        for (name, step, position, nibble) in [
+            (
+                "branch and odd extension nibbles",
+                key_step_nibble,
+                1u64,
+                Some(key_nibble),
+            ),
+            ("extension high nibbles", key_step_byte, 2, Some(hi)),
+            ("extension low nibbles", key_step_byte, 1, None),
+        ] {
+            meta.lookup_any(name, |meta| {
+                let condition = meta.query_fixed(q_enable, Rotation::cur())
+                    * cur(meta, step)
+                    * (cur(meta, is_branch) + cur(meta, is_extension));
+                let in_storage = cur(meta, sections[3]) + cur(meta, sections[5]);
+                let nibble = cur(meta, nibble.unwrap_or(lo));
+                let input_exprs = [
+                    1.expr(),
+                    cur(meta, update_index),
+                    in_storage,
+                    cur(meta, key_len) - position.expr(),
+                    nibble,
+                ];
+                let is_key = cur(meta, is_key);
+                let table_exprs = [
+                    1.expr(),
+                    cur(meta, update_index),
+                    cur(meta, sections[1]),
+                    cur(meta, index),
+                    cur(meta, byte),
+                ];
+                input_exprs
+                    .into_iter()
+                    .zip(table_exprs)
+                    .map(|(input, table)| (condition.clone() * input, is_key.clone() * table))
+                    .collect()
+            });
+        }
+
+        meta.lookup_any(
+            "new nodes are old nodes with a new child or value",
+            |meta| {
+                let enable = meta.query_fixed(q_enable, Rotation::cur()) * cur(meta, is_final);
+                let is_trie = cur(meta, is_branch) + cur(meta, is_extension) + cur(meta, is_leaf);
+                let is_new = cur(meta, sections[4]) + cur(meta, sections[5]);
+                let condition =
+                    enable.clone() * is_trie.clone() * is_new * not::expr(cur(meta, is_inserted));
+                let table_condition =
+                    enable * is_trie * (cur(meta, sections[2]) + cur(meta, sections[3]));
+                let in_storage = |meta: &mut VirtualCells<'_, F>| {
+                    cur(meta, sections[3]) + cur(meta, sections[5])
+                };
+                let exprs = |meta: &mut VirtualCells<'_, F>| {
+                    vec![
+                        1.expr(),
+                        cur(meta, update_index),
+                        in_storage(meta),
+                        cur(meta, depth),
+                        cur(meta, is_branch),
+                        cur(meta, is_extension),
+                        cur(meta, is_leaf),
+                        cur(meta, target_index),
+                        cur(meta, masked_rlc),
+                    ]
+                };
+                exprs(meta)
+                    .into_iter()
+                    .zip(exprs(meta))
+                    .map(|(input, table)| {
+                        (condition.clone() * input, table_condition.clone() * table)
+                    })
+                    .collect()
+            },
+        );
+
+        Self {
+            minimum_rows: meta.minimum_rows(),
+            q_enable,
+            q_first,
+            q_last,
+            byte_table,
+            mpt_table,
+            keccak_table,
+            update_index,
+            proof_types,
+            old_exists,
+            new_exists,
+            old_len,
+            new_len,
+            address_hash,
+            slot_hash,
+            sections,
+            is_preimage,
+            is_key,
+            is_branch,
+            is_extension,
+            is_leaf,
+            padding,
+            depth,
+            node_len,
+            node_hash,
+            child_hash,
+            target_index,
+            child_is_empty,
+            is_root,
+            is_last,
+            is_inserted,
+            byte,
+            hi,
+            lo,
+            is_zero,
+            is_short,
+            is_long,
+            is_list,
+            index,
+            is_final,
+            is_node_start,
+            hash_input_rlc,
+            is_header,
+            is_hdr_start,
+            hdr_rem,
+            hdr_len,
+            hdr_count,
+            is_item_start,
+            is_payload,
+            item_rem,
+            item_count,
+            item_tags,
+            child_count,
+            is_child_item,
+            is_value_item,
+            item_rlc,
+            value_rlc,
+            masked_rlc,
+            key_rlc,
+            key_pending,
+            key_odd,
+            key_len,
+            is_hp_first,
+            key_step_nibble,
+            key_step_byte,
+            key_nibble,
+            item_end,
+            is_target,
+            key_match,
+        }
+    }
+}
+
+impl<F: Field> MptCircuitConfig<F> {
+    /// Load the byte table
+    pub(crate) fn load(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        layouter.assign_region(
+            || "mpt byte table",
+            |mut region| {
+                for byte in 0u64..256 {
+                    for (name, column, value) in [
+                        ("enable", self.byte_table[0], 1),
+                        ("byte", self.byte_table[1], byte),
+                        ("hi", self.byte_table[2], byte >> 4),
+                        ("lo", self.byte_table[3], byte & 0xf),
+                        ("is_zero", self.byte_table[4], (byte == 0) as u64),
+                        ("is_short", self.byte_table[5], (byte < 0x80) as u64),
+                        ("is_long", self.byte_table[6], is_long(byte) as u64),
+                        ("is_list", self.byte_table[7], (byte >= 0xc0) as u64),
+                    ] {
+                        region.assign_fixed(
+                            || format!("mpt byte table {} {}", name, byte),
+                            column,
+                            byte as usize,
+                            || Value::known(F::from(value)),
+                        )?;
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Assign the rows of the updates and of the MPT table
+    pub(crate) fn assign(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        updates: &MptUpdates,
+        n_rows: usize,
+        challenges: &Challenges<Value<F>>,
+    ) -> Result<(), Error> {
+        let update_nodes = updates
+            .iter()
+            .map(UpdateNodes::new)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(unprovable)?;
+
+        // Subtract the unusable rows from the size
+        let last_row_offset = n_rows - self.minimum_rows + 1;
+
+        let item_end_chip = IsZeroChip::construct(self.item_end.clone());
+        let is_target_chip = IsZeroChip::construct(self.is_target.clone());
+        let key_match_chip = IsZeroChip::construct(self.key_match.clone());
+
+        layouter.assign_region(
+            || "mpt circuit",
+            |mut region| {
+                let mut offset = 0;
+                for (update_index, (update, nodes)) in
+                    updates.iter().zip(update_nodes.iter()).enumerate()
+                {
+                    let proof_type = update.key.proof_type();
+                    let proof_type_index = update.key.proof_type() as usize - 1;
+                    let mpt_row = update.table_assignment(challenges.evm_word());
+                    let address_hash = hash_rlc(&nodes.address_hash.0, challenges.evm_word());
+                    let slot_hash = hash_rlc(&nodes.slot_hash.0, challenges.evm_word());
+
+                    let mut key = KeyState::new();
+                    for node in nodes.nodes.iter() {
+                        if offset + node.rows.len() > last_row_offset {
+                            log::error!(
+                                "MPT Circuit: offset={} > last_row_offset={}",
+                                offset + node.rows.len(),
+                                last_row_offset
+                            );
+                            return Err(Error::Synthesis);
+                        }
+
+                        let mut update_values = vec![
+                            (
+                                self.update_index,
+                                Value::known(F::from(update_index as u64)),
+                            ),
+                            (
+                                self.old_exists,
+                                Value::known(F::from(nodes.old_exists as u64)),
+                            ),
+                            (
+                                self.new_exists,
+                                Value::known(F::from(nodes.new_exists as u64)),
+                            ),
+                            (self.old_len, Value::known(F::from(nodes.old_len as u64))),
+                            (self.new_len, Value::known(F::from(nodes.new_len as u64))),
+                            (self.address_hash, address_hash),
+                            (self.slot_hash, slot_hash),
+                        ];
+                        for (i, column) in self.proof_types.iter().enumerate() {
+                            update_values.push((
+                                *column,
+                                Value::known(F::from((i == proof_type_index) as u64)),
+                            ));
+                        }
+                        for (i, column) in self.mpt_table.columns().into_iter().enumerate() {
+                            update_values.push((column, *mpt_row.values().nth(i).unwrap()));
+                        }
+
+                        let node_values = self.node_values(node, challenges);
+                        let in_storage =
+                            matches!(node.section, Section::OldStorage | Section::NewStorage);
+                        let key_hash = if in_storage { slot_hash } else { address_hash };
+                        let is_trie = !matches!(node.kind, NodeKind::Preimage | NodeKind::Key);
+
+                        let mut hash_input_rlc = Value::known(F::zero());
+                        let mut item_rlc = Value::known(F::zero());
+                        let mut value_rlc = Value::known(F::zero());
+                        let mut masked_rlc = Value::known(F::zero());
+                        for (index, row) in node.rows.iter().enumerate() {
+                            let byte = F::from(row.byte as u64);
+                            hash_input_rlc = hash_input_rlc
+                                .zip(challenges.keccak_input())
+                                .map(|(acc, challenge)| acc * challenge + byte);
+
+                            let base = |is_nonce: bool| {
+                                if is_nonce {
+                                    Value::known(F::from(256))
+                                } else {
+                                    challenges.evm_word()
+                                }
+                            };
+                            match node.kind {
+                                NodeKind::Preimage => {
+                                    value_rlc = value_rlc
+                                        .zip(base(node.section == Section::Address))
+                                        .map(|(acc, base)| acc * base + byte);
+                                }
+                                NodeKind::Key => {
+                                    value_rlc = if index % 2 == 1 {
+                                        value_rlc.map(|acc| acc + byte)
+                                    } else {
+                                        value_rlc.zip(challenges.evm_word()).map(
+                                            |(acc, challenge)| acc * challenge + F::from(16) * byte,
+                                        )
+                                    };
+                                }
+                                _ => {
+                                    if row.is_item_start {
+                                        item_rlc = Value::known(if row.byte < 0x80 {
+                                            byte
+                                        } else {
+                                            F::zero()
+                                        });
+                                    } else if row.is_payload {
+                                        item_rlc = item_rlc
+                                            .zip(base(row.item == Some(ItemTag::Nonce)))
+                                            .map(|(acc, base)| acc * base + byte);
+                                    } else {
+                                        item_rlc = Value::known(F::zero());
+                                    }
+                                    let is_masked = !row.is_header
+                                        && !node.is_child_item(index, &proof_type)
+                                        && !node.is_value_item(index, &proof_type);
+                                    if is_masked {
+                                        masked_rlc = masked_rlc
+                                            .zip(challenges.evm_word())
+                                            .map(|(acc, challenge)| acc * challenge + byte);
+                                    }
+                                    key.step(
+                                        row,
+                                        index == 0 && node.is_root,
+                                        challenges.evm_word(),
+                                    );
+                                }
+                            }
+
+                            let is_child_item = is_trie && node.is_child_item(index, &proof_type);
+                            let is_value_item = is_trie && node.is_value_item(index, &proof_type);
+                            let (key_rlc, key_pending, key_odd, key_len) = if is_trie {
+                                (key.rlc, key.pending, key.odd, key.len)
+                            } else {
+                                (
+                                    Value::known(F::zero()),
+                                    0,
+                                    node.kind == NodeKind::Key && index % 2 == 1,
+                                    0,
+                                )
+                            };
+                            let flag = |value: bool| Value::known(F::from(value as u64));
+                            let number = |value: u64| Value::known(F::from(value));
+                            let mut row_values = vec![
+                                (self.byte, number(row.byte as u64)),
+                                (self.hi, number(row.byte as u64 >> 4)),
+                                (self.lo, number(row.byte as u64 & 0xf)),
+                                (self.is_zero, flag(row.byte == 0)),
+                                (self.is_short, flag(row.byte < 0x80)),
+                                (self.is_long, flag(is_long(row.byte as u64))),
+                                (self.is_list, flag(row.byte >= 0xc0)),
+                                (self.index, number(index as u64)),
+                                (self.is_final, flag(index + 1 == node.rows.len())),
+                                (self.is_node_start, flag(index == 0)),
+                                (self.hash_input_rlc, hash_input_rlc),
+                                (self.is_header, flag(row.is_header)),
+                                (self.is_hdr_start, flag(row.is_hdr_start)),
+                                (self.hdr_rem, number(row.hdr_rem)),
+                                (self.hdr_len, number(row.hdr_len)),
+                                (self.hdr_count, number(row.hdr_count)),
+                                (self.is_item_start, flag(row.is_item_start)),
+                                (self.is_payload, flag(row.is_payload)),
+                                (self.item_rem, number(row.item_rem)),
+                                (self.item_count, number(row.item_count)),
+                                (self.child_count, number(row.child_count)),
+                                (self.is_child_item, flag(is_child_item)),
+                                (self.is_value_item, flag(is_value_item)),
+                                (self.item_rlc, item_rlc),
+                                (self.value_rlc, value_rlc),
+                                (self.masked_rlc, masked_rlc),
+                                (self.key_rlc, key_rlc),
+                                (self.key_pending, number(key_pending)),
+                                (self.key_odd, flag(key_odd)),
+                                (self.key_len, number(key_len)),
+                                (self.is_hp_first, flag(row.is_hp_first)),
+                                (self.key_step_nibble, flag(row.key_step_nibble)),
+                                (self.key_step_byte, flag(row.key_step_byte)),
+                                (self.key_nibble, number(row.key_nibble as u64)),
+                            ];
+                            for (tag, column) in ITEM_TAGS.iter().zip(self.item_tags) {
+                                row_values.push((column, flag(row.item == Some(*tag))));
+                            }
+                            self.assign_row(
+                                &mut region,
+                                offset,
+                                update_values
+                                    .iter()
+                                    .chain(node_values.iter())
+                                    .chain(row_values.iter())
+                                    .copied(),
+                            )?;
+                            item_end_chip.assign(
+                                &mut region,
+                                offset,
+                                Value::known(F::from(row.item_rem)),
+                            )?;
+                            is_target_chip.assign(
+                                &mut region,
+                                offset,
+                                Value::known(
+                                    F::from(row.item_count)
+                                        - F::one()
+                                        - F::from(node.target_index as u64),
+                                ),
+                            )?;
+                            key_match_chip.assign(&mut region, offset, key_rlc - key_hash)?;
+                            self.assign_fixed_row(&mut region, offset, false)?;
+                            offset += 1;
+                        }
+                    }
+                }
+
+                // Padding
+                for offset in offset..=last_row_offset {
+                    let columns = [
+                        self.update_columns(),
+                        self.node_columns(),
+                        self.row_columns(),
+                    ]
+                    .concat();
+                    self.assign_row(
+                        &mut region,
+                        offset,
+                        columns.iter().map(|column| {
+                            let value = [
+                                self.padding,
+                                self.is_final,
+                                self.is_node_start,
+                                self.is_zero,
+                                self.is_short,
+                            ]
+                            .contains(column);
+                            (*column, Value::known(F::from(value as u64)))
+                        }),
+                    )?;
+                    item_end_chip.assign(&mut region, offset, Value::known(F::zero()))?;
+                    is_target_chip.assign(&mut region, offset, Value::known(-F::one()))?;
+                    key_match_chip.assign(&mut region, offset, Value::known(F::zero()))?;
+                    self.assign_fixed_row(&mut region, offset, offset == last_row_offset)?;
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Values of the columns that are constant in the rows of a node
+    fn node_values(
+        &self,
+        node: &Node,
+        challenges: &Challenges<Value<F>>,
+    ) -> Vec<(Column<Advice>, Value<F>)> {
+        let flag = |value: bool| Value::known(F::from(value as u64));
+        let sections = [
+            Section::Address,
+            Section::Slot,
+            Section::OldAccount,
+            Section::OldStorage,
+            Section::NewAccount,
+            Section::NewStorage,
+        ];
+        let node_hash = if node.is_hashed() {
+            hash_rlc(&keccak256(&node.bytes), challenges.evm_word())
+        } else {
+            Value::known(F::zero())
+        };
+        let child_hash = node
+            .child
+            .map(|child| hash_rlc(&child.0, challenges.evm_word()))
+            .unwrap_or_else(|| Value::known(F::zero()));
+
+        let mut values = vec![
+            (self.is_preimage, flag(node.kind == NodeKind::Preimage)),
+            (self.is_key, flag(node.kind == NodeKind::Key)),
+            (self.is_branch, flag(node.kind == NodeKind::Branch)),
+            (self.is_extension, flag(node.kind == NodeKind::Extension)),
+            (self.is_leaf, flag(node.kind == NodeKind::Leaf)),
+            (self.padding, flag(false)),
+            (self.depth, Value::known(F::from(node.depth as u64))),
+            (
+                self.node_len,
+                Value::known(F::from(node.bytes.len() as u64)),
+            ),
+            (self.node_hash, node_hash),
+            (self.child_hash, child_hash),
+            (
+                self.target_index,
+                Value::known(F::from(node.target_index as u64)),
+            ),
+            (self.child_is_empty, flag(node.child_is_empty)),
+            (self.is_root, flag(node.is_root)),
+            (self.is_last, flag(node.is_last)),
+            (self.is_inserted, flag(node.is_inserted)),
+        ];
+        for (section, column) in sections.iter().zip(self.sections) {
+            values.push((column, flag(node.section == *section)));
+        }
+        values
+    }
+
+    fn assign_row(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        values: impl IntoIterator<Item = (Column<Advice>, Value<F>)>,
+    ) -> Result<(), Error> {
+        for (column, value) in values {
+            region.assign_advice(
+                || format!("assign mpt circuit row {}", offset),
+                column,
+                offset,
+                || value,
+            )?;
+        }
+        Ok(())
+    }
+
+    fn assign_fixed_row(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        is_last: bool,
+    ) -> Result<(), Error> {
+        for (name, column, value) in [
+            ("q_enable", self.q_enable, true),
+            ("q_first", self.q_first, offset == 0),
+        ] {
+            region.assign_fixed(
+                || format!("assign {} {}", name, offset),
+                column,
+                offset,
+                || Value::known(F::from(value as u64)),
+            )?;
+        }
+        if is_last {
+            self.q_last.enable(region, offset)?;
+        }
+        Ok(())
+    }
+
+    fn update_columns(&self) -> Vec<Column<Advice>> {
+        [
+            vec![
+                self.update_index,
+                self.old_exists,
+                self.new_exists,
+                self.old_len,
+                self.new_len,
+                self.address_hash,
+                self.slot_hash,
+            ],
+            self.proof_types.to_vec(),
+            self.mpt_table.columns(),
+        ]
+        .concat()
+    }
+
+    fn node_columns(&self) -> Vec<Column<Advice>> {
+        [
+            self.sections.to_vec(),
+            vec![
+                self.is_preimage,
+                self.is_key,
+                self.is_branch,
+                self.is_extension,
+                self.is_leaf,
+                self.padding,
+                self.depth,
+                self.node_len,
+                self.node_hash,
+                self.child_hash,
+                self.target_index,
+                self.child_is_empty,
+                self.is_root,
+                self.is_last,
+                self.is_inserted,
+            ],
+        ]
+        .concat()
+    }
+
+    fn row_columns(&self) -> Vec<Column<Advice>> {
+        [
+            vec![
+                self.byte,
+                self.hi,
+                self.lo,
+                self.is_zero,
+                self.is_short,
+                self.is_long,
+                self.is_list,
+                self.index,
+                self.is_final,
+                self.is_node_start,
+                self.hash_input_rlc,
+                self.is_header,
+                self.is_hdr_start,
+                self.hdr_rem,
+                self.hdr_len,
+                self.hdr_count,
+                self.is_item_start,
+                self.is_payload,
+                self.item_rem,
+                self.item_count,
+                self.child_count,
+                self.is_child_item,
+                self.is_value_item,
+                self.item_rlc,
+                self.value_rlc,
+                self.masked_rlc,
+                self.key_rlc,
+                self.key_pending,
+                self.key_odd,
+                self.key_len,
+                self.is_hp_first,
+                self.key_step_nibble,
+                self.key_step_byte,
+                self.key_nibble,
+            ],
+            self.item_tags.to_vec(),
+        ]
+        .concat()
+    }
+}
+
+/// Whether the byte is the header of a string or a list longer than 55 bytes
+fn is_long(byte: u64) -> bool {
+    (0xb8..0xc0).contains(&byte) || byte >= 0xf8
+}
+
+/// RLC of a hash, as used for the words in the tables
+fn hash_rlc<F: Field>(hash: &[u8; 32], evm_word: Value<F>) -> Value<F> {
+    evm_word.map(|challenge| rlc::value(hash.iter().rev(), challenge))
+}
+
+/// The key nibbles accumulated along a trie path
+#[derive(Clone, Debug)]
+struct KeyState<F> {
+    rlc: Value<F>,
+    pending: u64,
+    odd: bool,
+    len: u64,
+}
+
+impl<F: Field> KeyState<F> {
+    fn new() -> Self {
+        Self {
+            rlc: Value::known(F::zero()),
+            pending: 0,
+            odd: false,
+            len: 0,
+        }
+    }
+
+    fn step(&mut self, row: &ByteRow, reset: bool, evm_word: Value<F>) {
+        let nibble = row.key_nibble as u64;
+        if reset {
+            *self = Self {
+                pending: if row.key_step_nibble { nibble } else { 0 },
+                odd: row.key_step_nibble,
+                len: row.key_step_nibble as u64,
+                ..Self::new()
+            };
+            return;
+        }
+        if row.key_step_nibble {
+            if self.odd {
+                let byte = F::from(self.pending * 16 + nibble);
+                self.rlc = self
+                    .rlc
+                    .zip(evm_word)
+                    .map(|(acc, challenge)| acc * challenge + byte);
+                self.pending = 0;
+            } else {
+                self.pending = nibble;
+            }
+            self.odd = !self.odd;
+            self.len += 1;
+        }
+        if row.key_step_byte {
+            let (hi, lo) = (row.byte as u64 >> 4, row.byte as u64 & 0xf);
+            let byte = if self.odd {
+                F::from(self.pending * 16 + hi)
+            } else {
+                F::from(row.byte as u64)
+            };
+            self.rlc = self
+                .rlc
+                .zip(evm_word)
+                .map(|(acc, challenge)| acc * challenge + byte);
+            self.pending = if self.odd { lo } else { 0 };
+            self.len += 2;
+        }
+    }
+}
+
+/// MPT Circuit for proving the updates of the MPT table
+#[derive(Clone, Default, Debug)]
+pub struct MptCircuit<F: Field> {
+    /// The updates of the MPT table, with the trie nodes witnessing them
+    pub updates: MptUpdates,
+    /// Number of rows of the circuit
+    pub n_rows: usize,
+    _marker: PhantomData<F>,
+}
+
+impl<F: Field> MptCircuit<F> {
+    /// Creates a new MptCircuit
+    pub fn new(updates: MptUpdates, n_rows: usize) -> Self {
+        Self {
+            updates,
+            n_rows,
+            _marker: PhantomData::default(),
+        }
+    }
+
+    /// Number of rows used by the trie nodes of the updates, which fails with
+    /// [`Error::UnsupportedMptUpdate`](eth_types::Error::UnsupportedMptUpdate)
+    /// when an update has no trie nodes or can't be proved by the circuit.
+    pub fn get_num_rows_required(updates: &MptUpdates) -> Result<usize, eth_types::Error> {
+        let mut num_rows = 0;
+        for update in updates.iter() {
+            let nodes = UpdateNodes::new(update)?;
+            num_rows += nodes
+                .nodes
+                .iter()
+                .map(|node| node.rows.len())
+                .sum::<usize>();
+        }
+        Ok(num_rows)
+    }
+
+    /// Inputs of the keccak table: the preimages of the keys and the trie
+    /// nodes of all the updates.
+    pub fn keccak_inputs(&self) -> Result<Vec<Vec<u8>>, eth_types::Error> {
+        keccak_inputs(&self.updates)
+    }
+}
+
+/// Inputs of the keccak table hashed by the MPT circuit to prove `updates`.
+pub(crate) fn keccak_inputs(updates: &MptUpdates) -> Result<Vec<Vec<u8>>, eth_types::Error> {
+    let mut inputs = vec![];
+    for update in updates.iter() {
+        let nodes = UpdateNodes::new(update)?;
+        inputs.extend(
+            nodes
+                .nodes
+                .into_iter()
+                .filter(|node| node.is_hashed())
+                .map(|node| node.bytes),
+        );
+    }
+    Ok(inputs)
+}
+
+impl<F: Field> SubCircuit<F> for MptCircuit<F> {
+    type Config = MptCircuitConfig<F>;
+
+    /// The updates are padded to the `max_mpt_rows` of the circuit params.
+    fn new_from_block(block: &witness::Block<F>) -> Self {
+        // Like for the Bytecode circuit, a few rows are left for the unusable
+        // rows instead of using the whole circuit.
+        let n_rows = match block.circuit_params.max_mpt_rows {
+            0 => Self::get_num_rows_required(&block.mpt_updates).unwrap_or_default() + 64,
+            max_mpt_rows => max_mpt_rows,
+        };
+        Self::new(block.mpt_updates.clone(), n_rows)
+    }
+
+    /// Make the assignments to the MptCircuit, including the mpt table
+    fn synthesize_sub(
+        &self,
+        config: &Self::Config,
+        challenges: &Challenges<Value<F>>,
+        layouter: &mut impl Layouter<F>,
+    ) -> Result<(), Error> {
+        config.load(layouter)?;
+        config.assign(layouter, &self.updates, self.n_rows, challenges)
+    }
+
+    /// The byte table takes 256 rows.
+    fn min_num_rows_block(block: &witness::Block<F>) -> usize {
+        Self::get_num_rows_required(&block.mpt_updates)
+            .unwrap_or_default()
+            .max(block.circuit_params.max_mpt_rows)
+            .max(256)
+    }
+}
+
+impl<F: Field> Circuit<F> for MptCircuit<F> {
+    type Config = (MptCircuitConfig<F>, Challenges);
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let mpt_table = MptTable::construct(meta);
+        let keccak_table = KeccakTable::construct(meta);
+        let challenges = Challenges::construct(meta);
+
+        let config = {
+            let challenges = challenges.exprs(meta);
+            MptCircuitConfig::new(
+                meta,
+                MptCircuitConfigArgs {
+                    mpt_table,
+                    keccak_table,
+                    challenges,
+                },
+            )
+        };
+
+        (config, challenges)
+    }
+
+    fn synthesize(
+        &self,
+        (config, challenges): Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let challenges = challenges.values(&mut layouter);
+
+        config.load(&mut layouter)?;
+        config.keccak_table.dev_load(
+            &mut layouter,
+            &self.keccak_inputs().map_err(unprovable)?,
+            &challenges,
+        )?;
+        self.synthesize_sub(&config, &challenges, &mut layouter)
+    }
+}
@@ -0,0 +1,638 @@
+//! Layout of the keys and trie nodes witnessing an MPT update in the rows of
+//! the MPT circuit.
+
+use crate::{
+    table::ProofType,
+    witness::{MptProof, MptUpdate},
+};
+use eth_types::{mpt, Error, ToBigEndian, H256};
+use ethers_core::utils::{keccak256, rlp::Rlp};
+
+/// Number of nibbles in the keys of the state and storage tries
+const KEY_NIBBLES: usize = mpt::KEY_NIBBLES;
+
+/// Part of an update a node belongs to.  The sections of an update are laid
+/// out in this order.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Section {
+    /// Address of the account and its key in the state trie
+    Address,
+    /// Storage slot and its key in the storage trie
+    Slot,
+    /// Path to the account in the old state trie
+    OldAccount,
+    /// Path to the slot in the old storage trie
+    OldStorage,
+    /// Path to the account in the new state trie
+    NewAccount,
+    /// Path to the slot in the new storage trie
+    NewStorage,
+}
+
+impl Section {
+    pub(crate) fn is_storage(&self) -> bool {
+        matches!(self, Self::Slot | Self::OldStorage | Self::NewStorage)
+    }
+
+    pub(crate) fn is_new(&self) -> bool {
+        matches!(self, Self::NewAccount | Self::NewStorage)
+    }
+
+    /// Section of the new path matching the section of an old path
+    fn new_side(&self) -> Self {
+        match self {
+            Self::OldAccount => Self::NewAccount,
+            Self::OldStorage => Self::NewStorage,
+            _ => *self,
+        }
+    }
+}
+
+/// Kind of the node a row belongs to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum NodeKind {
+    /// Preimage of a trie key: an address or a storage slot
+    Preimage,
+    /// Nibbles of a trie key, one per row
+    Key,
+    /// Branch node
+    Branch,
+    /// Extension node
+    Extension,
+    /// Leaf node
+    Leaf,
+}
+
+/// Item of an extension or a leaf node
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ItemTag {
+    /// Hex prefix encoded path
+    Path,
+    /// Hash of the child of an extension node
+    Child,
+    /// Storage value
+    Value,
+    /// Account nonce
+    Nonce,
+    /// Account balance
+    Balance,
+    /// Account storage root
+    StorageRoot,
+    /// Account code hash
+    CodeHash,
+}
+
+impl ItemTag {
+    /// Index of the item in its node, starting at 1
+    pub(crate) fn index(&self) -> u64 {
+        match self {
+            Self::Path => 1,
+            Self::Child | Self::Value | Self::Nonce => 2,
+            Self::Balance => 3,
+            Self::StorageRoot => 4,
+            Self::CodeHash => 5,
+        }
+    }
+}
+
+/// The values of the columns of a row that don't depend on the challenges.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ByteRow {
+    pub(crate) byte: u8,
+    pub(crate) is_header: bool,
+    pub(crate) is_hdr_start: bool,
+    pub(crate) hdr_rem: u64,
+    pub(crate) hdr_len: u64,
+    pub(crate) hdr_count: u64,
+    pub(crate) is_item_start: bool,
+    pub(crate) is_payload: bool,
+    pub(crate) item_rem: u64,
+    pub(crate) item_count: u64,
+    pub(crate) item: Option<ItemTag>,
+    pub(crate) child_count: u64,
+    pub(crate) is_hp_first: bool,
+    pub(crate) key_step_nibble: bool,
+    pub(crate) key_step_byte: bool,
+    pub(crate) key_nibble: u8,
+}
+
+/// A node laid out in the MPT circuit, one byte per row
+#[derive(Clone, Debug)]
+pub(crate) struct Node {
+    pub(crate) section: Section,
+    pub(crate) kind: NodeKind,
+    pub(crate) bytes: Vec<u8>,
+    pub(crate) rows: Vec<ByteRow>,
+    pub(crate) depth: usize,
+    /// Index of the child on the path in a branch node
+    pub(crate) target_index: u8,
+    /// Whether the child on the path in a branch node is empty
+    pub(crate) child_is_empty: bool,
+    /// Hash of the next node on the path
+    pub(crate) child: Option<H256>,
+    /// Whether the node is the first one of a trie section
+    pub(crate) is_root: bool,
+    /// Whether the node is the last one of its section
+    pub(crate) is_last: bool,
+    /// Whether the node is a leaf inserted by the update
+    pub(crate) is_inserted: bool,
+}
+
+impl Node {
+    fn key_part(section: Section, kind: NodeKind, bytes: Vec<u8>) -> Self {
+        let rows = bytes
+            .iter()
+            .map(|byte| ByteRow {
+                byte: *byte,
+                ..Default::default()
+            })
+            .collect();
+        Self {
+            section,
+            kind,
+            bytes,
+            rows,
+            depth: 0,
+            target_index: 0,
+            child_is_empty: false,
+            child: None,
+            is_root: false,
+            is_last: false,
+            is_inserted: false,
+        }
+    }
+
+    /// Returns whether the node is hashed with a lookup to the keccak table.
+    pub(crate) fn is_hashed(&self) -> bool {
+        self.kind != NodeKind::Key
+    }
+
+    /// Returns whether the byte at `index` belongs to the item on the path of
+    /// the update, which is excluded from the comparison of the old and new
+    /// nodes.
+    pub(crate) fn is_child_item(&self, index: usize, proof_type: &ProofType) -> bool {
+        let row = &self.rows[index];
+        !row.is_header
+            && match self.kind {
+                NodeKind::Branch => row.item_count == self.target_index as u64 + 1,
+                NodeKind::Extension => row.item == Some(ItemTag::Child),
+                NodeKind::Leaf => {
+                    row.item == Some(ItemTag::StorageRoot)
+                        && matches!(proof_type, ProofType::StorageChanged)
+                }
+                _ => false,
+            }
+    }
+
+    /// Returns whether the byte at `index` belongs to the value updated in a
+    /// leaf.
+    pub(crate) fn is_value_item(&self, index: usize, proof_type: &ProofType) -> bool {
+        let row = &self.rows[index];
+        !row.is_header
+            && matches!(
+                (row.item, proof_type),
+                (Some(ItemTag::Value), _)
+                    | (Some(ItemTag::Nonce), ProofType::NonceChanged)
+                    | (Some(ItemTag::Balance), ProofType::BalanceChanged)
+                    | (Some(ItemTag::CodeHash), ProofType::CodeHashExists)
+            )
+    }
+}
+
+/// The nodes of an update and the values that are constant in its rows.
+#[derive(Clone, Debug)]
+pub(crate) struct UpdateNodes {
+    pub(crate) nodes: Vec<Node>,
+    pub(crate) old_exists: bool,
+    pub(crate) new_exists: bool,
+    pub(crate) old_len: usize,
+    pub(crate) new_len: usize,
+    pub(crate) address_hash: H256,
+    pub(crate) slot_hash: H256,
+}
+
+impl UpdateNodes {
+    pub(crate) fn new(update: &MptUpdate) -> Result<Self, Error> {
+        let proof = update.proof.as_ref().ok_or_else(|| {
+            unsupported("updates without trie nodes can't be proved, build them from an MptState")
+        })?;
+        let proof_type = update.key.proof_type();
+        let is_storage = matches!(proof_type, ProofType::StorageChanged);
+
+        let address = update.key.account_address();
+        let address_hash = H256(mpt::account_key(&address));
+        let mut nodes = vec![
+            Node::key_part(Section::Address, NodeKind::Preimage, address.0.to_vec()),
+            Node::key_part(
+                Section::Address,
+                NodeKind::Key,
+                mpt::nibbles(address_hash.as_bytes()),
+            ),
+        ];
+        let slot_hash = match update.key.slot() {
+            Some(storage_key) if is_storage => {
+                let slot_hash = H256(mpt::storage_key(&storage_key));
+                nodes.push(Node::key_part(
+                    Section::Slot,
+                    NodeKind::Preimage,
+                    storage_key.to_be_bytes().to_vec(),
+                ));
+                nodes.push(Node::key_part(
+                    Section::Slot,
+                    NodeKind::Key,
+                    mpt::nibbles(slot_hash.as_bytes()),
+                ));
+                slot_hash
+            }
+            _ => H256::zero(),
+        };
+
+        let MptProof {
+            old_account_proof,
+            new_account_proof,
+            old_storage_proof,
+            new_storage_proof,
+        } = proof;
+        let mut paths = vec![];
+        for (section, path) in [
+            (Section::OldAccount, old_account_proof),
+            (Section::OldStorage, old_storage_proof),
+            (Section::NewAccount, new_account_proof),
+            (Section::NewStorage, new_storage_proof),
+        ] {
+            if section.is_storage() && !is_storage {
+                continue;
+            }
+            let key = if section.is_storage() {
+                slot_hash
+            } else {
+                address_hash
+            };
+            let path = Path::new(section, path, &key, &proof_type)?;
+            if !section.is_storage() && is_storage && !path.exists {
+                return Err(unsupported(
+                    "storage updates of accounts that don't exist are not supported",
+                ));
+            }
+            paths.push(path);
+        }
+
+        let target = |is_new: bool| {
+            paths
+                .iter()
+                .find(|path| {
+                    path.section.is_new() == is_new && path.section.is_storage() == is_storage
+                })
+                .expect("missing target path")
+        };
+        let (old_exists, old_len) = (target(false).exists, target(false).nodes.len());
+        let (new_exists, new_len) = (target(true).exists, target(true).nodes.len());
+        if new_len as i64 - old_len as i64 != new_exists as i64 - old_exists as i64 {
+            return Err(shape_change(old_exists, new_exists));
+        }
+        for old in paths.iter().filter(|path| !path.section.is_new()) {
+            let new = paths
+                .iter()
+                .find(|path| path.section == old.section.new_side())
+                .expect("missing new path");
+            let same_shape = old
+                .nodes
+                .iter()
+                .zip(new.nodes.iter())
+                .all(|(old, new)| old.kind == new.kind && old.target_index == new.target_index);
+            if !same_shape
+                || (old.section != target(false).section && old.nodes.len() != new.nodes.len())
+            {
+                return Err(shape_change(old.exists, new.exists));
+            }
+        }
+
+        for mut path in paths {
+            if path.section.is_new() && path.section.is_storage() == is_storage && !old_exists {
+                if let Some(last) = path.nodes.last_mut() {
+                    last.is_inserted = new_exists;
+                }
+            }
+            nodes.extend(path.nodes);
+        }
+
+        Ok(Self {
+            nodes,
+            old_exists,
+            new_exists,
+            old_len,
+            new_len,
+            address_hash,
+            slot_hash,
+        })
+    }
+}
+
+/// The nodes on the path to a key in a trie
+struct Path {
+    section: Section,
+    nodes: Vec<Node>,
+    exists: bool,
+}
+
+impl Path {
+    fn new(
+        section: Section,
+        proof: &[Vec<u8>],
+        key: &H256,
+        proof_type: &ProofType,
+    ) -> Result<Self, Error> {
+        if proof.is_empty() && !section.is_storage() {
+            return Err(unsupported("the state trie can't be empty"));
+        }
+        let key = mpt::nibbles(key.as_bytes());
+        let mut key_len = 0;
+        let mut exists = false;
+        let mut nodes: Vec<Node> = vec![];
+        for (depth, bytes) in proof.iter().enumerate() {
+            if let Some(parent) = nodes.last() {
+                if parent.child != Some(H256(keccak256(bytes))) {
+                    return Err(unsupported(
+                        "the proof nodes don't form a path, embedded nodes are not supported",
+                    ));
+                }
+            }
+            let (node, key_match) =
+                parse_node(section, depth, bytes, &key, &mut key_len, proof_type)?;
+            exists = key_match;
+            nodes.push(node);
+        }
+
+        if let Some(last) = nodes.last_mut() {
+            let is_terminal = match last.kind {
+                NodeKind::Leaf => true,
+                NodeKind::Branch => last.child_is_empty,
+                _ => false,
+            };
+            if !is_terminal {
+                return Err(unsupported(
+                    "proofs must end in a leaf or an empty branch child",
+                ));
+            }
+            last.is_last = true;
+        }
+        Ok(Self {
+            section,
+            nodes,
+            exists,
+        })
+    }
+}
+
+/// Parse a trie node into rows, advancing the number of key nibbles used by
+/// the path.  Returns the node and whether it is a leaf with the given key.
+fn parse_node(
+    section: Section,
+    depth: usize,
+    bytes: &[u8],
+    key: &[u8],
+    key_len: &mut usize,
+    proof_type: &ProofType,
+) -> Result<(Node, bool), Error> {
+    let rlp = Rlp::new(bytes);
+    let kind = match rlp.item_count() {
+        Ok(17) => NodeKind::Branch,
+        Ok(2) => {
+            let path = rlp
+                .at(0)
+                .and_then(|path| path.data())
+                .map_err(|_| unsupported("invalid trie node"))?;
+            match path.first().map(|flag| flag >> 4) {
+                Some(0 | 1) => NodeKind::Extension,
+                Some(2 | 3) => NodeKind::Leaf,
+                _ => return Err(unsupported("invalid hex prefix")),
+            }
+        }
+        _ => return Err(unsupported("invalid trie node")),
+    };
+
+    let mut parser = Parser {
+        bytes,
+        rows: vec![],
+        hdr_count: 0,
+        item_count: 0,
+        child_count: 0,
+    };
+    let mut node = Node {
+        section,
+        kind,
+        bytes: bytes.to_vec(),
+        rows: vec![],
+        depth,
+        target_index: 0,
+        child_is_empty: false,
+        child: None,
+        is_root: depth == 0,
+        is_last: false,
+        is_inserted: false,
+    };
+    let mut key_match = false;
+
+    parser.header()?;
+    match kind {
+        NodeKind::Branch => {
+            let target_index = *key
+                .get(*key_len)
+                .ok_or_else(|| unsupported("trie path longer than the key"))?;
+            *key_len += 1;
+            node.target_index = target_index;
+            for index in 0..17 {
+                let first = parser.peek()?;
+                if first != 0x80 && (first != 0xa0 || index == 16) {
+                    return Err(unsupported("embedded nodes are not supported"));
+                }
+                let payload = parser.item(None)?;
+                if index == target_index as usize {
+                    node.child_is_empty = first == 0x80;
+                    node.child = (first == 0xa0).then(|| H256::from_slice(payload));
+                }
+            }
+        }
+        NodeKind::Extension => {
+            let path = hex_prefix_nibbles(parser.item(Some(ItemTag::Path))?);
+            if !key[*key_len..].starts_with(&path) {
+                return Err(unsupported(
+                    "paths that diverge from the key in an extension node are not supported",
+                ));
+            }
+            *key_len += path.len();
+            if parser.peek()? != 0xa0 {
+                return Err(unsupported("embedded nodes are not supported"));
+            }
+            node.child = Some(H256::from_slice(parser.item(Some(ItemTag::Child))?));
+        }
+        NodeKind::Leaf => {
+            let path = hex_prefix_nibbles(parser.item(Some(ItemTag::Path))?);
+            key_match = *key_len + path.len() == KEY_NIBBLES && key[*key_len..] == path[..];
+            if section.is_storage() {
+                if parser.peek()? >= 0x80 {
+                    parser.header()?;
+                }
+                parser.item(Some(ItemTag::Value))?;
+            } else {
+                parser.header()?;
+                parser.header()?;
+                parser.item(Some(ItemTag::Nonce))?;
+                parser.item(Some(ItemTag::Balance))?;
+                let storage_root = parser.item(Some(ItemTag::StorageRoot))?;
+                if matches!(proof_type, ProofType::StorageChanged) {
+                    node.child = Some(H256::from_slice(storage_root));
+                }
+                parser.item(Some(ItemTag::CodeHash))?;
+            }
+        }
+        _ => unreachable!(),
+    }
+    if parser.rows.len() != bytes.len() {
+        return Err(unsupported("trailing bytes in trie node"));
+    }
+
+    node.rows = parser.rows;
+    set_key_steps(&mut node);
+    Ok((node, key_match))
+}
+
+/// Set the nibbles of the key given by each row of a node: the index of the
+/// child in a branch, and the nibbles of the hex prefix encoded paths.
+fn set_key_steps(node: &mut Node) {
+    let is_branch = node.kind == NodeKind::Branch;
+    let is_leaf = node.kind == NodeKind::Leaf;
+    let target_index = node.target_index;
+    let mut prev_is_item_start = false;
+    for (index, row) in node.rows.iter_mut().enumerate() {
+        let is_path = row.item == Some(ItemTag::Path);
+        row.is_hp_first = is_path
+            && ((row.is_item_start && row.byte < 0x80) || (row.is_payload && prev_is_item_start));
+        let is_odd = row.is_hp_first && (row.byte >> 4) - 2 * is_leaf as u8 == 1;
+        row.key_step_nibble = (is_branch && index == 0) || is_odd;
+        row.key_nibble = if is_branch {
+            target_index
+        } else if is_odd {
+            row.byte & 0xf
+        } else {
+            0
+        };
+        row.key_step_byte = is_path && row.is_payload && !prev_is_item_start;
+        prev_is_item_start = row.is_item_start;
+    }
+}
+
+/// Splits the RLP of a node into header and item rows.
+struct Parser<'a> {
+    bytes: &'a [u8],
+    rows: Vec<ByteRow>,
+    hdr_count: u64,
+    item_count: u64,
+    child_count: u64,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Result<u8, Error> {
+        self.bytes
+            .get(self.rows.len())
+            .copied()
+            .ok_or_else(|| unsupported("truncated trie node"))
+    }
+
+    /// Parse a list or string header, followed by the bytes of its length
+    fn header(&mut self) -> Result<(), Error> {
+        let first = self.peek()?;
+        if first < 0x80 {
+            return Err(unsupported("invalid header in trie node"));
+        }
+        let base = if first >= 0xc0 { 0xc0 } else { 0x80 };
+        let n_len_bytes = (first - base).saturating_sub(55) as usize;
+        if n_len_bytes > 2 {
+            return Err(unsupported("invalid header in trie node"));
+        }
+        self.hdr_count += 1;
+        let mut len = if n_len_bytes == 0 {
+            (first - base) as u64
+        } else {
+            0
+        };
+        for i in 0..=n_len_bytes {
+            let byte = self.peek()?;
+            if i > 0 {
+                len = len * 256 + byte as u64;
+            }
+            self.rows.push(ByteRow {
+                byte,
+                is_header: true,
+                is_hdr_start: i == 0,
+                hdr_rem: (n_len_bytes - i) as u64,
+                hdr_len: len,
+                hdr_count: self.hdr_count,
+                item_count: self.item_count,
+                child_count: self.child_count,
+                ..Default::default()
+            });
+        }
+        Ok(())
+    }
+
+    /// Parse a string item and return its payload
+    fn item(&mut self, item: Option<ItemTag>) -> Result<&'a [u8], Error> {
+        let start = self.rows.len();
+        let first = self.peek()?;
+        let len = match first {
+            0..=0x7f => 0,
+            0x80..=0xb7 => (first - 0x80) as usize,
+            _ => return Err(unsupported("unsupported item in trie node")),
+        };
+        self.item_count += 1;
+        if item.is_none() && first == 0xa0 {
+            self.child_count += 1;
+        }
+        for i in 0..=len {
+            let byte = self.peek()?;
+            self.rows.push(ByteRow {
+                byte,
+                is_item_start: i == 0,
+                is_payload: i > 0,
+                item_rem: (len - i) as u64,
+                item_count: self.item_count,
+                item,
+                hdr_count: self.hdr_count,
+                child_count: self.child_count,
+                ..Default::default()
+            });
+        }
+        Ok(if first < 0x80 {
+            &self.bytes[start..start + 1]
+        } else {
+            &self.bytes[start + 1..start + 1 + len]
+        })
+    }
+}
+
+/// Decode a hex prefix encoded path into nibbles
+fn hex_prefix_nibbles(encoded: &[u8]) -> Vec<u8> {
+    let mut nibbles = mpt::nibbles(encoded);
+    let is_odd = nibbles[0] & 1 == 1;
+    nibbles.drain(0..if is_odd { 1 } else { 2 });
+    nibbles
+}
+
+fn unsupported(reason: &'static str) -> Error {
+    Error::UnsupportedMptUpdate(reason)
+}
+
+/// Error for an update that changes the nodes on the path above the updated
+/// leaf, with the reason depending on whether it inserts or removes the key.
+fn shape_change(old_exists: bool, new_exists: bool) -> Error {
+    unsupported(match (old_exists, new_exists) {
+        (false, true) => {
+            "inserting a key next to an existing leaf splits it, which is not supported"
+        }
+        (true, false) => {
+            "removing a key from a branch with two children merges it, which is not supported"
+        }
+        _ => "updates that change the shape of the tries are not supported",
+    })
+}
@@ -0,0 +1,392 @@
+use super::MptCircuit;
+use crate::{
+    table::AccountFieldTag,
+    witness::{MptState, MptUpdates, Rw},
+};
+use bus_mapping::state_db::{self, StateDB};
+use eth_types::{
+    address, geth_types::Account, mpt, mpt::Trie, Address, Bytes, EIP1186ProofResponse, Error,
+    StorageProof, Word, H256, U256,
+};
+use ethers_core::utils::keccak256;
+use halo2_proofs::{
+    dev::{MockProver, VerifyFailure},
+    halo2curves::bn256::Fr,
+};
+use std::collections::HashMap;
+
+const K: u32 = 14;
+const N_ROWS: usize = 1 << K;
+
+fn accounts() -> Vec<Account> {
+    vec![
+        Account {
+            address: address!("0x00000000000000000000000000000000000000a1"),
+            nonce: Word::from(1),
+            balance: Word::from(1000),
+            code: Bytes::from(vec![0x60, 0x01, 0x00]),
+            storage: HashMap::from([
+                (Word::from(1), Word::from(5)),
+                (Word::from(2), Word::from(0x1234)),
+                (Word::from(3), U256::MAX),
+            ]),
+        },
+        Account {
+            address: address!("0x00000000000000000000000000000000000000a2"),
+            nonce: Word::from(3),
+            balance: Word::from(0x100000),
+            code: Bytes::default(),
+            storage: HashMap::new(),
+        },
+        Account {
+            address: address!("0x00000000000000000000000000000000000000a3"),
+            nonce: Word::zero(),
+            balance: Word::from(7),
+            code: Bytes::default(),
+            storage: HashMap::new(),
+        },
+    ]
+}
+
+fn account_row(address: Address, field_tag: AccountFieldTag, value: Word) -> Rw {
+    Rw::Account {
+        rw_counter: 1,
+        is_write: true,
+        account_address: address,
+        field_tag,
+        value,
+        value_prev: Word::zero(),
+    }
+}
+
+fn storage_row(address: Address, storage_key: Word, value: Word) -> Rw {
+    Rw::AccountStorage {
+        rw_counter: 1,
+        is_write: true,
+        account_address: address,
+        storage_key,
+        value,
+        value_prev: Word::zero(),
+        tx_id: 1,
+        committed_value: Word::zero(),
+    }
+}
+
+fn state_root() -> H256 {
+    MptState::new(&accounts()).unwrap().root()
+}
+
+fn updates(rows: &[Rw]) -> MptUpdates {
+    let mut state = MptState::new(&accounts()).unwrap();
+    MptUpdates::from_state(rows, &mut state).unwrap()
+}
+
+fn verify_updates(updates: MptUpdates) -> Result<(), Vec<VerifyFailure>> {
+    let circuit = MptCircuit::<Fr>::new(updates, N_ROWS);
+    let prover = MockProver::<Fr>::run(K, &circuit, vec![]).unwrap();
+    prover.verify()
+}
+
+fn verify(rows: &[Rw]) -> Result<(), Vec<VerifyFailure>> {
+    verify_updates(updates(rows))
+}
+
+#[test]
+fn no_updates() {
+    assert_eq!(verify(&[]), Ok(()));
+}
+
+#[test]
+fn nonce_update() {
+    let rows = [account_row(
+        address!("0x00000000000000000000000000000000000000a2"),
+        AccountFieldTag::Nonce,
+        Word::from(4),
+    )];
+    assert_eq!(verify(&rows), Ok(()));
+}
+
+#[test]
+fn balance_update() {
+    let rows = [account_row(
+        address!("0x00000000000000000000000000000000000000a1"),
+        AccountFieldTag::Balance,
+        Word::from(0x12345678),
+    )];
+    assert_eq!(verify(&rows), Ok(()));
+}
+
+#[test]
+fn code_hash_update() {
+    let rows = [account_row(
+        address!("0x00000000000000000000000000000000000000a3"),
+        AccountFieldTag::CodeHash,
+        Word::from(0xabcdef),
+    )];
+    assert_eq!(verify(&rows), Ok(()));
+}
+
+#[test]
+fn create_account() {
+    let rows = [account_row(
+        address!("0x00000000000000000000000000000000000000b1"),
+        AccountFieldTag::Balance,
+        Word::from(100),
+    )];
+    assert_eq!(verify(&rows), Ok(()));
+}
+
+#[test]
+fn empty_account_is_not_created() {
+    let rows = [account_row(
+        address!("0x00000000000000000000000000000000000000b1"),
+        AccountFieldTag::Nonce,
+        Word::zero(),
+    )];
+    assert_eq!(verify(&rows), Ok(()));
+}
+
+#[test]
+fn storage_updates() {
+    let address = address!("0x00000000000000000000000000000000000000a1");
+    let rows = [
+        storage_row(address, Word::from(1), Word::from(6)),
+        storage_row(address, Word::from(2), Word::zero()),
+        storage_row(address, Word::from(4), Word::from(0x80)),
+    ];
+    assert_eq!(verify(&rows), Ok(()));
+}
+
+#[test]
+fn storage_insert_in_empty_trie() {
+    let rows = [storage_row(
+        address!("0x00000000000000000000000000000000000000a2"),
+        Word::from(1),
+        Word::from(1),
+    )];
+    assert_eq!(verify(&rows), Ok(()));
+}
+
+#[test]
+fn chained_updates() {
+    let address = address!("0x00000000000000000000000000000000000000a1");
+    let rows = [
+        storage_row(address, Word::from(3), Word::from(9)),
+        account_row(address, AccountFieldTag::Nonce, Word::from(2)),
+        account_row(
+            address!("0x00000000000000000000000000000000000000a2"),
+            AccountFieldTag::Balance,
+            Word::zero(),
+        ),
+    ];
+    assert_eq!(verify(&rows), Ok(()));
+}
+
+#[test]
+fn wrong_new_value() {
+    let rows = [account_row(
+        address!("0x00000000000000000000000000000000000000a2"),
+        AccountFieldTag::Balance,
+        Word::from(5),
+    )];
+    let updates = updates(&rows)
+        .iter()
+        .cloned()
+        .map(|mut update| {
+            update.new_value = Word::from(6);
+            update
+        })
+        .collect();
+    assert!(verify_updates(updates).is_err());
+}
+
+#[test]
+fn wrong_new_root() {
+    let rows = [storage_row(
+        address!("0x00000000000000000000000000000000000000a1"),
+        Word::from(1),
+        Word::from(6),
+    )];
+    let updates = updates(&rows)
+        .iter()
+        .cloned()
+        .map(|mut update| {
+            update.new_root = update.old_root;
+            update
+        })
+        .collect();
+    assert!(verify_updates(updates).is_err());
+}
+
+#[test]
+fn mock_updates_are_rejected() {
+    let rows = [account_row(
+        address!("0x00000000000000000000000000000000000000a2"),
+        AccountFieldTag::Nonce,
+        Word::from(4),
+    )];
+    let circuit = MptCircuit::<Fr>::new(MptUpdates::mock_from(&rows), N_ROWS);
+    assert!(MockProver::<Fr>::run(K, &circuit, vec![]).is_err());
+}
+
+/// The initial state of `accounts()`, with the `eth_getProof` responses of all
+/// its accounts and storage slots.
+fn state_with_proofs() -> (StateDB, Vec<EIP1186ProofResponse>) {
+    let mut state_trie = Trie::new();
+    let mut sdb = StateDB::new();
+    let mut storage_tries = vec![];
+    for account in accounts() {
+        let mut storage = Trie::new();
+        for (key, value) in &account.storage {
+            storage
+                .insert(&mpt::storage_key(key), mpt::encode_storage_value(*value))
+                .unwrap();
+        }
+        let code_hash = H256(keccak256(&account.code));
+        state_trie
+            .insert(
+                &mpt::account_key(&account.address),
+                mpt::encode_account(
+                    account.nonce,
+                    account.balance,
+                    storage.root_hash(),
+                    code_hash,
+                ),
+            )
+            .unwrap();
+        sdb.set_account(
+            &account.address,
+            state_db::Account {
+                nonce: account.nonce,
+                balance: account.balance,
+                storage: account.storage.clone(),
+                code_hash,
+            },
+        );
+        storage_tries.push((account, storage, code_hash));
+    }
+
+    let to_bytes = |proof: Vec<Vec<u8>>| proof.into_iter().map(Bytes::from).collect();
+    let proofs = storage_tries
+        .into_iter()
+        .map(|(account, storage, code_hash)| EIP1186ProofResponse {
+            address: account.address,
+            balance: account.balance,
+            code_hash,
+            nonce: account.nonce,
+            storage_hash: storage.root_hash(),
+            account_proof: to_bytes(
+                state_trie
+                    .proof(&mpt::account_key(&account.address))
+                    .unwrap(),
+            ),
+            storage_proof: account
+                .storage
+                .iter()
+                .map(|(key, value)| StorageProof {
+                    key: *key,
+                    value: *value,
+                    proof: to_bytes(storage.proof(&mpt::storage_key(key)).unwrap()),
+                })
+                .collect(),
+        })
+        .collect();
+    (sdb, proofs)
+}
+
+#[test]
+fn updates_from_proofs() {
+    let address = address!("0x00000000000000000000000000000000000000a1");
+    let rows = [
+        storage_row(address, Word::from(1), Word::from(6)),
+        account_row(address, AccountFieldTag::Balance, Word::from(10)),
+        account_row(
+            address!("0x00000000000000000000000000000000000000a2"),
+            AccountFieldTag::Nonce,
+            Word::from(4),
+        ),
+    ];
+    let (sdb, proofs) = state_with_proofs();
+    let mut state = MptState::from_proofs(&sdb, &proofs, state_root()).unwrap();
+    let from_proofs = MptUpdates::from_state(&rows, &mut state).unwrap();
+
+    let roots = |updates: &MptUpdates| {
+        updates
+            .iter()
+            .map(|update| (update.old_root, update.new_root))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(roots(&from_proofs), roots(&updates(&rows)));
+    assert_eq!(verify_updates(from_proofs), Ok(()));
+}
+
+#[test]
+fn proofs_must_match_state_db() {
+    let (sdb, mut proofs) = state_with_proofs();
+    proofs[1].balance = proofs[1].balance + 1;
+    assert!(matches!(
+        MptState::from_proofs(&sdb, &proofs, state_root()),
+        Err(Error::InvalidStateProof(address)) if address == proofs[1].address
+    ));
+}
+
+#[test]
+fn proofs_must_start_at_prev_state_root() {
+    let (sdb, proofs) = state_with_proofs();
+    assert!(matches!(
+        MptState::from_proofs(&sdb, &proofs, H256::zero()),
+        Err(Error::PrevStateRootMismatch(prev_state_root, proofs_root))
+            if prev_state_root == H256::zero() && proofs_root == state_root()
+    ));
+}
+
+#[test]
+fn state_from_json_proofs() {
+    let (sdb, proofs) = state_with_proofs();
+    let json = serde_json::to_vec(&proofs).unwrap();
+    let state = MptState::from_proofs_json(&sdb, json.as_slice(), state_root()).unwrap();
+    assert_eq!(state.root(), state_root());
+}
+
+#[test]
+fn storage_update_of_non_existing_account_is_unsupported() {
+    let rows = [storage_row(
+        address!("0x00000000000000000000000000000000000000a9"),
+        Word::from(1),
+        Word::from(2),
+    )];
+    assert!(matches!(
+        MptCircuit::<Fr>::get_num_rows_required(&updates(&rows)),
+        Err(Error::UnsupportedMptUpdate(_))
+    ));
+}
+
+#[test]
+fn insert_splitting_a_leaf_is_unsupported() {
+    // The keys of the slots 3 and 10 both start with the nibble 0xc, so
+    // inserting the slot 10 replaces the leaf of the slot 3 with a branch.
+    let rows = [storage_row(
+        address!("0x00000000000000000000000000000000000000a1"),
+        Word::from(10),
+        Word::from(1),
+    )];
+    assert!(matches!(
+        MptCircuit::<Fr>::get_num_rows_required(&updates(&rows)),
+        Err(Error::UnsupportedMptUpdate(reason)) if reason.starts_with("inserting")
+    ));
+}
+
+#[test]
+fn delete_merging_a_branch_is_unsupported() {
+    // Removing the slots 1 and 2 leaves the slot 3 alone in the root branch of
+    // the storage trie, which is replaced by its leaf.
+    let address = address!("0x00000000000000000000000000000000000000a1");
+    let rows = [
+        storage_row(address, Word::from(1), Word::zero()),
+        storage_row(address, Word::from(2), Word::zero()),
+    ];
+    assert!(matches!(
+        MptCircuit::<Fr>::get_num_rows_required(&updates(&rows)),
+        Err(Error::UnsupportedMptUpdate(reason)) if reason.starts_with("removing")
+    ));
+}
@@ -1,31 +1,61 @@
 //! Public Input Circuit implementation
 
-use std::marker::PhantomData;
+use std::{iter, marker::PhantomData};
 
-use eth_types::geth_types::BlockConstants;
-use eth_types::geth_types::GethData;
 use eth_types::sign_types::SignData;
 use eth_types::H256;
 use eth_types::{
-    geth_types::Transaction, Address, Field, ToBigEndian, ToLittleEndian, ToScalar, Word,
+    geth_types::Transaction, Address, Field, ToBigEndian, ToLittleEndian, ToScalar, ToWord, Word,
 };
-use ethers_core::types::Block;
-use halo2_proofs::plonk::Instance;
+use ethers_core::utils::keccak256;
+use gadgets::util::Expr;
+use halo2_proofs::plonk::{Expression, Fixed, Instance};
 
-use crate::table::BlockTable;
 use crate::table::TxFieldTag;
 use crate::table::TxTable;
-use crate::util::random_linear_combine_word as rlc;
+use crate::table::{BlockContextFieldTag, BlockTable, DynamicTableColumns};
+use crate::util::{random_linear_combine_word as rlc, Challenges, SubCircuit, SubCircuitConfig};
+use crate::witness::{self, BlockContexts};
 use halo2_proofs::{
     circuit::{AssignedCell, Layouter, Region, SimpleFloorPlanner, Value},
-    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Selector},
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, SecondPhase, Selector},
     poly::Rotation,
 };
+use itertools::Itertools;
+
+/// Maximum number of blocks in a batch
+pub const MAX_INNER_BLOCKS: usize = 8;
 
 /// Fixed by the spec
-const TX_LEN: usize = 9;
-const BLOCK_LEN: usize = 7 + 256;
-const EXTRA_LEN: usize = 2;
+const TX_LEN: usize = 10;
+/// Raw public inputs of a block slot, with the difficulty and the base fee
+/// split in their hi and lo halves
+const BLOCK_FIELDS_LEN: usize = 8;
+const BLOCK_LEN: usize = BLOCK_FIELDS_LEN * MAX_INNER_BLOCKS + 2 + 2 * 256;
+const EXTRA_LEN: usize = 2 * (1 + 2 * MAX_INNER_BLOCKS);
+
+/// Rows of a block slot in the block table
+const BLOCK_TABLE_FIELDS_LEN: usize = 6;
+/// Block hashes of the block table: the ones of the 256 blocks before the
+/// first block and of the block slots but the last one
+const N_BLOCK_HASHES: usize = 256 + MAX_INNER_BLOCKS - 1;
+/// Rows of the block table, in the layout of
+/// `BlockContexts::table_assignments`
+const BLOCK_TABLE_LEN: usize = 1 + BLOCK_TABLE_FIELDS_LEN * MAX_INNER_BLOCKS + 1 + N_BLOCK_HASHES;
+/// Words of the block table: the difficulty and the base fee of each block
+/// slot, the chain id and the block hashes
+const N_WORDS: usize = 2 * MAX_INNER_BLOCKS + 1 + N_BLOCK_HASHES;
+/// Words decomposed after the ones of the block table: the state roots before
+/// and after the batch, and before and after the chunk of the batch, whose
+/// RLCs are linked to the State Circuit in the Super Circuit
+const N_STATE_ROOT_WORDS: usize = 4;
+/// Rows of the byte decomposition of a word, with 4 bytes per row
+const WORD_ROWS: usize = 8;
+
+/// Splits a word in its hi and lo 128 bits
+fn hi_lo<F: Field>(word: Word) -> [F; 2] {
+    [(word >> 128).low_u128(), word.low_u128()].map(F::from_u128)
+}
 
 /// Values of the block table (as in the spec)
 #[derive(Clone, Default, Debug)]
@@ -36,8 +66,6 @@ pub struct BlockValues {
     timestamp: u64,
     difficulty: Word,
     base_fee: Word, // NOTE: BaseFee was added by EIP-1559 and is ignored in legacy headers.
-    chain_id: u64,
-    history_hashes: Vec<H256>,
 }
 
 /// Values of the tx table (as in the spec)
@@ -52,74 +80,72 @@ pub struct TxValues {
     value: Word,
     call_data_len: u64,
     tx_sign_hash: [u8; 32],
+    block_number: u64,
 }
 
 /// Extra values (not contained in block or tx tables)
 #[derive(Default, Debug, Clone)]
 pub struct ExtraValues {
-    // block_hash: H256,
-    state_root: H256,
     prev_state_root: H256,
+    // Hash and state root after each block, padded with the state root of the
+    // last block
+    block_hashes: Vec<H256>,
+    state_roots: Vec<H256>,
 }
 
 /// PublicData contains all the values that the PiCircuit recieves as input
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct PublicData {
-    /// List of tranactions
+    /// List of tranactions of the blocks
     pub txs: Vec<Transaction>,
-    /// Information of Ethereum block
-    pub extra: GethData,
-    /// Constants related to Ethereum block
-    pub block_constants: BlockConstants,
-    /// Previous block root
+    /// Contexts of the consecutive blocks of the batch, with their hash and
+    /// the state root after them
+    pub block_contexts: BlockContexts,
+    /// State root before the first block
     pub prev_state_root: H256,
+    /// Id of the transaction before the first one of `txs`, which are the
+    /// transactions of a chunk of the batch when it's proven in chunks
+    pub tx_id_offset: usize,
+    /// State roots before and after the chunk of the batch, when it's proven
+    /// in chunks
+    pub chunk_state_roots: Option<[H256; 2]>,
 }
 
-impl Default for PublicData {
-    fn default() -> Self {
-        let geth_data = GethData {
-            chain_id: Word::default(),
-            history_hashes: Vec::default(),
-            eth_block: Block::default(),
-            geth_traces: Vec::default(),
-            accounts: Vec::default(),
-        };
-        Self {
-            txs: Vec::new(),
-            extra: geth_data,
-            block_constants: BlockConstants::default(),
-            prev_state_root: H256::default(),
-        }
+impl PublicData {
+    /// Returns the chain id of the blocks
+    pub fn chain_id(&self) -> Word {
+        self.block_contexts.chain_id()
     }
-}
 
-impl PublicData {
-    /// Returns struct with values for the block table
-    pub fn get_block_table_values(&self) -> BlockValues {
-        let mut history_hashes: Vec<H256> = self
-            .extra
-            .history_hashes
+    /// Returns the hashes of the 256 blocks before the first block, padded
+    /// with zeros at the front
+    pub fn history_hashes(&self) -> Vec<Word> {
+        let mut history_hashes = self.block_contexts.block_hashes();
+        history_hashes.truncate(256);
+        history_hashes
+    }
+
+    /// Returns struct with values for the block table of each block slot
+    pub fn get_block_table_values(&self) -> Vec<BlockValues> {
+        assert!(self.block_contexts.ctxs.len() <= MAX_INNER_BLOCKS);
+        self.block_contexts
+            .slots()
             .iter()
-            .map(|&hash| H256::from(hash.to_be_bytes()))
-            .collect();
-        history_hashes.extend(vec![H256::zero(); 256 - history_hashes.len()]);
-        BlockValues {
-            coinbase: self.block_constants.coinbase,
-            gas_limit: self.block_constants.gas_limit.as_u64(),
-            number: self.block_constants.number.as_u64(),
-            timestamp: self.block_constants.timestamp.as_u64(),
-            difficulty: self.block_constants.difficulty,
-            base_fee: self.block_constants.base_fee,
-            chain_id: self.extra.chain_id.as_u64(),
-            history_hashes,
-        }
+            .map(|ctx| BlockValues {
+                coinbase: ctx.coinbase,
+                gas_limit: ctx.gas_limit,
+                number: ctx.number.as_u64(),
+                timestamp: ctx.timestamp.as_u64(),
+                difficulty: ctx.difficulty,
+                base_fee: ctx.base_fee,
+            })
+            .collect()
     }
 
     /// Returns struct with values for the tx table
     pub fn get_tx_table_values(&self) -> Vec<TxValues> {
         let chain_id: u64 = self
-            .extra
-            .chain_id
+            .chain_id()
             .try_into()
             .expect("Error converting chain_id to u64");
         let mut tx_vals = vec![];
@@ -139,6 +165,7 @@ impl PublicData {
                 value: tx.value,
                 call_data_len: tx.call_data.0.len() as u64,
                 tx_sign_hash: msg_hash_le,
+                block_number: tx.block_number,
             });
         }
         tx_vals
@@ -146,18 +173,196 @@ impl PublicData {
 
     /// Returns struct with the extra values
     pub fn get_extra_values(&self) -> ExtraValues {
+        let mut block_hashes: Vec<H256> = self
+            .block_contexts
+            .ctxs
+            .values()
+            .map(|ctx| ctx.hash)
+            .collect();
+        let mut state_roots: Vec<H256> = self
+            .block_contexts
+            .ctxs
+            .values()
+            .map(|ctx| ctx.state_root)
+            .collect();
+        block_hashes.resize(MAX_INNER_BLOCKS, H256::zero());
+        state_roots.resize(
+            MAX_INNER_BLOCKS,
+            state_roots.last().copied().unwrap_or(self.prev_state_root),
+        );
         ExtraValues {
-            // block_hash: self.extra.eth_block.hash.unwrap_or_else(H256::zero),
-            state_root: self.extra.eth_block.state_root,
             prev_state_root: self.prev_state_root,
+            block_hashes,
+            state_roots,
         }
     }
+
+    /// Returns the state root after the last block
+    pub fn state_root(&self) -> H256 {
+        self.get_extra_values().state_roots[MAX_INNER_BLOCKS - 1]
+    }
+
+    /// Returns the state roots before and after the chunk of the batch, which
+    /// are the ones before and after the batch when it's not chunked
+    pub fn chunk_state_roots(&self) -> [H256; 2] {
+        self.chunk_state_roots
+            .unwrap_or_else(|| [self.prev_state_root, self.state_root()])
+    }
+
+    /// Returns the block section of the raw public inputs, after the zero
+    /// row: the fields of each block slot, the chain id and the hashes of the
+    /// 256 blocks before the first one, with the words split in their hi and
+    /// lo halves.
+    fn block_raw_values<F: Field>(&self) -> Vec<(&'static str, F)> {
+        let mut values = Vec::with_capacity(BLOCK_LEN);
+        for block in self.get_block_table_values() {
+            let [difficulty_hi, difficulty_lo] = hi_lo(block.difficulty);
+            let [base_fee_hi, base_fee_lo] = hi_lo(block.base_fee);
+            values.extend([
+                ("coinbase", block.coinbase.to_scalar().unwrap()),
+                ("timestamp", F::from(block.timestamp)),
+                ("number", F::from(block.number)),
+                ("difficulty_hi", difficulty_hi),
+                ("difficulty_lo", difficulty_lo),
+                ("gas_limit", F::from(block.gas_limit)),
+                ("base_fee_hi", base_fee_hi),
+                ("base_fee_lo", base_fee_lo),
+            ]);
+        }
+        let [chain_id_hi, chain_id_lo] = hi_lo(self.chain_id());
+        values.extend([("chain_id_hi", chain_id_hi), ("chain_id_lo", chain_id_lo)]);
+        for prev_hash in self.history_hashes() {
+            let [hi, lo] = hi_lo(prev_hash);
+            values.extend([("prev_hash_hi", hi), ("prev_hash_lo", lo)]);
+        }
+        assert_eq!(values.len(), BLOCK_LEN);
+        values
+    }
+
+    /// Returns the extra section of the raw public inputs: the previous state
+    /// root, then the hash and the state root of each block slot, split in
+    /// their hi and lo halves.
+    fn extra_raw_values<F: Field>(&self) -> Vec<(&'static str, F)> {
+        let extra = self.get_extra_values();
+        let hashes = iter::once(("parent_block.state_root", extra.prev_state_root)).chain(
+            extra
+                .block_hashes
+                .iter()
+                .zip_eq(extra.state_roots.iter())
+                .flat_map(|(block_hash, state_root)| {
+                    [("block.hash", *block_hash), ("state.root", *state_root)]
+                }),
+        );
+        let values = hashes
+            .flat_map(|(name, hash)| {
+                let [hi, lo] = hi_lo(hash.to_word());
+                [(name, hi), (name, lo)]
+            })
+            .collect_vec();
+        assert_eq!(values.len(), EXTRA_LEN);
+        values
+    }
+
+    /// Returns the words of the block table, in the order of their byte
+    /// decomposition, with the offset of their hi half in the raw public
+    /// inputs.  The hashes of the block slots are in the extra section.
+    fn block_table_words(&self) -> Vec<(Word, usize)> {
+        let slot_offset = |slot: usize| 1 + BLOCK_FIELDS_LEN * slot;
+        let chain_id_offset = slot_offset(MAX_INNER_BLOCKS);
+        let extra_offset = BLOCK_LEN + 1;
+        let words = self
+            .get_block_table_values()
+            .into_iter()
+            .enumerate()
+            .flat_map(|(slot, block)| {
+                [
+                    (block.difficulty, slot_offset(slot) + 3),
+                    (block.base_fee, slot_offset(slot) + 6),
+                ]
+            })
+            .chain(iter::once((self.chain_id(), chain_id_offset)))
+            .chain(
+                self.block_contexts
+                    .block_hashes()
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, hash)| match i.checked_sub(256) {
+                        None => (hash, chain_id_offset + 2 + 2 * i),
+                        Some(slot) => (hash, extra_offset + 2 + 4 * slot),
+                    }),
+            )
+            .collect_vec();
+        assert_eq!(words.len(), N_WORDS);
+        words
+    }
+
+    /// Returns the state roots before and after the batch, with the offset of
+    /// their hi half in the raw public inputs, followed by the ones before and
+    /// after the chunk of the batch, which are not in the raw public inputs.
+    fn state_root_words(&self) -> [(Word, Option<usize>); N_STATE_ROOT_WORDS] {
+        let [chunk_prev_state_root, chunk_state_root] = self.chunk_state_roots();
+        [
+            (self.prev_state_root.to_word(), Some(BLOCK_LEN + 1)),
+            (
+                self.state_root().to_word(),
+                Some(BLOCK_LEN + 1 + EXTRA_LEN - 2),
+            ),
+            (chunk_prev_state_root.to_word(), None),
+            (chunk_state_root.to_word(), None),
+        ]
+    }
+
+    /// Returns the randomness of the RLC of the words of the tx section and
+    /// of the raw public inputs, derived from a hash of the public data so
+    /// that the verifier can recompute them and the prover can't choose them.
+    /// The words of the block table are split in hi and lo halves instead.
+    pub fn get_randomness<F: Field>(&self) -> (F, F) {
+        let extra = self.get_extra_values();
+        let mut bytes = Vec::new();
+        for block in self.get_block_table_values() {
+            bytes.extend_from_slice(block.coinbase.as_bytes());
+            for value in [block.gas_limit, block.number, block.timestamp] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            for word in [block.difficulty, block.base_fee] {
+                bytes.extend_from_slice(&word.to_le_bytes());
+            }
+        }
+        for word in [self.chain_id()].iter().chain(&self.history_hashes()) {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes.extend_from_slice(extra.prev_state_root.as_bytes());
+        for hash in extra
+            .block_hashes
+            .iter()
+            .chain(&extra.state_roots)
+            .chain(&self.chunk_state_roots())
+        {
+            bytes.extend_from_slice(hash.as_bytes());
+        }
+        bytes.extend_from_slice(&(self.tx_id_offset as u64).to_le_bytes());
+        // The signing hash covers all the fields of a transaction but its
+        // sender and its block.
+        for tx in self.get_tx_table_values() {
+            bytes.extend_from_slice(tx.from_addr.as_bytes());
+            bytes.extend_from_slice(&tx.tx_sign_hash);
+            bytes.extend_from_slice(&tx.block_number.to_le_bytes());
+        }
+
+        let [randomness, rand_rpi] = [0u8, 1].map(|domain| {
+            let mut wide = [0u8; 64];
+            for (i, half) in wide.chunks_mut(32).enumerate() {
+                half.copy_from_slice(&keccak256([&bytes[..], &[domain, i as u8]].concat()));
+            }
+            F::from_bytes_wide(&wide)
+        });
+        (randomness, rand_rpi)
+    }
 }
 
 /// Config for PiCircuit
 #[derive(Clone, Debug)]
 pub struct PiCircuitConfig<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> {
-    q_block_table: Selector,
     block_table: BlockTable,
     q_tx_table: Selector,
     tx_table: TxTable,
@@ -167,17 +372,53 @@ pub struct PiCircuitConfig<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: u
     q_not_end: Selector,
     q_end: Selector,
 
-    pi: Column<Instance>, // rpi_rand, rpi_rlc, chain_ID, state_root, prev_state_root
+    // Byte decomposition of the words of the block table
+    q_word: Column<Fixed>,
+    q_word_first: Column<Fixed>,
+    q_word_half: Column<Fixed>,
+    word_bytes: [Column<Advice>; 4],
+    word_int: Column<Advice>,
+    word_rlc: Column<Advice>,
+    byte_table: Column<Fixed>,
+
+    // Numbers of the blocks of the block hashes of the block table
+    q_hash_number: Column<Fixed>,
+    q_hash_number_first: Column<Fixed>,
+    hash_number: Column<Advice>,
+
+    // rpi_rand, rpi_rlc, chain_ID, state_root hi/lo, prev_state_root hi/lo,
+    // chunk state_root hi/lo, chunk prev_state_root hi/lo
+    pi: Column<Instance>,
 
     _marker: PhantomData<F>,
 }
 
-impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize>
-    PiCircuitConfig<F, MAX_TXS, MAX_CALLDATA>
-{
-    fn new(meta: &mut ConstraintSystem<F>, block_table: BlockTable, tx_table: TxTable) -> Self {
-        let q_block_table = meta.selector();
+/// Circuit configuration arguments
+pub struct PiCircuitConfigArgs<F: Field> {
+    /// BlockTable
+    pub block_table: BlockTable,
+    /// TxTable
+    pub tx_table: TxTable,
+    /// Challenges
+    pub challenges: Challenges<Expression<F>>,
+}
 
+impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> SubCircuitConfig<F>
+    for PiCircuitConfig<F, MAX_TXS, MAX_CALLDATA>
+{
+    type ConfigArgs = PiCircuitConfigArgs<F>;
+
+    /// Return a new PiCircuitConfig.  Every cell of the `block_table` is
+    /// copy constrained, to a constant tag and to the raw public inputs, so
+    /// the table can be shared with the EVM Circuit.
+    fn new(
+        meta: &mut ConstraintSystem<F>,
+        Self::ConfigArgs {
+            block_table,
+            tx_table,
+            challenges,
+        }: Self::ConfigArgs,
+    ) -> Self {
         let q_tx_table = meta.selector();
 
         let raw_public_inputs = meta.advice_column();
@@ -186,11 +427,27 @@ impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize>
         let q_not_end = meta.selector();
         let q_end = meta.selector();
 
+        let [q_word, q_word_first, q_word_half, byte_table] = [(); 4].map(|_| meta.fixed_column());
+        let word_bytes = [(); 4].map(|_| meta.advice_column());
+        let word_int = meta.advice_column();
+        let word_rlc = meta.advice_column_in(SecondPhase);
+        let [q_hash_number, q_hash_number_first] = [(); 2].map(|_| meta.fixed_column());
+        let hash_number = meta.advice_column();
+
         let pi = meta.instance_column();
 
+        let constants = meta.fixed_column();
+        meta.enable_constant(constants);
+
+        for column in block_table.columns() {
+            meta.enable_equality(column);
+        }
         meta.enable_equality(raw_public_inputs);
         meta.enable_equality(rpi_rlc_acc);
         meta.enable_equality(rand_rpi);
+        meta.enable_equality(word_int);
+        meta.enable_equality(word_rlc);
+        meta.enable_equality(hash_number);
         meta.enable_equality(pi);
 
         // 0.0 rpi_rlc_acc[0] == RLC(raw_public_inputs, rand_rpi)
@@ -227,13 +484,78 @@ impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize>
             vec![q_not_end * (cur_rand_rpi - next_rand_rpi)]
         });
 
-        // 0.2 Block table -> value column match with raw_public_inputs at expected
-        // offset
-        meta.create_gate("block_table[i] = raw_public_inputs[offset + i]", |meta| {
-            let q_block_table = meta.query_selector(q_block_table);
-            let block_value = meta.query_advice(block_table.value, Rotation::cur());
-            let rpi_block_value = meta.query_advice(raw_public_inputs, Rotation::cur());
-            vec![q_block_table * (block_value - rpi_block_value)]
+        // 0.2 Block table -> every cell matches a constant tag, the
+        // raw_public_inputs at the expected offset or the byte decomposition
+        // of a word.  This is done with copy constraints in
+        // `constrain_block_table`, because the block table is assigned in
+        // its own region.
+
+        // The bytes of a word, 4 per row from the most significant one, are
+        // accumulated in `word_int` for each 128 bits half and in `word_rlc`
+        // for the `evm_word` RLC of the word.
+        meta.create_gate("word byte decomposition", |meta| {
+            let q_word = meta.query_fixed(q_word, Rotation::cur());
+            let q_word_first = meta.query_fixed(q_word_first, Rotation::cur());
+            let q_word_half = meta.query_fixed(q_word_half, Rotation::cur());
+            let bytes = word_bytes.map(|column| meta.query_advice(column, Rotation::cur()));
+            let r = challenges.evm_word();
+
+            let int = bytes
+                .iter()
+                .fold(0.expr(), |acc, byte| acc * 256.expr() + byte.clone());
+            let rlc = bytes
+                .iter()
+                .fold(0.expr(), |acc, byte| acc * r.clone() + byte.clone());
+            let prev_int = meta.query_advice(word_int, Rotation::prev())
+                * Expression::Constant(F::from(1 << 32));
+            let prev_rlc = meta.query_advice(word_rlc, Rotation::prev())
+                * r.clone()
+                * r.clone()
+                * r.clone()
+                * r;
+
+            vec![
+                q_word.clone()
+                    * (meta.query_advice(word_int, Rotation::cur())
+                        - (1.expr() - q_word_first.clone() - q_word_half) * prev_int
+                        - int),
+                q_word
+                    * (meta.query_advice(word_rlc, Rotation::cur())
+                        - (1.expr() - q_word_first) * prev_rlc
+                        - rlc),
+            ]
+        });
+        meta.lookup_any("word byte range", |meta| {
+            let q_word = meta.query_fixed(q_word, Rotation::cur());
+            let byte_table = meta.query_fixed(byte_table, Rotation::cur());
+            word_bytes
+                .iter()
+                .map(|column| {
+                    (
+                        q_word.clone() * meta.query_advice(*column, Rotation::cur()),
+                        byte_table.clone(),
+                    )
+                })
+                .collect()
+        });
+
+        // The block hashes of the block table are the ones of the blocks of
+        // number `number - 256 + i`, where `number` is the number of the
+        // first block slot.  Their numbers are at the first row of their word.
+        let first_hash_row = (WORD_ROWS * (2 * MAX_INNER_BLOCKS + 1)) as i32;
+        meta.create_gate("hash_number[i] = number - 256 + i", |meta| {
+            let q_hash_number = meta.query_fixed(q_hash_number, Rotation::cur());
+            let q_hash_number_first = meta.query_fixed(q_hash_number_first, Rotation::cur());
+            let hash_number = meta.query_advice(hash_number, Rotation::cur());
+            let prev_hash_number = meta.query_advice(hash_number, Rotation(-(WORD_ROWS as i32)));
+            // The number of the first block slot is at the offset 3 of the
+            // raw public inputs.
+            let number = meta.query_advice(raw_public_inputs, Rotation(3 - first_hash_row));
+
+            vec![
+                q_hash_number * (hash_number.clone() - prev_hash_number - 1.expr()),
+                q_hash_number_first * (hash_number - number + 256.expr()),
+            ]
         });
 
         let offset = BLOCK_LEN + 1 + EXTRA_LEN;
@@ -285,7 +607,6 @@ impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize>
         );
 
         Self {
-            q_block_table,
             block_table,
             q_tx_table,
             tx_table,
@@ -294,18 +615,40 @@ impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize>
             rand_rpi,
             q_not_end,
             q_end,
+            q_word,
+            q_word_first,
+            q_word_half,
+            word_bytes,
+            word_int,
+            word_rlc,
+            byte_table,
+            q_hash_number,
+            q_hash_number_first,
+            hash_number,
             pi,
             _marker: PhantomData,
         }
     }
+}
 
-    /// Return the number of rows in the circuit
+impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize>
+    PiCircuitConfig<F, MAX_TXS, MAX_CALLDATA>
+{
+    /// Return the number of raw public inputs
     #[inline]
-    fn circuit_len() -> usize {
+    pub(crate) fn circuit_len() -> usize {
         // +1 empty row in block table, +1 empty row in tx_table
         BLOCK_LEN + 1 + EXTRA_LEN + 3 * (TX_LEN * MAX_TXS + 1 + MAX_CALLDATA)
     }
 
+    /// Return the number of rows in the circuit, which also holds the byte
+    /// decomposition of the words of the block table
+    pub(crate) fn num_rows() -> usize {
+        Self::circuit_len()
+            .max(WORD_ROWS * (N_WORDS + N_STATE_ROOT_WORDS))
+            .max(256)
+    }
+
     /// Assigns a tx_table row and stores the values in a vec for the
     /// raw_public_inputs column
     #[allow(clippy::too_many_arguments)]
@@ -382,222 +725,195 @@ impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize>
         Ok(())
     }
 
-    /// Assigns the values for block table in the block_table column
-    /// and in the raw_public_inputs column. A copy is also stored in
-    /// a vector for computing RLC(raw_public_inputs)
-    fn assign_block_table(
+    /// Assigns the block and the extra sections of the raw_public_inputs
+    /// column, see `PublicData::block_raw_values` and
+    /// `PublicData::extra_raw_values`, and stores a copy in a vector for
+    /// computing RLC(raw_public_inputs).  Returns the assigned cells.
+    fn assign_block_extra_fields(
         &self,
         region: &mut Region<'_, F>,
-        block_values: BlockValues,
-        randomness: F,
+        public_data: &PublicData,
         raw_pi_vals: &mut [F],
-    ) -> Result<AssignedCell<F, F>, Error> {
-        let mut offset = 0;
-        for i in 0..BLOCK_LEN + 1 {
-            self.q_block_table.enable(region, offset + i)?;
-        }
-
-        // zero row
-        region.assign_advice(
-            || "zero",
-            self.block_table.value,
-            offset,
-            || Value::known(F::zero()),
-        )?;
-        region.assign_advice(
-            || "zero",
-            self.raw_public_inputs,
-            offset,
-            || Value::known(F::zero()),
-        )?;
-        raw_pi_vals[offset] = F::zero();
-        offset += 1;
-
-        // coinbase
-        let coinbase = block_values.coinbase.to_scalar().unwrap();
-        region.assign_advice(
-            || "coinbase",
-            self.block_table.value,
-            offset,
-            || Value::known(coinbase),
-        )?;
-        region.assign_advice(
-            || "coinbase",
-            self.raw_public_inputs,
-            offset,
-            || Value::known(coinbase),
-        )?;
-        raw_pi_vals[offset] = coinbase;
-        offset += 1;
-
-        // gas_limit
-        let gas_limit = F::from(block_values.gas_limit);
-        region.assign_advice(
-            || "gas_limit",
-            self.block_table.value,
-            offset,
-            || Value::known(gas_limit),
-        )?;
-        region.assign_advice(
-            || "gas_limit",
-            self.raw_public_inputs,
-            offset,
-            || Value::known(gas_limit),
-        )?;
-        raw_pi_vals[offset] = gas_limit;
-        offset += 1;
-
-        // number
-        let number = F::from(block_values.number);
-        region.assign_advice(
-            || "number",
-            self.block_table.value,
-            offset,
-            || Value::known(number),
-        )?;
-        region.assign_advice(
-            || "number",
-            self.raw_public_inputs,
-            offset,
-            || Value::known(number),
-        )?;
-        raw_pi_vals[offset] = number;
-        offset += 1;
-
-        // timestamp
-        let timestamp = F::from(block_values.timestamp);
-        region.assign_advice(
-            || "timestamp",
-            self.block_table.value,
-            offset,
-            || Value::known(timestamp),
-        )?;
-        region.assign_advice(
-            || "timestamp",
-            self.raw_public_inputs,
-            offset,
-            || Value::known(timestamp),
-        )?;
-        raw_pi_vals[offset] = timestamp;
-        offset += 1;
-
-        // difficulty
-        let difficulty = rlc(block_values.difficulty.to_le_bytes(), randomness);
-        region.assign_advice(
-            || "difficulty",
-            self.block_table.value,
-            offset,
-            || Value::known(difficulty),
-        )?;
-        region.assign_advice(
-            || "difficulty",
-            self.raw_public_inputs,
-            offset,
-            || Value::known(difficulty),
-        )?;
-        raw_pi_vals[offset] = difficulty;
-        offset += 1;
-
-        // base_fee
-        let base_fee = rlc(block_values.base_fee.to_le_bytes(), randomness);
-        region.assign_advice(
-            || "base_fee",
-            self.block_table.value,
-            offset,
-            || Value::known(base_fee),
-        )?;
-        region.assign_advice(
-            || "base_fee",
-            self.raw_public_inputs,
-            offset,
-            || Value::known(base_fee),
-        )?;
-        raw_pi_vals[offset] = base_fee;
-        offset += 1;
-
-        // chain_id
-        let chain_id = F::from(block_values.chain_id);
-        region.assign_advice(
-            || "chain_id",
-            self.block_table.value,
-            offset,
-            || Value::known(chain_id),
-        )?;
-        let chain_id_cell = region.assign_advice(
-            || "chain_id",
-            self.raw_public_inputs,
-            offset,
-            || Value::known(chain_id),
-        )?;
-        raw_pi_vals[offset] = chain_id;
-        offset += 1;
-
-        for prev_hash in block_values.history_hashes {
-            let prev_hash = rlc(prev_hash.to_fixed_bytes(), randomness);
-            region.assign_advice(
-                || "prev_hash",
-                self.block_table.value,
-                offset,
-                || Value::known(prev_hash),
-            )?;
-            region.assign_advice(
-                || "prev_hash",
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        let values = iter::once(("zero", F::zero()))
+            .chain(public_data.block_raw_values())
+            .chain(public_data.extra_raw_values());
+
+        let mut cells = Vec::with_capacity(BLOCK_LEN + 1 + EXTRA_LEN);
+        for (offset, (name, value)) in values.enumerate() {
+            cells.push(region.assign_advice(
+                || name,
                 self.raw_public_inputs,
                 offset,
-                || Value::known(prev_hash),
+                || Value::known(value),
+            )?);
+            raw_pi_vals[offset] = value;
+        }
+        Ok(cells)
+    }
+
+    /// Assigns the byte decomposition of the `words`, the ones of the block
+    /// table followed by the state roots, 4 big-endian bytes per row, and the
+    /// byte table.  Returns the cells of the hi and lo halves of
+    /// each word and of its `evm_word` RLC.
+    fn assign_words(
+        &self,
+        region: &mut Region<'_, F>,
+        words: impl IntoIterator<Item = Word>,
+        challenges: &Challenges<Value<F>>,
+    ) -> Result<Vec<[AssignedCell<F, F>; 3]>, Error> {
+        for byte in 0..256 {
+            region.assign_fixed(
+                || "byte table",
+                self.byte_table,
+                byte,
+                || Value::known(F::from(byte as u64)),
             )?;
-            raw_pi_vals[offset] = prev_hash;
-            offset += 1;
         }
 
-        Ok(chain_id_cell)
+        let mut word_cells = Vec::with_capacity(N_WORDS + N_STATE_ROOT_WORDS);
+        for (i, word) in words.into_iter().enumerate() {
+            let mut int = F::zero();
+            let mut rlc = Value::known(F::zero());
+            let mut cells = Vec::with_capacity(3);
+            for (row, bytes) in word.to_be_bytes().chunks(4).enumerate() {
+                let offset = WORD_ROWS * i + row;
+                for (name, column, enabled) in [
+                    ("q_word", self.q_word, true),
+                    ("q_word_first", self.q_word_first, row == 0),
+                    ("q_word_half", self.q_word_half, row == WORD_ROWS / 2),
+                ] {
+                    region.assign_fixed(
+                        || name,
+                        column,
+                        offset,
+                        || Value::known(F::from(enabled as u64)),
+                    )?;
+                }
+                if row == WORD_ROWS / 2 {
+                    int = F::zero();
+                }
+                for (column, byte) in self.word_bytes.iter().zip(bytes) {
+                    let byte = F::from(*byte as u64);
+                    region.assign_advice(|| "word byte", *column, offset, || Value::known(byte))?;
+                    int = int * F::from(256) + byte;
+                    rlc = rlc * challenges.evm_word() + Value::known(byte);
+                }
+                let int_cell = region.assign_advice(
+                    || "word int",
+                    self.word_int,
+                    offset,
+                    || Value::known(int),
+                )?;
+                let rlc_cell =
+                    region.assign_advice(|| "word rlc", self.word_rlc, offset, || rlc)?;
+                if row == WORD_ROWS / 2 - 1 || row == WORD_ROWS - 1 {
+                    cells.push(int_cell);
+                }
+                if row == WORD_ROWS - 1 {
+                    cells.push(rlc_cell);
+                }
+            }
+            word_cells.push(cells.try_into().unwrap());
+        }
+        assert_eq!(word_cells.len(), N_WORDS + N_STATE_ROOT_WORDS);
+        Ok(word_cells)
     }
 
-    /// Assigns the extra fields (not in block or tx tables):
-    ///   - state root
-    ///   - previous block state root
-    /// to the raw_public_inputs column and stores a copy in a
-    /// vector for computing RLC(raw_public_inputs).
-    fn assign_extra_fields(
+    /// Assigns the numbers of the blocks of the block hashes of the block
+    /// table, at the first row of the byte decomposition of their word, from
+    /// the number of the first block slot.  Returns the assigned cells.
+    fn assign_hash_numbers(
         &self,
         region: &mut Region<'_, F>,
-        extra: ExtraValues,
-        randomness: F,
-        raw_pi_vals: &mut [F],
-    ) -> Result<[AssignedCell<F, F>; 2], Error> {
-        let mut offset = BLOCK_LEN + 1;
-        // block hash
-        // let block_hash = rlc(extra.block_hash.to_fixed_bytes(), randomness);
-        // region.assign_advice(
-        //     || "block.hash",
-        //     self.raw_public_inputs,
-        //     offset,
-        //     || Ok(block_hash),
-        // )?;
-        // raw_pi_vals[offset] = block_hash;
-        // offset += 1;
-
-        // block state root
-        let state_root = rlc(extra.state_root.to_fixed_bytes(), randomness);
-        let state_root_cell = region.assign_advice(
-            || "state.root",
-            self.raw_public_inputs,
-            offset,
-            || Value::known(state_root),
-        )?;
-        raw_pi_vals[offset] = state_root;
-        offset += 1;
+        first_number: u64,
+    ) -> Result<Vec<AssignedCell<F, F>>, Error> {
+        let first_word = 2 * MAX_INNER_BLOCKS + 1;
+        (0..N_BLOCK_HASHES)
+            .map(|i| {
+                let offset = WORD_ROWS * (first_word + i);
+                let (name, q_hash_number) = match i {
+                    0 => ("q_hash_number_first", self.q_hash_number_first),
+                    _ => ("q_hash_number", self.q_hash_number),
+                };
+                region.assign_fixed(|| name, q_hash_number, offset, || Value::known(F::one()))?;
+                region.assign_advice(
+                    || "hash_number",
+                    self.hash_number,
+                    offset,
+                    || Value::known(F::from(first_number) - F::from(256) + F::from(i as u64)),
+                )
+            })
+            .collect()
+    }
 
-        // previous block state root
-        let prev_state_root = rlc(extra.prev_state_root.to_fixed_bytes(), randomness);
-        let prev_state_root_cell = region.assign_advice(
-            || "parent_block.hash",
-            self.raw_public_inputs,
-            offset,
-            || Value::known(prev_state_root),
-        )?;
-        raw_pi_vals[offset] = prev_state_root;
-        Ok([state_root_cell, prev_state_root_cell])
+    /// Constrains the `block_table_cells`, in the layout of
+    /// `BlockContexts::table_assignments`: the tags to constants, the indices
+    /// to the numbers of the block slots in the `raw_cells` or to the
+    /// `hash_number_cells`, and the values to the `raw_cells`, or to the RLC
+    /// of the `word_cells` for the words, whose halves are constrained to the
+    /// `raw_cells` at the offsets of `PublicData::block_table_words`.
+    fn constrain_block_table(
+        &self,
+        region: &mut Region<'_, F>,
+        public_data: &PublicData,
+        block_table_cells: &[[AssignedCell<F, F>; 3]],
+        raw_cells: &[AssignedCell<F, F>],
+        word_cells: &[[AssignedCell<F, F>; 3]],
+        hash_number_cells: &[AssignedCell<F, F>],
+    ) -> Result<(), Error> {
+        for ((_, offset), [hi, lo, _]) in public_data.block_table_words().iter().zip_eq(word_cells)
+        {
+            region.constrain_equal(hi.cell(), raw_cells[*offset].cell())?;
+            region.constrain_equal(lo.cell(), raw_cells[offset + 1].cell())?;
+        }
+
+        let zero = &raw_cells[0];
+        let mut words = word_cells.iter().map(|[_, _, rlc]| rlc);
+        let mut rows = Vec::with_capacity(BLOCK_TABLE_LEN);
+        rows.push((0, zero, zero));
+        for slot in 0..MAX_INNER_BLOCKS {
+            let raw = |field: usize| &raw_cells[1 + BLOCK_FIELDS_LEN * slot + field];
+            let number = raw(2);
+            rows.extend([
+                (BlockContextFieldTag::Coinbase as u64, number, raw(0)),
+                (BlockContextFieldTag::Timestamp as u64, number, raw(1)),
+                (BlockContextFieldTag::Number as u64, number, number),
+                (
+                    BlockContextFieldTag::Difficulty as u64,
+                    number,
+                    words.next().unwrap(),
+                ),
+                (BlockContextFieldTag::GasLimit as u64, number, raw(5)),
+                (
+                    BlockContextFieldTag::BaseFee as u64,
+                    number,
+                    words.next().unwrap(),
+                ),
+            ]);
+        }
+        rows.push((
+            BlockContextFieldTag::ChainId as u64,
+            zero,
+            words.next().unwrap(),
+        ));
+        for number in hash_number_cells {
+            rows.push((
+                BlockContextFieldTag::BlockHash as u64,
+                number,
+                words.next().unwrap(),
+            ));
+        }
+        assert_eq!(rows.len(), BLOCK_TABLE_LEN);
+
+        for ([tag, index, value], (expected_tag, expected_index, expected_value)) in
+            block_table_cells.iter().zip_eq(rows)
+        {
+            region.constrain_constant(tag.cell(), F::from(expected_tag))?;
+            region.constrain_equal(index.cell(), expected_index.cell())?;
+            region.constrain_equal(value.cell(), expected_value.cell())?;
+        }
+        Ok(())
     }
 
     /// Assign `rpi_rlc_acc` and `rand_rpi` columns
@@ -664,9 +980,11 @@ impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize>
 }
 
 /// Public Inputs Circuit
-#[derive(Default)]
+#[derive(Default, Debug)]
 pub struct PiCircuit<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> {
-    /// Randomness for RLC encdoing
+    /// Randomness for the RLC encoding of words in the raw public inputs.
+    /// Unlike the circuit challenges it must be known to the verifier, since
+    /// the verifier recomputes the raw public inputs.
     pub randomness: F,
 
     /// Randomness for PI encoding
@@ -676,49 +994,156 @@ pub struct PiCircuit<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize>
     pub public_data: PublicData,
 }
 
-impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> Circuit<F>
-    for PiCircuit<F, MAX_TXS, MAX_CALLDATA>
+impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize>
+    PiCircuit<F, MAX_TXS, MAX_CALLDATA>
 {
-    type Config = PiCircuitConfig<F, MAX_TXS, MAX_CALLDATA>;
-    type FloorPlanner = SimpleFloorPlanner;
-
-    fn without_witnesses(&self) -> Self {
-        Self::default()
+    /// Return a new PiCircuit, with the randomness derived from the public
+    /// data.
+    pub fn new(public_data: PublicData) -> Self {
+        let (randomness, rand_rpi) = public_data.get_randomness();
+        Self {
+            randomness,
+            rand_rpi,
+            public_data,
+        }
     }
 
-    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
-        let block_table = BlockTable::construct(meta);
-        let tx_table = TxTable::construct(meta);
-        PiCircuitConfig::new(meta, block_table, tx_table)
+    /// Compute the raw_public_inputs column from the verifier's perspective.
+    pub fn raw_public_inputs(&self) -> Vec<F> {
+        let public_data = &self.public_data;
+        let randomness = self.randomness;
+        let txs = public_data.get_tx_table_values();
+
+        let mut result =
+            vec![F::zero(); BLOCK_LEN + 1 + EXTRA_LEN + 3 * (TX_LEN * MAX_TXS + 1 + MAX_CALLDATA)];
+
+        //  Insert Block Values after the zero row, then the Extra Values
+        for (offset, (_, value)) in public_data
+            .block_raw_values()
+            .into_iter()
+            .chain(public_data.extra_raw_values())
+            .enumerate()
+        {
+            result[1 + offset] = value;
+        }
+
+        // Insert Tx table
+        let mut offset = 0;
+        assert!(txs.len() < MAX_TXS);
+        let tx_default = TxValues::default();
+
+        let tx_table_len = TX_LEN * MAX_TXS + 1 + MAX_CALLDATA;
+
+        let id_offset = BLOCK_LEN + 1 + EXTRA_LEN;
+        let index_offset = id_offset + tx_table_len;
+        let value_offset = index_offset + tx_table_len;
+
+        // Insert zero row
+        result[id_offset + offset] = F::zero();
+        result[index_offset + offset] = F::zero();
+        result[value_offset + offset] = F::zero();
+
+        offset += 1;
+
+        for i in 0..MAX_TXS {
+            let tx = if i < txs.len() { &txs[i] } else { &tx_default };
+
+            for val in &[
+                rlc(tx.nonce.to_le_bytes(), randomness),
+                rlc(tx.gas.to_le_bytes(), randomness),
+                rlc(tx.gas_price.to_le_bytes(), randomness),
+                tx.from_addr.to_scalar().expect("tx.from too big"),
+                tx.to_addr.to_scalar().expect("tx.to too big"),
+                F::from(tx.is_create),
+                rlc(tx.value.to_le_bytes(), randomness),
+                F::from(tx.call_data_len),
+                rlc(tx.tx_sign_hash, randomness),
+                F::from(tx.block_number),
+            ] {
+                result[id_offset + offset] = F::from((public_data.tx_id_offset + i + 1) as u64);
+                result[index_offset + offset] = F::zero();
+                result[value_offset + offset] = *val;
+
+                offset += 1;
+            }
+        }
+        // Tx Table CallData
+        let mut calldata_count = 0;
+        for (i, tx) in public_data.txs.iter().enumerate() {
+            for (index, byte) in tx.call_data.0.iter().enumerate() {
+                assert!(calldata_count < MAX_CALLDATA);
+                result[id_offset + offset] = F::from((public_data.tx_id_offset + i + 1) as u64);
+                result[index_offset + offset] = F::from(index as u64);
+                result[value_offset + offset] = F::from(*byte as u64);
+                offset += 1;
+                calldata_count += 1;
+            }
+        }
+        for _ in calldata_count..MAX_CALLDATA {
+            result[id_offset + offset] = F::zero();
+            result[index_offset + offset] = F::zero();
+            result[value_offset + offset] = F::zero();
+            offset += 1;
+        }
+
+        result
     }
 
-    fn synthesize(
+    /// Assign the Public Inputs Circuit, constraining the `block_table_cells`
+    /// returned by `BlockTable::load` to its block and extra sections.
+    /// Returns the cells of the `evm_word` RLCs of the state roots before and
+    /// after the batch, and before and after its chunk.
+    fn assign(
         &self,
-        config: Self::Config,
-        mut layouter: impl Layouter<F>,
-    ) -> Result<(), Error> {
-        let pi_cells = layouter.assign_region(
+        config: &PiCircuitConfig<F, MAX_TXS, MAX_CALLDATA>,
+        layouter: &mut impl Layouter<F>,
+        block_table_cells: &[[AssignedCell<F, F>; 3]],
+        challenges: &Challenges<Value<F>>,
+    ) -> Result<[[AssignedCell<F, F>; 2]; 2], Error> {
+        let (pi_cells, state_root_cells) = layouter.assign_region(
             || "region 0",
             |mut region| {
-                let circuit_len = Self::Config::circuit_len();
+                let circuit_len = PiCircuitConfig::<F, MAX_TXS, MAX_CALLDATA>::circuit_len();
                 let mut raw_pi_vals = vec![F::zero(); circuit_len];
 
-                // Assign block table
-                let block_values = self.public_data.get_block_table_values();
-                let chain_id = config.assign_block_table(
+                // Assign block table and extra fields
+                let raw_cells = config.assign_block_extra_fields(
                     &mut region,
-                    block_values,
-                    self.randomness,
+                    &self.public_data,
                     &mut raw_pi_vals,
                 )?;
-
-                // Assign extra fields
-                let extra_vals = self.public_data.get_extra_values();
-                let [state_root, prev_state_root] = config.assign_extra_fields(
+                let word_cells = config.assign_words(
                     &mut region,
-                    extra_vals,
-                    self.randomness,
-                    &mut raw_pi_vals,
+                    self.public_data
+                        .block_table_words()
+                        .into_iter()
+                        .chain(self.public_data.state_root_words())
+                        .map(|(word, _)| word),
+                    challenges,
+                )?;
+                let (word_cells, state_root_word_cells) = word_cells.split_at(N_WORDS);
+                for ((_, offset), [hi, lo, _]) in self
+                    .public_data
+                    .state_root_words()
+                    .iter()
+                    .zip_eq(state_root_word_cells)
+                {
+                    if let Some(offset) = offset {
+                        region.constrain_equal(hi.cell(), raw_cells[*offset].cell())?;
+                        region.constrain_equal(lo.cell(), raw_cells[offset + 1].cell())?;
+                    }
+                }
+                let hash_number_cells = config.assign_hash_numbers(
+                    &mut region,
+                    self.public_data.block_contexts.first_number(),
+                )?;
+                config.constrain_block_table(
+                    &mut region,
+                    &self.public_data,
+                    block_table_cells,
+                    &raw_cells,
+                    word_cells,
+                    &hash_number_cells,
                 )?;
 
                 let mut offset = 0;
@@ -770,11 +1195,12 @@ impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> Circuit<F>
                             TxFieldTag::TxSignHash,
                             rlc(tx.tx_sign_hash, self.randomness),
                         ),
+                        (TxFieldTag::BlockNumber, F::from(tx.block_number)),
                     ] {
                         config.assign_tx_row(
                             &mut region,
                             offset,
-                            i + 1,
+                            self.public_data.tx_id_offset + i + 1,
                             *tag,
                             0,
                             *value,
@@ -791,7 +1217,7 @@ impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> Circuit<F>
                         config.assign_tx_row(
                             &mut region,
                             offset,
-                            i + 1,
+                            self.public_data.tx_id_offset + i + 1,
                             TxFieldTag::CallData,
                             index,
                             F::from(*byte as u64),
@@ -818,13 +1244,34 @@ impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> Circuit<F>
                 let (rpi_rand, rpi_rlc) =
                     config.assign_rlc_pi(&mut region, self.rand_rpi, raw_pi_vals)?;
 
-                Ok(vec![
+                // The chain id is a u64, so its hi half is zero.
+                let chain_id_lo = 1 + BLOCK_FIELDS_LEN * MAX_INNER_BLOCKS + 1;
+                let state_root = BLOCK_LEN + 1 + EXTRA_LEN - 2;
+                let prev_state_root = BLOCK_LEN + 1;
+                let (chunk_prev_state_root, chunk_state_root) =
+                    (&state_root_word_cells[2], &state_root_word_cells[3]);
+                let pi_cells = [
                     rpi_rand,
                     rpi_rlc,
-                    chain_id,
-                    state_root,
-                    prev_state_root,
-                ])
+                    raw_cells[chain_id_lo].clone(),
+                    raw_cells[state_root].clone(),
+                    raw_cells[state_root + 1].clone(),
+                    raw_cells[prev_state_root].clone(),
+                    raw_cells[prev_state_root + 1].clone(),
+                    chunk_state_root[0].clone(),
+                    chunk_state_root[1].clone(),
+                    chunk_prev_state_root[0].clone(),
+                    chunk_prev_state_root[1].clone(),
+                ];
+                let [prev_state_root, state_root, chunk_prev_state_root, chunk_state_root] =
+                    [0, 1, 2, 3].map(|i| state_root_word_cells[i][2].clone());
+                Ok((
+                    pi_cells,
+                    [
+                        [prev_state_root, state_root],
+                        [chunk_prev_state_root, chunk_state_root],
+                    ],
+                ))
             },
         )?;
 
@@ -833,8 +1280,141 @@ impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> Circuit<F>
             layouter.constrain_instance(pi_cell.cell(), config.pi, i)?;
         }
 
+        Ok(state_root_cells)
+    }
+
+    /// Make the assignments to the PiCircuit like `synthesize_sub`, and
+    /// return the cells of the `evm_word` RLCs of the state roots before and
+    /// after the batch, and before and after its chunk, to link them to the
+    /// State Circuit.
+    pub(crate) fn synthesize_with_state_roots(
+        &self,
+        config: &PiCircuitConfig<F, MAX_TXS, MAX_CALLDATA>,
+        challenges: &Challenges<Value<F>>,
+        layouter: &mut impl Layouter<F>,
+    ) -> Result<[[AssignedCell<F, F>; 2]; 2], Error> {
+        let block_table_cells =
+            config
+                .block_table
+                .load(layouter, &self.public_data.block_contexts, challenges)?;
+        self.assign(config, layouter, &block_table_cells, challenges)
+    }
+}
+
+impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> SubCircuit<F>
+    for PiCircuit<F, MAX_TXS, MAX_CALLDATA>
+{
+    type Config = PiCircuitConfig<F, MAX_TXS, MAX_CALLDATA>;
+
+    /// The previous state root is the one of the witness block, which is only
+    /// known when its MPT updates are built from the tries of the state.  The
+    /// transactions of a chunk of a block are the ones of the chunk, without
+    /// the last one of the previous chunk of its tx table.
+    fn new_from_block(block: &witness::Block<F>) -> Self {
+        let (n_prev_chunk_txs, chunk_state_roots) = match &block.chunk {
+            Some(chunk) => (
+                chunk.txs.start - chunk.tx_table_txs().start,
+                Some([chunk.prev_state_root, chunk.state_root]),
+            ),
+            None => (0, None),
+        };
+        Self::new(PublicData {
+            txs: block.eth_txs[n_prev_chunk_txs..].to_vec(),
+            block_contexts: block.context.clone(),
+            prev_state_root: block.prev_state_root,
+            tx_id_offset: block.tx_table_txs()[n_prev_chunk_txs..]
+                .first()
+                .map_or(0, |tx| tx.id - 1),
+            chunk_state_roots,
+        })
+    }
+
+    /// Compute the public inputs of the circuit, in the order of the `pi`
+    /// instance column: rand_rpi, rpi_rlc, chain_id, and the hi and lo halves
+    /// of the state_root, of the prev_state_root, and of the state roots after
+    /// and before the chunk.
+    fn instance(&self) -> Vec<Vec<F>> {
+        let rpi_rlc = self
+            .raw_public_inputs()
+            .iter()
+            .rev()
+            .fold(F::zero(), |acc, val| acc * self.rand_rpi + val);
+
+        let [_, chain_id] = hi_lo(self.public_data.chain_id());
+        let [state_root_hi, state_root_lo] = hi_lo(self.public_data.state_root().to_word());
+        let [prev_state_root_hi, prev_state_root_lo] =
+            hi_lo(self.public_data.prev_state_root.to_word());
+        let [chunk_prev_state_root, chunk_state_root] = self.public_data.chunk_state_roots();
+        let [chunk_state_root_hi, chunk_state_root_lo] = hi_lo(chunk_state_root.to_word());
+        let [chunk_prev_state_root_hi, chunk_prev_state_root_lo] =
+            hi_lo(chunk_prev_state_root.to_word());
+        vec![vec![
+            self.rand_rpi,
+            rpi_rlc,
+            chain_id,
+            state_root_hi,
+            state_root_lo,
+            prev_state_root_hi,
+            prev_state_root_lo,
+            chunk_state_root_hi,
+            chunk_state_root_lo,
+            chunk_prev_state_root_hi,
+            chunk_prev_state_root_lo,
+        ]]
+    }
+
+    /// Make the assignments to the PiCircuit, including the block table
+    fn synthesize_sub(
+        &self,
+        config: &Self::Config,
+        challenges: &Challenges<Value<F>>,
+        layouter: &mut impl Layouter<F>,
+    ) -> Result<(), Error> {
+        self.synthesize_with_state_roots(config, challenges, layouter)?;
         Ok(())
     }
+
+    fn min_num_rows_block(_block: &witness::Block<F>) -> usize {
+        PiCircuitConfig::<F, MAX_TXS, MAX_CALLDATA>::num_rows()
+    }
+}
+
+impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> Circuit<F>
+    for PiCircuit<F, MAX_TXS, MAX_CALLDATA>
+{
+    type Config = (PiCircuitConfig<F, MAX_TXS, MAX_CALLDATA>, Challenges);
+    type FloorPlanner = SimpleFloorPlanner;
+
+    fn without_witnesses(&self) -> Self {
+        Self::default()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let block_table = BlockTable::construct(meta);
+        let tx_table = TxTable::construct(meta);
+        let challenges = Challenges::construct(meta);
+        let challenges_expr = challenges.exprs(meta);
+        (
+            PiCircuitConfig::new(
+                meta,
+                PiCircuitConfigArgs {
+                    block_table,
+                    tx_table,
+                    challenges: challenges_expr,
+                },
+            ),
+            challenges,
+        )
+    }
+
+    fn synthesize(
+        &self,
+        (config, challenges): Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let challenges = challenges.values(&mut layouter);
+        self.synthesize_sub(&config, &challenges, &mut layouter)
+    }
 }
 
 #[cfg(test)]
@@ -842,6 +1422,7 @@ mod pi_circuit_test {
     use super::*;
 
     use crate::test_util::rand_tx;
+    use crate::witness::BlockContext;
     use halo2_proofs::{
         dev::{MockProver, VerifyFailure},
         halo2curves::bn256::Fr,
@@ -850,119 +1431,6 @@ mod pi_circuit_test {
     use rand::SeedableRng;
     use rand_chacha::ChaCha20Rng;
 
-    /// Compute the raw_public_inputs column from the verifier's perspective.
-    fn raw_public_inputs_col<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize>(
-        public_data: &PublicData,
-        randomness: F, // For RLC encoding
-    ) -> Vec<F> {
-        let block = public_data.get_block_table_values();
-        let extra = public_data.get_extra_values();
-        let txs = public_data.get_tx_table_values();
-
-        let mut offset = 0;
-        let mut result =
-            vec![F::zero(); BLOCK_LEN + 1 + EXTRA_LEN + 3 * (TX_LEN * MAX_TXS + 1 + MAX_CALLDATA)];
-
-        //  Insert Block Values
-        // zero row
-        result[offset] = F::zero();
-        offset += 1;
-        // coinbase
-        let mut coinbase_bytes = [0u8; 32];
-        coinbase_bytes[12..].clone_from_slice(block.coinbase.as_bytes());
-        result[offset] = rlc(coinbase_bytes, randomness);
-        offset += 1;
-        // gas_limit
-        result[offset] = F::from(block.gas_limit);
-        offset += 1;
-        // number
-        result[offset] = F::from(block.number);
-        offset += 1;
-        // timestamp
-        result[offset] = F::from(block.timestamp);
-        offset += 1;
-        // difficulty
-        result[offset] = rlc(block.difficulty.to_le_bytes(), randomness);
-        offset += 1;
-        // base_fee
-        result[offset] = rlc(block.base_fee.to_le_bytes(), randomness);
-        offset += 1;
-        // chain_id
-        result[offset] = F::from(block.chain_id);
-        offset += 1;
-        // Previous block hashes
-        for prev_hash in block.history_hashes {
-            result[offset] = rlc(prev_hash.to_fixed_bytes(), randomness);
-            offset += 1;
-        }
-
-        // Insert Extra Values
-        // block Root
-        result[BLOCK_LEN] = rlc(extra.state_root.to_fixed_bytes(), randomness);
-        // parent block hash
-        result[BLOCK_LEN + 1] = rlc(extra.prev_state_root.to_fixed_bytes(), randomness);
-
-        // Insert Tx table
-        offset = 0;
-        assert!(txs.len() < MAX_TXS);
-        let tx_default = TxValues::default();
-
-        let tx_table_len = TX_LEN * MAX_TXS + 1 + MAX_CALLDATA;
-
-        let id_offset = BLOCK_LEN + 1 + EXTRA_LEN;
-        let index_offset = id_offset + tx_table_len;
-        let value_offset = index_offset + tx_table_len;
-
-        // Insert zero row
-        result[id_offset + offset] = F::zero();
-        result[index_offset + offset] = F::zero();
-        result[value_offset + offset] = F::zero();
-
-        offset += 1;
-
-        for i in 0..MAX_TXS {
-            let tx = if i < txs.len() { &txs[i] } else { &tx_default };
-
-            for val in &[
-                rlc(tx.nonce.to_le_bytes(), randomness),
-                rlc(tx.gas.to_le_bytes(), randomness),
-                rlc(tx.gas_price.to_le_bytes(), randomness),
-                tx.from_addr.to_scalar().expect("tx.from too big"),
-                tx.to_addr.to_scalar().expect("tx.to too big"),
-                F::from(tx.is_create),
-                rlc(tx.value.to_le_bytes(), randomness),
-                F::from(tx.call_data_len),
-                rlc(tx.tx_sign_hash, randomness),
-            ] {
-                result[id_offset + offset] = F::from((i + 1) as u64);
-                result[index_offset + offset] = F::zero();
-                result[value_offset + offset] = *val;
-
-                offset += 1;
-            }
-        }
-        // Tx Table CallData
-        let mut calldata_count = 0;
-        for (i, tx) in public_data.txs.iter().enumerate() {
-            for (index, byte) in tx.call_data.0.iter().enumerate() {
-                assert!(calldata_count < MAX_CALLDATA);
-                result[id_offset + offset] = F::from((i + 1) as u64);
-                result[index_offset + offset] = F::from(index as u64);
-                result[value_offset + offset] = F::from(*byte as u64);
-                offset += 1;
-                calldata_count += 1;
-            }
-        }
-        for _ in calldata_count..MAX_CALLDATA {
-            result[id_offset + offset] = F::zero();
-            result[index_offset + offset] = F::zero();
-            result[value_offset + offset] = F::zero();
-            offset += 1;
-        }
-
-        result
-    }
-
     fn run<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize>(
         k: u32,
         public_data: PublicData,
@@ -971,44 +1439,18 @@ mod pi_circuit_test {
         let randomness = F::random(&mut rng);
 
         let rand_rpi = F::random(&mut rng);
-        let rlc_rpi_col =
-            raw_public_inputs_col::<F, MAX_TXS, MAX_CALLDATA>(&public_data, randomness);
-        assert_eq!(
-            rlc_rpi_col.len(),
-            BLOCK_LEN + 1 + EXTRA_LEN + 3 * (TX_LEN * MAX_TXS + 1 + MAX_CALLDATA)
-        );
-
-        // Computation of raw_pulic_inputs
-        let rlc_rpi = rlc_rpi_col
-            .iter()
-            .rev()
-            .fold(F::zero(), |acc, val| acc * rand_rpi + val);
-
-        // let block_hash = public_data
-        //     .extra
-        //     .eth_block
-        //     .hash
-        //     .unwrap_or_else(H256::zero)
-        //     .to_fixed_bytes();
-
-        let public_inputs = vec![
-            rand_rpi,
-            rlc_rpi,
-            F::from(public_data.extra.chain_id.as_u64()),
-            rlc(
-                public_data.extra.eth_block.state_root.to_fixed_bytes(),
-                randomness,
-            ),
-            rlc(public_data.prev_state_root.to_fixed_bytes(), randomness),
-        ];
-
         let circuit = PiCircuit::<F, MAX_TXS, MAX_CALLDATA> {
             randomness,
             rand_rpi,
             public_data,
         };
+        assert_eq!(
+            circuit.raw_public_inputs().len(),
+            BLOCK_LEN + 1 + EXTRA_LEN + 3 * (TX_LEN * MAX_TXS + 1 + MAX_CALLDATA)
+        );
+        let public_inputs = circuit.instance();
 
-        let prover = match MockProver::run(k, &circuit, vec![public_inputs]) {
+        let prover = match MockProver::run(k, &circuit, public_inputs) {
             Ok(prover) => prover,
             Err(e) => panic!("{:#?}", e),
         };
@@ -1034,7 +1476,11 @@ mod pi_circuit_test {
 
         let mut public_data = PublicData::default();
         let chain_id = 1337u64;
-        public_data.extra.chain_id = Word::from(chain_id);
+        public_data.block_contexts = BlockContext {
+            chain_id: Word::from(chain_id),
+            ..Default::default()
+        }
+        .into();
 
         let n_tx = 2;
         for _ in 0..n_tx {
@@ -1044,4 +1490,27 @@ mod pi_circuit_test {
         let k = 13;
         assert_eq!(run::<Fr, MAX_TXS, MAX_CALLDATA>(k, public_data), Ok(()));
     }
+
+    #[test]
+    fn test_pi_with_history_hashes() {
+        const MAX_TXS: usize = 2;
+        const MAX_CALLDATA: usize = 8;
+
+        let number = 100u64;
+        let public_data = PublicData {
+            block_contexts: BlockContext {
+                number: number.into(),
+                difficulty: Word::MAX,
+                base_fee: Word::from(1_000_000_000u64),
+                chain_id: Word::from(1337u64),
+                history_hashes: (0..number).map(|i| Word::from(0xbeefcafe + i)).collect(),
+                ..Default::default()
+            }
+            .into(),
+            ..Default::default()
+        };
+
+        let k = 13;
+        assert_eq!(run::<Fr, MAX_TXS, MAX_CALLDATA>(k, public_data), Ok(()));
+    }
 }
@@ -10,15 +10,18 @@ mod test;
 use crate::{
     evm_circuit::param::N_BYTES_WORD,
     table::{LookupTable, MptTable, RwTable, RwTableTag},
-    util::{power_of_randomness_from_instance, Expr},
-    witness::{MptUpdates, Rw, RwMap},
+    util::{Challenges, Expr, SubCircuit, SubCircuitConfig},
+    witness::{self, MptUpdates, Rw, RwMap},
 };
 use constraint_builder::{ConstraintBuilder, Queries};
 use eth_types::{Address, Field};
 use gadgets::binary_number::{BinaryNumberChip, BinaryNumberConfig};
 use halo2_proofs::{
-    circuit::{Layouter, Region, SimpleFloorPlanner, Value},
-    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Expression, Fixed, VirtualCells},
+    circuit::{AssignedCell, Layouter, Region, SimpleFloorPlanner, Value},
+    plonk::{
+        Advice, Circuit, Column, ConstraintSystem, Error, Expression, Fixed, Instance, SecondPhase,
+        VirtualCells,
+    },
     poly::Rotation,
 };
 use lexicographic_ordering::Config as LexicographicOrderingConfig;
@@ -35,6 +38,12 @@ const N_LIMBS_RW_COUNTER: usize = 2;
 const N_LIMBS_ACCOUNT_ADDRESS: usize = 10;
 const N_LIMBS_ID: usize = 2;
 
+/// Number of rows of the rw table exposed as public inputs, see
+/// [`witness::Block::chunk_boundary_rws`].
+const N_EXPOSED_ROWS: usize = 2;
+/// Number of columns of the rw table
+const N_RW_TABLE_COLUMNS: usize = 11;
+
 /// Config for StateCircuit
 #[derive(Clone)]
 pub struct StateCircuitConfig<F> {
@@ -50,16 +59,35 @@ pub struct StateCircuitConfig<F> {
     lexicographic_ordering: LexicographicOrderingConfig,
     lookups: LookupsConfig,
     power_of_randomness: [Expression<F>; N_BYTES_WORD - 1],
+    // Enabled at the first row of each of the rw table rows exposed in the
+    // instance column, which are looked up in the rw table.
+    exposed: Column<Fixed>,
+    instance: Column<Instance>,
 }
 
-impl<F: Field> StateCircuitConfig<F> {
-    /// Configure StateCircuit
-    pub fn configure(
+/// Circuit configuration arguments
+pub struct StateCircuitConfigArgs<F: Field> {
+    /// RwTable
+    pub rw_table: RwTable,
+    /// MptTable
+    pub mpt_table: MptTable,
+    /// Challenges
+    pub challenges: Challenges<Expression<F>>,
+}
+
+impl<F: Field> SubCircuitConfig<F> for StateCircuitConfig<F> {
+    type ConfigArgs = StateCircuitConfigArgs<F>;
+
+    /// Return a new StateCircuitConfig
+    fn new(
         meta: &mut ConstraintSystem<F>,
-        power_of_randomness: [Expression<F>; 31],
-        rw_table: &RwTable,
-        mpt_table: &MptTable,
+        Self::ConfigArgs {
+            rw_table,
+            mpt_table,
+            challenges,
+        }: Self::ConfigArgs,
     ) -> Self {
+        let power_of_randomness: [Expression<F>; 31] = challenges.evm_word_powers_of_randomness();
         let selector = meta.fixed_column();
         let lookups = LookupsChip::configure(meta);
 
@@ -75,8 +103,12 @@ impl<F: Field> StateCircuitConfig<F> {
             power_of_randomness.clone(),
         );
 
-        let initial_value = meta.advice_column();
-        let state_root = meta.advice_column();
+        let initial_value = meta.advice_column_in(SecondPhase);
+        let state_root = meta.advice_column_in(SecondPhase);
+        meta.enable_equality(state_root);
+
+        let exposed = meta.fixed_column();
+        let instance = meta.instance_column();
 
         let sort_keys = SortKeysConfig {
             tag,
@@ -102,10 +134,27 @@ impl<F: Field> StateCircuitConfig<F> {
             lexicographic_ordering,
             lookups,
             power_of_randomness,
-            rw_table: *rw_table,
-            mpt_table: *mpt_table,
+            rw_table,
+            mpt_table,
+            exposed,
+            instance,
         };
 
+        // The exposed rows are rows of the rw table.  The rows of an unused
+        // exposed row are 0, which is an unassigned row of the rw table.
+        meta.lookup_any("exposed rw rows", |meta| {
+            let exposed = meta.query_fixed(exposed, Rotation::cur());
+            rw_table
+                .table_exprs(meta)
+                .into_iter()
+                .enumerate()
+                .map(|(column, table)| {
+                    let value = meta.query_instance(instance, Rotation(column as i32));
+                    (exposed.clone() * value, table)
+                })
+                .collect()
+        });
+
         let mut constraint_builder = ConstraintBuilder::new();
         meta.create_gate("state circuit constraints", |meta| {
             let queries = queries(meta, &config);
@@ -118,41 +167,53 @@ impl<F: Field> StateCircuitConfig<F> {
 
         config
     }
+}
 
+impl<F: Field> StateCircuitConfig<F> {
     /// load fixed tables
     pub(crate) fn load(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
-        LookupsChip::construct(self.lookups).load(layouter)
-    }
-    /// Make the assignments to the StateCircuit
-    pub fn assign(
-        &self,
-        layouter: &mut impl Layouter<F>,
-        rows: &[Rw],
-        n_rows: usize,
-        randomness: F,
-    ) -> Result<(), Error> {
-        let updates = MptUpdates::mock_from(rows);
+        LookupsChip::construct(self.lookups).load(layouter)?;
+        // The region only uses the `exposed` column, so it starts at the
+        // first row, aligned with the instance column.
         layouter.assign_region(
-            || "state circuit",
-            |mut region| self.assign_with_region(&mut region, rows, &updates, n_rows, randomness),
+            || "exposed rw rows",
+            |mut region| {
+                for row in 0..N_EXPOSED_ROWS {
+                    region.assign_fixed(
+                        || "exposed",
+                        self.exposed,
+                        row * N_RW_TABLE_COLUMNS,
+                        || Value::known(F::one()),
+                    )?;
+                }
+                Ok(())
+            },
         )
     }
 
+    /// Assign the rows, and return the cells of the state roots of the first
+    /// and the last rows.
     fn assign_with_region(
         &self,
         region: &mut Region<'_, F>,
         rows: &[Rw],
         updates: &MptUpdates,
         n_rows: usize,
-        randomness: F,
-    ) -> Result<(), Error> {
+        randomness: Value<F>,
+    ) -> Result<[AssignedCell<F, F>; 2], Error> {
         let tag_chip = BinaryNumberChip::construct(self.sort_keys.tag);
 
         let (rows, padding_length) = RwMap::table_assignments_prepad(rows, n_rows);
         let rows = rows.into_iter();
         let prev_rows = once(None).chain(rows.clone().map(Some));
+        let mut first_state_root = None;
+        let mut last_state_root = None;
 
-        let mut state_root = F::zero();
+        let mut state_root = updates
+            .iter()
+            .next()
+            .map(|update| randomness.map(|randomness| update.root_assignments(randomness).1))
+            .unwrap_or_else(|| Value::known(F::zero()));
 
         for (offset, (row, prev_row)) in rows.zip(prev_rows).enumerate() {
             if offset >= padding_length {
@@ -180,7 +241,7 @@ impl<F: Field> StateCircuitConfig<F> {
             if let Some(storage_key) = row.storage_key() {
                 self.sort_keys
                     .storage_key
-                    .assign(region, offset, randomness, storage_key)?;
+                    .assign(region, offset, storage_key)?;
             }
 
             if let Some(prev_row) = prev_row {
@@ -191,13 +252,19 @@ impl<F: Field> StateCircuitConfig<F> {
                 if is_first_access {
                     // If previous row was a last access, we need to update the state root.
                     if let Some(update) = updates.get(&prev_row) {
-                        let (new_root, old_root) = update.root_assignments(randomness);
-                        assert_eq!(state_root, old_root);
+                        let (new_root, old_root) = randomness
+                            .map(|randomness| update.root_assignments(randomness))
+                            .unzip();
+                        state_root
+                            .zip(old_root)
+                            .assert_if_known(|(state_root, old_root)| state_root == old_root);
                         state_root = new_root;
                     }
 
                     if matches!(row.tag(), RwTableTag::CallContext) && !row.is_write() {
-                        assert_eq!(row.value_assignment(randomness), F::zero(), "{:?}", row);
+                        randomness
+                            .map(|randomness| row.value_assignment(randomness))
+                            .assert_if_known(|value| *value == F::zero());
                     }
                 }
             }
@@ -205,45 +272,57 @@ impl<F: Field> StateCircuitConfig<F> {
             // The initial value can be determined from the mpt updates or is 0.
             let initial_value = updates
                 .get(&row)
-                .map(|u| u.value_assignments(randomness).1)
-                .unwrap_or_default();
+                .map(|u| randomness.map(|randomness| u.value_assignments(randomness).1))
+                .unwrap_or_else(|| Value::known(F::zero()));
             region.assign_advice(
                 || "initial_value",
                 self.initial_value,
                 offset,
-                || Value::known(initial_value),
+                || initial_value,
             )?;
 
             // TODO: Switch from Rw::Start -> Rw::Padding to simplify this logic.
             // State root assignment is at previous row (offset - 1) because the state root
             // changes on the last access row.
             if offset != 0 {
-                region.assign_advice(
+                let cell = region.assign_advice(
                     || "state_root",
                     self.state_root,
                     offset - 1,
-                    || Value::known(state_root),
+                    || state_root,
                 )?;
+                if offset == 1 {
+                    first_state_root = Some(cell);
+                }
             }
 
             if offset == n_rows - 1 {
                 // The last row is always a last access, so we need to handle the case where the
                 // state root changes because of an mpt lookup on the last row.
                 if let Some(update) = updates.get(&row) {
-                    let (new_root, old_root) = update.root_assignments(randomness);
-                    assert_eq!(state_root, old_root);
+                    let (new_root, old_root) = randomness
+                        .map(|randomness| update.root_assignments(randomness))
+                        .unzip();
+                    state_root
+                        .zip(old_root)
+                        .assert_if_known(|(state_root, old_root)| state_root == old_root);
                     state_root = new_root;
                 }
-                region.assign_advice(
+                let cell = region.assign_advice(
                     || "last row state_root",
                     self.state_root,
                     offset,
-                    || Value::known(state_root),
+                    || state_root,
                 )?;
+                if offset == 0 {
+                    first_state_root = Some(cell.clone());
+                }
+                last_state_root = Some(cell);
             }
         }
 
-        Ok(())
+        Ok([first_state_root, last_state_root]
+            .map(|cell| cell.expect("the state circuit has at least one row")))
     }
 }
 
@@ -264,33 +343,121 @@ type Lookup<F> = (&'static str, Expression<F>, Expression<F>);
 #[derive(Default, Clone)]
 pub struct StateCircuit<F> {
     pub(crate) rows: Vec<Rw>,
-    updates: MptUpdates,
+    pub(crate) updates: MptUpdates,
     pub(crate) n_rows: usize,
-    pub(crate) randomness: F,
+    /// Rows of the rw table exposed as public inputs
+    pub(crate) exposed_rows: [Option<Rw>; N_EXPOSED_ROWS],
     #[cfg(test)]
     overrides: HashMap<(test::AdviceColumn, isize), F>,
 }
 
 impl<F: Field> StateCircuit<F> {
     /// make a new state circuit from an RwMap
-    pub fn new(randomness: F, rw_map: RwMap, n_rows: usize) -> Self {
+    pub fn new(rw_map: RwMap, n_rows: usize) -> Self {
+        let updates = MptUpdates::mock_from(&rw_map.table_assignments());
+        Self::new_with_updates(rw_map, updates, n_rows)
+    }
+
+    /// Return the rows of the rw table of `block`: the `max_rws` of its
+    /// circuit params when set, or its rws preceded by a padding row.  The rws
+    /// of a chunk of a block are the ones of [`witness::Block::state_rws`].
+    fn n_rows_block(block: &witness::Block<F>) -> usize {
+        match block.circuit_params.max_rws {
+            0 => block.state_rws().0.values().map(Vec::len).sum::<usize>() + 1,
+            max_rws => max_rws,
+        }
+    }
+
+    /// make a new state circuit from an RwMap and the MPT updates of its
+    /// Account and AccountStorage rows
+    pub fn new_with_updates(rw_map: RwMap, updates: MptUpdates, n_rows: usize) -> Self {
         let rows = rw_map.table_assignments();
-        let updates = MptUpdates::mock_from(&rows);
         Self {
-            randomness,
             rows,
             updates,
             n_rows,
+            exposed_rows: [None; N_EXPOSED_ROWS],
             #[cfg(test)]
             overrides: HashMap::new(),
         }
     }
+}
+
+impl<F: Field> SubCircuit<F> for StateCircuit<F> {
+    type Config = StateCircuitConfig<F>;
+
+    fn new_from_block(block: &witness::Block<F>) -> Self {
+        Self {
+            exposed_rows: block.chunk_boundary_rws(),
+            ..Self::new_with_updates(
+                block.state_rws(),
+                block.mpt_updates.clone(),
+                Self::n_rows_block(block),
+            )
+        }
+    }
+
+    /// The values of the columns of the exposed rows of the rw table, or 0
+    /// for an unused exposed row.  The exposed rows are receipt rows, whose
+    /// values don't depend on the randomness.
+    fn instance(&self) -> Vec<Vec<F>> {
+        vec![self
+            .exposed_rows
+            .iter()
+            .flat_map(|row| match row {
+                Some(row) => row.table_assignment_aux(F::zero()).values(),
+                None => [F::zero(); N_RW_TABLE_COLUMNS],
+            })
+            .collect()]
+    }
+
+    /// Make the assignments to the StateCircuit, including the rw table
+    fn synthesize_sub(
+        &self,
+        config: &Self::Config,
+        challenges: &Challenges<Value<F>>,
+        layouter: &mut impl Layouter<F>,
+    ) -> Result<(), Error> {
+        self.synthesize_with_state_roots(config, challenges, layouter)?;
+        Ok(())
+    }
 
-    /// powers of randomness for instance columns
-    pub fn instance(&self) -> Vec<Vec<F>> {
-        (1..32)
-            .map(|exp| vec![self.randomness.pow(&[exp, 0, 0, 0]); self.n_rows])
-            .collect()
+    /// The fixed u16 table of the lookups takes `2^16` rows.
+    fn min_num_rows_block(block: &witness::Block<F>) -> usize {
+        Self::n_rows_block(block).max(1 << 16)
+    }
+}
+
+impl<F: Field> StateCircuit<F> {
+    /// Make the assignments to the StateCircuit like `synthesize_sub`, and
+    /// return the cells of the state roots of its first and last rows, which
+    /// are the state roots before and after the rows, to link them to the
+    /// public inputs.
+    pub(crate) fn synthesize_with_state_roots(
+        &self,
+        config: &StateCircuitConfig<F>,
+        challenges: &Challenges<Value<F>>,
+        layouter: &mut impl Layouter<F>,
+    ) -> Result<[AssignedCell<F, F>; 2], Error> {
+        config.load(layouter)?;
+        layouter.assign_region(
+            || "state circuit",
+            |mut region| {
+                config.rw_table.load_with_region(
+                    &mut region,
+                    &self.rows,
+                    self.n_rows,
+                    challenges,
+                )?;
+                config.assign_with_region(
+                    &mut region,
+                    &self.rows,
+                    &self.updates,
+                    self.n_rows,
+                    challenges.evm_word(),
+                )
+            },
+        )
     }
 }
 
@@ -298,7 +465,7 @@ impl<F: Field> Circuit<F> for StateCircuit<F>
 where
     F: Field,
 {
-    type Config = StateCircuitConfig<F>;
+    type Config = (StateCircuitConfig<F>, Challenges);
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
@@ -308,15 +475,29 @@ where
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
         let rw_table = RwTable::construct(meta);
         let mpt_table = MptTable::construct(meta);
-        let power_of_randomness: [Expression<F>; 31] = power_of_randomness_from_instance(meta);
-        Self::Config::configure(meta, power_of_randomness, &rw_table, &mpt_table)
+        let challenges = Challenges::construct(meta);
+
+        let config = {
+            let challenges = challenges.exprs(meta);
+            StateCircuitConfig::new(
+                meta,
+                StateCircuitConfigArgs {
+                    rw_table,
+                    mpt_table,
+                    challenges,
+                },
+            )
+        };
+
+        (config, challenges)
     }
 
     fn synthesize(
         &self,
-        config: Self::Config,
+        (config, challenges): Self::Config,
         mut layouter: impl Layouter<F>,
     ) -> Result<(), Error> {
+        let challenges = challenges.values(&mut layouter);
         config.load(&mut layouter)?;
 
         // Assigning to same columns in different regions should be avoided.
@@ -329,19 +510,19 @@ where
                     &mut region,
                     &self.rows,
                     self.n_rows,
-                    self.randomness,
+                    &challenges,
                 )?;
 
                 config
                     .mpt_table
-                    .load_with_region(&mut region, &self.updates, self.randomness)?;
+                    .load_with_region(&mut region, &self.updates, &challenges)?;
 
                 config.assign_with_region(
                     &mut region,
                     &self.rows,
                     &self.updates,
                     self.n_rows,
-                    self.randomness,
+                    challenges.evm_word(),
                 )?;
                 #[cfg(test)]
                 {
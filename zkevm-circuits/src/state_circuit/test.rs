@@ -1,7 +1,8 @@
-use super::{StateCircuit, StateCircuitConfig};
+use super::{StateCircuit, StateCircuitConfig, N_EXPOSED_ROWS, N_RW_TABLE_COLUMNS};
 use crate::{
     table::{AccountFieldTag, CallContextFieldTag, RwTableTag, TxLogFieldTag, TxReceiptFieldTag},
-    witness::{MptUpdates, Rw, RwMap},
+    util::SubCircuit,
+    witness::{chunk_convert, Block, ExecStep, MptUpdates, Rw, RwMap, Transaction},
 };
 use bus_mapping::operation::{
     MemoryOp, Operation, OperationContainer, RWCounter, StackOp, StorageOp, RW,
@@ -91,11 +92,9 @@ fn test_state_circuit_ok(
         ..Default::default()
     });
 
-    let randomness = Fr::from(0xcafeu64);
-    let circuit = StateCircuit::<Fr>::new(randomness, rw_map, N_ROWS);
-    let power_of_randomness = circuit.instance();
+    let circuit = StateCircuit::<Fr>::new(rw_map, N_ROWS);
 
-    let prover = MockProver::<Fr>::run(19, &circuit, power_of_randomness).unwrap();
+    let prover = MockProver::<Fr>::run(19, &circuit, circuit.instance()).unwrap();
     let verify_result = prover.verify();
     assert_eq!(verify_result, Ok(()));
 }
@@ -109,12 +108,10 @@ fn degree() {
 
 #[test]
 fn verifying_key_independent_of_rw_length() {
-    let randomness = Fr::from(0xcafeu64);
     let params = ParamsKZG::<Bn256>::setup(17, rand_chacha::ChaCha20Rng::seed_from_u64(2));
 
-    let no_rows = StateCircuit::<Fr>::new(randomness, RwMap::default(), N_ROWS);
+    let no_rows = StateCircuit::<Fr>::new(RwMap::default(), N_ROWS);
     let one_row = StateCircuit::<Fr>::new(
-        randomness,
         RwMap::from(&OperationContainer {
             memory: vec![Operation::new(
                 RWCounter::from(1),
@@ -484,13 +481,18 @@ fn storage_key_byte_out_of_range() {
         committed_value: U256::from(500),
     }];
     let overrides = HashMap::from([
-        ((AdviceColumn::StorageKeyByte0, 0), Fr::from(0xcafeu64)), /* 0xcafeu64 is the fixed
-                                                                    * "randomness" we use for
-                                                                    * this test. */
+        ((AdviceColumn::StorageKeyByte0, 0), Fr::from(256)),
         ((AdviceColumn::StorageKeyByte1, 0), Fr::zero()),
     ]);
 
-    let result = verify_with_overrides(rows, overrides);
+    // The overridden bytes can't match the RLC encoding of the storage key without
+    // knowing the challenge, so we ignore that error and only check the range one.
+    let result = verify_with_overrides(rows, overrides).map_err(|errors| {
+        errors
+            .into_iter()
+            .filter(|error| !format!("{}", error).contains("rlc encoded value matches bytes"))
+            .collect()
+    });
 
     assert_error_matches(result, "rlc bytes fit into u8");
 }
@@ -980,19 +982,140 @@ fn bad_initial_tx_receipt_value() {
     );
 }
 
+#[test]
+fn chunked_rows() {
+    let rows = vec![
+        Rw::Stack {
+            rw_counter: 1,
+            is_write: true,
+            call_id: 1,
+            stack_pointer: 1023,
+            value: U256::from(394500u64),
+        },
+        Rw::Stack {
+            rw_counter: 2,
+            is_write: false,
+            call_id: 1,
+            stack_pointer: 1023,
+            value: U256::from(394500u64),
+        },
+        Rw::TxReceipt {
+            rw_counter: 3,
+            is_write: true,
+            tx_id: 1,
+            field_tag: TxReceiptFieldTag::CumulativeGasUsed,
+            value: 21000,
+        },
+        Rw::TxReceipt {
+            rw_counter: 4,
+            is_write: false,
+            tx_id: 1,
+            field_tag: TxReceiptFieldTag::CumulativeGasUsed,
+            value: 21000,
+        },
+        Rw::TxReceipt {
+            rw_counter: 5,
+            is_write: true,
+            tx_id: 2,
+            field_tag: TxReceiptFieldTag::CumulativeGasUsed,
+            value: 42000,
+        },
+        Rw::Memory {
+            rw_counter: 6,
+            is_write: true,
+            call_id: 2,
+            memory_address: 0,
+            byte: 5,
+        },
+    ];
+    let mut rws = RwMap::default();
+    for row in &rows {
+        rws.0.entry(row.tag()).or_default().push(*row);
+    }
+    let tx = |id, rw_counter| Transaction {
+        id,
+        steps: vec![ExecStep {
+            rw_counter,
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let block = Block::<Fr> {
+        rws,
+        mpt_updates: MptUpdates::mock_from(&rows),
+        txs: vec![tx(1, 1), tx(2, 4)],
+        eth_txs: vec![Default::default(); 2],
+        ..Default::default()
+    };
+
+    let chunks = chunk_convert(&block, 1);
+    assert_eq!(chunks.len(), 2);
+    let circuits: Vec<_> = chunks.iter().map(StateCircuit::new_from_block).collect();
+    assert_eq!(circuits[0].rows.len(), 3);
+    // The second chunk has the cumulative gas used of the first transaction,
+    // which is exposed by both chunks.
+    assert_eq!(circuits[1].rows.len(), 4);
+    assert_eq!(
+        circuits[0].instance()[0][N_RW_TABLE_COLUMNS..],
+        rows[2].table_assignment_aux(Fr::zero()).values()
+    );
+    assert_eq!(
+        circuits[0].instance()[0][N_RW_TABLE_COLUMNS..],
+        circuits[1].instance()[0][..N_RW_TABLE_COLUMNS]
+    );
+    // The first chunk doesn't import a row and the last one doesn't export one.
+    assert_eq!(
+        circuits[0].instance()[0][..N_RW_TABLE_COLUMNS],
+        circuits[1].instance()[0][N_RW_TABLE_COLUMNS..]
+    );
+    assert!(circuits[1].instance()[0][N_RW_TABLE_COLUMNS..]
+        .iter()
+        .all(|value| *value == Fr::zero()));
+    for circuit in circuits {
+        let prover = MockProver::<Fr>::run(17, &circuit, circuit.instance()).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+}
+
+#[test]
+fn exposed_row_not_in_rw_table() {
+    let row = Rw::TxReceipt {
+        rw_counter: 1,
+        is_write: true,
+        tx_id: 1,
+        field_tag: TxReceiptFieldTag::CumulativeGasUsed,
+        value: 21000,
+    };
+    let rw_map = RwMap([(RwTableTag::TxReceipt, vec![row])].into());
+    let circuit = StateCircuit::<Fr> {
+        exposed_rows: [
+            None,
+            Some(Rw::TxReceipt {
+                rw_counter: 1,
+                is_write: true,
+                tx_id: 1,
+                field_tag: TxReceiptFieldTag::CumulativeGasUsed,
+                value: 42000,
+            }),
+        ],
+        ..StateCircuit::new(rw_map, N_ROWS)
+    };
+
+    let prover = MockProver::<Fr>::run(17, &circuit, circuit.instance()).unwrap();
+    assert!(prover.verify().is_err());
+}
+
 fn prover(rows: Vec<Rw>, overrides: HashMap<(AdviceColumn, isize), Fr>) -> MockProver<Fr> {
-    let randomness = Fr::from(0xcafeu64);
     let updates = MptUpdates::mock_from(&rows);
     let circuit = StateCircuit::<Fr> {
-        randomness,
         rows,
         updates,
         overrides,
         n_rows: N_ROWS,
+        exposed_rows: [None; N_EXPOSED_ROWS],
     };
-    let power_of_randomness = circuit.instance();
 
-    MockProver::<Fr>::run(17, &circuit, power_of_randomness).unwrap()
+    MockProver::<Fr>::run(17, &circuit, circuit.instance()).unwrap()
 }
 
 fn verify(rows: Vec<Rw>) -> Result<(), Vec<VerifyFailure>> {
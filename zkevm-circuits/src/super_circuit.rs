@@ -4,28 +4,30 @@
 //!   tables, to verify that the table layouts match.
 //! - Allow having a single circuit setup for which a proof can be generated
 //!   that would be verified under a single aggregation circuit for the first
-//!   milestone.
+//!   milestone.  The proofs of the Super Circuit are the ones aggregated by the
+//!   [`AggregationCircuit`](crate::aggregation_circuit::AggregationCircuit),
+//!   which doesn't aggregate separate proofs of the sub-circuits.
 //!
 //! The current implementation contains the following circuits:
 //!
 //! - [x] EVM Circuit
-//! - [ ] State Circuit
+//! - [x] State Circuit
 //! - [x] Tx Circuit
 //! - [x] Bytecode Circuit
 //! - [x] Copy Circuit
-//! - [ ] Keccak Circuit
-//! - [ ] MPT Circuit
-//! - [ ] PublicInputs Circuit
+//! - [x] Keccak Circuit
+//! - [x] MPT Circuit
+//! - [x] PublicInputs Circuit
 //!
 //! And the following shared tables, with the circuits that use them:
 //!
 //! - [x] Copy Table
 //!   - [x] Copy Circuit
 //!   - [x] EVM Circuit
-//! - [ ] Rw Table
-//!   - [ ] State Circuit
-//!   - [ ] EVM Circuit
-//!   - [ ] Copy Circuit
+//! - [x] Rw Table
+//!   - [x] State Circuit
+//!   - [x] EVM Circuit
+//!   - [x] Copy Circuit
 //! - [x] Tx Table
 //!   - [x] Tx Circuit
 //!   - [x] EVM Circuit
@@ -35,89 +37,220 @@
 //!   - [x] Bytecode Circuit
 //!   - [x] EVM Circuit
 //!   - [x] Copy Circuit
-//! - [ ] Block Table
-//!   - [ ] EVM Circuit
-//!   - [ ] PublicInputs Circuit
-//! - [ ] MPT Table
-//!   - [ ] MPT Circuit
-//!   - [ ] State Circuit
+//! - [x] Block Table
+//!   - [x] EVM Circuit
+//!   - [x] Tx Circuit
+//!   - [x] PublicInputs Circuit
+//! - [x] MPT Table
+//!   - [x] MPT Circuit
+//!   - [x] State Circuit
 //! - [x] Keccak Table
-//!   - [ ] Keccak Circuit
-//!   - [ ] EVM Circuit
+//!   - [x] Keccak Circuit
+//!   - [x] EVM Circuit
 //!   - [x] Bytecode Circuit
 //!   - [x] Tx Circuit
-//!   - [ ] MPT Circuit
-
-use crate::state_circuit::StateCircuitConfig;
-use crate::tx_circuit::{TxCircuit, TxCircuitConfig};
+//!   - [x] MPT Circuit
+//!
+//! The state roots before and after the rows of the State Circuit, which the
+//! MPT Circuit proves the updates between, are the previous state root and the
+//! state root of the last block of the public inputs, or the state roots
+//! before and after the chunk of the block described below.
+//!
+//! A block too large for a single Super Circuit is proven in chunks of whole
+//! transactions, see [`SuperCircuit::build_chunks_from_circuit_input_builder`].
+//! The circuits of a chunk only have the parts of the block used by its
+//! transactions: the EVM Circuit proves their steps, and the State Circuit
+//! proves their RW rows, whose MPT updates go from the state root before the
+//! chunk to the one after it.  Consecutive chunks are linked by their public
+//! inputs: the step states at their boundaries in the EVM Circuit, the receipt
+//! row exported by a chunk and imported by the next one in the State Circuit
+//! (see [`Block::chunk_boundary_rws`]), and the state roots of the chunks in
+//! the PublicInputs Circuit, which are the ones of the batch for the first and
+//! the last chunk.  The raw public inputs of a chunk have the blocks of the
+//! batch and the transactions of the chunk.
+//!
+//! The MPT Circuit only proves the updates that keep the shape of the tries
+//! above the updated leaf (see [`crate::mpt_circuit`]), so the blocks with
+//! other updates, such as inserting a key next to an existing leaf, are
+//! rejected with [`Error::UnsupportedMptUpdate`] when building the circuit.
+//!
+//! [`Error::UnsupportedMptUpdate`]: eth_types::Error::UnsupportedMptUpdate
 
-use crate::bytecode_circuit::bytecode_unroller::{
-    unroll, Config as BytecodeConfig, UnrolledBytecode,
+use crate::bytecode_circuit::{BytecodeCircuit, BytecodeCircuitConfig, BytecodeCircuitConfigArgs};
+use crate::copy_circuit::{CopyCircuit, CopyCircuitConfig, CopyCircuitConfigArgs};
+use crate::evm_circuit::{EvmCircuit, EvmCircuitConfig, EvmCircuitConfigArgs};
+use crate::keccak_circuit::keccak_bit::{
+    self, KeccakBitCircuit, KeccakBitConfig, KeccakBitConfigArgs,
 };
-
-use crate::evm_circuit::{table::FixedTableTag, EvmCircuit};
-use crate::table::{BlockTable, BytecodeTable, CopyTable, MptTable, RwTable, TxTable};
-use crate::util::{power_of_randomness_from_instance, Challenges};
-use crate::witness::Block;
+use crate::mpt_circuit::{MptCircuit, MptCircuitConfig, MptCircuitConfigArgs};
+use crate::pi_circuit::{PiCircuit, PiCircuitConfig, PiCircuitConfigArgs};
+use crate::state_circuit::{StateCircuit, StateCircuitConfig, StateCircuitConfigArgs};
+use crate::table::{BlockTable, BytecodeTable, CopyTable, KeccakTable, MptTable, RwTable, TxTable};
+use crate::tx_circuit::{TxCircuit, TxCircuitConfig, TxCircuitConfigArgs, MAX_ACCESS_LIST_ENTRIES};
+use crate::util::{Challenges, SubCircuit, SubCircuitConfig};
+use crate::witness::{
+    batch_convert, block_convert_with_state, chunk_convert_with_state, Block, CircuitParams,
+    MptState, ParamUnset, ParamsExceeded,
+};
+use bus_mapping::circuit_input_builder::{self, CircuitInputBuilder};
+use bus_mapping::mock::BlockData;
+use eth_types::geth_types::GethData;
 use eth_types::Field;
 use halo2_proofs::{
     circuit::{Layouter, SimpleFloorPlanner, Value},
-    plonk::{Circuit, ConstraintSystem, Error},
+    halo2curves::bn256::Fr,
+    plonk::{Circuit, ConstraintSystem, Error, Expression},
 };
 
-use super::copy_circuit::CopyCircuit;
-use crate::{
-    keccak_circuit::keccak_bit::{KeccakBitCircuit, KeccakBitConfig},
-    tx_circuit::sign_verify::POW_RAND_SIZE,
-    witness::block_convert,
-};
-use bus_mapping::mock::BlockData;
-use eth_types::geth_types::{self, GethData};
-use halo2_proofs::arithmetic::{CurveAffine, Field as Halo2Field};
-use halo2_proofs::halo2curves::{
-    bn256::Fr,
-    group::{Curve, Group},
-    secp256k1::Secp256k1Affine,
-};
-use rand::RngCore;
-use strum::IntoEnumIterator;
+mod row_usage;
+
+pub use row_usage::{BlockRowUsage, RowUsageEstimator};
 
 /// Configuration of the Super Circuit
 #[derive(Clone)]
 pub struct SuperCircuitConfig<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> {
-    tx_table: TxTable,
-    rw_table: RwTable,
-    mpt_table: MptTable,
-    bytecode_table: BytecodeTable,
-    block_table: BlockTable,
-    copy_table: CopyTable,
-    evm_circuit: EvmCircuit<F>,
+    evm_circuit: EvmCircuitConfig<F>,
     state_circuit: StateCircuitConfig<F>,
     tx_circuit: TxCircuitConfig<F>,
-    bytecode_circuit: BytecodeConfig<F>,
-    copy_circuit: CopyCircuit<F>,
+    bytecode_circuit: BytecodeCircuitConfig<F>,
+    copy_circuit: CopyCircuitConfig<F>,
     keccak_circuit: KeccakBitConfig<F>,
+    mpt_circuit: MptCircuitConfig<F>,
+    pi_circuit: PiCircuitConfig<F, MAX_TXS, MAX_CALLDATA>,
+}
+
+/// Circuit configuration arguments
+pub struct SuperCircuitConfigArgs<F: Field> {
+    /// Challenges
+    pub challenges: Challenges<Expression<F>>,
+}
+
+impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> SubCircuitConfig<F>
+    for SuperCircuitConfig<F, MAX_TXS, MAX_CALLDATA>
+{
+    type ConfigArgs = SuperCircuitConfigArgs<F>;
+
+    /// Configure SuperCircuitConfig
+    fn new(
+        meta: &mut ConstraintSystem<F>,
+        Self::ConfigArgs { challenges }: Self::ConfigArgs,
+    ) -> Self {
+        let tx_table = TxTable::construct(meta);
+        let rw_table = RwTable::construct(meta);
+        let mpt_table = MptTable::construct(meta);
+        let bytecode_table = BytecodeTable::construct(meta);
+        let block_table = BlockTable::construct(meta);
+        let q_copy_table = meta.fixed_column();
+        let copy_table = CopyTable::construct(meta, q_copy_table);
+        let keccak_table = KeccakTable::construct(meta);
+
+        let keccak_circuit = KeccakBitConfig::new(
+            meta,
+            KeccakBitConfigArgs {
+                keccak_table: keccak_table.clone(),
+                challenges: challenges.clone(),
+            },
+        );
+        let evm_circuit = EvmCircuitConfig::new(
+            meta,
+            EvmCircuitConfigArgs {
+                challenges: challenges.clone(),
+                tx_table: tx_table.clone(),
+                rw_table,
+                bytecode_table: bytecode_table.clone(),
+                block_table: block_table.clone(),
+                copy_table,
+                keccak_table: keccak_table.clone(),
+            },
+        );
+        let state_circuit = StateCircuitConfig::new(
+            meta,
+            StateCircuitConfigArgs {
+                rw_table,
+                mpt_table,
+                challenges: challenges.clone(),
+            },
+        );
+        let mpt_circuit = MptCircuitConfig::new(
+            meta,
+            MptCircuitConfigArgs {
+                mpt_table,
+                keccak_table: keccak_table.clone(),
+                challenges: challenges.clone(),
+            },
+        );
+        let copy_circuit = CopyCircuitConfig::new(
+            meta,
+            CopyCircuitConfigArgs {
+                tx_table: tx_table.clone(),
+                rw_table,
+                bytecode_table: bytecode_table.clone(),
+                copy_table,
+                q_enable: q_copy_table,
+                challenges: challenges.clone(),
+            },
+        );
+        let tx_circuit = TxCircuitConfig::new(
+            meta,
+            TxCircuitConfigArgs {
+                tx_table,
+                keccak_table: keccak_table.clone(),
+                block_table: block_table.clone(),
+                rw_table,
+                challenges: challenges.clone(),
+            },
+        );
+        let bytecode_circuit = BytecodeCircuitConfig::new(
+            meta,
+            BytecodeCircuitConfigArgs {
+                bytecode_table,
+                keccak_table,
+                challenges: challenges.clone(),
+            },
+        );
+        // The PI Circuit keeps its own tx table: only the block table is
+        // shared for now.
+        let pi_circuit = PiCircuitConfig::new(
+            meta,
+            PiCircuitConfigArgs {
+                block_table,
+                tx_table: TxTable::construct(meta),
+                challenges,
+            },
+        );
+
+        Self {
+            evm_circuit,
+            state_circuit,
+            tx_circuit,
+            bytecode_circuit,
+            copy_circuit,
+            keccak_circuit,
+            mpt_circuit,
+            pi_circuit,
+        }
+    }
 }
 
 /// The Super Circuit contains all the zkEVM circuits
-#[derive(Default, Debug)]
+#[derive(Default)]
 pub struct SuperCircuit<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> {
-    // EVM Circuit
-    /// Block witness. Usually derived via
-    /// `evm_circuit::witness::block_convert`.
-    pub block: Block<F>,
-    /// Inputs for the keccak circuit
-    pub keccak_inputs: Vec<Vec<u8>>,
-    /// Passed down to the evm_circuit. Usually that will be
-    /// `FixedTableTag::iter().collect()`.
-    pub fixed_table_tags: Vec<FixedTableTag>,
-    // Tx Circuit
+    /// EVM Circuit
+    pub evm_circuit: EvmCircuit<F>,
+    /// State Circuit
+    pub state_circuit: StateCircuit<F>,
     /// The transaction circuit that will be used in the `synthesize` step.
     pub tx_circuit: TxCircuit<F, MAX_TXS, MAX_CALLDATA>,
-    // Bytecode Circuit
-    // bytecodes: Vec<UnrolledBytecode<F>>,
-    /// The maximium size for the underlying bytecode circuit.
-    pub bytecode_size: usize,
+    /// Public Input Circuit
+    pub pi_circuit: PiCircuit<F, MAX_TXS, MAX_CALLDATA>,
+    /// Bytecode Circuit
+    pub bytecode_circuit: BytecodeCircuit<F>,
+    /// Copy Circuit
+    pub copy_circuit: CopyCircuit<F>,
+    /// Keccak Circuit
+    pub keccak_circuit: KeccakBitCircuit<F>,
+    /// MPT Circuit
+    pub mpt_circuit: MptCircuit<F>,
 }
 
 impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize>
@@ -126,15 +259,203 @@ impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize>
     /// Return the number of rows required to verify a given block
     pub fn get_num_rows_required(block: &Block<F>) -> usize {
         let mut cs = ConstraintSystem::default();
-        let config = Self::configure(&mut cs);
+        let (config, _) = Self::configure(&mut cs);
         config.evm_circuit.get_num_rows_required(block)
     }
+
+    /// Return the degree of a SuperCircuit whose sub-circuits require at most
+    /// `min_num_rows` rows, with room for the blinding rows.
+    pub fn degree_for_rows(min_num_rows: usize) -> u32 {
+        let log2_ceil = |n| u32::BITS - (n as u32).leading_zeros() - (n & (n - 1) == 0) as u32;
+        log2_ceil(64 + min_num_rows) + 1
+    }
+
+    /// Generate a SuperCircuit without a block, padded to `circuit_params`,
+    /// from which the keys of all the blocks that fit in the parameters are
+    /// derived.  All the parameters must be set, see
+    /// [`CircuitParams::check_padded`].
+    pub fn new_for_keygen(circuit_params: CircuitParams) -> Result<Self, ParamUnset> {
+        let circuit_params = CircuitParams {
+            max_txs: MAX_TXS,
+            max_calldata: MAX_CALLDATA,
+            ..circuit_params
+        };
+        circuit_params.check_padded()?;
+        Ok(Self::new_from_block(&Block {
+            circuit_params,
+            ..Default::default()
+        }))
+    }
+
+    /// Check that the witness of the circuit fits in the [`CircuitParams`] of
+    /// its block, so that a block that is too big for the parameters is
+    /// rejected before generating a proof.  The sub-circuits padded to the
+    /// parameters must also fit in the rows of the degree.
+    pub fn check_params(&self) -> Result<(), ParamsExceeded> {
+        let block = &self.evm_circuit.block;
+        let params = &block.circuit_params;
+        params.check_block(block)?;
+
+        let mut cs = ConstraintSystem::default();
+        let (config, _) = Self::configure(&mut cs);
+        // Each bytecode takes a header row and a row per byte, and the
+        // Bytecode circuit leaves its last `minimum_rows - 2` rows disabled.
+        let bytecode_rows = block
+            .bytecodes
+            .values()
+            .map(|bytecode| bytecode.bytes.len() + 1)
+            .sum::<usize>()
+            + cs.minimum_rows()
+            - 2;
+        // The MPT circuit leaves its last `minimum_rows - 1` rows disabled.
+        let mpt_rows = MptCircuit::<F>::get_num_rows_required(&block.mpt_updates)
+            .unwrap_or_default()
+            + cs.minimum_rows()
+            - 1;
+        CircuitParams::check_sizes([
+            (
+                "degree",
+                Self::degree_for_rows(Self::min_num_rows_block(block)) as usize,
+                params.degree as usize,
+            ),
+            (
+                "max_evm_rows",
+                config.evm_circuit.get_num_rows_required(block),
+                params.max_evm_rows,
+            ),
+            ("max_bytecode", bytecode_rows, params.max_bytecode),
+            ("max_mpt_rows", mpt_rows, params.max_mpt_rows),
+            (
+                "max_keccak_rows",
+                keccak_bit::get_num_rows_required_block(block),
+                params.max_keccak_rows,
+            ),
+            (
+                "rlp_rows",
+                TxCircuit::<F, MAX_TXS, MAX_CALLDATA>::get_num_rlp_rows_required(
+                    &self.tx_circuit.txs,
+                    self.tx_circuit.chain_id,
+                ),
+                TxCircuit::<F, MAX_TXS, MAX_CALLDATA>::rlp_rows_capacity(),
+            ),
+            (
+                "access_list_entries",
+                TxCircuit::<F, MAX_TXS, MAX_CALLDATA>::get_num_access_list_entries(
+                    &self.tx_circuit.txs,
+                ),
+                MAX_ACCESS_LIST_ENTRIES,
+            ),
+        ])
+    }
 }
 
-impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> Circuit<F>
+impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> SubCircuit<F>
     for SuperCircuit<F, MAX_TXS, MAX_CALLDATA>
 {
     type Config = SuperCircuitConfig<F, MAX_TXS, MAX_CALLDATA>;
+
+    fn new_from_block(block: &Block<F>) -> Self {
+        Self {
+            evm_circuit: EvmCircuit::new_from_block(block),
+            state_circuit: StateCircuit::new_from_block(block),
+            tx_circuit: TxCircuit::new_from_block(block),
+            pi_circuit: PiCircuit::new_from_block(block),
+            bytecode_circuit: BytecodeCircuit::new_from_block(block),
+            copy_circuit: CopyCircuit::new_from_block(block),
+            keccak_circuit: KeccakBitCircuit::new_from_block(block),
+            mpt_circuit: MptCircuit::new_from_block(block),
+        }
+    }
+
+    /// Return the instance columns of the EVM, State, Tx and PublicInputs
+    /// circuits, in the order they are configured.
+    fn instance(&self) -> Vec<Vec<F>> {
+        let mut instance = self.evm_circuit.instance();
+        instance.extend(self.state_circuit.instance());
+        instance.extend(self.tx_circuit.instance());
+        instance.extend(self.pi_circuit.instance());
+        instance
+    }
+
+    /// Make the assignments to all the sub-circuits
+    fn synthesize_sub(
+        &self,
+        config: &Self::Config,
+        challenges: &Challenges<Value<F>>,
+        layouter: &mut impl Layouter<F>,
+    ) -> Result<(), Error> {
+        self.keccak_circuit
+            .synthesize_sub(&config.keccak_circuit, challenges, layouter)?;
+        self.bytecode_circuit
+            .synthesize_sub(&config.bytecode_circuit, challenges, layouter)?;
+        self.tx_circuit
+            .synthesize_sub(&config.tx_circuit, challenges, layouter)?;
+        let state_roots = self.state_circuit.synthesize_with_state_roots(
+            &config.state_circuit,
+            challenges,
+            layouter,
+        )?;
+        self.mpt_circuit
+            .synthesize_sub(&config.mpt_circuit, challenges, layouter)?;
+        self.copy_circuit
+            .synthesize_sub(&config.copy_circuit, challenges, layouter)?;
+        self.evm_circuit
+            .synthesize_sub(&config.evm_circuit, challenges, layouter)?;
+        let [batch_state_roots, chunk_state_roots] = self.pi_circuit.synthesize_with_state_roots(
+            &config.pi_circuit,
+            challenges,
+            layouter,
+        )?;
+        // The state roots before the first chunk and after the last one are
+        // the ones before and after the batch.
+        let chunk = self.evm_circuit.block.chunk.as_ref();
+        let is_boundary = [
+            chunk.map_or(true, |chunk| chunk.is_first()),
+            chunk.map_or(true, |chunk| chunk.is_last()),
+        ];
+        layouter.assign_region(
+            || "state roots",
+            |mut region| {
+                for (state_root, chunk_state_root) in state_roots.iter().zip(&chunk_state_roots) {
+                    region.constrain_equal(state_root.cell(), chunk_state_root.cell())?;
+                }
+                for ((chunk_state_root, batch_state_root), is_boundary) in chunk_state_roots
+                    .iter()
+                    .zip(&batch_state_roots)
+                    .zip(is_boundary)
+                {
+                    if is_boundary {
+                        region.constrain_equal(chunk_state_root.cell(), batch_state_root.cell())?;
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// The Super Circuit needs the largest number of rows of its
+    /// sub-circuits.
+    fn min_num_rows_block(block: &Block<F>) -> usize {
+        [
+            EvmCircuit::min_num_rows_block(block),
+            StateCircuit::min_num_rows_block(block),
+            TxCircuit::<F, MAX_TXS, MAX_CALLDATA>::min_num_rows_block(block),
+            PiCircuit::<F, MAX_TXS, MAX_CALLDATA>::min_num_rows_block(block),
+            BytecodeCircuit::min_num_rows_block(block),
+            CopyCircuit::min_num_rows_block(block),
+            KeccakBitCircuit::min_num_rows_block(block),
+            MptCircuit::min_num_rows_block(block),
+        ]
+        .into_iter()
+        .max()
+        .unwrap()
+    }
+}
+
+impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> Circuit<F>
+    for SuperCircuit<F, MAX_TXS, MAX_CALLDATA>
+{
+    type Config = (SuperCircuitConfig<F, MAX_TXS, MAX_CALLDATA>, Challenges);
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
@@ -142,131 +463,26 @@ impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> Circuit<F>
     }
 
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
-        let tx_table = TxTable::construct(meta);
-        let rw_table = RwTable::construct(meta);
-        let mpt_table = MptTable::construct(meta);
-        let bytecode_table = BytecodeTable::construct(meta);
-        let block_table = BlockTable::construct(meta);
-        let q_copy_table = meta.fixed_column();
-        let copy_table = CopyTable::construct(meta, q_copy_table);
-
-        let keccak_circuit = KeccakBitCircuit::configure(meta);
-        let keccak_table = keccak_circuit.keccak_table.clone();
-
-        let power_of_randomness = power_of_randomness_from_instance(meta);
-        let evm_circuit = EvmCircuit::configure(
-            meta,
-            power_of_randomness[..31].to_vec().try_into().unwrap(),
-            &tx_table,
-            &rw_table,
-            &bytecode_table,
-            &block_table,
-            &copy_table,
-            &keccak_table,
-        );
-        let state_circuit = StateCircuitConfig::configure(
-            meta,
-            power_of_randomness[..31].to_vec().try_into().unwrap(),
-            &rw_table,
-            &mpt_table,
-        );
-
-        Self::Config {
-            tx_table: tx_table.clone(),
-            rw_table,
-            mpt_table,
-            bytecode_table: bytecode_table.clone(),
-            block_table,
-            copy_table,
-            evm_circuit,
-            state_circuit,
-            copy_circuit: CopyCircuit::configure(
-                meta,
-                &tx_table,
-                &rw_table,
-                &bytecode_table,
-                copy_table,
-                q_copy_table,
-                power_of_randomness[0].clone(),
-            ),
-            tx_circuit: TxCircuitConfig::new(
+        let challenges = Challenges::construct(meta);
+        let challenges_expr = challenges.exprs(meta);
+        (
+            SuperCircuitConfig::new(
                 meta,
-                power_of_randomness.clone(),
-                tx_table,
-                keccak_table.clone(),
+                SuperCircuitConfigArgs {
+                    challenges: challenges_expr,
+                },
             ),
-            bytecode_circuit: BytecodeConfig::configure(
-                meta,
-                bytecode_table,
-                keccak_table,
-                Challenges::mock(power_of_randomness[0].clone()),
-            ),
-            keccak_circuit,
-        }
+            challenges,
+        )
     }
 
     fn synthesize(
         &self,
-        config: Self::Config,
+        (config, challenges): Self::Config,
         mut layouter: impl Layouter<F>,
     ) -> Result<(), Error> {
-        let challenges = Challenges::mock(Value::known(self.block.randomness));
-
-        // --- EVM Circuit ---
-        config
-            .evm_circuit
-            .load_fixed_table(&mut layouter, self.fixed_table_tags.clone())?;
-        config.evm_circuit.load_byte_table(&mut layouter)?;
-        config.rw_table.load(
-            &mut layouter,
-            &self.block.rws.table_assignments(),
-            self.block.state_circuit_pad_to,
-            self.block.randomness,
-        )?;
-        config.state_circuit.load(&mut layouter)?;
-        config
-            .block_table
-            .load(&mut layouter, &self.block.context, self.block.randomness)?;
-        config
-            .copy_table
-            .load(&mut layouter, &self.block, self.block.randomness)?;
-        config
-            .evm_circuit
-            .assign_block(&mut layouter, &self.block)?;
-        config.state_circuit.assign(
-            &mut layouter,
-            &self.block.rws.table_assignments(),
-            self.block.state_circuit_pad_to,
-            self.block.randomness,
-        )?;
-        // --- Tx Circuit ---
-        self.tx_circuit.assign(&config.tx_circuit, &mut layouter)?;
-        // --- Bytecode Circuit ---
-        let bytecodes: Vec<UnrolledBytecode<F>> = self
-            .block
-            .bytecodes
-            .iter()
-            .map(|(_, b)| unroll(b.bytes.clone()))
-            .collect();
-        config.bytecode_circuit.load(&mut layouter)?;
-        config.bytecode_circuit.assign(
-            &mut layouter,
-            self.bytecode_size,
-            &bytecodes,
-            &challenges,
-        )?;
-        // --- Keccak Table ---
-        config.keccak_circuit.load(&mut layouter)?;
-        config.keccak_circuit.assign_from_witness(
-            &mut layouter,
-            &self.keccak_inputs,
-            self.block.randomness,
-        )?;
-        // --- Copy Circuit ---
-        config
-            .copy_circuit
-            .assign_block(&mut layouter, &self.block, self.block.randomness)?;
-        Ok(())
+        let challenges = challenges.values(&mut layouter);
+        self.synthesize_sub(&config, &challenges, &mut layouter)
     }
 }
 
@@ -275,71 +491,154 @@ impl<const MAX_TXS: usize, const MAX_CALLDATA: usize> SuperCircuit<Fr, MAX_TXS,
     /// sub-circuits filled with their corresponding witnesses.
     ///
     /// Also, return with it the minimum required SRS degree for the circuit and
-    /// the Public Inputs needed.
-    pub fn build(
-        geth_data: GethData,
-        rng: &mut (impl RngCore + Clone),
+    /// the Public Inputs needed.  The state root after the block must be the
+    /// one of its header.
+    pub fn build(geth_data: GethData) -> Result<(u32, Self, Vec<Vec<Fr>>), bus_mapping::Error> {
+        let block = Self::convert_geth_data(&geth_data)?;
+        Self::build_from_witness(block, CircuitParams::default())
+    }
+
+    /// Generate a SuperCircuit instance like [`SuperCircuit::build`] from the
+    /// witness data of a mock block, whose header doesn't have the state root
+    /// of its accounts: the state roots of the block are replaced by the root
+    /// of the updated tries.
+    #[cfg(any(feature = "test", test))]
+    pub fn build_mock(
+        mut geth_data: GethData,
     ) -> Result<(u32, Self, Vec<Vec<Fr>>), bus_mapping::Error> {
-        let txs = geth_data
-            .eth_block
-            .transactions
-            .iter()
-            .map(geth_types::Transaction::from)
-            .collect();
+        let block = match Self::convert_geth_data(&geth_data) {
+            Err(bus_mapping::Error::EthTypeError(eth_types::Error::StateRootMismatch(
+                _,
+                state_root,
+            ))) => {
+                geth_data.eth_block.state_root = state_root;
+                Self::convert_geth_data(&geth_data)?
+            }
+            result => result?,
+        };
+        Self::build_from_witness(block, CircuitParams::default())
+    }
 
+    /// Convert the witness data of a block to a witness block, checking that
+    /// the state root after the block is the one of its header.
+    fn convert_geth_data(geth_data: &GethData) -> Result<Block<Fr>, bus_mapping::Error> {
         let mut builder =
             BlockData::new_from_geth_data(geth_data.clone()).new_circuit_input_builder();
 
         builder
             .handle_block(&geth_data.eth_block, &geth_data.geth_traces)
             .expect("could not handle block tx");
-        let keccak_inputs = builder.keccak_inputs()?;
-        let mut block = block_convert(&builder.block, &builder.code_db);
 
-        block.randomness = Fr::random(rng.clone());
-        let aux_generator = <Secp256k1Affine as CurveAffine>::CurveExt::random(rng).to_affine();
+        let state = MptState::new(&geth_data.accounts).map_err(bus_mapping::Error::EthTypeError)?;
+        Self::convert_block(&builder, state)
+    }
 
-        let fixed_table_tags: Vec<FixedTableTag> = FixedTableTag::iter().collect();
-        let log2_ceil = |n| u32::BITS - (n as u32).leading_zeros() - (n & (n - 1) == 0) as u32;
+    /// Convert the circuit inputs of a block to a witness block, with the MPT
+    /// updates of the block applied to `state`, checking that the state root
+    /// after them is the one of its header.
+    fn convert_block(
+        builder: &CircuitInputBuilder,
+        mut state: MptState,
+    ) -> Result<Block<Fr>, bus_mapping::Error> {
+        let block = block_convert_with_state(&builder.block, &builder.code_db, &mut state)
+            .map_err(bus_mapping::Error::EthTypeError)?;
+        if state.root() != block.state_root {
+            return Err(bus_mapping::Error::EthTypeError(
+                eth_types::Error::StateRootMismatch(block.state_root, state.root()),
+            ));
+        }
+        Ok(block)
+    }
 
-        let num_rows_required_for_steps =
-            SuperCircuit::<_, MAX_TXS, MAX_CALLDATA>::get_num_rows_required(&block);
+    /// From the circuit inputs generated by a [`CircuitInputBuilder`] that
+    /// has already handled a block, generate a SuperCircuit instance like
+    /// [`SuperCircuit::build`] does.  This allows building the circuit from
+    /// inputs that were not generated from a [`BlockData`], like the ones of
+    /// a [`BlockBundle`](bus_mapping::circuit_input_builder::BlockBundle).
+    /// `state` holds the tries of the state before the block, to which the
+    /// MPT updates of the block are applied, and the state root after them
+    /// must be the one of the header of the block.
+    ///
+    /// The sub-circuits are padded to the sizes of `circuit_params`, and the
+    /// returned degree is `circuit_params.degree` when it's set.  The maximum
+    /// number of transactions and calldata are always the ones of the
+    /// `MAX_TXS` and `MAX_CALLDATA` generics.  Use
+    /// [`SuperCircuit::check_params`] to check that the block fits in the
+    /// parameters.
+    pub fn build_from_circuit_input_builder(
+        builder: &CircuitInputBuilder,
+        state: MptState,
+        circuit_params: CircuitParams,
+    ) -> Result<(u32, Self, Vec<Vec<Fr>>), bus_mapping::Error> {
+        let block = Self::convert_block(builder, state)?;
+        Self::build_from_witness(block, circuit_params)
+    }
 
-        let k = log2_ceil(
-            64 + fixed_table_tags
-                .iter()
-                .map(|tag| tag.build::<Fr>().count())
-                .sum::<usize>(),
-        );
-        let bytecodes_len = block
-            .bytecodes
-            .iter()
-            .map(|(_, bytecode)| bytecode.bytes.len())
-            .sum::<usize>();
-        let k = k.max(log2_ceil(64 + bytecodes_len));
-        let k = k.max(log2_ceil(64 + num_rows_required_for_steps));
-        let k = k + 1;
-        log::debug!("super circuit uses k = {}", k);
+    /// From the circuit inputs generated by a [`CircuitInputBuilder`] that
+    /// has already handled a block, generate a SuperCircuit instance for each
+    /// chunk of whole transactions with at most `max_steps` steps of the
+    /// block, like [`SuperCircuit::build_from_circuit_input_builder`] does for
+    /// the whole block.  The degree of each instance is the one of its chunk
+    /// when `circuit_params.degree` isn't set.  See
+    /// [`chunk_convert_with_state`].
+    pub fn build_chunks_from_circuit_input_builder(
+        builder: &CircuitInputBuilder,
+        mut state: MptState,
+        circuit_params: CircuitParams,
+        max_steps: usize,
+    ) -> Result<Vec<(u32, Self, Vec<Vec<Fr>>)>, bus_mapping::Error> {
+        let block = Self::convert_block(builder, state.clone())?;
+        chunk_convert_with_state(&block, max_steps, &mut state)
+            .map_err(bus_mapping::Error::EthTypeError)?
+            .into_iter()
+            .map(|chunk| Self::build_from_witness(chunk, circuit_params))
+            .collect()
+    }
 
-        let mut instance: Vec<Vec<Fr>> = (1..POW_RAND_SIZE + 1)
-            .map(|exp| vec![block.randomness.pow_vartime(&[exp as u64, 0, 0, 0]); (1 << k) - 64])
-            .collect();
-        // SignVerifyChip -> ECDSAChip -> MainGate instance column
-        instance.push(vec![]);
+    /// From the consecutive blocks of a batch, handled by a
+    /// [`CircuitInputBuilder`] with
+    /// [`CounterMode::Continuous`](circuit_input_builder::CounterMode::Continuous),
+    /// generate a SuperCircuit instance proving them together, like
+    /// [`SuperCircuit::build_from_circuit_input_builder`] does for a block.
+    /// `state` holds the tries of the state before the first block, and the
+    /// state root after the MPT updates of the batch must be the one of the
+    /// header of the last block, see [`batch_convert`].
+    pub fn build_from_batch(
+        blocks: &[circuit_input_builder::Block],
+        code_db: &bus_mapping::state_db::CodeDB,
+        mut state: MptState,
+        circuit_params: CircuitParams,
+    ) -> Result<(u32, Self, Vec<Vec<Fr>>), bus_mapping::Error> {
+        let block =
+            batch_convert(blocks, code_db, &mut state).map_err(bus_mapping::Error::EthTypeError)?;
+        Self::build_from_witness(block, circuit_params)
+    }
 
-        let chain_id = block.context.chain_id;
-        let tx_circuit = TxCircuit::new(aux_generator, block.randomness, chain_id.as_u64(), txs);
+    /// Generate a SuperCircuit instance from a witness block whose MPT
+    /// updates are built from the tries of the state, padded to the sizes of
+    /// `circuit_params`.
+    fn build_from_witness(
+        mut block: Block<Fr>,
+        circuit_params: CircuitParams,
+    ) -> Result<(u32, Self, Vec<Vec<Fr>>), bus_mapping::Error> {
+        block.circuit_params = CircuitParams {
+            max_txs: MAX_TXS,
+            max_calldata: MAX_CALLDATA,
+            ..circuit_params
+        };
+        // Fail on an invalid transaction signature or an MPT update that can't
+        // be proved before building the Keccak circuit, which expects valid
+        // ones.
+        keccak_bit::keccak_inputs(&block)?;
 
-        let circuit = SuperCircuit::<_, MAX_TXS, MAX_CALLDATA> {
-            block,
-            fixed_table_tags,
-            tx_circuit,
-            keccak_inputs,
-            // Instead of using 1 << k - NUM_BLINDING_ROWS, we use a much smaller number of enabled
-            // rows for the Bytecode Circuit because otherwise it penalizes significantly the
-            // MockProver verification time.
-            bytecode_size: bytecodes_len + 64,
+        let k = match circuit_params.degree {
+            0 => Self::degree_for_rows(Self::min_num_rows_block(&block)),
+            degree => degree,
         };
+        log::debug!("super circuit uses k = {}", k);
+
+        let circuit = Self::new_from_block(&block);
+        let instance = circuit.instance();
         Ok((k, circuit, instance))
     }
 }
@@ -349,12 +648,15 @@ mod super_circuit_tests {
     use super::*;
     use ethers_signers::{LocalWallet, Signer};
     use halo2_proofs::dev::MockProver;
-    use mock::{TestContext, MOCK_CHAIN_ID};
+    use mock::{eth, TestContext, MOCK_ACCOUNTS, MOCK_CHAIN_ID, MOCK_WALLETS};
     use rand::SeedableRng;
     use rand_chacha::ChaCha20Rng;
     use std::collections::HashMap;
 
-    use eth_types::{address, bytecode, geth_types::GethData, Word};
+    use crate::evm_circuit::test::run_test_circuit;
+    use bus_mapping::{circuit_input_builder::CounterMode, state_db::CodeDB};
+    use eth_types::{address, bytecode, geth_types::GethData, ToWord, Word, H256};
+    use itertools::Itertools;
 
     // High memory usage test.  Run in serial with:
     // `cargo test [...] skip_ -- --ignored --test-threads 1`
@@ -402,8 +704,7 @@ mod super_circuit_tests {
 
         block.sign(&wallets);
 
-        let (k, circuit, instance) =
-            SuperCircuit::<_, 1, 32>::build(block, &mut ChaCha20Rng::seed_from_u64(2)).unwrap();
+        let (k, circuit, instance) = SuperCircuit::<_, 1, 32>::build_mock(block).unwrap();
         let prover = MockProver::run(k, &circuit, instance).unwrap();
         let res = prover.verify();
         if let Err(err) = res {
@@ -412,4 +713,312 @@ mod super_circuit_tests {
             panic!("Failed verification");
         }
     }
+
+    #[test]
+    fn build_rejects_header_state_root_mismatch() {
+        let block: GethData = TestContext::<2, 1>::simple_ctx_with_bytecode(bytecode! { STOP })
+            .unwrap()
+            .into();
+        // The header of the mock block doesn't have the state root of its
+        // accounts.
+        assert!(matches!(
+            SuperCircuit::<Fr, 1, 32>::build(block),
+            Err(bus_mapping::Error::EthTypeError(
+                eth_types::Error::StateRootMismatch(header_root, _)
+            )) if header_root == H256::zero()
+        ));
+    }
+
+    fn chunks_of_simple_block() -> Vec<(u32, SuperCircuit<Fr, 1, 32>, Vec<Vec<Fr>>)> {
+        let code = bytecode! {
+            PUSH1(0x01)
+            PUSH1(0x02)
+            ADD
+            PUSH1(0x03)
+            MUL
+            STOP
+        };
+        let block: GethData = TestContext::<2, 3>::new(
+            None,
+            |accs| {
+                accs[0]
+                    .address(MOCK_ACCOUNTS[0])
+                    .balance(eth(10))
+                    .code(code);
+                accs[1].address(MOCK_ACCOUNTS[1]).balance(eth(10));
+            },
+            |txs, accs| {
+                for tx in txs {
+                    tx.from(accs[1].address).to(accs[0].address);
+                }
+            },
+            |block, _tx| block.number(0xcafeu64),
+        )
+        .unwrap()
+        .into();
+        let mut builder = BlockData::new_from_geth_data(block.clone()).new_circuit_input_builder();
+        builder
+            .handle_block(&block.eth_block, &block.geth_traces)
+            .unwrap();
+        let state = MptState::new(&block.accounts).unwrap();
+        // The header of the mock block doesn't have the state root of its
+        // accounts.
+        builder.block.state_root =
+            block_convert_with_state(&builder.block, &builder.code_db, &mut state.clone())
+                .unwrap()
+                .state_root;
+        // Each transaction has 8 steps, so two of them don't fit in a chunk.
+        SuperCircuit::build_chunks_from_circuit_input_builder(
+            &builder,
+            state,
+            CircuitParams::default(),
+            10,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn chunks_are_linked_by_their_boundaries() {
+        let chunks = chunks_of_simple_block();
+        assert_eq!(chunks.len(), 3);
+        // The instance columns are the ones of the EVM, State, Tx and
+        // PublicInputs circuits.  The rw row exposed by the State circuit has
+        // 11 columns, and the public inputs are rand_rpi, rpi_rlc, chain_id,
+        // and the hi and lo halves of the state root and the previous state
+        // root of the batch, and of the state root after and before the chunk.
+        let pi_state_root = |instance: &[Vec<Fr>]| instance[3][3..5].to_vec();
+        let pi_prev_state_root = |instance: &[Vec<Fr>]| instance[3][5..7].to_vec();
+        let pi_chunk_state_root = |instance: &[Vec<Fr>]| instance[3][7..9].to_vec();
+        let pi_chunk_prev_state_root = |instance: &[Vec<Fr>]| instance[3][9..11].to_vec();
+
+        let (first, last) = (&chunks[0].2, &chunks[chunks.len() - 1].2);
+        assert_eq!(pi_chunk_prev_state_root(first), pi_prev_state_root(first));
+        assert_eq!(pi_chunk_state_root(last), pi_state_root(last));
+        for ((_, _, instance), (_, _, next_instance)) in chunks.iter().tuple_windows() {
+            // The state after the last step of a chunk is the state of the first
+            // step of the next one.
+            let (evm, next_evm) = (&instance[0], &next_instance[0]);
+            assert_eq!(evm[evm.len() / 2..], next_evm[..next_evm.len() / 2]);
+            // The receipt row exported by a chunk is imported by the next one.
+            let (state, next_state) = (&instance[1], &next_instance[1]);
+            assert_eq!(state[11..], next_state[..11]);
+            assert!(state[11..].iter().any(|value| *value != Fr::zero()));
+            // The state root after a chunk is the one before the next one.
+            assert_eq!(
+                pi_chunk_state_root(instance),
+                pi_chunk_prev_state_root(next_instance)
+            );
+            assert_eq!(instance[3][2..7], next_instance[3][2..7]);
+        }
+    }
+
+    // High memory usage test.  Run in serial with:
+    // `cargo test [...] skip_ -- --ignored --test-threads 1`
+    #[ignore]
+    #[test]
+    fn skip_test_super_circuit_chunks() {
+        for (k, circuit, instance) in chunks_of_simple_block() {
+            let prover = MockProver::run(k, &circuit, instance).unwrap();
+            assert_eq!(prover.verify(), Ok(()));
+        }
+    }
+
+    #[test]
+    fn row_usage_of_simple_block() {
+        let code = bytecode! {
+            PUSH1(0x01)
+            PUSH1(0x02)
+            ADD
+            STOP
+        };
+        let block: GethData = TestContext::<2, 1>::simple_ctx_with_bytecode(code)
+            .unwrap()
+            .into();
+        let mut builder = BlockData::new_from_geth_data(block.clone()).new_circuit_input_builder();
+        builder
+            .handle_block(&block.eth_block, &block.geth_traces)
+            .unwrap();
+        let mut state = MptState::new(&block.accounts).unwrap();
+        let witness_block =
+            block_convert_with_state(&builder.block, &builder.code_db, &mut state).unwrap();
+
+        let estimator = RowUsageEstimator::<1, 32>::new();
+        let row_usage = estimator.row_usage(&witness_block).unwrap();
+        assert_eq!(
+            row_usage.evm,
+            SuperCircuit::<Fr, 1, 32>::get_num_rows_required(&witness_block)
+        );
+        assert_eq!(
+            row_usage.state,
+            witness_block.rws.0.values().map(Vec::len).sum::<usize>() + 1
+        );
+        assert_eq!(
+            row_usage.mpt,
+            MptCircuit::<Fr>::get_num_rows_required(&witness_block.mpt_updates).unwrap()
+        );
+        assert!(row_usage.mpt > 0);
+        assert!(row_usage.max_rows() < 1 << estimator.min_k(&row_usage));
+        // The degree of the circuit built from the block
+        assert_eq!(
+            estimator.min_k(&row_usage),
+            SuperCircuit::<Fr, 1, 32>::degree_for_rows(
+                SuperCircuit::<Fr, 1, 32>::min_num_rows_block(&witness_block)
+            )
+        );
+    }
+
+    #[test]
+    fn keygen_params_must_be_set_and_fit_in_degree() {
+        let circuit_params = CircuitParams {
+            degree: 20,
+            max_txs: 1,
+            max_calldata: 32,
+            max_rws: 1 << 12,
+            max_bytecode: 1 << 12,
+            max_copy_rows: 1 << 12,
+            max_keccak_rows: 1 << 15,
+            max_evm_rows: 1 << 12,
+            max_mpt_rows: 0,
+        };
+        assert_eq!(
+            SuperCircuit::<Fr, 1, 32>::new_for_keygen(circuit_params).err(),
+            Some(ParamUnset {
+                param: "max_mpt_rows"
+            })
+        );
+
+        let circuit = SuperCircuit::<Fr, 1, 32>::new_for_keygen(CircuitParams {
+            max_mpt_rows: 1 << 12,
+            ..circuit_params
+        })
+        .unwrap();
+        assert_eq!(circuit.check_params(), Ok(()));
+
+        // The padded rows don't fit in 2^degree
+        let circuit = SuperCircuit::<Fr, 1, 32>::new_for_keygen(CircuitParams {
+            max_mpt_rows: 1 << 20,
+            ..circuit_params
+        })
+        .unwrap();
+        assert_eq!(
+            circuit.check_params().map_err(|err| err.param),
+            Err("degree")
+        );
+    }
+
+    /// Two consecutive blocks, each one with a signed transfer from a
+    /// different account, handled with continuous counters, and the tries of
+    /// the state before them.  The state root of the header of the last block
+    /// is the one after the transfers.
+    fn batch_of_two_blocks() -> (Vec<circuit_input_builder::Block>, CodeDB, MptState) {
+        let parent_hash = H256::from_low_u64_be(0xcafe);
+        let blocks: Vec<GethData> = (0..2)
+            .map(|index| {
+                TestContext::<3, 1>::new(
+                    None,
+                    |accs| {
+                        accs[0].address(MOCK_ACCOUNTS[0]).balance(eth(10));
+                        for (acc, wallet) in accs[1..].iter_mut().zip(MOCK_WALLETS.iter()) {
+                            acc.address(wallet.address()).balance(eth(10));
+                        }
+                    },
+                    |mut txs, accs| {
+                        txs[0]
+                            .from(MOCK_WALLETS[index].clone())
+                            .to(accs[0].address)
+                            .value(eth(1));
+                    },
+                    |block, _tx| {
+                        if index == 0 {
+                            block.number(0xcafe).hash(parent_hash)
+                        } else {
+                            block.number(0xcaff).parent_hash(parent_hash)
+                        }
+                    },
+                )
+                .unwrap()
+                .into()
+            })
+            .collect();
+
+        let block_data = BlockData::new_from_geth_data(blocks[0].clone());
+        let mut builder = block_data.new_circuit_input_builder();
+        builder
+            .handle_block(&blocks[0].eth_block, &blocks[0].geth_traces)
+            .unwrap();
+        let mut history_hashes = block_data.history_hashes.clone();
+        history_hashes.push(blocks[1].eth_block.parent_hash.to_word());
+        let next_block = circuit_input_builder::Block::new(
+            block_data.chain_id,
+            history_hashes,
+            &blocks[1].eth_block,
+        )
+        .unwrap();
+        let first_block = builder.begin_next_block(next_block, CounterMode::Continuous);
+        builder
+            .handle_block(&blocks[1].eth_block, &blocks[1].geth_traces)
+            .unwrap();
+        let mut batch = vec![first_block, builder.block];
+        let state = MptState::new(&blocks[0].accounts).unwrap();
+
+        // The headers of the mock blocks don't have the state root of their
+        // accounts.
+        match batch_convert(&batch, &builder.code_db, &mut state.clone()) {
+            Err(eth_types::Error::StateRootMismatch(header_root, state_root)) => {
+                assert_eq!(header_root, H256::zero());
+                batch[1].state_root = state_root;
+            }
+            result => panic!("unexpected batch conversion result: {:?}", result.err()),
+        }
+        (batch, builder.code_db, state)
+    }
+
+    #[test]
+    fn batch_of_two_blocks_in_evm_and_state_circuits() {
+        let (blocks, code_db, mut state) = batch_of_two_blocks();
+        let prev_state_root = state.root();
+        let block = batch_convert(&blocks, &code_db, &mut state).unwrap();
+
+        assert_eq!(block.prev_state_root, prev_state_root);
+        assert_eq!(block.state_root, state.root());
+        assert_eq!(
+            block
+                .txs
+                .iter()
+                .map(|tx| (tx.id, tx.block_number))
+                .collect::<Vec<_>>(),
+            vec![(1, 0xcafe), (2, 0xcaff)]
+        );
+
+        assert_eq!(run_test_circuit(block.clone()), Ok(()));
+        let state_circuit = StateCircuit::<Fr>::new_from_block(&block);
+        let prover = MockProver::<Fr>::run(18, &state_circuit, state_circuit.instance()).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
+
+    #[test]
+    fn batch_with_missing_block() {
+        let (blocks, code_db, mut state) = batch_of_two_blocks();
+        assert!(matches!(
+            batch_convert(&blocks[1..], &code_db, &mut state),
+            Err(eth_types::Error::InvalidBatch(_))
+        ));
+    }
+
+    // High memory usage test.  Run in serial with:
+    // `cargo test [...] skip_ -- --ignored --test-threads 1`
+    #[ignore]
+    #[test]
+    fn skip_test_super_circuit_batch() {
+        let (blocks, code_db, state) = batch_of_two_blocks();
+        let (k, circuit, instance) = SuperCircuit::<_, 3, 32>::build_from_batch(
+            &blocks,
+            &code_db,
+            state,
+            CircuitParams::default(),
+        )
+        .unwrap();
+        let prover = MockProver::run(k, &circuit, instance).unwrap();
+        assert_eq!(prover.verify(), Ok(()));
+    }
 }
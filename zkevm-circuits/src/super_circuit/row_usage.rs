@@ -0,0 +1,166 @@
+//! Estimation of the rows required by the circuits of the Super Circuit to
+//! prove a block, so that a block builder can check that a block is provable
+//! before sealing it.
+
+use super::SuperCircuit;
+use crate::evm_circuit::step::ExecutionState;
+use crate::keccak_circuit::keccak_bit;
+use crate::mpt_circuit::MptCircuit;
+use crate::pi_circuit::PiCircuitConfig;
+use crate::tx_circuit::TxCircuit;
+use crate::util::SubCircuit;
+use crate::witness::Block;
+use eth_types::{Error, Field};
+use halo2_proofs::{halo2curves::bn256::Fr, plonk::Circuit, plonk::ConstraintSystem};
+use std::collections::HashMap;
+use strum::IntoEnumIterator;
+
+/// Number of rows required by each circuit to prove a block.
+///
+/// The rows of the fixed tables and of the signature verification of the Tx
+/// circuit, which don't depend on the block, are not included.  They are
+/// accounted for by [`RowUsageEstimator::min_k`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BlockRowUsage {
+    /// Rows of the execution steps of the EVM circuit
+    pub evm: usize,
+    /// Rows of the rw table and of the State circuit
+    pub state: usize,
+    /// Rows of the Copy circuit
+    pub copy: usize,
+    /// Rows of the Bytecode circuit
+    pub bytecode: usize,
+    /// Rows of the Keccak circuit
+    pub keccak: usize,
+    /// Rows of the tx table and of the RLP encoding of the Tx circuit
+    pub tx: usize,
+    /// Rows of the PublicInputs circuit
+    pub pi: usize,
+    /// Rows of the MPT circuit, which are 0 when the MPT updates of the block
+    /// have no trie nodes, like the ones of
+    /// [`block_convert`](crate::witness::block_convert)
+    pub mpt: usize,
+}
+
+impl BlockRowUsage {
+    /// Largest number of rows required by a circuit.
+    pub fn max_rows(&self) -> usize {
+        [
+            self.evm,
+            self.state,
+            self.copy,
+            self.bytecode,
+            self.keccak,
+            self.tx,
+            self.pi,
+            self.mpt,
+        ]
+        .into_iter()
+        .max()
+        .unwrap()
+    }
+}
+
+/// Estimator of the [`BlockRowUsage`] of the Super Circuit with `MAX_TXS`
+/// transactions and `MAX_CALLDATA` bytes of call data.
+///
+/// The Super Circuit is configured once when the estimator is created, so
+/// that an estimation only walks the witness of the block and is cheap
+/// enough to be done for each candidate transaction of a block.
+#[derive(Debug, Clone)]
+pub struct RowUsageEstimator<const MAX_TXS: usize, const MAX_CALLDATA: usize> {
+    step_heights: HashMap<ExecutionState, usize>,
+    // The minimum rows of the Super Circuit for an empty block, which are the
+    // rows of the fixed tables and of the signature verification
+    empty_block_rows: usize,
+}
+
+impl<const MAX_TXS: usize, const MAX_CALLDATA: usize> Default
+    for RowUsageEstimator<MAX_TXS, MAX_CALLDATA>
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const MAX_TXS: usize, const MAX_CALLDATA: usize> RowUsageEstimator<MAX_TXS, MAX_CALLDATA> {
+    /// Configure the Super Circuit to get the heights of the execution steps,
+    /// and get the rows that don't depend on the block.
+    pub fn new() -> Self {
+        let mut cs = ConstraintSystem::<Fr>::default();
+        let (config, _) = SuperCircuit::<Fr, MAX_TXS, MAX_CALLDATA>::configure(&mut cs);
+        let step_heights = ExecutionState::iter()
+            .filter_map(|state| {
+                config
+                    .evm_circuit
+                    .execution
+                    .get_step_height_option(state)
+                    .map(|height| (state, height))
+            })
+            .collect();
+
+        Self {
+            step_heights,
+            empty_block_rows: SuperCircuit::<Fr, MAX_TXS, MAX_CALLDATA>::min_num_rows_block(
+                &Block::default(),
+            ),
+        }
+    }
+
+    /// Return the rows required by each circuit to prove `block`, or the
+    /// error of an MPT update that the MPT circuit can't prove.
+    pub fn row_usage<F: Field>(&self, block: &Block<F>) -> Result<BlockRowUsage, Error> {
+        let chain_id = block.context.chain_id().as_u64();
+        let txs = &block.eth_txs;
+
+        // Start at 1 so that there is an unused `next` row available at the end
+        // of a block, and a chunk of a block is followed by the first step of
+        // the next chunk
+        let evm = 1 + block
+            .chunk_steps()
+            .chain(block.chunk_next_step())
+            .map(|(_, step)| self.step_heights[&step.execution_state])
+            .sum::<usize>();
+        // The rws are preceded by a padding row
+        let state = 1 + block.state_rws().0.values().map(Vec::len).sum::<usize>();
+        // Each copied byte takes a read and a write row, and the copy events
+        // are followed by two disabled rows
+        let copy = 2 + block
+            .copy_events
+            .iter()
+            .map(|copy_event| copy_event.bytes.len() * 2)
+            .sum::<usize>();
+        // Each bytecode takes a header row and a row per byte
+        let bytecode = block
+            .bytecodes
+            .values()
+            .map(|bytecode| bytecode.bytes.len() + 1)
+            .sum();
+        let keccak = keccak_bit::get_num_rows_required_block(block);
+        let tx = TxCircuit::<F, MAX_TXS, MAX_CALLDATA>::get_num_tx_table_rows_required(txs)
+            .max(TxCircuit::<F, MAX_TXS, MAX_CALLDATA>::get_num_rlp_rows_required(txs, chain_id));
+        let pi = PiCircuitConfig::<F, MAX_TXS, MAX_CALLDATA>::num_rows();
+        let mpt = MptCircuit::<F>::get_num_rows_required(&block.mpt_updates)?;
+
+        Ok(BlockRowUsage {
+            evm,
+            state,
+            copy,
+            bytecode,
+            keccak,
+            tx,
+            pi,
+            mpt,
+        })
+    }
+
+    /// Return the degree of the Super Circuit that fits the rows of
+    /// `row_usage` and the rows that don't depend on the block, which is the
+    /// one used by [`SuperCircuit::build_from_circuit_input_builder`] with
+    /// the default circuit params.
+    pub fn min_k(&self, row_usage: &BlockRowUsage) -> u32 {
+        SuperCircuit::<Fr, MAX_TXS, MAX_CALLDATA>::degree_for_rows(
+            row_usage.max_rows().max(self.empty_block_rows),
+        )
+    }
+}
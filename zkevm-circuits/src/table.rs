@@ -5,20 +5,20 @@ use crate::evm_circuit::util::{rlc, RandomLinearCombination};
 use crate::impl_expr;
 use crate::util::Challenges;
 use crate::witness::{
-    Block, BlockContext, Bytecode, MptUpdateRow, MptUpdates, Rw, RwMap, RwRow, Transaction,
+    Block, BlockContexts, Bytecode, MptUpdateRow, MptUpdates, Rw, RwMap, RwRow, Transaction,
 };
 use bus_mapping::circuit_input_builder::{CopyDataType, CopyEvent};
 use eth_types::{Field, ToAddress, ToLittleEndian, ToScalar, Word, U256};
 use gadgets::binary_number::{BinaryNumberChip, BinaryNumberConfig};
 use halo2_proofs::{
     arithmetic::FieldExt,
-    circuit::{Region, Value},
+    circuit::{AssignedCell, Region, Value},
     plonk::{Advice, Column, ConstraintSystem, Error},
 };
 use halo2_proofs::{circuit::Layouter, plonk::*, poly::Rotation};
 use itertools::Itertools;
 use keccak256::plain::Keccak;
-use std::array;
+use std::{array, iter};
 use strum_macros::{EnumCount, EnumIter};
 
 /// Trait used for dynamic tables.  Used to get an automatic implementation of
@@ -62,7 +62,7 @@ pub enum TxFieldTag {
     Nonce,
     /// Gas
     Gas,
-    /// GasPrice
+    /// GasPrice, the effective gas price paid by the transaction
     GasPrice,
     /// CallerAddress
     CallerAddress,
@@ -76,11 +76,31 @@ pub enum TxFieldTag {
     CallDataLength,
     /// Gas cost for transaction call data (4 for byte == 0, 16 otherwise)
     CallDataGasCost,
+    /// Number of addresses in the EIP-2930 access list
+    AccessListAddressesLen,
+    /// Number of storage keys in the EIP-2930 access list
+    AccessListStorageKeysLen,
     /// TxSignHash: Hash of the transaction without the signature, used for
     /// signing.
     TxSignHash,
+    /// Number of the block including the transaction
+    BlockNumber,
     /// CallData
     CallData,
+    /// GasFeeCap (EIP-1559 max fee per gas).  Equal to GasPrice for legacy
+    /// transactions.
+    GasFeeCap,
+    /// GasTipCap (EIP-1559 max priority fee per gas).  Equal to GasPrice for
+    /// legacy transactions.
+    GasTipCap,
+    /// RW counter of the first write of the access list in BeginTx
+    AccessListRwCounter,
+    /// Address of an access list entry without storage key.  The index is
+    /// the one of the entry in the writes of the access list.
+    AccessListAddress,
+    /// Storage key of an access list entry.  The index is the one of the
+    /// entry in the writes of the access list.
+    AccessListStorageKey,
 }
 impl_expr!(TxFieldTag);
 
@@ -107,7 +127,7 @@ impl TxTable {
             tx_id: meta.advice_column(),
             tag: meta.advice_column(),
             index: meta.advice_column(),
-            value: meta.advice_column(),
+            value: meta.advice_column_in(SecondPhase),
         }
     }
 
@@ -117,7 +137,7 @@ impl TxTable {
         &self,
         layouter: &mut impl Layouter<F>,
         txs: &[Transaction],
-        randomness: F,
+        challenges: &Challenges<Value<F>>,
     ) -> Result<(), Error> {
         layouter.assign_region(
             || "tx table",
@@ -135,13 +155,13 @@ impl TxTable {
 
                 let tx_table_columns = self.columns();
                 for tx in txs.iter() {
-                    for row in tx.table_assignments(randomness) {
+                    for row in tx.table_assignments(challenges) {
                         for (column, value) in tx_table_columns.iter().zip_eq(row) {
                             region.assign_advice(
                                 || format!("tx table row {}", offset),
                                 *column,
                                 offset,
-                                || Value::known(value),
+                                || value,
                             )?;
                         }
                         offset += 1;
@@ -211,7 +231,7 @@ impl From<RwTableTag> for usize {
 }
 
 /// Tag for an AccountField in RwTable
-#[derive(Clone, Copy, Debug, EnumIter, Hash, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, EnumIter, Hash, PartialEq, Eq, PartialOrd, Ord)]
 pub enum AccountFieldTag {
     /// Nonce field
     Nonce = 1,
@@ -359,11 +379,11 @@ impl RwTable {
             id: meta.advice_column(),
             address: meta.advice_column(),
             field_tag: meta.advice_column(),
-            storage_key: meta.advice_column(),
-            value: meta.advice_column(),
-            value_prev: meta.advice_column(),
+            storage_key: meta.advice_column_in(SecondPhase),
+            value: meta.advice_column_in(SecondPhase),
+            value_prev: meta.advice_column_in(SecondPhase),
             aux1: meta.advice_column(),
-            aux2: meta.advice_column(),
+            aux2: meta.advice_column_in(SecondPhase),
         }
     }
     /// Assign a `RwRow` at offset into the `RwTable`
@@ -371,7 +391,7 @@ impl RwTable {
         &self,
         region: &mut Region<'_, F>,
         offset: usize,
-        row: &RwRow<F>,
+        row: &RwRow<Value<F>>,
     ) -> Result<(), Error> {
         for (column, value) in [
             (self.rw_counter, row.rw_counter),
@@ -386,12 +406,7 @@ impl RwTable {
             (self.aux1, row.aux1),
             (self.aux2, row.aux2),
         ] {
-            region.assign_advice(
-                || "assign rw row on rw table",
-                column,
-                offset,
-                || Value::known(value),
-            )?;
+            region.assign_advice(|| "assign rw row on rw table", column, offset, || value)?;
         }
         Ok(())
     }
@@ -403,11 +418,11 @@ impl RwTable {
         layouter: &mut impl Layouter<F>,
         rws: &[Rw],
         n_rows: usize,
-        randomness: F,
+        challenges: &Challenges<Value<F>>,
     ) -> Result<(), Error> {
         layouter.assign_region(
             || "rw table",
-            |mut region| self.load_with_region(&mut region, rws, n_rows, randomness),
+            |mut region| self.load_with_region(&mut region, rws, n_rows, challenges),
         )
     }
 
@@ -416,11 +431,11 @@ impl RwTable {
         region: &mut Region<'_, F>,
         rws: &[Rw],
         n_rows: usize,
-        randomness: F,
+        challenges: &Challenges<Value<F>>,
     ) -> Result<(), Error> {
         let (rows, _) = RwMap::table_assignments_prepad(rws, n_rows);
         for (offset, row) in rows.iter().enumerate() {
-            self.assign(region, offset, &row.table_assignment(randomness))?;
+            self.assign(region, offset, &row.table_assignment(challenges.evm_word()))?;
         }
         Ok(())
     }
@@ -466,22 +481,28 @@ impl DynamicTableColumns for MptTable {
 impl MptTable {
     /// Construct a new MptTable
     pub(crate) fn construct<F: FieldExt>(meta: &mut ConstraintSystem<F>) -> Self {
-        Self([0; 7].map(|_| meta.advice_column()))
+        let [address, proof_type] = array::from_fn(|_| meta.advice_column());
+        let [storage_key, new_root, old_root, new_value, old_value] =
+            array::from_fn(|_| meta.advice_column_in(SecondPhase));
+        Self([
+            address,
+            storage_key,
+            proof_type,
+            new_root,
+            old_root,
+            new_value,
+            old_value,
+        ])
     }
 
     pub(crate) fn assign<F: Field>(
         &self,
         region: &mut Region<'_, F>,
         offset: usize,
-        row: &MptUpdateRow<F>,
+        row: &MptUpdateRow<Value<F>>,
     ) -> Result<(), Error> {
         for (column, value) in self.0.iter().zip_eq(row.values()) {
-            region.assign_advice(
-                || "assign mpt table row value",
-                *column,
-                offset,
-                || Value::known(*value),
-            )?;
+            region.assign_advice(|| "assign mpt table row value", *column, offset, || *value)?;
         }
         Ok(())
     }
@@ -490,11 +511,11 @@ impl MptTable {
         &self,
         layouter: &mut impl Layouter<F>,
         updates: &MptUpdates,
-        randomness: F,
+        challenges: &Challenges<Value<F>>,
     ) -> Result<(), Error> {
         layouter.assign_region(
             || "mpt table",
-            |mut region| self.load_with_region(&mut region, updates, randomness),
+            |mut region| self.load_with_region(&mut region, updates, challenges),
         )
     }
 
@@ -502,9 +523,13 @@ impl MptTable {
         &self,
         region: &mut Region<'_, F>,
         updates: &MptUpdates,
-        randomness: F,
+        challenges: &Challenges<Value<F>>,
     ) -> Result<(), Error> {
-        for (offset, row) in updates.table_assignments(randomness).iter().enumerate() {
+        for (offset, row) in updates
+            .table_assignments(challenges.evm_word())
+            .iter()
+            .enumerate()
+        {
             self.assign(region, offset, row)?;
         }
         Ok(())
@@ -635,7 +660,7 @@ impl_expr!(BlockContextFieldTag);
 pub struct BlockTable {
     /// Tag
     pub tag: Column<Advice>,
-    /// Index
+    /// Index: the number of the block of the field, zero for the chain id
     pub index: Column<Advice>,
     /// Value
     pub value: Column<Advice>,
@@ -647,45 +672,44 @@ impl BlockTable {
         Self {
             tag: meta.advice_column(),
             index: meta.advice_column(),
-            value: meta.advice_column(),
+            value: meta.advice_column_in(SecondPhase),
         }
     }
 
-    /// Assign the `BlockTable` from a `BlockContext`.
+    /// Assign the `BlockTable` from the `BlockContexts` of a batch.  Returns
+    /// the assigned cells of the `tag`, `index` and `value` columns of each
+    /// row, starting with the all-zero row, so that other circuits can
+    /// copy-constrain against them.
     pub fn load<F: Field>(
         &self,
         layouter: &mut impl Layouter<F>,
-        block: &BlockContext,
-        randomness: F,
-    ) -> Result<(), Error> {
+        block: &BlockContexts,
+        challenges: &Challenges<Value<F>>,
+    ) -> Result<Vec<[AssignedCell<F, F>; 3]>, Error> {
         layouter.assign_region(
             || "block table",
             |mut region| {
-                let mut offset = 0;
-                for column in self.columns() {
-                    region.assign_advice(
-                        || "block table all-zero row",
-                        column,
-                        offset,
-                        || Value::known(F::zero()),
-                    )?;
-                }
-                offset += 1;
-
-                let block_table_columns = self.columns();
-                for row in block.table_assignments(randomness) {
-                    for (column, value) in block_table_columns.iter().zip_eq(row) {
-                        region.assign_advice(
-                            || format!("block table row {}", offset),
-                            *column,
-                            offset,
-                            || Value::known(value),
-                        )?;
-                    }
-                    offset += 1;
+                let rows = iter::once([Value::known(F::zero()); 3])
+                    .chain(block.table_assignments(challenges));
+                let mut cells = Vec::new();
+                for (offset, row) in rows.enumerate() {
+                    let row_cells = self
+                        .columns()
+                        .into_iter()
+                        .zip_eq(row)
+                        .map(|(column, value)| {
+                            region.assign_advice(
+                                || format!("block table row {}", offset),
+                                column,
+                                offset,
+                                || value,
+                            )
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    cells.push(row_cells.try_into().unwrap());
                 }
 
-                Ok(())
+                Ok(cells)
             },
         )
     }
@@ -753,15 +777,10 @@ impl KeccakTable {
         &self,
         region: &mut Region<F>,
         offset: usize,
-        values: [F; 4],
+        values: [Value<F>; 4],
     ) -> Result<(), Error> {
         for (column, value) in self.columns().iter().zip(values.iter()) {
-            region.assign_advice(
-                || format!("assign {}", offset),
-                *column,
-                offset,
-                || Value::known(*value),
-            )?;
+            region.assign_advice(|| format!("assign {}", offset), *column, offset, || *value)?;
         }
         Ok(())
     }
@@ -860,12 +879,12 @@ impl CopyTable {
     pub fn construct<F: Field>(meta: &mut ConstraintSystem<F>, q_enable: Column<Fixed>) -> Self {
         Self {
             is_first: meta.advice_column(),
-            id: meta.advice_column(),
+            id: meta.advice_column_in(SecondPhase),
             tag: BinaryNumberChip::configure(meta, q_enable, None),
             addr: meta.advice_column(),
             src_addr_end: meta.advice_column(),
             bytes_left: meta.advice_column(),
-            rlc_acc: meta.advice_column(),
+            rlc_acc: meta.advice_column_in(SecondPhase),
             rw_counter: meta.advice_column(),
             rwc_inc_left: meta.advice_column(),
         }
@@ -874,8 +893,8 @@ impl CopyTable {
     /// Generate the copy table assignments from a copy event.
     pub fn assignments<F: Field>(
         copy_event: &CopyEvent,
-        randomness: F,
-    ) -> Vec<(CopyDataType, [F; 8])> {
+        challenges: &Challenges<Value<F>>,
+    ) -> Vec<(CopyDataType, [Value<F>; 8])> {
         let mut assignments = Vec::new();
         let rlc_acc = if copy_event.dst_type == CopyDataType::RlcAcc {
             let values = copy_event
@@ -883,9 +902,11 @@ impl CopyTable {
                 .iter()
                 .map(|(value, _)| *value)
                 .collect::<Vec<u8>>();
-            rlc::value(values.iter().rev(), randomness)
+            challenges
+                .keccak_input()
+                .map(|keccak_input| rlc::value(values.iter().rev(), keccak_input))
         } else {
-            F::zero()
+            Value::known(F::zero())
         };
         for (step_idx, is_read_step) in copy_event
             .bytes
@@ -902,7 +923,9 @@ impl CopyTable {
                 } else {
                     &copy_event.dst_id
                 };
-                number_or_hash_to_field(id, randomness)
+                challenges
+                    .evm_word()
+                    .map(|evm_word| number_or_hash_to_field(id, evm_word))
             };
             // addr
             let tag = if is_read_step {
@@ -931,14 +954,14 @@ impl CopyTable {
             assignments.push((
                 tag,
                 [
-                    is_first,
+                    Value::known(is_first),
                     id,
-                    addr,
-                    F::from(copy_event.src_addr_end),
-                    F::from(bytes_left),
+                    Value::known(addr),
+                    Value::known(F::from(copy_event.src_addr_end)),
+                    Value::known(F::from(bytes_left)),
                     rlc_acc,
-                    F::from(copy_event.rw_counter(step_idx)),
-                    F::from(copy_event.rw_counter_increase_left(step_idx)),
+                    Value::known(F::from(copy_event.rw_counter(step_idx))),
+                    Value::known(F::from(copy_event.rw_counter_increase_left(step_idx))),
                 ],
             ));
         }
@@ -950,7 +973,7 @@ impl CopyTable {
         &self,
         layouter: &mut impl Layouter<F>,
         block: &Block<F>,
-        randomness: F,
+        challenges: &Challenges<Value<F>>,
     ) -> Result<(), Error> {
         layouter.assign_region(
             || "copy table",
@@ -969,13 +992,13 @@ impl CopyTable {
                 let tag_chip = BinaryNumberChip::construct(self.tag);
                 let copy_table_columns = self.columns();
                 for copy_event in block.copy_events.iter() {
-                    for (tag, row) in Self::assignments(copy_event, randomness) {
+                    for (tag, row) in Self::assignments(copy_event, challenges) {
                         for (column, value) in copy_table_columns.iter().zip_eq(row) {
                             region.assign_advice(
                                 || format!("copy table row {}", offset),
                                 *column,
                                 offset,
-                                || Value::known(value),
+                                || value,
                             )?;
                         }
                         tag_chip.assign(&mut region, offset, &tag)?;
@@ -1,6 +1,6 @@
 //! Testing utilities
 
-use crate::{state_circuit::StateCircuit, witness::Block};
+use crate::{state_circuit::StateCircuit, util::SubCircuit, witness::Block};
 use bus_mapping::mock::BlockData;
 use eth_types::geth_types::{GethData, Transaction};
 use ethers_core::types::{NameOrAddress, TransactionRequest};
@@ -67,13 +67,10 @@ pub fn test_circuits_using_witness_block(
     }
 
     // run state circuit test
-    // TODO: use randomness as one of the circuit public input, since randomness in
-    // state circuit and evm circuit must be same
     if config.enable_state_circuit_test {
         const N_ROWS: usize = 1 << 16;
-        let state_circuit = StateCircuit::<Fr>::new(block.randomness, block.rws, N_ROWS);
-        let power_of_randomness = state_circuit.instance();
-        let prover = MockProver::<Fr>::run(18, &state_circuit, power_of_randomness).unwrap();
+        let state_circuit = StateCircuit::<Fr>::new(block.rws, N_ROWS);
+        let prover = MockProver::<Fr>::run(18, &state_circuit, state_circuit.instance()).unwrap();
         prover.verify_at_rows(
             N_ROWS - state_circuit.rows.len()..N_ROWS,
             N_ROWS - state_circuit.rows.len()..N_ROWS,
@@ -108,6 +105,8 @@ pub(crate) fn rand_tx<R: Rng + CryptoRng>(mut rng: R, chain_id: u64) -> Transact
         to,
         gas_limit: tx.gas.unwrap(),
         gas_price: tx.gas_price.unwrap(),
+        gas_fee_cap: tx.gas_price.unwrap(),
+        gas_tip_cap: tx.gas_price.unwrap(),
         value: tx.value.unwrap(),
         call_data: tx.data.unwrap(),
         nonce: tx.nonce.unwrap(),
@@ -4,23 +4,34 @@
 // - *_be: Big-Endian bytes
 // - *_le: Little-Endian bytes
 
+pub mod rlp_encoding;
 pub mod sign_verify;
 
-use crate::table::{KeccakTable, TxFieldTag, TxTable};
+use crate::evm_circuit::util::{and, constraint_builder::BaseConstraintBuilder, not};
+use crate::table::{
+    BlockContextFieldTag, BlockTable, DynamicTableColumns, KeccakTable, RwTable, RwTableTag,
+    TxFieldTag, TxTable,
+};
 use crate::util::{
-    power_of_randomness_from_instance, random_linear_combine_word as rlc, Challenges,
+    random_linear_combine_word as rlc, Challenges, Expr, SubCircuit, SubCircuitConfig,
 };
+use crate::witness::{self, access_list_entries, AccessListEntry, BlockContext, BlockContexts, Rw};
 use bus_mapping::circuit_input_builder::keccak_inputs_tx_circuit;
 use eth_types::{
     sign_types::SignData,
-    {geth_types::Transaction, Address, Field, ToLittleEndian, ToScalar},
+    {geth_types::Transaction, Address, Field, ToLittleEndian, ToScalar, Word},
 };
 use halo2_proofs::{
+    arithmetic::CurveAffine,
     circuit::{AssignedCell, Layouter, Region, SimpleFloorPlanner, Value},
-    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Expression},
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Expression, Fixed, VirtualCells},
+    poly::Rotation,
 };
 use itertools::Itertools;
 use log::error;
+use rand::SeedableRng;
+use rand_xorshift::XorShiftRng;
+use rlp_encoding::{rlp_rows, RlpEncodingConfig, MAX_ROWS_PER_ACCESS_LIST_ENTRY, MAX_ROWS_PER_TX};
 use sign_verify::{SignVerifyChip, SignVerifyConfig};
 use std::marker::PhantomData;
 
@@ -32,7 +43,15 @@ pub use halo2_proofs::halo2curves::{
     },
     secp256k1::{self, Secp256k1Affine, Secp256k1Compressed},
 };
-pub use sign_verify::{POW_RAND_SIZE, VERIF_HEIGHT};
+pub use sign_verify::VERIF_HEIGHT;
+
+/// Number of rows of each transaction in the tx table, besides its call data
+/// and its access list.
+pub const TX_LEN: usize = 16;
+
+/// Maximum number of entries of the access lists of the transactions, an
+/// entry being an address or a storage key.
+pub const MAX_ACCESS_LIST_ENTRIES: usize = 64;
 
 /// Config for TxCircuit
 #[derive(Clone, Debug)]
@@ -41,18 +60,50 @@ pub struct TxCircuitConfig<F: Field> {
     tag: Column<Advice>,
     index: Column<Advice>,
     value: Column<Advice>,
+    q_sign_hash: Column<Fixed>,
+    q_access_list_len: Column<Fixed>,
+    q_access_list: Column<Fixed>,
+    q_block_number: Column<Fixed>,
+    is_padding_tx: Column<Advice>,
+    pub(crate) access_list_address: Column<Advice>,
+    access_list_rw_counter: Column<Advice>,
+    access_list_is_last: Column<Advice>,
+    access_list_is_padding: Column<Advice>,
     sign_verify: SignVerifyConfig<F>,
+    rlp_encoding: RlpEncodingConfig<F>,
     keccak_table: KeccakTable,
+    block_table: BlockTable,
+    pub(crate) rw_table: RwTable,
     _marker: PhantomData<F>,
 }
 
-impl<F: Field> TxCircuitConfig<F> {
+/// Circuit configuration arguments
+pub struct TxCircuitConfigArgs<F: Field> {
+    /// TxTable
+    pub tx_table: TxTable,
+    /// KeccakTable
+    pub keccak_table: KeccakTable,
+    /// BlockTable
+    pub block_table: BlockTable,
+    /// RwTable
+    pub rw_table: RwTable,
+    /// Challenges
+    pub challenges: Challenges<Expression<F>>,
+}
+
+impl<F: Field> SubCircuitConfig<F> for TxCircuitConfig<F> {
+    type ConfigArgs = TxCircuitConfigArgs<F>;
+
     /// Return a new TxCircuitConfig
-    pub fn new(
+    fn new(
         meta: &mut ConstraintSystem<F>,
-        power_of_randomness: [Expression<F>; sign_verify::POW_RAND_SIZE],
-        tx_table: TxTable,
-        keccak_table: KeccakTable,
+        Self::ConfigArgs {
+            tx_table,
+            keccak_table,
+            block_table,
+            rw_table,
+            challenges,
+        }: Self::ConfigArgs,
     ) -> Self {
         let tx_id = tx_table.tx_id;
         let tag = tx_table.tag;
@@ -60,19 +111,254 @@ impl<F: Field> TxCircuitConfig<F> {
         let value = tx_table.value;
         meta.enable_equality(value);
 
-        let sign_verify = SignVerifyConfig::new(meta, power_of_randomness, keccak_table.clone());
+        let [access_list_address, access_list_rw_counter, access_list_is_last, access_list_is_padding] =
+            [(); 4].map(|_| meta.advice_column());
+
+        let rlp_encoding = RlpEncodingConfig::configure(
+            meta,
+            &challenges,
+            &tx_table,
+            access_list_address,
+            &keccak_table,
+            &block_table,
+        );
+        let sign_verify = SignVerifyConfig::new(meta, &challenges, keccak_table.clone());
+
+        // Every TxSignHash is the hash of a proven signing payload.  Padding
+        // transactions have a zero TxSignHash which matches any row.
+        let q_sign_hash = meta.fixed_column();
+        meta.lookup_any("TxSignHash in RLP encoding", |meta| {
+            let q_sign_hash = meta.query_fixed(q_sign_hash, Rotation::cur());
+            let tx_id = meta.query_advice(tx_id, Rotation::cur());
+            let value = meta.query_advice(value, Rotation::cur());
+            [
+                q_sign_hash.clone() * value.clone(),
+                q_sign_hash * tx_id * value,
+            ]
+            .into_iter()
+            .zip(rlp_encoding.sign_hash_table_exprs(meta))
+            .collect()
+        });
+
+        // The entries of the access list of a transaction follow its call
+        // data, with consecutive indices from 0.  Each entry is looked up in
+        // the writes of the access list of the transaction in BeginTx, which
+        // start at its `AccessListRwCounter`.  The entries and the length of
+        // the access list are decoded from the signing payload by the RLP
+        // encoding chip.
+        let q_access_list = meta.fixed_column();
+        meta.create_gate("access list entry", |meta| {
+            let mut cb = BaseConstraintBuilder::default();
+
+            let q_prev = meta.query_fixed(q_access_list, Rotation::prev());
+            let q_next = meta.query_fixed(q_access_list, Rotation::next());
+            let [tx_id, index, is_padding] = [tx_id, index, access_list_is_padding].map(|column| {
+                (
+                    meta.query_advice(column, Rotation::cur()),
+                    meta.query_advice(column, Rotation::next()),
+                )
+            });
+            let is_storage_key =
+                meta.query_advice(tag, Rotation::cur()) - TxFieldTag::AccessListAddress.expr();
+            let value = meta.query_advice(value, Rotation::cur());
+            let address = meta.query_advice(access_list_address, Rotation::cur());
+            let is_last = meta.query_advice(access_list_is_last, Rotation::cur());
+            let is_last_prev = meta.query_advice(access_list_is_last, Rotation::prev());
+
+            // The entries are followed by padding rows up to the fixed number
+            // of entries, on which the other constraints are disabled.
+            cb.require_boolean("is_padding is boolean", is_padding.0.clone());
+            cb.require_zero(
+                "a padding row is followed by a padding row",
+                q_next.clone() * is_padding.0.clone() * not::expr(is_padding.1.clone()),
+            );
+            let is_entry = not::expr(is_padding.0);
+            cb.condition(is_entry.clone(), |cb| {
+                cb.require_boolean("is_last is boolean", is_last.clone());
+                cb.require_boolean(
+                    "tag is AccessListAddress or AccessListStorageKey",
+                    is_storage_key.clone(),
+                );
+                cb.require_zero(
+                    "the first entry of a transaction has index 0",
+                    (1.expr() - q_prev.clone() + q_prev * is_last_prev) * index.0.clone(),
+                );
+            });
+            cb.condition(
+                and::expr([is_entry.clone(), not::expr(is_storage_key)]),
+                |cb| {
+                    cb.require_equal("value == address", value, address);
+                },
+            );
+            cb.condition(and::expr([is_entry, not::expr(is_last)]), |cb| {
+                cb.require_equal("the entries continue on the next row", q_next, 1.expr());
+                cb.require_zero("the next row is not padding", is_padding.1.clone());
+                cb.require_equal("tx_id_next == tx_id", tx_id.1.clone(), tx_id.0.clone());
+                cb.require_equal(
+                    "index_next == index + 1",
+                    index.1.clone(),
+                    index.0.clone() + 1.expr(),
+                );
+            });
+
+            cb.gate(meta.query_fixed(q_access_list, Rotation::cur()))
+        });
+
+        let q_access_list_entry = |meta: &mut VirtualCells<F>| {
+            meta.query_fixed(q_access_list, Rotation::cur())
+                * not::expr(meta.query_advice(access_list_is_padding, Rotation::cur()))
+        };
+
+        meta.lookup_any("access list entry rw counter", |meta| {
+            let enable = q_access_list_entry(meta);
+            [
+                meta.query_advice(tx_id, Rotation::cur()),
+                TxFieldTag::AccessListRwCounter.expr(),
+                0.expr(),
+                meta.query_advice(access_list_rw_counter, Rotation::cur())
+                    - meta.query_advice(index, Rotation::cur()),
+            ]
+            .into_iter()
+            .zip(tx_table.columns())
+            .map(|(input, table)| {
+                (
+                    enable.clone() * input,
+                    meta.query_advice(table, Rotation::cur()),
+                )
+            })
+            .collect()
+        });
+
+        meta.lookup_any("access list entry write", |meta| {
+            let enable = q_access_list_entry(meta);
+            let is_storage_key =
+                meta.query_advice(tag, Rotation::cur()) - TxFieldTag::AccessListAddress.expr();
+            [
+                meta.query_advice(access_list_rw_counter, Rotation::cur()),
+                1.expr(),
+                RwTableTag::TxAccessListAccount.expr()
+                    + is_storage_key.clone()
+                        * (RwTableTag::TxAccessListAccountStorage.expr()
+                            - RwTableTag::TxAccessListAccount.expr()),
+                meta.query_advice(tx_id, Rotation::cur()),
+                meta.query_advice(access_list_address, Rotation::cur()),
+                0.expr(),
+                is_storage_key * meta.query_advice(value, Rotation::cur()),
+                1.expr(),
+            ]
+            .into_iter()
+            .zip(rw_table.columns())
+            .map(|(input, table)| {
+                (
+                    enable.clone() * input,
+                    meta.query_advice(table, Rotation::cur()),
+                )
+            })
+            .collect()
+        });
+
+        // The entry of the last write of the access list of each transaction
+        // exists: the access list length, made of the AccessListAddressesLen
+        // and AccessListStorageKeysLen rows, is `index + 1` of one of its
+        // entries, or any other row of the transaction when it's 0.
+        let q_access_list_len = meta.fixed_column();
+        meta.lookup_any("access list length", |meta| {
+            let q_access_list_len = meta.query_fixed(q_access_list_len, Rotation::cur());
+            let tx_id = meta.query_advice(tx_id, Rotation::cur());
+            let access_list_len = meta.query_advice(value, Rotation::cur())
+                + meta.query_advice(value, Rotation::next());
+            let entry_len =
+                q_access_list_entry(meta) * (meta.query_advice(index, Rotation::cur()) + 1.expr());
+            vec![
+                (q_access_list_len.clone() * tx_id.clone(), tx_id),
+                (q_access_list_len * access_list_len, entry_len),
+            ]
+        });
+
+        // The block number of each transaction is the number of a block of
+        // the block table, and doesn't decrease from a transaction to the
+        // next one.  The padding transactions, which follow the others, have
+        // a zero block number.
+        let q_block_number = meta.fixed_column();
+        let is_padding_tx = meta.advice_column();
+        meta.create_gate("tx block number", |meta| {
+            let mut cb = BaseConstraintBuilder::default();
+
+            let q_next = meta.query_fixed(q_block_number, Rotation(TX_LEN as i32));
+            let is_padding = meta.query_advice(is_padding_tx, Rotation::cur());
+            let is_padding_next = meta.query_advice(is_padding_tx, Rotation(TX_LEN as i32));
+            let block_number = meta.query_advice(value, Rotation::cur());
+
+            cb.require_boolean("is_padding_tx is boolean", is_padding.clone());
+            cb.require_zero(
+                "a padding tx has a zero block number",
+                is_padding.clone() * block_number,
+            );
+            cb.require_zero(
+                "a padding tx is followed by a padding tx",
+                q_next * is_padding * not::expr(is_padding_next),
+            );
+
+            cb.gate(meta.query_fixed(q_block_number, Rotation::cur()))
+        });
+
+        meta.lookup_any("tx block number in block table", |meta| {
+            let enable = meta.query_fixed(q_block_number, Rotation::cur())
+                * not::expr(meta.query_advice(is_padding_tx, Rotation::cur()));
+            let block_number = meta.query_advice(value, Rotation::cur());
+            [
+                BlockContextFieldTag::Number.expr(),
+                block_number.clone(),
+                block_number,
+            ]
+            .into_iter()
+            .zip(block_table.columns())
+            .map(|(input, table)| {
+                (
+                    enable.clone() * input,
+                    meta.query_advice(table, Rotation::cur()),
+                )
+            })
+            .collect()
+        });
+
+        meta.lookup_any("tx block number doesn't decrease", |meta| {
+            let enable = meta.query_fixed(q_block_number, Rotation::cur())
+                * meta.query_fixed(q_block_number, Rotation(TX_LEN as i32))
+                * not::expr(meta.query_advice(is_padding_tx, Rotation(TX_LEN as i32)));
+            let diff = meta.query_advice(value, Rotation(TX_LEN as i32))
+                - meta.query_advice(value, Rotation::cur());
+            vec![(
+                enable * diff,
+                meta.query_fixed(rlp_encoding.byte_column(), Rotation::cur()),
+            )]
+        });
 
         Self {
             tx_id,
             tag,
             index,
             value,
+            q_sign_hash,
+            q_access_list_len,
+            q_access_list,
+            q_block_number,
+            is_padding_tx,
+            access_list_address,
+            access_list_rw_counter,
+            access_list_is_last,
+            access_list_is_padding,
             sign_verify,
+            rlp_encoding,
             keccak_table,
+            block_table,
+            rw_table,
             _marker: PhantomData,
         }
     }
+}
 
+impl<F: Field> TxCircuitConfig<F> {
     /// Assigns a tx circuit row and returns the assigned cell of the value in
     /// the row.
     fn assign_row(
@@ -82,7 +368,7 @@ impl<F: Field> TxCircuitConfig<F> {
         tx_id: usize,
         tag: TxFieldTag,
         index: usize,
-        value: F,
+        value: Value<F>,
     ) -> Result<AssignedCell<F, F>, Error> {
         region.assign_advice(
             || "tx_id",
@@ -102,7 +388,62 @@ impl<F: Field> TxCircuitConfig<F> {
             offset,
             || Value::known(F::from(index as u64)),
         )?;
-        region.assign_advice(|| "value", self.value, offset, || Value::known(value))
+        region.assign_advice(|| "value", self.value, offset, || value)
+    }
+
+    /// Assigns the row of an access list entry, or a padding row when there
+    /// is no entry.
+    #[allow(clippy::too_many_arguments)]
+    fn assign_access_list_row(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        tx_id: usize,
+        index: usize,
+        entry: Option<&AccessListEntry>,
+        rw_counter: usize,
+        is_last: bool,
+        challenges: &Challenges<Value<F>>,
+    ) -> Result<(), Error> {
+        self.assign_row(
+            region,
+            offset,
+            tx_id,
+            entry.map_or(TxFieldTag::AccessListAddress, |entry| entry.tag()),
+            index,
+            entry.map_or(Value::known(F::zero()), |entry| entry.value(challenges)),
+        )?;
+        region.assign_fixed(
+            || "q_access_list",
+            self.q_access_list,
+            offset,
+            || Value::known(F::one()),
+        )?;
+        for (name, column, value) in [
+            (
+                "access_list_address",
+                self.access_list_address,
+                entry.map_or(F::zero(), |entry| entry.address.to_scalar().unwrap()),
+            ),
+            (
+                "access_list_rw_counter",
+                self.access_list_rw_counter,
+                F::from(rw_counter as u64),
+            ),
+            (
+                "access_list_is_last",
+                self.access_list_is_last,
+                F::from(is_last as u64),
+            ),
+            (
+                "access_list_is_padding",
+                self.access_list_is_padding,
+                F::from(entry.is_none() as u64),
+            ),
+        ] {
+            region.assign_advice(|| name, column, offset, || Value::known(value))?;
+        }
+        Ok(())
     }
 }
 
@@ -111,41 +452,53 @@ impl<F: Field> TxCircuitConfig<F> {
 pub struct TxCircuit<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> {
     /// SignVerify chip
     pub sign_verify: SignVerifyChip<F, MAX_TXS>,
-    /// Randomness for RLC encoding
-    pub randomness: F,
     /// List of Transactions
     pub txs: Vec<Transaction>,
     /// Chain ID
     pub chain_id: u64,
+    /// RW counter of the first write of the access list of each transaction
+    /// in BeginTx
+    pub access_list_rw_counters: Vec<usize>,
+    /// Id of the transaction before the first one, whose id is
+    /// `tx_id_offset + 1`
+    pub tx_id_offset: usize,
 }
 
 impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize>
     TxCircuit<F, MAX_TXS, MAX_CALLDATA>
 {
-    /// Return a new TxCircuit
-    pub fn new(
-        aux_generator: Secp256k1Affine,
-        randomness: F,
-        chain_id: u64,
-        txs: Vec<Transaction>,
-    ) -> Self {
+    /// Return a new TxCircuit.  The writes of the access lists of the
+    /// transactions are taken to follow each other from the RW counter 1.
+    pub fn new(aux_generator: Secp256k1Affine, chain_id: u64, txs: Vec<Transaction>) -> Self {
+        let access_list_rw_counters = txs
+            .iter()
+            .scan(1, |rw_counter, tx| {
+                let access_list_rw_counter = *rw_counter;
+                *rw_counter +=
+                    access_list_entries(&tx.access_list.clone().unwrap_or_default()).len();
+                Some(access_list_rw_counter)
+            })
+            .collect();
         TxCircuit::<F, MAX_TXS, MAX_CALLDATA> {
             sign_verify: SignVerifyChip {
                 aux_generator,
                 window_size: 2,
                 _marker: PhantomData,
             },
-            randomness,
             txs,
             chain_id,
+            access_list_rw_counters,
+            tx_id_offset: 0,
         }
     }
 
-    /// Make the assignments to the TxCircuit
-    pub fn assign(
+    /// Make the assignments to the tx table and the RLP encoding of the
+    /// TxCircuit
+    fn assign(
         &self,
         config: &TxCircuitConfig<F>,
         layouter: &mut impl Layouter<F>,
+        challenges: &Challenges<Value<F>>,
     ) -> Result<(), Error> {
         assert!(self.txs.len() <= MAX_TXS);
         let sign_datas: Vec<SignData> = self
@@ -161,14 +514,21 @@ impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize>
 
         let assigned_sig_verifs =
             self.sign_verify
-                .assign(&config.sign_verify, layouter, self.randomness, &sign_datas)?;
+                .assign(&config.sign_verify, layouter, challenges, &sign_datas)?;
 
         layouter.assign_region(
             || "tx table",
             |mut region| {
                 let mut offset = 0;
                 // Empty entry
-                config.assign_row(&mut region, offset, 0, TxFieldTag::Null, 0, F::zero())?;
+                config.assign_row(
+                    &mut region,
+                    offset,
+                    0,
+                    TxFieldTag::Null,
+                    0,
+                    Value::known(F::zero()),
+                )?;
                 offset += 1;
                 // Assign al Tx fields except for call data
                 let tx_default = Transaction::default();
@@ -180,56 +540,124 @@ impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize>
                         &tx_default
                     };
 
+                    let access_list = tx.access_list.clone().unwrap_or_default();
                     let address_cell = assigned_sig_verif.address.cell();
                     let msg_hash_rlc_cell = assigned_sig_verif.msg_hash_rlc.cell();
-                    let mut msg_hash_rlc_value = F::zero();
-                    assigned_sig_verif.msg_hash_rlc.value().map(|f| {
-                        msg_hash_rlc_value = *f;
-                        f
-                    });
+                    let msg_hash_rlc_value = assigned_sig_verif.msg_hash_rlc.value().copied();
+                    let word_rlc = |word: Word| {
+                        challenges
+                            .evm_word()
+                            .map(|randomness| rlc(word.to_le_bytes(), randomness))
+                    };
                     for (tag, value) in &[
+                        (TxFieldTag::Nonce, Value::known(F::from(tx.nonce.as_u64()))),
                         (
-                            TxFieldTag::Nonce,
-                            rlc(tx.nonce.to_le_bytes(), self.randomness),
-                        ),
-                        (TxFieldTag::Gas, F::from(tx.gas_limit.as_u64())),
-                        (
-                            TxFieldTag::GasPrice,
-                            rlc(tx.gas_price.to_le_bytes(), self.randomness),
+                            TxFieldTag::Gas,
+                            Value::known(F::from(tx.gas_limit.as_u64())),
                         ),
+                        (TxFieldTag::GasPrice, word_rlc(tx.gas_price)),
+                        (TxFieldTag::GasFeeCap, word_rlc(tx.gas_fee_cap)),
+                        (TxFieldTag::GasTipCap, word_rlc(tx.gas_tip_cap)),
                         (
                             TxFieldTag::CallerAddress,
-                            tx.from.to_scalar().expect("tx.from too big"),
+                            Value::known(tx.from.to_scalar().expect("tx.from too big")),
                         ),
                         (
                             TxFieldTag::CalleeAddress,
-                            tx.to
-                                .unwrap_or_else(Address::zero)
-                                .to_scalar()
-                                .expect("tx.to too big"),
+                            Value::known(
+                                tx.to
+                                    .unwrap_or_else(Address::zero)
+                                    .to_scalar()
+                                    .expect("tx.to too big"),
+                            ),
                         ),
-                        (TxFieldTag::IsCreate, F::from(tx.to.is_none() as u64)),
                         (
-                            TxFieldTag::Value,
-                            rlc(tx.value.to_le_bytes(), self.randomness),
+                            TxFieldTag::IsCreate,
+                            Value::known(F::from(tx.to.is_none() as u64)),
                         ),
+                        (TxFieldTag::Value, word_rlc(tx.value)),
                         (
                             TxFieldTag::CallDataLength,
-                            F::from(tx.call_data.0.len() as u64),
+                            Value::known(F::from(tx.call_data.0.len() as u64)),
                         ),
                         (
                             TxFieldTag::CallDataGasCost,
-                            F::from(
+                            Value::known(F::from(
                                 tx.call_data
                                     .0
                                     .iter()
                                     .fold(0, |acc, byte| acc + if *byte == 0 { 4 } else { 16 }),
-                            ),
+                            )),
+                        ),
+                        (
+                            TxFieldTag::AccessListAddressesLen,
+                            Value::known(F::from(access_list.0.len() as u64)),
+                        ),
+                        (
+                            TxFieldTag::AccessListStorageKeysLen,
+                            Value::known(F::from(
+                                access_list
+                                    .0
+                                    .iter()
+                                    .map(|item| item.storage_keys.len() as u64)
+                                    .sum::<u64>(),
+                            )),
                         ),
                         (TxFieldTag::TxSignHash, msg_hash_rlc_value),
+                        (
+                            TxFieldTag::BlockNumber,
+                            Value::known(F::from(tx.block_number)),
+                        ),
+                        (
+                            TxFieldTag::AccessListRwCounter,
+                            Value::known(F::from(
+                                self.access_list_rw_counters
+                                    .get(i)
+                                    .copied()
+                                    .unwrap_or_default() as u64,
+                            )),
+                        ),
                     ] {
-                        let assigned_cell =
-                            config.assign_row(&mut region, offset, i + 1, *tag, 0, *value)?;
+                        let assigned_cell = config.assign_row(
+                            &mut region,
+                            offset,
+                            self.tx_id_offset + i + 1,
+                            *tag,
+                            0,
+                            *value,
+                        )?;
+                        for (name, column, enabled) in [
+                            (
+                                "q_sign_hash",
+                                config.q_sign_hash,
+                                matches!(tag, TxFieldTag::TxSignHash),
+                            ),
+                            (
+                                "q_access_list_len",
+                                config.q_access_list_len,
+                                matches!(tag, TxFieldTag::AccessListAddressesLen),
+                            ),
+                            (
+                                "q_block_number",
+                                config.q_block_number,
+                                matches!(tag, TxFieldTag::BlockNumber),
+                            ),
+                        ] {
+                            region.assign_fixed(
+                                || name,
+                                column,
+                                offset,
+                                || Value::known(F::from(enabled as u64)),
+                            )?;
+                        }
+                        if let TxFieldTag::BlockNumber = tag {
+                            region.assign_advice(
+                                || "is_padding_tx",
+                                config.is_padding_tx,
+                                offset,
+                                || Value::known(F::from((i >= self.txs.len()) as u64)),
+                            )?;
+                        }
                         offset += 1;
 
                         // Ref. spec 0. Copy constraints using fixed offsets between the tx rows and
@@ -254,10 +682,10 @@ impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize>
                         config.assign_row(
                             &mut region,
                             offset,
-                            i + 1, // tx_id
+                            self.tx_id_offset + i + 1, // tx_id
                             TxFieldTag::CallData,
                             index,
-                            F::from(*byte as u64),
+                            Value::known(F::from(*byte as u64)),
                         )?;
                         offset += 1;
                         calldata_count += 1;
@@ -270,21 +698,188 @@ impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize>
                         0, // tx_id
                         TxFieldTag::CallData,
                         0,
-                        F::zero(),
+                        Value::known(F::zero()),
+                    )?;
+                    offset += 1;
+                }
+
+                // Assign the access list entries
+                let mut entries_count = 0;
+                for (i, tx) in self.txs.iter().enumerate() {
+                    let entries = access_list_entries(&tx.access_list.clone().unwrap_or_default());
+                    for (index, entry) in entries.iter().enumerate() {
+                        assert!(entries_count < MAX_ACCESS_LIST_ENTRIES);
+                        config.assign_access_list_row(
+                            &mut region,
+                            offset,
+                            self.tx_id_offset + i + 1, // tx_id
+                            index,
+                            Some(entry),
+                            self.access_list_rw_counters[i] + index,
+                            index + 1 == entries.len(),
+                            challenges,
+                        )?;
+                        offset += 1;
+                        entries_count += 1;
+                    }
+                }
+                for _ in entries_count..MAX_ACCESS_LIST_ENTRIES {
+                    config.assign_access_list_row(
+                        &mut region,
+                        offset,
+                        0, // tx_id
+                        0,
+                        None,
+                        0,
+                        false,
+                        challenges,
                     )?;
                     offset += 1;
                 }
                 Ok(())
             },
         )?;
-        Ok(())
+
+        config.rlp_encoding.load(layouter)?;
+        let rows = self
+            .txs
+            .iter()
+            .enumerate()
+            .map(|(i, tx)| rlp_rows(self.tx_id_offset + i + 1, tx, self.chain_id, challenges))
+            .flatten_ok()
+            .collect::<Result<Vec<_>, _>>()?;
+        let chain_id = challenges
+            .evm_word()
+            .map(|randomness| rlc(Word::from(self.chain_id).to_le_bytes(), randomness));
+        config
+            .rlp_encoding
+            .assign(layouter, &rows, Self::rlp_rows_capacity(), chain_id)
+    }
+
+    /// Number of rows of the RLP encoding chip, enough for the signing
+    /// payloads of `MAX_TXS` transactions with `MAX_CALLDATA` bytes of call
+    /// data and `MAX_ACCESS_LIST_ENTRIES` access list entries.
+    pub fn rlp_rows_capacity() -> usize {
+        MAX_TXS * MAX_ROWS_PER_TX
+            + MAX_CALLDATA
+            + MAX_ACCESS_LIST_ENTRIES * MAX_ROWS_PER_ACCESS_LIST_ENTRY
+    }
+
+    /// Number of rows of the RLP encoding chip used by the signing payloads
+    /// of the transactions.
+    pub fn get_num_rlp_rows_required(txs: &[Transaction], chain_id: u64) -> usize {
+        txs.iter().map(|tx| tx.sign_payload(chain_id).len()).sum()
+    }
+
+    /// Number of rows of the tx table used by the transactions, including
+    /// its empty first row.
+    pub fn get_num_tx_table_rows_required(txs: &[Transaction]) -> usize {
+        1 + txs.len() * TX_LEN
+            + txs.iter().map(|tx| tx.call_data.0.len()).sum::<usize>()
+            + Self::get_num_access_list_entries(txs)
+    }
+
+    /// Number of entries of the access lists of the transactions.
+    pub fn get_num_access_list_entries(txs: &[Transaction]) -> usize {
+        txs.iter()
+            .map(|tx| access_list_entries(&tx.access_list.clone().unwrap_or_default()).len())
+            .sum()
+    }
+
+    /// Writes of the access lists of the transactions in BeginTx, which the
+    /// entries of the tx table are looked up in.
+    fn access_list_rws(&self) -> Vec<Rw> {
+        self.txs
+            .iter()
+            .zip(self.access_list_rw_counters.iter())
+            .enumerate()
+            .flat_map(|(i, (tx, rw_counter))| {
+                access_list_entries(&tx.access_list.clone().unwrap_or_default())
+                    .into_iter()
+                    .enumerate()
+                    .map(move |(index, entry)| match entry.storage_key {
+                        Some(storage_key) => Rw::TxAccessListAccountStorage {
+                            rw_counter: rw_counter + index,
+                            is_write: true,
+                            tx_id: self.tx_id_offset + i + 1,
+                            account_address: entry.address,
+                            storage_key,
+                            is_warm: true,
+                            is_warm_prev: false,
+                        },
+                        None => Rw::TxAccessListAccount {
+                            rw_counter: rw_counter + index,
+                            is_write: true,
+                            tx_id: self.tx_id_offset + i + 1,
+                            account_address: entry.address,
+                            is_warm: true,
+                            is_warm_prev: false,
+                        },
+                    })
+            })
+            .collect()
     }
 }
 
-impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> Circuit<F>
+impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> SubCircuit<F>
     for TxCircuit<F, MAX_TXS, MAX_CALLDATA>
 {
     type Config = TxCircuitConfig<F>;
+
+    /// The aux generator of the signature verification is derived from a
+    /// fixed seed, so that the circuit only depends on the block.  The
+    /// transactions of a chunk of a block are the ones of its tx table, see
+    /// [`witness::Chunk::tx_table_txs`].
+    fn new_from_block(block: &witness::Block<F>) -> Self {
+        let mut rng = XorShiftRng::seed_from_u64(2);
+        let aux_generator =
+            <Secp256k1Affine as CurveAffine>::CurveExt::random(&mut rng).to_affine();
+        let txs = block.tx_table_txs();
+        Self {
+            access_list_rw_counters: txs.iter().map(|tx| tx.access_list_rw_counter).collect(),
+            tx_id_offset: txs.first().map_or(0, |tx| tx.id - 1),
+            ..Self::new(
+                aux_generator,
+                block.context.chain_id().as_u64(),
+                block.eth_txs.clone(),
+            )
+        }
+    }
+
+    /// SignVerifyChip -> ECDSAChip -> MainGate instance column
+    fn instance(&self) -> Vec<Vec<F>> {
+        vec![vec![]]
+    }
+
+    /// Make the assignments to the TxCircuit, including the tx table
+    fn synthesize_sub(
+        &self,
+        config: &Self::Config,
+        challenges: &Challenges<Value<F>>,
+        layouter: &mut impl Layouter<F>,
+    ) -> Result<(), Error> {
+        config.sign_verify.load_range(layouter)?;
+        self.assign(config, layouter, challenges)
+    }
+
+    /// The tx table and the RLP encoding are padded to `MAX_TXS` transactions
+    /// with `MAX_CALLDATA` bytes of call data and `MAX_ACCESS_LIST_ENTRIES`
+    /// access list entries, the RLP byte table takes 256 rows, and the
+    /// signature verification of `MAX_TXS` transactions, with its range
+    /// tables which are loaded even without transactions, takes the rows of
+    /// [`SignVerifyChip::min_num_rows`].
+    fn min_num_rows_block(_block: &witness::Block<F>) -> usize {
+        (1 + MAX_TXS * TX_LEN + MAX_CALLDATA + MAX_ACCESS_LIST_ENTRIES)
+            .max(Self::rlp_rows_capacity() + 1)
+            .max(256)
+            .max(SignVerifyChip::<F, MAX_TXS>::min_num_rows())
+    }
+}
+
+impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> Circuit<F>
+    for TxCircuit<F, MAX_TXS, MAX_CALLDATA>
+{
+    type Config = (TxCircuitConfig<F>, Challenges);
     type FloorPlanner = SimpleFloorPlanner;
 
     fn without_witnesses(&self) -> Self {
@@ -292,27 +887,56 @@ impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> Circuit<F>
     }
 
     fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
-        let tx_table = TxTable {
-            tx_id: meta.advice_column(),
-            tag: meta.advice_column(),
-            index: meta.advice_column(),
-            value: meta.advice_column(),
+        let tx_table = TxTable::construct(meta);
+        let keccak_table = KeccakTable::construct(meta);
+        let block_table = BlockTable::construct(meta);
+        let rw_table = RwTable::construct(meta);
+        let challenges = Challenges::construct(meta);
+
+        let config = {
+            let challenges = challenges.exprs(meta);
+            TxCircuitConfig::new(
+                meta,
+                TxCircuitConfigArgs {
+                    tx_table,
+                    keccak_table,
+                    block_table,
+                    rw_table,
+                    challenges,
+                },
+            )
         };
 
-        let power_of_randomness = power_of_randomness_from_instance(meta);
-        let keccak_table = KeccakTable::construct(meta);
-        TxCircuitConfig::new(meta, power_of_randomness, tx_table, keccak_table)
+        (config, challenges)
     }
 
     fn synthesize(
         &self,
-        config: Self::Config,
+        (config, challenges): Self::Config,
         mut layouter: impl Layouter<F>,
     ) -> Result<(), Error> {
-        let challenges = Challenges::mock(Value::known(self.randomness));
+        let challenges = challenges.values(&mut layouter);
 
-        config.sign_verify.load_range(&mut layouter)?;
-        self.assign(&config, &mut layouter)?;
+        self.synthesize_sub(&config, &challenges, &mut layouter)?;
+        // The blocks of the transactions, which their block numbers are
+        // looked up in.
+        let block_contexts = BlockContexts {
+            ctxs: self
+                .txs
+                .iter()
+                .map(|tx| {
+                    let ctx = BlockContext {
+                        chain_id: self.chain_id.into(),
+                        number: tx.block_number.into(),
+                        ..Default::default()
+                    };
+                    (tx.block_number, ctx)
+                })
+                .collect(),
+        };
+        config
+            .block_table
+            .load(&mut layouter, &block_contexts, &challenges)?;
         config.keccak_table.dev_load(
             &mut layouter,
             &keccak_inputs_tx_circuit(&self.txs[..], self.chain_id).map_err(|e| {
@@ -320,22 +944,24 @@ impl<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize> Circuit<F>
                 Error::Synthesis
             })?,
             &challenges,
-        )
+        )?;
+        let rws = self.access_list_rws();
+        config
+            .rw_table
+            .load(&mut layouter, &rws, rws.len() + 1, &challenges)
     }
 }
 
 #[cfg(test)]
 mod tx_circuit_tests {
     use super::*;
-    use eth_types::address;
+    use eth_types::{address, word, AccessList, AccessListItem, H256};
     use halo2_proofs::{
-        arithmetic::CurveAffine,
         dev::{MockProver, VerifyFailure},
         halo2curves::{bn256::Fr, group::Group},
     };
     use mock::AddrOrWallet;
     use pretty_assertions::assert_eq;
-    use rand::SeedableRng;
     use rand_chacha::ChaCha20Rng;
 
     fn run<F: Field, const MAX_TXS: usize, const MAX_CALLDATA: usize>(
@@ -347,22 +973,9 @@ mod tx_circuit_tests {
         let aux_generator =
             <Secp256k1Affine as CurveAffine>::CurveExt::random(&mut rng).to_affine();
 
-        let randomness = F::random(&mut rng);
-        let mut instance: Vec<Vec<F>> = (1..POW_RAND_SIZE + 1)
-            .map(|exp| vec![randomness.pow(&[exp as u64, 0, 0, 0]); txs.len() * VERIF_HEIGHT])
-            .collect();
         // SignVerifyChip -> ECDSAChip -> MainGate instance column
-        instance.push(vec![]);
-        let circuit = TxCircuit::<F, MAX_TXS, MAX_CALLDATA> {
-            sign_verify: SignVerifyChip {
-                aux_generator,
-                window_size: 2,
-                _marker: PhantomData,
-            },
-            randomness,
-            txs,
-            chain_id,
-        };
+        let instance = vec![vec![]];
+        let circuit = TxCircuit::<F, MAX_TXS, MAX_CALLDATA>::new(aux_generator, chain_id, txs);
 
         let prover = match MockProver::run(k, &circuit, instance) {
             Ok(prover) => prover,
@@ -371,6 +984,15 @@ mod tx_circuit_tests {
         prover.verify()
     }
 
+    #[test]
+    fn min_num_rows_include_signature_verification() {
+        let block = witness::Block::<Fr>::default();
+        let min_num_rows = TxCircuit::<Fr, 2, 32>::min_num_rows_block(&block);
+        assert!(min_num_rows >= 2 * 104773);
+        // The degree used by the tests with up to 2 txs
+        assert!(min_num_rows < 1 << 19);
+    }
+
     // High memory usage test.  Run in serial with:
     // `cargo test [...] serial_ -- --ignored --test-threads 1`
     #[ignore]
@@ -413,6 +1035,39 @@ mod tx_circuit_tests {
         );
     }
 
+    // High memory usage test.  Run in serial with:
+    // `cargo test [...] serial_ -- --ignored --test-threads 1`
+    #[ignore]
+    #[test]
+    fn serial_test_tx_circuit_access_list() {
+        const MAX_TXS: usize = 1;
+        const MAX_CALLDATA: usize = 32;
+
+        let mut rng = ChaCha20Rng::seed_from_u64(2);
+        let tx = mock::MockTransaction::default()
+            .from(AddrOrWallet::random(&mut rng))
+            .to(mock::MOCK_ACCOUNTS[0])
+            .transaction_type(1)
+            .gas_price(word!("0x4d2"))
+            .access_list(AccessList(vec![
+                AccessListItem {
+                    address: mock::MOCK_ACCOUNTS[1],
+                    storage_keys: vec![H256::from_low_u64_be(1), H256::from_low_u64_be(2)],
+                },
+                AccessListItem {
+                    address: mock::MOCK_ACCOUNTS[2],
+                    storage_keys: vec![],
+                },
+            ]))
+            .build();
+
+        let k = 19;
+        assert_eq!(
+            run::<Fr, MAX_TXS, MAX_CALLDATA>(k, vec![tx.into()], mock::MOCK_CHAIN_ID.as_u64()),
+            Ok(())
+        );
+    }
+
     // High memory usage test.  Run in serial with:
     // `cargo test [...] serial_ -- --ignored --test-threads 1`
     #[ignore]
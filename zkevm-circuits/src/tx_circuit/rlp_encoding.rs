@@ -0,0 +1,1642 @@
+//! RLP encoding chip of the TxCircuit.  It decodes the RLP encoded payload
+//! signed by each transaction byte by byte, binds the decoded fields to the
+//! values in the tx table and the payload to its `TxSignHash` via a lookup to
+//! the keccak table.
+//!
+//! Each row holds one byte of a payload, tagged with the item (field) of the
+//! transaction it belongs to.  An item is made of its RLP header bytes
+//! followed by its content bytes, except for the lists whose content is made
+//! of the following items.  The sequence of items of a payload is constrained
+//! by a fixed table of tag transitions per transaction type:
+//! - Legacy: `rlp([nonce, gasPrice, gas, to, value, data])`, with `[chain_id,
+//!   0, 0]` appended when the transaction is EIP-155 replay protected.
+//! - EIP-2930: `0x01 || rlp([chain_id, nonce, gasPrice, gas, to, value, data,
+//!   access_list])`
+//! - EIP-1559: `0x02 || rlp([chain_id, nonce, gasTipCap, gasFeeCap, gas, to,
+//!   value, data, access_list])`
+//!
+//! The access list is decoded as the list of its items `[address,
+//! [storage_key, ...]]`, each address and storage key being the entry of the
+//! tx table at its index among the entries of the access list.
+//!
+//! The encoding is checked to be canonical: the scalar fields have no leading
+//! zero bytes, a single byte below 0x80 is its own encoding, and the long form
+//! of a header is only used for a length above 55, without leading zero
+//! bytes.
+
+use crate::{
+    evm_circuit::util::{and, constraint_builder::BaseConstraintBuilder, not, select},
+    impl_expr,
+    table::{
+        BlockContextFieldTag, BlockTable, DynamicTableColumns, KeccakTable, TxFieldTag, TxTable,
+    },
+    util::{random_linear_combine_word as rlc, Challenges, Expr},
+};
+use eth_types::{geth_types::Transaction, Address, Field, ToLittleEndian, ToScalar, Word};
+use ethers_core::utils::keccak256;
+use halo2_proofs::{
+    circuit::{Layouter, Region, Value},
+    plonk::{
+        Advice, Column, ConstraintSystem, Error, Expression, Fixed, SecondPhase, VirtualCells,
+    },
+    poly::Rotation,
+};
+use log::error;
+use std::marker::PhantomData;
+
+/// Tag of an item of the signing payload of a transaction.  Items which are
+/// fields of the tx table share the value of their `TxFieldTag`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RlpTag {
+    /// Unused tag, also marking the start and the end of a payload in the tag
+    /// transitions.
+    Null = 0,
+    /// Nonce
+    Nonce = TxFieldTag::Nonce as isize,
+    /// Gas
+    Gas = TxFieldTag::Gas as isize,
+    /// GasPrice
+    GasPrice = TxFieldTag::GasPrice as isize,
+    /// To
+    CalleeAddress = TxFieldTag::CalleeAddress as isize,
+    /// Value
+    Value = TxFieldTag::Value as isize,
+    /// Data
+    CallData = TxFieldTag::CallData as isize,
+    /// GasFeeCap
+    GasFeeCap = TxFieldTag::GasFeeCap as isize,
+    /// GasTipCap
+    GasTipCap = TxFieldTag::GasTipCap as isize,
+    /// EIP-2718 type byte of a typed transaction
+    TxType,
+    /// Header of the list of fields
+    ListHeader,
+    /// Chain ID
+    ChainId,
+    /// Empty `r` placeholder of an EIP-155 payload
+    SigR,
+    /// Empty `s` placeholder of an EIP-155 payload
+    SigS,
+    /// Header of the access list
+    AccessList,
+    /// Header of an item of the access list
+    AccessListItem,
+    /// Address of an item of the access list
+    AccessListAddress,
+    /// Header of the storage keys of an item of the access list
+    StorageKeys,
+    /// Storage key of an item of the access list
+    StorageKey,
+}
+impl_expr!(RlpTag);
+
+impl RlpTag {
+    const ALL: [Self; 19] = [
+        Self::Null,
+        Self::Nonce,
+        Self::Gas,
+        Self::GasPrice,
+        Self::CalleeAddress,
+        Self::Value,
+        Self::CallData,
+        Self::GasFeeCap,
+        Self::GasTipCap,
+        Self::TxType,
+        Self::ListHeader,
+        Self::ChainId,
+        Self::SigR,
+        Self::SigS,
+        Self::AccessList,
+        Self::AccessListItem,
+        Self::AccessListAddress,
+        Self::StorageKeys,
+        Self::StorageKey,
+    ];
+
+    /// Top level items of the signing payload of each transaction type, in
+    /// order.
+    fn sequence(tx_type: u64, is_eip155: bool) -> Vec<Self> {
+        use RlpTag::*;
+        match tx_type {
+            0 => {
+                let mut tags = vec![
+                    ListHeader,
+                    Nonce,
+                    GasPrice,
+                    Gas,
+                    CalleeAddress,
+                    Value,
+                    CallData,
+                ];
+                if is_eip155 {
+                    tags.extend([ChainId, SigR, SigS]);
+                }
+                tags
+            }
+            1 => vec![
+                TxType,
+                ListHeader,
+                ChainId,
+                Nonce,
+                GasPrice,
+                Gas,
+                CalleeAddress,
+                Value,
+                CallData,
+                AccessList,
+            ],
+            2 => vec![
+                TxType,
+                ListHeader,
+                ChainId,
+                Nonce,
+                GasTipCap,
+                GasFeeCap,
+                Gas,
+                CalleeAddress,
+                Value,
+                CallData,
+                AccessList,
+            ],
+            _ => vec![],
+        }
+    }
+
+    /// Pairs of consecutive items of the signing payload of each transaction
+    /// type, including the start and the end of the payload as `Null`.  The
+    /// access list, which ends the payload of the typed transactions, is
+    /// followed by any number of items, each one made of an address and any
+    /// number of storage keys.
+    fn transitions(tx_type: u64, is_eip155: bool) -> Vec<(Self, Self)> {
+        use RlpTag::*;
+        let tags = Self::sequence(tx_type, is_eip155);
+        let mut transitions = std::iter::once(Null)
+            .chain(tags.iter().copied())
+            .zip(tags.iter().copied().chain(std::iter::once(Null)))
+            .collect::<Vec<_>>();
+        if tags.contains(&AccessList) {
+            transitions.extend([
+                (AccessList, AccessListItem),
+                (AccessListItem, AccessListAddress),
+                (AccessListAddress, StorageKeys),
+                (StorageKeys, StorageKey),
+                (StorageKeys, AccessListItem),
+                (StorageKeys, Null),
+                (StorageKey, StorageKey),
+                (StorageKey, AccessListItem),
+                (StorageKey, Null),
+            ]);
+        }
+        transitions
+    }
+
+    /// Whether the item is a list, whose content is made of the following
+    /// items.
+    fn is_list(&self) -> bool {
+        matches!(
+            self,
+            Self::ListHeader | Self::AccessList | Self::AccessListItem | Self::StorageKeys
+        )
+    }
+
+    /// Whether the item is a list spanning the rest of the payload.
+    fn is_outer_list(&self) -> bool {
+        matches!(self, Self::ListHeader | Self::AccessList)
+    }
+
+    /// Whether the value of the item is accumulated as the RLC of its
+    /// little-endian bytes, instead of as an integer.
+    fn is_word(&self) -> bool {
+        matches!(
+            self,
+            Self::GasPrice
+                | Self::GasFeeCap
+                | Self::GasTipCap
+                | Self::Value
+                | Self::ChainId
+                | Self::StorageKey
+        )
+    }
+
+    /// Whether the item is a field of the tx table.
+    fn is_tx_field(&self) -> bool {
+        (Self::Nonce as u64..=Self::GasTipCap as u64).contains(&(*self as u64))
+    }
+
+    /// Whether the item is an integer, encoded without leading zero bytes.
+    fn is_scalar(&self) -> bool {
+        matches!(
+            self,
+            Self::Nonce
+                | Self::Gas
+                | Self::GasPrice
+                | Self::Value
+                | Self::GasFeeCap
+                | Self::GasTipCap
+                | Self::ChainId
+                | Self::SigR
+                | Self::SigS
+        )
+    }
+
+    /// Properties of the tag in the tag table.
+    fn properties(&self) -> [bool; N_TAG_PROPERTIES] {
+        [
+            self.is_list(),
+            self.is_outer_list(),
+            self.is_word(),
+            *self == Self::CallData,
+            self.is_tx_field(),
+            *self == Self::ChainId,
+            matches!(self, Self::SigR | Self::SigS),
+            *self == Self::CalleeAddress,
+            self.is_scalar(),
+            *self == Self::AccessListItem,
+            *self == Self::AccessListAddress,
+            *self == Self::StorageKeys,
+            *self == Self::StorageKey,
+        ]
+    }
+}
+
+const N_TAG_PROPERTIES: usize = 13;
+
+/// Maximum number of rows of the signing payload of a transaction, besides
+/// the content of its call data and of its access list: the ones of an
+/// EIP-1559 payload with the longest headers and scalar fields.
+pub(crate) const MAX_ROWS_PER_TX: usize = 175;
+
+/// Maximum number of rows of an entry of an access list: the 33 bytes of an
+/// encoded storage key, more than the ones of an address with the headers of
+/// its item and of its storage keys.
+pub(crate) const MAX_ROWS_PER_ACCESS_LIST_ENTRY: usize = 33;
+
+/// Class of a byte when it's the first byte of an RLP item.
+#[derive(Clone, Copy, Debug)]
+enum ByteClass {
+    /// [0x00, 0x7f]: the byte is its own encoding
+    SingleByte = 0,
+    /// [0x80, 0xb7]: header of a string of up to 55 bytes
+    ShortString,
+    /// [0xb8, 0xbf]: header of a longer string
+    LongString,
+    /// [0xc0, 0xf7]: header of a list of up to 55 bytes
+    ShortList,
+    /// [0xf8, 0xff]: header of a longer list
+    LongList,
+}
+impl_expr!(ByteClass);
+
+impl From<u8> for ByteClass {
+    fn from(byte: u8) -> Self {
+        match byte {
+            0x00..=0x7f => Self::SingleByte,
+            0x80..=0xb7 => Self::ShortString,
+            0xb8..=0xbf => Self::LongString,
+            0xc0..=0xf7 => Self::ShortList,
+            0xf8..=0xff => Self::LongList,
+        }
+    }
+}
+
+/// Row of the RLP encoding of a signing payload.
+#[derive(Clone, Debug)]
+pub(crate) struct RlpRow<F> {
+    tx_id: usize,
+    tx_type: u64,
+    tag: RlpTag,
+    index: usize,
+    byte: u8,
+    min_byte: u8,
+    rlc: Value<F>,
+    is_first: bool,
+    is_last: bool,
+    is_item_start: bool,
+    is_item_end: bool,
+    is_header: bool,
+    is_long: bool,
+    tag_length: usize,
+    tag_rindex: usize,
+    value_acc: Value<F>,
+    payload_len: usize,
+    hash_rlc: Value<F>,
+    item_end: usize,
+    address: Value<F>,
+    n_addresses: usize,
+    n_keys: usize,
+}
+
+/// Decoded RLP header of an item: the header bytes and the length of its
+/// content.
+fn decode_header(payload: &[u8]) -> Option<(&[u8], usize)> {
+    let prefix = *payload.first()?;
+    Some(match ByteClass::from(prefix) {
+        ByteClass::SingleByte => (&payload[..0], 1),
+        ByteClass::ShortString => (&payload[..1], (prefix - 0x80) as usize),
+        ByteClass::ShortList => (&payload[..1], (prefix - 0xc0) as usize),
+        class => {
+            let base = if matches!(class, ByteClass::LongString) {
+                0xb7
+            } else {
+                0xf7
+            };
+            let len_of_len = (prefix - base) as usize;
+            let len = payload
+                .get(1..1 + len_of_len)?
+                .iter()
+                .fold(0usize, |acc, byte| acc * 256 + *byte as usize);
+            (&payload[..1 + len_of_len], len)
+        }
+    })
+}
+
+/// Split consecutive RLP items into their header and content.
+fn split_items(mut bytes: &[u8]) -> Option<Vec<(&[u8], &[u8])>> {
+    let mut items = Vec::new();
+    while !bytes.is_empty() {
+        let (header, len) = decode_header(bytes)?;
+        let item = bytes.get(..header.len() + len)?;
+        items.push((header, &item[header.len()..]));
+        bytes = &bytes[item.len()..];
+    }
+    Some(items)
+}
+
+/// Split the content of an access list into its tagged items: the header of
+/// each item of the list, followed by its address, the header of its storage
+/// keys and its storage keys.
+fn access_list_items(content: &[u8]) -> Option<Vec<(RlpTag, &[u8], &[u8])>> {
+    let mut items = Vec::new();
+    for (header, content) in split_items(content)? {
+        items.push((RlpTag::AccessListItem, header, content));
+        let fields = split_items(content)?;
+        match fields[..] {
+            [(address_header, address), (keys_header, keys)] => {
+                items.push((RlpTag::AccessListAddress, address_header, address));
+                items.push((RlpTag::StorageKeys, keys_header, keys));
+                items.extend(
+                    split_items(keys)?
+                        .into_iter()
+                        .map(|(header, key)| (RlpTag::StorageKey, header, key)),
+                );
+            }
+            _ => return None,
+        }
+    }
+    Some(items)
+}
+
+/// Generate the rows of the RLP encoding of the signing payload of a
+/// transaction.  The payload is accumulated with the `keccak_input` challenge
+/// and the word fields with the `evm_word` one.
+pub(crate) fn rlp_rows<F: Field>(
+    tx_id: usize,
+    tx: &Transaction,
+    chain_id: u64,
+    challenges: &Challenges<Value<F>>,
+) -> Result<Vec<RlpRow<F>>, Error> {
+    let payload = tx.sign_payload(chain_id);
+    let invalid_payload = || {
+        error!("invalid signing payload for tx {}: {:?}", tx_id, payload);
+        Error::Synthesis
+    };
+
+    // Split the payload into its top level items: (header, content)
+    let mut items = Vec::new();
+    let mut rest = &payload[..];
+    if tx.transaction_type != 0 {
+        items.push((&rest[..0], &rest[..1]));
+        rest = &rest[1..];
+    }
+    let (header, _) = decode_header(rest).ok_or_else(invalid_payload)?;
+    items.push((header, &rest[header.len()..]));
+    items.extend(split_items(&rest[header.len()..]).ok_or_else(invalid_payload)?);
+    let tags = RlpTag::sequence(tx.transaction_type, tx.is_eip155());
+    if tags.len() != items.len() {
+        return Err(invalid_payload());
+    }
+    let mut tagged_items = Vec::with_capacity(items.len());
+    for (tag, (header, content)) in tags.into_iter().zip(items) {
+        tagged_items.push((tag, header, content));
+        if tag == RlpTag::AccessList {
+            tagged_items.extend(access_list_items(content).ok_or_else(invalid_payload)?);
+        }
+    }
+
+    let hash_rlc = challenges.evm_word().map(|randomness| {
+        rlc(
+            Word::from_big_endian(&keccak256(&payload)).to_le_bytes(),
+            randomness,
+        )
+    });
+    let mut rows = Vec::with_capacity(payload.len());
+    let mut payload_rlc = Value::known(F::zero());
+    let mut item_end = 0;
+    let mut address = Value::known(F::zero());
+    let (mut n_addresses, mut n_keys) = (0, 0);
+    for (tag, header, content) in tagged_items {
+        let tag_length = content.len();
+        match tag {
+            RlpTag::AccessListItem => item_end = rows.len() + header.len() + tag_length,
+            RlpTag::AccessListAddress => {
+                let bytes: [u8; 20] = content.try_into().map_err(|_| invalid_payload())?;
+                address = Value::known(Address::from(bytes).to_scalar().unwrap());
+                n_addresses += 1;
+            }
+            RlpTag::StorageKey => n_keys += 1,
+            _ => (),
+        }
+        // The content of a list is made of the following items.
+        let content = if tag.is_list() {
+            &content[..0]
+        } else {
+            content
+        };
+        let mult = if tag.is_word() {
+            challenges.evm_word()
+        } else {
+            Value::known(F::from(256))
+        };
+        let is_long = matches!(
+            ByteClass::from(header.first().copied().unwrap_or_default()),
+            ByteClass::LongString | ByteClass::LongList
+        );
+        let n_rows = header.len() + content.len();
+        let mut value_acc = Value::known(F::zero());
+        for (i, byte) in header.iter().chain(content.iter()).enumerate() {
+            let is_header = i < header.len();
+            let tag_rindex = if is_header {
+                header.len() - i
+            } else {
+                n_rows - i
+            };
+            // Lower bound of the byte in a canonical encoding
+            let min_byte = if i == 0 && !is_header {
+                tag.is_scalar() as u8
+            } else if i > 0 && i == header.len() {
+                if tag_length == 1 {
+                    0x80
+                } else {
+                    tag.is_scalar() as u8
+                }
+            } else if i == 1 && is_long {
+                if header.len() == 2 {
+                    56
+                } else {
+                    1
+                }
+            } else {
+                0
+            };
+            let byte_value = Value::known(F::from(*byte as u64));
+            value_acc = if i == 0 && is_header {
+                Value::known(F::zero())
+            } else if i == header.len() {
+                byte_value
+            } else if is_header {
+                value_acc * Value::known(F::from(256)) + byte_value
+            } else {
+                value_acc * mult + byte_value
+            };
+            payload_rlc = payload_rlc * challenges.keccak_input() + byte_value;
+            rows.push(RlpRow {
+                tx_id,
+                tx_type: tx.transaction_type,
+                tag,
+                index: rows.len() + 1,
+                byte: *byte,
+                min_byte,
+                rlc: payload_rlc,
+                is_first: rows.is_empty(),
+                is_last: rows.len() + 1 == payload.len(),
+                is_item_start: i == 0,
+                is_item_end: i + 1 == n_rows,
+                is_header,
+                is_long,
+                tag_length,
+                tag_rindex,
+                value_acc,
+                payload_len: payload.len(),
+                hash_rlc,
+                item_end,
+                address,
+                n_addresses,
+                n_keys,
+            });
+        }
+    }
+    Ok(rows)
+}
+
+/// Config of the RLP encoding chip
+#[derive(Clone, Debug)]
+pub(crate) struct RlpEncodingConfig<F> {
+    q_enable: Column<Fixed>,
+    q_first: Column<Fixed>,
+    tx_id: Column<Advice>,
+    tx_type: Column<Advice>,
+    tag: Column<Advice>,
+    index: Column<Advice>,
+    byte: Column<Advice>,
+    byte_class: Column<Advice>,
+    min_byte: Column<Advice>,
+    rlc: Column<Advice>,
+    is_first: Column<Advice>,
+    is_last: Column<Advice>,
+    is_item_start: Column<Advice>,
+    is_item_end: Column<Advice>,
+    is_header: Column<Advice>,
+    is_long: Column<Advice>,
+    tag_length: Column<Advice>,
+    tag_rindex: Column<Advice>,
+    value_acc: Column<Advice>,
+    payload_len: Column<Advice>,
+    hash_rlc: Column<Advice>,
+    item_end: Column<Advice>,
+    address: Column<Advice>,
+    n_addresses: Column<Advice>,
+    n_keys: Column<Advice>,
+    chain_id: Column<Advice>,
+    is_padding: Column<Advice>,
+    // [is_list, is_outer_list, is_word, is_calldata, is_tx_field, is_chain_id, is_zero, is_to,
+    // is_scalar, is_access_list_item, is_address, is_storage_keys, is_storage_key]
+    tag_properties: [Column<Advice>; N_TAG_PROPERTIES],
+    // [byte, class]
+    byte_table: [Column<Fixed>; 2],
+    // [q_enable, tag, tag_properties...]
+    tag_table: [Column<Fixed>; N_TAG_PROPERTIES + 2],
+    // [q_enable, tx_type, tag, tag_next]
+    transition_table: [Column<Fixed>; 4],
+    _marker: PhantomData<F>,
+}
+
+impl<F: Field> RlpEncodingConfig<F> {
+    /// Configure the chip, with the column of the addresses of the access
+    /// list entries next to the tx table.
+    pub(crate) fn configure(
+        meta: &mut ConstraintSystem<F>,
+        challenges: &Challenges<Expression<F>>,
+        tx_table: &TxTable,
+        access_list_address: Column<Advice>,
+        keccak_table: &KeccakTable,
+        block_table: &BlockTable,
+    ) -> Self {
+        let q_enable = meta.fixed_column();
+        let q_first = meta.fixed_column();
+        let [tx_id, tx_type, tag, index, byte, byte_class, min_byte, is_first, is_last, is_item_start, is_item_end, is_header, is_long, tag_length, tag_rindex, payload_len, item_end, address, n_addresses, n_keys, is_padding] =
+            [(); 21].map(|_| meta.advice_column());
+        // Columns of random linear combinations
+        let [rlc, value_acc, hash_rlc, chain_id] =
+            [(); 4].map(|_| meta.advice_column_in(SecondPhase));
+        let tag_properties = [(); N_TAG_PROPERTIES].map(|_| meta.advice_column());
+        let byte_table = [(); 2].map(|_| meta.fixed_column());
+        let tag_table = [(); N_TAG_PROPERTIES + 2].map(|_| meta.fixed_column());
+        let transition_table = [(); 4].map(|_| meta.fixed_column());
+
+        meta.create_gate("RLP encoding row", |meta| {
+            let mut cb = BaseConstraintBuilder::default();
+
+            let q_next = meta.query_fixed(q_enable, Rotation::next());
+            let is_padding = (
+                meta.query_advice(is_padding, Rotation::cur()),
+                meta.query_advice(is_padding, Rotation::next()),
+            );
+            let [is_item_start_prev, is_header_prev] =
+                [is_item_start, is_header].map(|column| meta.query_advice(column, Rotation::prev()));
+            let [tx_id, tx_type, tag, index, byte, byte_class, min_byte, rlc, is_first, is_last, is_item_start, is_item_end, is_header, is_long, tag_length, tag_rindex, value_acc, payload_len, hash_rlc, item_end, address, n_addresses, n_keys, chain_id] =
+                [
+                    tx_id,
+                    tx_type,
+                    tag,
+                    index,
+                    byte,
+                    byte_class,
+                    min_byte,
+                    rlc,
+                    is_first,
+                    is_last,
+                    is_item_start,
+                    is_item_end,
+                    is_header,
+                    is_long,
+                    tag_length,
+                    tag_rindex,
+                    value_acc,
+                    payload_len,
+                    hash_rlc,
+                    item_end,
+                    address,
+                    n_addresses,
+                    n_keys,
+                    chain_id,
+                ]
+                .map(|column| {
+                    (
+                        meta.query_advice(column, Rotation::cur()),
+                        meta.query_advice(column, Rotation::next()),
+                    )
+                });
+            let [is_list, is_outer_list, is_word, _, _, is_chain_id, is_zero, is_to, is_scalar, is_access_list_item, is_address, is_storage_keys, is_storage_key] =
+                tag_properties.map(|column| {
+                    (
+                        meta.query_advice(column, Rotation::cur()),
+                        meta.query_advice(column, Rotation::next()),
+                    )
+                });
+
+            for (name, value) in [
+                ("is_first is boolean", &is_first.0),
+                ("is_last is boolean", &is_last.0),
+                ("is_item_start is boolean", &is_item_start.0),
+                ("is_item_end is boolean", &is_item_end.0),
+                ("is_header is boolean", &is_header.0),
+                ("is_long is boolean", &is_long.0),
+            ] {
+                cb.require_boolean(name, value.clone());
+            }
+            cb.require_equal(
+                "the chain id is the same in all the payloads",
+                q_next.clone() * chain_id.1,
+                q_next.clone() * chain_id.0.clone(),
+            );
+            // The payloads are followed by padding rows up to the fixed number
+            // of enabled rows, on which the other constraints are disabled.
+            cb.require_boolean("is_padding is boolean", is_padding.0.clone());
+            cb.require_zero(
+                "a padding row is followed by a padding row",
+                q_next.clone() * is_padding.0.clone() * not::expr(is_padding.1.clone()),
+            );
+            let is_payload = not::expr(is_padding.0);
+            let q_first = meta.query_fixed(q_first, Rotation::cur());
+            cb.condition(and::expr([is_payload.clone(), q_first]), |cb| {
+                cb.require_equal("the first row starts a payload", is_first.0.clone(), 1.expr());
+            });
+
+            // First byte of a payload
+            cb.condition(and::expr([is_payload.clone(), is_first.0.clone()]), |cb| {
+                cb.require_equal("is_item_start", is_item_start.0.clone(), 1.expr());
+                cb.require_equal("index == 1", index.0.clone(), 1.expr());
+                cb.require_equal("rlc == byte", rlc.0.clone(), byte.0.clone());
+                cb.require_zero(
+                    "typed transactions start with their type",
+                    tx_type.0.clone() * (byte.0.clone() - tx_type.0.clone()),
+                );
+                cb.require_zero("n_addresses == 0", n_addresses.0.clone());
+                cb.require_zero("n_keys == 0", n_keys.0.clone());
+            });
+
+            // Last byte of a payload
+            cb.condition(and::expr([is_payload.clone(), is_last.0.clone()]), |cb| {
+                cb.require_equal("is_item_end", is_item_end.0.clone(), 1.expr());
+                cb.require_equal(
+                    "index == payload_len",
+                    index.0.clone(),
+                    payload_len.0.clone(),
+                );
+                cb.require_equal(
+                    "the next payload or the padding starts on the next row",
+                    q_next.clone() * (is_first.1.clone() + is_padding.1.clone()),
+                    q_next.clone(),
+                );
+            });
+
+            // Continuation of a payload
+            let is_continued = and::expr([is_payload.clone(), not::expr(is_last.0.clone())]);
+            cb.condition(is_continued.clone(), |cb| {
+                cb.require_equal("the payload continues on the next row", q_next, 1.expr());
+                cb.require_zero("the next row is not padding", is_padding.1.clone());
+                for (name, (cur, next)) in [
+                    ("tx_id", &tx_id),
+                    ("tx_type", &tx_type),
+                    ("payload_len", &payload_len),
+                    ("hash_rlc", &hash_rlc),
+                ] {
+                    cb.require_equal(name, next.clone(), cur.clone());
+                }
+                cb.require_equal(
+                    "index_next == index + 1",
+                    index.1.clone(),
+                    index.0.clone() + 1.expr(),
+                );
+                cb.require_equal(
+                    "rlc_next == rlc * r + byte_next",
+                    rlc.1.clone(),
+                    rlc.0.clone() * challenges.keccak_input() + byte.1.clone(),
+                );
+                cb.require_zero("is_first_next == 0", is_first.1.clone());
+                cb.require_equal(
+                    "an item starts after the end of the previous one",
+                    is_item_start.1.clone(),
+                    is_item_end.0.clone(),
+                );
+                // Numbers of addresses and storage keys of the access list
+                // decoded so far, including the item of the row.
+                cb.require_equal(
+                    "n_addresses_next == n_addresses + is_address_start_next",
+                    n_addresses.1.clone(),
+                    n_addresses.0.clone() + is_item_start.1.clone() * is_address.1.clone(),
+                );
+                cb.require_equal(
+                    "n_keys_next == n_keys + is_storage_key_start_next",
+                    n_keys.1.clone(),
+                    n_keys.0.clone() + is_item_start.1.clone() * is_storage_key.1.clone(),
+                );
+            });
+            // The end of an access list item and its address are set by its
+            // header and its address, and are kept until the next ones.
+            cb.condition(
+                and::expr([is_continued.clone(), not::expr(is_access_list_item.1.clone())]),
+                |cb| {
+                    cb.require_equal("item_end_next == item_end", item_end.1, item_end.0.clone());
+                },
+            );
+            cb.condition(
+                and::expr([is_continued, not::expr(is_address.1)]),
+                |cb| {
+                    cb.require_equal("address_next == address", address.1, address.0.clone());
+                },
+            );
+
+            // First byte of an item
+            cb.condition(
+                and::expr([
+                    is_payload.clone(),
+                    is_item_start.0.clone(),
+                    not::expr(is_header.0.clone()),
+                ]),
+                |cb| {
+                    cb.require_equal(
+                        "a single byte item is its own content",
+                        tag_length.0.clone(),
+                        1.expr(),
+                    );
+                    cb.require_equal("tag_rindex == 1", tag_rindex.0.clone(), 1.expr());
+                    cb.require_equal(
+                        "a single byte item is in [0x00, 0x7f]",
+                        byte_class.0.clone(),
+                        ByteClass::SingleByte.expr(),
+                    );
+                    cb.require_zero("a list has a header", is_list.0.clone());
+                    cb.require_equal("value_acc == byte", value_acc.0.clone(), byte.0.clone());
+                },
+            );
+            cb.condition(
+                and::expr([
+                    is_payload.clone(),
+                    is_item_start.0.clone(),
+                    is_header.0.clone(),
+                ]),
+                |cb| {
+                    cb.require_zero("value_acc == 0", value_acc.0.clone());
+                    cb.require_equal(
+                        "the class of the prefix matches the kind of item",
+                        byte_class.0.clone(),
+                        ByteClass::ShortString.expr()
+                            + is_list.0.clone() * 2.expr()
+                            + is_long.0.clone(),
+                    );
+                    cb.require_equal(
+                        "prefix == base + (is_long ? 55 + len_of_len : length)",
+                        byte.0.clone(),
+                        0x80.expr()
+                            + is_list.0.clone() * 0x40.expr()
+                            + select::expr(
+                                is_long.0.clone(),
+                                55.expr() + tag_rindex.0.clone() - 1.expr(),
+                                tag_length.0.clone(),
+                            ),
+                    );
+                },
+            );
+            cb.condition(
+                and::expr([
+                    is_payload.clone(),
+                    is_item_start.0.clone(),
+                    is_header.0.clone(),
+                    not::expr(is_long.0.clone()),
+                ]),
+                |cb| {
+                    cb.require_equal(
+                        "a short header is a single byte",
+                        tag_rindex.0.clone(),
+                        1.expr(),
+                    );
+                },
+            );
+
+            // Last byte of an item
+            let is_end = and::expr([is_payload.clone(), is_item_end.0.clone()]);
+            cb.condition(is_end.clone(), |cb| {
+                cb.require_equal("tag_rindex == 1", tag_rindex.0.clone(), 1.expr());
+            });
+            cb.condition(
+                and::expr([
+                    is_end.clone(),
+                    is_header.0.clone(),
+                    not::expr(is_list.0.clone()),
+                ]),
+                |cb| {
+                    cb.require_zero("an item ending in its header is empty", tag_length.0.clone());
+                },
+            );
+            cb.condition(
+                and::expr([is_end.clone(), is_header.0.clone(), is_long.0.clone()]),
+                |cb| {
+                    cb.require_equal(
+                        "the length bytes decode to the length",
+                        value_acc.0.clone(),
+                        tag_length.0.clone(),
+                    );
+                },
+            );
+            cb.condition(and::expr([is_end.clone(), is_outer_list.0]), |cb| {
+                cb.require_equal(
+                    "the list spans the rest of the payload",
+                    index.0.clone() + tag_length.0.clone(),
+                    payload_len.0.clone(),
+                );
+            });
+            cb.condition(and::expr([is_end.clone(), is_chain_id.0]), |cb| {
+                cb.require_equal("value_acc == chain_id", value_acc.0.clone(), chain_id.0);
+            });
+            cb.condition(and::expr([is_end.clone(), is_zero.0]), |cb| {
+                cb.require_zero("value_acc == 0", value_acc.0.clone());
+            });
+            cb.condition(and::expr([is_end.clone(), is_to.0]), |cb| {
+                cb.require_zero(
+                    "to is either empty or an address",
+                    tag_length.0.clone() * (tag_length.0.clone() - 20.expr()),
+                );
+            });
+            cb.condition(
+                and::expr([is_end.clone(), is_access_list_item.0.clone()]),
+                |cb| {
+                    cb.require_equal(
+                        "item_end == index + tag_length",
+                        item_end.0.clone(),
+                        index.0.clone() + tag_length.0.clone(),
+                    );
+                },
+            );
+            cb.condition(and::expr([is_end.clone(), is_address.0.clone()]), |cb| {
+                cb.require_equal("an address is 20 bytes", tag_length.0.clone(), 20.expr());
+                cb.require_equal("address == value_acc", address.0, value_acc.0.clone());
+            });
+            cb.condition(
+                and::expr([is_end.clone(), is_storage_keys.0.clone()]),
+                |cb| {
+                    cb.require_equal(
+                        "the storage keys end with their access list item",
+                        index.0.clone() + tag_length.0.clone(),
+                        item_end.0.clone(),
+                    );
+                },
+            );
+            cb.condition(
+                and::expr([is_end.clone(), is_storage_key.0.clone()]),
+                |cb| {
+                    cb.require_equal("a storage key is 32 bytes", tag_length.0.clone(), 32.expr());
+                },
+            );
+            // An access list item ends with its storage keys, followed by the
+            // next item or by the end of the payload.
+            cb.condition(
+                and::expr([
+                    is_end,
+                    is_storage_keys.0 + is_storage_key.0,
+                    is_last.0.clone() + not::expr(is_last.0) * is_access_list_item.1,
+                ]),
+                |cb| {
+                    cb.require_equal(
+                        "index == item_end",
+                        index.0.clone(),
+                        item_end.0.clone(),
+                    );
+                },
+            );
+
+            // Continuation of an item
+            let is_item_continued =
+                and::expr([is_payload.clone(), not::expr(is_item_end.0.clone())]);
+            cb.condition(is_item_continued.clone(), |cb| {
+                for (name, (cur, next)) in [
+                    ("tag", &tag),
+                    ("tag_length", &tag_length),
+                    ("is_long", &is_long),
+                ] {
+                    cb.require_equal(name, next.clone(), cur.clone());
+                }
+            });
+            cb.condition(
+                and::expr([
+                    is_item_continued.clone(),
+                    is_header.0.clone(),
+                    is_header.1.clone(),
+                ]),
+                |cb| {
+                    cb.require_equal(
+                        "tag_rindex_next == tag_rindex - 1",
+                        tag_rindex.1.clone(),
+                        tag_rindex.0.clone() - 1.expr(),
+                    );
+                    cb.require_equal(
+                        "value_acc_next == value_acc * 256 + byte_next",
+                        value_acc.1.clone(),
+                        value_acc.0.clone() * 256.expr() + byte.1.clone(),
+                    );
+                },
+            );
+            let is_header_end = and::expr([
+                is_item_continued.clone(),
+                is_header.0.clone(),
+                not::expr(is_header.1.clone()),
+            ]);
+            cb.condition(is_header_end.clone(), |cb| {
+                cb.require_equal("tag_rindex == 1", tag_rindex.0.clone(), 1.expr());
+                cb.require_equal(
+                    "tag_rindex_next == tag_length",
+                    tag_rindex.1.clone(),
+                    tag_length.0.clone(),
+                );
+                cb.require_equal(
+                    "value_acc_next == byte_next",
+                    value_acc.1.clone(),
+                    byte.1.clone(),
+                );
+            });
+            cb.condition(and::expr([is_header_end, is_long.0.clone()]), |cb| {
+                cb.require_equal(
+                    "the length bytes decode to the length",
+                    value_acc.0.clone(),
+                    tag_length.0.clone(),
+                );
+            });
+            cb.condition(
+                and::expr([is_item_continued, not::expr(is_header.0.clone())]),
+                |cb| {
+                    cb.require_zero("the content is the end of an item", is_header.1.clone());
+                    cb.require_equal(
+                        "tag_rindex_next == tag_rindex - 1",
+                        tag_rindex.1.clone(),
+                        tag_rindex.0.clone() - 1.expr(),
+                    );
+                    cb.require_equal(
+                        "value_acc_next == value_acc * (is_word ? r : 256) + byte_next",
+                        value_acc.1.clone(),
+                        value_acc.0.clone()
+                            * select::expr(is_word.0, challenges.evm_word(), 256.expr())
+                            + byte.1.clone(),
+                    );
+                },
+            );
+
+            // Lower bound of the byte in a canonical encoding, looked up below:
+            // - a single byte scalar is not zero, which is encoded as 0x80;
+            // - the first content byte of a scalar is not zero, and a single
+            //   content byte is not in [0x00, 0x7f];
+            // - the first length byte of a long header is not zero, and a single
+            //   length byte encodes a length above 55.
+            // All the conditions are zero on the padding rows.
+            let is_first_content = is_header_prev
+                * not::expr(is_header.0.clone())
+                * not::expr(is_item_start.0.clone());
+            let is_first_length = is_header.0.clone()
+                * is_long.0
+                * is_item_start_prev
+                * not::expr(is_item_start.0.clone());
+            let is_last_length = not::expr(not::expr(is_item_end.0.clone()) * is_header.1);
+            cb.require_equal(
+                "min_byte of a canonical encoding",
+                min_byte.0,
+                is_item_start.0 * not::expr(is_header.0) * is_scalar.0.clone()
+                    + is_first_content * select::expr(is_item_end.0, 0x80.expr(), is_scalar.0)
+                    + is_first_length * (1.expr() + 55.expr() * is_last_length),
+            );
+
+            cb.gate(meta.query_fixed(q_enable, Rotation::cur()))
+        });
+
+        // Enabled rows of the payloads
+        let q_payload = |meta: &mut VirtualCells<F>| {
+            meta.query_fixed(q_enable, Rotation::cur())
+                * not::expr(meta.query_advice(is_padding, Rotation::cur()))
+        };
+
+        meta.lookup_any("RLP byte class", |meta| {
+            let q_enable = q_payload(meta);
+            [byte, byte_class]
+                .into_iter()
+                .zip(byte_table)
+                .map(|(input, table)| {
+                    (
+                        q_enable.clone() * meta.query_advice(input, Rotation::cur()),
+                        meta.query_fixed(table, Rotation::cur()),
+                    )
+                })
+                .collect()
+        });
+
+        // byte - min_byte is a byte
+        meta.lookup_any("RLP canonical encoding", |meta| {
+            let q_enable = meta.query_fixed(q_enable, Rotation::cur());
+            let byte = meta.query_advice(byte, Rotation::cur());
+            let min_byte = meta.query_advice(min_byte, Rotation::cur());
+            vec![(
+                q_enable * (byte - min_byte),
+                meta.query_fixed(byte_table[0], Rotation::cur()),
+            )]
+        });
+
+        meta.lookup_any("RLP tag properties", |meta| {
+            let q_enable = q_payload(meta);
+            std::iter::once(q_enable.clone())
+                .chain(
+                    std::iter::once(tag).chain(tag_properties).map(|column| {
+                        q_enable.clone() * meta.query_advice(column, Rotation::cur())
+                    }),
+                )
+                .zip(tag_table)
+                .map(|(input, table)| (input, meta.query_fixed(table, Rotation::cur())))
+                .collect()
+        });
+
+        // The first item of a payload, the item following each item and the
+        // last item of a payload are the ones of the transaction type.
+        for name in ["RLP first tag", "RLP tag transition", "RLP last tag"] {
+            meta.lookup_any(name, |meta| {
+                let is_first = meta.query_advice(is_first, Rotation::cur());
+                let is_item_end = meta.query_advice(is_item_end, Rotation::cur());
+                let is_last = meta.query_advice(is_last, Rotation::cur());
+                let (condition, tag_cur, tag_next) = match name {
+                    "RLP first tag" => (
+                        is_first,
+                        RlpTag::Null.expr(),
+                        meta.query_advice(tag, Rotation::cur()),
+                    ),
+                    "RLP tag transition" => (
+                        is_item_end * not::expr(is_last),
+                        meta.query_advice(tag, Rotation::cur()),
+                        meta.query_advice(tag, Rotation::next()),
+                    ),
+                    _ => (
+                        is_last,
+                        meta.query_advice(tag, Rotation::cur()),
+                        RlpTag::Null.expr(),
+                    ),
+                };
+                let enable = q_payload(meta) * condition;
+                [
+                    enable.clone(),
+                    enable.clone() * meta.query_advice(tx_type, Rotation::cur()),
+                    enable.clone() * tag_cur,
+                    enable * tag_next,
+                ]
+                .into_iter()
+                .zip(transition_table)
+                .map(|(input, table)| (input, meta.query_fixed(table, Rotation::cur())))
+                .collect()
+            });
+        }
+
+        let tx_lookup = |meta: &mut VirtualCells<F>,
+                         enable: Expression<F>,
+                         field_tag: Expression<F>,
+                         field_index: Expression<F>,
+                         value: Expression<F>| {
+            [
+                meta.query_advice(tx_id, Rotation::cur()),
+                field_tag,
+                field_index,
+                value,
+            ]
+            .into_iter()
+            .zip(tx_table.columns())
+            .map(|(input, table)| {
+                (
+                    enable.clone() * input,
+                    meta.query_advice(table, Rotation::cur()),
+                )
+            })
+            .collect::<Vec<_>>()
+        };
+
+        meta.lookup_any("RLP tx field", |meta| {
+            let [is_calldata, is_tx_field] =
+                [3, 4].map(|i| meta.query_advice(tag_properties[i], Rotation::cur()));
+            let enable =
+                q_payload(meta) * meta.query_advice(is_item_end, Rotation::cur()) * is_tx_field;
+            // The end of the call data is checked against its length, its bytes are
+            // checked one by one below.
+            let field_tag = meta.query_advice(tag, Rotation::cur())
+                + is_calldata.clone()
+                    * (TxFieldTag::CallDataLength.expr() - TxFieldTag::CallData.expr());
+            let value = select::expr(
+                is_calldata,
+                meta.query_advice(tag_length, Rotation::cur()),
+                meta.query_advice(value_acc, Rotation::cur()),
+            );
+            tx_lookup(meta, enable, field_tag, 0.expr(), value)
+        });
+
+        meta.lookup_any("RLP tx call data", |meta| {
+            let enable = q_payload(meta)
+                * not::expr(meta.query_advice(is_header, Rotation::cur()))
+                * meta.query_advice(tag_properties[3], Rotation::cur());
+            let calldata_index = meta.query_advice(tag_length, Rotation::cur())
+                - meta.query_advice(tag_rindex, Rotation::cur());
+            let value = meta.query_advice(byte, Rotation::cur());
+            tx_lookup(
+                meta,
+                enable,
+                TxFieldTag::CallData.expr(),
+                calldata_index,
+                value,
+            )
+        });
+
+        meta.lookup_any("RLP tx is_create", |meta| {
+            let enable = q_payload(meta)
+                * meta.query_advice(is_item_end, Rotation::cur())
+                * meta.query_advice(tag_properties[7], Rotation::cur());
+            // is_create == (20 - tag_length) / 20, as tag_length is either 0 or 20
+            let is_create = (20.expr() - meta.query_advice(tag_length, Rotation::cur()))
+                * Expression::Constant(F::from(20).invert().unwrap());
+            tx_lookup(
+                meta,
+                enable,
+                TxFieldTag::IsCreate.expr(),
+                0.expr(),
+                is_create,
+            )
+        });
+
+        // Each address and storage key of the access list is the entry of the
+        // tx table at its index among the entries of the transaction, with the
+        // address of its access list item.
+        meta.lookup_any("RLP tx access list entry", |meta| {
+            let [is_address, is_storage_key] =
+                [10, 12].map(|i| meta.query_advice(tag_properties[i], Rotation::cur()));
+            let enable = q_payload(meta)
+                * meta.query_advice(is_item_end, Rotation::cur())
+                * (is_address + is_storage_key.clone());
+            let field_tag = TxFieldTag::AccessListAddress.expr()
+                + is_storage_key
+                    * (TxFieldTag::AccessListStorageKey.expr()
+                        - TxFieldTag::AccessListAddress.expr());
+            let entry_index = meta.query_advice(n_addresses, Rotation::cur())
+                + meta.query_advice(n_keys, Rotation::cur())
+                - 1.expr();
+            let value = meta.query_advice(value_acc, Rotation::cur());
+            let address = meta.query_advice(address, Rotation::cur());
+            let mut lookup = tx_lookup(meta, enable.clone(), field_tag, entry_index, value);
+            lookup.push((
+                enable * address,
+                meta.query_advice(access_list_address, Rotation::cur()),
+            ));
+            lookup
+        });
+
+        // The lengths of the access list are the numbers of its decoded
+        // addresses and storage keys, which are zero for legacy transactions.
+        for (name, field_tag, column) in [
+            (
+                "RLP tx access list addresses length",
+                TxFieldTag::AccessListAddressesLen,
+                n_addresses,
+            ),
+            (
+                "RLP tx access list storage keys length",
+                TxFieldTag::AccessListStorageKeysLen,
+                n_keys,
+            ),
+        ] {
+            meta.lookup_any(name, |meta| {
+                let enable = q_payload(meta) * meta.query_advice(is_last, Rotation::cur());
+                let value = meta.query_advice(column, Rotation::cur());
+                tx_lookup(meta, enable, field_tag.expr(), 0.expr(), value)
+            });
+        }
+
+        meta.lookup_any("RLP tx sign hash", |meta| {
+            let enable = q_payload(meta) * meta.query_advice(is_last, Rotation::cur());
+            let hash_rlc = meta.query_advice(hash_rlc, Rotation::cur());
+            tx_lookup(
+                meta,
+                enable,
+                TxFieldTag::TxSignHash.expr(),
+                0.expr(),
+                hash_rlc,
+            )
+        });
+
+        meta.lookup_any("RLP keccak", |meta| {
+            let enable = q_payload(meta) * meta.query_advice(is_last, Rotation::cur());
+            [
+                1.expr(),
+                meta.query_advice(rlc, Rotation::cur()),
+                meta.query_advice(index, Rotation::cur()),
+                meta.query_advice(hash_rlc, Rotation::cur()),
+            ]
+            .into_iter()
+            .zip(keccak_table.columns())
+            .map(|(input, table)| {
+                (
+                    enable.clone() * input,
+                    meta.query_advice(table, Rotation::cur()),
+                )
+            })
+            .collect()
+        });
+
+        // The transactions are signed for the chain id of the block, which is
+        // the same in all the enabled rows.
+        meta.lookup_any("RLP chain id in block table", |meta| {
+            let enable = meta.query_fixed(q_first, Rotation::cur());
+            [
+                BlockContextFieldTag::ChainId.expr(),
+                0.expr(),
+                meta.query_advice(chain_id, Rotation::cur()),
+            ]
+            .into_iter()
+            .zip(block_table.columns())
+            .map(|(input, table)| {
+                (
+                    enable.clone() * input,
+                    meta.query_advice(table, Rotation::cur()),
+                )
+            })
+            .collect()
+        });
+
+        Self {
+            q_enable,
+            q_first,
+            tx_id,
+            tx_type,
+            tag,
+            index,
+            byte,
+            byte_class,
+            min_byte,
+            rlc,
+            is_first,
+            is_last,
+            is_item_start,
+            is_item_end,
+            is_header,
+            is_long,
+            tag_length,
+            tag_rindex,
+            value_acc,
+            payload_len,
+            hash_rlc,
+            item_end,
+            address,
+            n_addresses,
+            n_keys,
+            chain_id,
+            is_padding,
+            tag_properties,
+            byte_table,
+            tag_table,
+            transition_table,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Fixed column of the bytes 0 to 255, to look up byte range checks in.
+    pub(crate) fn byte_column(&self) -> Column<Fixed> {
+        self.byte_table[0]
+    }
+
+    /// Expressions `(tx_id, sign_hash)` of the last row of each payload, and
+    /// zeros on the other rows.  The sign hash is multiplied into the tx id so
+    /// that a zero sign hash (of a padding transaction) matches any row.
+    pub(crate) fn sign_hash_table_exprs(&self, meta: &mut VirtualCells<F>) -> [Expression<F>; 2] {
+        let enable = meta.query_fixed(self.q_enable, Rotation::cur())
+            * not::expr(meta.query_advice(self.is_padding, Rotation::cur()))
+            * meta.query_advice(self.is_last, Rotation::cur());
+        let hash_rlc = meta.query_advice(self.hash_rlc, Rotation::cur());
+        [
+            enable.clone() * hash_rlc.clone(),
+            enable * meta.query_advice(self.tx_id, Rotation::cur()) * hash_rlc,
+        ]
+    }
+
+    /// Load the fixed tables of the chip.
+    pub(crate) fn load(&self, layouter: &mut impl Layouter<F>) -> Result<(), Error> {
+        layouter.assign_region(
+            || "RLP byte table",
+            |mut region| {
+                for byte in 0..=255u8 {
+                    for (column, value) in self
+                        .byte_table
+                        .iter()
+                        .zip([byte as u64, ByteClass::from(byte) as u64])
+                    {
+                        region.assign_fixed(
+                            || "RLP byte table",
+                            *column,
+                            byte as usize,
+                            || Value::known(F::from(value)),
+                        )?;
+                    }
+                }
+                Ok(())
+            },
+        )?;
+
+        layouter.assign_region(
+            || "RLP tag table",
+            |mut region| {
+                // The first row is all zeros, for disabled lookups.
+                for (offset, tag) in std::iter::once(None)
+                    .chain(RlpTag::ALL.into_iter().map(Some))
+                    .enumerate()
+                {
+                    let values = std::iter::once(tag.is_some())
+                        .chain(std::iter::once(false))
+                        .chain(tag.map(|tag| tag.properties()).unwrap_or_default());
+                    for (i, (column, value)) in self.tag_table.iter().zip(values).enumerate() {
+                        let value = if i == 1 {
+                            F::from(tag.map(|tag| tag as u64).unwrap_or_default())
+                        } else {
+                            F::from(value as u64)
+                        };
+                        region.assign_fixed(
+                            || "RLP tag table",
+                            *column,
+                            offset,
+                            || Value::known(value),
+                        )?;
+                    }
+                }
+                Ok(())
+            },
+        )?;
+
+        layouter.assign_region(
+            || "RLP tag transition table",
+            |mut region| {
+                let mut transitions = vec![[0; 4]];
+                for (tx_type, is_eip155) in [(0, false), (0, true), (1, true), (2, true)] {
+                    transitions.extend(
+                        RlpTag::transitions(tx_type, is_eip155)
+                            .into_iter()
+                            .map(|(tag, tag_next)| [1, tx_type, tag as u64, tag_next as u64]),
+                    );
+                }
+                for (offset, transition) in transitions.into_iter().enumerate() {
+                    for (column, value) in self.transition_table.iter().zip(transition) {
+                        region.assign_fixed(
+                            || "RLP tag transition table",
+                            *column,
+                            offset,
+                            || Value::known(F::from(value)),
+                        )?;
+                    }
+                }
+                Ok(())
+            },
+        )
+    }
+
+    /// Assign the rows of the signing payloads, padded up to `num_rows`
+    /// enabled rows, with the chain id (as the RLC of its little-endian
+    /// bytes).
+    pub(crate) fn assign(
+        &self,
+        layouter: &mut impl Layouter<F>,
+        rows: &[RlpRow<F>],
+        num_rows: usize,
+        chain_id: Value<F>,
+    ) -> Result<(), Error> {
+        if rows.len() > num_rows {
+            error!(
+                "RLP encoding: {} rows exceed the {} available rows",
+                rows.len(),
+                num_rows
+            );
+            return Err(Error::Synthesis);
+        }
+        layouter.assign_region(
+            || "RLP encoding",
+            |mut region| {
+                // The enabled rows are followed by a disabled all-zero row.
+                for offset in 0..=num_rows {
+                    self.assign_row(
+                        &mut region,
+                        offset,
+                        rows.get(offset),
+                        offset < num_rows,
+                        chain_id,
+                    )?;
+                }
+                Ok(())
+            },
+        )
+    }
+
+    fn assign_row(
+        &self,
+        region: &mut Region<'_, F>,
+        offset: usize,
+        row: Option<&RlpRow<F>>,
+        enabled: bool,
+        chain_id: Value<F>,
+    ) -> Result<(), Error> {
+        for (name, column, value) in [
+            ("q_enable", self.q_enable, enabled),
+            ("q_first", self.q_first, enabled && offset == 0),
+        ] {
+            region.assign_fixed(
+                || format!("RLP {} {}", name, offset),
+                column,
+                offset,
+                || Value::known(F::from(value as u64)),
+            )?;
+        }
+
+        let properties = row
+            .map(|row| row.tag.properties())
+            .unwrap_or_default()
+            .map(|value| Value::known(F::from(value as u64)));
+        let values = row
+            .map(|row| {
+                [
+                    Value::known(F::from(row.tx_id as u64)),
+                    Value::known(F::from(row.tx_type)),
+                    Value::known(F::from(row.tag as u64)),
+                    Value::known(F::from(row.index as u64)),
+                    Value::known(F::from(row.byte as u64)),
+                    Value::known(F::from(ByteClass::from(row.byte) as u64)),
+                    Value::known(F::from(row.min_byte as u64)),
+                    row.rlc,
+                    Value::known(F::from(row.is_first as u64)),
+                    Value::known(F::from(row.is_last as u64)),
+                    Value::known(F::from(row.is_item_start as u64)),
+                    Value::known(F::from(row.is_item_end as u64)),
+                    Value::known(F::from(row.is_header as u64)),
+                    Value::known(F::from(row.is_long as u64)),
+                    Value::known(F::from(row.tag_length as u64)),
+                    Value::known(F::from(row.tag_rindex as u64)),
+                    row.value_acc,
+                    Value::known(F::from(row.payload_len as u64)),
+                    row.hash_rlc,
+                    Value::known(F::from(row.item_end as u64)),
+                    row.address,
+                    Value::known(F::from(row.n_addresses as u64)),
+                    Value::known(F::from(row.n_keys as u64)),
+                ]
+            })
+            .unwrap_or([Value::known(F::zero()); 23]);
+        for (column, value) in [
+            self.tx_id,
+            self.tx_type,
+            self.tag,
+            self.index,
+            self.byte,
+            self.byte_class,
+            self.min_byte,
+            self.rlc,
+            self.is_first,
+            self.is_last,
+            self.is_item_start,
+            self.is_item_end,
+            self.is_header,
+            self.is_long,
+            self.tag_length,
+            self.tag_rindex,
+            self.value_acc,
+            self.payload_len,
+            self.hash_rlc,
+            self.item_end,
+            self.address,
+            self.n_addresses,
+            self.n_keys,
+        ]
+        .into_iter()
+        .chain(self.tag_properties)
+        .zip(values.into_iter().chain(properties))
+        {
+            region.assign_advice(
+                || format!("RLP encoding row {}", offset),
+                column,
+                offset,
+                || value,
+            )?;
+        }
+        region.assign_advice(
+            || format!("RLP is_padding {}", offset),
+            self.is_padding,
+            offset,
+            || Value::known(F::from(row.is_none() as u64)),
+        )?;
+        region.assign_advice(
+            || format!("RLP chain_id {}", offset),
+            self.chain_id,
+            offset,
+            || chain_id,
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod rlp_encoding_tests {
+    use super::*;
+    use eth_types::{AccessList, AccessListItem, H256};
+    use halo2_proofs::halo2curves::bn256::Fr;
+    use itertools::Itertools;
+
+    #[test]
+    fn rlp_rows_decode_sign_payload() {
+        let chain_id = mock::MOCK_CHAIN_ID.as_u64();
+        for tx in mock::CORRECT_MOCK_TXS.iter() {
+            let tx = Transaction::from(tx.clone());
+            let payload = tx.sign_payload(chain_id);
+            let randomness = Value::known(Fr::from(0x100));
+            let challenges = Challenges::mock(randomness, randomness, randomness);
+            let rows = rlp_rows(1, &tx, chain_id, &challenges).unwrap();
+
+            assert_eq!(rows.iter().map(|row| row.byte).collect_vec(), payload);
+            assert!(rows[0].is_first && rows[rows.len() - 1].is_last);
+            assert_eq!(
+                rows.iter()
+                    .filter(|row| row.is_item_start)
+                    .map(|row| row.tag)
+                    .collect_vec(),
+                RlpTag::sequence(tx.transaction_type, tx.is_eip155())
+            );
+            let nonce = rows
+                .iter()
+                .find(|row| row.tag == RlpTag::Nonce && row.is_item_end)
+                .unwrap();
+            nonce
+                .value_acc
+                .assert_if_known(|value_acc| *value_acc == Fr::from(tx.nonce.as_u64()));
+            assert!(rows.iter().all(|row| row.byte >= row.min_byte));
+        }
+    }
+
+    #[test]
+    fn rlp_rows_decode_access_list() {
+        let chain_id = mock::MOCK_CHAIN_ID.as_u64();
+        let mut tx = Transaction::from(mock::CORRECT_MOCK_TXS[0].clone());
+        tx.transaction_type = 1;
+        tx.access_list = Some(AccessList(vec![
+            AccessListItem {
+                address: mock::MOCK_ACCOUNTS[1],
+                storage_keys: vec![H256::from_low_u64_be(1), H256::from_low_u64_be(2)],
+            },
+            AccessListItem {
+                address: mock::MOCK_ACCOUNTS[2],
+                storage_keys: vec![],
+            },
+        ]));
+        let payload = tx.sign_payload(chain_id);
+        let randomness = Value::known(Fr::from(0x100));
+        let challenges = Challenges::mock(randomness, randomness, randomness);
+        let rows = rlp_rows(1, &tx, chain_id, &challenges).unwrap();
+
+        assert_eq!(rows.iter().map(|row| row.byte).collect_vec(), payload);
+        assert!(rows.iter().all(|row| row.byte >= row.min_byte));
+        let tags = rows
+            .iter()
+            .filter(|row| row.is_item_start)
+            .map(|row| row.tag)
+            .collect_vec();
+        assert_eq!(
+            tags[tags.len() - 9..],
+            [
+                RlpTag::AccessList,
+                RlpTag::AccessListItem,
+                RlpTag::AccessListAddress,
+                RlpTag::StorageKeys,
+                RlpTag::StorageKey,
+                RlpTag::StorageKey,
+                RlpTag::AccessListItem,
+                RlpTag::AccessListAddress,
+                RlpTag::StorageKeys,
+            ]
+        );
+        let transitions = RlpTag::transitions(1, true);
+        assert!(std::iter::once(RlpTag::Null)
+            .chain(tags.iter().copied())
+            .zip(tags.iter().copied().chain(std::iter::once(RlpTag::Null)))
+            .all(|transition| transitions.contains(&transition)));
+
+        // The entries are counted in the order of the access list writes, and
+        // the storage keys carry the address of their item.
+        let last = &rows[rows.len() - 1];
+        assert_eq!((last.n_addresses, last.n_keys), (2, 2));
+        assert_eq!(last.index, last.item_end);
+        let key = rows
+            .iter()
+            .find(|row| row.tag == RlpTag::StorageKey && row.is_item_end)
+            .unwrap();
+        assert_eq!(key.n_addresses + key.n_keys - 1, 1);
+        key.address
+            .assert_if_known(|address| *address == mock::MOCK_ACCOUNTS[1].to_scalar().unwrap());
+    }
+}
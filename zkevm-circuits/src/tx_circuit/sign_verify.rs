@@ -7,7 +7,7 @@
 use crate::{
     evm_circuit::util::{not, RandomLinearCombination, Word},
     table::KeccakTable,
-    util::Expr,
+    util::{Challenges, Expr},
 };
 use ecc::{EccConfig, GeneralEccChip};
 use ecdsa::ecdsa::{AssignedEcdsaSig, AssignedPublicKey, EcdsaChip};
@@ -19,7 +19,7 @@ use halo2_proofs::{
     arithmetic::FieldExt,
     circuit::{AssignedCell, Layouter, Region, Value},
     halo2curves::secp256k1,
-    plonk::{Advice, Column, ConstraintSystem, Error, Expression, Selector},
+    plonk::{Advice, Column, ConstraintSystem, Error, Expression, SecondPhase, Selector},
     poly::Rotation,
 };
 use integer::{AssignedInteger, IntegerChip, IntegerConfig, IntegerInstructions, Range};
@@ -33,9 +33,6 @@ use maingate::{
 };
 use std::marker::PhantomData;
 
-/// Power of randomness vector size required for the SignVerifyChip
-pub const POW_RAND_SIZE: usize = 63;
-
 /// Number of rows required for a verification of the SignVerifyChip in the
 /// "signature address verify" region.
 pub const VERIF_HEIGHT: usize = 1;
@@ -106,7 +103,6 @@ pub(crate) struct SignVerifyConfig<F: Field> {
     // endian.
     pk: [[Column<Advice>; 32]; 2],
     msg_hash: [Column<Advice>; 32],
-    power_of_randomness: [Expression<F>; POW_RAND_SIZE],
 
     // [is_enabled, input_rlc, input_len, output_rlc]
     keccak_table: KeccakTable,
@@ -115,9 +111,16 @@ pub(crate) struct SignVerifyConfig<F: Field> {
 impl<F: Field> SignVerifyConfig<F> {
     pub(crate) fn new(
         meta: &mut ConstraintSystem<F>,
-        power_of_randomness: [Expression<F>; POW_RAND_SIZE],
+        challenges: &Challenges<Expression<F>>,
         keccak_table: KeccakTable,
     ) -> Self {
+        // The public key is an input of the keccak table, while its hash and the
+        // message hash are words.
+        let keccak_powers_of_randomness: [Expression<F>; 63] =
+            challenges.keccak_powers_of_randomness();
+        let word_powers_of_randomness: [Expression<F>; 31] =
+            challenges.evm_word_powers_of_randomness();
+
         let q_enable = meta.complex_selector();
 
         let pk = [(); 2].map(|_| [(); 32].map(|_| meta.advice_column()));
@@ -132,7 +135,7 @@ impl<F: Field> SignVerifyConfig<F> {
 
         let pk_hash = [(); 32].map(|_| meta.advice_column());
 
-        let msg_hash_rlc = meta.advice_column();
+        let msg_hash_rlc = meta.advice_column_in(SecondPhase);
         meta.enable_equality(msg_hash_rlc);
 
         let address_inv = meta.advice_column();
@@ -170,8 +173,10 @@ impl<F: Field> SignVerifyConfig<F> {
                 .expect("vector to array of size 64");
             let mut pk_be = pk_bytes_swap_endianness(&pk_le);
             pk_be.reverse();
-            let pk_rlc =
-                RandomLinearCombination::random_linear_combine_expr(pk_be, &power_of_randomness);
+            let pk_rlc = RandomLinearCombination::random_linear_combine_expr(
+                pk_be,
+                &keccak_powers_of_randomness,
+            );
             table_map.push((selector.clone() * pk_rlc, keccak_input_rlc));
 
             // Column 2: input_len (64)
@@ -185,7 +190,7 @@ impl<F: Field> SignVerifyConfig<F> {
                                    // `random_linear_combine_expr` expects LSB first.
             let pk_hash_rlc = RandomLinearCombination::random_linear_combine_expr(
                 pk_hash_rev,
-                &power_of_randomness,
+                &word_powers_of_randomness,
             );
             table_map.push((selector * pk_hash_rlc, keccak_output_rlc));
 
@@ -213,7 +218,7 @@ impl<F: Field> SignVerifyConfig<F> {
 
             let expected_msg_hash_rlc = RandomLinearCombination::random_linear_combine_expr(
                 msg_hash,
-                &power_of_randomness[..32],
+                &word_powers_of_randomness,
             );
             vec![q_enable * (msg_hash_rlc - is_not_padding.clone() * expected_msg_hash_rlc)]
         });
@@ -243,7 +248,6 @@ impl<F: Field> SignVerifyConfig<F> {
             main_gate_config,
             pk,
             msg_hash,
-            power_of_randomness,
             keccak_table,
         }
     }
@@ -331,6 +335,24 @@ struct ChipsRef<'a, F: Field, const NUMBER_OF_LIMBS: usize, const BIT_LEN_LIMB:
 }
 
 impl<F: Field, const MAX_VERIF: usize> SignVerifyChip<F, MAX_VERIF> {
+    /// Return the minimum number of rows required to verify `MAX_VERIF`
+    /// signatures: the rows of the range tables, or the rows of the
+    /// verifications when there are more of them.
+    pub(crate) fn min_num_rows() -> usize {
+        // The number of rows of the tables of the range chip, and of the regions
+        // of the auxiliary points of the ecc chip, of one ECDSA verification and
+        // of the recovery of its address, as reported by the debug logs of the
+        // tx circuit with one tx.
+        let rows_range_chip_table = 295188;
+        let rows_ecc_chip_aux = 226;
+        let rows_ecdsa_chip_verification = 104471;
+        let rows_signature_address_verify = 76;
+        rows_range_chip_table.max(
+            (rows_ecc_chip_aux + rows_ecdsa_chip_verification + rows_signature_address_verify)
+                * MAX_VERIF,
+        )
+    }
+
     fn assign_aux(
         &self,
         ctx: &mut RegionCtx<'_, F>,
@@ -406,7 +428,7 @@ impl<F: Field, const MAX_VERIF: usize> SignVerifyChip<F, MAX_VERIF> {
         config: &SignVerifyConfig<F>,
         region: &mut Region<'_, F>,
         offset: usize,
-        randomness: F,
+        randomness: Value<F>,
         address_is_zero_chip: &IsZeroChip<F>,
         sign_data: Option<&SignData>,
         assigned_ecdsa: &AssignedECDSA<F>,
@@ -452,13 +474,16 @@ impl<F: Field, const MAX_VERIF: usize> SignVerifyChip<F, MAX_VERIF> {
         msg_hash_le
             .as_mut_slice()
             .copy_from_slice(msg_hash.to_bytes().as_slice());
-        let msg_hash_rlc = Word::random_linear_combine(msg_hash_le, randomness);
-        let msg_hash_rlc = if !padding { msg_hash_rlc } else { F::zero() };
+        let msg_hash_rlc = if !padding {
+            randomness.map(|randomness| Word::random_linear_combine(msg_hash_le, randomness))
+        } else {
+            Value::known(F::zero())
+        };
         let msg_hash_rlc_assigned = region.assign_advice(
             || "msg_hash_rlc",
             config.msg_hash_rlc,
             offset,
-            || Value::known(msg_hash_rlc),
+            || msg_hash_rlc,
         )?;
 
         // Assign pk
@@ -533,7 +558,7 @@ impl<F: Field, const MAX_VERIF: usize> SignVerifyChip<F, MAX_VERIF> {
         &self,
         config: &SignVerifyConfig<F>,
         layouter: &mut impl Layouter<F>,
-        randomness: F,
+        challenges: &Challenges<Value<F>>,
         signatures: &[SignData],
     ) -> Result<Vec<AssignedSignatureVerify<F>>, Error> {
         if signatures.len() > MAX_VERIF {
@@ -604,7 +629,7 @@ impl<F: Field, const MAX_VERIF: usize> SignVerifyChip<F, MAX_VERIF> {
                         config,
                         &mut region,
                         i, // offset
-                        randomness,
+                        challenges.evm_word(),
                         &address_is_zero_chip,
                         sign_data,
                         assigned_ecdsa,
@@ -631,7 +656,7 @@ fn pub_key_hash_to_address<F: Field>(pk_hash: &[u8]) -> F {
 #[cfg(test)]
 mod sign_verify_tests {
     use super::*;
-    use crate::util::{power_of_randomness_from_instance, Challenges};
+    use crate::util::Challenges;
     use bus_mapping::circuit_input_builder::keccak_inputs_sign_verify;
     use eth_types::sign_types::sign;
     use halo2_proofs::arithmetic::Field as HaloField;
@@ -655,11 +680,11 @@ mod sign_verify_tests {
     }
 
     impl<F: Field> TestCircuitSignVerifyConfig<F> {
-        pub(crate) fn new(meta: &mut ConstraintSystem<F>) -> Self {
-            let power_of_randomness = power_of_randomness_from_instance(meta);
+        pub(crate) fn new(meta: &mut ConstraintSystem<F>, challenges: Challenges) -> Self {
             let keccak_table = KeccakTable::construct(meta);
 
-            let sign_verify = SignVerifyConfig::new(meta, power_of_randomness, keccak_table);
+            let challenges = challenges.exprs(meta);
+            let sign_verify = SignVerifyConfig::new(meta, &challenges, keccak_table);
             TestCircuitSignVerifyConfig { sign_verify }
         }
     }
@@ -667,12 +692,11 @@ mod sign_verify_tests {
     #[derive(Default)]
     struct TestCircuitSignVerify<F: Field, const MAX_VERIF: usize> {
         sign_verify: SignVerifyChip<F, MAX_VERIF>,
-        randomness: F,
         signatures: Vec<SignData>,
     }
 
     impl<F: Field, const MAX_VERIF: usize> Circuit<F> for TestCircuitSignVerify<F, MAX_VERIF> {
-        type Config = TestCircuitSignVerifyConfig<F>;
+        type Config = (TestCircuitSignVerifyConfig<F>, Challenges);
         type FloorPlanner = SimpleFloorPlanner;
 
         fn without_witnesses(&self) -> Self {
@@ -680,20 +704,24 @@ mod sign_verify_tests {
         }
 
         fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
-            TestCircuitSignVerifyConfig::new(meta)
+            let challenges = Challenges::construct(meta);
+            (
+                TestCircuitSignVerifyConfig::new(meta, challenges),
+                challenges,
+            )
         }
 
         fn synthesize(
             &self,
-            config: Self::Config,
+            (config, challenges): Self::Config,
             mut layouter: impl Layouter<F>,
         ) -> Result<(), Error> {
-            let challenges = Challenges::mock(Value::known(self.randomness));
+            let challenges = challenges.values(&mut layouter);
 
             self.sign_verify.assign(
                 &config.sign_verify,
                 &mut layouter,
-                self.randomness,
+                &challenges,
                 &self.signatures,
             )?;
             config.sign_verify.keccak_table.dev_load(
@@ -711,25 +739,18 @@ mod sign_verify_tests {
         let aux_generator =
             <Secp256k1Affine as CurveAffine>::CurveExt::random(&mut rng).to_affine();
 
-        let randomness = F::random(&mut rng);
-        let mut power_of_randomness: Vec<Vec<F>> = (1..POW_RAND_SIZE + 1)
-            .map(|exp| {
-                vec![randomness.pow(&[exp as u64, 0, 0, 0]); signatures.len() * VERIF_HEIGHT]
-            })
-            .collect();
-        // SignVerifyChip -> ECDSAChip -> MainGate instance column
-        power_of_randomness.push(vec![]);
         let circuit = TestCircuitSignVerify::<F, MAX_VERIF> {
             sign_verify: SignVerifyChip {
                 aux_generator,
                 window_size: 2,
                 _marker: PhantomData,
             },
-            randomness,
             signatures,
         };
 
-        let prover = match MockProver::run(k, &circuit, power_of_randomness) {
+        // SignVerifyChip -> ECDSAChip -> MainGate instance column
+        let instance = vec![vec![]];
+        let prover = match MockProver::run(k, &circuit, instance) {
             Ok(prover) => prover,
             Err(e) => panic!("{:#?}", e),
         };
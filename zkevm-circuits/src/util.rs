@@ -2,10 +2,14 @@
 use halo2_proofs::{
     arithmetic::FieldExt,
     circuit::{Layouter, Value},
-    plonk::{Challenge, ConstraintSystem, Expression, FirstPhase, VirtualCells},
-    poly::Rotation,
+    plonk::{
+        Challenge, ConstraintSystem, Error, Expression, FirstPhase, SecondPhase, VirtualCells,
+    },
 };
 
+use crate::witness;
+use eth_types::Field;
+
 pub use gadgets::util::Expr;
 
 pub(crate) fn query_expression<F: FieldExt, T>(
@@ -24,47 +28,38 @@ pub(crate) fn random_linear_combine_word<F: FieldExt>(bytes: [u8; 32], randomnes
     crate::evm_circuit::util::Word::random_linear_combine(bytes, randomness)
 }
 
-/// Query N instances at current rotation and return their expressions.  This
-/// function is used to get the power of randomness (passed as
-/// instances) in our tests.
-pub fn power_of_randomness_from_instance<F: FieldExt, const N: usize>(
-    meta: &mut ConstraintSystem<F>,
-) -> [Expression<F>; N] {
-    // This gate is used just to get the array of expressions from the power of
-    // randomness instance column, so that later on we don't need to query
-    // columns everywhere, and can pass the power of randomness array
-    // expression everywhere.  The gate itself doesn't add any constraints.
-
-    let columns = [(); N].map(|_| meta.instance_column());
-    query_expression(meta, |meta| {
-        columns.map(|column| meta.query_instance(column, Rotation::cur()))
-    })
-}
-
 /// All challenges used in `SuperCircuit`.
 #[derive(Clone, Copy, Debug)]
 pub struct Challenges<T = Challenge> {
     evm_word: T,
     keccak_input: T,
+    lookup_input: T,
 }
 
 impl Challenges {
     /// Construct `Challenges` by allocating challenges in specific phases.
+    /// `evm_word` and `keccak_input` are usable after the first phase, so
+    /// that the bytes they combine are committed before, and `lookup_input`
+    /// is usable after the second phase, so that it can compress lookups of
+    /// values combined with the former.
     pub fn construct<F: FieldExt>(meta: &mut ConstraintSystem<F>) -> Self {
         Self {
             evm_word: meta.challenge_usable_after(FirstPhase),
             keccak_input: meta.challenge_usable_after(FirstPhase),
+            lookup_input: meta.challenge_usable_after(SecondPhase),
         }
     }
 
     /// Returns `Expression` of challenges from `ConstraintSystem`.
     pub fn exprs<F: FieldExt>(&self, meta: &mut ConstraintSystem<F>) -> Challenges<Expression<F>> {
-        let [evm_word, keccak_input] = query_expression(meta, |meta| {
-            [self.evm_word, self.keccak_input].map(|challenge| meta.query_challenge(challenge))
+        let [evm_word, keccak_input, lookup_input] = query_expression(meta, |meta| {
+            [self.evm_word, self.keccak_input, self.lookup_input]
+                .map(|challenge| meta.query_challenge(challenge))
         });
         Challenges {
             evm_word,
             keccak_input,
+            lookup_input,
         }
     }
 
@@ -73,6 +68,7 @@ impl Challenges {
         Challenges {
             evm_word: layouter.get_challenge(self.evm_word),
             keccak_input: layouter.get_challenge(self.keccak_input),
+            lookup_input: layouter.get_challenge(self.lookup_input),
         }
     }
 }
@@ -88,10 +84,90 @@ impl<T: Clone> Challenges<T> {
         self.keccak_input.clone()
     }
 
-    pub(crate) fn mock(challenge: T) -> Self {
+    /// Returns challenge of `lookup_input`.
+    pub fn lookup_input(&self) -> T {
+        self.lookup_input.clone()
+    }
+
+    /// Returns the challenges indexed by the challenge index, in the order
+    /// they are allocated by [`Challenges::construct`].
+    pub fn indexed(&self) -> [&T; 3] {
+        [&self.evm_word, &self.keccak_input, &self.lookup_input]
+    }
+
+    pub(crate) fn mock(evm_word: T, keccak_input: T, lookup_input: T) -> Self {
         Self {
-            evm_word: challenge.clone(),
-            keccak_input: challenge,
+            evm_word,
+            keccak_input,
+            lookup_input,
         }
     }
 }
+
+impl<F: FieldExt> Challenges<Expression<F>> {
+    /// Returns powers of randomness
+    fn powers_of<const S: usize>(base: Expression<F>) -> [Expression<F>; S] {
+        std::iter::successors(base.clone().into(), |power| {
+            (base.clone() * power.clone()).into()
+        })
+        .take(S)
+        .collect::<Vec<_>>()
+        .try_into()
+        .unwrap()
+    }
+
+    /// Returns powers of randomness for word RLC encoding
+    pub fn evm_word_powers_of_randomness<const S: usize>(&self) -> [Expression<F>; S] {
+        Self::powers_of(self.evm_word.clone())
+    }
+
+    /// Returns powers of randomness for keccak circuit's input
+    pub fn keccak_powers_of_randomness<const S: usize>(&self) -> [Expression<F>; S] {
+        Self::powers_of(self.keccak_input.clone())
+    }
+
+    /// Returns powers of randomness for lookups
+    pub fn lookup_input_powers_of_randomness<const S: usize>(&self) -> [Expression<F>; S] {
+        Self::powers_of(self.lookup_input.clone())
+    }
+}
+
+/// Circuit of the zkEVM that can be composed with the other circuits in the
+/// [`SuperCircuit`](crate::super_circuit::SuperCircuit), sharing their lookup
+/// tables.
+pub trait SubCircuit<F: Field> {
+    /// Configuration of the SubCircuit.
+    type Config: SubCircuitConfig<F>;
+
+    /// Create a new SubCircuit from a witness Block
+    fn new_from_block(block: &witness::Block<F>) -> Self;
+
+    /// Returns the instance columns required for this circuit.
+    fn instance(&self) -> Vec<Vec<F>> {
+        vec![]
+    }
+
+    /// Assign only the columns used by this sub-circuit.  This includes the
+    /// columns that belong to the exposed lookup table contained within, if
+    /// any; and excludes external tables that this sub-circuit does lookups
+    /// to.
+    fn synthesize_sub(
+        &self,
+        config: &Self::Config,
+        challenges: &Challenges<Value<F>>,
+        layouter: &mut impl Layouter<F>,
+    ) -> Result<(), Error>;
+
+    /// Return the minimum number of rows required to prove the block, with
+    /// the sizes of its [`CircuitParams`](crate::witness::CircuitParams).
+    fn min_num_rows_block(block: &witness::Block<F>) -> usize;
+}
+
+/// SubCircuit configuration
+pub trait SubCircuitConfig<F: Field> {
+    /// Config constructor arguments
+    type ConfigArgs;
+
+    /// Type constructor
+    fn new(meta: &mut ConstraintSystem<F>, args: Self::ConfigArgs) -> Self;
+}
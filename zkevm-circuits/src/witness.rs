@@ -3,16 +3,23 @@
 //! used to generate witnesses for circuits.
 
 mod block;
-pub use block::{block_convert, Block, BlockContext};
+pub use block::{
+    batch_convert, block_convert, block_convert_with_proofs, block_convert_with_state,
+    block_range_convert, Block, BlockContext, BlockContexts,
+};
 mod bytecode;
 pub use bytecode::Bytecode;
 mod call;
 pub use call::Call;
+mod chunk;
+pub use chunk::{chunk_convert, chunk_convert_with_state, Chunk};
 mod mpt;
-pub use mpt::{MptUpdate, MptUpdateRow, MptUpdates};
+pub use mpt::{MptProof, MptState, MptUpdate, MptUpdateRow, MptUpdates};
+mod params;
+pub use params::{CircuitParams, ParamUnset, ParamsExceeded};
 mod rw;
 pub use rw::{Rw, RwMap, RwRow};
 mod step;
 pub use step::ExecStep;
 mod tx;
-pub use tx::Transaction;
+pub use tx::{access_list_entries, AccessListEntry, Transaction};
@@ -1,36 +1,67 @@
-use std::collections::HashMap;
+use std::{
+    collections::{BTreeMap, HashMap},
+    iter,
+    marker::PhantomData,
+};
 
-use bus_mapping::circuit_input_builder::{self, CopyEvent};
-use eth_types::{Address, Field, ToLittleEndian, ToScalar, Word};
-use halo2_proofs::halo2curves::bn256::Fr;
+use bus_mapping::{
+    circuit_input_builder::{self, CopyEvent},
+    state_db::{CodeDB, StateDB},
+};
+use eth_types::{
+    geth_types, Address, EIP1186ProofResponse, Error, Field, ToLittleEndian, ToScalar, ToWord,
+    Word, H256,
+};
+use halo2_proofs::{circuit::Value, halo2curves::bn256::Fr};
 use itertools::Itertools;
 
-use crate::{evm_circuit::util::RandomLinearCombination, table::BlockContextFieldTag};
+use crate::{
+    evm_circuit::util::RandomLinearCombination,
+    pi_circuit::MAX_INNER_BLOCKS,
+    table::{BlockContextFieldTag, RwTableTag},
+    util::Challenges,
+};
 
-use super::{tx::tx_convert, Bytecode, RwMap, Transaction};
+use super::{
+    tx::tx_convert, Bytecode, Chunk, CircuitParams, MptState, MptUpdates, RwMap, Transaction,
+};
 
 /// Block is the struct used by all circuits, which constains all the needed
-/// data for witness generation.
+/// data for witness generation.  It can hold a batch of consecutive blocks,
+/// see [`batch_convert`].
 #[derive(Debug, Default, Clone)]
 pub struct Block<F> {
-    /// The randomness for random linear combination
-    pub randomness: F,
+    /// The field type of the circuits using the block
+    pub _marker: PhantomData<F>,
     /// Transactions in the block
     pub txs: Vec<Transaction>,
     /// Read write events in the RwTable
     pub rws: RwMap,
+    /// Updates of the MptTable for the account fields and storage slots
+    /// accessed in the block
+    pub mpt_updates: MptUpdates,
     /// Bytecode used in the block
     pub bytecodes: HashMap<Word, Bytecode>,
-    /// The block context
-    pub context: BlockContext,
+    /// The contexts of the blocks of the batch
+    pub context: BlockContexts,
     /// Copy events for the EVM circuit's copy table.
     pub copy_events: Vec<CopyEvent>,
-    /// Pad evm circuit to make selectors fixed, so vk/pk can be universal.
-    pub evm_circuit_pad_to: usize,
-    /// Length to rw table rows in state circuit
-    pub state_circuit_pad_to: usize,
+    /// Sizes the circuits are padded to, so that vk/pk can be universal.
+    pub circuit_params: CircuitParams,
     /// Inputs to the SHA3 opcode
     pub sha3_inputs: Vec<Vec<u8>>,
+    /// Transactions of the block, with their signatures, for the Tx and
+    /// PublicInputs circuits
+    pub eth_txs: Vec<geth_types::Transaction>,
+    /// State root before the first block, which is only known when the MPT
+    /// updates are built from the tries of the state, see
+    /// [`block_convert_with_state`]
+    pub prev_state_root: H256,
+    /// State root after the last block
+    pub state_root: H256,
+    /// The chunk of the block whose steps are proven by the EVM circuit, see
+    /// [`chunk_convert`](super::chunk_convert).  None proves the whole block.
+    pub chunk: Option<Chunk>,
 }
 
 /// Block context for execution
@@ -52,77 +83,62 @@ pub struct BlockContext {
     pub history_hashes: Vec<Word>,
     /// The chain id
     pub chain_id: Word,
+    /// The hash of the block
+    pub hash: H256,
+    /// The state root after the block
+    pub state_root: H256,
 }
 
 impl BlockContext {
-    /// Assignments for block table
-    pub fn table_assignments<F: Field>(&self, randomness: F) -> Vec<[F; 3]> {
-        [
-            vec![
-                [
-                    F::from(BlockContextFieldTag::Coinbase as u64),
-                    F::zero(),
-                    self.coinbase.to_scalar().unwrap(),
-                ],
-                [
-                    F::from(BlockContextFieldTag::Timestamp as u64),
-                    F::zero(),
-                    self.timestamp.to_scalar().unwrap(),
-                ],
-                [
-                    F::from(BlockContextFieldTag::Number as u64),
-                    F::zero(),
-                    self.number.to_scalar().unwrap(),
-                ],
-                [
-                    F::from(BlockContextFieldTag::Difficulty as u64),
-                    F::zero(),
+    /// Assignments for block table of the fields of the block, indexed by its
+    /// number
+    pub fn table_assignments<F: Field>(
+        &self,
+        challenges: &Challenges<Value<F>>,
+    ) -> Vec<[Value<F>; 3]> {
+        let number = Value::known(self.number.to_scalar().unwrap());
+        vec![
+            [
+                Value::known(F::from(BlockContextFieldTag::Coinbase as u64)),
+                number,
+                Value::known(self.coinbase.to_scalar().unwrap()),
+            ],
+            [
+                Value::known(F::from(BlockContextFieldTag::Timestamp as u64)),
+                number,
+                Value::known(self.timestamp.to_scalar().unwrap()),
+            ],
+            [
+                Value::known(F::from(BlockContextFieldTag::Number as u64)),
+                number,
+                Value::known(self.number.to_scalar().unwrap()),
+            ],
+            [
+                Value::known(F::from(BlockContextFieldTag::Difficulty as u64)),
+                number,
+                challenges.evm_word().map(|challenge| {
                     RandomLinearCombination::random_linear_combine(
                         self.difficulty.to_le_bytes(),
-                        randomness,
-                    ),
-                ],
-                [
-                    F::from(BlockContextFieldTag::GasLimit as u64),
-                    F::zero(),
-                    F::from(self.gas_limit),
-                ],
-                [
-                    F::from(BlockContextFieldTag::BaseFee as u64),
-                    F::zero(),
+                        challenge,
+                    )
+                }),
+            ],
+            [
+                Value::known(F::from(BlockContextFieldTag::GasLimit as u64)),
+                number,
+                Value::known(F::from(self.gas_limit)),
+            ],
+            [
+                Value::known(F::from(BlockContextFieldTag::BaseFee as u64)),
+                number,
+                challenges.evm_word().map(|challenge| {
                     RandomLinearCombination::random_linear_combine(
                         self.base_fee.to_le_bytes(),
-                        randomness,
-                    ),
-                ],
-                [
-                    F::from(BlockContextFieldTag::ChainId as u64),
-                    F::zero(),
-                    RandomLinearCombination::random_linear_combine(
-                        self.chain_id.to_le_bytes(),
-                        randomness,
-                    ),
-                ],
+                        challenge,
+                    )
+                }),
             ],
-            {
-                let len_history = self.history_hashes.len();
-                self.history_hashes
-                    .iter()
-                    .enumerate()
-                    .map(|(idx, hash)| {
-                        [
-                            F::from(BlockContextFieldTag::BlockHash as u64),
-                            (self.number - len_history + idx).to_scalar().unwrap(),
-                            RandomLinearCombination::random_linear_combine(
-                                hash.to_le_bytes(),
-                                randomness,
-                            ),
-                        ]
-                    })
-                    .collect()
-            },
         ]
-        .concat()
     }
 }
 
@@ -137,24 +153,140 @@ impl From<&circuit_input_builder::Block> for BlockContext {
             base_fee: block.base_fee,
             history_hashes: block.history_hashes.clone(),
             chain_id: block.chain_id,
+            hash: block.hash,
+            state_root: block.state_root,
         }
     }
 }
 
-/// Convert a block struct in bus-mapping to a witness block used in circuits
-pub fn block_convert(
-    block: &circuit_input_builder::Block,
-    code_db: &bus_mapping::state_db::CodeDB,
-) -> Block<Fr> {
+/// Block contexts of the consecutive blocks of a batch
+#[derive(Debug, Default, Clone)]
+pub struct BlockContexts {
+    /// The block contexts, indexed by block number
+    pub ctxs: BTreeMap<u64, BlockContext>,
+}
+
+impl BlockContexts {
+    /// The chain id, which is the same for all the blocks
+    pub fn chain_id(&self) -> Word {
+        self.ctxs
+            .values()
+            .next()
+            .map(|ctx| ctx.chain_id)
+            .unwrap_or_default()
+    }
+
+    /// The number of the first block, or zero without blocks
+    pub fn first_number(&self) -> u64 {
+        self.ctxs.keys().next().copied().unwrap_or_default()
+    }
+
+    /// The block contexts of the [`MAX_INNER_BLOCKS`] block slots of the
+    /// PublicInputs circuit.  The slots after the last block are empty but
+    /// for their number, which continues the ones of the batch.
+    pub fn slots(&self) -> Vec<BlockContext> {
+        let first_number = self.first_number();
+        self.ctxs
+            .values()
+            .cloned()
+            .chain((self.ctxs.len()..MAX_INNER_BLOCKS).map(|i| BlockContext {
+                number: (first_number + i as u64).into(),
+                ..Default::default()
+            }))
+            .collect()
+    }
+
+    /// The hashes of the 256 blocks before the first block, padded with zeros
+    /// at the front when the chain is shorter, then of the block slots but the
+    /// last one: the one of the block of number `first_number() - 256 + i`
+    /// is at `i`.
+    pub fn block_hashes(&self) -> Vec<Word> {
+        let slots = self.slots();
+        let history = slots[0].history_hashes.as_slice();
+        let history = &history[history.len().saturating_sub(256)..];
+        iter::repeat(Word::zero())
+            .take(256 - history.len())
+            .chain(history.iter().copied())
+            .chain(
+                slots[..slots.len() - 1]
+                    .iter()
+                    .map(|ctx| ctx.hash.to_word()),
+            )
+            .collect()
+    }
+
+    /// Assignments for block table: the fields of each block slot, indexed by
+    /// its number, then the chain id, then the [`block_hashes`]
+    /// (Self::block_hashes), indexed by the number of their block.  The layout
+    /// only depends on the number of block slots, so that the PublicInputs
+    /// circuit can copy constrain the table with the same constraints for
+    /// any batch.
+    pub fn table_assignments<F: Field>(
+        &self,
+        challenges: &Challenges<Value<F>>,
+    ) -> Vec<[Value<F>; 3]> {
+        let first_hash_number = F::from(self.first_number()) - F::from(256);
+        [
+            self.slots()
+                .iter()
+                .flat_map(|ctx| ctx.table_assignments(challenges))
+                .collect(),
+            vec![[
+                Value::known(F::from(BlockContextFieldTag::ChainId as u64)),
+                Value::known(F::zero()),
+                challenges.evm_word().map(|challenge| {
+                    RandomLinearCombination::random_linear_combine(
+                        self.chain_id().to_le_bytes(),
+                        challenge,
+                    )
+                }),
+            ]],
+            self.block_hashes()
+                .into_iter()
+                .enumerate()
+                .map(|(i, hash)| {
+                    [
+                        Value::known(F::from(BlockContextFieldTag::BlockHash as u64)),
+                        Value::known(first_hash_number + F::from(i as u64)),
+                        challenges.evm_word().map(|challenge| {
+                            RandomLinearCombination::random_linear_combine(
+                                hash.to_le_bytes(),
+                                challenge,
+                            )
+                        }),
+                    ]
+                })
+                .collect(),
+        ]
+        .concat()
+    }
+}
+
+impl From<BlockContext> for BlockContexts {
+    fn from(ctx: BlockContext) -> Self {
+        Self {
+            ctxs: BTreeMap::from([(ctx.number.as_u64(), ctx)]),
+        }
+    }
+}
+
+/// Convert a block struct in bus-mapping to a witness block used in circuits.
+///
+/// The MPT updates are mocked, with made up roots and without the trie nodes
+/// witnessing them, so the block can't be proved by the MPT circuit nor by
+/// the Super Circuit, which includes it.  Use [`block_convert_with_state`] or
+/// [`block_convert_with_proofs`] to build the real updates.
+pub fn block_convert(block: &circuit_input_builder::Block, code_db: &CodeDB) -> Block<Fr> {
+    let rws = RwMap::from(&block.container);
     Block {
-        randomness: Fr::from(0xcafeu64),
-        context: block.into(),
-        rws: RwMap::from(&block.container),
+        context: BlockContext::from(block).into(),
+        mpt_updates: MptUpdates::mock_from(&rws.table_assignments()),
+        rws,
         txs: block
             .txs()
             .iter()
             .enumerate()
-            .map(|(idx, tx)| tx_convert(tx, idx + 1))
+            .map(|(idx, tx)| tx_convert(tx, block.tx_id_offset + idx + 1))
             .collect(),
         bytecodes: block
             .txs()
@@ -174,6 +306,131 @@ pub fn block_convert(
             .collect(),
         copy_events: block.copy_events.clone(),
         sha3_inputs: block.sha3_inputs.clone(),
+        eth_txs: block
+            .txs()
+            .iter()
+            .map(geth_types::Transaction::from)
+            .collect(),
+        state_root: block.state_root,
+        ..Default::default()
+    }
+}
+
+/// Convert the consecutive blocks of a batch, handled by a bus-mapping
+/// `CircuitInputBuilder` with `CounterMode::Continuous`, to a witness block
+/// used in circuits to prove them together.  The transaction ids and the RW
+/// counter of each block continue the ones of the previous block, so the
+/// indices of the RWs of each block are shifted after the ones of the previous
+/// blocks.
+///
+/// The MPT updates of the batch are applied to `state`, which must hold the
+/// tries of the state before the first block, and the state root after them
+/// is checked against the one of the header of the last block.  The updates
+/// are made per account field and storage slot over the whole batch, so only
+/// the state roots before and after the batch are proven by the MPT circuit:
+/// the state roots after the other blocks are only public inputs.
+pub fn batch_convert(
+    blocks: &[circuit_input_builder::Block],
+    code_db: &CodeDB,
+    state: &mut MptState,
+) -> Result<Block<Fr>, Error> {
+    let mut batch = Block::<Fr> {
+        prev_state_root: state.root(),
         ..Default::default()
+    };
+    for (block, next_block) in blocks.iter().tuple_windows() {
+        if block.number + 1 != next_block.number {
+            return Err(Error::InvalidBatch("blocks are not consecutive"));
+        }
     }
+    for block in blocks {
+        if block.tx_id_offset != batch.txs.len() {
+            return Err(Error::InvalidBatch(
+                "transaction ids don't continue the ones of the previous block",
+            ));
+        }
+        let mut witness = block_convert(block, code_db);
+
+        let rw_offsets: HashMap<RwTableTag, usize> = batch
+            .rws
+            .0
+            .iter()
+            .map(|(tag, rws)| (*tag, rws.len()))
+            .collect();
+        for step in witness.txs.iter_mut().flat_map(|tx| tx.steps.iter_mut()) {
+            for (tag, idx) in step.rw_indices.iter_mut() {
+                *idx += rw_offsets.get(tag).copied().unwrap_or_default();
+            }
+        }
+        for (tag, rws) in witness.rws.0 {
+            batch.rws.0.entry(tag).or_default().extend(rws);
+        }
+
+        batch.txs.extend(witness.txs);
+        batch.bytecodes.extend(witness.bytecodes);
+        batch.context.ctxs.extend(witness.context.ctxs);
+        batch.copy_events.extend(witness.copy_events);
+        batch.sha3_inputs.extend(witness.sha3_inputs);
+        batch.eth_txs.extend(witness.eth_txs);
+        batch.state_root = witness.state_root;
+    }
+    batch.mpt_updates = MptUpdates::from_state(&batch.rws.table_assignments(), state)?;
+    if state.root() != batch.state_root {
+        return Err(Error::StateRootMismatch(batch.state_root, state.root()));
+    }
+    Ok(batch)
+}
+
+/// Convert a block struct in bus-mapping to a witness block used in circuits,
+/// with the MPT updates of the block replayed on the tries of the state before
+/// it.  `sdb` is the state before the block and `proofs` are the `eth_getProof`
+/// responses of the accounts and storage slots it accesses, which must start
+/// at `prev_state_root`, the state root of the parent block.
+pub fn block_convert_with_proofs(
+    block: &circuit_input_builder::Block,
+    code_db: &CodeDB,
+    sdb: &StateDB,
+    proofs: &[EIP1186ProofResponse],
+    prev_state_root: H256,
+) -> Result<Block<Fr>, Error> {
+    let mut state = MptState::from_proofs(sdb, proofs, prev_state_root)?;
+    block_convert_with_state(block, code_db, &mut state)
+}
+
+/// Convert a block struct in bus-mapping to a witness block used in circuits,
+/// with the MPT updates of the block applied to `state`, which must hold the
+/// tries of the state before the block.
+pub fn block_convert_with_state(
+    block: &circuit_input_builder::Block,
+    code_db: &CodeDB,
+    state: &mut MptState,
+) -> Result<Block<Fr>, Error> {
+    let mut witness = block_convert(block, code_db);
+    witness.prev_state_root = state.root();
+    witness.mpt_updates = MptUpdates::from_state(&witness.rws.table_assignments(), state)?;
+    Ok(witness)
+}
+
+/// Convert the circuit inputs of a range of consecutive blocks, generated by
+/// `BuilderClient::gen_inputs_range`, to a witness block per block.  The MPT
+/// updates of each block are applied to the tries of the state after the
+/// previous one, so the `prev_state_root` of each block is the `state_root` of
+/// the previous block, and the state root after each block is checked against
+/// the one of its header.
+pub fn block_range_convert(
+    inputs: &circuit_input_builder::BlockRangeInputs,
+) -> Result<Vec<Block<Fr>>, Error> {
+    let mut state =
+        MptState::from_proofs(&inputs.prev_state(), &inputs.proofs, inputs.prev_state_root)?;
+    inputs
+        .blocks
+        .iter()
+        .map(|block| {
+            let witness = block_convert_with_state(block, &inputs.code_db, &mut state)?;
+            if state.root() != witness.state_root {
+                return Err(Error::StateRootMismatch(witness.state_root, state.root()));
+            }
+            Ok(witness)
+        })
+        .collect()
 }
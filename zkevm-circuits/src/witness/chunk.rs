@@ -0,0 +1,315 @@
+use std::ops::Range;
+
+use eth_types::{Error, Field, ToBigEndian, H256};
+use itertools::Itertools;
+
+use bus_mapping::circuit_input_builder::CopyDataType;
+
+use super::{access_list_entries, Block, ExecStep, MptState, MptUpdates, Rw, RwMap, Transaction};
+use crate::table::{RwTableTag, TxReceiptFieldTag};
+
+/// Chunk of a block proven by one instance of the Super Circuit, when the
+/// block is too large for a single one.  A chunk is made of whole
+/// transactions of the block.
+///
+/// The EVM circuit proves the steps of the transactions of the chunk, and
+/// exposes as public inputs the state of its first step and the state of the
+/// first step of the next chunk (or of the EndBlock step for the last chunk).
+/// The State circuit proves the RW rows of these steps, whose rw counters are
+/// in `rw_counters`, and the MPT updates of their accounts and storage slots
+/// go from `prev_state_root` to `state_root`.  The only RW of a previous
+/// transaction read by a transaction is the cumulative gas used written in
+/// the receipt of the previous transaction, so the State circuit also has the
+/// one of the last transaction of the previous chunk, and exposes it as a
+/// public input with the one of the last transaction of the chunk, see
+/// [`Block::chunk_boundary_rws`].  The other circuits get the parts of the
+/// block used by the transactions of the chunk, and the tx table also has the
+/// last transaction of the previous chunk, see [`Chunk::tx_table_txs`].
+///
+/// Consecutive chunk proofs are linked by checking that the end of one is the
+/// beginning of the next: the step state, the state root and the exposed
+/// receipt row.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chunk {
+    /// The index of the chunk in the block
+    pub index: usize,
+    /// The number of chunks of the block
+    pub total: usize,
+    /// The range of the transactions of the chunk, in the transactions of
+    /// the block
+    pub txs: Range<usize>,
+    /// The range of the steps of the chunk, in the steps of all the
+    /// transactions of the block
+    pub steps: Range<usize>,
+    /// The range of the rw counters of the RWs of the steps of the chunk
+    pub rw_counters: Range<usize>,
+    /// The state root before the RWs of the chunk
+    pub prev_state_root: H256,
+    /// The state root after the RWs of the chunk
+    pub state_root: H256,
+}
+
+impl Chunk {
+    /// Whether the chunk is the first one of the block
+    pub fn is_first(&self) -> bool {
+        self.index == 0
+    }
+
+    /// Whether the chunk is the last one of the block
+    pub fn is_last(&self) -> bool {
+        self.index + 1 == self.total
+    }
+
+    /// The range of the transactions of the tx table of the chunk: its
+    /// transactions, preceded by the last one of the previous chunk, whose
+    /// block number is looked up by the EndTx step of the first transaction.
+    pub fn tx_table_txs(&self) -> Range<usize> {
+        self.txs.start.saturating_sub(1)..self.txs.end
+    }
+}
+
+/// Split a block in chunks of whole transactions with at most `max_steps`
+/// steps in total, or of a single transaction with more steps, and return a
+/// witness block for each of them.  The MPT updates of each chunk are mocked
+/// like the ones of [`block_convert`](super::block_convert), so the chunks
+/// can't be proved by the MPT circuit.  Use [`chunk_convert_with_state`] to
+/// build the real updates.
+pub fn chunk_convert<F: Field>(block: &Block<F>, max_steps: usize) -> Vec<Block<F>> {
+    chunk_convert_with_updates(block, max_steps, |rows| {
+        let updates = MptUpdates::mock_from(rows);
+        let roots = [
+            updates.iter().next().map(|update| update.old_root),
+            updates.iter().last().map(|update| update.new_root),
+        ]
+        .map(|root| H256::from(root.unwrap_or_default().to_be_bytes()));
+        Ok((updates, roots))
+    })
+    .expect("mocked MPT updates can't fail")
+}
+
+/// Split a block in chunks like [`chunk_convert`], with the MPT updates of
+/// each chunk applied to `state`, which must hold the tries of the state
+/// before the block.  The state root before each chunk is the one after the
+/// previous chunk.
+pub fn chunk_convert_with_state<F: Field>(
+    block: &Block<F>,
+    max_steps: usize,
+    state: &mut MptState,
+) -> Result<Vec<Block<F>>, Error> {
+    chunk_convert_with_updates(block, max_steps, |rows| {
+        let prev_state_root = state.root();
+        let updates = MptUpdates::from_state(rows, state)?;
+        Ok((updates, [prev_state_root, state.root()]))
+    })
+}
+
+/// Split the transactions of a block in consecutive ranges of at most
+/// `max_steps` steps, but for a transaction with more steps, which is alone
+/// in its range.  A block without transactions has a single empty range.
+fn tx_ranges(txs: &[Transaction], max_steps: usize) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut n_steps = 0;
+    for (idx, tx) in txs.iter().enumerate() {
+        if idx > start && n_steps + tx.steps.len() > max_steps {
+            ranges.push(start..idx);
+            start = idx;
+            n_steps = 0;
+        }
+        n_steps += tx.steps.len();
+    }
+    ranges.push(start..txs.len());
+    ranges
+}
+
+/// Split a block in chunks with the MPT updates built by `updates` from the
+/// sorted RW rows of each chunk, in order, which also returns the state roots
+/// before and after them.
+fn chunk_convert_with_updates<F: Field>(
+    block: &Block<F>,
+    max_steps: usize,
+    mut updates: impl FnMut(&[Rw]) -> Result<(MptUpdates, [H256; 2]), Error>,
+) -> Result<Vec<Block<F>>, Error> {
+    assert!(max_steps > 0, "chunks must have at least one step");
+    let n_rws = block.rws.0.values().map(Vec::len).sum::<usize>();
+    let first_step = |tx: usize| -> usize { block.txs[..tx].iter().map(|tx| tx.steps.len()).sum() };
+    // The RWs of a transaction start at its first step, and the RWs of the
+    // EndBlock step are in the last chunk.
+    let first_rw_counter = |tx: usize| match tx {
+        0 => 1,
+        tx if tx == block.txs.len() => n_rws + 1,
+        tx => block.txs[tx].steps[0].rw_counter,
+    };
+    let rows = block.rws.table_assignments();
+
+    let tx_ranges = tx_ranges(&block.txs, max_steps);
+    let total = tx_ranges.len();
+    tx_ranges
+        .into_iter()
+        .enumerate()
+        .map(|(index, txs)| {
+            let rw_counters = first_rw_counter(txs.start)..first_rw_counter(txs.end);
+            let chunk_rows = rows
+                .iter()
+                .filter(|row| rw_counters.contains(&row.rw_counter()))
+                .cloned()
+                .collect_vec();
+            let (mpt_updates, [prev_state_root, state_root]) = updates(&chunk_rows)?;
+            let chunk = Chunk {
+                index,
+                total,
+                steps: first_step(txs.start)..first_step(txs.end),
+                txs,
+                rw_counters,
+                prev_state_root,
+                state_root,
+            };
+
+            let copy_events = block
+                .copy_events
+                .iter()
+                .filter(|event| chunk.rw_counters.contains(&event.rw_counter_start.0))
+                .cloned()
+                .collect_vec();
+            // Each SHA3 step copies its input from the memory to an RLC
+            // accumulator.
+            let sha3_inputs = copy_events
+                .iter()
+                .filter(|event| event.dst_type == CopyDataType::RlcAcc)
+                .map(|event| event.bytes.iter().map(|(byte, _)| *byte).collect())
+                .collect();
+            let bytecodes = block.txs[chunk.txs.clone()]
+                .iter()
+                .flat_map(|tx| tx.calls.iter().map(|call| call.code_hash))
+                .unique()
+                .filter_map(|code_hash| {
+                    block
+                        .bytecodes
+                        .get(&code_hash)
+                        .map(|bytecode| (code_hash, bytecode.clone()))
+                })
+                .collect();
+            Ok(Block {
+                mpt_updates,
+                eth_txs: block.eth_txs[chunk.tx_table_txs()].to_vec(),
+                copy_events,
+                sha3_inputs,
+                bytecodes,
+                chunk: Some(chunk),
+                ..block.clone()
+            })
+        })
+        .collect()
+}
+
+impl<F> Block<F> {
+    /// Iterate over the steps of all the transactions of the block, with
+    /// their transaction.
+    pub(crate) fn steps(&self) -> impl Iterator<Item = (&Transaction, &ExecStep)> + '_ {
+        self.txs
+            .iter()
+            .flat_map(|tx| tx.steps.iter().map(move |step| (tx, step)))
+    }
+
+    /// Iterate over the steps of the chunk of the block, or of the whole block
+    /// when it's not chunked, with their transaction.
+    pub fn chunk_steps(&self) -> impl Iterator<Item = (&Transaction, &ExecStep)> + '_ {
+        let steps = self
+            .chunk
+            .as_ref()
+            .map_or(0..usize::MAX, |chunk| chunk.steps.clone());
+        self.steps().skip(steps.start).take(steps.len())
+    }
+
+    /// Return the first step of the next chunk, with its transaction, or None
+    /// for the last chunk or a block which is not chunked.
+    pub fn chunk_next_step(&self) -> Option<(&Transaction, &ExecStep)> {
+        self.chunk
+            .as_ref()
+            .filter(|chunk| !chunk.is_last())
+            .and_then(|chunk| self.steps().nth(chunk.steps.end))
+    }
+
+    /// Return the transactions of the tx table: the ones of
+    /// [`Chunk::tx_table_txs`], or all of them when the block is not chunked.
+    pub fn tx_table_txs(&self) -> &[Transaction] {
+        match &self.chunk {
+            Some(chunk) => &self.txs[chunk.tx_table_txs()],
+            None => &self.txs,
+        }
+    }
+
+    /// Return the RWs of the rw table of the State circuit: the RWs of the
+    /// chunk of the block and the receipt row imported from the previous
+    /// chunk, see [`Block::chunk_boundary_rws`], or all the RWs when the block
+    /// is not chunked.  The writes of the access list of the last transaction
+    /// of the previous chunk are also in the rw table, for the Tx circuit
+    /// which has the transaction in its tx table.
+    pub fn state_rws(&self) -> RwMap {
+        let chunk = match &self.chunk {
+            Some(chunk) => chunk,
+            None => return self.rws.clone(),
+        };
+        let [import, _] = self.chunk_boundary_rws();
+        let prev_access_list_rw_counters = if chunk.is_first() {
+            0..0
+        } else {
+            let tx = &self.txs[chunk.txs.start - 1];
+            let len = access_list_entries(&tx.access_list).len();
+            tx.access_list_rw_counter..tx.access_list_rw_counter + len
+        };
+        let mut rws = RwMap::default();
+        for rw in self
+            .rws
+            .0
+            .values()
+            .flatten()
+            .filter(|rw| {
+                chunk.rw_counters.contains(&rw.rw_counter())
+                    || prev_access_list_rw_counters.contains(&rw.rw_counter())
+            })
+            .chain(import.as_ref())
+        {
+            rws.0.entry(rw.tag()).or_default().push(*rw);
+        }
+        rws
+    }
+
+    /// Return the rows of the rw table exposed as public inputs by the State
+    /// circuit of the chunk of the block: the write of the cumulative gas used
+    /// of the last transaction of the previous chunk, which the first
+    /// transaction of the chunk reads, and the one of the last transaction of
+    /// the chunk.  The rows are None for the first and the last chunk, and for
+    /// a block which is not chunked.
+    pub fn chunk_boundary_rws(&self) -> [Option<Rw>; 2] {
+        let chunk = match &self.chunk {
+            Some(chunk) => chunk,
+            None => return [None, None],
+        };
+        let cumulative_gas_used_write = |tx: usize| {
+            let id = self.txs[tx].id;
+            self.rws
+                .0
+                .get(&RwTableTag::TxReceipt)
+                .into_iter()
+                .flatten()
+                .find(|rw| {
+                    matches!(
+                        rw,
+                        Rw::TxReceipt {
+                            is_write: true,
+                            tx_id,
+                            field_tag: TxReceiptFieldTag::CumulativeGasUsed,
+                            ..
+                        } if *tx_id == id
+                    )
+                })
+                .copied()
+        };
+        [
+            (!chunk.is_first()).then(|| chunk.txs.start - 1),
+            (!chunk.is_last()).then(|| chunk.txs.end - 1),
+        ]
+        .map(|tx| tx.and_then(cumulative_gas_used_write))
+    }
+}
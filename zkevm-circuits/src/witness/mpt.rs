@@ -1,34 +1,78 @@
 use crate::evm_circuit::{util::RandomLinearCombination, witness::Rw};
 use crate::table::{AccountFieldTag, ProofType};
-use eth_types::{Address, Field, ToLittleEndian, ToScalar, Word};
+use bus_mapping::state_db::StateDB;
+use eth_types::{
+    geth_types,
+    mpt::{self, Trie},
+    Address, EIP1186ProofResponse, Error, Field, ToBigEndian, ToLittleEndian, ToScalar, ToWord,
+    Word, H256,
+};
+use ethers_core::utils::{
+    keccak256,
+    rlp::{self, Rlp},
+};
+use halo2_proofs::circuit::Value;
 use itertools::Itertools;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
 
 /// An MPT update whose validility is proved by the MptCircuit
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct MptUpdate {
-    key: Key,
-    old_value: Word,
-    new_value: Word,
-    old_root: Word,
-    new_root: Word,
+    pub(crate) key: Key,
+    pub(crate) old_value: Word,
+    pub(crate) new_value: Word,
+    pub(crate) old_root: Word,
+    pub(crate) new_root: Word,
+    /// Trie nodes witnessing the update.  Only updates built from an
+    /// [`MptState`] have them, and only those can be proved by the MptCircuit.
+    pub(crate) proof: Option<MptProof>,
+}
+
+/// The RLP encoded trie nodes on the paths to the updated account (and storage
+/// slot) before and after an update, in the format of `eth_getProof`.
+#[derive(Debug, Clone, Default)]
+pub struct MptProof {
+    /// Path to the account in the state trie before the update
+    pub old_account_proof: Vec<Vec<u8>>,
+    /// Path to the account in the state trie after the update
+    pub new_account_proof: Vec<Vec<u8>>,
+    /// Path to the slot in the storage trie before the update
+    pub old_storage_proof: Vec<Vec<u8>>,
+    /// Path to the slot in the storage trie after the update
+    pub new_storage_proof: Vec<Vec<u8>>,
 }
 
-/// All the MPT updates in the MptCircuit, accessible by their key
-#[derive(Default, Clone)]
-pub struct MptUpdates(HashMap<Key, MptUpdate>);
+/// All the MPT updates in the MptCircuit, accessible by their key.  They are
+/// iterated in the order in which the State Circuit applies them.
+#[derive(Default, Clone, Debug)]
+pub struct MptUpdates(BTreeMap<Key, MptUpdate>);
 
 /// The field element encoding of an MPT update, which is used by the MptTable
 #[derive(Debug, Clone, Copy)]
 pub struct MptUpdateRow<F>([F; 7]);
 
 impl MptUpdates {
-    pub(crate) fn get(&self, row: &Rw) -> Option<MptUpdate> {
-        key(row).map(|key| *self.0.get(&key).expect("missing key in mpt updates"))
+    pub(crate) fn get(&self, row: &Rw) -> Option<&MptUpdate> {
+        key(row).map(|key| self.0.get(&key).expect("missing key in mpt updates"))
+    }
+
+    pub(crate) fn iter(&self) -> impl Iterator<Item = &MptUpdate> {
+        self.0.values()
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Whether all the updates have the trie nodes witnessing them, which
+    /// are needed to prove them with the MptCircuit.
+    pub(crate) fn has_proofs(&self) -> bool {
+        self.0.values().all(|update| update.proof.is_some())
     }
 
     pub(crate) fn mock_from(rows: &[Rw]) -> Self {
-        let map: HashMap<_, _> = rows
+        let map: BTreeMap<_, _> = rows
             .iter()
             .group_by(|row| key(row))
             .into_iter()
@@ -45,6 +89,7 @@ impl MptUpdates {
                         new_root: Word::from(i as u64 + 1),
                         old_value: value_prev(first),
                         new_value: value(last),
+                        proof: None,
                     },
                 )
             })
@@ -52,27 +97,61 @@ impl MptUpdates {
         MptUpdates(map)
     }
 
-    pub(crate) fn table_assignments<F: Field>(&self, randomness: F) -> Vec<MptUpdateRow<F>> {
+    /// Build the MPT updates of the Account and AccountStorage rows by
+    /// applying them to `state`, which must hold the tries of the state
+    /// before the block.  `rows` must be sorted in the State Circuit order.
+    pub fn from_state(rows: &[Rw], state: &mut MptState) -> Result<Self, Error> {
+        rows.iter()
+            .group_by(|row| key(row))
+            .into_iter()
+            .filter_map(|(key, rows)| key.map(|key| (key, rows)))
+            .map(|(key, rows)| state.apply(key, value(rows.last().unwrap())))
+            .collect()
+    }
+
+    pub(crate) fn table_assignments<F: Field>(
+        &self,
+        randomness: Value<F>,
+    ) -> Vec<MptUpdateRow<Value<F>>> {
         self.0
             .values()
-            .map(|update| {
-                let (new_root, old_root) = update.root_assignments(randomness);
-                let (new_value, old_value) = update.value_assignments(randomness);
-                MptUpdateRow([
-                    update.key.address(),
-                    update.key.storage_key(randomness),
-                    update.key.proof_type(),
-                    new_root,
-                    old_root,
-                    new_value,
-                    old_value,
-                ])
-            })
+            .map(|update| update.table_assignment(randomness))
             .collect()
     }
 }
 
+impl FromIterator<MptUpdate> for MptUpdates {
+    fn from_iter<I: IntoIterator<Item = MptUpdate>>(iter: I) -> Self {
+        Self(
+            iter.into_iter()
+                .map(|update| (update.key, update))
+                .collect(),
+        )
+    }
+}
+
 impl MptUpdate {
+    pub(crate) fn table_assignment<F: Field>(
+        &self,
+        randomness: Value<F>,
+    ) -> MptUpdateRow<Value<F>> {
+        let (new_root, old_root) = randomness
+            .map(|randomness| self.root_assignments(randomness))
+            .unzip();
+        let (new_value, old_value) = randomness
+            .map(|randomness| self.value_assignments(randomness))
+            .unzip();
+        MptUpdateRow([
+            Value::known(self.key.address()),
+            randomness.map(|randomness| self.key.storage_key(randomness)),
+            Value::known(F::from(self.key.proof_type() as u64)),
+            new_root,
+            old_root,
+            new_value,
+            old_value,
+        ])
+    }
+
     pub(crate) fn value_assignments<F: Field>(&self, word_randomness: F) -> (F, F) {
         let assign = |x: Word| match self.key {
             Key::Account {
@@ -99,51 +178,331 @@ impl MptUpdate {
     }
 }
 
-#[derive(Eq, PartialEq, Hash, Clone, Debug, Copy)]
-enum Key {
+/// The key of an MPT update.  The variants and their fields are ordered like
+/// the rows of the RwTable, so that the updates are sorted in the order in
+/// which the State Circuit applies them.
+#[derive(Eq, PartialEq, Hash, Clone, Debug, Copy, PartialOrd, Ord)]
+pub(crate) enum Key {
+    AccountStorage {
+        tx_id: usize,
+        address: Address,
+        storage_key: Word,
+    },
     Account {
         address: Address,
         field_tag: AccountFieldTag,
     },
-    AccountStorage {
-        tx_id: usize,
+    AccountDestructed {
+        address: Address,
+    },
+    AccountDoesNotExist {
         address: Address,
-        storage_key: Word,
     },
 }
 
 impl Key {
-    fn address<F: Field>(&self) -> F {
+    pub(crate) fn account_address(&self) -> Address {
         match self {
-            Self::Account { address, .. } | Self::AccountStorage { address, .. } => {
-                address.to_scalar().unwrap()
-            }
+            Self::AccountStorage { address, .. }
+            | Self::Account { address, .. }
+            | Self::AccountDestructed { address }
+            | Self::AccountDoesNotExist { address } => *address,
+        }
+    }
+    pub(crate) fn slot(&self) -> Option<Word> {
+        match self {
+            Self::AccountStorage { storage_key, .. } => Some(*storage_key),
+            _ => None,
         }
     }
-    fn proof_type<F: Field>(&self) -> F {
-        let proof_type = match self {
+    fn address<F: Field>(&self) -> F {
+        self.account_address().to_scalar().unwrap()
+    }
+    pub(crate) fn proof_type(&self) -> ProofType {
+        match self {
             Self::AccountStorage { .. } => ProofType::StorageChanged,
             Self::Account { field_tag, .. } => (*field_tag).into(),
-        };
-        F::from(proof_type as u64)
+            Self::AccountDestructed { .. } => ProofType::AccountDestructed,
+            Self::AccountDoesNotExist { .. } => ProofType::AccountDoesNotExist,
+        }
     }
     fn storage_key<F: Field>(&self, randomness: F) -> F {
         match self {
-            Self::Account { .. } => F::zero(),
             Self::AccountStorage { storage_key, .. } => {
                 RandomLinearCombination::random_linear_combine(
                     storage_key.to_le_bytes(),
                     randomness,
                 )
             }
+            _ => F::zero(),
+        }
+    }
+}
+
+/// The state trie and the storage tries of the accounts, which are updated
+/// while building the witness of the MptCircuit to compute the intermediate
+/// roots and the trie nodes on the updated paths.
+#[derive(Debug, Clone, Default)]
+pub struct MptState {
+    accounts: Trie,
+    storage: HashMap<Address, Trie>,
+}
+
+impl MptState {
+    /// Build the tries of a state made of `accounts`.
+    pub fn new(accounts: &[geth_types::Account]) -> Result<Self, Error> {
+        let mut state = Self::default();
+        for account in accounts {
+            let mut storage = Trie::new();
+            for (key, value) in &account.storage {
+                storage.insert(&mpt::storage_key(key), mpt::encode_storage_value(*value))?;
+            }
+            let leaf = AccountLeaf {
+                nonce: account.nonce,
+                balance: account.balance,
+                storage_root: storage.root_hash(),
+                code_hash: H256(keccak256(&account.code)),
+            };
+            if leaf != AccountLeaf::empty() {
+                state
+                    .accounts
+                    .insert(&mpt::account_key(&account.address), leaf.encode())?;
+            }
+            state.storage.insert(account.address, storage);
+        }
+        Ok(state)
+    }
+
+    /// Build the tries of the state before a block from the `eth_getProof`
+    /// responses of the accounts and storage slots accessed in the block, as
+    /// fetched by `BuilderClient::get_state`, checking them against the
+    /// initial `sdb`.  The account proofs must start at `prev_state_root`,
+    /// the state root of the header of the parent block, otherwise
+    /// [`Error::PrevStateRootMismatch`] is returned.
+    ///
+    /// Only the nodes on the proven paths are known, which is enough to
+    /// replay the updates of the block unless they remove a key from a branch
+    /// that is merged with a sibling outside of the proofs, in which case
+    /// [`Error::MissingTrieNode`] is returned.
+    pub fn from_proofs(
+        sdb: &StateDB,
+        proofs: &[EIP1186ProofResponse],
+        prev_state_root: H256,
+    ) -> Result<Self, Error> {
+        let mut state = Self {
+            accounts: Trie::from_root_hash(prev_state_root),
+            storage: HashMap::new(),
+        };
+        for proof in proofs {
+            let invalid = || Error::InvalidStateProof(proof.address);
+            let proof_root = proof
+                .account_proof
+                .first()
+                .map(|root| H256(keccak256(root)))
+                .ok_or_else(invalid)?;
+            if proof_root != prev_state_root {
+                return Err(Error::PrevStateRootMismatch(prev_state_root, proof_root));
+            }
+            let account_key = mpt::account_key(&proof.address);
+            state
+                .accounts
+                .load_proof(&account_key, &proof.account_proof)?;
+
+            let (_, account) = sdb.get_account(&proof.address);
+            if (account.nonce, account.balance) != (proof.nonce, proof.balance) {
+                return Err(invalid());
+            }
+            let leaf = AccountLeaf {
+                nonce: proof.nonce,
+                balance: proof.balance,
+                storage_root: proof.storage_hash,
+                code_hash: proof.code_hash,
+            };
+            match state.accounts.get(&account_key)? {
+                Some(encoded)
+                    if encoded != leaf.encode().as_slice()
+                        || account.code_hash != proof.code_hash =>
+                {
+                    return Err(invalid())
+                }
+                // The hashes of the accounts that don't exist are not
+                // consistent across clients, so only their fields are checked.
+                None if !proof.nonce.is_zero() || !proof.balance.is_zero() => return Err(invalid()),
+                _ => (),
+            }
+
+            let storage = state.storage_trie(proof.address, proof.storage_hash);
+            for storage_proof in &proof.storage_proof {
+                let storage_key = mpt::storage_key(&storage_proof.key);
+                storage.load_proof(&storage_key, &storage_proof.proof)?;
+                let value: Word = storage
+                    .get(&storage_key)?
+                    .map(|value| rlp::decode(value).map_err(|_| invalid()))
+                    .transpose()?
+                    .unwrap_or_default();
+                let (_, sdb_value) = sdb.get_storage(&proof.address, &storage_proof.key);
+                if value != storage_proof.value || value != *sdb_value {
+                    return Err(invalid());
+                }
+            }
+        }
+        Ok(state)
+    }
+
+    /// Build the tries of the state before a block like
+    /// [`MptState::from_proofs`], from a JSON fixture holding the array of the
+    /// `eth_getProof` responses.
+    pub fn from_proofs_json<R: Read>(
+        sdb: &StateDB,
+        reader: R,
+        prev_state_root: H256,
+    ) -> Result<Self, Error> {
+        let proofs: Vec<EIP1186ProofResponse> =
+            serde_json::from_reader(reader).map_err(Error::SerdeError)?;
+        Self::from_proofs(sdb, &proofs, prev_state_root)
+    }
+
+    /// Returns the root of the state trie.
+    pub fn root(&self) -> H256 {
+        self.accounts.root_hash()
+    }
+
+    /// Apply an update to the tries and return it, with the roots and the
+    /// trie nodes witnessing it.
+    pub(crate) fn apply(&mut self, key: Key, new_value: Word) -> Result<MptUpdate, Error> {
+        let address = key.account_address();
+        let account_key = mpt::account_key(&address);
+
+        let old_root = self.accounts.root_hash();
+        let old_account = self
+            .accounts
+            .get(&account_key)?
+            .map(AccountLeaf::decode)
+            .transpose()?;
+        let old_account_proof = self.accounts.proof(&account_key)?;
+        let leaf = old_account.clone().unwrap_or_else(AccountLeaf::empty);
+
+        let (old_value, new_account, old_storage_proof, new_storage_proof) = match key {
+            Key::AccountStorage { storage_key, .. } => {
+                let slot_key = mpt::storage_key(&storage_key);
+                let storage = self.storage_trie(address, leaf.storage_root);
+                let old_storage_proof = storage.proof(&slot_key)?;
+                let old_value = storage
+                    .get(&slot_key)?
+                    .map(|value| rlp::decode(value).map_err(|_| Error::InvalidTrieNode))
+                    .transpose()?
+                    .unwrap_or_default();
+                storage.insert(&slot_key, mpt::encode_storage_value(new_value))?;
+                let new_account = AccountLeaf {
+                    storage_root: storage.root_hash(),
+                    ..leaf
+                };
+                let new_storage_proof = storage.proof(&slot_key)?;
+                (
+                    old_value,
+                    Some(new_account),
+                    old_storage_proof,
+                    new_storage_proof,
+                )
+            }
+            Key::Account { field_tag, .. } => {
+                let mut new_account = leaf.clone();
+                new_account.set_field(field_tag, new_value);
+                // Accounts are only created when they become non empty.
+                let new_account = (old_account.is_some() || new_account != AccountLeaf::empty())
+                    .then_some(new_account);
+                (leaf.field(field_tag), new_account, vec![], vec![])
+            }
+            Key::AccountDestructed { .. } => {
+                self.storage.remove(&address);
+                (Word::zero(), None, vec![], vec![])
+            }
+            Key::AccountDoesNotExist { .. } => (Word::zero(), old_account, vec![], vec![]),
+        };
+        match &new_account {
+            Some(account) => self.accounts.insert(&account_key, account.encode())?,
+            None => self.accounts.remove(&account_key)?,
+        }
+
+        Ok(MptUpdate {
+            key,
+            old_value,
+            new_value,
+            old_root: old_root.to_word(),
+            new_root: self.accounts.root_hash().to_word(),
+            proof: Some(MptProof {
+                old_account_proof,
+                new_account_proof: self.accounts.proof(&account_key)?,
+                old_storage_proof,
+                new_storage_proof,
+            }),
+        })
+    }
+
+    /// Returns the storage trie of an account, which is only known by its
+    /// root until its nodes are loaded.
+    fn storage_trie(&mut self, address: Address, storage_root: H256) -> &mut Trie {
+        self.storage
+            .entry(address)
+            .or_insert_with(|| Trie::from_root_hash(storage_root))
+    }
+}
+
+/// The fields of an account, as stored in the leaves of the state trie.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct AccountLeaf {
+    nonce: Word,
+    balance: Word,
+    storage_root: H256,
+    code_hash: H256,
+}
+
+impl AccountLeaf {
+    fn empty() -> Self {
+        Self {
+            nonce: Word::zero(),
+            balance: Word::zero(),
+            storage_root: *mpt::EMPTY_TRIE_HASH,
+            code_hash: H256(keccak256([])),
+        }
+    }
+
+    fn decode(bytes: &[u8]) -> Result<Self, Error> {
+        let rlp = Rlp::new(bytes);
+        let invalid = |_| Error::InvalidTrieNode;
+        Ok(Self {
+            nonce: rlp.val_at(0).map_err(invalid)?,
+            balance: rlp.val_at(1).map_err(invalid)?,
+            storage_root: rlp.val_at(2).map_err(invalid)?,
+            code_hash: rlp.val_at(3).map_err(invalid)?,
+        })
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        mpt::encode_account(self.nonce, self.balance, self.storage_root, self.code_hash)
+    }
+
+    fn field(&self, field_tag: AccountFieldTag) -> Word {
+        match field_tag {
+            AccountFieldTag::Nonce => self.nonce,
+            AccountFieldTag::Balance => self.balance,
+            AccountFieldTag::CodeHash => self.code_hash.to_word(),
+        }
+    }
+
+    fn set_field(&mut self, field_tag: AccountFieldTag, value: Word) {
+        match field_tag {
+            AccountFieldTag::Nonce => self.nonce = value,
+            AccountFieldTag::Balance => self.balance = value,
+            AccountFieldTag::CodeHash => self.code_hash = H256(value.to_be_bytes()),
         }
     }
 }
 
-impl<F: Field> MptUpdateRow<F> {
+impl<T> MptUpdateRow<T> {
     /// The individual values of the row, in the column order used by the
     /// MptTable
-    pub fn values(&self) -> impl Iterator<Item = &F> {
+    pub fn values(&self) -> impl Iterator<Item = &T> {
         self.0.iter()
     }
 }
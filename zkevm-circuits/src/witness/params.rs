@@ -0,0 +1,150 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use eth_types::Field;
+
+use super::Block;
+
+/// Parameters fixing the shape of the circuits: each circuit is padded up to
+/// its maximum size, so that the fixed columns don't depend on the witness
+/// and the same proving and verifying keys can be used for all the blocks
+/// that fit in the parameters.  A maximum of 0 disables the padding of the
+/// corresponding circuit, which is then sized to the witness, so all the
+/// parameters must be set to generate keys, see
+/// [`CircuitParams::check_padded`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct CircuitParams {
+    /// Degree of the circuits, which have 2^degree rows
+    pub degree: u32,
+    /// Maximum number of transactions
+    pub max_txs: usize,
+    /// Maximum size of the calldata of all the transactions
+    pub max_calldata: usize,
+    /// Number of rows of the rw table and of the State circuit
+    pub max_rws: usize,
+    /// Number of rows of the Bytecode circuit
+    pub max_bytecode: usize,
+    /// Number of rows of the Copy circuit
+    pub max_copy_rows: usize,
+    /// Number of rows of the Keccak circuit
+    pub max_keccak_rows: usize,
+    /// Number of rows of the EVM circuit
+    pub max_evm_rows: usize,
+    /// Number of rows of the MPT circuit
+    pub max_mpt_rows: usize,
+}
+
+impl CircuitParams {
+    /// Check that all the parameters are set, so that the circuits are
+    /// padded to them and their keys don't depend on the proved blocks.
+    pub fn check_padded(&self) -> Result<(), ParamUnset> {
+        for (param, value) in [
+            ("degree", self.degree as usize),
+            ("max_txs", self.max_txs),
+            ("max_calldata", self.max_calldata),
+            ("max_rws", self.max_rws),
+            ("max_bytecode", self.max_bytecode),
+            ("max_copy_rows", self.max_copy_rows),
+            ("max_keccak_rows", self.max_keccak_rows),
+            ("max_evm_rows", self.max_evm_rows),
+            ("max_mpt_rows", self.max_mpt_rows),
+        ] {
+            if value == 0 {
+                return Err(ParamUnset { param });
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the sizes required by a block, as `(parameter name,
+    /// required size, maximum size)`, fit in the parameters.
+    pub(crate) fn check_sizes(
+        sizes: impl IntoIterator<Item = (&'static str, usize, usize)>,
+    ) -> Result<(), ParamsExceeded> {
+        for (param, required, max) in sizes {
+            if max != 0 && required > max {
+                return Err(ParamsExceeded {
+                    param,
+                    required,
+                    max,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the transactions, the rws and the copy events of a block,
+    /// or of its chunk, fit in the parameters.
+    pub fn check_block<F: Field>(&self, block: &Block<F>) -> Result<(), ParamsExceeded> {
+        let txs = block.tx_table_txs();
+        Self::check_sizes([
+            ("max_txs", txs.len(), self.max_txs),
+            (
+                "max_calldata",
+                txs.iter().map(|tx| tx.call_data.len()).sum(),
+                self.max_calldata,
+            ),
+            // The rws are preceded by at least one padding row
+            (
+                "max_rws",
+                block.state_rws().0.values().map(Vec::len).sum::<usize>() + 1,
+                self.max_rws,
+            ),
+            // Each copied byte takes a read and a write row, and the copy
+            // events are followed by two disabled rows
+            (
+                "max_copy_rows",
+                block
+                    .copy_events
+                    .iter()
+                    .map(|copy_event| copy_event.bytes.len() * 2)
+                    .sum::<usize>()
+                    + 2,
+                self.max_copy_rows,
+            ),
+        ])
+    }
+}
+
+/// Error returned when a block doesn't fit in the [`CircuitParams`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParamsExceeded {
+    /// Name of the exceeded parameter
+    pub param: &'static str,
+    /// Size required by the block
+    pub required: usize,
+    /// Value of the parameter
+    pub max: usize,
+}
+
+impl Display for ParamsExceeded {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "block requires {} = {}, but the circuit parameters allow {}",
+            self.param, self.required, self.max
+        )
+    }
+}
+
+impl std::error::Error for ParamsExceeded {}
+
+/// Error returned when a [`CircuitParams`] used to generate keys is 0, which
+/// would make the keys depend on the witness.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParamUnset {
+    /// Name of the unset parameter
+    pub param: &'static str,
+}
+
+impl Display for ParamUnset {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "circuit parameter {} must be set to generate keys for all the blocks that fit in \
+             the parameters",
+            self.param
+        )
+    }
+}
+
+impl std::error::Error for ParamUnset {}
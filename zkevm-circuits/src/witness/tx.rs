@@ -1,7 +1,10 @@
 use bus_mapping::circuit_input_builder;
-use eth_types::{Address, Field, ToLittleEndian, ToScalar, ToWord, Word};
+use eth_types::{AccessList, Address, Field, ToLittleEndian, ToScalar, ToWord, Word};
+use halo2_proofs::circuit::Value;
 
-use crate::{evm_circuit::util::RandomLinearCombination, table::TxContextFieldTag};
+use crate::{
+    evm_circuit::util::RandomLinearCombination, table::TxContextFieldTag, util::Challenges,
+};
 
 use super::{step::step_convert, Call, ExecStep};
 
@@ -10,12 +13,18 @@ use super::{step::step_convert, Call, ExecStep};
 pub struct Transaction {
     /// The transaction identifier in the block
     pub id: usize,
+    /// The number of the block including the transaction
+    pub block_number: u64,
     /// The sender account nonce of the transaction
     pub nonce: u64,
     /// The gas limit of the transaction
     pub gas: u64,
-    /// The gas price
+    /// The effective gas price
     pub gas_price: Word,
+    /// The gas fee cap
+    pub gas_fee_cap: Word,
+    /// The gas tip cap
+    pub gas_tip_cap: Word,
     /// The caller address
     pub caller_address: Address,
     /// The callee address
@@ -30,6 +39,14 @@ pub struct Transaction {
     pub call_data_length: usize,
     /// The gas cost for transaction call data
     pub call_data_gas_cost: u64,
+    /// The number of addresses in the access list
+    pub access_list_addresses_len: u64,
+    /// The number of storage keys in the access list
+    pub access_list_storage_keys_len: u64,
+    /// The access list
+    pub access_list: AccessList,
+    /// The RW counter of the first write of the access list in BeginTx
+    pub access_list_rw_counter: usize,
     /// The calls made in the transaction
     pub calls: Vec<Call>,
     /// The steps executioned in the transaction
@@ -38,68 +55,121 @@ pub struct Transaction {
 
 impl Transaction {
     /// Assignments for tx table
-    pub fn table_assignments<F: Field>(&self, randomness: F) -> Vec<[F; 4]> {
+    pub fn table_assignments<F: Field>(
+        &self,
+        challenges: &Challenges<Value<F>>,
+    ) -> Vec<[Value<F>; 4]> {
         [
             vec![
                 [
-                    F::from(self.id as u64),
-                    F::from(TxContextFieldTag::Nonce as u64),
-                    F::zero(),
-                    F::from(self.nonce),
+                    Value::known(F::from(self.id as u64)),
+                    Value::known(F::from(TxContextFieldTag::Nonce as u64)),
+                    Value::known(F::zero()),
+                    Value::known(F::from(self.nonce)),
                 ],
                 [
-                    F::from(self.id as u64),
-                    F::from(TxContextFieldTag::Gas as u64),
-                    F::zero(),
-                    F::from(self.gas),
+                    Value::known(F::from(self.id as u64)),
+                    Value::known(F::from(TxContextFieldTag::Gas as u64)),
+                    Value::known(F::zero()),
+                    Value::known(F::from(self.gas)),
                 ],
                 [
-                    F::from(self.id as u64),
-                    F::from(TxContextFieldTag::GasPrice as u64),
-                    F::zero(),
-                    RandomLinearCombination::random_linear_combine(
-                        self.gas_price.to_le_bytes(),
-                        randomness,
-                    ),
+                    Value::known(F::from(self.id as u64)),
+                    Value::known(F::from(TxContextFieldTag::GasPrice as u64)),
+                    Value::known(F::zero()),
+                    challenges.evm_word().map(|challenge| {
+                        RandomLinearCombination::random_linear_combine(
+                            self.gas_price.to_le_bytes(),
+                            challenge,
+                        )
+                    }),
                 ],
                 [
-                    F::from(self.id as u64),
-                    F::from(TxContextFieldTag::CallerAddress as u64),
-                    F::zero(),
-                    self.caller_address.to_scalar().unwrap(),
+                    Value::known(F::from(self.id as u64)),
+                    Value::known(F::from(TxContextFieldTag::GasFeeCap as u64)),
+                    Value::known(F::zero()),
+                    challenges.evm_word().map(|challenge| {
+                        RandomLinearCombination::random_linear_combine(
+                            self.gas_fee_cap.to_le_bytes(),
+                            challenge,
+                        )
+                    }),
                 ],
                 [
-                    F::from(self.id as u64),
-                    F::from(TxContextFieldTag::CalleeAddress as u64),
-                    F::zero(),
-                    self.callee_address.to_scalar().unwrap(),
+                    Value::known(F::from(self.id as u64)),
+                    Value::known(F::from(TxContextFieldTag::GasTipCap as u64)),
+                    Value::known(F::zero()),
+                    challenges.evm_word().map(|challenge| {
+                        RandomLinearCombination::random_linear_combine(
+                            self.gas_tip_cap.to_le_bytes(),
+                            challenge,
+                        )
+                    }),
                 ],
                 [
-                    F::from(self.id as u64),
-                    F::from(TxContextFieldTag::IsCreate as u64),
-                    F::zero(),
-                    F::from(self.is_create as u64),
+                    Value::known(F::from(self.id as u64)),
+                    Value::known(F::from(TxContextFieldTag::CallerAddress as u64)),
+                    Value::known(F::zero()),
+                    Value::known(self.caller_address.to_scalar().unwrap()),
                 ],
                 [
-                    F::from(self.id as u64),
-                    F::from(TxContextFieldTag::Value as u64),
-                    F::zero(),
-                    RandomLinearCombination::random_linear_combine(
-                        self.value.to_le_bytes(),
-                        randomness,
-                    ),
+                    Value::known(F::from(self.id as u64)),
+                    Value::known(F::from(TxContextFieldTag::CalleeAddress as u64)),
+                    Value::known(F::zero()),
+                    Value::known(self.callee_address.to_scalar().unwrap()),
                 ],
                 [
-                    F::from(self.id as u64),
-                    F::from(TxContextFieldTag::CallDataLength as u64),
-                    F::zero(),
-                    F::from(self.call_data_length as u64),
+                    Value::known(F::from(self.id as u64)),
+                    Value::known(F::from(TxContextFieldTag::IsCreate as u64)),
+                    Value::known(F::zero()),
+                    Value::known(F::from(self.is_create as u64)),
                 ],
                 [
-                    F::from(self.id as u64),
-                    F::from(TxContextFieldTag::CallDataGasCost as u64),
-                    F::zero(),
-                    F::from(self.call_data_gas_cost),
+                    Value::known(F::from(self.id as u64)),
+                    Value::known(F::from(TxContextFieldTag::Value as u64)),
+                    Value::known(F::zero()),
+                    challenges.evm_word().map(|challenge| {
+                        RandomLinearCombination::random_linear_combine(
+                            self.value.to_le_bytes(),
+                            challenge,
+                        )
+                    }),
+                ],
+                [
+                    Value::known(F::from(self.id as u64)),
+                    Value::known(F::from(TxContextFieldTag::CallDataLength as u64)),
+                    Value::known(F::zero()),
+                    Value::known(F::from(self.call_data_length as u64)),
+                ],
+                [
+                    Value::known(F::from(self.id as u64)),
+                    Value::known(F::from(TxContextFieldTag::CallDataGasCost as u64)),
+                    Value::known(F::zero()),
+                    Value::known(F::from(self.call_data_gas_cost)),
+                ],
+                [
+                    Value::known(F::from(self.id as u64)),
+                    Value::known(F::from(TxContextFieldTag::AccessListAddressesLen as u64)),
+                    Value::known(F::zero()),
+                    Value::known(F::from(self.access_list_addresses_len)),
+                ],
+                [
+                    Value::known(F::from(self.id as u64)),
+                    Value::known(F::from(TxContextFieldTag::AccessListStorageKeysLen as u64)),
+                    Value::known(F::zero()),
+                    Value::known(F::from(self.access_list_storage_keys_len)),
+                ],
+                [
+                    Value::known(F::from(self.id as u64)),
+                    Value::known(F::from(TxContextFieldTag::BlockNumber as u64)),
+                    Value::known(F::zero()),
+                    Value::known(F::from(self.block_number)),
+                ],
+                [
+                    Value::known(F::from(self.id as u64)),
+                    Value::known(F::from(TxContextFieldTag::AccessListRwCounter as u64)),
+                    Value::known(F::zero()),
+                    Value::known(F::from(self.access_list_rw_counter as u64)),
                 ],
             ],
             self.call_data
@@ -107,10 +177,22 @@ impl Transaction {
                 .enumerate()
                 .map(|(idx, byte)| {
                     [
-                        F::from(self.id as u64),
-                        F::from(TxContextFieldTag::CallData as u64),
-                        F::from(idx as u64),
-                        F::from(*byte as u64),
+                        Value::known(F::from(self.id as u64)),
+                        Value::known(F::from(TxContextFieldTag::CallData as u64)),
+                        Value::known(F::from(idx as u64)),
+                        Value::known(F::from(*byte as u64)),
+                    ]
+                })
+                .collect(),
+            access_list_entries(&self.access_list)
+                .into_iter()
+                .enumerate()
+                .map(|(idx, entry)| {
+                    [
+                        Value::known(F::from(self.id as u64)),
+                        Value::known(F::from(entry.tag() as u64)),
+                        Value::known(F::from(idx as u64)),
+                        entry.value(challenges),
                     ]
                 })
                 .collect(),
@@ -119,12 +201,64 @@ impl Transaction {
     }
 }
 
+/// Entry of an access list, written into the access list of the transaction
+/// by BeginTx.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AccessListEntry {
+    /// The address of the item of the entry
+    pub address: Address,
+    /// The storage key of the entry, if it's not the address of its item
+    pub storage_key: Option<Word>,
+}
+
+impl AccessListEntry {
+    /// Tag of the entry in the tx table
+    pub fn tag(&self) -> TxContextFieldTag {
+        if self.storage_key.is_some() {
+            TxContextFieldTag::AccessListStorageKey
+        } else {
+            TxContextFieldTag::AccessListAddress
+        }
+    }
+
+    /// Value of the entry in the tx table: the address, or the RLC of the
+    /// storage key.
+    pub fn value<F: Field>(&self, challenges: &Challenges<Value<F>>) -> Value<F> {
+        match self.storage_key {
+            Some(storage_key) => challenges.evm_word().map(|challenge| {
+                RandomLinearCombination::random_linear_combine(storage_key.to_le_bytes(), challenge)
+            }),
+            None => Value::known(self.address.to_scalar().unwrap()),
+        }
+    }
+}
+
+/// Entries of an access list in the order of their writes in BeginTx: the
+/// address of each item followed by its storage keys.
+pub fn access_list_entries(access_list: &AccessList) -> Vec<AccessListEntry> {
+    access_list
+        .0
+        .iter()
+        .flat_map(|item| {
+            std::iter::once(None)
+                .chain(item.storage_keys.iter().map(|key| Some(key.to_word())))
+                .map(move |storage_key| AccessListEntry {
+                    address: item.address,
+                    storage_key,
+                })
+        })
+        .collect()
+}
+
 pub(super) fn tx_convert(tx: &circuit_input_builder::Transaction, id: usize) -> Transaction {
     Transaction {
         id,
+        block_number: tx.block_number,
         nonce: tx.nonce,
         gas: tx.gas,
         gas_price: tx.gas_price,
+        gas_fee_cap: tx.gas_fee_cap,
+        gas_tip_cap: tx.gas_tip_cap,
         caller_address: tx.from,
         callee_address: tx.to,
         is_create: tx.is_create(),
@@ -135,6 +269,23 @@ pub(super) fn tx_convert(tx: &circuit_input_builder::Transaction, id: usize) ->
             .input
             .iter()
             .fold(0, |acc, byte| acc + if *byte == 0 { 4 } else { 16 }),
+        access_list_addresses_len: tx.access_list.0.len() as u64,
+        access_list_storage_keys_len: tx
+            .access_list
+            .0
+            .iter()
+            .map(|item| item.storage_keys.len() as u64)
+            .sum(),
+        access_list: tx.access_list.clone(),
+        // The access list is written at the end of the BeginTx step.
+        access_list_rw_counter: tx
+            .steps()
+            .first()
+            .map(|step| {
+                usize::from(step.rwc) + step.bus_mapping_instance.len()
+                    - access_list_entries(&tx.access_list).len()
+            })
+            .unwrap_or_default(),
         calls: tx
             .calls()
             .iter()